@@ -0,0 +1,128 @@
+// benches/hot_path.rs
+//
+// Per-event cost of the terminal-time check, and the event loop of `World::run` it sits in. The `terminal_check`
+// group compares converting every step to `f64` against comparing with a precomputed terminal step, the way the
+// engines did before and after precomputing it. The `far_future` group runs worlds whose events are almost all
+// scheduled past the wheel horizon, where the cost is in the overflow queue behind the wheels. Save a baseline on the
+// older tree with
+// `cargo bench --bench hot_path -- --save-baseline before` and compare against it with `--baseline before`.
+
+use aika::{
+    agents::{Agent, WorldContext},
+    objects::{Action, Event, Msg},
+    st::World,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+const TERMINAL: f64 = 1_000_000.0;
+const TIMESTEP: f64 = 0.5;
+
+// Times out every tick, so every tick holds one event per agent
+struct Dense;
+
+impl Agent<8, Msg<()>> for Dense {
+    fn step(&mut self, context: &mut WorldContext<8, Msg<()>>, id: usize) -> Event {
+        Event::new(context.time, context.time, id, Action::Timeout(1))
+    }
+}
+
+fn bench_terminal_check(c: &mut Criterion) {
+    let steps = (0..4096u64).map(|i| i * 977).collect::<Vec<_>>();
+    let past_step = (TERMINAL / TIMESTEP) as u64 + 1;
+    let mut group = c.benchmark_group("terminal_check");
+    group.throughput(Throughput::Elements(steps.len() as u64));
+    group.bench_function("f64_per_event", |b| {
+        b.iter(|| {
+            let (timestep, terminal) = black_box((TIMESTEP, TERMINAL));
+            steps
+                .iter()
+                .filter(|&&step| step as f64 * timestep > terminal)
+                .count()
+        })
+    });
+    group.bench_function("precomputed_step", |b| {
+        b.iter(|| {
+            let past_step = black_box(past_step);
+            steps.iter().filter(|&&step| step >= past_step).count()
+        })
+    });
+    group.finish();
+}
+
+fn bench_dense_ticks(c: &mut Criterion) {
+    const TICKS: u64 = 1000;
+    let mut group = c.benchmark_group("dense_ticks");
+    group.sample_size(20);
+    for agents in [10, 1000] {
+        group.throughput(Throughput::Elements(agents as u64 * TICKS));
+        group.bench_with_input(BenchmarkId::new("agents", agents), &agents, |b, &agents| {
+            b.iter_with_setup(
+                || {
+                    let mut world = World::<8, 128, 1, ()>::init(TICKS as f64, 1.0, 0).unwrap();
+                    for _ in 0..agents {
+                        world.spawn_agent(Box::new(Dense));
+                    }
+                    world.init_support_layers(None).unwrap();
+                    world.schedule_all_agents(1).unwrap();
+                    world
+                },
+                |mut world| {
+                    world.run().unwrap();
+                    black_box(world.now())
+                },
+            );
+        });
+    }
+    group.finish();
+}
+
+// Waits for a trigger, so every step comes from the schedule set up front
+struct Idle;
+
+impl Agent<8, Msg<()>> for Idle {
+    fn step(&mut self, context: &mut WorldContext<8, Msg<()>>, id: usize) -> Event {
+        Event::new(context.time, context.time, id, Action::Wait)
+    }
+}
+
+fn bench_far_future(c: &mut Criterion) {
+    const TICKS: u64 = 100_000;
+    let mut group = c.benchmark_group("far_future");
+    group.sample_size(10);
+    for events in [10_000u64, 100_000] {
+        // spread over the run in a scattered order, nearly all of it past the 272 steps the wheels span
+        let schedule = (0..events)
+            .map(|i| (0, 1 + (i * 7919) % (TICKS - 1)))
+            .collect::<Vec<_>>();
+        group.throughput(Throughput::Elements(events));
+        group.bench_with_input(
+            BenchmarkId::new("events", events),
+            &schedule,
+            |b, schedule| {
+                b.iter_with_setup(
+                    || {
+                        let mut world = World::<8, 16, 2, ()>::init(TICKS as f64, 1.0, 0).unwrap();
+                        world.spawn_agent(Box::new(Idle));
+                        world.init_support_layers(None).unwrap();
+                        world
+                    },
+                    |mut world| {
+                        world.schedule_many(schedule).unwrap();
+                        world.run().unwrap();
+                        black_box(world.now())
+                    },
+                );
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_terminal_check,
+    bench_dense_ticks,
+    bench_far_future
+);
+criterion_main!(benches);
@@ -0,0 +1,91 @@
+//! Checkpoints and GVT.
+//! A `SimHook` on every planet and on the `Galaxy` watches blocks being submitted at each checkpoint and GVT
+//! advancing behind them.
+use std::sync::{Arc, Mutex};
+
+use aika::{
+    hooks::SimHook,
+    mt::hybrid::{config::HybridConfig, HybridEngine},
+    prelude::*,
+    testing::RunOutcome,
+};
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+struct Beat {
+    count: u32,
+}
+
+unsafe impl Pod for Beat {}
+unsafe impl Zeroable for Beat {}
+
+struct Metronome;
+
+impl ThreadedAgent<16, Beat> for Metronome {
+    fn step(&mut self, context: &mut PlanetContext<16, Beat>, agent_id: usize) -> Event {
+        Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+    }
+
+    fn read_message(
+        &mut self,
+        _context: &mut PlanetContext<16, Beat>,
+        _msg: Msg<Beat>,
+        _agent_id: usize,
+    ) {
+    }
+}
+
+#[derive(Clone, Default)]
+struct Blocks {
+    submitted: Arc<Mutex<Vec<(usize, u64)>>>,
+    gvt: Arc<Mutex<Vec<u64>>>,
+}
+
+impl SimHook for Blocks {
+    fn on_gvt_advance(&mut self, _from: u64, to: u64) {
+        self.gvt.lock().unwrap().push(to);
+    }
+
+    fn on_block_submit(&mut self, world_id: usize, checkpoint: u64) {
+        self.submitted.lock().unwrap().push((world_id, checkpoint));
+    }
+}
+
+fn run() -> (RunOutcome, Blocks) {
+    let config = HybridConfig::new(2, 16)
+        .with_time_bounds(30.0, 1.0)
+        .with_optimistic_sync(2, 10)
+        .with_uniform_worlds(16, 1, 16);
+    let mut engine = HybridEngine::<16, 128, 1, Beat>::create(config).unwrap();
+    let blocks = Blocks::default();
+    for planet in 0..2 {
+        engine.spawn_agent(planet, Box::new(Metronome)).unwrap();
+        engine.planets[planet].set_hook(Box::new(blocks.clone()));
+    }
+    engine.galaxy.set_hook(Box::new(blocks.clone()));
+    engine.schedule_all_agents(1).unwrap();
+    let engine = engine.run().unwrap();
+    (RunOutcome::of_hybrid(&engine), blocks)
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    let (_, blocks) = run();
+    println!("blocks: {:?}", blocks.submitted.lock().unwrap());
+    println!("gvt: {:?}", blocks.gvt.lock().unwrap());
+}
+
+#[test]
+fn cookbook_checkpoints() {
+    let (outcome, blocks) = run();
+    outcome.assert_reached(30);
+    let mut submitted = blocks.submitted.lock().unwrap().clone();
+    submitted.sort();
+    assert_eq!(
+        submitted,
+        vec![(0, 10), (0, 20), (0, 30), (1, 10), (1, 20), (1, 30)]
+    );
+    let gvt = blocks.gvt.lock().unwrap().clone();
+    assert!(gvt.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(gvt.last(), Some(&30));
+}
@@ -0,0 +1,104 @@
+//! Steering a running simulation.
+//! `ControlAction`s skip the interplanetary mail queue: here a thermostat's setpoint is changed and an extra
+//! reading is scheduled from outside the model, and every applied action is logged for replay.
+use std::sync::{Arc, Mutex};
+
+use aika::{
+    mt::hybrid::{config::HybridConfig, control::ControlAction, HybridEngine},
+    prelude::*,
+    testing::RunOutcome,
+};
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+struct Reading {
+    celsius: f64,
+}
+
+unsafe impl Pod for Reading {}
+unsafe impl Zeroable for Reading {}
+
+const SETPOINT: usize = 0;
+
+// Records its setpoint every time it is stepped
+struct Thermostat {
+    setpoint: f64,
+    log: Arc<Mutex<Vec<(u64, f64)>>>,
+}
+
+impl ThreadedAgent<16, Reading> for Thermostat {
+    fn step(&mut self, context: &mut PlanetContext<16, Reading>, agent_id: usize) -> Event {
+        let time = context.time;
+        self.log.lock().unwrap().push((time, self.setpoint));
+        Event::new(time, time, agent_id, Action::Wait)
+    }
+
+    fn read_message(
+        &mut self,
+        _context: &mut PlanetContext<16, Reading>,
+        _msg: Msg<Reading>,
+        _agent_id: usize,
+    ) {
+    }
+
+    fn on_control(
+        &mut self,
+        _context: &mut PlanetContext<16, Reading>,
+        action: ControlAction,
+        _agent_id: usize,
+    ) {
+        if let ControlAction::Parameter {
+            key: SETPOINT,
+            value,
+            ..
+        } = action
+        {
+            self.setpoint = value;
+        }
+    }
+}
+
+fn run() -> (RunOutcome, Vec<(u64, f64)>, usize) {
+    let config = HybridConfig::new(1, 16)
+        .with_time_bounds(20.0, 1.0)
+        .with_optimistic_sync(5, 10)
+        .with_uniform_worlds(16, 1, 16);
+    let mut engine = HybridEngine::<16, 128, 1, Reading>::create(config).unwrap();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let thermostat = Thermostat {
+        setpoint: 18.0,
+        log: Arc::clone(&log),
+    };
+    engine.spawn_agent(0, Box::new(thermostat)).unwrap();
+
+    let control = engine.control_handle();
+    let update = ControlAction::Parameter {
+        agent: Some(0),
+        key: SETPOINT,
+        value: 21.5,
+    };
+    control.send(0, update).unwrap();
+    control
+        .send(0, ControlAction::Intervene { agent: 0, time: 12 })
+        .unwrap();
+    let engine = engine.run().unwrap();
+
+    let applied = engine.planets[0].control_log().len();
+    let log = log.lock().unwrap().clone();
+    (RunOutcome::of_hybrid(&engine), log, applied)
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    let (_, log, applied) = run();
+    println!("{applied} control actions applied, thermostat readings: {log:?}");
+}
+
+#[test]
+fn cookbook_control_plane() {
+    let (outcome, log, applied) = run();
+    outcome.assert_reached(20);
+    assert_eq!(applied, 2);
+    // the update lands before the intervention wakes the thermostat
+    assert_eq!(log, vec![(12, 21.5)]);
+}
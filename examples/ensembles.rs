@@ -0,0 +1,106 @@
+//! Ensembles of replications.
+//! A `Sweep` runs the same Poisson arrival model once per seed for each arrival rate, spread over threads, and
+//! gathers one row per replication. The per-replication rates, with the warm-up deleted, give a confidence interval
+//! for each rate, and the table comes out the same however many threads ran it.
+use aika::{
+    agents::generators::{Arrivals, Emission, Generator},
+    analysis::estimates::Estimate,
+    experiments::{Metrics, Point, Sweep},
+    export::{Table, Value},
+    prelude::*,
+    st::World,
+};
+
+const TERMINAL: f64 = 60.0;
+const WARMUP: u64 = 10;
+
+// Counts the letters it polls every tick, from the end of the warm-up on
+#[derive(Default)]
+struct Counter {
+    arrivals: u64,
+}
+
+impl Agent<8, Msg<u8>> for Counter {
+    fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, agent_id: usize) -> Event {
+        if let Some(mailbox) = &mut context.agent_states[agent_id].mailbox {
+            while let Some(letters) = mailbox.poll() {
+                if context.time >= WARMUP {
+                    self.arrivals += letters.len() as u64;
+                }
+            }
+        }
+        Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+    }
+}
+
+fn replicate(point: &Point) -> Result<Metrics, AikaError> {
+    let rate = point.float("rate")?;
+    let mut world = World::<8, 128, 1, u8>::init(TERMINAL, 1.0, 0)?;
+    let generator = Generator::new(
+        Arrivals::Poisson { rate },
+        Emission::Mail { to: 1, data: 1 },
+    )?
+    .with_seed(point.seed);
+    world.spawn_agent(Box::new(generator));
+    world.spawn_agent(Box::new(Counter::default()));
+    world.init_support_layers(None)?;
+    world.schedule(1, 0)?;
+    world.schedule(1, 1)?;
+    world.run()?;
+    let arrivals = world.agents_of::<Counter>().next().unwrap().1.arrivals;
+    let observed = arrivals as f64 / (TERMINAL - WARMUP as f64);
+    Ok(Metrics::new().with("observed", Value::Float(observed)))
+}
+
+fn sweep() -> Sweep {
+    Sweep::new()
+        .axis("rate", vec![Value::Float(1.0), Value::Float(4.0)])
+        .with_seeds(0..12)
+}
+
+/// The interval over the replications of each rate in `table`.
+fn estimates(table: &Table) -> Vec<(f64, Estimate)> {
+    [1.0, 4.0]
+        .into_iter()
+        .map(|rate| {
+            let observed = table
+                .rows()
+                .iter()
+                .filter(|row| row[0] == Value::Float(rate))
+                .map(|row| match row[2] {
+                    Value::Float(observed) => observed,
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>();
+            (rate, Estimate::from_samples(&observed, 0.95).unwrap())
+        })
+        .collect()
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    let table = sweep().parallel(4).run(replicate).unwrap();
+    for (rate, estimate) in estimates(&table) {
+        let (low, high) = estimate.interval();
+        println!(
+            "rate {rate}: observed {:.3} in [{low:.3}, {high:.3}]",
+            estimate.mean
+        );
+    }
+}
+
+#[test]
+fn cookbook_ensembles() {
+    let table = sweep().parallel(4).run(replicate).unwrap();
+    assert_eq!(table.len(), 24);
+    assert_eq!(
+        table.rows(),
+        sweep().run(replicate).unwrap().rows(),
+        "the same table on one thread"
+    );
+    for (rate, estimate) in estimates(&table) {
+        assert_eq!(estimate.samples, 12);
+        assert!(estimate.contains(rate), "{rate} outside {estimate:?}");
+        assert!(estimate.relative_precision() < 0.2);
+    }
+}
@@ -0,0 +1,102 @@
+//! Hybrid messaging with rollbacks.
+//! A slow sender mails a fast receiver on another planet. The receiver runs ahead optimistically, gets rolled
+//! back by each straggler, and still reads every message exactly at its receive time.
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+    thread::sleep,
+    time::Duration,
+};
+
+use aika::{
+    mt::hybrid::{config::HybridConfig, HybridEngine},
+    prelude::*,
+    testing::{run_hybrid_checked, RunOutcome},
+};
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+struct Parcel {
+    weight: u32,
+}
+
+unsafe impl Pod for Parcel {}
+unsafe impl Zeroable for Parcel {}
+
+// Takes a while per step, then mails agent 0 of planet 1, arriving 2 ticks later
+struct Tortoise;
+
+impl ThreadedAgent<16, Parcel> for Tortoise {
+    fn step(&mut self, context: &mut PlanetContext<16, Parcel>, agent_id: usize) -> Event {
+        sleep(Duration::from_millis(2));
+        let time = context.time;
+        let msg = Msg::new(Parcel { weight: 1 }, time, time + 2, agent_id, Some(0));
+        context.send_mail(msg, 1).unwrap();
+        Event::new(time, time, agent_id, Action::Timeout(5))
+    }
+
+    fn read_message(
+        &mut self,
+        _context: &mut PlanetContext<16, Parcel>,
+        _msg: Msg<Parcel>,
+        _agent_id: usize,
+    ) {
+    }
+}
+
+// Never has anything to do, so its planet races ahead to the throttle horizon
+struct Hare {
+    received: Arc<Mutex<BTreeSet<u64>>>,
+}
+
+impl ThreadedAgent<16, Parcel> for Hare {
+    fn step(&mut self, context: &mut PlanetContext<16, Parcel>, agent_id: usize) -> Event {
+        Event::new(context.time, context.time, agent_id, Action::Wait)
+    }
+
+    fn read_message(
+        &mut self,
+        _context: &mut PlanetContext<16, Parcel>,
+        msg: Msg<Parcel>,
+        _agent_id: usize,
+    ) {
+        self.received.lock().unwrap().insert(msg.recv);
+    }
+}
+
+fn run() -> (RunOutcome, BTreeSet<u64>) {
+    let config = HybridConfig::new(2, 64)
+        .with_time_bounds(40.0, 1.0)
+        .with_optimistic_sync(30, 100)
+        .with_uniform_worlds(16, 1, 16);
+    let mut engine = HybridEngine::<16, 128, 1, Parcel>::create(config).unwrap();
+    let received = Arc::new(Mutex::new(BTreeSet::new()));
+    engine.spawn_agent(0, Box::new(Tortoise)).unwrap();
+    let hare = Hare {
+        received: Arc::clone(&received),
+    };
+    engine.spawn_agent(1, Box::new(hare)).unwrap();
+    engine.schedule(0, 0, 5).unwrap();
+    let (engine, _) = run_hybrid_checked(engine).unwrap();
+    let received = received.lock().unwrap().clone();
+    (RunOutcome::of_hybrid(&engine), received)
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    let (outcome, received) = run();
+    println!(
+        "rollbacks: {}, mail read at {received:?}",
+        outcome.rollbacks
+    );
+}
+
+#[test]
+fn cookbook_hybrid_rollbacks() {
+    let (outcome, received) = run();
+    outcome.assert_reached(40);
+    outcome.assert_conserved();
+    assert!(outcome.rollbacks >= 1);
+    // sent at 5, 10, ..., 35
+    assert_eq!(received, (1..=7).map(|i| i * 5 + 2).collect());
+}
@@ -0,0 +1,67 @@
+//! Real-time pacing.
+//! A fixed-timestep frame loop drives a `World` at ten simulated seconds per wall-clock second. Every frame hands
+//! `advance_by()` the simulated time the frame covers plus what the last frame left over, and sleeps out the rest
+//! of the frame, so the run never gets ahead of the wall clock.
+use std::time::{Duration, Instant};
+
+use aika::{prelude::*, st::World};
+
+/// simulated seconds per wall-clock second
+const SPEED: f64 = 10.0;
+const FRAME: Duration = Duration::from_millis(20);
+
+// Blinks once every tick
+struct Lamp {
+    blinks: u64,
+}
+
+impl Agent<8, Msg<u8>> for Lamp {
+    fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, agent_id: usize) -> Event {
+        self.blinks += 1;
+        Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+    }
+}
+
+/// Ticks run in every frame, and the wall time the whole run took.
+fn run() -> (Vec<u64>, Duration, u64) {
+    let mut world = World::<8, 16, 1, u8>::init(3.0, 0.25, 0).unwrap();
+    world.spawn_agent(Box::new(Lamp { blinks: 0 }));
+    world.init_support_layers(None).unwrap();
+    world.schedule(0, 0).unwrap();
+
+    let start = Instant::now();
+    let mut frames = Vec::new();
+    let mut carry = 0.0;
+    loop {
+        let deadline = start + FRAME * (frames.len() as u32 + 1);
+        carry += FRAME.as_secs_f64() * SPEED;
+        let quantum = world.advance_by(carry).unwrap();
+        carry -= quantum.elapsed;
+        frames.push(quantum.ticks);
+        if quantum.finished {
+            break;
+        }
+        std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+    }
+    let blinks = world.agents_of::<Lamp>().next().unwrap().1.blinks;
+    (frames, start.elapsed(), blinks)
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    let (frames, wall, blinks) = run();
+    println!("ticks per frame: {frames:?}");
+    println!("{blinks} blinks in {wall:?}");
+}
+
+#[test]
+fn cookbook_real_time_pacing() {
+    let (frames, wall, blinks) = run();
+    // a frame covers 0.2s of a 0.25s timestep, so every fifth frame runs no tick and carries its time over
+    assert_eq!(frames[..5], [0, 1, 1, 1, 1]);
+    assert!(frames.iter().all(|ticks| *ticks <= 1));
+    assert_eq!(frames.iter().sum::<u64>(), blinks);
+    // the last tick can't run before the wall clock reaches its simulated time
+    let simulated = blinks as f64 * 0.25;
+    assert!(wall.as_secs_f64() >= (simulated / SPEED) - FRAME.as_secs_f64());
+}
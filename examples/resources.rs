@@ -0,0 +1,127 @@
+//! Shared resources.
+//! Four patients on planet 0 queue for two doctors, a `Resource` of capacity 2, once a slower referrer on planet 1
+//! sends them in. Referrals arriving late roll planet 0 back, and the doctors' queue and statistics roll back with it.
+use aika::{
+    agents::resources::{QueueDiscipline, ResourceStats},
+    mt::hybrid::{config::HybridConfig, HybridEngine},
+    prelude::*,
+    testing::RunOutcome,
+};
+
+const DOCTORS: usize = 0;
+const PATIENTS: usize = 4;
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+struct Referral {
+    patient: u32,
+}
+
+unsafe impl Pod for Referral {}
+unsafe impl Zeroable for Referral {}
+
+// Refers every patient at time 2, stepping slowly so the referrals reach planet 0 late
+struct Referrer;
+
+impl ThreadedAgent<16, Referral> for Referrer {
+    fn step(&mut self, context: &mut PlanetContext<16, Referral>, agent_id: usize) -> Event {
+        let time = context.time;
+        std::thread::sleep(std::time::Duration::from_micros(300));
+        if time == 2 {
+            for patient in 0..PATIENTS {
+                let referral = Referral {
+                    patient: patient as u32,
+                };
+                let msg = Msg::new(referral, time, time + 1, agent_id, Some(patient));
+                context.send_mail(msg, 0).unwrap();
+            }
+        }
+        Event::new(time, time, agent_id, Action::Timeout(1))
+    }
+
+    fn read_message(
+        &mut self,
+        _context: &mut PlanetContext<16, Referral>,
+        _msg: Msg<Referral>,
+        _agent_id: usize,
+    ) {
+    }
+}
+
+// Queues for a doctor when referred, and leaves on the tick it is seen. Whether it holds a doctor is read off the
+// resource rather than kept in the agent, so a rollback can't leave the two out of step.
+struct Patient;
+
+impl ThreadedAgent<16, Referral> for Patient {
+    fn step(&mut self, context: &mut PlanetContext<16, Referral>, agent_id: usize) -> Event {
+        let time = context.time;
+        let doctors = context.resources.get(DOCTORS).unwrap();
+        let action = if doctors.held_by(agent_id) == 0 {
+            Action::Acquire {
+                resource: DOCTORS,
+                priority: 0,
+            }
+        } else {
+            Action::Release {
+                resource: DOCTORS,
+                delay: None,
+            }
+        };
+        Event::new(time, time, agent_id, action)
+    }
+
+    fn read_message(
+        &mut self,
+        context: &mut PlanetContext<16, Referral>,
+        msg: Msg<Referral>,
+        agent_id: usize,
+    ) {
+        context.schedule_wakeup(agent_id, msg.recv);
+    }
+}
+
+fn run() -> (RunOutcome, ResourceStats, u64) {
+    let config = HybridConfig::new(2, 64)
+        .with_time_bounds(30.0, 1.0)
+        .with_optimistic_sync(8, 10)
+        .with_uniform_worlds(64, 4, 64)
+        .with_determinism(7);
+    let mut engine = HybridEngine::<16, 128, 1, Referral>::create(config).unwrap();
+    engine.add_resource(0, 2, QueueDiscipline::Fifo).unwrap();
+    for _ in 0..PATIENTS {
+        engine.spawn_agent(0, Box::new(Patient)).unwrap();
+    }
+    engine.spawn_agent(1, Box::new(Referrer)).unwrap();
+    engine.schedule(1, 0, 1).unwrap();
+    let engine = engine.run().unwrap();
+
+    let doctors = engine.planets[0].context.resources.get(DOCTORS).unwrap();
+    (
+        RunOutcome::of_hybrid(&engine),
+        doctors.stats(),
+        doctors.in_use(),
+    )
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    let (outcome, stats, _) = run();
+    println!("{stats:?}");
+    println!("rollbacks: {}", outcome.rollbacks);
+}
+
+#[test]
+fn cookbook_resources() {
+    let (outcome, stats, in_use) = run();
+    outcome.assert_reached(30);
+    // patients 0 and 1 see a doctor at 4, and 2 and 3 wait a tick for them to leave
+    let expected = ResourceStats {
+        acquisitions: 4,
+        waited: 2,
+        total_wait: 2,
+        max_queue: 2,
+        busy: 4,
+    };
+    assert_eq!(stats, expected);
+    assert_eq!(in_use, 0);
+}
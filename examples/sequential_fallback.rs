@@ -0,0 +1,121 @@
+//! Running a hybrid model on one thread.
+//! The same `HybridConfig` and `ThreadedAgent`s run on the `HybridEngine` and on a `SequentialRunner`, and
+//! produce the same traffic.
+use std::sync::{Arc, Mutex};
+
+use aika::{
+    mt::hybrid::{config::HybridConfig, HybridEngine},
+    prelude::*,
+    st::sequential::SequentialRunner,
+    testing::RunOutcome,
+};
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+struct Volley {
+    hits: u32,
+}
+
+unsafe impl Pod for Volley {}
+unsafe impl Zeroable for Volley {}
+
+type Shots = Vec<(usize, u64, u32)>;
+type Rally = Arc<Mutex<Shots>>;
+
+// Returns every volley to the other planet, 3 ticks later
+struct Player {
+    other: usize,
+    rally: Rally,
+}
+
+impl ThreadedAgent<16, Volley> for Player {
+    fn step(&mut self, context: &mut PlanetContext<16, Volley>, agent_id: usize) -> Event {
+        let time = context.time;
+        let msg = Msg::new(Volley { hits: 0 }, time, time + 3, agent_id, Some(0));
+        context.send_mail(msg, self.other).unwrap();
+        Event::new(time, time, agent_id, Action::Wait)
+    }
+
+    fn read_message(
+        &mut self,
+        context: &mut PlanetContext<16, Volley>,
+        msg: Msg<Volley>,
+        agent_id: usize,
+    ) {
+        self.rally
+            .lock()
+            .unwrap()
+            .push((context.world_id, msg.recv, msg.data.hits));
+        let volley = Volley {
+            hits: msg.data.hits + 1,
+        };
+        let reply = Msg::new(volley, msg.recv, msg.recv + 3, agent_id, Some(0));
+        context.send_mail(reply, self.other).unwrap();
+    }
+}
+
+fn config() -> HybridConfig {
+    HybridConfig::new(2, 64)
+        .with_time_bounds(30.0, 1.0)
+        .with_optimistic_sync(2, 10)
+        .with_uniform_worlds(16, 1, 16)
+}
+
+fn players(rally: &Rally) -> Vec<Vec<Box<dyn ThreadedAgent<16, Volley>>>> {
+    (0..2)
+        .map(|planet| {
+            let player = Player {
+                other: 1 - planet,
+                rally: Arc::clone(rally),
+            };
+            vec![Box::new(player) as Box<dyn ThreadedAgent<16, Volley>>]
+        })
+        .collect()
+}
+
+fn run() -> (RunOutcome, Shots, Shots) {
+    let sequential_rally = Rally::default();
+    let mut runner = SequentialRunner::<16, 128, 1, Volley>::from_hybrid_config(
+        config(),
+        players(&sequential_rally),
+    )
+    .unwrap();
+    runner.engine().schedule(0, 0, 1).unwrap();
+    let sequential = runner.run().unwrap();
+
+    let hybrid_rally = Rally::default();
+    let mut engine = HybridEngine::<16, 128, 1, Volley>::create(config()).unwrap();
+    for (planet, agents) in players(&hybrid_rally).into_iter().enumerate() {
+        for agent in agents {
+            engine.spawn_agent(planet, agent).unwrap();
+        }
+    }
+    engine.schedule(0, 0, 1).unwrap();
+    engine.run().unwrap();
+
+    let mut sequential_rally = sequential_rally.lock().unwrap().clone();
+    let mut hybrid_rally = hybrid_rally.lock().unwrap().clone();
+    sequential_rally.sort();
+    hybrid_rally.sort();
+    (
+        RunOutcome::of_hybrid(&sequential),
+        sequential_rally,
+        hybrid_rally,
+    )
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    let (_, sequential, hybrid) = run();
+    println!("sequential: {sequential:?}");
+    println!("hybrid:     {hybrid:?}");
+}
+
+#[test]
+fn cookbook_sequential_fallback() {
+    let (outcome, sequential, hybrid) = run();
+    outcome.assert_reached(30);
+    assert_eq!(outcome.rollbacks, 0);
+    assert_eq!(sequential.len(), 9);
+    assert_eq!(sequential, hybrid);
+}
@@ -0,0 +1,79 @@
+//! Single-threaded worlds and multicast groups.
+//! A `World` runs agents on the calling thread. A newsroom multicasts to a named group, and only subscribers
+//! read the bulletin; the message ledger confirms nothing went missing.
+use std::{cell::RefCell, rc::Rc};
+
+use aika::{
+    prelude::*,
+    st::World,
+    testing::{run_world_checked, RunOutcome},
+};
+
+const SUBSCRIBERS: &str = "subscribers";
+
+struct Newsroom;
+
+impl Agent<8, Msg<u8>> for Newsroom {
+    fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+        let time = context.time;
+        if let Some(mailbox) = &context.agent_states[id].mailbox {
+            let bulletin = Msg::multicast(1, time, time + 1, id, GroupId::named(SUBSCRIBERS));
+            mailbox.send(bulletin).unwrap();
+        }
+        Event::new(time, time, id, Action::Timeout(10))
+    }
+}
+
+struct Reader {
+    read: Rc<RefCell<usize>>,
+}
+
+impl Agent<8, Msg<u8>> for Reader {
+    fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+        let time = context.time;
+        if let Some(mailbox) = &mut context.agent_states[id].mailbox {
+            if let Some(msgs) = mailbox.poll() {
+                *self.read.borrow_mut() += msgs.len();
+            }
+        }
+        Event::new(time, time, id, Action::Timeout(1))
+    }
+}
+
+fn run() -> (RunOutcome, Vec<usize>) {
+    let mut world = World::<8, 128, 1, u8>::init(40.0, 1.0, 0).unwrap();
+    world.spawn_agent(Box::new(Newsroom));
+    let counts = (0..3)
+        .map(|_| {
+            let read = Rc::new(RefCell::new(0));
+            world.spawn_agent(Box::new(Reader {
+                read: Rc::clone(&read),
+            }));
+            read
+        })
+        .collect::<Vec<_>>();
+    world.init_support_layers(None).unwrap();
+    world
+        .world_context
+        .join_group(GroupId::named(SUBSCRIBERS), 1);
+    world
+        .world_context
+        .join_group(GroupId::named(SUBSCRIBERS), 3);
+    world.schedule_all_agents(1).unwrap();
+    run_world_checked(&mut world).unwrap();
+    let counts = counts.iter().map(|read| *read.borrow()).collect();
+    (RunOutcome::of_world(&world), counts)
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    let (_, counts) = run();
+    println!("bulletins read per reader: {counts:?}");
+}
+
+#[test]
+fn cookbook_world_multicast() {
+    let (outcome, counts) = run();
+    outcome.assert_reached(39);
+    assert_eq!(counts, vec![4, 0, 4]);
+}
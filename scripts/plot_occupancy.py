@@ -0,0 +1,46 @@
+#!/usr/bin/env python3
+"""Plot the timing wheel occupancy written by `Table::from_occupancy()`.
+
+Usage: plot_occupancy.py occupancy.csv [out.png]
+
+Draws one panel per world, with a line per wheel level and one for the events past the wheels (level -1).
+Needs matplotlib.
+"""
+import csv
+import sys
+from collections import defaultdict
+
+import matplotlib.pyplot as plt
+
+
+def main():
+    if len(sys.argv) not in (2, 3):
+        sys.exit(__doc__)
+    # world -> level -> [(time, events)]
+    series = defaultdict(lambda: defaultdict(list))
+    with open(sys.argv[1], newline="") as file:
+        for row in csv.DictReader(file):
+            series[int(row["world"])][int(row["level"])].append(
+                (int(row["time"]), int(row["events"]))
+            )
+
+    worlds = sorted(series)
+    figure, axes = plt.subplots(len(worlds), 1, sharex=True, squeeze=False)
+    for ax, world in zip(axes[:, 0], worlds):
+        for level in sorted(series[world], key=lambda level: (level < 0, level)):
+            times, events = zip(*series[world][level])
+            label = "overflow" if level < 0 else f"level {level}"
+            ax.step(times, events, where="post", label=label)
+        ax.set_title(f"world {world}")
+        ax.set_ylabel("events")
+        ax.legend(loc="upper right")
+    axes[-1, 0].set_xlabel("time")
+    figure.tight_layout()
+    if len(sys.argv) == 3:
+        figure.savefig(sys.argv[2])
+    else:
+        plt.show()
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,198 @@
+//! Encoded (non-`Pod`) state in `Journal`s.
+//! A `StateCodec` turns an agent or world state into bytes, which are stored in the `Journal` as fixed-capacity
+//! `EncodedState` blocks so they roll back like any other entry. With the `serde` feature, every
+//! serde-serializable type is a `StateCodec` (encoded as JSON), and a `StateSnapshot` of a `Journal`'s timeline
+//! can be exported and imported across versions of a model.
+use bytemuck::{Pod, Zeroable};
+use mesocarp::logging::journal::Journal;
+
+use crate::AikaError;
+
+/// Version of the `StateSnapshot` layout, checked on import.
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+
+/// Conversion between a state value and the bytes stored in a `Journal`.
+pub trait StateCodec: Sized {
+    fn encode(&self) -> Result<Vec<u8>, AikaError>;
+    fn decode(bytes: &[u8]) -> Result<Self, AikaError>;
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> StateCodec for T {
+    fn encode(&self) -> Result<Vec<u8>, AikaError> {
+        serde_json::to_vec(self).map_err(|err| AikaError::StateCodec(err.to_string()))
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, AikaError> {
+        serde_json::from_slice(bytes).map_err(|err| AikaError::StateCodec(err.to_string()))
+    }
+}
+
+/// An encoded state of at most `CAPACITY` bytes, as written to a `Journal`.
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct EncodedState<const CAPACITY: usize> {
+    bytes: [u8; CAPACITY],
+    len: [u8; 4],
+}
+
+unsafe impl<const CAPACITY: usize> Pod for EncodedState<CAPACITY> {}
+unsafe impl<const CAPACITY: usize> Zeroable for EncodedState<CAPACITY> {}
+
+impl<const CAPACITY: usize> EncodedState<CAPACITY> {
+    pub fn new(bytes: &[u8]) -> Result<Self, AikaError> {
+        if bytes.len() > CAPACITY {
+            return Err(AikaError::StateCodec(format!(
+                "encoded state is {} bytes, capacity is {CAPACITY}",
+                bytes.len()
+            )));
+        }
+        let mut state = Self::zeroed();
+        state.bytes[..bytes.len()].copy_from_slice(bytes);
+        state.len = (bytes.len() as u32).to_le_bytes();
+        Ok(state)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..u32::from_le_bytes(self.len) as usize]
+    }
+}
+
+/// Write and read `StateCodec` values on a `Journal`, using `EncodedState<CAPACITY>` entries.
+pub trait CodecJournal {
+    /// Encode and log a state at `time`.
+    fn write_encoded<const CAPACITY: usize, T: StateCodec>(
+        &mut self,
+        state: &T,
+        time: u64,
+        horizon: Option<u64>,
+    ) -> Result<(), AikaError>;
+
+    /// Decode the most recently written state.
+    fn read_decoded<const CAPACITY: usize, T: StateCodec>(&self) -> Result<T, AikaError>;
+
+    /// Export every logged entry, in time order.
+    fn export_snapshot<const CAPACITY: usize>(&self) -> StateSnapshot;
+
+    /// Replay a snapshot's entries into this `Journal`.
+    fn import_snapshot<const CAPACITY: usize>(
+        &mut self,
+        snapshot: &StateSnapshot,
+    ) -> Result<(), AikaError>;
+}
+
+impl CodecJournal for Journal {
+    fn write_encoded<const CAPACITY: usize, T: StateCodec>(
+        &mut self,
+        state: &T,
+        time: u64,
+        horizon: Option<u64>,
+    ) -> Result<(), AikaError> {
+        let encoded = EncodedState::<CAPACITY>::new(&state.encode()?)?;
+        self.write(encoded, time, horizon);
+        Ok(())
+    }
+
+    fn read_decoded<const CAPACITY: usize, T: StateCodec>(&self) -> Result<T, AikaError> {
+        let encoded = self.read_state::<EncodedState<CAPACITY>>()?;
+        T::decode(encoded.as_bytes())
+    }
+
+    fn export_snapshot<const CAPACITY: usize>(&self) -> StateSnapshot {
+        let entries = self
+            .read_all::<EncodedState<CAPACITY>>()
+            .into_iter()
+            .map(|(state, time)| (time, state.as_bytes().to_vec()))
+            .collect();
+        StateSnapshot {
+            version: SNAPSHOT_FORMAT_VERSION,
+            entries,
+        }
+    }
+
+    fn import_snapshot<const CAPACITY: usize>(
+        &mut self,
+        snapshot: &StateSnapshot,
+    ) -> Result<(), AikaError> {
+        if snapshot.version > SNAPSHOT_FORMAT_VERSION {
+            return Err(AikaError::StateCodec(format!(
+                "snapshot format version {} is newer than {SNAPSHOT_FORMAT_VERSION}",
+                snapshot.version
+            )));
+        }
+        for (time, bytes) in &snapshot.entries {
+            self.write(EncodedState::<CAPACITY>::new(bytes)?, *time, None);
+        }
+        Ok(())
+    }
+}
+
+/// The encoded timeline of a `Journal`, as `(time, bytes)` entries.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StateSnapshot {
+    pub version: u32,
+    pub entries: Vec<(u64, Vec<u8>)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    struct Inventory {
+        items: Vec<u16>,
+    }
+
+    #[cfg(not(feature = "serde"))]
+    impl StateCodec for Inventory {
+        fn encode(&self) -> Result<Vec<u8>, AikaError> {
+            Ok(self
+                .items
+                .iter()
+                .flat_map(|item| item.to_le_bytes())
+                .collect())
+        }
+
+        fn decode(bytes: &[u8]) -> Result<Self, AikaError> {
+            let items = bytes
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect();
+            Ok(Self { items })
+        }
+    }
+
+    #[test]
+    fn test_encoded_state_round_trip() {
+        let mut journal = Journal::init(1024);
+        for time in 1..4 {
+            let state = Inventory {
+                items: (0..time as u16).collect(),
+            };
+            journal.write_encoded::<64, _>(&state, time, None).unwrap();
+        }
+        let latest: Inventory = journal.read_decoded::<64, _>().unwrap();
+        assert_eq!(latest.items, vec![0, 1, 2]);
+
+        journal.rollback(2);
+        let rewound: Inventory = journal.read_decoded::<64, _>().unwrap();
+        assert_eq!(rewound.items, vec![0, 1]);
+
+        let snapshot = journal.export_snapshot::<64>();
+        let mut restored = Journal::init(1024);
+        restored.import_snapshot::<64>(&snapshot).unwrap();
+        assert_eq!(restored.export_snapshot::<64>(), snapshot);
+    }
+
+    #[test]
+    fn test_encoded_state_over_capacity() {
+        let mut journal = Journal::init(1024);
+        let state = Inventory { items: vec![7; 64] };
+        assert!(matches!(
+            journal.write_encoded::<16, _>(&state, 1, None),
+            Err(AikaError::StateCodec(_))
+        ));
+    }
+}
@@ -0,0 +1,318 @@
+//! Async/await front-end for `ThreadedAgent`s.
+//! An `AsyncAgent` wraps an `async` block whose awaitable primitives (`sleep`, `recv`, `send`) are
+//! translated into the `Planet`'s existing `Event`/`Action` machinery, which acts as the cooperative scheduler.
+//!
+//! Agent logic lives inside the future rather than in a state `Journal`, so it is not rewound when a
+//! `Planet` rolls back. Use it for agents whose protocol never receives stragglers, or keep any state that must
+//! survive rollback in the `PlanetContext` journals.
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll, Waker},
+};
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    agents::{PlanetContext, ThreadedAgent},
+    objects::{Action, Event, Msg},
+    AikaError,
+};
+
+type CoopFuture = Pin<Box<dyn Future<Output = ()>>>;
+type CoopFactory<MessageType> = Box<dyn FnOnce(Coop<MessageType>) -> CoopFuture>;
+
+/// What the agent's future is currently blocked on.
+enum Wait<MessageType: Clone> {
+    Idle,
+    Sleep(u64),
+    Recv,
+    Send(Msg<MessageType>, usize),
+}
+
+struct CoopState<MessageType: Clone> {
+    agent_id: usize,
+    time: u64,
+    inbox: VecDeque<Msg<MessageType>>,
+    wait: Wait<MessageType>,
+    sent: Option<Result<(), AikaError>>,
+}
+
+/// Handle given to an `AsyncAgent`'s future for awaiting simulation time, mail, and sending mail.
+pub struct Coop<MessageType: Clone> {
+    state: Rc<RefCell<CoopState<MessageType>>>,
+}
+
+impl<MessageType: Clone> Clone for Coop<MessageType> {
+    fn clone(&self) -> Self {
+        Self {
+            state: Rc::clone(&self.state),
+        }
+    }
+}
+
+impl<MessageType: Clone> Coop<MessageType> {
+    /// Current simulation time as seen by the agent.
+    pub fn now(&self) -> u64 {
+        self.state.borrow().time
+    }
+
+    /// Index of the agent on its `Planet`.
+    pub fn agent_id(&self) -> usize {
+        self.state.borrow().agent_id
+    }
+
+    /// Suspend the agent for `ticks` steps of simulation time.
+    pub fn sleep(&self, ticks: u64) -> Sleep<MessageType> {
+        Sleep {
+            coop: self.clone(),
+            ticks,
+            until: None,
+        }
+    }
+
+    /// Suspend the agent until a `Msg` is delivered to it.
+    pub fn recv(&self) -> Recv<MessageType> {
+        Recv { coop: self.clone() }
+    }
+
+    /// Send a `Msg` to an agent on another `Planet`.
+    pub fn send(&self, msg: Msg<MessageType>, to_world: usize) -> SendMail<MessageType> {
+        SendMail {
+            coop: self.clone(),
+            mail: Some((msg, to_world)),
+        }
+    }
+}
+
+/// Future returned by `Coop::sleep()`.
+pub struct Sleep<MessageType: Clone> {
+    coop: Coop<MessageType>,
+    ticks: u64,
+    until: Option<u64>,
+}
+
+impl<MessageType: Clone> Future for Sleep<MessageType> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        let now = self.coop.now();
+        let ticks = self.ticks;
+        let until = *self.until.get_or_insert(now.saturating_add(ticks));
+        if now >= until {
+            return Poll::Ready(());
+        }
+        self.coop.state.borrow_mut().wait = Wait::Sleep(until - now);
+        Poll::Pending
+    }
+}
+
+/// Future returned by `Coop::recv()`.
+pub struct Recv<MessageType: Clone> {
+    coop: Coop<MessageType>,
+}
+
+impl<MessageType: Clone> Future for Recv<MessageType> {
+    type Output = Msg<MessageType>;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Msg<MessageType>> {
+        let mut state = self.coop.state.borrow_mut();
+        match state.inbox.pop_front() {
+            Some(msg) => Poll::Ready(msg),
+            None => {
+                state.wait = Wait::Recv;
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Future returned by `Coop::send()`.
+pub struct SendMail<MessageType: Clone> {
+    coop: Coop<MessageType>,
+    mail: Option<(Msg<MessageType>, usize)>,
+}
+
+impl<MessageType: Clone> Unpin for SendMail<MessageType> {}
+
+impl<MessageType: Clone> Future for SendMail<MessageType> {
+    type Output = Result<(), AikaError>;
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), AikaError>> {
+        if let Some((msg, to_world)) = self.mail.take() {
+            self.coop.state.borrow_mut().wait = Wait::Send(msg, to_world);
+            return Poll::Pending;
+        }
+        match self.coop.state.borrow_mut().sent.take() {
+            Some(result) => Poll::Ready(result),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// A `ThreadedAgent` whose logic is written as an `async` block over a `Coop` handle.
+pub struct AsyncAgent<MessageType: Clone> {
+    coop: Coop<MessageType>,
+    factory: Option<CoopFactory<MessageType>>,
+    future: Option<CoopFuture>,
+}
+
+impl<MessageType: Clone + 'static> AsyncAgent<MessageType> {
+    /// Wrap an agent body. The future is started on the agent's first `step()`.
+    pub fn new<F, Fut>(body: F) -> Self
+    where
+        F: FnOnce(Coop<MessageType>) -> Fut + 'static,
+        Fut: Future<Output = ()> + 'static,
+    {
+        let state = CoopState {
+            agent_id: 0,
+            time: 0,
+            inbox: VecDeque::new(),
+            wait: Wait::Idle,
+            sent: None,
+        };
+        Self {
+            coop: Coop {
+                state: Rc::new(RefCell::new(state)),
+            },
+            factory: Some(Box::new(move |coop| Box::pin(body(coop)))),
+            future: None,
+        }
+    }
+
+    /// Whether the agent's future has run to completion.
+    pub fn is_finished(&self) -> bool {
+        self.factory.is_none() && self.future.is_none()
+    }
+
+    /// Poll the agent's future until it blocks, returning the number of ticks it wants to sleep for, if any.
+    fn drive<const SLOTS: usize>(
+        &mut self,
+        context: &mut PlanetContext<SLOTS, MessageType>,
+        agent_id: usize,
+        time: u64,
+    ) -> Option<u64>
+    where
+        MessageType: Pod + Zeroable,
+    {
+        {
+            let mut state = self.coop.state.borrow_mut();
+            state.agent_id = agent_id;
+            state.time = time;
+        }
+        if let Some(factory) = self.factory.take() {
+            self.future = Some(factory(self.coop.clone()));
+        }
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            let future = self.future.as_mut()?;
+            if future.as_mut().poll(&mut cx).is_ready() {
+                self.future = None;
+                return None;
+            }
+            let wait = std::mem::replace(&mut self.coop.state.borrow_mut().wait, Wait::Idle);
+            match wait {
+                Wait::Send(msg, to_world) => {
+                    self.coop.state.borrow_mut().sent = Some(context.send_mail(msg, to_world));
+                }
+                Wait::Recv => {
+                    let mut state = self.coop.state.borrow_mut();
+                    if state.inbox.is_empty() {
+                        state.wait = Wait::Recv;
+                        return None;
+                    }
+                }
+                Wait::Sleep(ticks) => return Some(ticks),
+                Wait::Idle => return None,
+            }
+        }
+    }
+}
+
+impl<const SLOTS: usize, MessageType: Pod + Zeroable + Clone> ThreadedAgent<SLOTS, MessageType>
+    for AsyncAgent<MessageType>
+{
+    fn step(&mut self, context: &mut PlanetContext<SLOTS, MessageType>, agent_id: usize) -> Event {
+        let time = context.time;
+        match self.drive(context, agent_id, time) {
+            Some(ticks) => Event::new(time, time, agent_id, Action::Timeout(ticks)),
+            None => Event::new(time, time, agent_id, Action::Wait),
+        }
+    }
+
+    fn read_message(
+        &mut self,
+        context: &mut PlanetContext<SLOTS, MessageType>,
+        msg: Msg<MessageType>,
+        agent_id: usize,
+    ) {
+        let time = msg.recv;
+        let waiting = {
+            let mut state = self.coop.state.borrow_mut();
+            state.inbox.push_back(msg);
+            matches!(state.wait, Wait::Recv)
+        };
+        if !waiting {
+            return;
+        }
+        if let Some(ticks) = self.drive(context, agent_id, time) {
+            context.schedule_wakeup(agent_id, time.saturating_add(ticks));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::mt::hybrid::{config::HybridConfig, HybridEngine};
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Ping {
+        value: u32,
+    }
+
+    unsafe impl Pod for Ping {}
+    unsafe impl Zeroable for Ping {}
+
+    #[test]
+    fn test_async_ping_pong() {
+        let config = HybridConfig::new(2, 16)
+            .with_time_bounds(50.0, 1.0)
+            .with_optimistic_sync(1, 10)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<128, 128, 1, Ping>::create(config).unwrap();
+
+        let pinger = AsyncAgent::new(|coop: Coop<Ping>| async move {
+            for value in 0..3 {
+                coop.sleep(5).await;
+                let now = coop.now();
+                let msg = Msg::new(Ping { value }, now, now + 3, coop.agent_id(), Some(0));
+                coop.send(msg, 1).await.unwrap();
+            }
+        });
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let received = Arc::clone(&log);
+        let ponger = AsyncAgent::new(move |coop: Coop<Ping>| async move {
+            loop {
+                let msg = coop.recv().await;
+                coop.sleep(1).await;
+                received.lock().unwrap().push((coop.now(), msg.data.value));
+            }
+        });
+
+        engine.spawn_agent(0, Box::new(pinger)).unwrap();
+        engine.spawn_agent(1, Box::new(ponger)).unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        engine.run().unwrap();
+
+        // sent at 6, 11 and 16, received 3 ticks later and handled one tick after that
+        assert_eq!(*log.lock().unwrap(), vec![(10, 0), (15, 1), (20, 2)]);
+    }
+}
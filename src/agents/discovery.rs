@@ -0,0 +1,132 @@
+//! Discovery of peers by attribute, so model code needn't hardcode the indices of the agents it talks to.
+//! Agents publish `(key, value)` attributes, e.g. key 3 for "offers service 3" with the price as value, and query
+//! for the agents that published a key, or a key with a given value. A query made with `discover()` is answered
+//! the next step through `Agent::on_peers()` or `ThreadedAgent::on_peers()`, like a reply; `peers()` reads the same
+//! answer at once. On a `World` attributes apply as soon as they are published. On a `HybridEngine` a `Planet` hands
+//! them to the `Galaxy` alongside its reduction contributions, rollbacks withdraw them, and the `Galaxy` commits them
+//! at every GVT checkpoint, so a query sees the attributes published before the last checkpoint however far ahead its
+//! `Planet` runs. Peers that moved since they published are found at their new address.
+use std::collections::BTreeMap;
+
+/// An agent that published the key a query asked for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Peer {
+    pub world: usize,
+    pub agent: usize,
+    /// the value it published for the key
+    pub value: u64,
+}
+
+/// The agents that published `key`, with `value` if one is given.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Query {
+    pub key: usize,
+    pub value: Option<u64>,
+}
+
+impl Query {
+    pub fn new(key: usize) -> Self {
+        Self { key, value: None }
+    }
+
+    /// Only the agents that published `value` for the key.
+    pub fn with_value(mut self, value: u64) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    pub fn matches(&self, value: u64) -> bool {
+        self.value.is_none_or(|wanted| wanted == value)
+    }
+}
+
+/// The attributes published by the agents of a run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Attributes {
+    /// values by `(key, world, agent)`
+    values: BTreeMap<(usize, usize, usize), u64>,
+}
+
+impl Attributes {
+    /// Publish `value` for `key` on behalf of the agent at `(world, agent)`, replacing what it published before.
+    pub fn set(&mut self, world: usize, agent: usize, key: usize, value: u64) {
+        self.values.insert((key, world, agent), value);
+    }
+
+    /// Withdraw `key` for the agent at `(world, agent)`.
+    pub fn remove(&mut self, world: usize, agent: usize, key: usize) {
+        self.values.remove(&(key, world, agent));
+    }
+
+    /// Withdraw every attribute of the agent at `(world, agent)`.
+    pub fn remove_agent(&mut self, world: usize, agent: usize) {
+        self.values
+            .retain(|(_, at_world, at_agent), _| (*at_world, *at_agent) != (world, agent));
+    }
+
+    /// Add the attributes of `other`, whose agents are numbered from `offset` on here.
+    pub(crate) fn append(&mut self, other: Attributes, offset: usize) {
+        for ((key, world, agent), value) in other.values {
+            self.values.insert((key, world, agent + offset), value);
+        }
+    }
+
+    /// The agents matching `query`, by address.
+    pub fn peers(&self, query: &Query) -> Vec<Peer> {
+        self.values
+            .range((query.key, 0, 0)..=(query.key, usize::MAX, usize::MAX))
+            .filter(|(_, value)| query.matches(**value))
+            .map(|((_, world, agent), value)| Peer {
+                world: *world,
+                agent: *agent,
+                value: *value,
+            })
+            .collect()
+    }
+
+    /// Number of attributes published.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queries() {
+        let mut attributes = Attributes::default();
+        attributes.set(0, 4, 7, 10);
+        attributes.set(1, 2, 7, 20);
+        attributes.set(0, 5, 8, 10);
+        attributes.set(0, 4, 7, 30);
+        assert_eq!(attributes.len(), 3);
+        assert_eq!(
+            attributes.peers(&Query::new(7)),
+            [
+                Peer {
+                    world: 0,
+                    agent: 4,
+                    value: 30
+                },
+                Peer {
+                    world: 1,
+                    agent: 2,
+                    value: 20
+                }
+            ]
+        );
+        assert_eq!(attributes.peers(&Query::new(7).with_value(20)).len(), 1);
+        assert!(attributes.peers(&Query::new(9)).is_empty());
+
+        attributes.remove(1, 2, 7);
+        attributes.remove_agent(0, 4);
+        assert!(attributes.peers(&Query::new(7)).is_empty());
+        assert_eq!(attributes.len(), 1);
+    }
+}
@@ -0,0 +1,378 @@
+//! Built-in arrival processes, for models driven by exogenous arrivals of customers, jobs or packets.
+//! A `Generator` is an agent that draws arrival times from its `Arrivals` and, for every arrival, either mails a
+//! copy of its payload to a target or wakes the target up, in the generator's own `World` or `Planet`. Arrivals are
+//! given in seconds and land on the tick nearest to them with the engine's timestep. A generator always works one
+//! arrival tick ahead: woken at `t`, it emits every arrival of the next tick that has any, as mail received or a
+//! wake-up at that tick, and sleeps until then. The first arrivals are drawn from the time it is first woken.
+//!
+//! Poisson gaps are drawn with the same seeded SplitMix64 ranking used for canonical tick orders, keyed by the
+//! generator's seed, world, agent and draw count, so a run with the same seeds gets the same arrivals. On a `Planet`
+//! the draw count and next arrival live in the agent's state `Journal` and roll back with it, so re-executed steps
+//! draw the same gaps again.
+use std::{fs, path::Path, sync::Arc};
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    agents::{Agent, PlanetContext, ThreadedAgent, WorldContext},
+    objects::{seeded_rank, Action, Event, Msg},
+    time::SimTime,
+    AikaError,
+};
+
+/// When arrivals happen, in seconds of simulated time.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Arrivals {
+    /// exponential gaps with a mean of `1 / rate` seconds
+    Poisson { rate: f64 },
+    /// a fixed gap of `interval` seconds
+    Deterministic { interval: f64 },
+    /// the given times, in order, then no more
+    Trace(Arc<[f64]>),
+}
+
+impl Arrivals {
+    /// Arrival times read from a file of one time in seconds per line. Blank lines and lines starting with `#` are
+    /// skipped.
+    pub fn from_trace_file(path: impl AsRef<Path>) -> Result<Self, AikaError> {
+        let contents = fs::read_to_string(path)?;
+        let mut times = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let time = line.parse::<f64>().map_err(|_| {
+                AikaError::ConfigError(format!(
+                    "line {} of the trace is not a time: `{line}`",
+                    number + 1
+                ))
+            })?;
+            times.push(time);
+        }
+        Ok(Arrivals::Trace(times.into()))
+    }
+
+    fn validate(&self) -> Result<(), AikaError> {
+        let valid = match self {
+            Arrivals::Poisson { rate } => rate.is_finite() && *rate > 0.0,
+            Arrivals::Deterministic { interval } => interval.is_finite() && *interval > 0.0,
+            Arrivals::Trace(times) => {
+                times.iter().all(|time| time.is_finite() && *time >= 0.0)
+                    && times.windows(2).all(|pair| pair[0] <= pair[1])
+            }
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(AikaError::ConfigError(format!(
+                "invalid arrival process {self:?}, rates and intervals must be positive and traces sorted"
+            )))
+        }
+    }
+
+    /// The time of arrival `index`, the one after `previous`, or `None` once a trace runs out.
+    fn arrival(&self, key: u64, index: u64, previous: f64) -> Option<f64> {
+        match self {
+            Arrivals::Poisson { rate } => {
+                let uniform = (seeded_rank(key, index) >> 11) as f64 / (1u64 << 53) as f64;
+                Some(previous - (1.0 - uniform).ln() / rate)
+            }
+            Arrivals::Deterministic { interval } => Some(previous + interval),
+            Arrivals::Trace(times) => times.get(index as usize).copied(),
+        }
+    }
+}
+
+/// What a `Generator` does on every arrival.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Emission<D> {
+    /// send `data` to agent `to`, received at the arrival
+    Mail { to: usize, data: D },
+    /// step agent `to` at the arrival
+    Trigger(usize),
+}
+
+/// How far a `Generator` has drawn its arrivals.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[repr(C)]
+pub struct GeneratorState {
+    /// time of the next arrival not yet emitted in seconds, infinite once a trace has run out
+    pub next: f64,
+    /// arrivals drawn so far
+    pub drawn: u64,
+}
+
+unsafe impl Pod for GeneratorState {}
+unsafe impl Zeroable for GeneratorState {}
+
+/// An agent emitting `Emission`s at the times of its `Arrivals`.
+pub struct Generator<D> {
+    arrivals: Arrivals,
+    emission: Emission<D>,
+    seed: u64,
+    /// state on a `World`; a `Planet` keeps it in the agent's state `Journal`
+    state: Option<GeneratorState>,
+}
+
+impl<D> Generator<D> {
+    pub fn new(arrivals: Arrivals, emission: Emission<D>) -> Result<Self, AikaError> {
+        arrivals.validate()?;
+        Ok(Self {
+            arrivals,
+            emission,
+            seed: 0,
+            state: None,
+        })
+    }
+
+    /// Draw Poisson gaps with `seed`. Generators on the same seed still differ by world and agent.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// How far the generator has drawn on a `World`, `None` before its first step.
+    pub fn state(&self) -> Option<GeneratorState> {
+        self.state
+    }
+
+    /// Draw the arrivals of the next tick after `now` that has any, returning the new state and that tick with its
+    /// number of arrivals. Arrivals nearest to `now` or earlier are moved to the next tick.
+    fn advance(
+        &self,
+        state: Option<GeneratorState>,
+        world: usize,
+        agent: usize,
+        now: u64,
+        timestep: f64,
+    ) -> (GeneratorState, Option<(u64, u64)>) {
+        let key = seeded_rank(seeded_rank(self.seed, world as u64), agent as u64);
+        let mut state = state.unwrap_or_else(|| GeneratorState {
+            next: self
+                .arrivals
+                .arrival(key, 0, SimTime(now).as_secs_f64(timestep))
+                .unwrap_or(f64::INFINITY),
+            drawn: 1,
+        });
+        let earliest = now.saturating_add(1);
+        let tick = |secs| SimTime::from_secs_f64(secs, timestep).ticks().max(earliest);
+        if !state.next.is_finite() {
+            return (state, None);
+        }
+        let due = tick(state.next);
+        let mut count = 0;
+        while state.next.is_finite() && tick(state.next) == due {
+            count += 1;
+            state.next = self
+                .arrivals
+                .arrival(key, state.drawn, state.next)
+                .unwrap_or(f64::INFINITY);
+            state.drawn += 1;
+        }
+        (state, Some((due, count)))
+    }
+}
+
+impl<const SLOTS: usize, D: Clone + 'static> Agent<SLOTS, Msg<D>> for Generator<D> {
+    fn step(&mut self, context: &mut WorldContext<SLOTS, Msg<D>>, agent_id: usize) -> Event {
+        let now = context.time;
+        let (state, next) = self.advance(self.state, 0, agent_id, now, context.timestep);
+        self.state = Some(state);
+        let Some((time, count)) = next else {
+            return Event::new(now, now, agent_id, Action::Wait);
+        };
+        for _ in 0..count {
+            match &self.emission {
+                Emission::Mail { to, data } => {
+                    if let Some(mailbox) = &context.agent_states[agent_id].mailbox {
+                        let _ =
+                            mailbox.send(Msg::new(data.clone(), now, time, agent_id, Some(*to)));
+                    }
+                }
+                Emission::Trigger(to) => context.schedule_wakeup(*to, time),
+            }
+        }
+        Event::new(now, now, agent_id, Action::Schedule(time))
+    }
+}
+
+impl<const SLOTS: usize, D: Pod + Zeroable + Clone> ThreadedAgent<SLOTS, D> for Generator<D> {
+    fn step(&mut self, context: &mut PlanetContext<SLOTS, D>, agent_id: usize) -> Event {
+        let now = context.time;
+        let state = context.agent_states[agent_id]
+            .read_state::<GeneratorState>()
+            .ok()
+            .copied();
+        let (state, next) = self.advance(state, context.world_id, agent_id, now, context.timestep);
+        context.agent_states[agent_id].write(state, now, None);
+        let Some((time, count)) = next else {
+            return Event::new(now, now, agent_id, Action::Wait);
+        };
+        for _ in 0..count {
+            match self.emission {
+                Emission::Mail { to, data } => {
+                    let msg = Msg::new(data, now, time, agent_id, Some(to));
+                    let _ = context.send_mail(msg, context.world_id);
+                }
+                Emission::Trigger(to) => context.schedule_wakeup(to, time),
+            }
+        }
+        Event::new(now, now, agent_id, Action::Schedule(time))
+    }
+
+    /// Generators only emit, so mail is ignored.
+    fn read_message(
+        &mut self,
+        _context: &mut PlanetContext<SLOTS, D>,
+        _msg: Msg<D>,
+        _agent_id: usize,
+    ) {
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        st::World,
+    };
+    use std::{cell::RefCell, rc::Rc};
+
+    // Notes the ticks it is stepped at, and counts its mail, polling every tick if `polling`
+    #[derive(Default)]
+    struct Sink {
+        polling: bool,
+        seen: Rc<RefCell<(Vec<u64>, usize)>>,
+    }
+
+    impl Agent<8, Msg<u8>> for Sink {
+        fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, agent_id: usize) -> Event {
+            let mut seen = self.seen.borrow_mut();
+            seen.0.push(context.time);
+            if let Some(mailbox) = &mut context.agent_states[agent_id].mailbox {
+                while let Some(letters) = mailbox.poll() {
+                    seen.1 += letters.len();
+                }
+            }
+            let action = if self.polling {
+                Action::Timeout(1)
+            } else {
+                Action::Wait
+            };
+            Event::new(context.time, context.time, agent_id, action)
+        }
+    }
+
+    fn run_world(generator: Generator<u8>, sink: Sink, timestep: f64) -> Option<GeneratorState> {
+        let mut world = World::<8, 128, 1, u8>::init(50.0, timestep, 0).unwrap();
+        let polling = sink.polling;
+        world.spawn_agent(Box::new(generator));
+        world.spawn_agent(Box::new(sink));
+        world.init_support_layers(None).unwrap();
+        world.schedule(1, 0).unwrap();
+        if polling {
+            world.schedule(1, 1).unwrap();
+        }
+        world.run().unwrap();
+        let state = world.agents_of::<Generator<u8>>().next().unwrap().1.state();
+        state
+    }
+
+    #[test]
+    fn test_triggers_follow_the_trace() {
+        let path = std::env::temp_dir().join(format!("aika-trace-{}.txt", std::process::id()));
+        fs::write(&path, "# arrivals\n2.2\n2.4\n\n7.0\n0.5e1\n").unwrap();
+        assert!(matches!(
+            Generator::new(
+                Arrivals::from_trace_file(&path).unwrap(),
+                Emission::<u8>::Trigger(1)
+            ),
+            Err(AikaError::ConfigError(_))
+        ));
+        fs::write(&path, "# arrivals\n0.2\n2.1\n2.2\n\n5.0\n7.0\n").unwrap();
+        let arrivals = Arrivals::from_trace_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let generator = Generator::new(arrivals, Emission::Trigger(1)).unwrap();
+        let sink = Sink::default();
+        let seen = sink.seen.clone();
+        let state = run_world(generator, sink, 0.5).unwrap();
+        // 0.2s is before the first step and moves to the tick after it, and 2.1s and 2.2s share tick 4
+        assert_eq!(seen.borrow().0, [2, 4, 4, 10, 14]);
+        assert_eq!(state.drawn, 6);
+        assert!(state.next.is_infinite());
+    }
+
+    #[test]
+    fn test_poisson_mail_follows_the_seed() {
+        let run = |seed| {
+            let generator = Generator::new(
+                Arrivals::Poisson { rate: 2.0 },
+                Emission::Mail { to: 1, data: 7 },
+            )
+            .unwrap()
+            .with_seed(seed);
+            let sink = Sink {
+                polling: true,
+                ..Sink::default()
+            };
+            let seen = sink.seen.clone();
+            let state = run_world(generator, sink, 1.0);
+            let letters = seen.borrow().1;
+            (state, letters)
+        };
+        let (state, letters) = run(3);
+        // about two a second for 49 seconds
+        assert!((70..130).contains(&letters), "{letters} letters");
+        assert_eq!(run(3), (state, letters));
+        assert_ne!(run(4).0, state);
+    }
+
+    // Counts its letters in its state
+    struct Counter;
+
+    impl ThreadedAgent<16, u64> for Counter {
+        fn step(&mut self, context: &mut PlanetContext<16, u64>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            context: &mut PlanetContext<16, u64>,
+            msg: Msg<u64>,
+            agent_id: usize,
+        ) {
+            let mut count = context.typed_state::<u64>(agent_id).unwrap();
+            count.set(count.get_or(0) + msg.data);
+        }
+    }
+
+    #[test]
+    fn test_mail_on_planets() {
+        let config = HybridConfig::new(1, 64)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_uniform_worlds(256, 2, 1024);
+        let mut engine = HybridEngine::<16, 16, 2, u64>::create(config).unwrap();
+        let generator = Generator::new(
+            Arrivals::Deterministic { interval: 3.0 },
+            Emission::Mail { to: 1, data: 1 },
+        )
+        .unwrap();
+        engine.spawn_agent(0, Box::new(generator)).unwrap();
+        engine.spawn_agent(0, Box::new(Counter)).unwrap();
+        engine.schedule(0, 0, 1).unwrap();
+        let mut engine = engine.run().unwrap();
+        let context = &mut engine.planets[0].context;
+        // arrivals at 4, 7, ..., 19, and the one at 22 is past the terminal time
+        assert_eq!(context.typed_state::<u64>(1).unwrap().get(), Some(6));
+        let state = context
+            .typed_state::<GeneratorState>(0)
+            .unwrap()
+            .get()
+            .unwrap();
+        assert_eq!(state.next, 25.0);
+    }
+}
@@ -0,0 +1,289 @@
+//! Waits that incoming mail can cut short.
+//! An agent that yields `Action::Interruptible(delay)` times out like `Action::Timeout(delay)`, unless mail is handed
+//! to it first: it is then woken at the receive time of that mail, or on the next tick if that tick's events already
+//! ran, and the wake-up it set is dropped. `PlanetContext::interrupted()` and `WorldContext::interrupted()` tell the
+//! `step()` of an early wake-up from the others. Any later step of the agent ends its wait, so only the wait set by
+//! its last step can be interrupted. An `InterruptTable` keeps the waits of a `Planet` or `World`; a `Planet` rolls it
+//! back with the rest of its state and forgets the waits GVT has passed. An agent that migrates leaves its wait
+//! behind, and its wake-up falls due as set.
+use std::collections::BTreeMap;
+
+#[derive(Copy, Clone, Debug)]
+struct Wait {
+    /// time of the wake-up the agent set
+    wake: u64,
+    /// time a later step or an interrupt ended the wait
+    ended: Option<u64>,
+    /// `(receive time of the interrupting mail, time of the early wake-up)`
+    interrupt: Option<(u64, u64)>,
+}
+
+/// How an event waking an agent stands with its waits.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Wake {
+    Due,
+    /// the early wake-up of an interrupted wait
+    Interrupted,
+    /// the wake-up of an interrupted wait, replaced by the early one
+    Dropped,
+}
+
+/// The interruptible waits of the agents of one `Planet` or `World`.
+#[derive(Clone, Debug, Default)]
+pub struct InterruptTable {
+    /// waits by `(agent, time set)`
+    waits: BTreeMap<(usize, u64), Wait>,
+    /// interrupted waits whose early wake-up isn't scheduled yet, as `(agent, time set)`
+    pending: Vec<(usize, u64)>,
+}
+
+impl InterruptTable {
+    /// Number of waits mail can still interrupt.
+    pub fn open(&self) -> usize {
+        self.waits
+            .values()
+            .filter(|wait| wait.ended.is_none())
+            .count()
+    }
+
+    fn waits_of(&mut self, agent: usize) -> impl Iterator<Item = (&(usize, u64), &mut Wait)> {
+        self.waits.range_mut((agent, 0)..=(agent, u64::MAX))
+    }
+
+    /// End the wait of `agent`, stepped at `time`.
+    pub(crate) fn stepped(&mut self, agent: usize, time: u64) {
+        for (_, wait) in self.waits_of(agent) {
+            if wait.ended.is_none() {
+                wait.ended = Some(time);
+            }
+        }
+    }
+
+    /// Let mail interrupt the wait of `agent` set at `time` until `wake`.
+    pub(crate) fn sleep(&mut self, agent: usize, time: u64, wake: u64) {
+        self.waits.insert(
+            (agent, time),
+            Wait {
+                wake,
+                ended: None,
+                interrupt: None,
+            },
+        );
+    }
+
+    /// Interrupt the wait of `agent` with mail received at `time`, returning whether it was waiting.
+    pub(crate) fn interrupt(&mut self, agent: usize, time: u64) -> bool {
+        let Some((key, wait)) = self
+            .waits_of(agent)
+            .find(|(_, wait)| wait.ended.is_none() && wait.wake > time)
+        else {
+            return false;
+        };
+        wait.ended = Some(time);
+        wait.interrupt = Some((time, time));
+        let key = *key;
+        self.pending.push(key);
+        true
+    }
+
+    /// Schedule the early wake-ups of the waits interrupted since the last call, none before `earliest`, returning
+    /// them as `(agent, time)`. A wait whose wake-up would come no later is left to it.
+    pub(crate) fn schedule(&mut self, earliest: u64) -> Vec<(usize, u64)> {
+        let mut wakes = Vec::new();
+        for key in std::mem::take(&mut self.pending) {
+            let Some(wait) = self.waits.get_mut(&key) else {
+                continue;
+            };
+            let Some((recv, _)) = wait.interrupt else {
+                continue;
+            };
+            let early = recv.max(earliest);
+            if early >= wait.wake {
+                wait.interrupt = None;
+                continue;
+            }
+            wait.interrupt = Some((recv, early));
+            wakes.push((key.0, early));
+        }
+        wakes
+    }
+
+    /// How the event waking `agent` at `time` stands with its waits.
+    pub(crate) fn wake(&self, agent: usize, time: u64) -> Wake {
+        let mut wake = Wake::Due;
+        for wait in self
+            .waits
+            .range((agent, 0)..=(agent, u64::MAX))
+            .map(|(_, wait)| wait)
+        {
+            match wait.interrupt {
+                Some((_, early)) if early == time => return Wake::Interrupted,
+                Some(_) if wait.wake == time => wake = Wake::Dropped,
+                _ => {}
+            }
+        }
+        wake
+    }
+
+    /// Undo the waits set, ended and interrupted at or after `time`.
+    pub(crate) fn rollback(&mut self, time: u64) {
+        self.waits.retain(|(_, set), _| *set < time);
+        for wait in self.waits.values_mut() {
+            if wait.interrupt.is_some_and(|(recv, _)| recv >= time) {
+                wait.interrupt = None;
+            }
+            if wait.ended.is_some_and(|ended| ended >= time) {
+                wait.ended = None;
+            }
+        }
+        let waits = &self.waits;
+        self.pending
+            .retain(|key| waits.get(key).is_some_and(|wait| wait.interrupt.is_some()));
+    }
+
+    /// Forget the waits ended before `gvt` whose wake-ups no event still needs told apart.
+    pub(crate) fn prune(&mut self, gvt: u64) {
+        self.waits.retain(|_, wait| {
+            wait.ended.is_none_or(|ended| ended >= gvt)
+                || (wait.interrupt.is_some() && wait.wake >= gvt)
+        });
+    }
+
+    /// Drop the waits of `agent`.
+    pub(crate) fn remove_agent(&mut self, agent: usize) {
+        self.waits.retain(|(waiting, _), _| *waiting != agent);
+        self.pending.retain(|(waiting, _)| *waiting != agent);
+    }
+
+    /// Move the waits of the agents from `start` on into a table of their own, renumbered from 0.
+    pub(crate) fn split_off(&mut self, start: usize) -> InterruptTable {
+        let waits = self
+            .waits
+            .split_off(&(start, 0))
+            .into_iter()
+            .map(|((agent, set), wait)| ((agent - start, set), wait))
+            .collect();
+        let (kept, moved) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|(agent, _)| *agent < start);
+        self.pending = kept;
+        InterruptTable {
+            waits,
+            pending: moved
+                .into_iter()
+                .map(|(agent, set): (usize, u64)| (agent - start, set))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    type Steps = Arc<Mutex<Vec<(u64, bool)>>>;
+
+    // Waits 100 ticks at a time unless woken by mail
+    struct Sleeper {
+        steps: Steps,
+    }
+
+    impl ThreadedAgent<16, u64> for Sleeper {
+        fn step(&mut self, context: &mut PlanetContext<16, u64>, id: usize) -> Event {
+            let step = (context.time, context.interrupted());
+            self.steps.lock().unwrap().push(step);
+            Event::new(context.time, context.time, id, Action::Interruptible(100))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, u64>,
+            _msg: Msg<u64>,
+            _id: usize,
+        ) {
+        }
+    }
+
+    // Mails the sleeper once, two ticks ahead
+    struct Pinger;
+
+    impl ThreadedAgent<16, u64> for Pinger {
+        fn step(&mut self, context: &mut PlanetContext<16, u64>, id: usize) -> Event {
+            let time = context.time;
+            context
+                .send_mail(Msg::new(1, time, time + 2, id, Some(0)), 0)
+                .unwrap();
+            Event::new(time, time, id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, u64>,
+            _msg: Msg<u64>,
+            _id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_mail_cuts_waits_short() {
+        let config = HybridConfig::new(1, 64)
+            .with_time_bounds(150.0, 1.0)
+            .with_optimistic_sync(10, 20)
+            .with_uniform_worlds(16, 2, 16);
+        let mut engine = HybridEngine::<16, 16, 2, u64>::create(config).unwrap();
+        let steps = Steps::default();
+        engine
+            .spawn_agent(
+                0,
+                Box::new(Sleeper {
+                    steps: Arc::clone(&steps),
+                }),
+            )
+            .unwrap();
+        engine.spawn_agent(0, Box::new(Pinger)).unwrap();
+        engine.schedule(0, 0, 1).unwrap();
+        engine.schedule(0, 1, 10).unwrap();
+        let engine = engine.run().unwrap();
+
+        // the wake-up set for 101 gave way to the mail at 12
+        assert_eq!(
+            *steps.lock().unwrap(),
+            [(1, false), (12, true), (112, false)]
+        );
+        // the wait set at 112 runs past the end of the run
+        assert_eq!(engine.planets[0].context.interrupts.open(), 0);
+    }
+
+    #[test]
+    fn test_rollback_reopens_waits() {
+        let mut table = InterruptTable::default();
+        table.sleep(0, 1, 101);
+        assert!(!table.interrupt(1, 5));
+        assert!(table.interrupt(0, 12));
+        assert!(!table.interrupt(0, 13));
+        assert_eq!(table.schedule(13), [(0, 13)]);
+        assert_eq!(table.wake(0, 13), Wake::Interrupted);
+        assert_eq!(table.wake(0, 101), Wake::Dropped);
+        assert_eq!(table.open(), 0);
+
+        table.rollback(12);
+        assert_eq!(table.open(), 1);
+        assert_eq!(table.wake(0, 101), Wake::Due);
+        // an early wake-up no sooner than the one set is no interrupt
+        assert!(table.interrupt(0, 99));
+        assert!(table.schedule(101).is_empty());
+        assert_eq!(table.wake(0, 101), Wake::Due);
+
+        table.prune(100);
+        assert_eq!(table.open(), 0);
+        assert_eq!(table.split_off(0).open(), 0);
+    }
+}
@@ -0,0 +1,1099 @@
+//! Agent traits and execution contexts for both single-threaded and multi-threaded simulations.
+//! Provides `Agent` trait for single-threaded worlds and `ThreadedAgent` for multi-threaded planets,
+//! along with their respective context structures that manage state and inter-agent communication.
+use std::{
+    any::Any,
+    collections::{BTreeMap, BTreeSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use bytemuck::{Pod, Zeroable};
+use mesocarp::{
+    comms::mailbox::{Message, ThreadedMessengerUser},
+    logging::journal::Journal,
+};
+
+#[cfg(feature = "validate-causality")]
+use crate::mt::hybrid::vclock::CausalValidator;
+use crate::{
+    agents::{
+        discovery::{Attributes, Peer, Query},
+        interrupts::InterruptTable,
+        partitions::{Partition, Partitions},
+        resources::Resources,
+        rpc::{Reply, RequestId, Rpc, RpcTable},
+        sandbox::Sandbox,
+        subscriptions::{StateCell, Subscriptions},
+    },
+    analysis::causality::{CausalKind, CausalityLog},
+    extensions::Extensions,
+    mt::hybrid::{
+        barrier::{BarrierRequest, Barriers},
+        batch::Outbox,
+        control::ControlAction,
+        directory::{Directory, Publication},
+        faults::{Fault, FaultInjector, Letter},
+        link::Links,
+        parallel::ParallelStep,
+        parcels::{Parcel, ParcelStore},
+        reduce::{Contribution, Reductions},
+        routing::{AgentHandle, RoutingTable},
+        shared::SharedData,
+        spawn::SpawnRequest,
+    },
+    objects::{
+        AntiMsg, CausalId, DeliveryFailure, Event, EventId, GroupId, Groups, Mail, MailBundle, Msg,
+        Scatter, Transfer,
+    },
+    testing::{Address, MessageLedger},
+    time::{SimTime, TimeScale},
+    AikaError,
+};
+
+pub mod codec;
+pub mod coop;
+pub mod discovery;
+pub mod generators;
+pub mod interrupts;
+pub mod partitions;
+pub mod process;
+pub mod resources;
+pub mod rpc;
+pub mod sandbox;
+pub mod subscriptions;
+pub mod subworld;
+pub mod typed;
+
+pub struct AgentSupport<const SLOTS: usize, T: Message> {
+    pub mailbox: Option<ThreadedMessengerUser<SLOTS, T>>,
+    pub state: Option<Journal>,
+}
+
+impl<const SLOTS: usize, T: Message> AgentSupport<SLOTS, T> {
+    #[allow(clippy::unnecessary_unwrap)]
+    pub fn new(mail: Option<ThreadedMessengerUser<SLOTS, T>>, arena_size: Option<usize>) -> Self {
+        let state = if arena_size.is_some() {
+            let size = arena_size.unwrap();
+            Some(Journal::init(size))
+        } else {
+            None
+        };
+        Self {
+            mailbox: mail,
+            state,
+        }
+    }
+}
+
+pub struct WorldContext<const SLOTS: usize, T: Message> {
+    pub agent_states: Vec<AgentSupport<SLOTS, T>>,
+    pub world_state: Journal,
+    pub time: u64,
+    /// sub-tick offset of the event being processed
+    pub offset: f64,
+    /// `(agent, time, sequence, version)` of the event being processed
+    pub(crate) event_key: (usize, u64, u32, u32),
+    /// multicast group memberships
+    pub groups: Groups,
+    /// `(agent, time, payload)` timers set by the running handler, taken by the `World` once it returns
+    pub(crate) timers: Vec<(usize, u64, T)>,
+    /// `(agent, time)` wake-ups requested by the running handler, committed by the `World` once it returns
+    pub(crate) wakeups: Vec<(usize, u64)>,
+    /// world extensions registered on the `World`
+    pub extensions: Extensions,
+    /// subscriptions to world state cells
+    pub subscriptions: Subscriptions,
+    /// resources the agents acquire and release
+    pub resources: Resources,
+    /// waits of the agents that incoming mail can cut short
+    pub interrupts: InterruptTable,
+    /// whether the running `step()` is the early wake-up of an interrupted wait
+    pub(crate) interrupted: bool,
+    /// attributes the agents published for discovery
+    pub attributes: Attributes,
+    /// `(agent, time, query)` discovery queries made by the running handler, answered by the `World` at `time`
+    pub(crate) queries: Vec<(usize, u64, Query)>,
+    /// length of a tick in seconds
+    pub(crate) timestep: f64,
+}
+
+impl<const SLOTS: usize, T: Message> WorldContext<SLOTS, T> {
+    pub fn new(world_arena_size: usize) -> Self {
+        Self {
+            agent_states: Vec::new(),
+            world_state: Journal::init(world_arena_size),
+            time: 0,
+            offset: 0.0,
+            event_key: (0, 0, 0, 0),
+            groups: Groups::default(),
+            timers: Vec::new(),
+            wakeups: Vec::new(),
+            extensions: Extensions::default(),
+            subscriptions: Subscriptions::default(),
+            resources: Resources::default(),
+            interrupts: InterruptTable::default(),
+            interrupted: false,
+            attributes: Attributes::default(),
+            queries: Vec::new(),
+            timestep: 1.0,
+        }
+    }
+
+    /// Whether the running `step()` was woken early, by mail cutting short an `Action::Interruptible` wait.
+    pub fn interrupted(&self) -> bool {
+        self.interrupted
+    }
+
+    /// The current time as a `SimTime`.
+    pub fn sim_time(&self) -> SimTime {
+        SimTime(self.time)
+    }
+
+    /// Conversions between ticks and seconds with the `World`'s timestep.
+    pub fn scale(&self) -> TimeScale {
+        TimeScale::new(self.timestep)
+    }
+
+    /// Ask the `World` to step `agent_id` at `time`, from within `step()` or `on_timer()`. Wake-ups before the next
+    /// tick or past the terminal time are dropped.
+    pub fn schedule_wakeup(&mut self, agent_id: usize, time: impl Into<SimTime>) {
+        self.wakeups.push((agent_id, time.into().ticks()));
+    }
+
+    /// Publish `value` for attribute `key` on behalf of `agent_id`, replacing what it published for `key` before.
+    pub fn publish(&mut self, agent_id: usize, key: usize, value: u64) {
+        self.attributes.set(0, agent_id, key, value);
+    }
+
+    /// Withdraw attribute `key` of `agent_id`.
+    pub fn unpublish(&mut self, agent_id: usize, key: usize) {
+        self.attributes.remove(0, agent_id, key);
+    }
+
+    /// The agents matching `query` now.
+    pub fn peers(&self, query: &Query) -> Vec<Peer> {
+        self.attributes.peers(query)
+    }
+
+    /// Look up the agents matching `query` on behalf of `agent_id`, handing them to `Agent::on_peers()` the next
+    /// step.
+    pub fn discover(&mut self, agent_id: usize, query: Query) {
+        self.queries
+            .push((agent_id, self.time.saturating_add(1), query));
+    }
+
+    /// Hand `data` back to `Agent::on_timer()` of `agent_id` after `delay` steps, at least one. A delay reaching
+    /// past `u64::MAX` never fires.
+    pub fn set_timer(&mut self, agent_id: usize, delay: u64, data: T) {
+        self.timers
+            .push((agent_id, self.time.saturating_add(delay.max(1)), data));
+    }
+
+    /// Subscribe an agent to multicast `Msg`s sent to `group`.
+    pub fn join_group(&mut self, group: GroupId, agent_id: usize) {
+        self.groups.join(group, agent_id);
+    }
+
+    pub fn leave_group(&mut self, group: GroupId, agent_id: usize) {
+        self.groups.leave(group, agent_id);
+    }
+
+    /// Subscribe an agent to changes of `cell` in the world state, see `write_world_state()`.
+    pub fn subscribe(&mut self, cell: StateCell, agent_id: usize) {
+        self.subscriptions.subscribe(cell, agent_id);
+    }
+
+    pub fn unsubscribe(&mut self, cell: StateCell, agent_id: usize) {
+        self.subscriptions.unsubscribe(cell, agent_id);
+    }
+
+    /// Log `state` as the world state at the current time, notifying the subscribers of every cell it changed.
+    pub fn write_world_state<S: Pod + Zeroable + 'static>(&mut self, state: S) {
+        let before = self.world_state.read_state::<S>().ok().copied();
+        self.subscriptions.changed(
+            before.as_ref().map(bytemuck::bytes_of),
+            bytemuck::bytes_of(&state),
+            self.time,
+        );
+        self.world_state.write(state, self.time, None);
+    }
+
+    /// Id of the event being processed.
+    pub fn event_id(&self) -> EventId {
+        let (agent, time, sequence, version) = self.event_key;
+        EventId::new(0, agent, time, sequence, version)
+    }
+}
+
+/// Shared context local `ThreadedAgents` mutate within a `Planet` thread
+pub struct PlanetContext<const INTER_SLOTS: usize, MessageType: Pod + Zeroable + Clone> {
+    /// state of each `ThreadedAgent` on the `Planet`
+    pub agent_states: Vec<Journal>,
+    /// `Planet` global state
+    pub world_state: Journal,
+    /// current time
+    pub time: u64,
+    /// sub-tick offset of the event being processed
+    pub offset: f64,
+    /// `(agent, time, sequence, version)` of the event being processed
+    pub(crate) event_key: (usize, u64, u32, u32),
+    /// world ID in the interplanetary messaging system
+    pub world_id: usize,
+    /// Counter for unprocessed messages in the system
+    pub counter: Arc<AtomicUsize>,
+    /// interplanetary messaging system user interface
+    pub user: ThreadedMessengerUser<INTER_SLOTS, MailBundle<MessageType>>,
+    /// all anti messages generated by this `Planet`
+    pub anti_msgs: Journal,
+    /// `(agent, time)` wake-ups requested outside of `step()`, committed by the `Planet` after each tick's mail
+    pub(crate) wakeups: Vec<(usize, u64, CausalId)>,
+    /// timers set by the running handler, committed by the `Planet` alongside wake-ups
+    pub(crate) timers: Vec<Msg<MessageType>>,
+    /// per-pair message accounting, if enabled on the `Planet`
+    pub(crate) ledger: Option<MessageLedger>,
+    /// multicast group memberships
+    pub groups: Groups,
+    /// the `Galaxy`'s routing table, if the `Planet` belongs to a `HybridEngine`
+    pub(crate) routes: Option<Arc<Mutex<RoutingTable>>>,
+    /// bandwidth models of the outgoing interplanetary links, if configured
+    pub(crate) links: Option<Links>,
+    /// drops, duplicates and delays of outgoing interplanetary mail, if configured
+    pub(crate) faults: Option<FaultInjector>,
+    /// worlds the routes to have been removed
+    pub(crate) closed_routes: BTreeSet<usize>,
+    /// read-only data shared by every `Planet` of the run, if any
+    pub(crate) shared: Option<SharedData>,
+    /// payloads of the `Parcel`s in flight, if heap payloads are enabled
+    pub(crate) parcels: Option<ParcelStore>,
+    /// global aggregates of the run, if the `Planet` belongs to a `HybridEngine`
+    pub(crate) reductions: Option<Arc<Reductions>>,
+    /// contributions to the aggregates not yet handed to the `Galaxy`
+    pub(crate) contributions: Vec<Contribution>,
+    /// attributes published by the agents of the run, if the `Planet` belongs to a `HybridEngine`
+    pub(crate) directory: Option<Arc<Directory>>,
+    /// publications not yet handed to the `Galaxy`
+    pub(crate) publications: Vec<Publication>,
+    /// discovery queries made, until GVT passes their answer
+    pub(crate) queries: BTreeMap<RequestId, Query>,
+    /// global barriers of the run, if the `Planet` belongs to a `HybridEngine`
+    pub(crate) barriers: Option<Arc<Barriers>>,
+    /// barrier requests not yet handed to the `Galaxy`
+    pub(crate) barrier_requests: Vec<BarrierRequest>,
+    /// outgoing mail waiting to be bundled, if batching is enabled
+    pub(crate) outbox: Option<Outbox<MessageType>>,
+    /// transfers written into the interplanetary messenger
+    pub(crate) bundles_sent: u64,
+    /// `Msg`s sent so far this tick
+    pub(crate) sends: u32,
+    /// number of agent templates registered on the `Planet`
+    pub(crate) templates: usize,
+    /// index the next spawned agent will get
+    pub(crate) next_agent: usize,
+    /// spawns requested by the running handler
+    pub(crate) spawns: Vec<SpawnRequest<MessageType>>,
+    /// world extensions registered on the `Planet`
+    pub extensions: Extensions,
+    /// subscriptions to world state cells
+    pub subscriptions: Subscriptions,
+    /// requests made by agents of the `Planet` that wait for, or recently got, their outcome
+    pub rpc: RpcTable,
+    /// resources the agents acquire and release
+    pub resources: Resources,
+    /// waits of the agents that incoming mail can cut short
+    pub interrupts: InterruptTable,
+    /// whether the running `step()` is the early wake-up of an interrupted wait
+    pub(crate) interrupted: bool,
+    /// state partitions shared by groups of agents
+    pub partitions: Partitions,
+    /// capabilities of the sandboxed agents and the calls they were refused
+    pub(crate) sandbox: Sandbox,
+    /// ids and causes of the events scheduled and `Msg`s sent, if kept
+    pub(crate) causality: Option<CausalityLog>,
+    /// `CausalId` of the event or `Msg` being handled
+    pub(crate) cause: CausalId,
+    /// length of a tick in seconds
+    pub(crate) timestep: f64,
+    /// vector clock of the `Planet` and the mail it read, see `vclock`
+    #[cfg(feature = "validate-causality")]
+    pub(crate) causal: CausalValidator,
+}
+
+impl<const INTER_SLOTS: usize, MessageType: Pod + Zeroable + Clone>
+    PlanetContext<INTER_SLOTS, MessageType>
+{
+    /// Spawn a new context environment for a `Planet`.
+    pub fn new(
+        world_arena_size: usize,
+        anti_msg_arena_size: usize,
+        user: ThreadedMessengerUser<INTER_SLOTS, MailBundle<MessageType>>,
+        world_id: usize,
+        counter: Arc<AtomicUsize>,
+    ) -> Self {
+        Self {
+            agent_states: Vec::new(),
+            world_state: Journal::init(world_arena_size),
+            time: 0,
+            offset: 0.0,
+            event_key: (0, 0, 0, 0),
+            user,
+            world_id,
+            counter,
+            anti_msgs: Journal::init(anti_msg_arena_size),
+            wakeups: Vec::new(),
+            timers: Vec::new(),
+            ledger: None,
+            groups: Groups::default(),
+            routes: None,
+            links: None,
+            faults: None,
+            closed_routes: BTreeSet::new(),
+            shared: None,
+            parcels: None,
+            reductions: None,
+            contributions: Vec::new(),
+            directory: None,
+            publications: Vec::new(),
+            queries: BTreeMap::new(),
+            barriers: None,
+            barrier_requests: Vec::new(),
+            outbox: None,
+            bundles_sent: 0,
+            sends: 0,
+            templates: 0,
+            next_agent: 0,
+            spawns: Vec::new(),
+            extensions: Extensions::default(),
+            subscriptions: Subscriptions::default(),
+            rpc: RpcTable::default(),
+            resources: Resources::default(),
+            interrupts: InterruptTable::default(),
+            interrupted: false,
+            partitions: Partitions::default(),
+            sandbox: Sandbox::default(),
+            causality: None,
+            cause: CausalId::NONE,
+            timestep: 1.0,
+            #[cfg(feature = "validate-causality")]
+            causal: CausalValidator::new(world_id, Default::default()),
+        }
+    }
+
+    /// Whether the running `step()` was woken early, by mail cutting short an `Action::Interruptible` wait.
+    pub fn interrupted(&self) -> bool {
+        self.interrupted
+    }
+
+    /// The current time as a `SimTime`.
+    pub fn sim_time(&self) -> SimTime {
+        SimTime(self.time)
+    }
+
+    /// Conversions between ticks and seconds with the `Planet`'s timestep.
+    pub fn scale(&self) -> TimeScale {
+        TimeScale::new(self.timestep)
+    }
+
+    /// Subscribe an agent to changes of `cell` in the world state, see `write_world_state()`.
+    pub fn subscribe(&mut self, cell: StateCell, agent_id: usize) {
+        self.subscriptions.subscribe(cell, agent_id);
+    }
+
+    pub fn unsubscribe(&mut self, cell: StateCell, agent_id: usize) {
+        self.subscriptions.unsubscribe(cell, agent_id);
+    }
+
+    /// Log `state` as the world state at the current time, notifying the subscribers of every cell it changed. Does
+    /// nothing when called by a sandboxed agent without the capability.
+    pub fn write_world_state<S: Pod + Zeroable + 'static>(&mut self, state: S) {
+        if self
+            .sandbox
+            .check_world_write(self.world_id, self.time)
+            .is_err()
+        {
+            return;
+        }
+        let before = self.world_state.read_state::<S>().ok().copied();
+        self.subscriptions.changed(
+            before.as_ref().map(bytemuck::bytes_of),
+            bytemuck::bytes_of(&state),
+            self.time,
+        );
+        self.world_state.write(state, self.time, None);
+    }
+
+    /// Id of the event being processed, stable across rollbacks.
+    pub fn event_id(&self) -> EventId {
+        let (agent, time, sequence, version) = self.event_key;
+        EventId::new(self.world_id, agent, time, sequence, version)
+    }
+
+    /// Contribute `value` to the global aggregate `key`, combined across planets as configured with
+    /// `HybridConfig::with_reduction()`. Contributions are undone by rollbacks and committed at GVT checkpoints.
+    pub fn reduce(&mut self, key: usize, value: f64) {
+        self.contributions.push(Contribution {
+            time: self.time,
+            key,
+            value,
+        });
+    }
+
+    /// The aggregate of `key` over every contribution made before the last GVT checkpoint.
+    pub fn aggregate(&self, key: usize) -> Option<f64> {
+        self.reductions.as_ref()?.value(key)
+    }
+
+    /// Publish `value` for attribute `key` on behalf of `agent_id`, replacing what it published for `key` before.
+    /// Publications are undone by rollbacks and committed at GVT checkpoints, see `agents::discovery`.
+    pub fn publish(&mut self, agent_id: usize, key: usize, value: u64) {
+        self.push_publication(agent_id, key, Some(value));
+    }
+
+    /// Withdraw attribute `key` of `agent_id`, once committed like a publication.
+    pub fn unpublish(&mut self, agent_id: usize, key: usize) {
+        self.push_publication(agent_id, key, None);
+    }
+
+    fn push_publication(&mut self, agent: usize, key: usize, value: Option<u64>) {
+        self.publications.push(Publication {
+            time: self.time,
+            world: self.world_id,
+            agent,
+            key,
+            value,
+        });
+    }
+
+    /// The agents matching `query` among the attributes published before the last GVT checkpoint.
+    pub fn peers(&self, query: &Query) -> Vec<Peer> {
+        match &self.directory {
+            Some(directory) => directory.peers(query, self.routes.as_ref()),
+            None => Vec::new(),
+        }
+    }
+
+    /// Look up the agents matching `query` on behalf of `agent_id`, handing them to `ThreadedAgent::on_peers()` the
+    /// next step under the returned id. The query is withdrawn if the `Planet` rolls back past this call.
+    pub fn discover(&mut self, agent_id: usize, query: Query) -> RequestId {
+        let id = self.rpc.next_id(self.world_id, agent_id, self.time);
+        self.rpc.open_request(id);
+        self.queries.insert(id, query);
+        self.push_timer(agent_id, 1, MessageType::zeroed(), Some(Rpc::Discovery(id)));
+        id
+    }
+
+    /// Ask, on behalf of `agent_id`, that every `Planet` waits at `time` until all of them reach it, running the
+    /// callbacks registered with `HybridEngine::on_barrier()` in between. `time` must lie further ahead than the
+    /// throttle horizon and before the terminal time. The request is withdrawn if the `Planet` rolls back past it.
+    pub fn request_barrier(&mut self, agent_id: usize, time: u64) -> Result<(), AikaError> {
+        let barriers = self.barriers.as_ref().ok_or_else(|| {
+            AikaError::ConfigError("barriers need a planet of a `HybridEngine`".to_string())
+        })?;
+        barriers.check(self.time, time)?;
+        self.barrier_requests.push(BarrierRequest {
+            requested: self.time,
+            time,
+            world: self.world_id,
+            agent: agent_id,
+        });
+        Ok(())
+    }
+
+    /// The run's shared read-only data, if it was set and is a `T`. See `HybridEngine::with_shared_data()`.
+    pub fn shared_data<T: Any>(&self) -> Option<&T> {
+        self.shared.as_ref()?.get::<T>()
+    }
+
+    /// Initialize a `ThreadedAgent`'s state `Journal`.
+    pub fn init_agent_contexts(&mut self, state_arena_size: usize) {
+        self.agent_states.push(Journal::init(state_arena_size));
+    }
+    /// Subscribe an agent to multicast `Msg`s sent to `group`.
+    pub fn join_group(&mut self, group: GroupId, agent_id: usize) {
+        self.groups.join(group, agent_id);
+    }
+
+    pub fn leave_group(&mut self, group: GroupId, agent_id: usize) {
+        self.groups.leave(group, agent_id);
+    }
+
+    /// Create a partition of `size` zeroed values shared by the agents that join it, see `partitions`.
+    pub fn create_shared_partition<T: Pod + Zeroable + Send>(
+        &mut self,
+        size: usize,
+    ) -> Partition<T> {
+        self.partitions.create(size)
+    }
+
+    /// Let an agent read and write `partition`. Joining twice does nothing.
+    pub fn join_partition<T>(&mut self, partition: Partition<T>, agent_id: usize) {
+        self.partitions.join(partition, agent_id);
+    }
+
+    pub fn leave_partition<T>(&mut self, partition: Partition<T>, agent_id: usize) {
+        self.partitions.leave(partition, agent_id);
+    }
+
+    /// The values of `partition` as an agent that joined it sees them, with every write before this one applied.
+    pub fn read_partition<T: 'static>(
+        &self,
+        partition: Partition<T>,
+        agent_id: usize,
+    ) -> Result<&[T], AikaError> {
+        self.partitions.read(partition, agent_id)
+    }
+
+    /// Set value `index` of `partition` at the current time, on behalf of an agent that joined it. Rollbacks past
+    /// the current time restore the value it replaced.
+    pub fn write_partition<T: 'static>(
+        &mut self,
+        partition: Partition<T>,
+        agent_id: usize,
+        index: usize,
+        value: T,
+    ) -> Result<(), AikaError> {
+        self.partitions
+            .write(partition, agent_id, index, value, self.time)
+    }
+
+    /// Ask the `Planet` to step a `ThreadedAgent` at `time`, e.g. from within `read_message()`. Does nothing when a
+    /// sandboxed agent asks for an agent outside its `TriggerScope`.
+    pub fn schedule_wakeup(&mut self, agent_id: usize, time: impl Into<SimTime>) {
+        let time = time.into().ticks();
+        if self
+            .sandbox
+            .check_wakeup(self.world_id, agent_id, self.time, &self.groups)
+            .is_err()
+        {
+            return;
+        }
+        self.wakeups.push((agent_id, time, self.cause));
+    }
+
+    /// Hand `data` back to `ThreadedAgent::on_timer()` of `agent_id` after `delay` steps, at least one. The timer is
+    /// cancelled if the `Planet` rolls back past this call. A delay reaching past `u64::MAX` never fires.
+    pub fn set_timer(&mut self, agent_id: usize, delay: u64, data: MessageType) {
+        self.push_timer(agent_id, delay, data, None);
+    }
+
+    fn push_timer(&mut self, agent_id: usize, delay: u64, data: MessageType, rpc: Option<Rpc>) {
+        let recv = self.time.saturating_add(delay.max(1));
+        let mut timer = Msg::new(data, self.time, recv, agent_id, Some(agent_id));
+        timer.timer = true;
+        timer.rpc = rpc;
+        timer.from_world = self.world_id;
+        self.trace_msg(&mut timer);
+        let mut anti = AntiMsg::new(timer.sent, timer.recv, agent_id, Some(agent_id));
+        anti.id = timer.id;
+        let stays: Mail<MessageType> =
+            Mail::write_letter(Transfer::AntiMsg(anti), self.world_id, Some(self.world_id));
+        self.anti_msgs.write(stays, self.time, None);
+        self.timers.push(timer);
+    }
+
+    /// Send `data` from `agent_id` as a request to the `(world, agent)` `target`, arriving at the next step. The
+    /// reply, or `Reply::TimedOut` if none arrived within `timeout` steps, is handed to `ThreadedAgent::on_reply()`
+    /// under the returned id. The request is withdrawn if the `Planet` rolls back past this call.
+    pub fn request(
+        &mut self,
+        agent_id: usize,
+        target: (usize, usize),
+        data: MessageType,
+        timeout: u64,
+    ) -> Result<RequestId, AikaError> {
+        let id = self.rpc.next_id(self.world_id, agent_id, self.time);
+        let (world, agent) = target;
+        let mut msg = Msg::new(data, self.time, self.time + 1, agent_id, Some(agent));
+        msg.rpc = Some(Rpc::Request(id));
+        self.send_mail(msg, world)?;
+        self.rpc.open_request(id);
+        self.push_timer(
+            agent_id,
+            timeout,
+            MessageType::zeroed(),
+            Some(Rpc::Timeout(id)),
+        );
+        Ok(id)
+    }
+
+    /// Answer `request`, a `Msg` sent with `request()`, from `agent_id` with `data`, arriving at the next step.
+    pub fn reply(
+        &mut self,
+        agent_id: usize,
+        request: &Msg<MessageType>,
+        data: MessageType,
+    ) -> Result<(), AikaError> {
+        let Some(Rpc::Request(id)) = request.rpc else {
+            return Err(AikaError::NotARequest(request.from, request.from_world));
+        };
+        let mut msg = Msg::new(data, self.time, self.time + 1, agent_id, Some(id.agent));
+        msg.rpc = Some(Rpc::Reply(id));
+        self.send_mail(msg, id.world)
+    }
+
+    /// Create an agent from a registered template once the running handler returns, returning its index on this
+    /// `Planet`. The agent is removed again if the `Planet` rolls back past this call.
+    pub fn spawn_from_template(
+        &mut self,
+        template_id: usize,
+        init_payload: MessageType,
+    ) -> Result<usize, AikaError> {
+        if template_id >= self.templates {
+            return Err(AikaError::InvalidTemplate(template_id));
+        }
+        let agent = self.next_agent;
+        self.next_agent += 1;
+        self.spawns.push(SpawnRequest {
+            template: template_id,
+            agent,
+            init: init_payload,
+        });
+        Ok(agent)
+    }
+
+    /// Send a `Msg` to another `Planet`. A direct `Msg` follows its recipient if it has been moved. Fails with
+    /// `AikaError::CapabilityDenied` past the send limit of a sandboxed agent.
+    pub fn send_mail(
+        &mut self,
+        mut msg: Msg<MessageType>,
+        mut to_world: usize,
+    ) -> Result<(), AikaError> {
+        if let (Some(routes), Some(to)) = (&self.routes, msg.to) {
+            let routes = routes.lock().map_err(|_| AikaError::PoisonedLock)?;
+            let (world, agent) = routes.resolve(to_world, to);
+            to_world = world;
+            msg.to = Some(agent);
+        }
+        if self.closed_routes.contains(&to_world) {
+            return Err(AikaError::NoRoute(self.world_id, to_world));
+        }
+        self.sandbox.check_send(self.world_id, self.time)?;
+        msg.from_world = self.world_id;
+        msg.seq = self.sends;
+        self.sends += 1;
+        self.trace_msg(&mut msg);
+        if let Some(links) = &mut self.links {
+            msg.recv = links.transmit(to_world, self.time, msg.sent, msg.recv);
+        }
+        let Some(copies) = self.inject_fault(&mut msg, to_world) else {
+            return self.settle_parcel(&msg, None);
+        };
+        self.settle_parcel(&msg, Some(msg.recv))?;
+        let mut anti = AntiMsg::new(msg.sent, msg.recv, msg.from, msg.to);
+        anti.id = msg.id;
+        for _ in 0..copies {
+            let outgoing = Mail::write_letter(Transfer::Msg(msg), self.world_id, Some(to_world));
+            self.post(outgoing)?;
+        }
+        if let Some(ledger) = &mut self.ledger {
+            ledger.record_sent(
+                Address::new(self.world_id, Some(anti.from)),
+                Address::new(to_world, anti.to),
+            );
+        }
+        let stays: Mail<MessageType> =
+            Mail::write_letter(Transfer::AntiMsg(anti), self.world_id, Some(to_world));
+        self.anti_msgs.write(stays, self.time, None);
+        Ok(())
+    }
+
+    /// Send a `Msg` to every agent on every `Planet`, this one included. The `Galaxy` hands each planet a copy of
+    /// its own, and a rollback past the send cancels every copy. Bandwidth models, fault injection and closed routes
+    /// only apply to mail for a single planet and are skipped.
+    pub fn broadcast_mail(&mut self, mut msg: Msg<MessageType>) -> Result<(), AikaError> {
+        self.sandbox.check_send(self.world_id, self.time)?;
+        msg.to = None;
+        msg.group = None;
+        msg.from_world = self.world_id;
+        msg.seq = self.sends;
+        self.sends += 1;
+        self.trace_msg(&mut msg);
+        self.settle_parcel(&msg, Some(msg.recv))?;
+        let mut anti = AntiMsg::new(msg.sent, msg.recv, msg.from, None);
+        anti.id = msg.id;
+        self.post(Mail::write_letter(Transfer::Msg(msg), self.world_id, None))?;
+        let stays: Mail<MessageType> =
+            Mail::write_letter(Transfer::AntiMsg(anti), self.world_id, None);
+        self.anti_msgs.write(stays, self.time, None);
+        Ok(())
+    }
+
+    /// Send `data` from `agent_id` to every `(world, agent)` in `recipients`, arriving at `recv`. Each destination
+    /// `Planet` gets the payload once, with the indices of its recipients, in a single transfer that counts as one
+    /// letter in flight. Recipients that have been moved are followed, and nothing is sent if any route is closed or
+    /// a sandboxed agent is past its send limit.
+    pub fn send_many(
+        &mut self,
+        agent_id: usize,
+        recipients: &[(usize, usize)],
+        data: MessageType,
+        recv: u64,
+    ) -> Result<(), AikaError> {
+        let mut by_world = BTreeMap::<usize, Vec<usize>>::new();
+        match &self.routes {
+            Some(routes) => {
+                let routes = routes.lock().map_err(|_| AikaError::PoisonedLock)?;
+                for &(world, agent) in recipients {
+                    let (world, agent) = routes.resolve(world, agent);
+                    by_world.entry(world).or_default().push(agent);
+                }
+            }
+            None => {
+                for &(world, agent) in recipients {
+                    by_world.entry(world).or_default().push(agent);
+                }
+            }
+        }
+        if let Some(&world) = by_world
+            .keys()
+            .find(|world| self.closed_routes.contains(world))
+        {
+            return Err(AikaError::NoRoute(self.world_id, world));
+        }
+        self.sandbox.check_send(self.world_id, self.time)?;
+        let mut msg = Msg::new(data, self.time, recv, agent_id, None);
+        msg.from_world = self.world_id;
+        msg.seq = self.sends;
+        self.sends += 1;
+        self.trace_msg(&mut msg);
+        for (to_world, to) in by_world {
+            let mut msg = msg;
+            if let Some(links) = &mut self.links {
+                msg.recv = links.transmit(to_world, self.time, msg.sent, msg.recv);
+            }
+            let Some(copies) = self.inject_fault(&mut msg, to_world) else {
+                continue;
+            };
+            self.settle_parcel(&msg, Some(msg.recv))?;
+            for &agent in &to {
+                if let Some(ledger) = &mut self.ledger {
+                    ledger.record_sent(
+                        Address::new(self.world_id, Some(agent_id)),
+                        Address::new(to_world, Some(agent)),
+                    );
+                }
+                let mut anti = AntiMsg::new(msg.sent, msg.recv, agent_id, Some(agent));
+                anti.id = msg.id;
+                let stays: Mail<MessageType> =
+                    Mail::write_letter(Transfer::AntiMsg(anti), self.world_id, Some(to_world));
+                self.anti_msgs.write(stays, self.time, None);
+            }
+            let scatter = Scatter {
+                msg,
+                to,
+                #[cfg(feature = "validate-causality")]
+                clock: self.causal.stamp(self.time)?,
+            };
+            for _ in 1..copies {
+                let bundle = MailBundle::scatter(scatter.clone(), self.world_id, to_world);
+                self.dispatch(bundle, false)?;
+            }
+            let bundle = MailBundle::scatter(scatter, self.world_id, to_world);
+            self.dispatch(bundle, false)?;
+        }
+        self.settle_parcel(&msg, None)
+    }
+
+    /// Give an outgoing `msg` its `CausalId`, if ids are kept.
+    fn trace_msg(&mut self, msg: &mut Msg<MessageType>) {
+        if let Some(log) = &mut self.causality {
+            msg.id = log.record(CausalKind::Msg, self.cause, msg.from, msg.sent);
+        }
+    }
+
+    /// Apply the configured fault to an outgoing `msg`, moving its receive time if it is delayed. Returns how many
+    /// copies to send, or `None` if it is dropped.
+    fn inject_fault(&mut self, msg: &mut Msg<MessageType>, to_world: usize) -> Option<usize> {
+        let Some(faults) = &mut self.faults else {
+            return Some(1);
+        };
+        let letter = Letter {
+            world: self.world_id,
+            from: msg.from,
+            sent: msg.sent,
+            seq: msg.seq,
+            to_world,
+        };
+        match faults.inject(letter, msg.recv, self.time) {
+            Fault::Deliver { copies, recv } => {
+                msg.recv = recv;
+                Some(copies)
+            }
+            Fault::Drop => None,
+        }
+    }
+
+    /// Note when the `Parcel` an outgoing `msg` carries, if any, is due, see `ParcelStore::settle()`.
+    fn settle_parcel(&self, msg: &Msg<MessageType>, recv: Option<u64>) -> Result<(), AikaError> {
+        match (
+            &self.parcels,
+            (&msg.data as &dyn Any).downcast_ref::<Parcel>(),
+        ) {
+            (Some(parcels), Some(parcel)) => parcels.settle(parcel, recv),
+            _ => Ok(()),
+        }
+    }
+
+    /// Hand a letter to the interplanetary messenger, or to the outbox if batching is enabled. A batched letter counts
+    /// as in flight while it waits, and a bad destination only fails once its batch is sent.
+    pub(crate) fn post(&mut self, mail: Mail<MessageType>) -> Result<(), AikaError> {
+        #[cfg(feature = "validate-causality")]
+        let mail = Mail {
+            clock: self.causal.stamp(self.time)?,
+            ..mail
+        };
+        let Some(outbox) = &mut self.outbox else {
+            return self.dispatch(MailBundle::single(mail), false);
+        };
+        self.counter.fetch_add(1, Ordering::SeqCst);
+        match outbox.push(mail, self.time) {
+            Some(bundle) => self.dispatch(bundle, true),
+            None => Ok(()),
+        }
+    }
+
+    /// Send the batches that have waited long enough by local time `now`.
+    pub(crate) fn flush_due(&mut self, now: u64) -> Result<(), AikaError> {
+        let bundles = self
+            .outbox
+            .as_mut()
+            .map(|outbox| outbox.due(now))
+            .unwrap_or_default();
+        bundles
+            .into_iter()
+            .try_for_each(|bundle| self.dispatch(bundle, true))
+    }
+
+    /// Send every waiting batch.
+    pub(crate) fn flush_mail(&mut self) -> Result<(), AikaError> {
+        let bundles = self
+            .outbox
+            .as_mut()
+            .filter(|outbox| !outbox.is_empty())
+            .map(|outbox| outbox.drain())
+            .unwrap_or_default();
+        bundles
+            .into_iter()
+            .try_for_each(|bundle| self.dispatch(bundle, true))
+    }
+
+    /// Write a bundle into the messenger, counting its letters as in flight unless they were counted when batched.
+    /// Letters that can't be sent are no longer counted.
+    fn dispatch(
+        &mut self,
+        bundle: MailBundle<MessageType>,
+        counted: bool,
+    ) -> Result<(), AikaError> {
+        let letters = bundle.in_flight();
+        if !counted {
+            self.counter.fetch_add(letters, Ordering::SeqCst);
+        }
+        if let Err(err) = self.user.send(bundle) {
+            self.counter.fetch_sub(letters, Ordering::SeqCst);
+            return Err(err.into());
+        }
+        self.bundles_sent += 1;
+        Ok(())
+    }
+
+    /// Send a direct `Msg` to the agent behind `handle`, wherever it currently lives.
+    pub fn send_mail_to(
+        &mut self,
+        handle: AgentHandle,
+        mut msg: Msg<MessageType>,
+    ) -> Result<(), AikaError> {
+        let (world, agent) = self
+            .routes
+            .as_ref()
+            .and_then(|routes| routes.lock().ok()?.locate(handle))
+            .ok_or(AikaError::InvalidAgentHandle(handle.0))?;
+        msg.to = Some(agent);
+        self.send_mail(msg, world)
+    }
+}
+
+impl<const INTER_SLOTS: usize> PlanetContext<INTER_SLOTS, Parcel> {
+    fn parcel_store(&self) -> Result<ParcelStore, AikaError> {
+        self.parcels.clone().ok_or_else(|| {
+            AikaError::ConfigError(
+                "heap payloads need `HybridConfig::with_heap_payloads()`".to_string(),
+            )
+        })
+    }
+
+    /// Send `payload` in `msg`, whose data is replaced by the `Parcel` naming it, like `send_mail()` does. The
+    /// payload stays on the heap, shared by every recipient, until GVT passes the receive time.
+    pub fn send_parcel<P: Any + Send + Sync>(
+        &mut self,
+        payload: P,
+        mut msg: Msg<Parcel>,
+        to_world: usize,
+    ) -> Result<(), AikaError> {
+        let parcels = self.parcel_store()?;
+        msg.data = Parcel {
+            world: self.world_id as u64,
+            sent: self.time,
+            seq: self.sends as u64,
+        };
+        parcels.put(msg.data, payload)?;
+        let sent = self.send_mail(msg, to_world);
+        if sent.is_err() {
+            parcels.settle(&msg.data, None)?;
+        }
+        sent
+    }
+
+    /// The payload of a `Msg` sent with `send_parcel()`, if it is a `P`.
+    pub fn open_parcel<P: Any + Send + Sync>(
+        &self,
+        msg: &Msg<Parcel>,
+    ) -> Result<Arc<P>, AikaError> {
+        self.parcel_store()?.open(&msg.data)
+    }
+}
+
+/// Access to an agent as `Any`, for downcasting a boxed agent back to its concrete type after a run. Implemented for
+/// every `'static` type, so agents get it for free. Call it on the agent rather than its `Box`, e.g.
+/// `agent.as_ref().as_any()`, or the `Box` itself is what gets downcast.
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// An `Agent` is an independent logical process that can interact with a single threaded `st::World`
+pub trait Agent<const SLOTS: usize, T: Message>: AsAny {
+    fn step(&mut self, context: &mut WorldContext<SLOTS, T>, agent_id: usize) -> Event;
+    /// Behavior version mixed into this agent's `EventId`s. Bump it when the agent's logic changes.
+    fn version(&self) -> u32 {
+        0
+    }
+    /// Receive the payload of a timer set with `WorldContext::set_timer()`. Ignored by default.
+    fn on_timer(&mut self, _context: &mut WorldContext<SLOTS, T>, _data: T, _agent_id: usize) {}
+    /// Receive a `Msg` at its receive time, on a `World` with `World::enable_push_delivery()`. Ignored by default.
+    fn on_message(&mut self, _context: &mut WorldContext<SLOTS, T>, _msg: T, _agent_id: usize) {}
+    /// Hear that a subscribed `cell` of the world state changed. Ignored by default.
+    fn on_world_change(
+        &mut self,
+        _context: &mut WorldContext<SLOTS, T>,
+        _cell: StateCell,
+        _agent_id: usize,
+    ) {
+    }
+    /// Receive the answer to a query this agent made with `WorldContext::discover()`. Ignored by default.
+    fn on_peers(
+        &mut self,
+        _context: &mut WorldContext<SLOTS, T>,
+        _query: Query,
+        _peers: Vec<Peer>,
+        _agent_id: usize,
+    ) {
+    }
+    /// Shift the ids of the agents this agent addresses by `offset`, when its `World` is merged into another with
+    /// `World::merge()`. Ignored by default.
+    fn renumber(&mut self, _offset: usize) {}
+}
+
+/// A `ThreadedAgent` is an independent logical process that belongs to a `Planet` and can schedule events,
+/// send messages, and interact with that `Planet`'s `PlanetContext`.
+pub trait ThreadedAgent<const SLOTS: usize, MessageType: Pod + Zeroable + Clone>: AsAny {
+    fn step(&mut self, context: &mut PlanetContext<SLOTS, MessageType>, agent_id: usize) -> Event;
+    /// Behavior version mixed into this agent's `EventId`s. Bump it when the agent's logic changes.
+    fn version(&self) -> u32 {
+        0
+    }
+    fn read_message(
+        &mut self,
+        context: &mut PlanetContext<SLOTS, MessageType>,
+        msg: Msg<MessageType>,
+        agent_id: usize,
+    );
+    /// Receive every broadcast delivered to this agent in one tick, sorted by sub-tick offset, then sender, then sent time.
+    /// Defaults to calling `read_message()` once per `Msg`.
+    fn read_messages(
+        &mut self,
+        context: &mut PlanetContext<SLOTS, MessageType>,
+        msgs: Vec<Msg<MessageType>>,
+        agent_id: usize,
+    ) {
+        for msg in msgs {
+            self.read_message(context, msg, agent_id);
+        }
+    }
+    /// Receive the payload of a timer set with `PlanetContext::set_timer()`. Defaults to calling `read_message()` with
+    /// a `Msg` from the agent to itself.
+    fn on_timer(
+        &mut self,
+        context: &mut PlanetContext<SLOTS, MessageType>,
+        data: MessageType,
+        agent_id: usize,
+    ) {
+        let msg = Msg::new(data, context.time, context.time, agent_id, Some(agent_id));
+        self.read_message(context, msg, agent_id);
+    }
+    /// The part of this agent's step a `Planet` with parallel steps may run on a worker thread ahead of `step()`, see
+    /// `mt::hybrid::parallel`. `None` by default, stepping the agent wholly on the `Planet` thread.
+    fn parallel(&mut self) -> Option<&mut dyn ParallelStep> {
+        None
+    }
+    /// Receive the answer to query `id` this agent made with `PlanetContext::discover()`. Ignored by default.
+    fn on_peers(
+        &mut self,
+        _context: &mut PlanetContext<SLOTS, MessageType>,
+        _id: RequestId,
+        _query: Query,
+        _peers: Vec<Peer>,
+        _agent_id: usize,
+    ) {
+    }
+    /// Receive the outcome of request `id` this agent made with `PlanetContext::request()`. Ignored by default.
+    fn on_reply(
+        &mut self,
+        _context: &mut PlanetContext<SLOTS, MessageType>,
+        _id: RequestId,
+        _reply: Reply<MessageType>,
+        _agent_id: usize,
+    ) {
+    }
+    /// Receive a `Msg` this agent sent that was dropped because the inbox of its destination `Planet` was full. The
+    /// `Msg` is handed back as it was sent, so it can be sent again. Ignored by default.
+    fn on_send_failed(
+        &mut self,
+        _context: &mut PlanetContext<SLOTS, MessageType>,
+        _failure: DeliveryFailure<MessageType>,
+        _agent_id: usize,
+    ) {
+    }
+    /// Hear that a subscribed `cell` of the world state changed. Ignored by default.
+    fn on_world_change(
+        &mut self,
+        _context: &mut PlanetContext<SLOTS, MessageType>,
+        _cell: StateCell,
+        _agent_id: usize,
+    ) {
+    }
+    /// Encode this agent's state for a consistent cut. Empty by default.
+    fn snapshot(&self, _context: &PlanetContext<SLOTS, MessageType>, _agent_id: usize) -> Vec<u8> {
+        Vec::new()
+    }
+    /// Receive a `ControlAction::Parameter` update or an applied `ControlAction::Rewire` from the control plane.
+    /// Ignored by default.
+    fn on_control(
+        &mut self,
+        _context: &mut PlanetContext<SLOTS, MessageType>,
+        _action: ControlAction,
+        _agent_id: usize,
+    ) {
+    }
+}
@@ -0,0 +1,395 @@
+//! State partitions that a group of agents on one `Planet` share, such as the order book of an exchange its traders
+//! all trade on. A partition is a fixed number of `Pod` values, zeroed at creation, that the agents who joined it read
+//! and write through their `PlanetContext` without a round trip of messages. Every write logs the value it replaced
+//! by time, so a `Planet` rolls the partition back with everything else and forgets the writes GVT passed. Creating a
+//! partition and joining it, like group memberships, are not undone by rollbacks. Partitions belong to the `Planet`
+//! they were created on: agents moved away by a split or a migration leave them.
+use std::{
+    any::Any,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    marker::PhantomData,
+};
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::AikaError;
+
+/// Handle of a partition of values of `T`, valid on the `Planet` it was created on.
+pub struct Partition<T> {
+    id: usize,
+    len: usize,
+    _values: PhantomData<fn() -> T>,
+}
+
+impl<T> Partition<T> {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Number of values in the partition.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T> Clone for Partition<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Partition<T> {}
+
+impl<T> PartialEq for Partition<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T> Eq for Partition<T> {}
+
+impl<T> fmt::Debug for Partition<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Partition")
+            .field("id", &self.id)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+/// The values of a partition, with the values each write replaced.
+struct Values<T> {
+    values: Vec<T>,
+    /// `(time, index, replaced value)` of every write, in the order written
+    undo: Vec<(u64, usize, T)>,
+}
+
+/// What a `Planet` needs of a partition without knowing its type.
+trait Journaled: Send {
+    fn rollback(&mut self, time: u64);
+    fn prune(&mut self, gvt: u64);
+    fn bytes(&self) -> &[u8];
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Pod + Zeroable + Send> Journaled for Values<T> {
+    fn rollback(&mut self, time: u64) {
+        while let Some((_, index, value)) = self.undo.pop_if(|(at, _, _)| *at >= time) {
+            self.values[index] = value;
+        }
+    }
+
+    fn prune(&mut self, gvt: u64) {
+        let settled = self.undo.partition_point(|(at, _, _)| *at < gvt);
+        self.undo.drain(..settled);
+    }
+
+    fn bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.values)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// The shared partitions of a `Planet`, numbered in the order they were created, and their members.
+#[derive(Default)]
+pub struct Partitions {
+    partitions: Vec<Box<dyn Journaled>>,
+    members: BTreeMap<usize, BTreeSet<usize>>,
+    /// oldest tick whose values the kept writes can still reconstruct
+    floor: u64,
+}
+
+impl fmt::Debug for Partitions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Partitions")
+            .field("len", &self.partitions.len())
+            .field("members", &self.members)
+            .field("floor", &self.floor)
+            .finish()
+    }
+}
+
+impl Partitions {
+    /// Create a partition of `size` zeroed values, returning its handle.
+    pub fn create<T: Pod + Zeroable + Send>(&mut self, size: usize) -> Partition<T> {
+        self.partitions.push(Box::new(Values::<T> {
+            values: vec![T::zeroed(); size],
+            undo: Vec::new(),
+        }));
+        Partition {
+            id: self.partitions.len() - 1,
+            len: size,
+            _values: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.partitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.partitions.is_empty()
+    }
+
+    pub fn join<T>(&mut self, partition: Partition<T>, agent: usize) {
+        self.members.entry(partition.id).or_default().insert(agent);
+    }
+
+    pub fn leave<T>(&mut self, partition: Partition<T>, agent: usize) {
+        if let Some(agents) = self.members.get_mut(&partition.id) {
+            agents.remove(&agent);
+            if agents.is_empty() {
+                self.members.remove(&partition.id);
+            }
+        }
+    }
+
+    /// Members of `partition`, in ascending agent order.
+    pub fn members<T>(&self, partition: Partition<T>) -> Vec<usize> {
+        self.members
+            .get(&partition.id)
+            .map(|agents| agents.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// The values of `partition`, whoever reads them.
+    pub fn values<T: 'static>(&self, partition: Partition<T>) -> Result<&[T], AikaError> {
+        self.partitions
+            .get(partition.id)
+            .and_then(|values| values.as_any().downcast_ref::<Values<T>>())
+            .map(|values| values.values.as_slice())
+            .ok_or(AikaError::InvalidPartition(partition.id))
+    }
+
+    /// The values of `partition` as they stood at the end of tick `time`, with every later write reverted.
+    pub fn values_at<T: Pod + 'static>(
+        &self,
+        partition: Partition<T>,
+        time: u64,
+    ) -> Result<Vec<T>, AikaError> {
+        if time < self.floor {
+            return Err(AikaError::HistoryPruned {
+                time,
+                floor: self.floor,
+            });
+        }
+        let values = self
+            .partitions
+            .get(partition.id)
+            .and_then(|values| values.as_any().downcast_ref::<Values<T>>())
+            .ok_or(AikaError::InvalidPartition(partition.id))?;
+        let mut out = values.values.clone();
+        for (_, index, value) in values.undo.iter().rev().take_while(|(at, _, _)| *at > time) {
+            out[*index] = *value;
+        }
+        Ok(out)
+    }
+
+    /// Oldest tick `values_at()` can reconstruct, the one before the GVT the writes were last pruned at.
+    pub fn floor(&self) -> u64 {
+        self.floor
+    }
+
+    fn check_member<T>(&self, partition: Partition<T>, agent: usize) -> Result<(), AikaError> {
+        let joined = self
+            .members
+            .get(&partition.id)
+            .is_some_and(|agents| agents.contains(&agent));
+        if !joined {
+            return Err(AikaError::NotAPartitionMember {
+                partition: partition.id,
+                agent,
+            });
+        }
+        Ok(())
+    }
+
+    /// The values of `partition`, read by `agent`.
+    pub(crate) fn read<T: 'static>(
+        &self,
+        partition: Partition<T>,
+        agent: usize,
+    ) -> Result<&[T], AikaError> {
+        self.check_member(partition, agent)?;
+        self.values(partition)
+    }
+
+    /// Set value `index` of `partition` to `value`, written by `agent` at `time`.
+    pub(crate) fn write<T: 'static>(
+        &mut self,
+        partition: Partition<T>,
+        agent: usize,
+        index: usize,
+        value: T,
+        time: u64,
+    ) -> Result<(), AikaError> {
+        self.check_member(partition, agent)?;
+        let values = self
+            .partitions
+            .get_mut(partition.id)
+            .and_then(|values| values.as_any_mut().downcast_mut::<Values<T>>())
+            .ok_or(AikaError::InvalidPartition(partition.id))?;
+        let slot = values
+            .values
+            .get_mut(index)
+            .ok_or(AikaError::PartitionIndex {
+                partition: partition.id,
+                index,
+            })?;
+        let replaced = std::mem::replace(slot, value);
+        values.undo.push((time, index, replaced));
+        Ok(())
+    }
+
+    /// Undo the writes at or after `time`.
+    pub(crate) fn rollback(&mut self, time: u64) {
+        for partition in &mut self.partitions {
+            partition.rollback(time);
+        }
+    }
+
+    /// Forget the values replaced before `gvt`, which no rollback can restore.
+    pub(crate) fn prune(&mut self, gvt: u64) {
+        for partition in &mut self.partitions {
+            partition.prune(gvt);
+        }
+        self.floor = self.floor.max(gvt.saturating_sub(1));
+    }
+
+    /// The values of every partition as bytes, in the order they were created.
+    pub(crate) fn snapshot(&self) -> Vec<Vec<u8>> {
+        self.partitions
+            .iter()
+            .map(|partition| partition.bytes().to_vec())
+            .collect()
+    }
+
+    /// Drop every membership of `agent`.
+    pub(crate) fn remove_agent(&mut self, agent: usize) {
+        for agents in self.members.values_mut() {
+            agents.remove(&agent);
+        }
+        self.members.retain(|_, agents| !agents.is_empty());
+    }
+
+    /// Drop the memberships of the agents from `start` on, which are split off to another `Planet`.
+    pub(crate) fn split_off(&mut self, start: usize) {
+        for agents in self.members.values_mut() {
+            agents.split_off(&start);
+        }
+        self.members.retain(|_, agents| !agents.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[test]
+    fn test_writes_roll_back() {
+        let mut partitions = Partitions::default();
+        let book = partitions.create::<u64>(3);
+        let other = partitions.create::<[u32; 2]>(1);
+        partitions.join(book, 4);
+
+        partitions.write(book, 4, 0, 7, 2).unwrap();
+        partitions.write(book, 4, 0, 8, 5).unwrap();
+        partitions.write(book, 4, 2, 9, 5).unwrap();
+        partitions.write(book, 4, 1, 3, 6).unwrap();
+        assert_eq!(partitions.read(book, 4).unwrap(), [8, 3, 9]);
+        assert!(matches!(
+            partitions.read(book, 5),
+            Err(AikaError::NotAPartitionMember {
+                partition: 0,
+                agent: 5
+            })
+        ));
+        assert!(matches!(
+            partitions.write(book, 4, 3, 1, 6),
+            Err(AikaError::PartitionIndex {
+                partition: 0,
+                index: 3
+            })
+        ));
+        assert_eq!(partitions.values(other).unwrap(), [[0, 0]]);
+        assert_eq!(partitions.values_at(book, 5).unwrap(), [8, 0, 9]);
+        assert_eq!(partitions.values_at(book, 1).unwrap(), [0, 0, 0]);
+
+        partitions.prune(3);
+        assert_eq!(partitions.floor(), 2);
+        assert_eq!(partitions.values_at(book, 2).unwrap(), [7, 0, 0]);
+        assert!(matches!(
+            partitions.values_at(book, 1),
+            Err(AikaError::HistoryPruned { time: 1, floor: 2 })
+        ));
+        partitions.rollback(5);
+        assert_eq!(partitions.values(book).unwrap(), [7, 0, 0]);
+        // the write at 2 is past GVT and stays
+        partitions.rollback(0);
+        assert_eq!(partitions.values(book).unwrap(), [7, 0, 0]);
+
+        partitions.split_off(4);
+        assert!(partitions.members(book).is_empty());
+    }
+
+    // Adds one to the first value of the partition every step
+    struct Trader {
+        book: Partition<u64>,
+    }
+
+    impl ThreadedAgent<16, u64> for Trader {
+        fn step(&mut self, context: &mut PlanetContext<16, u64>, id: usize) -> Event {
+            context.join_partition(self.book, id);
+            let trades = context.read_partition(self.book, id).unwrap()[0];
+            context
+                .write_partition(self.book, id, 0, trades + 1)
+                .unwrap();
+            Event::new(context.time, context.time, id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, u64>,
+            _msg: Msg<u64>,
+            _id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_traders_share_a_book() {
+        let config = HybridConfig::new(1, 64)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(4, 10)
+            .with_uniform_worlds(16, 3, 16);
+        let mut engine = HybridEngine::<16, 16, 2, u64>::create(config).unwrap();
+        let book = engine.create_shared_partition::<u64>(0, 1).unwrap();
+        for _ in 0..3 {
+            engine.spawn_agent(0, Box::new(Trader { book })).unwrap();
+        }
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+        let partitions = &engine.planets[0].context.partitions;
+        assert_eq!(partitions.members(book), [0, 1, 2]);
+        // three traders stepping over [1, 20)
+        assert_eq!(partitions.values(book).unwrap(), [57]);
+    }
+}
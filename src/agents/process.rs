@@ -0,0 +1,206 @@
+//! Process-style agents, for models written the SimPy way: hold for a while, request a resource, release it.
+//! A `Process` is an explicit state machine that is resumed every time its agent is woken and answers with the
+//! `Step` it waits for next. `ProcessAgent` turns it into an `Agent` for a `World` or a `ThreadedAgent` for a
+//! `Planet`, so processes and ordinary agents run side by side and wake each other with `Action::Trigger`. A
+//! `Process` generic over its context `C` runs in both engines. On a `Planet` the process is kept in its agent's
+//! state `Journal`, so it must be `Pod` and rolls back with the rest of the `Planet`.
+use bytemuck::{Pod, Zeroable};
+use mesocarp::comms::mailbox::Message;
+
+use crate::{
+    agents::{Agent, PlanetContext, ThreadedAgent, WorldContext},
+    objects::{Action, Event, Msg},
+};
+
+/// What a `Process` waits for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// resume after `n` steps
+    Hold(u64),
+    /// resume at a given time
+    HoldUntil(u64),
+    /// resume on the tick after a unit of a resource is granted, see `Action::Acquire`
+    Request { resource: usize, priority: u64 },
+    /// hand back a unit of a resource and resume on the next tick
+    Release(usize),
+    /// resume only when woken by another agent
+    Passivate,
+    /// done; a finished process that is woken again should keep answering `Finish`
+    Finish,
+}
+
+impl Step {
+    fn action(self) -> Action {
+        match self {
+            Step::Hold(steps) => Action::Timeout(steps),
+            Step::HoldUntil(time) => Action::Schedule(time),
+            Step::Request { resource, priority } => Action::Acquire { resource, priority },
+            Step::Release(resource) => Action::Release {
+                resource,
+                delay: Some(1),
+            },
+            Step::Passivate | Step::Finish => Action::Wait,
+        }
+    }
+}
+
+/// A process resumed with its context `C` at time `now` whenever its agent is woken.
+pub trait Process<C> {
+    fn resume(&mut self, context: &mut C, now: u64, agent_id: usize) -> Step;
+}
+
+/// Runs a `Process` as an agent.
+pub struct ProcessAgent<P> {
+    process: P,
+}
+
+impl<P> ProcessAgent<P> {
+    pub fn new(process: P) -> Self {
+        Self { process }
+    }
+
+    /// The process as it is now on a `World`, or as it started on a `Planet`, where its current state is the latest
+    /// in the agent's state `Journal`.
+    pub fn process(&self) -> &P {
+        &self.process
+    }
+}
+
+impl<const SLOTS: usize, T: Message, P: Process<WorldContext<SLOTS, T>> + 'static> Agent<SLOTS, T>
+    for ProcessAgent<P>
+{
+    fn step(&mut self, context: &mut WorldContext<SLOTS, T>, agent_id: usize) -> Event {
+        let now = context.time;
+        let step = self.process.resume(context, now, agent_id);
+        Event::new(now, now, agent_id, step.action())
+    }
+}
+
+impl<const SLOTS: usize, T, P> ThreadedAgent<SLOTS, T> for ProcessAgent<P>
+where
+    T: Pod + Zeroable + Clone,
+    P: Process<PlanetContext<SLOTS, T>> + Pod + Zeroable,
+{
+    fn step(&mut self, context: &mut PlanetContext<SLOTS, T>, agent_id: usize) -> Event {
+        let now = context.time;
+        let mut process = context.agent_states[agent_id]
+            .read_state::<P>()
+            .copied()
+            .unwrap_or(self.process);
+        let step = process.resume(context, now, agent_id);
+        context.agent_states[agent_id].write(process, now, None);
+        Event::new(now, now, agent_id, step.action())
+    }
+
+    /// Processes only interact through their context, resources and wake-ups, so mail is ignored.
+    fn read_message(
+        &mut self,
+        _context: &mut PlanetContext<SLOTS, T>,
+        _msg: Msg<T>,
+        _agent_id: usize,
+    ) {
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agents::resources::QueueDiscipline,
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        st::World,
+    };
+
+    // Sets up for `setup` steps, then runs a three step job on the shared machine and notes when it finished
+    #[derive(Copy, Clone, Debug, Default)]
+    #[repr(C)]
+    struct Job {
+        setup: u64,
+        phase: u64,
+        done: u64,
+    }
+
+    unsafe impl Pod for Job {}
+    unsafe impl Zeroable for Job {}
+
+    impl<C> Process<C> for Job {
+        fn resume(&mut self, _context: &mut C, now: u64, _agent_id: usize) -> Step {
+            self.phase += 1;
+            match self.phase {
+                1 => Step::Hold(self.setup),
+                2 => Step::Request {
+                    resource: 0,
+                    priority: 0,
+                },
+                3 => Step::Hold(3),
+                4 => Step::Release(0),
+                5 => {
+                    self.done = now;
+                    Step::Finish
+                }
+                _ => Step::Finish,
+            }
+        }
+    }
+
+    // An ordinary agent that wakes the third process at 15
+    struct Caller;
+
+    impl Agent<8, Msg<u8>> for Caller {
+        fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+            Event::new(
+                context.time,
+                context.time,
+                id,
+                Action::Trigger { time: 15, idx: 2 },
+            )
+        }
+    }
+
+    #[test]
+    fn test_processes_share_a_machine() {
+        let jobs = [Job {
+            setup: 1,
+            ..Job::default()
+        }; 2];
+
+        let mut world = World::<8, 16, 2, u8>::init(30.0, 1.0, 0).unwrap();
+        world.add_resource(1, QueueDiscipline::Fifo);
+        for job in jobs {
+            world.spawn_agent(Box::new(ProcessAgent::new(job)));
+        }
+        world.spawn_agent(Box::new(ProcessAgent::new(Job {
+            setup: 40,
+            ..Job::default()
+        })));
+        world.spawn_agent(Box::new(Caller));
+        world.init_support_layers(None).unwrap();
+        world.schedule_all_agents(1).unwrap();
+        world.run().unwrap();
+        // both request at 2 and are granted at 2 and 6, and the caller's wake-up cuts the long setup short
+        let done = world
+            .agents_of::<ProcessAgent<Job>>()
+            .map(|(_, agent)| agent.process().done)
+            .collect::<Vec<_>>();
+        assert_eq!(done, [7, 11, 20]);
+
+        let config = HybridConfig::new(1, 64)
+            .with_time_bounds(30.0, 1.0)
+            .with_optimistic_sync(4, 10)
+            .with_uniform_worlds(16, 2, 1024);
+        let mut engine = HybridEngine::<16, 16, 2, u64>::create(config).unwrap();
+        engine.add_resource(0, 1, QueueDiscipline::Fifo).unwrap();
+        for job in jobs {
+            engine
+                .spawn_agent(0, Box::new(ProcessAgent::new(job)))
+                .unwrap();
+        }
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+        let done = engine.planets[0].context.agent_states[..2]
+            .iter()
+            .map(|journal| journal.read_state::<Job>().unwrap().done)
+            .collect::<Vec<_>>();
+        assert_eq!(done, [7, 11]);
+    }
+}
@@ -0,0 +1,415 @@
+//! Resources with a capacity, such as servers or machines, that the agents of a `World` or `Planet` share.
+//! An agent yields `Action::Acquire` to take a unit of a resource and `Action::Release` to hand one back. A free
+//! unit is granted at once, otherwise the agent waits in the resource's queue, served first come first served or by
+//! priority. Either way the agent is woken on the tick after it is granted the unit. The state of every resource is
+//! kept by time of change, so a `Planet` rolls it back with everything else and forgets the changes GVT passed.
+//! Resources belong to the `World` or `Planet` they were added to: agents moved away by a split or a migration
+//! can't reach them any more, and keep the units they held.
+use std::collections::BTreeMap;
+
+use crate::AikaError;
+
+/// Order in which waiting agents are granted a resource.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum QueueDiscipline {
+    #[default]
+    Fifo,
+    /// highest priority first, first come first served among equals
+    Priority,
+}
+
+/// What a resource did so far.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourceStats {
+    /// units granted
+    pub acquisitions: u64,
+    /// units granted after waiting in the queue
+    pub waited: u64,
+    /// ticks spent waiting by the agents granted a unit
+    pub total_wait: u64,
+    /// longest the queue has been
+    pub max_queue: u64,
+    /// units in use times the ticks they were in use, up to the last change
+    pub busy: u64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Waiter {
+    agent: usize,
+    priority: u64,
+    since: u64,
+    arrival: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct ResourceState {
+    /// units held per agent
+    holders: BTreeMap<usize, u64>,
+    in_use: u64,
+    queue: Vec<Waiter>,
+    arrivals: u64,
+    /// time `stats.busy` was last brought up to date
+    since: u64,
+    stats: ResourceStats,
+}
+
+/// A resource with `capacity` units.
+#[derive(Clone, Debug)]
+pub struct Resource {
+    capacity: u64,
+    discipline: QueueDiscipline,
+    /// `(time, state after every change at that time)`, the last being the current state
+    history: Vec<(u64, ResourceState)>,
+}
+
+impl Resource {
+    fn new(capacity: u64, discipline: QueueDiscipline) -> Self {
+        Self {
+            capacity,
+            discipline,
+            history: vec![(0, ResourceState::default())],
+        }
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    pub fn discipline(&self) -> QueueDiscipline {
+        self.discipline
+    }
+
+    fn state(&self) -> &ResourceState {
+        &self.history.last().unwrap().1
+    }
+
+    /// The state to change at `time`, taking its `busy` count up to `time`.
+    fn change(&mut self, time: u64) -> &mut ResourceState {
+        if self.history.last().unwrap().0 != time {
+            let state = self.state().clone();
+            self.history.push((time, state));
+        }
+        let state = &mut self.history.last_mut().unwrap().1;
+        state.stats.busy += state.in_use * time.saturating_sub(state.since);
+        state.since = state.since.max(time);
+        state
+    }
+
+    /// Units in use.
+    pub fn in_use(&self) -> u64 {
+        self.state().in_use
+    }
+
+    /// Units `agent` holds.
+    pub fn held_by(&self, agent: usize) -> u64 {
+        self.state().holders.get(&agent).copied().unwrap_or(0)
+    }
+
+    /// Agents waiting for a unit, in the order they will be granted one.
+    pub fn queue(&self) -> Vec<usize> {
+        let mut queue = self.state().queue.clone();
+        if self.discipline == QueueDiscipline::Priority {
+            queue.sort_by_key(|waiter| (u64::MAX - waiter.priority, waiter.arrival));
+        }
+        queue.into_iter().map(|waiter| waiter.agent).collect()
+    }
+
+    pub fn stats(&self) -> ResourceStats {
+        self.state().stats
+    }
+
+    /// Fraction of the capacity in use over `[0, now)`.
+    pub fn utilization(&self, now: u64) -> f64 {
+        let state = self.state();
+        let busy = state.stats.busy + state.in_use * now.saturating_sub(state.since);
+        if now == 0 || self.capacity == 0 {
+            return 0.0;
+        }
+        busy as f64 / (self.capacity * now) as f64
+    }
+
+    fn grant(state: &mut ResourceState, agent: usize) {
+        state.in_use += 1;
+        *state.holders.entry(agent).or_default() += 1;
+        state.stats.acquisitions += 1;
+    }
+
+    /// Take a unit for `agent` at `time`, or queue it. Returns whether it was granted.
+    fn acquire(&mut self, agent: usize, priority: u64, time: u64) -> bool {
+        let capacity = self.capacity;
+        let state = self.change(time);
+        if state.in_use < capacity {
+            Self::grant(state, agent);
+            return true;
+        }
+        state.queue.push(Waiter {
+            agent,
+            priority,
+            since: time,
+            arrival: state.arrivals,
+        });
+        state.arrivals += 1;
+        state.stats.max_queue = state.stats.max_queue.max(state.queue.len() as u64);
+        false
+    }
+
+    /// Hand back a unit `agent` holds at `time`, returning the waiting agent granted it, if any.
+    fn release(&mut self, agent: usize, time: u64) -> Option<Option<usize>> {
+        let discipline = self.discipline;
+        let state = self.change(time);
+        let held = state.holders.get_mut(&agent)?;
+        *held -= 1;
+        if *held == 0 {
+            state.holders.remove(&agent);
+        }
+        state.in_use -= 1;
+        let next = match discipline {
+            _ if state.queue.is_empty() => return Some(None),
+            QueueDiscipline::Fifo => 0,
+            QueueDiscipline::Priority => state
+                .queue
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, waiter)| (waiter.priority, u64::MAX - waiter.arrival))
+                .map(|(i, _)| i)
+                .unwrap(),
+        };
+        let waiter = state.queue.remove(next);
+        Self::grant(state, waiter.agent);
+        state.stats.waited += 1;
+        state.stats.total_wait += time - waiter.since;
+        Some(Some(waiter.agent))
+    }
+
+    fn rollback(&mut self, time: u64) {
+        let keep = self.history.partition_point(|(at, _)| *at < time).max(1);
+        self.history.truncate(keep);
+    }
+
+    fn prune(&mut self, gvt: u64) {
+        let settled = self.history.partition_point(|(at, _)| *at < gvt);
+        self.history.drain(..settled.saturating_sub(1));
+    }
+}
+
+/// The resources of a `World` or `Planet`, numbered in the order they were added.
+#[derive(Clone, Debug, Default)]
+pub struct Resources {
+    resources: Vec<Resource>,
+}
+
+impl Resources {
+    /// Add a resource with `capacity` units, returning its id.
+    pub fn add(&mut self, capacity: u64, discipline: QueueDiscipline) -> usize {
+        self.resources.push(Resource::new(capacity, discipline));
+        self.resources.len() - 1
+    }
+
+    pub fn get(&self, resource: usize) -> Option<&Resource> {
+        self.resources.get(resource)
+    }
+
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    fn resource(&mut self, resource: usize) -> Result<&mut Resource, AikaError> {
+        self.resources
+            .get_mut(resource)
+            .ok_or(AikaError::InvalidResource(resource))
+    }
+
+    /// Take a unit of `resource` for `agent` at `time`, or queue it. Returns whether it was granted.
+    pub(crate) fn acquire(
+        &mut self,
+        resource: usize,
+        agent: usize,
+        priority: u64,
+        time: u64,
+    ) -> Result<bool, AikaError> {
+        Ok(self.resource(resource)?.acquire(agent, priority, time))
+    }
+
+    /// Hand back a unit of `resource` that `agent` holds at `time`, returning the waiting agent granted it, if any.
+    pub(crate) fn release(
+        &mut self,
+        resource: usize,
+        agent: usize,
+        time: u64,
+    ) -> Result<Option<usize>, AikaError> {
+        self.resource(resource)?
+            .release(agent, time)
+            .ok_or(AikaError::ResourceNotHeld { resource, agent })
+    }
+
+    /// Undo the changes at or after `time`.
+    pub(crate) fn rollback(&mut self, time: u64) {
+        for resource in &mut self.resources {
+            resource.rollback(time);
+        }
+    }
+
+    /// Forget the changes before `gvt`, which no rollback can undo, but the state they left.
+    pub(crate) fn prune(&mut self, gvt: u64) {
+        for resource in &mut self.resources {
+            resource.prune(gvt);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agents::{Agent, PlanetContext, ThreadedAgent, WorldContext},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+        st::World,
+    };
+
+    #[test]
+    fn test_queues_and_rollback() {
+        let mut resources = Resources::default();
+        let fifo = resources.add(1, QueueDiscipline::Fifo);
+        let ranked = resources.add(1, QueueDiscipline::Priority);
+
+        assert!(resources.acquire(fifo, 0, 0, 2).unwrap());
+        assert!(!resources.acquire(fifo, 1, 9, 3).unwrap());
+        assert!(!resources.acquire(fifo, 2, 0, 3).unwrap());
+        assert!(resources.acquire(ranked, 0, 0, 2).unwrap());
+        for (agent, priority) in [(1, 1), (2, 5), (3, 5)] {
+            assert!(!resources.acquire(ranked, agent, priority, 3).unwrap());
+        }
+        assert_eq!(resources.get(ranked).unwrap().queue(), [2, 3, 1]);
+
+        assert_eq!(resources.release(fifo, 0, 6).unwrap(), Some(1));
+        assert_eq!(resources.release(ranked, 0, 6).unwrap(), Some(2));
+        assert!(matches!(
+            resources.release(fifo, 0, 7),
+            Err(AikaError::ResourceNotHeld {
+                resource: 0,
+                agent: 0
+            })
+        ));
+        let stats = resources.get(fifo).unwrap().stats();
+        assert_eq!(
+            (stats.acquisitions, stats.waited, stats.total_wait),
+            (2, 1, 3)
+        );
+        assert_eq!(stats.max_queue, 2);
+        // one unit busy over [2, 10)
+        assert_eq!(resources.get(fifo).unwrap().utilization(10), 0.8);
+
+        resources.prune(4);
+        resources.rollback(6);
+        let fifo = resources.get(fifo).unwrap();
+        assert_eq!((fifo.held_by(0), fifo.held_by(1)), (1, 0));
+        assert_eq!(fifo.queue(), [1, 2]);
+        assert_eq!(fifo.stats().waited, 0);
+        assert!(matches!(
+            resources.acquire(7, 0, 0, 8),
+            Err(AikaError::InvalidResource(7))
+        ));
+    }
+
+    // Queues for the server, holds it for three steps, then leaves
+    #[derive(Default)]
+    struct Customer {
+        phase: u8,
+        served: Option<u64>,
+    }
+
+    impl Customer {
+        fn next(&mut self, time: u64) -> Action {
+            self.phase += 1;
+            match self.phase {
+                1 => Action::Acquire {
+                    resource: 0,
+                    priority: 0,
+                },
+                2 => {
+                    self.served = Some(time);
+                    Action::Timeout(3)
+                }
+                3 => Action::Release {
+                    resource: 0,
+                    delay: None,
+                },
+                _ => Action::Wait,
+            }
+        }
+    }
+
+    impl Agent<8, Msg<u8>> for Customer {
+        fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+            Event::new(context.time, context.time, id, self.next(context.time))
+        }
+    }
+
+    impl ThreadedAgent<16, u64> for Customer {
+        fn step(&mut self, context: &mut PlanetContext<16, u64>, id: usize) -> Event {
+            Event::new(context.time, context.time, id, self.next(context.time))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, u64>,
+            _msg: Msg<u64>,
+            _id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_customers_share_a_server() {
+        let check = |served: Vec<Option<u64>>, server: &Resource| {
+            assert_eq!(served, [Some(2), Some(6), Some(10)]);
+            let stats = server.stats();
+            assert_eq!(
+                (stats.acquisitions, stats.waited, stats.max_queue),
+                (3, 2, 2)
+            );
+            // the second waited from 1 to 5, the third from 1 to 9
+            assert_eq!(stats.total_wait, 12);
+            assert_eq!(server.in_use(), 0);
+            assert_eq!(server.utilization(20), 0.6);
+        };
+
+        let mut world = World::<8, 16, 2, u8>::init(20.0, 1.0, 0).unwrap();
+        world.add_resource(1, QueueDiscipline::Fifo);
+        for _ in 0..3 {
+            world.spawn_agent(Box::new(Customer::default()));
+        }
+        world.init_support_layers(None).unwrap();
+        world.schedule_all_agents(1).unwrap();
+        world.run().unwrap();
+        let served = world
+            .agents_of::<Customer>()
+            .map(|(_, customer)| customer.served)
+            .collect();
+        check(served, world.resources().get(0).unwrap());
+
+        let config = HybridConfig::new(1, 64)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(4, 10)
+            .with_uniform_worlds(16, 3, 16);
+        let mut engine = HybridEngine::<16, 16, 2, u64>::create(config).unwrap();
+        engine.add_resource(0, 1, QueueDiscipline::Fifo).unwrap();
+        for _ in 0..3 {
+            engine
+                .spawn_agent(0, Box::new(Customer::default()))
+                .unwrap();
+        }
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+        let served = engine
+            .agents_of::<Customer>()
+            .map(|(_, customer)| customer.served)
+            .collect();
+        check(served, engine.planets[0].context.resources.get(0).unwrap());
+    }
+}
@@ -0,0 +1,271 @@
+//! Request/response calls between `ThreadedAgent`s on top of `Msg`.
+//! `PlanetContext::request()` sends a `Msg` tagged with a fresh `RequestId` and sets a timer for its timeout. The
+//! target reads the request in `read_message()` like any other `Msg` and answers it with `PlanetContext::reply()`.
+//! Whichever comes first of the reply and the timeout is handed to the requester's `ThreadedAgent::on_reply()`,
+//! and the other is dropped. A `RequestId` is made of the requester's address, the time of the request and its
+//! position among the requests the agent made at that time, so the re-execution after a rollback issues the same
+//! ids. The correlation table drops the requests a rollback undid and reopens the ones it settled, and forgets
+//! settled requests once GVT passes them.
+use std::collections::BTreeMap;
+
+/// Identifier of a request, unique across the run and stable across rollbacks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RequestId {
+    pub world: usize,
+    pub agent: usize,
+    pub time: u64,
+    pub seq: u32,
+}
+
+/// Role of a `Msg` in a request/response call.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Rpc {
+    Request(RequestId),
+    Reply(RequestId),
+    /// the timer of a request, a `Msg` from the requester to itself
+    Timeout(RequestId),
+    /// the answer to a discovery query, a timer from the querying agent to itself
+    Discovery(RequestId),
+}
+
+/// The outcome of a request, handed to `ThreadedAgent::on_reply()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Reply<T> {
+    Data(T),
+    TimedOut,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Pending {
+    agent: usize,
+    /// time the reply or timeout was handed to the requester
+    settled: Option<u64>,
+}
+
+/// The requests made on one `Planet`, until GVT passes their outcome.
+#[derive(Clone, Debug, Default)]
+pub struct RpcTable {
+    pending: BTreeMap<RequestId, Pending>,
+    /// requests made so far per `(agent, time)`
+    issued: BTreeMap<(usize, u64), u32>,
+}
+
+impl RpcTable {
+    /// Number of requests still waiting for a reply or timeout.
+    pub fn open(&self) -> usize {
+        self.pending
+            .values()
+            .filter(|pending| pending.settled.is_none())
+            .count()
+    }
+
+    /// The id of the next request `agent` makes at `time` on `world`.
+    pub(crate) fn next_id(&self, world: usize, agent: usize, time: u64) -> RequestId {
+        let seq = self.issued.get(&(agent, time)).copied().unwrap_or_default();
+        RequestId {
+            world,
+            agent,
+            time,
+            seq,
+        }
+    }
+
+    pub(crate) fn open_request(&mut self, id: RequestId) {
+        *self.issued.entry((id.agent, id.time)).or_default() += 1;
+        self.pending.insert(
+            id,
+            Pending {
+                agent: id.agent,
+                settled: None,
+            },
+        );
+    }
+
+    /// Settle `id` at `time`, returning the requester if it was still open.
+    pub(crate) fn settle(&mut self, id: RequestId, time: u64) -> Option<usize> {
+        let pending = self.pending.get_mut(&id)?;
+        if pending.settled.is_some() {
+            return None;
+        }
+        pending.settled = Some(time);
+        Some(pending.agent)
+    }
+
+    /// Undo the requests made and settled at or after `time`.
+    pub(crate) fn rollback(&mut self, time: u64) {
+        self.pending.retain(|id, _| id.time < time);
+        self.issued.retain(|(_, issued), _| *issued < time);
+        for pending in self.pending.values_mut() {
+            if pending.settled.is_some_and(|settled| settled >= time) {
+                pending.settled = None;
+            }
+        }
+    }
+
+    /// Forget the requests settled before `gvt`, which no rollback can reopen.
+    pub(crate) fn prune(&mut self, gvt: u64) {
+        self.pending
+            .retain(|_, pending| pending.settled.is_none_or(|settled| settled >= gvt));
+        self.issued.retain(|(_, issued), _| *issued >= gvt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Number {
+        value: u64,
+    }
+
+    unsafe impl Pod for Number {}
+    unsafe impl Zeroable for Number {}
+
+    type Replies = Arc<Mutex<Vec<(RequestId, u64, Reply<Number>)>>>;
+
+    // Asks agent 1 to double a number every 5 steps, and agent 2, which never answers, every 10
+    struct Client {
+        replies: Replies,
+    }
+
+    impl ThreadedAgent<16, Number> for Client {
+        fn step(&mut self, context: &mut PlanetContext<16, Number>, agent_id: usize) -> Event {
+            let time = context.time;
+            if time.is_multiple_of(5) {
+                let value = Number { value: time };
+                context.request(agent_id, (0, 1), value, 4).unwrap();
+            }
+            if time.is_multiple_of(10) {
+                context
+                    .request(agent_id, (0, 2), Number { value: 0 }, 3)
+                    .unwrap();
+            }
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, Number>,
+            _msg: Msg<Number>,
+            _agent_id: usize,
+        ) {
+        }
+
+        fn on_reply(
+            &mut self,
+            context: &mut PlanetContext<16, Number>,
+            id: RequestId,
+            reply: Reply<Number>,
+            _agent_id: usize,
+        ) {
+            self.replies.lock().unwrap().push((id, context.time, reply));
+        }
+    }
+
+    struct Doubler;
+
+    impl ThreadedAgent<16, Number> for Doubler {
+        fn step(&mut self, context: &mut PlanetContext<16, Number>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            context: &mut PlanetContext<16, Number>,
+            msg: Msg<Number>,
+            agent_id: usize,
+        ) {
+            let doubled = Number {
+                value: msg.data.value * 2,
+            };
+            context.reply(agent_id, &msg, doubled).unwrap();
+        }
+    }
+
+    struct Silent;
+
+    impl ThreadedAgent<16, Number> for Silent {
+        fn step(&mut self, context: &mut PlanetContext<16, Number>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, Number>,
+            _msg: Msg<Number>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_requests_are_answered_or_time_out() {
+        let config = HybridConfig::new(1, 16)
+            .with_time_bounds(30.0, 1.0)
+            .with_optimistic_sync(10, 20)
+            .with_uniform_worlds(16, 3, 16);
+        let mut engine = HybridEngine::<16, 16, 1, Number>::create(config).unwrap();
+        let replies = Arc::new(Mutex::new(Vec::new()));
+        engine
+            .spawn_agent(
+                0,
+                Box::new(Client {
+                    replies: Arc::clone(&replies),
+                }),
+            )
+            .unwrap();
+        engine.spawn_agent(0, Box::new(Doubler)).unwrap();
+        engine.spawn_agent(0, Box::new(Silent)).unwrap();
+        engine.schedule(0, 0, 1).unwrap();
+        engine.run().unwrap();
+
+        // an outcome heard again after a rollback replaces the one it undid
+        let mut outcomes = BTreeMap::new();
+        for (id, time, reply) in replies.lock().unwrap().iter() {
+            outcomes.insert(*id, (*time, *reply));
+        }
+        assert!(outcomes.keys().any(|id| id.seq == 0));
+        assert!(outcomes.keys().any(|id| id.seq == 1));
+        for (id, (time, reply)) in outcomes {
+            assert_eq!((id.world, id.agent), (0, 0));
+            match id.seq {
+                // the request and the reply take a step each
+                0 => assert_eq!(
+                    (time, reply),
+                    (id.time + 2, Reply::Data(Number { value: id.time * 2 }))
+                ),
+                _ => assert_eq!((time, reply), (id.time + 3, Reply::TimedOut)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_rollback_reopens_settled_requests() {
+        let mut table = RpcTable::default();
+        let first = table.next_id(0, 2, 4);
+        table.open_request(first);
+        let second = table.next_id(0, 2, 4);
+        assert_eq!(second.seq, 1);
+        table.open_request(second);
+        assert_eq!(table.settle(first, 6), Some(2));
+        assert_eq!(table.settle(first, 7), None);
+
+        table.rollback(5);
+        assert_eq!(table.open(), 2);
+        assert_eq!(table.settle(first, 5), Some(2));
+        table.rollback(4);
+        assert_eq!(table.open(), 0);
+        assert_eq!(table.next_id(0, 2, 4), first);
+    }
+}
@@ -0,0 +1,287 @@
+//! Capability sets restricting what untrusted agents may do through their `PlanetContext`.
+//! An agent spawned with `Capabilities` can only send so many `Msg`s per tick, write the world state if allowed,
+//! and wake or trigger the agents its `TriggerScope` covers. A refused call does nothing: the methods returning a
+//! `Result` fail with `AikaError::CapabilityDenied`, the others and refused `Action::Trigger`s are dropped. Every
+//! refusal is recorded as a `Violation`, undone by rollbacks like the call it refused, and reported in
+//! `PlanetStats::violations`. Agents spawned without `Capabilities` are unrestricted.
+use std::collections::BTreeMap;
+
+use crate::{objects::Groups, AikaError};
+
+/// Which agents an agent may trigger or wake besides itself.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TriggerScope {
+    /// any agent of its `Planet`
+    #[default]
+    Any,
+    /// agents sharing a multicast group with it
+    SharedGroup,
+    /// none
+    SelfOnly,
+}
+
+/// What an agent may do, see `HybridEngine::spawn_sandboxed()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// most `Msg`s sent per tick, each `send_mail()` or `send_many()` call counting once
+    pub max_sends_per_tick: Option<u32>,
+    /// whether it may write the world state
+    pub write_world_state: bool,
+    pub triggers: TriggerScope,
+}
+
+impl Capabilities {
+    /// Everything allowed.
+    pub fn unrestricted() -> Self {
+        Self {
+            max_sends_per_tick: None,
+            write_world_state: true,
+            triggers: TriggerScope::Any,
+        }
+    }
+
+    /// Nothing beyond itself: no mail, no world state writes, and no triggers of other agents.
+    pub fn isolated() -> Self {
+        Self {
+            max_sends_per_tick: Some(0),
+            write_world_state: false,
+            triggers: TriggerScope::SelfOnly,
+        }
+    }
+
+    /// Send at most `limit` `Msg`s per tick.
+    pub fn with_send_limit(mut self, limit: u32) -> Self {
+        self.max_sends_per_tick = Some(limit);
+        self
+    }
+
+    /// Allow or forbid world state writes.
+    pub fn with_world_writes(mut self, allowed: bool) -> Self {
+        self.write_world_state = allowed;
+        self
+    }
+
+    /// Trigger and wake only the agents `scope` covers.
+    pub fn with_triggers(mut self, scope: TriggerScope) -> Self {
+        self.triggers = scope;
+        self
+    }
+}
+
+/// A capability an agent lacked.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// a `Msg` past the per-tick limit
+    SendLimit {
+        limit: u32,
+    },
+    WorldStateWrite,
+    /// a trigger or wake-up of `target` outside its `TriggerScope`
+    Trigger {
+        target: usize,
+    },
+}
+
+/// A call refused to an agent.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub world: usize,
+    pub agent: usize,
+    pub time: u64,
+    pub kind: ViolationKind,
+}
+
+/// The capabilities of the sandboxed agents of one `Planet` and what they were refused.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Sandbox {
+    capabilities: BTreeMap<usize, Capabilities>,
+    /// agent whose handler is running, if any
+    pub(crate) acting: Option<usize>,
+    /// `(tick, sends)` of each sandboxed agent's latest tick with mail
+    sends: BTreeMap<usize, (u64, u32)>,
+    /// by time
+    violations: Vec<Violation>,
+}
+
+impl Sandbox {
+    pub(crate) fn restrict(&mut self, agent: usize, capabilities: Capabilities) {
+        self.capabilities.insert(agent, capabilities);
+    }
+
+    /// Lift the restrictions of an agent that leaves the `Planet`, returning them.
+    pub(crate) fn release(&mut self, agent: usize) -> Option<Capabilities> {
+        self.sends.remove(&agent);
+        self.capabilities.remove(&agent)
+    }
+
+    /// Move the capabilities of agents from `start` on into a new `Sandbox`, numbering them from 0.
+    pub(crate) fn split_off(&mut self, start: usize) -> Self {
+        let moved = self.capabilities.split_off(&start);
+        self.sends.split_off(&start);
+        Self {
+            capabilities: moved
+                .into_iter()
+                .map(|(agent, capabilities)| (agent - start, capabilities))
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    fn restricted(&self) -> Option<(usize, &Capabilities)> {
+        let agent = self.acting?;
+        Some((agent, self.capabilities.get(&agent)?))
+    }
+
+    fn deny(&mut self, world: usize, agent: usize, time: u64, kind: ViolationKind) -> AikaError {
+        let violation = Violation {
+            world,
+            agent,
+            time,
+            kind,
+        };
+        self.violations.push(violation);
+        AikaError::CapabilityDenied(violation)
+    }
+
+    /// Count a `Msg` sent by the acting agent at `time`.
+    pub(crate) fn check_send(&mut self, world: usize, time: u64) -> Result<(), AikaError> {
+        let Some((agent, capabilities)) = self.restricted() else {
+            return Ok(());
+        };
+        let Some(limit) = capabilities.max_sends_per_tick else {
+            return Ok(());
+        };
+        let sends = self.sends.entry(agent).or_insert((time, 0));
+        if sends.0 != time {
+            *sends = (time, 0);
+        }
+        if sends.1 >= limit {
+            return Err(self.deny(world, agent, time, ViolationKind::SendLimit { limit }));
+        }
+        sends.1 += 1;
+        Ok(())
+    }
+
+    pub(crate) fn check_world_write(&mut self, world: usize, time: u64) -> Result<(), AikaError> {
+        match self.restricted() {
+            Some((agent, capabilities)) if !capabilities.write_world_state => {
+                Err(self.deny(world, agent, time, ViolationKind::WorldStateWrite))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that `agent` may trigger or wake `target`.
+    pub(crate) fn check_trigger(
+        &mut self,
+        world: usize,
+        agent: usize,
+        target: usize,
+        time: u64,
+        groups: &Groups,
+    ) -> Result<(), AikaError> {
+        let Some(capabilities) = self.capabilities.get(&agent) else {
+            return Ok(());
+        };
+        let allowed = target == agent
+            || match capabilities.triggers {
+                TriggerScope::Any => true,
+                TriggerScope::SharedGroup => groups.share_group(agent, target),
+                TriggerScope::SelfOnly => false,
+            };
+        if allowed {
+            return Ok(());
+        }
+        Err(self.deny(world, agent, time, ViolationKind::Trigger { target }))
+    }
+
+    /// Check that the acting agent, if any, may wake `target`.
+    pub(crate) fn check_wakeup(
+        &mut self,
+        world: usize,
+        target: usize,
+        time: u64,
+        groups: &Groups,
+    ) -> Result<(), AikaError> {
+        match self.acting {
+            Some(agent) => self.check_trigger(world, agent, target, time, groups),
+            None => Ok(()),
+        }
+    }
+
+    /// Forget the sends and violations at or after `time`.
+    pub(crate) fn rollback(&mut self, time: u64) {
+        self.sends.retain(|_, (tick, _)| *tick < time);
+        let keep = self
+            .violations
+            .partition_point(|violation| violation.time < time);
+        self.violations.truncate(keep);
+    }
+
+    pub(crate) fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::GroupId;
+
+    #[test]
+    fn test_capabilities_are_enforced() {
+        let mut sandbox = Sandbox::default();
+        sandbox.restrict(0, Capabilities::unrestricted().with_send_limit(2));
+        sandbox.restrict(
+            1,
+            Capabilities::isolated().with_triggers(TriggerScope::SharedGroup),
+        );
+        let mut groups = Groups::default();
+        groups.join(GroupId::named("pen"), 1);
+        groups.join(GroupId::named("pen"), 2);
+
+        sandbox.acting = Some(0);
+        assert!(sandbox.check_send(0, 5).is_ok());
+        assert!(sandbox.check_send(0, 5).is_ok());
+        assert!(sandbox.check_send(0, 5).is_err());
+        assert!(sandbox.check_send(0, 6).is_ok());
+        assert!(sandbox.check_world_write(0, 6).is_ok());
+
+        sandbox.acting = Some(1);
+        assert!(sandbox.check_world_write(0, 7).is_err());
+        assert!(sandbox.check_wakeup(0, 2, 7, &groups).is_ok());
+        assert!(sandbox.check_wakeup(0, 1, 7, &groups).is_ok());
+        assert!(sandbox.check_wakeup(0, 3, 7, &groups).is_err());
+
+        // unsandboxed agents and calls outside any handler are never refused
+        sandbox.acting = Some(2);
+        assert!(sandbox.check_world_write(0, 7).is_ok());
+        sandbox.acting = None;
+        assert!(sandbox.check_send(0, 7).is_ok());
+
+        let kinds = |sandbox: &Sandbox| {
+            sandbox
+                .violations()
+                .iter()
+                .map(|violation| (violation.agent, violation.kind))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kinds(&sandbox),
+            vec![
+                (0, ViolationKind::SendLimit { limit: 2 }),
+                (1, ViolationKind::WorldStateWrite),
+                (1, ViolationKind::Trigger { target: 3 }),
+            ]
+        );
+        sandbox.rollback(6);
+        assert_eq!(kinds(&sandbox).len(), 1);
+
+        let child = sandbox.split_off(1);
+        assert_eq!(
+            child.capabilities.get(&0).map(|caps| caps.triggers),
+            Some(TriggerScope::SharedGroup)
+        );
+        assert!(!sandbox.capabilities.contains_key(&1));
+    }
+}
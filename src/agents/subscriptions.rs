@@ -0,0 +1,292 @@
+//! Agent subscriptions to cells of the world state, with notifications on change.
+//! Instead of reading the world state every step to spot a change, an agent subscribes to the `StateCell`s it cares
+//! about. Writes through `WorldContext::write_world_state()` or `PlanetContext::write_world_state()` compare every
+//! subscribed cell against the state they replace, and each subscriber of a cell whose bytes changed gets a call to
+//! `on_world_change()` after the handlers of the tick, or after those of the next tick with `NotifyAt::NextTick`.
+//! Writes straight to the `world_state` journal notify no one. Notifications a rollback undid are raised again when
+//! the write is re-executed, but subscriptions themselves, like group memberships, are not rolled back.
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A field of the world state, `len` bytes at byte `offset`, e.g. from `std::mem::offset_of!`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StateCell {
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl StateCell {
+    pub fn new(offset: usize, len: usize) -> Self {
+        Self { offset, len }
+    }
+
+    /// The bytes of this cell in `state`, if it lies inside.
+    fn read<'a>(&self, state: &'a [u8]) -> Option<&'a [u8]> {
+        state.get(self.offset..self.offset.checked_add(self.len)?)
+    }
+}
+
+/// When subscribers hear of a change.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum NotifyAt {
+    /// at the end of the tick of the write
+    #[default]
+    SameTick,
+    /// at the end of the tick after the write
+    NextTick,
+}
+
+/// A change to `cell` written at `written`, for `agent`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Notice {
+    pub written: u64,
+    pub due: u64,
+    pub agent: usize,
+    pub cell: StateCell,
+    delivered: bool,
+}
+
+/// The subscriptions of the agents on one world, and the notifications raised for them.
+#[derive(Clone, Debug, Default)]
+pub struct Subscriptions {
+    cells: BTreeMap<StateCell, BTreeSet<usize>>,
+    notify_at: NotifyAt,
+    /// notices in the order they were raised, kept until GVT passes them
+    notices: Vec<Notice>,
+}
+
+impl Subscriptions {
+    pub fn subscribe(&mut self, cell: StateCell, agent: usize) {
+        self.cells.entry(cell).or_default().insert(agent);
+    }
+
+    pub fn unsubscribe(&mut self, cell: StateCell, agent: usize) {
+        if let Some(agents) = self.cells.get_mut(&cell) {
+            agents.remove(&agent);
+            if agents.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    /// Subscribers of `cell`, in ascending agent order.
+    pub fn subscribers(&self, cell: StateCell) -> Vec<usize> {
+        self.cells
+            .get(&cell)
+            .map(|agents| agents.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn notify_at(&self) -> NotifyAt {
+        self.notify_at
+    }
+
+    pub(crate) fn set_notify_at(&mut self, notify_at: NotifyAt) {
+        self.notify_at = notify_at;
+    }
+
+    /// Raise a notice for every subscriber of each cell that differs between `before` and `after`, written at
+    /// `time`. Every cell inside `after` counts as changed if there was no state `before`.
+    pub(crate) fn changed(&mut self, before: Option<&[u8]>, after: &[u8], time: u64) {
+        let due = match self.notify_at {
+            NotifyAt::SameTick => time,
+            NotifyAt::NextTick => time.saturating_add(1),
+        };
+        for (cell, agents) in &self.cells {
+            let Some(new) = cell.read(after) else {
+                continue;
+            };
+            if before.and_then(|before| cell.read(before)) == Some(new) {
+                continue;
+            }
+            self.notices.extend(agents.iter().map(|agent| Notice {
+                written: time,
+                due,
+                agent: *agent,
+                cell: *cell,
+                delivered: false,
+            }));
+        }
+    }
+
+    /// Take the notices due by `time` that weren't delivered yet, in the order they were raised.
+    pub(crate) fn take_due(&mut self, time: u64) -> Vec<Notice> {
+        self.notices
+            .iter_mut()
+            .filter(|notice| !notice.delivered && notice.due <= time)
+            .map(|notice| {
+                notice.delivered = true;
+                *notice
+            })
+            .collect()
+    }
+
+    /// Earliest time a notice not delivered yet falls due.
+    pub(crate) fn next_due(&self) -> Option<u64> {
+        self.notices
+            .iter()
+            .filter(|notice| !notice.delivered)
+            .map(|notice| notice.due)
+            .min()
+    }
+
+    /// Drop the notices raised at or after `time`, and deliver again those that fall due from then on.
+    pub(crate) fn rollback(&mut self, time: u64) {
+        self.notices.retain(|notice| notice.written < time);
+        for notice in &mut self.notices {
+            if notice.due >= time {
+                notice.delivered = false;
+            }
+        }
+    }
+
+    /// Drop the notices delivered before `gvt`, which no rollback can reach.
+    pub(crate) fn prune(&mut self, gvt: u64) {
+        self.notices
+            .retain(|notice| !notice.delivered || notice.due >= gvt);
+    }
+
+    /// Drop every subscription and pending notice of `agent`.
+    pub(crate) fn remove_agent(&mut self, agent: usize) {
+        for agents in self.cells.values_mut() {
+            agents.remove(&agent);
+        }
+        self.cells.retain(|_, agents| !agents.is_empty());
+        self.notices.retain(|notice| notice.agent != agent);
+    }
+
+    /// Move the subscriptions and notices of agents `start..` into a new `Subscriptions`, re-indexed from zero.
+    pub(crate) fn split_off(&mut self, start: usize) -> Subscriptions {
+        let mut moved = Subscriptions {
+            notify_at: self.notify_at,
+            ..Default::default()
+        };
+        for (cell, agents) in self.cells.iter_mut() {
+            for agent in agents.split_off(&start) {
+                moved.subscribe(*cell, agent - start);
+            }
+        }
+        self.cells.retain(|_, agents| !agents.is_empty());
+        let (kept, notices) = std::mem::take(&mut self.notices)
+            .into_iter()
+            .partition(|notice| notice.agent < start);
+        self.notices = kept;
+        moved.notices = notices
+            .into_iter()
+            .map(|notice: Notice| Notice {
+                agent: notice.agent - start,
+                ..notice
+            })
+            .collect();
+        moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        mem::{offset_of, size_of},
+        sync::{Arc, Mutex},
+    };
+
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        agents::{Agent, WorldContext},
+        objects::{Action, Event, Msg},
+        st::World,
+    };
+
+    #[derive(Copy, Clone, Debug, Default)]
+    #[repr(C)]
+    struct Market {
+        price: u64,
+        volume: u64,
+    }
+
+    unsafe impl Pod for Market {}
+    unsafe impl Zeroable for Market {}
+
+    const PRICE: StateCell = StateCell {
+        offset: offset_of!(Market, price),
+        len: size_of::<u64>(),
+    };
+
+    // Trades every tick, moving the price every third
+    struct Exchange;
+
+    impl Agent<8, Msg<u8>> for Exchange {
+        fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, agent_id: usize) -> Event {
+            let market = Market {
+                price: context.time / 3,
+                volume: context.time,
+            };
+            context.write_world_state(market);
+            Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+        }
+    }
+
+    // Never polls, only hears of price moves
+    struct Trader {
+        heard: Arc<Mutex<Vec<(u64, u64)>>>,
+    }
+
+    impl Agent<8, Msg<u8>> for Trader {
+        fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Wait)
+        }
+
+        fn on_world_change(
+            &mut self,
+            context: &mut WorldContext<8, Msg<u8>>,
+            cell: StateCell,
+            _agent_id: usize,
+        ) {
+            assert_eq!(cell, PRICE);
+            let price = context.world_state.read_state::<Market>().unwrap().price;
+            self.heard.lock().unwrap().push((context.time, price));
+        }
+    }
+
+    fn run(notify_at: NotifyAt) -> Vec<(u64, u64)> {
+        let heard = Arc::new(Mutex::new(Vec::new()));
+        let mut world = World::<8, 16, 1, u8>::init(10.0, 1.0, 256).unwrap();
+        world.spawn_agent(Box::new(Exchange));
+        let trader = world.spawn_agent(Box::new(Trader {
+            heard: heard.clone(),
+        }));
+        world.init_support_layers(None).unwrap();
+        world.set_notify_at(notify_at);
+        world.world_context.subscribe(PRICE, trader);
+        world.schedule_all_agents(0).unwrap();
+        world.run().unwrap();
+        let heard = heard.lock().unwrap().clone();
+        heard
+    }
+
+    #[test]
+    fn test_world_state_subscriptions() {
+        assert_eq!(
+            run(NotifyAt::SameTick),
+            vec![(0, 0), (3, 1), (6, 2), (9, 3)]
+        );
+        assert_eq!(run(NotifyAt::NextTick)[..3], [(1, 0), (4, 1), (7, 2)]);
+
+        // a rollback hands out the notices due from then on again, and forgets those it undid
+        let mut subscriptions = Subscriptions::default();
+        subscriptions.set_notify_at(NotifyAt::NextTick);
+        subscriptions.subscribe(PRICE, 2);
+        subscriptions.changed(None, bytemuck::bytes_of(&Market::default()), 4);
+        assert!(subscriptions.take_due(4).is_empty());
+        assert_eq!(subscriptions.take_due(5).len(), 1);
+        assert!(subscriptions.take_due(5).is_empty());
+        subscriptions.rollback(5);
+        assert_eq!(subscriptions.take_due(5)[0].agent, 2);
+        subscriptions.rollback(4);
+        assert!(subscriptions.take_due(5).is_empty());
+
+        let moved = subscriptions.split_off(1);
+        assert_eq!(moved.subscribers(PRICE), vec![1]);
+        assert!(subscriptions.subscribers(PRICE).is_empty());
+    }
+}
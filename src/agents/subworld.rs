@@ -0,0 +1,207 @@
+//! Hierarchical composition: a `ThreadedAgent` whose behavior is a full single-threaded `World`.
+//! The inner `World` is advanced in lockstep with the outer `Planet` clock, and boundary `Msg`s are
+//! mapped in through `World::deliver()` and out through the `World`'s boundary address.
+//!
+//! Like any state outside the `PlanetContext` journals, the inner `World` is not rewound when the outer
+//! `Planet` rolls back.
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    agents::{PlanetContext, ThreadedAgent},
+    objects::{Action, Event, Msg},
+    st::World,
+};
+
+type Inbound<MessageType> = Box<dyn FnMut(Msg<MessageType>) -> Option<Msg<MessageType>>>;
+type Outbound<MessageType> = Box<dyn FnMut(Msg<MessageType>) -> Option<(Msg<MessageType>, usize)>>;
+
+/// A `ThreadedAgent` adapter that runs a nested `st::World` as its internal sub-model.
+pub struct SubWorldAgent<
+    const MESSAGE_SLOTS: usize,
+    const CLOCK_SLOTS: usize,
+    const CLOCK_HEIGHT: usize,
+    MessageType: Clone,
+> {
+    pub world: World<MESSAGE_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
+    inbound: Option<Inbound<MessageType>>,
+    outbound: Option<Outbound<MessageType>>,
+}
+
+impl<
+        const MESSAGE_SLOTS: usize,
+        const CLOCK_SLOTS: usize,
+        const CLOCK_HEIGHT: usize,
+        MessageType: Clone,
+    > SubWorldAgent<MESSAGE_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>
+{
+    /// Wrap an inner `World`. Without mappings, boundary `Msg`s are dropped in both directions.
+    pub fn new(world: World<MESSAGE_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>) -> Self {
+        Self {
+            world,
+            inbound: None,
+            outbound: None,
+        }
+    }
+
+    /// Map `Msg`s delivered to this agent onto a recipient inside the inner `World`, or drop them with `None`.
+    pub fn with_inbound(
+        mut self,
+        inbound: impl FnMut(Msg<MessageType>) -> Option<Msg<MessageType>> + 'static,
+    ) -> Self {
+        self.inbound = Some(Box::new(inbound));
+        self
+    }
+
+    /// Map `Msg`s sent to the inner `World`'s boundary address onto `(Msg, to_world)` pairs for the outer simulation.
+    pub fn with_outbound(
+        mut self,
+        outbound: impl FnMut(Msg<MessageType>) -> Option<(Msg<MessageType>, usize)> + 'static,
+    ) -> Self {
+        self.outbound = Some(Box::new(outbound));
+        self
+    }
+}
+
+impl<
+        const INTER_SLOTS: usize,
+        const MESSAGE_SLOTS: usize,
+        const CLOCK_SLOTS: usize,
+        const CLOCK_HEIGHT: usize,
+        MessageType: Pod + Zeroable + Clone,
+    > ThreadedAgent<INTER_SLOTS, MessageType>
+    for SubWorldAgent<MESSAGE_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>
+{
+    fn step(
+        &mut self,
+        context: &mut PlanetContext<INTER_SLOTS, MessageType>,
+        agent_id: usize,
+    ) -> Event {
+        let time = context.time;
+        if self.world.advance_to(time).is_err() {
+            return Event::new(time, time, agent_id, Action::Wait);
+        }
+        for msg in self.world.take_boundary_mail() {
+            if let Some((msg, to_world)) = self.outbound.as_mut().and_then(|map| map(msg)) {
+                let _ = context.send_mail(msg, to_world);
+            }
+        }
+        Event::new(time, time, agent_id, Action::Timeout(1))
+    }
+
+    fn read_message(
+        &mut self,
+        _context: &mut PlanetContext<INTER_SLOTS, MessageType>,
+        msg: Msg<MessageType>,
+        _agent_id: usize,
+    ) {
+        if let Some(msg) = self.inbound.as_mut().and_then(|map| map(msg)) {
+            let _ = self.world.deliver(msg);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{
+        agents::{Agent, WorldContext},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Num {
+        value: u32,
+    }
+
+    unsafe impl Pod for Num {}
+    unsafe impl Zeroable for Num {}
+
+    // Inner agent that doubles every number it receives and hands it to the boundary
+    struct Doubler {
+        boundary: usize,
+    }
+
+    impl Agent<8, Msg<Num>> for Doubler {
+        fn step(&mut self, context: &mut WorldContext<8, Msg<Num>>, agent_id: usize) -> Event {
+            let time = context.time;
+            if let Some(mailbox) = &mut context.agent_states[agent_id].mailbox {
+                for msg in mailbox.poll().unwrap_or_default() {
+                    let doubled = Num {
+                        value: msg.data.value * 2,
+                    };
+                    let out = Msg::new(doubled, time, time + 1, agent_id, Some(self.boundary));
+                    mailbox.send(out).unwrap();
+                }
+            }
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+    }
+
+    // Outer agent that sends a single number and records what comes back
+    struct Client {
+        received: Arc<Mutex<Vec<(u64, u32)>>>,
+    }
+
+    impl ThreadedAgent<128, Num> for Client {
+        fn step(&mut self, context: &mut PlanetContext<128, Num>, agent_id: usize) -> Event {
+            let time = context.time;
+            let msg = Msg::new(Num { value: 21 }, time, time + 3, agent_id, Some(0));
+            context.send_mail(msg, 0).unwrap();
+            Event::new(time, time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Num>,
+            msg: Msg<Num>,
+            _agent_id: usize,
+        ) {
+            self.received
+                .lock()
+                .unwrap()
+                .push((msg.recv, msg.data.value));
+        }
+    }
+
+    #[test]
+    fn test_sub_world_round_trip() {
+        let mut world = World::<8, 128, 1, Num>::init(40.0, 1.0, 16).unwrap();
+        let doubler = world.spawn_agent(Box::new(Doubler { boundary: 1 }));
+        assert_eq!(world.spawn_boundary(), 1);
+        world.init_support_layers(None).unwrap();
+        world.schedule(1, doubler).unwrap();
+
+        let sub_world = SubWorldAgent::new(world)
+            .with_inbound(move |mut msg| {
+                msg.to = Some(doubler);
+                Some(msg)
+            })
+            .with_outbound(|mut msg| {
+                msg.from = 0;
+                msg.to = Some(0);
+                msg.recv = msg.sent + 2;
+                Some((msg, 1))
+            });
+
+        let config = HybridConfig::new(2, 16)
+            .with_time_bounds(40.0, 1.0)
+            .with_optimistic_sync(1, 10)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<128, 128, 1, Num>::create(config).unwrap();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let client = Client {
+            received: Arc::clone(&received),
+        };
+        engine.spawn_agent(0, Box::new(sub_world)).unwrap();
+        engine.spawn_agent(1, Box::new(client)).unwrap();
+        engine.schedule(0, 0, 1).unwrap();
+        engine.schedule(1, 0, 2).unwrap();
+        engine.run().unwrap();
+
+        // sent at 2, delivered into the inner world at 5, doubled on the inner tick 5, back out at 7
+        assert_eq!(*received.lock().unwrap(), vec![(7, 42)]);
+    }
+}
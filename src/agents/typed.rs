@@ -0,0 +1,168 @@
+//! Typed views of state `Journal`s.
+//! A `TypedJournal<T>` reads and writes one `Journal` as a timeline of `T`s on behalf of the agent that is running.
+//! Every value is written at the context's current time, so a rollback undoes exactly the writes made after it,
+//! and values are handed out by copy, so nothing can be changed in place behind the `Journal`'s back, where a
+//! rollback wouldn't see it. A `Journal` stores its entries untyped, so it must only ever be viewed as one `T`.
+use std::marker::PhantomData;
+
+use bytemuck::{Pod, Zeroable};
+use mesocarp::{comms::mailbox::Message, logging::journal::Journal};
+
+use crate::{
+    agents::{PlanetContext, WorldContext},
+    AikaError,
+};
+
+/// A `Journal` viewed as the history of a `T`, written at `time`.
+pub struct TypedJournal<'a, T> {
+    journal: &'a mut Journal,
+    time: u64,
+    _state: PhantomData<T>,
+}
+
+impl<'a, T: Pod + Zeroable + 'static> TypedJournal<'a, T> {
+    pub fn new(journal: &'a mut Journal, time: u64) -> Self {
+        Self {
+            journal,
+            time,
+            _state: PhantomData,
+        }
+    }
+
+    /// The latest value, or `None` if nothing has been written yet or every write was rolled back.
+    pub fn get(&self) -> Option<T> {
+        self.journal.read_state::<T>().ok().copied()
+    }
+
+    /// The latest value, or `default` if there is none.
+    pub fn get_or(&self, default: T) -> T {
+        self.get().unwrap_or(default)
+    }
+
+    /// Log `value` as the state from now on.
+    pub fn set(&mut self, value: T) {
+        self.journal.write(value, self.time, None);
+    }
+
+    /// Every value written, with the time it was written at, oldest first.
+    pub fn history(&self) -> Vec<(T, u64)> {
+        self.journal
+            .read_all::<T>()
+            .into_iter()
+            .map(|(value, time)| (*value, time))
+            .collect()
+    }
+}
+
+impl<const SLOTS: usize, T: Pod + Zeroable + Clone> PlanetContext<SLOTS, T> {
+    /// An agent's state `Journal` as a history of `S`.
+    pub fn typed_state<S: Pod + Zeroable + 'static>(
+        &mut self,
+        agent_id: usize,
+    ) -> Result<TypedJournal<'_, S>, AikaError> {
+        let journal = self
+            .agent_states
+            .get_mut(agent_id)
+            .ok_or(AikaError::InvalidAgentId(agent_id))?;
+        Ok(TypedJournal::new(journal, self.time))
+    }
+
+    /// The world state `Journal` as a history of `S`.
+    pub fn typed_world_state<S: Pod + Zeroable + 'static>(&mut self) -> TypedJournal<'_, S> {
+        TypedJournal::new(&mut self.world_state, self.time)
+    }
+}
+
+impl<const SLOTS: usize, T: Message> WorldContext<SLOTS, T> {
+    /// An agent's state `Journal` as a history of `S`. Agents spawned without a state arena have none.
+    pub fn typed_state<S: Pod + Zeroable + 'static>(
+        &mut self,
+        agent_id: usize,
+    ) -> Result<TypedJournal<'_, S>, AikaError> {
+        let journal = self
+            .agent_states
+            .get_mut(agent_id)
+            .ok_or(AikaError::InvalidAgentId(agent_id))?
+            .state
+            .as_mut()
+            .ok_or(AikaError::NoStateJournal(agent_id))?;
+        Ok(TypedJournal::new(journal, self.time))
+    }
+
+    /// The world state `Journal` as a history of `S`.
+    pub fn typed_world_state<S: Pod + Zeroable + 'static>(&mut self) -> TypedJournal<'_, S> {
+        TypedJournal::new(&mut self.world_state, self.time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agents::ThreadedAgent,
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[test]
+    fn test_views_roll_back() {
+        let mut journal = Journal::init(64);
+        assert_eq!(TypedJournal::<u64>::new(&mut journal, 0).get(), None);
+        for time in 1..=4 {
+            let mut view = TypedJournal::<u64>::new(&mut journal, time);
+            let count = view.get_or(0);
+            view.set(count + time);
+        }
+        journal.rollback(2);
+        let view = TypedJournal::<u64>::new(&mut journal, 2);
+        assert_eq!(view.get(), Some(3));
+        assert_eq!(view.history(), vec![(1, 1), (3, 2)]);
+    }
+
+    // Counts its steps, and a hundred for every letter, in its state
+    struct Tally;
+
+    impl ThreadedAgent<16, u64> for Tally {
+        fn step(&mut self, context: &mut PlanetContext<16, u64>, agent_id: usize) -> Event {
+            let time = context.time;
+            if context.world_id == 1 && time < 10 {
+                let msg = Msg::new(1, time, time + 3, agent_id, Some(0));
+                context.send_mail(msg, 0).unwrap();
+            }
+            let mut tally = context.typed_state::<u64>(agent_id).unwrap();
+            tally.set(tally.get_or(0) + 1);
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            context: &mut PlanetContext<16, u64>,
+            msg: Msg<u64>,
+            agent_id: usize,
+        ) {
+            let mut tally = context.typed_state::<u64>(agent_id).unwrap();
+            tally.set(tally.get_or(0) + 100 * msg.data);
+        }
+    }
+
+    #[test]
+    fn test_tally_on_planets() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_uniform_worlds(256, 1, 1024);
+        let mut engine = HybridEngine::<16, 16, 1, u64>::create(config).unwrap();
+        for planet in 0..2 {
+            engine.spawn_agent(planet, Box::new(Tally)).unwrap();
+        }
+        engine.schedule_all_agents(1).unwrap();
+        let mut engine = engine.run().unwrap();
+        let context = &mut engine.planets[0].context;
+        // 19 steps and the 9 letters sent at 1 through 9
+        assert_eq!(context.typed_state::<u64>(0).unwrap().get(), Some(919));
+        assert!(matches!(
+            context.typed_state::<u64>(1),
+            Err(AikaError::InvalidAgentId(1))
+        ));
+    }
+}
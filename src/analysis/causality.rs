@@ -0,0 +1,157 @@
+//! Which event caused which message, for debugging causality violations.
+//! Every `Planet` that keeps a `CausalityLog` gives each event it schedules and each `Msg` its agents send a
+//! `CausalId`, and records the event or message being handled at the time as its parent. Nothing is dropped on
+//! rollback, since the work a rollback undoes is what explains it: the chain of a rollback starts at the straggler
+//! that caused it and follows parents back to an event scheduled from outside the run, across planets once the logs
+//! are merged.
+use std::collections::BTreeMap;
+
+use crate::objects::CausalId;
+
+/// What a `CausalId` was given to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CausalKind {
+    Event,
+    Msg,
+}
+
+/// An event scheduled, or a `Msg` sent, by `agent` for `time`, with the event or `Msg` handled when it was.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CausalRecord {
+    pub id: CausalId,
+    pub parent: Option<CausalId>,
+    pub kind: CausalKind,
+    /// agent woken by the event, or that sent the `Msg`
+    pub agent: usize,
+    /// time the event is due, or the `Msg` was sent
+    pub time: u64,
+}
+
+/// A rollback of `planet` from `from` to `to`, caused by the `Msg` `straggler`, or by its cancellation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RollbackCause {
+    pub planet: usize,
+    pub from: u64,
+    pub to: u64,
+    pub straggler: CausalId,
+    /// whether an anti-message cancelling the straggler arrived, rather than the straggler itself
+    pub cancelled: bool,
+}
+
+/// `CausalId`s handed out by one or more `Planet`s, and the rollbacks they went through.
+#[derive(Clone, Debug, Default)]
+pub struct CausalityLog {
+    planet: usize,
+    /// last counter handed out
+    counter: u64,
+    records: BTreeMap<CausalId, CausalRecord>,
+    rollbacks: Vec<RollbackCause>,
+}
+
+impl CausalityLog {
+    pub fn new(planet: usize) -> Self {
+        Self {
+            planet,
+            ..Self::default()
+        }
+    }
+
+    /// Give an event or `Msg` the next id, with `parent` as its cause.
+    pub(crate) fn record(
+        &mut self,
+        kind: CausalKind,
+        parent: CausalId,
+        agent: usize,
+        time: u64,
+    ) -> CausalId {
+        self.counter += 1;
+        let id = CausalId::new(self.planet, self.counter);
+        let parent = (!parent.is_none()).then_some(parent);
+        self.records.insert(
+            id,
+            CausalRecord {
+                id,
+                parent,
+                kind,
+                agent,
+                time,
+            },
+        );
+        id
+    }
+
+    pub(crate) fn rollback(&mut self, from: u64, to: u64, straggler: CausalId, cancelled: bool) {
+        self.rollbacks.push(RollbackCause {
+            planet: self.planet,
+            from,
+            to,
+            straggler,
+            cancelled,
+        });
+    }
+
+    /// Append all records from another log.
+    pub fn merge(&mut self, other: &CausalityLog) {
+        self.records
+            .extend(other.records.iter().map(|(id, record)| (*id, *record)));
+        self.rollbacks.extend_from_slice(&other.rollbacks);
+    }
+
+    pub fn get(&self, id: CausalId) -> Option<&CausalRecord> {
+        self.records.get(&id)
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = &CausalRecord> {
+        self.records.values()
+    }
+
+    /// Every rollback, in the order each `Planet` went through them.
+    pub fn rollbacks(&self) -> &[RollbackCause] {
+        &self.rollbacks
+    }
+
+    /// The chain of events and messages that led to `id`, oldest first and ending with `id`. The chain stops early at
+    /// an id from a `Planet` whose log wasn't merged in.
+    pub fn chain(&self, id: CausalId) -> Vec<CausalRecord> {
+        let mut chain = Vec::new();
+        let mut next = self.records.get(&id);
+        while let Some(record) = next {
+            chain.push(*record);
+            next = record.parent.and_then(|parent| self.records.get(&parent));
+        }
+        chain.reverse();
+        chain
+    }
+
+    /// The chain of events and messages that led to the straggler of `rollback`.
+    pub fn explain(&self, rollback: &RollbackCause) -> Vec<CausalRecord> {
+        self.chain(rollback.straggler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chains_cross_planets() {
+        let mut first = CausalityLog::new(0);
+        let mut second = CausalityLog::new(1);
+        let root = first.record(CausalKind::Event, CausalId::NONE, 0, 1);
+        let msg = first.record(CausalKind::Msg, root, 0, 1);
+        let event = second.record(CausalKind::Event, msg, 3, 1);
+        let straggler = second.record(CausalKind::Msg, event, 3, 1);
+        first.rollback(4, 2, straggler, false);
+        assert_eq!(root, CausalId::new(0, 1));
+        assert_eq!((straggler.planet(), straggler.counter()), (1, 2));
+
+        // without the other planet's log the chain stops at the first id it can't see
+        assert_eq!(first.explain(&first.rollbacks()[0]), Vec::new());
+        first.merge(&second);
+        let chain = first.explain(&first.rollbacks()[0]);
+        let ids = chain.iter().map(|record| record.id).collect::<Vec<_>>();
+        assert_eq!(ids, [root, msg, event, straggler]);
+        assert_eq!(chain[0].parent, None);
+        assert_eq!(chain[2].kind, CausalKind::Event);
+    }
+}
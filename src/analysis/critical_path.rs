@@ -0,0 +1,382 @@
+//! Critical-path analysis over the causal graph of a completed run.
+//! Every executed agent activation is a node, while scheduling links (via `commit_time`) and message
+//! deliveries are edges. The longest causal chain bounds the speedup any parallelization can reach.
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use crate::objects::AntiMsg;
+
+/// Activations, and links, a `CausalLog` keeps before it drops further records.
+pub const CAUSAL_LOG_LIMIT: usize = 1 << 22;
+
+/// A single activation of an agent (a `step()` or message read) at a point in simulation time.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CausalNode {
+    pub planet: usize,
+    pub agent: usize,
+    pub time: u64,
+}
+
+impl CausalNode {
+    pub fn new(planet: usize, agent: usize, time: u64) -> Self {
+        Self {
+            planet,
+            agent,
+            time,
+        }
+    }
+}
+
+/// A causal dependency between two activations.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CausalLink {
+    pub cause: CausalNode,
+    pub effect: CausalNode,
+}
+
+/// Records of each planet, by time.
+type ByTime<T> = BTreeMap<usize, BTreeMap<u64, Vec<T>>>;
+
+fn insert<T>(records: &mut ByTime<T>, planet: usize, time: u64, record: T) {
+    records
+        .entry(planet)
+        .or_default()
+        .entry(time)
+        .or_default()
+        .push(record);
+}
+
+/// Remove the records of `planet` at or after `time`, returning how many there were.
+fn cut<T>(records: &mut ByTime<T>, planet: usize, time: u64) -> usize {
+    records.get_mut(&planet).map_or(0, |times| {
+        times.split_off(&time).values().map(Vec::len).sum()
+    })
+}
+
+fn all<T: Copy>(records: &ByTime<T>) -> impl Iterator<Item = T> + '_ {
+    records
+        .values()
+        .flat_map(|times| times.values().flatten().copied())
+}
+
+/// Recorded activations and causal links of a run.
+///
+/// The whole run is needed for its critical path, so nothing is pruned at GVT. Instead the log is capped: past
+/// `limit` activations, or `limit` links, further records of that kind are dropped and counted in `dropped()`.
+/// Activations are kept by planet and time and links by the planet and time of their cause, so a rollback or an
+/// annihilated message only touches the records it removes.
+#[derive(Debug, Clone)]
+pub struct CausalLog {
+    activations: ByTime<CausalNode>,
+    links: ByTime<CausalLink>,
+    activation_count: usize,
+    link_count: usize,
+    /// first time recorded, the end of the warm-up
+    from: u64,
+    limit: usize,
+    dropped: u64,
+}
+
+impl Default for CausalLog {
+    fn default() -> Self {
+        Self::with_limit(CAUSAL_LOG_LIMIT)
+    }
+}
+
+impl CausalLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A log keeping at most `limit` activations and `limit` links.
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            activations: BTreeMap::new(),
+            links: BTreeMap::new(),
+            activation_count: 0,
+            link_count: 0,
+            from: 0,
+            limit,
+            dropped: 0,
+        }
+    }
+
+    /// Ignore activations before `time`, and links from them, which belong to the warm-up.
+    pub(crate) fn start_at(&mut self, time: u64) {
+        self.from = time;
+    }
+
+    /// Record that an agent was activated.
+    pub fn activate(&mut self, node: CausalNode) {
+        if node.time < self.from {
+            return;
+        }
+        if self.activation_count >= self.limit {
+            self.dropped += 1;
+            return;
+        }
+        self.activation_count += 1;
+        insert(&mut self.activations, node.planet, node.time, node);
+    }
+
+    /// Record that `cause` scheduled or messaged `effect`.
+    pub fn link(&mut self, cause: CausalNode, effect: CausalNode) {
+        if cause.time < self.from {
+            return;
+        }
+        if self.link_count >= self.limit {
+            self.dropped += 1;
+            return;
+        }
+        self.link_count += 1;
+        insert(
+            &mut self.links,
+            cause.planet,
+            cause.time,
+            CausalLink { cause, effect },
+        );
+    }
+
+    /// Drop everything a `Planet` computed at or after `time`. Links caused on other planets are kept.
+    pub fn rollback(&mut self, planet: usize, time: u64) {
+        self.activation_count -= cut(&mut self.activations, planet, time);
+        self.link_count -= cut(&mut self.links, planet, time);
+    }
+
+    /// Remove the delivery link on `planet` matching a message annihilated by `anti_msg` from `from_world`.
+    pub(crate) fn cancel_message(&mut self, planet: usize, from_world: usize, anti_msg: &AntiMsg) {
+        let Some(links) = self
+            .links
+            .get_mut(&from_world)
+            .and_then(|times| times.get_mut(&anti_msg.sent))
+        else {
+            return;
+        };
+        let before = links.len();
+        links.retain(|link| {
+            !(link.effect.planet == planet
+                && link.cause.agent == anti_msg.from
+                && link.effect.time == anti_msg.received
+                && anti_msg.to.is_none_or(|to| to == link.effect.agent))
+        });
+        self.link_count -= before - links.len();
+    }
+
+    /// Append all records from another log.
+    pub fn merge(&mut self, other: &CausalLog) {
+        for node in all(&other.activations) {
+            insert(&mut self.activations, node.planet, node.time, node);
+        }
+        for link in all(&other.links) {
+            insert(&mut self.links, link.cause.planet, link.cause.time, link);
+        }
+        self.activation_count += other.activation_count;
+        self.link_count += other.link_count;
+        self.dropped += other.dropped;
+    }
+
+    /// Activations and links not recorded because the log was full. A critical path computed from a log that
+    /// dropped records only covers the part of the run that was recorded.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Every recorded activation, by planet and then time.
+    pub fn activations(&self) -> Vec<CausalNode> {
+        all(&self.activations).collect()
+    }
+
+    /// Every recorded link, by the planet and then the time of its cause.
+    pub fn links(&self) -> Vec<CausalLink> {
+        all(&self.links).collect()
+    }
+
+    /// Compute the longest chain of causally dependent activations.
+    ///
+    /// Links that point at a time where the target agent was not activated (e.g. a message read on a later step)
+    /// resolve to that agent's next activation.
+    pub fn critical_path(&self) -> CriticalPath {
+        let mut nodes = all(&self.activations)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        nodes.sort_unstable_by_key(|node| (node.time, node.planet, node.agent));
+        let index = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (*node, i))
+            .collect::<HashMap<_, _>>();
+        let mut timelines: HashMap<(usize, usize), BTreeMap<u64, usize>> = HashMap::new();
+        for (i, node) in nodes.iter().enumerate() {
+            timelines
+                .entry((node.planet, node.agent))
+                .or_default()
+                .insert(node.time, i);
+        }
+
+        let mut edges = HashSet::new();
+        for link in all(&self.links) {
+            let Some(&cause) = index.get(&link.cause) else {
+                continue;
+            };
+            let effect = timelines
+                .get(&(link.effect.planet, link.effect.agent))
+                .and_then(|line| line.range(link.effect.time..).next())
+                .map(|(_, i)| *i);
+            if let Some(effect) = effect {
+                if effect != cause {
+                    edges.insert((cause, effect));
+                }
+            }
+        }
+
+        let mut successors = vec![Vec::new(); nodes.len()];
+        let mut in_degree = vec![0usize; nodes.len()];
+        for &(cause, effect) in &edges {
+            successors[cause].push(effect);
+            in_degree[effect] += 1;
+        }
+
+        let mut depth = vec![1usize; nodes.len()];
+        let mut previous = vec![usize::MAX; nodes.len()];
+        let mut ready = (0..nodes.len())
+            .filter(|i| in_degree[*i] == 0)
+            .collect::<VecDeque<_>>();
+        while let Some(i) = ready.pop_front() {
+            for &next in &successors[i] {
+                if depth[i] + 1 > depth[next] {
+                    depth[next] = depth[i] + 1;
+                    previous[next] = i;
+                }
+                in_degree[next] -= 1;
+                if in_degree[next] == 0 {
+                    ready.push_back(next);
+                }
+            }
+        }
+
+        let mut path = Vec::new();
+        let mut cursor = (0..nodes.len()).max_by_key(|i| (depth[*i], usize::MAX - *i));
+        while let Some(i) = cursor {
+            path.push(nodes[i]);
+            cursor = (previous[i] != usize::MAX).then_some(previous[i]);
+        }
+        path.reverse();
+
+        let mut agents: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut planets: HashMap<usize, usize> = HashMap::new();
+        for node in &path {
+            *agents.entry((node.planet, node.agent)).or_default() += 1;
+            *planets.entry(node.planet).or_default() += 1;
+        }
+        let mut agent_share = agents.into_iter().collect::<Vec<_>>();
+        agent_share.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let mut planet_share = planets.into_iter().collect::<Vec<_>>();
+        planet_share.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        CriticalPath {
+            path,
+            total_activations: nodes.len(),
+            agent_share,
+            planet_share,
+        }
+    }
+}
+
+/// The longest causal chain of a run, and which agents and planets it runs through.
+#[derive(Debug, Clone)]
+pub struct CriticalPath {
+    /// activations on the critical path, in causal order
+    pub path: Vec<CausalNode>,
+    /// number of distinct activations in the run
+    pub total_activations: usize,
+    /// `((planet, agent), activations on the path)`, most dominant first
+    pub agent_share: Vec<((usize, usize), usize)>,
+    /// `(planet, activations on the path)`, most dominant first
+    pub planet_share: Vec<(usize, usize)>,
+}
+
+impl CriticalPath {
+    pub fn len(&self) -> usize {
+        self.path.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.path.is_empty()
+    }
+
+    /// Upper bound on the speedup from parallel execution: total work over critical path length.
+    pub fn max_speedup(&self) -> f64 {
+        if self.path.is_empty() {
+            return 1.0;
+        }
+        self.total_activations as f64 / self.path.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cross_planet_critical_path() {
+        let mut log = CausalLog::new();
+        // planet 0 agent 0 steps at 1, 2, 3
+        for t in 1..=3 {
+            log.activate(CausalNode::new(0, 0, t));
+            if t > 1 {
+                log.link(CausalNode::new(0, 0, t - 1), CausalNode::new(0, 0, t));
+            }
+        }
+        // an unrelated agent on planet 1
+        log.activate(CausalNode::new(1, 1, 2));
+        // planet 0 agent 0 messages planet 1 agent 0, which reads it at its next activation (5)
+        log.activate(CausalNode::new(1, 0, 5));
+        log.activate(CausalNode::new(1, 0, 6));
+        log.link(CausalNode::new(0, 0, 3), CausalNode::new(1, 0, 4));
+        log.link(CausalNode::new(1, 0, 5), CausalNode::new(1, 0, 6));
+
+        let path = log.critical_path();
+        assert_eq!(path.len(), 5);
+        assert_eq!(path.total_activations, 6);
+        assert_eq!(path.path[0], CausalNode::new(0, 0, 1));
+        assert_eq!(path.path[4], CausalNode::new(1, 0, 6));
+        assert_eq!(path.planet_share, vec![(0, 3), (1, 2)]);
+        assert!((path.max_speedup() - 1.2).abs() < 1e-9);
+
+        // rolling back planet 1 past time 5 cuts the chain
+        log.rollback(1, 5);
+        assert_eq!(log.critical_path().len(), 3);
+    }
+
+    #[test]
+    fn test_cancel_message_matches_the_sending_planet() {
+        let mut log = CausalLog::new();
+        // agent 0 on planets 0 and 2 both mail planet 1 at 5 for 7
+        log.link(CausalNode::new(0, 0, 5), CausalNode::new(1, 3, 7));
+        log.link(CausalNode::new(2, 0, 5), CausalNode::new(1, 3, 7));
+        let anti_msg = AntiMsg::new(5, 7, 0, Some(3));
+        log.cancel_message(1, 2, &anti_msg);
+        let links = log.links();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].cause.planet, 0);
+    }
+
+    #[test]
+    fn test_log_stops_at_its_limit() {
+        let mut log = CausalLog::with_limit(2);
+        for t in 1..=4 {
+            log.activate(CausalNode::new(0, 0, t));
+            log.link(CausalNode::new(0, 0, t), CausalNode::new(0, 0, t + 1));
+        }
+        assert_eq!(log.activations().len(), 2);
+        assert_eq!(log.links().len(), 2);
+        assert_eq!(log.dropped(), 4);
+        assert_eq!(log.critical_path().len(), 2);
+
+        // a rollback frees room for the records computed again
+        log.rollback(0, 2);
+        log.activate(CausalNode::new(0, 0, 2));
+        assert_eq!(log.activations().len(), 2);
+        assert_eq!(log.dropped(), 4);
+    }
+}
@@ -0,0 +1,294 @@
+//! Point estimates with confidence intervals for the output of terminating and steady-state runs.
+//! A metric tracked over a run, e.g. a `world_history()` mapped to `f64`, is autocorrelated, so the textbook
+//! interval over its raw observations is far too narrow. Three methods account for that:
+//! - `replication_deletion()` drops the warm-up of each independent replication and takes the spread of their means.
+//! - `batch_means()` splits one long run into batches. It doubles the batch size until the lag-1 autocorrelation of
+//!   the batch means falls below `MAX_BATCH_CORRELATION`, then takes the spread of those means.
+//! - `warmup_sensitivity()` repeats the batch means estimate for several warm-up lengths. An estimate that still
+//!   drifts as the warm-up grows means the warm-up is too short.
+//!
+//! Every `Estimate` uses a Student-t interval with the number of replications or batches, less one, as its degrees
+//! of freedom.
+use crate::AikaError;
+
+/// Batch means whose lag-1 autocorrelation is at most this are treated as independent.
+pub const MAX_BATCH_CORRELATION: f64 = 0.2;
+/// Fewest batches `batch_means()` merges down to.
+pub const MIN_BATCHES: usize = 10;
+/// Batches `batch_means()` starts from.
+pub const MAX_BATCHES: usize = 40;
+
+/// A point estimate with a symmetric confidence interval.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Estimate {
+    pub mean: f64,
+    /// half the width of the interval, which is `mean ± half_width`
+    pub half_width: f64,
+    /// coverage of the interval, e.g. `0.95`
+    pub confidence: f64,
+    /// independent observations behind the estimate, replications or batches
+    pub samples: usize,
+}
+
+impl Estimate {
+    /// The estimate of the mean of `samples`, treated as independent.
+    pub fn from_samples(samples: &[f64], confidence: f64) -> Result<Self, AikaError> {
+        if samples.len() < 2 {
+            return Err(AikaError::InsufficientData(format!(
+                "an interval needs at least 2 independent samples, got {}",
+                samples.len()
+            )));
+        }
+        if confidence <= 0.0 || confidence >= 1.0 {
+            return Err(AikaError::InsufficientData(format!(
+                "confidence {confidence} is not within (0, 1)"
+            )));
+        }
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        let t = student_t_quantile(0.5 + confidence / 2.0, samples.len() - 1);
+        Ok(Self {
+            mean,
+            half_width: t * (variance / n).sqrt(),
+            confidence,
+            samples: samples.len(),
+        })
+    }
+
+    /// Bounds of the interval.
+    pub fn interval(&self) -> (f64, f64) {
+        (self.mean - self.half_width, self.mean + self.half_width)
+    }
+
+    /// Half width relative to the mean, infinite for a mean of 0.
+    pub fn relative_precision(&self) -> f64 {
+        self.half_width / self.mean.abs()
+    }
+
+    /// Whether `value` lies within the interval.
+    pub fn contains(&self, value: f64) -> bool {
+        (self.mean - value).abs() <= self.half_width
+    }
+}
+
+/// The estimate of a batch means run, with the batching it settled on.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BatchMeans {
+    pub estimate: Estimate,
+    pub batch_size: usize,
+    /// lag-1 autocorrelation of the batch means
+    pub correlation: f64,
+}
+
+impl BatchMeans {
+    /// Whether the batch means look independent, so the interval can be trusted.
+    pub fn independent(&self) -> bool {
+        self.correlation.abs() <= MAX_BATCH_CORRELATION
+    }
+}
+
+/// The estimate over independent `replications` of the mean of the metric after its first `warmup` observations.
+pub fn replication_deletion(
+    replications: &[Vec<f64>],
+    warmup: usize,
+    confidence: f64,
+) -> Result<Estimate, AikaError> {
+    let means = replications
+        .iter()
+        .map(|series| {
+            mean(series.get(warmup..).unwrap_or_default()).ok_or_else(|| {
+                AikaError::InsufficientData(format!(
+                    "a replication of {} observations has none after a warm-up of {warmup}",
+                    series.len()
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Estimate::from_samples(&means, confidence)
+}
+
+/// The batch means estimate of the mean of `series` after its first `warmup` observations. Starting from
+/// `MAX_BATCHES` batches, the batch size doubles while the batch means are correlated and at least `MIN_BATCHES`
+/// would remain. Check `BatchMeans::independent()` before trusting the interval.
+pub fn batch_means(
+    series: &[f64],
+    warmup: usize,
+    confidence: f64,
+) -> Result<BatchMeans, AikaError> {
+    let kept = series.get(warmup..).unwrap_or_default();
+    let mut batch_size = (kept.len() / MAX_BATCHES).max(1);
+    loop {
+        let means = kept
+            .chunks_exact(batch_size)
+            .map(|batch| batch.iter().sum::<f64>() / batch_size as f64)
+            .collect::<Vec<_>>();
+        let correlation = autocorrelation(&means, 1);
+        if correlation.abs() <= MAX_BATCH_CORRELATION || kept.len() / (batch_size * 2) < MIN_BATCHES
+        {
+            return Ok(BatchMeans {
+                estimate: Estimate::from_samples(&means, confidence)?,
+                batch_size,
+                correlation,
+            });
+        }
+        batch_size *= 2;
+    }
+}
+
+/// The batch means estimate of `series` after each warm-up length in `warmups`.
+pub fn warmup_sensitivity(
+    series: &[f64],
+    warmups: &[usize],
+    confidence: f64,
+) -> Result<Vec<(usize, BatchMeans)>, AikaError> {
+    warmups
+        .iter()
+        .map(|&warmup| Ok((warmup, batch_means(series, warmup, confidence)?)))
+        .collect()
+}
+
+/// The lag-`lag` autocorrelation of `series`, 0 when it is too short or constant.
+pub fn autocorrelation(series: &[f64], lag: usize) -> f64 {
+    let Some(mean) = mean(series) else {
+        return 0.0;
+    };
+    if lag >= series.len() {
+        return 0.0;
+    }
+    let variance = series.iter().map(|x| (x - mean).powi(2)).sum::<f64>();
+    if variance == 0.0 {
+        return 0.0;
+    }
+    let covariance = series
+        .iter()
+        .zip(&series[lag..])
+        .map(|(a, b)| (a - mean) * (b - mean))
+        .sum::<f64>();
+    covariance / variance
+}
+
+fn mean(series: &[f64]) -> Option<f64> {
+    (!series.is_empty()).then(|| series.iter().sum::<f64>() / series.len() as f64)
+}
+
+/// The `p` quantile of Student's t distribution with `dof` degrees of freedom. Exact for 1 and 2 degrees of freedom,
+/// and the Cornish-Fisher expansion around the normal quantile otherwise.
+fn student_t_quantile(p: f64, dof: usize) -> f64 {
+    match dof {
+        1 => (std::f64::consts::PI * (p - 0.5)).tan(),
+        2 => (2.0 * p - 1.0) / (2.0 * p * (1.0 - p)).sqrt(),
+        _ => {
+            let z = normal_quantile(p);
+            let v = dof as f64;
+            let (z3, z5, z7, z9) = (z.powi(3), z.powi(5), z.powi(7), z.powi(9));
+            let g1 = (z3 + z) / 4.0;
+            let g2 = (5.0 * z5 + 16.0 * z3 + 3.0 * z) / 96.0;
+            let g3 = (3.0 * z7 + 19.0 * z5 + 17.0 * z3 - 15.0 * z) / 384.0;
+            let g4 = (79.0 * z9 + 776.0 * z7 + 1482.0 * z5 - 1920.0 * z3 - 945.0 * z) / 92160.0;
+            z + g1 / v + g2 / v.powi(2) + g3 / v.powi(3) + g4 / v.powi(4)
+        }
+    }
+}
+
+/// The `p` quantile of the standard normal distribution, by Acklam's rational approximation.
+fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969_683_028_665_376e1,
+        2.209_460_984_245_205e2,
+        -2.759_285_104_469_687e2,
+        1.383_577_518_672_69e2,
+        -3.066_479_806_614_716e1,
+        2.506_628_277_459_239,
+    ];
+    const B: [f64; 5] = [
+        -5.447_609_879_822_406e1,
+        1.615_858_368_580_409e2,
+        -1.556_989_798_598_866e2,
+        6.680_131_188_771_972e1,
+        -1.328_068_155_288_572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784_894_002_430_293e-3,
+        -3.223_964_580_411_365e-1,
+        -2.400_758_277_161_838,
+        -2.549_732_539_343_734,
+        4.374_664_141_464_968,
+        2.938_163_982_698_783,
+    ];
+    const D: [f64; 4] = [
+        7.784_695_709_041_462e-3,
+        3.224_671_290_700_398e-1,
+        2.445_134_137_142_996,
+        3.754_408_661_907_416,
+    ];
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if p < 0.02425 {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - 0.02425 {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An AR(1) series around `mean` with coefficient `phi`, driven by uniform noise from a seeded LCG.
+    fn ar1(len: usize, mean: f64, phi: f64, start: f64, seed: u64) -> Vec<f64> {
+        let mut state = seed;
+        let mut x = start;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1_442_695_040_888_963_407);
+                let noise = (state >> 11) as f64 / (1u64 << 53) as f64 - 0.5;
+                x = mean + phi * (x - mean) + noise;
+                x
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_quantiles() {
+        assert!((normal_quantile(0.975) - 1.959_964).abs() < 1e-6);
+        assert!((normal_quantile(0.005) + 2.575_829).abs() < 1e-6);
+        assert!((student_t_quantile(0.975, 1) - 12.706_2).abs() < 1e-3);
+        assert!((student_t_quantile(0.975, 2) - 4.302_7).abs() < 1e-3);
+        assert!((student_t_quantile(0.975, 9) - 2.262_2).abs() < 1e-3);
+        assert!((student_t_quantile(0.95, 29) - 1.699_1).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_intervals_cover_the_steady_state_mean() {
+        // starts far from the mean of 10 and is strongly autocorrelated
+        let series = ar1(20_000, 10.0, 0.9, 100.0, 7);
+        let naive = Estimate::from_samples(&series[1000..], 0.95).unwrap();
+        let batched = batch_means(&series, 1000, 0.95).unwrap();
+        assert!(batched.independent());
+        assert!(batched.estimate.samples >= MIN_BATCHES);
+        assert!(batched.estimate.contains(10.0));
+        assert!(batched.estimate.half_width > naive.half_width);
+
+        // too short a warm-up leaves the initial transient in the estimate
+        let sensitivity = warmup_sensitivity(&series[..1000], &[0, 200], 0.95).unwrap();
+        assert!(sensitivity[0].1.estimate.mean > sensitivity[1].1.estimate.mean + 0.5);
+
+        let replications = (0..10)
+            .map(|seed| ar1(2000, 10.0, 0.9, 100.0, seed))
+            .collect::<Vec<_>>();
+        let estimate = replication_deletion(&replications, 200, 0.95).unwrap();
+        assert_eq!(estimate.samples, 10);
+        assert!(estimate.contains(10.0));
+        assert!(replication_deletion(&replications, 2000, 0.95).is_err());
+    }
+}
@@ -0,0 +1,84 @@
+//! Typed time series out of the `Journal`s of a finished run.
+//! Agent and world state is logged as raw `Pod` values, one entry per `write()`. `World` and `Planet` expose
+//! `agent_history::<T>()` and `world_history::<T>()` over these entries, oldest first, along with `*_between()`
+//! variants restricted to a window of local time. `T` has to be the type the journal was written with. Entries a
+//! rollback undid are gone from the journal, so a hybrid run yields only the committed history. Once an arena fills,
+//! a `Journal` keeps only the first of several writes at the same time, so log at most one state per tick for an
+//! exact history.
+use std::ops::RangeBounds;
+
+use bytemuck::{Pod, Zeroable};
+use mesocarp::logging::journal::Journal;
+
+/// Every state logged in `journal`, oldest first, as `(time, state)`.
+pub fn journal_history<T: Pod + Zeroable + 'static>(
+    journal: &Journal,
+) -> impl Iterator<Item = (u64, T)> + '_ {
+    journal
+        .read_all::<T>()
+        .into_iter()
+        .map(|(state, time)| (time, *state))
+}
+
+/// The states logged in `journal` at a time within `window`, oldest first.
+pub fn journal_window<T: Pod + Zeroable + 'static>(
+    journal: &Journal,
+    window: impl RangeBounds<u64> + 'static,
+) -> impl Iterator<Item = (u64, T)> + '_ {
+    journal_history(journal).filter(move |(time, _)| window.contains(time))
+}
+
+/// The last state logged in `journal` at or before `time`.
+pub fn journal_state_at<T: Pod + Zeroable + 'static>(journal: &Journal, time: u64) -> Option<T> {
+    journal_window(journal, ..=time)
+        .last()
+        .map(|(_, state)| state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agents::{Agent, WorldContext},
+        objects::{Action, Event, Msg},
+        st::World,
+    };
+
+    // Doubles its balance every step and logs the world's step count
+    struct Saver;
+
+    impl Agent<8, Msg<u8>> for Saver {
+        fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+            let time = context.time;
+            let journal = context.agent_states[id].state.as_mut().unwrap();
+            let balance = journal.read_state::<u64>().map_or(1, |balance| balance * 2);
+            journal.write(balance, time, None);
+            let steps = context.world_state.read_state::<u32>().map_or(0, |s| *s) + 1;
+            context.world_state.write(steps, time, None);
+            Event::new(time, time, id, Action::Timeout(2))
+        }
+    }
+
+    #[test]
+    fn test_histories_after_run() {
+        let mut world = World::<8, 16, 1, u8>::init(10.0, 1.0, 64).unwrap();
+        world.spawn_agent(Box::new(Saver));
+        world.init_support_layers(Some(64)).unwrap();
+        world.schedule(1, 0).unwrap();
+        world.run().unwrap();
+
+        let history = world.agent_history::<u64>(0).collect::<Vec<_>>();
+        assert_eq!(history, vec![(1, 1), (3, 2), (5, 4), (7, 8), (9, 16)]);
+        let window = world
+            .agent_history_between::<u64>(0, 3..7)
+            .collect::<Vec<_>>();
+        assert_eq!(window, vec![(3, 2), (5, 4)]);
+        assert_eq!(world.agent_history::<u64>(1).count(), 0);
+        let steps = world.world_history_between::<u32>(6..).collect::<Vec<_>>();
+        assert_eq!(steps, vec![(7, 4), (9, 5)]);
+        assert_eq!(world.world_history::<u32>().count(), 5);
+        let journal = world.world_context.agent_states[0].state.as_ref().unwrap();
+        assert_eq!(journal_state_at::<u64>(journal, 6), Some(4));
+        assert_eq!(journal_state_at::<u64>(journal, 0), None);
+    }
+}
@@ -0,0 +1,187 @@
+//! Per-bucket KPIs of a `World` or `Planet`, recorded by the engine without any change to the agents.
+//! A `KpiRecorder` counts the events processed, the `Msg`s delivered and the distinct agents that stepped in every
+//! bucket of `width` ticks. A `Planet` keeps the counts of each tick after GVT apart until GVT passes it, dropping
+//! those a rollback undid, so a bucket only ever holds committed work. `Table::from_kpis()` lays the buckets of any
+//! number of worlds out one row per world and bucket, for the same export paths as every other `Table`.
+use std::collections::BTreeSet;
+
+/// What one world did over the ticks `[start, start + width)`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KpiBucket {
+    pub start: u64,
+    pub events: u64,
+    /// `Msg`s handed to agents, a broadcast counting once per recipient
+    pub delivered: u64,
+    /// distinct agents that processed an event
+    pub active_agents: u64,
+}
+
+/// Counts of a single tick that may still be rolled back.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct TickCounts {
+    time: u64,
+    events: u64,
+    delivered: u64,
+    agents: Vec<usize>,
+}
+
+/// Accumulates the KPIs of world `world` in buckets of `width` ticks.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KpiRecorder {
+    world: usize,
+    width: u64,
+    /// buckets in time order without gaps, the last possibly still filling
+    buckets: Vec<KpiBucket>,
+    /// agents active so far in the last bucket
+    open_agents: BTreeSet<usize>,
+    /// ticks not yet committed, in time order
+    pending: Vec<TickCounts>,
+    /// first tick counted, the end of the warm-up
+    from: u64,
+}
+
+impl KpiRecorder {
+    pub fn new(world: usize, width: u64) -> Self {
+        Self {
+            world,
+            width: width.max(1),
+            ..Self::default()
+        }
+    }
+
+    pub fn world(&self) -> usize {
+        self.world
+    }
+
+    pub fn width(&self) -> u64 {
+        self.width
+    }
+
+    /// Committed buckets so far, oldest first. Every bucket from the first with activity to the last is listed, the
+    /// last possibly still filling until the run ends.
+    pub fn buckets(&self) -> &[KpiBucket] {
+        &self.buckets
+    }
+
+    /// Ignore the ticks before `time`, which belong to the warm-up.
+    pub(crate) fn start_at(&mut self, time: u64) {
+        self.from = time;
+    }
+
+    fn tick(&mut self, time: u64) -> &mut TickCounts {
+        if self.pending.last().is_none_or(|tick| tick.time != time) {
+            self.pending.push(TickCounts {
+                time,
+                ..TickCounts::default()
+            });
+        }
+        self.pending.last_mut().unwrap()
+    }
+
+    /// Count an event of `agent` at `time`.
+    pub(crate) fn event(&mut self, time: u64, agent: usize) {
+        if time < self.from {
+            return;
+        }
+        let tick = self.tick(time);
+        tick.events += 1;
+        tick.agents.push(agent);
+    }
+
+    /// Count `count` `Msg`s handed to agents at `time`.
+    pub(crate) fn delivered(&mut self, time: u64, count: u64) {
+        if count > 0 && time >= self.from {
+            self.tick(time).delivered += count;
+        }
+    }
+
+    /// Fold the ticks before `gvt`, which no rollback can undo any more, into their buckets.
+    pub(crate) fn commit(&mut self, gvt: u64) {
+        let settled = self.pending.partition_point(|tick| tick.time < gvt);
+        let rest = self.pending.split_off(settled);
+        for tick in std::mem::replace(&mut self.pending, rest) {
+            let start = tick.time - tick.time % self.width;
+            let mut next = self
+                .buckets
+                .last()
+                .map_or(start, |bucket| bucket.start + self.width);
+            while self
+                .buckets
+                .last()
+                .is_none_or(|bucket| bucket.start < start)
+            {
+                self.buckets.push(KpiBucket {
+                    start: next,
+                    ..KpiBucket::default()
+                });
+                self.open_agents.clear();
+                next += self.width;
+            }
+            self.open_agents.extend(tick.agents);
+            let bucket = self.buckets.last_mut().unwrap();
+            bucket.events += tick.events;
+            bucket.delivered += tick.delivered;
+            bucket.active_agents = self.open_agents.len() as u64;
+        }
+    }
+
+    /// Drop the counts of the ticks at or after `time`, which a rollback to `time` undid.
+    pub(crate) fn rollback(&mut self, time: u64) {
+        let keep = self.pending.partition_point(|tick| tick.time < time);
+        self.pending.truncate(keep);
+    }
+
+    /// An empty recorder with the same bucket width, for world `world`.
+    pub(crate) fn fork(&self, world: usize) -> Self {
+        Self {
+            from: self.from,
+            ..Self::new(world, self.width)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets_hold_committed_ticks() {
+        let mut recorder = KpiRecorder::new(3, 10);
+        recorder.event(12, 0);
+        recorder.event(12, 1);
+        recorder.delivered(13, 2);
+        recorder.event(15, 0);
+        recorder.event(34, 2);
+        recorder.delivered(36, 1);
+        recorder.commit(30);
+        assert_eq!(
+            recorder.buckets(),
+            [KpiBucket {
+                start: 10,
+                events: 3,
+                delivered: 2,
+                active_agents: 2,
+            }]
+        );
+
+        // the straggler undoes tick 36 but not 34, and the quiet bucket in between is filled in
+        recorder.rollback(35);
+        recorder.event(35, 1);
+        recorder.commit(u64::MAX);
+        assert_eq!(
+            recorder.buckets()[1..],
+            [
+                KpiBucket {
+                    start: 20,
+                    ..KpiBucket::default()
+                },
+                KpiBucket {
+                    start: 30,
+                    events: 2,
+                    delivered: 0,
+                    active_agents: 2,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,15 @@
+//! Post-run analysis utilities for completed simulations.
+//! Provides critical-path analysis over the causal graph recorded during a run, the causal chains behind rollbacks,
+//! typed histories of the logged `Journal`s, confidence intervals for metrics tracked over one or more runs, time
+//! series of timing wheel occupancy and per-bucket KPIs, and, with the `rollback-export` feature, a streaming export
+//! of rollbacks. With the `agent-profiling` feature it also accounts the CPU time of every agent's calls.
+pub mod causality;
+pub mod critical_path;
+pub mod estimates;
+pub mod history;
+pub mod kpi;
+pub mod occupancy;
+#[cfg(feature = "agent-profiling")]
+pub mod profiling;
+#[cfg(feature = "rollback-export")]
+pub mod rollbacks;
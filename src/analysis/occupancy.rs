@@ -0,0 +1,197 @@
+//! Time series of how full the timing wheels of a `World` or `Planet` are.
+//! An `OccupancyRecorder` samples the events waiting on each level of the wheels, and in the overflow queue past
+//! them, at the start of every `every`-th tick. A `Planet` drops the samples a rollback undid, so the series stays
+//! ordered by time. `Table::from_occupancy()` lays the samples of any number of worlds out in long form, one row
+//! per world, time and level, for `scripts/plot_occupancy.py` or any other plotting tool.
+
+/// Events waiting in the wheels of one world at the start of a tick.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OccupancySample {
+    pub time: u64,
+    /// events on each level of the wheels, lowest first
+    pub levels: Vec<u64>,
+    /// events past the wheels, in memory or spilled
+    pub overflow: u64,
+}
+
+/// Samples the wheel occupancy of world `world` every `every` ticks.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OccupancyRecorder {
+    world: usize,
+    every: u64,
+    samples: Vec<OccupancySample>,
+    /// time of the first sample, the end of the warm-up
+    from: u64,
+}
+
+impl OccupancyRecorder {
+    pub fn new(world: usize, every: u64) -> Self {
+        Self {
+            world,
+            every: every.max(1),
+            samples: Vec::new(),
+            from: 0,
+        }
+    }
+
+    pub fn world(&self) -> usize {
+        self.world
+    }
+
+    pub fn every(&self) -> u64 {
+        self.every
+    }
+
+    /// Samples taken so far, oldest first.
+    pub fn samples(&self) -> &[OccupancySample] {
+        &self.samples
+    }
+
+    /// Take no samples before `time`, which belong to the warm-up.
+    pub(crate) fn start_at(&mut self, time: u64) {
+        self.from = time;
+    }
+
+    /// Whether a sample is due at the start of the tick at `time`.
+    pub(crate) fn due(&self, time: u64) -> bool {
+        time >= self.from
+            && time.is_multiple_of(self.every)
+            && self.samples.last().is_none_or(|sample| sample.time < time)
+    }
+
+    /// First tick from `time` on at the start of which a sample is due.
+    pub(crate) fn next_due(&self, time: u64) -> u64 {
+        let time = time.max(self.from);
+        let next = time.div_ceil(self.every).saturating_mul(self.every);
+        if next == time && !self.due(time) {
+            return next.saturating_add(self.every);
+        }
+        next
+    }
+
+    pub(crate) fn record(&mut self, time: u64, levels: Vec<u64>, overflow: u64) {
+        self.samples.push(OccupancySample {
+            time,
+            levels,
+            overflow,
+        });
+    }
+
+    /// Drop the samples taken at or after `time`, which a rollback to `time` undid.
+    pub(crate) fn rollback(&mut self, time: u64) {
+        let keep = self.samples.partition_point(|sample| sample.time < time);
+        self.samples.truncate(keep);
+    }
+
+    /// An empty recorder with the same period, for world `world`.
+    pub(crate) fn fork(&self, world: usize) -> Self {
+        Self {
+            from: self.from,
+            ..Self::new(world, self.every)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        agents::{Agent, PlanetContext, ThreadedAgent, WorldContext},
+        export::Value,
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+        st::World,
+    };
+
+    // Sleeps `delay` ticks between steps
+    struct Sleeper {
+        delay: u64,
+    }
+
+    impl Agent<8, Msg<u8>> for Sleeper {
+        fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, agent_id: usize) -> Event {
+            Event::new(
+                context.time,
+                context.time,
+                agent_id,
+                Action::Timeout(self.delay),
+            )
+        }
+    }
+
+    impl ThreadedAgent<16, u64> for Sleeper {
+        fn step(&mut self, context: &mut PlanetContext<16, u64>, agent_id: usize) -> Event {
+            Event::new(
+                context.time,
+                context.time,
+                agent_id,
+                Action::Timeout(self.delay),
+            )
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, u64>,
+            _msg: Msg<u64>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_wheel_occupancy() {
+        let mut world = World::<8, 16, 2, u8>::init(100.0, 1.0, 0).unwrap();
+        world.spawn_agent(Box::new(Sleeper { delay: 1 }));
+        world.spawn_agent(Box::new(Sleeper { delay: 40 }));
+        world.init_support_layers(None).unwrap();
+        world.enable_occupancy_recording(10);
+        world.schedule_all_agents(0).unwrap();
+        world.run().unwrap();
+
+        let samples = world.occupancy().unwrap().samples();
+        let times = samples.iter().map(|sample| sample.time).collect::<Vec<_>>();
+        assert_eq!(times[..3], [0, 10, 20]);
+        // both agents always wait somewhere, the sleeper far enough out to reach the upper level at times
+        for sample in samples.iter().filter(|sample| sample.time < 80) {
+            assert_eq!(sample.levels.len(), 2);
+            assert_eq!(sample.levels.iter().sum::<u64>() + sample.overflow, 2);
+        }
+        assert!(samples.iter().any(|sample| sample.levels[1] > 0));
+
+        let mut recorder = world.occupancy().unwrap().clone();
+        recorder.rollback(10);
+        assert_eq!(recorder.samples().len(), 1);
+        assert!(recorder.due(10) && !recorder.due(5));
+
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(30.0, 1.0)
+            .with_optimistic_sync(4, 10)
+            .with_uniform_worlds(16, 2, 16)
+            .with_wheel_occupancy(5);
+        let mut engine = HybridEngine::<16, 16, 2, u64>::create(config).unwrap();
+        for planet in 0..2 {
+            engine
+                .spawn_agent(planet, Box::new(Sleeper { delay: 3 }))
+                .unwrap();
+        }
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+        let table = engine.occupancy_table().unwrap();
+        let names = table
+            .columns()
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["world", "time", "level", "events"]);
+        for world in 0..2 {
+            // two levels and the overflow per sample, taken at 0, 5, ..., 25
+            let rows = table
+                .rows()
+                .iter()
+                .filter(|row| row[0] == Value::UInt(world))
+                .collect::<Vec<_>>();
+            assert_eq!(rows.len(), 6 * 3);
+            assert_eq!(rows[3][1], Value::UInt(5));
+            assert_eq!(rows[2][2], Value::Int(-1));
+        }
+    }
+}
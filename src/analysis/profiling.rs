@@ -0,0 +1,334 @@
+//! Per-agent CPU accounting for finding expensive agents, behind the `agent-profiling` feature.
+//! With `HybridEngine::enable_agent_profiling()` every `Planet` reads the CPU's time-stamp counter around each call
+//! of an agent's `step()` and `read_message()`, and counts the cycles in a histogram of power-of-two buckets per
+//! agent and kind of call. Calls a rollback undid still count, as the CPU spent them. A `ProfileReport` turns cycles
+//! into wall time at the rate the counter ran over the profiled run, and ranks the agents by the time they took.
+//! Targets without a time-stamp counter read `Instant` instead, at nanosecond resolution. Without the feature none of
+//! this is compiled in; with it but profiling not enabled, a `Planet` pays one branch per call.
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+/// Buckets of a `CallHistogram`, one per bit of a cycle count.
+pub const BUCKETS: usize = 64;
+
+/// Current reading of the time-stamp counter.
+#[inline(always)]
+pub fn cycles() -> u64 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        // every x86_64 CPU has the time-stamp counter
+        unsafe { std::arch::x86_64::_rdtsc() }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        static EPOCH: std::sync::OnceLock<Instant> = std::sync::OnceLock::new();
+        EPOCH.get_or_init(Instant::now).elapsed().as_nanos() as u64
+    }
+}
+
+/// Cycles taken by the calls of one kind, counted in buckets of powers of two: bucket `b` holds the calls that took
+/// fewer than `2^b` cycles but at least `2^(b - 1)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallHistogram {
+    calls: u64,
+    cycles: u64,
+    max: u64,
+    buckets: [u64; BUCKETS],
+}
+
+impl Default for CallHistogram {
+    fn default() -> Self {
+        Self {
+            calls: 0,
+            cycles: 0,
+            max: 0,
+            buckets: [0; BUCKETS],
+        }
+    }
+}
+
+impl CallHistogram {
+    pub(crate) fn record(&mut self, cycles: u64) {
+        self.calls += 1;
+        self.cycles = self.cycles.saturating_add(cycles);
+        self.max = self.max.max(cycles);
+        let bucket = (u64::BITS - cycles.leading_zeros()) as usize;
+        self.buckets[bucket.min(BUCKETS - 1)] += 1;
+    }
+
+    fn merge(&mut self, other: &CallHistogram) {
+        self.calls += other.calls;
+        self.cycles = self.cycles.saturating_add(other.cycles);
+        self.max = self.max.max(other.max);
+        for (bucket, calls) in self.buckets.iter_mut().zip(&other.buckets) {
+            *bucket += calls;
+        }
+    }
+
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+
+    /// Cycles of every call together.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Cycles of the slowest call.
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    pub fn buckets(&self) -> &[u64; BUCKETS] {
+        &self.buckets
+    }
+
+    /// Upper bound in cycles of the bucket holding the `q` quantile of the calls.
+    pub fn quantile(&self, q: f64) -> Option<u64> {
+        let rank = (q.clamp(0.0, 1.0) * self.calls as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        self.buckets.iter().enumerate().find_map(|(bucket, calls)| {
+            seen += calls;
+            (seen >= rank).then(|| {
+                1u64.checked_shl(bucket as u32)
+                    .map_or(u64::MAX, |bound| bound - 1)
+            })
+        })
+    }
+}
+
+/// The calls into one agent.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AgentCalls {
+    pub step: CallHistogram,
+    pub read_message: CallHistogram,
+}
+
+impl AgentCalls {
+    /// Cycles of every call together.
+    pub fn cycles(&self) -> u64 {
+        self.step
+            .cycles()
+            .saturating_add(self.read_message.cycles())
+    }
+
+    pub fn calls(&self) -> u64 {
+        self.step.calls() + self.read_message.calls()
+    }
+}
+
+/// Which agent method a call was to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Call {
+    Step,
+    ReadMessage,
+}
+
+/// The calls into the agents of one `Planet`.
+#[derive(Clone, Debug)]
+pub struct AgentProfiler {
+    world: usize,
+    agents: Vec<AgentCalls>,
+    /// when profiling started, to measure the rate of the counter
+    started: (Instant, u64),
+}
+
+impl AgentProfiler {
+    pub(crate) fn new(world: usize) -> Self {
+        Self {
+            world,
+            agents: Vec::new(),
+            started: (Instant::now(), cycles()),
+        }
+    }
+
+    /// A profiler for the `Planet` `world` split off from this one, sharing its start.
+    pub(crate) fn fork(&self, world: usize) -> Self {
+        Self {
+            world,
+            agents: Vec::new(),
+            started: self.started,
+        }
+    }
+
+    /// Count a call into `agent` that took `cycles`.
+    pub(crate) fn record(&mut self, agent: usize, call: Call, cycles: u64) {
+        if agent >= self.agents.len() {
+            self.agents.resize_with(agent + 1, AgentCalls::default);
+        }
+        let calls = &mut self.agents[agent];
+        match call {
+            Call::Step => calls.step.record(cycles),
+            Call::ReadMessage => calls.read_message.record(cycles),
+        }
+    }
+
+    pub fn world(&self) -> usize {
+        self.world
+    }
+
+    /// The calls into every agent called so far, by agent.
+    pub fn agents(&self) -> &[AgentCalls] {
+        &self.agents
+    }
+}
+
+/// One of the most expensive agents of a run.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HotSpot {
+    pub world: usize,
+    pub agent: usize,
+    /// time spent in `step()` and `read_message()` together
+    pub total: Duration,
+    pub calls: u64,
+    /// time of the slowest call
+    pub slowest: Duration,
+}
+
+/// The calls into every agent of a run, with the rate of the counter that timed them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileReport {
+    /// wall time per counter cycle, in nanoseconds
+    pub ns_per_cycle: f64,
+    /// calls by `(world, agent)`
+    pub agents: BTreeMap<(usize, usize), AgentCalls>,
+}
+
+impl ProfileReport {
+    pub(crate) fn new<'a>(profilers: impl IntoIterator<Item = &'a AgentProfiler>) -> Option<Self> {
+        let mut agents = BTreeMap::new();
+        let mut started = None;
+        for profiler in profilers {
+            started = started.or(Some(profiler.started));
+            for (agent, calls) in profiler.agents.iter().enumerate() {
+                let entry: &mut AgentCalls = agents.entry((profiler.world, agent)).or_default();
+                entry.step.merge(&calls.step);
+                entry.read_message.merge(&calls.read_message);
+            }
+        }
+        let (at, counted) = started?;
+        let elapsed = at.elapsed().as_nanos() as f64;
+        let ticks = cycles().saturating_sub(counted);
+        let ns_per_cycle = if ticks > 0 {
+            elapsed / ticks as f64
+        } else {
+            1.0
+        };
+        Some(Self {
+            ns_per_cycle,
+            agents,
+        })
+    }
+
+    /// Wall time of `cycles` of the counter.
+    pub fn duration(&self, cycles: u64) -> Duration {
+        Duration::from_nanos((cycles as f64 * self.ns_per_cycle) as u64)
+    }
+
+    /// The `k` agents that took the most time, most expensive first.
+    pub fn top(&self, k: usize) -> Vec<HotSpot> {
+        let mut spots = self
+            .agents
+            .iter()
+            .filter(|(_, calls)| calls.calls() > 0)
+            .map(|(&(world, agent), calls)| HotSpot {
+                world,
+                agent,
+                total: self.duration(calls.cycles()),
+                calls: calls.calls(),
+                slowest: self.duration(calls.step.max().max(calls.read_message.max())),
+            })
+            .collect::<Vec<_>>();
+        spots.sort_by(|a, b| {
+            b.total
+                .cmp(&a.total)
+                .then((a.world, a.agent).cmp(&(b.world, b.agent)))
+        });
+        spots.truncate(k);
+        spots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    // Burns `work` rounds of arithmetic every step
+    struct Worker {
+        work: u64,
+    }
+
+    impl ThreadedAgent<16, u64> for Worker {
+        fn step(&mut self, context: &mut PlanetContext<16, u64>, id: usize) -> Event {
+            let mut acc = context.time;
+            for round in 0..self.work {
+                acc = std::hint::black_box(acc.wrapping_mul(31).wrapping_add(round));
+            }
+            std::hint::black_box(acc);
+            Event::new(context.time, context.time, id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, u64>,
+            _msg: Msg<u64>,
+            _id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut calls = CallHistogram::default();
+        for cycles in [0, 1, 3, 100, 100, 5000] {
+            calls.record(cycles);
+        }
+        assert_eq!(calls.calls(), 6);
+        assert_eq!(calls.cycles(), 5204);
+        assert_eq!(calls.max(), 5000);
+        assert_eq!(calls.buckets()[..3], [1, 1, 1]);
+        // 100 falls in [64, 128)
+        assert_eq!(calls.buckets()[7], 2);
+        assert_eq!(calls.quantile(0.5), Some(3));
+        assert_eq!(calls.quantile(0.8), Some(127));
+        assert_eq!(calls.quantile(1.0), Some(8191));
+        assert_eq!(CallHistogram::default().quantile(0.5), None);
+    }
+
+    #[test]
+    fn test_hot_spots() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(50.0, 1.0)
+            .with_optimistic_sync(4, 10)
+            .with_uniform_worlds(16, 2, 16);
+        let mut engine = HybridEngine::<16, 16, 2, u64>::create(config).unwrap();
+        engine
+            .spawn_agent(0, Box::new(Worker { work: 10 }))
+            .unwrap();
+        engine.spawn_agent(1, Box::new(Worker { work: 0 })).unwrap();
+        engine
+            .spawn_agent(1, Box::new(Worker { work: 200_000 }))
+            .unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        assert!(engine.agent_profile().is_none());
+        engine.enable_agent_profiling();
+        let engine = engine.run().unwrap();
+
+        let report = engine.agent_profile().unwrap();
+        assert_eq!(report.agents.len(), 3);
+        assert_eq!(report.agents[&(1, 1)].step.calls(), 49);
+        assert_eq!(report.agents[&(1, 1)].read_message.calls(), 0);
+        let top = report.top(2);
+        assert_eq!(top.len(), 2);
+        assert_eq!((top[0].world, top[0].agent), (1, 1));
+        assert!(top[0].total >= top[1].total);
+        assert!(top[0].slowest > Duration::ZERO);
+    }
+}
@@ -0,0 +1,277 @@
+//! Streaming export of rollbacks for offline analysis, behind the `rollback-export` feature.
+//! With `HybridEngine::export_rollbacks()` every `Planet` records a `RollbackRecord` for each straggler that rolls
+//! it back: how deep it went, how far the `Planet` was ahead of GVT, the straggler's metadata and the mail rates of
+//! the last few steps. Records are collected column by column and written to the shared sink one block at a time,
+//! each block a JSON object of equal-length column arrays on its own line, so long runs stream out as they go.
+//! Without the feature none of this is compiled in; with it but no export requested, a `Planet` pays one branch per
+//! poll and step.
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+use crate::AikaError;
+
+/// Steps the recent mail rates are averaged over.
+pub const RATE_WINDOW: usize = 32;
+
+/// One rollback and the features of the `Planet` when it happened.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RollbackRecord {
+    pub world: usize,
+    /// local time before the rollback
+    pub from: u64,
+    /// local time rolled back to
+    pub to: u64,
+    pub gvt: u64,
+    /// world and agent that sent the straggler
+    pub cause_world: usize,
+    pub cause_agent: usize,
+    /// recipient of the straggler, `None` for a broadcast
+    pub cause_to: Option<usize>,
+    pub cause_sent: u64,
+    pub cause_recv: u64,
+    /// whether the straggler was an anti-message
+    pub cause_anti: bool,
+    /// interplanetary letters received per step over the last `RATE_WINDOW` steps
+    pub recv_rate: f64,
+    /// `Msg`s sent per step over the last `RATE_WINDOW` steps
+    pub send_rate: f64,
+}
+
+impl RollbackRecord {
+    pub fn depth(&self) -> u64 {
+        self.from - self.to
+    }
+
+    /// How far the `Planet` had run ahead of GVT.
+    pub fn lvt_gvt_gap(&self) -> u64 {
+        self.from.saturating_sub(self.gvt)
+    }
+}
+
+/// Rollback records stored column by column.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RollbackColumns {
+    pub world: Vec<usize>,
+    pub from: Vec<u64>,
+    pub to: Vec<u64>,
+    pub depth: Vec<u64>,
+    pub gvt: Vec<u64>,
+    pub lvt_gvt_gap: Vec<u64>,
+    pub cause_world: Vec<usize>,
+    pub cause_agent: Vec<usize>,
+    pub cause_to: Vec<Option<usize>>,
+    pub cause_sent: Vec<u64>,
+    pub cause_recv: Vec<u64>,
+    pub cause_anti: Vec<bool>,
+    pub recv_rate: Vec<f64>,
+    pub send_rate: Vec<f64>,
+}
+
+impl RollbackColumns {
+    pub fn len(&self) -> usize {
+        self.world.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.world.is_empty()
+    }
+
+    pub fn push(&mut self, record: RollbackRecord) {
+        self.world.push(record.world);
+        self.from.push(record.from);
+        self.to.push(record.to);
+        self.depth.push(record.depth());
+        self.gvt.push(record.gvt);
+        self.lvt_gvt_gap.push(record.lvt_gvt_gap());
+        self.cause_world.push(record.cause_world);
+        self.cause_agent.push(record.cause_agent);
+        self.cause_to.push(record.cause_to);
+        self.cause_sent.push(record.cause_sent);
+        self.cause_recv.push(record.cause_recv);
+        self.cause_anti.push(record.cause_anti);
+        self.recv_rate.push(record.recv_rate);
+        self.send_rate.push(record.send_rate);
+    }
+
+    /// Render the block as one line of JSON, one array per column.
+    pub fn to_json_line(&self) -> String {
+        fn column<T>(out: &mut String, name: &str, values: &[T], render: impl Fn(&T) -> String) {
+            if !out.ends_with('{') {
+                out.push(',');
+            }
+            let values = values.iter().map(render).collect::<Vec<_>>().join(",");
+            let _ = write!(out, "\"{name}\":[{values}]");
+        }
+        let mut out = String::from("{");
+        column(&mut out, "world", &self.world, usize::to_string);
+        column(&mut out, "from", &self.from, u64::to_string);
+        column(&mut out, "to", &self.to, u64::to_string);
+        column(&mut out, "depth", &self.depth, u64::to_string);
+        column(&mut out, "gvt", &self.gvt, u64::to_string);
+        column(&mut out, "lvt_gvt_gap", &self.lvt_gvt_gap, u64::to_string);
+        column(&mut out, "cause_world", &self.cause_world, usize::to_string);
+        column(&mut out, "cause_agent", &self.cause_agent, usize::to_string);
+        column(&mut out, "cause_to", &self.cause_to, |to| {
+            to.map_or("null".to_string(), |to| to.to_string())
+        });
+        column(&mut out, "cause_sent", &self.cause_sent, u64::to_string);
+        column(&mut out, "cause_recv", &self.cause_recv, u64::to_string);
+        column(&mut out, "cause_anti", &self.cause_anti, bool::to_string);
+        column(&mut out, "recv_rate", &self.recv_rate, f64::to_string);
+        column(&mut out, "send_rate", &self.send_rate, f64::to_string);
+        out.push('}');
+        out
+    }
+}
+
+/// Writer shared by every `Planet` of a run.
+#[derive(Clone)]
+pub struct RollbackSink(Arc<Mutex<Box<dyn Write + Send>>>);
+
+impl RollbackSink {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(Box::new(writer))))
+    }
+
+    fn write_block(&self, block: &RollbackColumns) -> Result<(), AikaError> {
+        let mut writer = self.0.lock().map_err(|_| AikaError::PoisonedLock)?;
+        writeln!(writer, "{}", block.to_json_line())?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Per-`Planet` buffer of rollback records and recent mail rates.
+pub(crate) struct RollbackExporter {
+    sink: RollbackSink,
+    block_rows: usize,
+    block: RollbackColumns,
+    /// `(letters received, Msgs sent)` per recent step
+    window: VecDeque<(u64, u64)>,
+    received: u64,
+}
+
+impl RollbackExporter {
+    pub(crate) fn new(sink: RollbackSink, block_rows: usize) -> Self {
+        Self {
+            sink,
+            block_rows: block_rows.max(1),
+            block: RollbackColumns::default(),
+            window: VecDeque::with_capacity(RATE_WINDOW),
+            received: 0,
+        }
+    }
+
+    /// A fresh exporter writing to the same sink, e.g. for a `Planet` created by a split.
+    pub(crate) fn fork(&self) -> Self {
+        Self::new(self.sink.clone(), self.block_rows)
+    }
+
+    pub(crate) fn receive(&mut self, letters: u64) {
+        self.received += letters;
+    }
+
+    /// Close a step in which `sent` `Msg`s were sent.
+    pub(crate) fn tick(&mut self, sent: u64) {
+        if self.window.len() == RATE_WINDOW {
+            self.window.pop_front();
+        }
+        self.window
+            .push_back((std::mem::take(&mut self.received), sent));
+    }
+
+    /// Recent `(received, sent)` mail per step.
+    pub(crate) fn rates(&self) -> (f64, f64) {
+        if self.window.is_empty() {
+            return (0.0, 0.0);
+        }
+        let (received, sent) = self
+            .window
+            .iter()
+            .fold((0, 0), |(r, s), (received, sent)| (r + received, s + sent));
+        let steps = self.window.len() as f64;
+        (received as f64 / steps, sent as f64 / steps)
+    }
+
+    pub(crate) fn record(&mut self, record: RollbackRecord) -> Result<(), AikaError> {
+        self.block.push(record);
+        if self.block.len() >= self.block_rows {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write out the records buffered so far.
+    pub(crate) fn flush(&mut self) -> Result<(), AikaError> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        self.sink.write_block(&std::mem::take(&mut self.block))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn record(from: u64, to: u64) -> RollbackRecord {
+        RollbackRecord {
+            world: 1,
+            from,
+            to,
+            gvt: 2,
+            cause_world: 0,
+            cause_agent: 3,
+            cause_to: None,
+            cause_sent: to - 1,
+            cause_recv: to,
+            cause_anti: false,
+            recv_rate: 0.0,
+            send_rate: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_rollbacks_stream_in_blocks() {
+        let out = Shared::default();
+        let mut exporter = RollbackExporter::new(RollbackSink::new(out.clone()), 2);
+        exporter.receive(3);
+        exporter.tick(1);
+        exporter.tick(3);
+        assert_eq!(exporter.rates(), (1.5, 2.0));
+
+        exporter.record(record(9, 5)).unwrap();
+        assert!(out.0.lock().unwrap().is_empty());
+        exporter.record(record(12, 10)).unwrap();
+        exporter.record(record(14, 13)).unwrap();
+        exporter.flush().unwrap();
+
+        let text = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(
+            lines[0].starts_with("{\"world\":[1,1],\"from\":[9,12],\"to\":[5,10],\"depth\":[4,2]")
+        );
+        assert!(lines[0].contains("\"lvt_gvt_gap\":[7,10]"));
+        assert!(lines[0].contains("\"cause_to\":[null,null]"));
+        assert!(lines[1].contains("\"depth\":[1]"));
+    }
+}
@@ -0,0 +1,331 @@
+//! Timing wheels sized at runtime.
+//! The `Clock` of `mesocarp` is sized by its `CLOCK_SLOTS` and `CLOCK_HEIGHT` const generics, and a hierarchy too
+//! shallow for a model silently pushes most of its events into the overflow queue. A `DynClock` picks its slots and
+//! height from the run it is built for instead, with a bottom wheel spanning the throttle horizon and enough levels
+//! to span the whole run. If the overflow queue behind it still fills up, the hierarchy grows another level. Select it
+//! for every `Planet` with `HybridConfig::with_dynamic_clock()`.
+use mesocarp::scheduling::Scheduleable;
+
+use crate::AikaError;
+
+/// Fewest slots per wheel.
+const MIN_SLOTS: u64 = 16;
+/// Most slots per wheel.
+const MAX_SLOTS: u64 = 1024;
+/// Most levels a `DynClock` starts with, before any growth.
+const MAX_HEIGHT: usize = 4;
+
+/// Slots per wheel and number of wheels of a `DynClock`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ClockSizing {
+    pub slots: u64,
+    pub height: usize,
+}
+
+impl ClockSizing {
+    /// Wheels for a run to `terminal` in steps of `timestep`, whose bottom wheel spans `throttle_horizon` steps.
+    pub fn for_run(terminal: f64, timestep: f64, throttle_horizon: u64) -> Self {
+        let slots = throttle_horizon
+            .saturating_add(1)
+            .checked_next_power_of_two()
+            .unwrap_or(MAX_SLOTS)
+            .clamp(MIN_SLOTS, MAX_SLOTS);
+        let steps = if timestep > 0.0 {
+            (terminal / timestep).ceil() as u64
+        } else {
+            0
+        };
+        let mut height = 1;
+        let mut span = slots as u128;
+        while span <= steps as u128 && height < MAX_HEIGHT {
+            span *= slots as u128;
+            height += 1;
+        }
+        Self { slots, height }
+    }
+}
+
+/// Hierarchical timing wheel whose height can grow at runtime. Level `k` holds the items that share every digit
+/// above the `k`-th (in base `slots`) with the current time, in the slot of their `k`-th digit.
+#[derive(Debug)]
+pub struct DynClock<T: Scheduleable> {
+    wheels: Vec<Vec<Vec<T>>>,
+    slots: u64,
+    pub time: u64,
+    /// levels added since the clock was built
+    grown: u64,
+}
+
+impl<T: Scheduleable> DynClock<T> {
+    pub fn new(sizing: ClockSizing, time: u64) -> Result<Self, AikaError> {
+        if sizing.slots < 2 || sizing.height < 1 {
+            return Err(AikaError::ConfigError(format!(
+                "a clock needs at least 2 slots and 1 level, got {sizing:?}"
+            )));
+        }
+        let wheels = (0..sizing.height)
+            .map(|_| (0..sizing.slots).map(|_| Vec::new()).collect())
+            .collect();
+        Ok(Self {
+            wheels,
+            slots: sizing.slots,
+            time,
+            grown: 0,
+        })
+    }
+
+    pub fn sizing(&self) -> ClockSizing {
+        ClockSizing {
+            slots: self.slots,
+            height: self.wheels.len(),
+        }
+    }
+
+    /// Number of levels added since the clock was built.
+    pub fn grown(&self) -> u64 {
+        self.grown
+    }
+
+    /// `slots^level`, saturating at `u64::MAX + 1`.
+    fn span(&self, level: usize) -> u128 {
+        (self.slots as u128)
+            .checked_pow(level as u32)
+            .map_or(u64::MAX as u128 + 1, |span| span.min(u64::MAX as u128 + 1))
+    }
+
+    /// First time past the top wheel, which has to wait in an overflow queue.
+    pub fn until(&self) -> u64 {
+        self.limit().min(u64::MAX as u128) as u64
+    }
+
+    fn limit(&self) -> u128 {
+        let top = self.span(self.wheels.len());
+        (self.time as u128 / top + 1) * top
+    }
+
+    /// Place `item` on the wheels, handing it back if it lies past the top wheel. Items before the current time land
+    /// in the current slot, to be reported as time travel when it comes around again.
+    pub fn insert(&mut self, item: T) -> Result<(), T> {
+        let time = item.time().max(self.time);
+        if time as u128 >= self.limit() {
+            return Err(item);
+        }
+        for level in 0..self.wheels.len() {
+            let above = self.span(level + 1);
+            if time as u128 / above == self.time as u128 / above {
+                let slot = (time as u128 / self.span(level) % self.slots as u128) as usize;
+                self.wheels[level][slot].push(item);
+                return Ok(());
+            }
+        }
+        Err(item)
+    }
+
+    /// Take the items of the current step.
+    pub fn tick(&mut self) -> Result<Vec<T>, AikaError> {
+        let slot = (self.time % self.slots) as usize;
+        let items = std::mem::take(&mut self.wheels[0][slot]);
+        if items.iter().any(|item| item.time() < self.time) {
+            return Err(AikaError::TimeTravel);
+        }
+        Ok(items)
+    }
+
+    /// Move one step forward, cascading the items of each level whose digit just rolled over.
+    pub fn increment(&mut self) {
+        self.time = self.time.saturating_add(1);
+        let rolled = (1..self.wheels.len())
+            .take_while(|level| (self.time as u128).is_multiple_of(self.span(*level)))
+            .last();
+        let Some(top) = rolled else {
+            return;
+        };
+        for level in (1..=top).rev() {
+            let slot = (self.time as u128 / self.span(level) % self.slots as u128) as usize;
+            for item in std::mem::take(&mut self.wheels[level][slot]) {
+                // a cascaded item always fits a lower level
+                let _ = self.insert(item);
+            }
+        }
+    }
+
+    /// Time of the earliest item. Every item on a level is due before those on the levels above, so only the lowest
+    /// occupied level is searched.
+    pub fn next_due(&self) -> Option<u64> {
+        self.wheels
+            .iter()
+            .find(|level| level.iter().any(|slot| !slot.is_empty()))
+            .and_then(|level| level.iter().flatten().map(Scheduleable::time).min())
+    }
+
+    /// Move forward to `time` without handing out any item, so nothing may be due before `time`. A short gap is
+    /// stepped through, a long one places the items again relative to `time`.
+    pub fn skip_to(&mut self, time: u64) {
+        if time.saturating_sub(self.time) <= self.slots {
+            while self.time < time {
+                self.increment();
+            }
+            return;
+        }
+        let items = self.drain(|_| true);
+        self.time = time;
+        for item in items {
+            // the wheels reach at least as far from a later time, so every item still fits
+            let _ = self.insert(item);
+        }
+    }
+
+    /// Add a level on top, unless the top wheel already spans every time. Returns whether it grew.
+    pub fn grow(&mut self) -> bool {
+        if self.span(self.wheels.len()) > u64::MAX as u128 {
+            return false;
+        }
+        self.wheels
+            .push((0..self.slots).map(|_| Vec::new()).collect());
+        self.grown += 1;
+        true
+    }
+
+    /// Drop every item and restart at `time`, keeping the current sizing.
+    pub fn reset(&mut self, time: u64) {
+        for slot in self.wheels.iter_mut().flatten() {
+            slot.clear();
+        }
+        self.time = time;
+    }
+
+    /// Remove every item matching `pred`.
+    pub fn drain(&mut self, pred: impl Fn(&T) -> bool) -> Vec<T> {
+        let mut drained = Vec::new();
+        for slot in self.wheels.iter_mut().flatten() {
+            let (matching, rest): (Vec<T>, Vec<T>) =
+                std::mem::take(slot).into_iter().partition(&pred);
+            *slot = rest;
+            drained.extend(matching);
+        }
+        drained
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.wheels.iter().flatten().flatten()
+    }
+
+    /// Items on each level, lowest first.
+    pub fn occupancy(&self) -> Vec<u64> {
+        self.wheels
+            .iter()
+            .map(|level| level.iter().map(|slot| slot.len() as u64).sum())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    };
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, LocalEventSystem, Msg},
+    };
+
+    // Steps every 3 ticks, counting its steps
+    struct Walker {
+        steps: Arc<AtomicU64>,
+    }
+
+    impl ThreadedAgent<128, u64> for Walker {
+        fn step(&mut self, context: &mut PlanetContext<128, u64>, agent_id: usize) -> Event {
+            self.steps.fetch_add(1, Ordering::Relaxed);
+            Event::new(context.time, context.time, agent_id, Action::Timeout(3))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, u64>,
+            _msg: Msg<u64>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_dyn_clock_grows_under_pressure() {
+        let config = HybridConfig::new(1, 64)
+            .with_time_bounds(100_000.0, 1.0)
+            .with_optimistic_sync(10, 100)
+            .with_dynamic_clock(true);
+        let sizing = config.clock_sizing().unwrap();
+        assert_eq!(
+            sizing,
+            ClockSizing {
+                slots: 16,
+                height: 4
+            }
+        );
+        assert_eq!(ClockSizing::for_run(10.0, 1.0, 1).height, 1);
+
+        let mut clock = DynClock::new(
+            ClockSizing {
+                slots: 4,
+                height: 2,
+            },
+            0,
+        )
+        .unwrap();
+        assert_eq!(clock.until(), 16);
+        for time in [0, 3, 5, 15] {
+            clock.insert(Event::new(0, time, 0, Action::Wait)).unwrap();
+        }
+        assert!(clock.insert(Event::new(0, 16, 0, Action::Wait)).is_err());
+        let mut seen = Vec::new();
+        for _ in 0..16 {
+            seen.extend(clock.tick().unwrap().iter().map(|event| event.time));
+            clock.increment();
+        }
+        assert_eq!(seen, vec![0, 3, 5, 15]);
+
+        // two slots and one level only span two steps, so the overflow queue fills up and the clock grows
+        let mut system = LocalEventSystem::<16, 1>::new().unwrap();
+        system
+            .set_dynamic_clock(ClockSizing {
+                slots: 2,
+                height: 1,
+            })
+            .unwrap();
+        for time in (0..40).rev() {
+            system.insert(Event::new(0, time, 0, Action::Wait)).unwrap();
+        }
+        let stats = system.overflow_stats();
+        assert!(stats.grown >= 4);
+        assert!(stats.in_memory <= 2);
+        let mut seen = Vec::new();
+        for _ in 0..40 {
+            seen.extend(system.tick().unwrap().iter().map(|event| event.time));
+            system.increment().unwrap();
+        }
+        assert_eq!(seen, (0..40).collect::<Vec<_>>());
+
+        // the const generic wheels of this engine span a single step
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(60.0, 1.0)
+            .with_optimistic_sync(4, 10)
+            .with_uniform_worlds(16, 1, 16)
+            .with_dynamic_clock(true);
+        let mut engine = HybridEngine::<128, 2, 1, u64>::create(config).unwrap();
+        let steps = Arc::new(AtomicU64::new(0));
+        for planet in 0..2 {
+            let walker = Walker {
+                steps: steps.clone(),
+            };
+            engine.spawn_agent(planet, Box::new(walker)).unwrap();
+            engine.schedule(planet, 0, 1).unwrap();
+        }
+        engine.run().unwrap();
+        assert_eq!(steps.load(Ordering::Relaxed), 40);
+    }
+}
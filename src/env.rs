@@ -0,0 +1,243 @@
+//! Gym-style environments for training agents with reinforcement learning against a `HybridEngine`.
+//! An `Environment` says how to build the engine of a fresh episode, how to hand it the learner's actions and how
+//! to observe and score it. A `SimEnv` drives it: `reset()` builds a new engine and observes it before it runs, and
+//! every `step()` applies the actions, advances GVT by a fixed interval with `HybridEngine::advance()` and observes
+//! the engine there. Every `Planet` is parked at the new GVT when it is observed, so an observation never sees work
+//! a rollback could still undo, and the same actions on the same episode give the same observations.
+use bytemuck::{Pod, Zeroable};
+
+use crate::{mt::hybrid::HybridEngine, AikaError};
+
+/// What a step earned the learner.
+pub type Reward = f64;
+
+/// A simulation a learner acts on, through a `SimEnv`.
+pub trait Environment<
+    const INTER_SLOTS: usize,
+    const CLOCK_SLOTS: usize,
+    const CLOCK_HEIGHT: usize,
+    MessageType: Pod + Zeroable + Clone,
+>
+{
+    type Observation;
+    type Actions;
+
+    /// The engine of a new episode, with its agents spawned and scheduled.
+    fn build(
+        &mut self,
+    ) -> Result<HybridEngine<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>, AikaError>;
+
+    /// Hand `actions` to the parked engine, e.g. by changing its agents, scheduling them or sending control actions,
+    /// which the planets apply at GVT when they resume.
+    fn apply(
+        &mut self,
+        engine: &mut HybridEngine<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
+        actions: Self::Actions,
+    ) -> Result<(), AikaError>;
+
+    /// Observe the engine, parked at GVT.
+    fn observe(
+        &mut self,
+        engine: &HybridEngine<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
+    ) -> Self::Observation;
+
+    /// The reward for the step that just ended, called after `observe()`.
+    fn reward(
+        &mut self,
+        engine: &HybridEngine<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
+    ) -> Reward;
+
+    /// Whether the episode ends before the run reaches its terminal time, where it always ends.
+    fn done(
+        &mut self,
+        _engine: &HybridEngine<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
+    ) -> bool {
+        false
+    }
+}
+
+/// Steps an `Environment` `interval` ticks of GVT at a time.
+pub struct SimEnv<
+    E,
+    const INTER_SLOTS: usize,
+    const CLOCK_SLOTS: usize,
+    const CLOCK_HEIGHT: usize,
+    MessageType: Pod + Zeroable + Clone,
+> {
+    env: E,
+    interval: u64,
+    engine: Option<HybridEngine<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>>,
+    done: bool,
+}
+
+impl<
+        E: Environment<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
+        const INTER_SLOTS: usize,
+        const CLOCK_SLOTS: usize,
+        const CLOCK_HEIGHT: usize,
+        MessageType: Pod + Zeroable + Clone + Send + 'static,
+    > SimEnv<E, INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>
+{
+    pub fn new(env: E, interval: u64) -> Self {
+        Self {
+            env,
+            interval: interval.max(1),
+            engine: None,
+            done: false,
+        }
+    }
+
+    /// Start a new episode, dropping the engine of the last one.
+    pub fn reset(&mut self) -> Result<E::Observation, AikaError> {
+        let engine = self.env.build()?;
+        let observation = self.env.observe(&engine);
+        self.engine = Some(engine);
+        self.done = false;
+        Ok(observation)
+    }
+
+    /// Apply `actions`, run the episode until GVT has advanced by the interval, and observe and score it there.
+    pub fn step(
+        &mut self,
+        actions: E::Actions,
+    ) -> Result<(E::Observation, Reward, bool), AikaError> {
+        if self.done {
+            return Err(AikaError::ConfigError(
+                "the episode is over, reset() the environment".to_string(),
+            ));
+        }
+        let mut engine = self.engine.take().ok_or_else(|| {
+            AikaError::ConfigError("reset() the environment before stepping it".to_string())
+        })?;
+        self.env.apply(&mut engine, actions)?;
+        let engine = self.engine.insert(engine.advance(self.interval)?);
+        let observation = self.env.observe(engine);
+        let reward = self.env.reward(engine);
+        self.done = engine.is_finished() || self.env.done(engine);
+        Ok((observation, reward, self.done))
+    }
+
+    /// The engine of the current episode, parked at GVT.
+    pub fn engine(
+        &self,
+    ) -> Option<&HybridEngine<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>> {
+        self.engine.as_ref()
+    }
+
+    pub fn env(&self) -> &E {
+        &self.env
+    }
+
+    pub fn env_mut(&mut self) -> &mut E {
+        &mut self.env
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agents::{partitions::Partition, PlanetContext, ThreadedAgent},
+        mt::hybrid::config::HybridConfig,
+        objects::{Action, Event, Msg},
+    };
+
+    // Adds its rate to the first value of the partition every step
+    struct Producer {
+        output: Partition<u64>,
+        rate: u64,
+    }
+
+    impl ThreadedAgent<16, u64> for Producer {
+        fn step(&mut self, context: &mut PlanetContext<16, u64>, id: usize) -> Event {
+            context.join_partition(self.output, id);
+            let output = context.read_partition(self.output, id).unwrap()[0];
+            context
+                .write_partition(self.output, id, 0, output + self.rate)
+                .unwrap();
+            Event::new(context.time, context.time, id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, u64>,
+            _msg: Msg<u64>,
+            _id: usize,
+        ) {
+        }
+    }
+
+    // The learner sets the production rate, and is rewarded for the output of each step
+    #[derive(Default)]
+    struct Factory {
+        output: Option<Partition<u64>>,
+        last: u64,
+    }
+
+    impl Factory {
+        fn output(&self, engine: &HybridEngine<16, 16, 2, u64>) -> u64 {
+            let partitions = &engine.planets[0].context.partitions;
+            partitions.values(self.output.unwrap()).unwrap()[0]
+        }
+    }
+
+    impl Environment<16, 16, 2, u64> for Factory {
+        type Observation = u64;
+        type Actions = u64;
+
+        fn build(&mut self) -> Result<HybridEngine<16, 16, 2, u64>, AikaError> {
+            let config = HybridConfig::new(1, 64)
+                .with_time_bounds(20.0, 1.0)
+                .with_optimistic_sync(4, 10)
+                .with_uniform_worlds(16, 1, 16);
+            let mut engine = HybridEngine::create(config)?;
+            let output = engine.create_shared_partition(0, 1)?;
+            engine.spawn_agent(0, Box::new(Producer { output, rate: 0 }))?;
+            engine.schedule_all_agents(1)?;
+            self.output = Some(output);
+            self.last = 0;
+            Ok(engine)
+        }
+
+        fn apply(
+            &mut self,
+            engine: &mut HybridEngine<16, 16, 2, u64>,
+            rate: u64,
+        ) -> Result<(), AikaError> {
+            let producer = engine.planets[0].agents[0]
+                .as_mut()
+                .as_any_mut()
+                .downcast_mut::<Producer>()
+                .ok_or(AikaError::InvalidAgentId(0))?;
+            producer.rate = rate;
+            Ok(())
+        }
+
+        fn observe(&mut self, engine: &HybridEngine<16, 16, 2, u64>) -> u64 {
+            self.output(engine)
+        }
+
+        fn reward(&mut self, engine: &HybridEngine<16, 16, 2, u64>) -> Reward {
+            let output = self.output(engine);
+            let reward = (output - self.last) as Reward;
+            self.last = output;
+            reward
+        }
+    }
+
+    #[test]
+    fn test_episodes() {
+        let mut env = SimEnv::new(Factory::default(), 5);
+        assert!(env.step(1).is_err());
+        for _ in 0..2 {
+            assert_eq!(env.reset().unwrap(), 0);
+            // steps at 1..5 at rate 1, 5..10 at rate 2, and so on
+            assert_eq!(env.step(1).unwrap(), (4, 4.0, false));
+            assert_eq!(env.step(2).unwrap(), (14, 10.0, false));
+            assert_eq!(env.step(0).unwrap(), (14, 0.0, false));
+            assert_eq!(env.step(3).unwrap(), (29, 15.0, true));
+            assert!(env.step(3).is_err());
+        }
+        assert_eq!(env.engine().unwrap().gvt(), 20);
+    }
+}
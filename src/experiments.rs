@@ -0,0 +1,371 @@
+//! Repeatable experiments over a grid of parameters.
+//! A `Sweep` names a list of values for each parameter, e.g. agent counts or throttle horizons, and a list of seeds,
+//! and calls a closure once for every combination of them. The closure builds and runs a `World` or `HybridEngine`
+//! from the `Point` it is handed and returns the `Metrics` it wants kept. Runs can be spread over threads, but the
+//! results always come back as one `Table` with a row per `Point` in the same order, so a sweep run twice with the
+//! same seeds gives the same table however many threads it used.
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use crate::{
+    export::{Column, ColumnType, Table, Value},
+    mt::hybrid::stats::{PlanetStats, RunStats},
+    AikaError,
+};
+
+/// One combination of parameter values, and the seed to run it with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Point {
+    /// position of the point in the sweep, and of its row in the results
+    pub index: usize,
+    pub seed: u64,
+    values: Vec<(String, Value)>,
+}
+
+impl Point {
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values
+            .iter()
+            .find(|(axis, _)| axis == name)
+            .map(|(_, value)| value)
+    }
+
+    /// The value of `name` as an unsigned integer.
+    pub fn uint(&self, name: &str) -> Result<u64, AikaError> {
+        match self.get(name) {
+            Some(Value::UInt(value)) => Ok(*value),
+            other => Err(Self::mismatch(name, "UInt", other)),
+        }
+    }
+
+    /// The value of `name` as a float. Integer values are converted.
+    pub fn float(&self, name: &str) -> Result<f64, AikaError> {
+        match self.get(name) {
+            Some(Value::Float(value)) => Ok(*value),
+            Some(Value::UInt(value)) => Ok(*value as f64),
+            Some(Value::Int(value)) => Ok(*value as f64),
+            other => Err(Self::mismatch(name, "Float", other)),
+        }
+    }
+
+    /// Every parameter and its value, in the order the axes were added.
+    pub fn values(&self) -> &[(String, Value)] {
+        &self.values
+    }
+
+    fn mismatch(name: &str, expected: &str, found: Option<&Value>) -> AikaError {
+        match found {
+            Some(value) => AikaError::ConfigError(format!(
+                "parameter `{name}` is {value:?}, expected {expected}"
+            )),
+            None => AikaError::ConfigError(format!("no parameter `{name}` in the sweep")),
+        }
+    }
+}
+
+/// Named results of a single run, one column each in the sweep's table.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metrics {
+    values: Vec<(String, Value)>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, name: &str, value: Value) -> Self {
+        self.values.push((name.to_string(), value));
+        self
+    }
+
+    /// The counters of a `HybridEngine` run, summed over every `Planet`.
+    pub fn from_stats(stats: &RunStats) -> Self {
+        let sum =
+            |count: fn(&PlanetStats) -> u64| Value::UInt(stats.planets.iter().map(count).sum());
+        Self::new()
+            .with("rollbacks", sum(|planet| planet.rollbacks))
+            .with("rollback_steps", sum(|planet| planet.rollback_steps))
+            .with("migrated", sum(|planet| planet.migrated_out))
+            .with("bundles_sent", sum(|planet| planet.bundles_sent))
+            .with("warnings", sum(|planet| planet.warnings.len() as u64))
+    }
+
+    pub fn values(&self) -> &[(String, Value)] {
+        &self.values
+    }
+}
+
+/// A cartesian product of parameter values, each run once per seed.
+#[derive(Clone, Debug)]
+pub struct Sweep {
+    axes: Vec<(String, Vec<Value>)>,
+    seeds: Vec<u64>,
+    threads: usize,
+}
+
+impl Default for Sweep {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sweep {
+    /// A sweep of a single run with seed 0, on the calling thread.
+    pub fn new() -> Self {
+        Self {
+            axes: Vec::new(),
+            seeds: vec![0],
+            threads: 1,
+        }
+    }
+
+    /// Add a parameter taking each of `values` in turn. Later axes vary fastest.
+    pub fn axis(mut self, name: &str, values: Vec<Value>) -> Self {
+        self.axes.push((name.to_string(), values));
+        self
+    }
+
+    /// Run every combination once per seed.
+    pub fn with_seeds(mut self, seeds: impl IntoIterator<Item = u64>) -> Self {
+        self.seeds = seeds.into_iter().collect();
+        self
+    }
+
+    /// Spread the runs over `threads` threads.
+    pub fn parallel(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Every point of the sweep, seeds varying fastest.
+    pub fn points(&self) -> Vec<Point> {
+        let mut combos = vec![Vec::new()];
+        for (name, values) in &self.axes {
+            combos = combos
+                .into_iter()
+                .flat_map(|combo: Vec<(String, Value)>| {
+                    values.iter().map(move |value| {
+                        let mut combo = combo.clone();
+                        combo.push((name.clone(), value.clone()));
+                        combo
+                    })
+                })
+                .collect();
+        }
+        combos
+            .into_iter()
+            .flat_map(|values| self.seeds.iter().map(move |seed| (values.clone(), *seed)))
+            .enumerate()
+            .map(|(index, (values, seed))| Point {
+                index,
+                seed,
+                values,
+            })
+            .collect()
+    }
+
+    fn validate(&self) -> Result<(), AikaError> {
+        if self.threads == 0 {
+            return Err(AikaError::ConfigError(
+                "a sweep needs at least one thread".to_string(),
+            ));
+        }
+        if self.seeds.is_empty() {
+            return Err(AikaError::ConfigError(
+                "a sweep needs at least one seed".to_string(),
+            ));
+        }
+        for (index, (name, values)) in self.axes.iter().enumerate() {
+            if values.is_empty() {
+                return Err(AikaError::ConfigError(format!(
+                    "parameter `{name}` has no values"
+                )));
+            }
+            if name == "seed" || self.axes[..index].iter().any(|(other, _)| other == name) {
+                return Err(AikaError::ConfigError(format!(
+                    "parameter `{name}` is named twice"
+                )));
+            }
+            if let Some(value) = values
+                .iter()
+                .find(|value| value.column_type() != values[0].column_type())
+            {
+                return Err(AikaError::ConfigError(format!(
+                    "parameter `{name}` mixes {:?} and {value:?} values",
+                    values[0].column_type()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Call `run` at every point and gather the results into a table of the parameters, the seed and the metrics,
+    /// one row per point in sweep order. Every run must return the same metrics, and the first error, in sweep
+    /// order, is returned instead.
+    pub fn run<F>(&self, run: F) -> Result<Table, AikaError>
+    where
+        F: Fn(&Point) -> Result<Metrics, AikaError> + Sync,
+    {
+        self.validate()?;
+        let points = self.points();
+        let results = if self.threads == 1 {
+            points.iter().map(&run).collect::<Vec<_>>()
+        } else {
+            let next = AtomicUsize::new(0);
+            let results = Mutex::new(Vec::with_capacity(points.len()));
+            thread::scope(|scope| {
+                for _ in 0..self.threads.min(points.len()) {
+                    scope.spawn(|| loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(point) = points.get(index) else {
+                            break;
+                        };
+                        let metrics = run(point);
+                        results.lock().unwrap().push((index, metrics));
+                    });
+                }
+            });
+            let mut results = results.into_inner().unwrap();
+            results.sort_by_key(|(index, _)| *index);
+            results.into_iter().map(|(_, metrics)| metrics).collect()
+        };
+
+        let mut table: Option<Table> = None;
+        for (point, metrics) in points.iter().zip(results) {
+            let metrics = metrics?;
+            let table = table.get_or_insert_with(|| self.table(&metrics));
+            let names = table.columns()[self.axes.len() + 1..]
+                .iter()
+                .map(|column| column.name.as_str());
+            if !names.eq(metrics.values.iter().map(|(name, _)| name.as_str())) {
+                return Err(AikaError::Export(format!(
+                    "run {} returned different metrics from the first run",
+                    point.index
+                )));
+            }
+            let mut row = point
+                .values
+                .iter()
+                .map(|(_, value)| value.clone())
+                .collect::<Vec<_>>();
+            row.push(Value::UInt(point.seed));
+            row.extend(metrics.values.into_iter().map(|(_, value)| value));
+            table.push_row(row)?;
+        }
+        Ok(table.unwrap_or_else(|| self.table(&Metrics::new())))
+    }
+
+    fn table(&self, metrics: &Metrics) -> Table {
+        let mut columns = self
+            .axes
+            .iter()
+            .map(|(name, values)| Column::new(name, values[0].column_type()))
+            .collect::<Vec<_>>();
+        columns.push(Column::new("seed", ColumnType::UInt));
+        columns.extend(
+            metrics
+                .values
+                .iter()
+                .map(|(name, value)| Column::new(name, value.column_type())),
+        );
+        Table::new(columns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agents::{Agent, WorldContext},
+        objects::{Action, Event, Msg},
+        st::World,
+    };
+
+    // Steps every `period` ticks, starting at a time picked by the seed
+    struct Ticker {
+        period: u64,
+    }
+
+    impl Agent<8, Msg<u8>> for Ticker {
+        fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+            let time = context.time;
+            Event::new(time, time, id, Action::Timeout(self.period))
+        }
+    }
+
+    fn run_world(point: &Point) -> Result<Metrics, AikaError> {
+        let agents = point.uint("agents")?;
+        let period = point.uint("period")?;
+        let mut world = World::<8, 16, 1, u8>::init(40.0, 1.0, 0)?;
+        for _ in 0..agents {
+            world.spawn_agent(Box::new(Ticker { period }));
+        }
+        world.init_support_layers(None)?;
+        for agent in 0..agents as usize {
+            world.schedule(1 + point.seed, agent)?;
+        }
+        let quantum = world.advance_by(40.0)?;
+        Ok(Metrics::new().with("events", Value::UInt(quantum.events)))
+    }
+
+    #[test]
+    fn test_sweep_grid() {
+        let sweep = Sweep::new()
+            .axis("agents", vec![Value::UInt(1), Value::UInt(3)])
+            .axis("period", vec![Value::UInt(5), Value::UInt(10)])
+            .with_seeds([0, 9]);
+        let table = sweep.run(run_world).unwrap();
+        let names = table
+            .columns()
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["agents", "period", "seed", "events"]);
+        assert_eq!(table.len(), 8);
+        assert_eq!(
+            table.rows()[1],
+            [
+                Value::UInt(1),
+                Value::UInt(5),
+                Value::UInt(9),
+                Value::UInt(6)
+            ]
+        );
+        assert_eq!(table.rows()[7][3], Value::UInt(9));
+
+        // the same table whatever the number of threads
+        assert_eq!(
+            sweep.clone().parallel(3).run(run_world).unwrap().rows(),
+            table.rows()
+        );
+    }
+
+    #[test]
+    fn test_sweep_errors() {
+        let sweep = Sweep::new().axis("agents", vec![Value::UInt(1), Value::Float(2.0)]);
+        assert!(matches!(
+            sweep.run(run_world),
+            Err(AikaError::ConfigError(_))
+        ));
+        let sweep = Sweep::new()
+            .axis("agents", vec![Value::UInt(1), Value::UInt(2)])
+            .axis("period", vec![Value::UInt(0), Value::UInt(5)])
+            .parallel(2);
+        let mismatched = sweep.run(|point| {
+            let name = if point.index == 0 { "a" } else { "b" };
+            Ok(Metrics::new().with(name, Value::Bool(true)))
+        });
+        assert!(matches!(mismatched, Err(AikaError::Export(_))));
+        let failed = sweep.run(|point| match point.index {
+            1 | 3 => Err(AikaError::InvalidAgentId(point.index)),
+            _ => Ok(Metrics::new()),
+        });
+        assert!(matches!(failed, Err(AikaError::InvalidAgentId(1))));
+    }
+}
@@ -0,0 +1,604 @@
+//! Tabular export of simulation results for data pipelines.
+//! A `Table` is a list of typed columns and rows of `Value`s. Tables are built from agent state histories, with
+//! columns read out of the `Pod` state by a `PodLayout`, from the events recorded in a `CausalLog`, from a run's
+//! `RunStats`, from a `ParameterTimeline`, from the samples of `OccupancyRecorder`s, from the buckets of
+//! `KpiRecorder`s, from the blocks of a `BlockLedger` or, with the `serde` feature, from any flat `Serialize` records.
+//! Every table can be written as CSV and, with the `parquet` feature, as a Parquet file with one uncompressed,
+//! plain-encoded row group.
+use std::{fs::File, io::Write, path::Path};
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    analysis::{critical_path::CausalNode, kpi::KpiRecorder, occupancy::OccupancyRecorder},
+    mt::hybrid::{blocks::BlockLedger, params::ParameterTimeline, stats::RunStats},
+    AikaError,
+};
+
+#[cfg(feature = "parquet")]
+mod parquet;
+
+/// The type of a `Table` column.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColumnType {
+    UInt,
+    Int,
+    Float,
+    Bool,
+    Text,
+}
+
+/// A named, typed `Table` column.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Column {
+    pub name: String,
+    pub ty: ColumnType,
+}
+
+impl Column {
+    pub fn new(name: &str, ty: ColumnType) -> Self {
+        Self {
+            name: name.to_string(),
+            ty,
+        }
+    }
+}
+
+/// A single cell of a `Table`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    UInt(u64),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Text(String),
+}
+
+impl Value {
+    pub fn column_type(&self) -> ColumnType {
+        match self {
+            Value::UInt(_) => ColumnType::UInt,
+            Value::Int(_) => ColumnType::Int,
+            Value::Float(_) => ColumnType::Float,
+            Value::Bool(_) => ColumnType::Bool,
+            Value::Text(_) => ColumnType::Text,
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        match self {
+            Value::UInt(value) => value.to_string(),
+            Value::Int(value) => value.to_string(),
+            Value::Float(value) => value.to_string(),
+            Value::Bool(value) => value.to_string(),
+            Value::Text(text) if text.contains([',', '"', '\n', '\r']) => {
+                format!("\"{}\"", text.replace('"', "\"\""))
+            }
+            Value::Text(text) => text.clone(),
+        }
+    }
+}
+
+/// A scalar field type inside a `Pod` state.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Scalar {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    /// one byte, nonzero is `true`
+    Bool,
+}
+
+impl Scalar {
+    pub fn size(&self) -> usize {
+        match self {
+            Scalar::U8 | Scalar::I8 | Scalar::Bool => 1,
+            Scalar::U16 | Scalar::I16 => 2,
+            Scalar::U32 | Scalar::I32 | Scalar::F32 => 4,
+            Scalar::U64 | Scalar::I64 | Scalar::F64 => 8,
+        }
+    }
+
+    pub fn column_type(&self) -> ColumnType {
+        match self {
+            Scalar::U8 | Scalar::U16 | Scalar::U32 | Scalar::U64 => ColumnType::UInt,
+            Scalar::I8 | Scalar::I16 | Scalar::I32 | Scalar::I64 => ColumnType::Int,
+            Scalar::F32 | Scalar::F64 => ColumnType::Float,
+            Scalar::Bool => ColumnType::Bool,
+        }
+    }
+
+    fn read(&self, bytes: &[u8]) -> Value {
+        let mut word = [0u8; 8];
+        word[..bytes.len()].copy_from_slice(bytes);
+        let unsigned = u64::from_le_bytes(word);
+        // sign-extend from the field's width
+        let shift = 64 - 8 * bytes.len() as u32;
+        let signed = ((unsigned << shift) as i64) >> shift;
+        match self {
+            Scalar::U8 | Scalar::U16 | Scalar::U32 | Scalar::U64 => Value::UInt(unsigned),
+            Scalar::I8 | Scalar::I16 | Scalar::I32 | Scalar::I64 => Value::Int(signed),
+            Scalar::F32 => Value::Float(f32::from_bits(unsigned as u32) as f64),
+            Scalar::F64 => Value::Float(f64::from_bits(unsigned)),
+            Scalar::Bool => Value::Bool(unsigned != 0),
+        }
+    }
+}
+
+/// The columns of a `Pod` state: a name, scalar type and byte offset per field, e.g. from `std::mem::offset_of!`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PodLayout {
+    fields: Vec<(String, Scalar, usize)>,
+}
+
+impl PodLayout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a column read from the `scalar` at byte `offset`.
+    pub fn field(mut self, name: &str, scalar: Scalar, offset: usize) -> Self {
+        self.fields.push((name.to_string(), scalar, offset));
+        self
+    }
+
+    pub fn columns(&self) -> Vec<Column> {
+        self.fields
+            .iter()
+            .map(|(name, scalar, _)| Column::new(name, scalar.column_type()))
+            .collect()
+    }
+
+    /// Read every field out of `state`, failing if a field lies outside of it.
+    pub fn values<T: Pod + Zeroable>(&self, state: &T) -> Result<Vec<Value>, AikaError> {
+        let bytes = bytemuck::bytes_of(state);
+        self.fields
+            .iter()
+            .map(|(name, scalar, offset)| {
+                let field = bytes.get(*offset..*offset + scalar.size()).ok_or_else(|| {
+                    AikaError::Export(format!(
+                        "field `{name}` at offset {offset} lies outside a {}-byte state",
+                        bytes.len()
+                    ))
+                })?;
+                Ok(scalar.read(field))
+            })
+            .collect()
+    }
+}
+
+/// Typed columns and rows ready to be written out.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Table {
+    columns: Vec<Column>,
+    rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    pub fn new(columns: Vec<Column>) -> Self {
+        Self {
+            columns,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    pub fn rows(&self) -> &[Vec<Value>] {
+        &self.rows
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Append a row, checking it against the columns.
+    pub fn push_row(&mut self, row: Vec<Value>) -> Result<(), AikaError> {
+        if row.len() != self.columns.len() {
+            return Err(AikaError::Export(format!(
+                "row has {} values for {} columns",
+                row.len(),
+                self.columns.len()
+            )));
+        }
+        if let Some((column, value)) = self
+            .columns
+            .iter()
+            .zip(&row)
+            .find(|(column, value)| column.ty != value.column_type())
+        {
+            return Err(AikaError::Export(format!(
+                "column `{}` holds {:?} values, got {value:?}",
+                column.name, column.ty
+            )));
+        }
+        self.rows.push(row);
+        Ok(())
+    }
+
+    /// A table of `(world, agent, time)` followed by the `layout` columns, one row per logged state.
+    pub fn from_states<T: Pod + Zeroable>(
+        layout: &PodLayout,
+        states: impl IntoIterator<Item = (usize, usize, u64, T)>,
+    ) -> Result<Self, AikaError> {
+        let mut columns = vec![
+            Column::new("world", ColumnType::UInt),
+            Column::new("agent", ColumnType::UInt),
+            Column::new("time", ColumnType::UInt),
+        ];
+        columns.extend(layout.columns());
+        let mut table = Self::new(columns);
+        for (world, agent, time, state) in states {
+            let mut row = vec![
+                Value::UInt(world as u64),
+                Value::UInt(agent as u64),
+                Value::UInt(time),
+            ];
+            row.extend(layout.values(&state)?);
+            table.push_row(row)?;
+        }
+        Ok(table)
+    }
+
+    /// A table of the `(world, agent, time)` of every processed event, e.g. from `CausalLog::activations()`.
+    pub fn from_events(events: &[CausalNode]) -> Self {
+        let columns = vec![
+            Column::new("world", ColumnType::UInt),
+            Column::new("agent", ColumnType::UInt),
+            Column::new("time", ColumnType::UInt),
+        ];
+        let rows = events
+            .iter()
+            .map(|event| {
+                vec![
+                    Value::UInt(event.planet as u64),
+                    Value::UInt(event.agent as u64),
+                    Value::UInt(event.time),
+                ]
+            })
+            .collect();
+        Self { columns, rows }
+    }
+
+    /// A table of the counters of every `Planet`, one row per world.
+    pub fn from_stats(stats: &RunStats) -> Self {
+        let columns = [
+            "world",
+            "rollbacks",
+            "rollback_steps",
+            "migrated_out",
+            "migrated_in",
+            "bundles_sent",
+            "warnings",
+        ]
+        .into_iter()
+        .map(|name| Column::new(name, ColumnType::UInt))
+        .collect();
+        let rows = stats
+            .planets
+            .iter()
+            .map(|planet| {
+                [
+                    planet.world_id as u64,
+                    planet.rollbacks,
+                    planet.rollback_steps,
+                    planet.migrated_out,
+                    planet.migrated_in,
+                    planet.bundles_sent,
+                    planet.warnings.len() as u64,
+                ]
+                .into_iter()
+                .map(Value::UInt)
+                .collect()
+            })
+            .collect();
+        Self { columns, rows }
+    }
+
+    /// A table of every parameter change, one row each. An `agent` of -1 marks a change for every agent.
+    pub fn from_parameters(timeline: &ParameterTimeline) -> Self {
+        let columns = vec![
+            Column::new("world", ColumnType::UInt),
+            Column::new("agent", ColumnType::Int),
+            Column::new("key", ColumnType::UInt),
+            Column::new("value", ColumnType::Float),
+            Column::new("time", ColumnType::UInt),
+        ];
+        let rows = timeline
+            .changes()
+            .iter()
+            .map(|change| {
+                vec![
+                    Value::UInt(change.world as u64),
+                    Value::Int(change.agent.map_or(-1, |agent| agent as i64)),
+                    Value::UInt(change.key as u64),
+                    Value::Float(change.value),
+                    Value::UInt(change.time),
+                ]
+            })
+            .collect();
+        Self { columns, rows }
+    }
+
+    /// A table of wheel occupancy samples, one row per world, time and level. A `level` of -1 marks the events past
+    /// the wheels.
+    pub fn from_occupancy(recorders: &[&OccupancyRecorder]) -> Self {
+        let columns = vec![
+            Column::new("world", ColumnType::UInt),
+            Column::new("time", ColumnType::UInt),
+            Column::new("level", ColumnType::Int),
+            Column::new("events", ColumnType::UInt),
+        ];
+        let rows = recorders
+            .iter()
+            .flat_map(|recorder| {
+                recorder.samples().iter().flat_map(|sample| {
+                    let levels = sample
+                        .levels
+                        .iter()
+                        .enumerate()
+                        .map(|(level, events)| (level as i64, *events));
+                    levels
+                        .chain([(-1, sample.overflow)])
+                        .map(|(level, events)| {
+                            vec![
+                                Value::UInt(recorder.world() as u64),
+                                Value::UInt(sample.time),
+                                Value::Int(level),
+                                Value::UInt(events),
+                            ]
+                        })
+                })
+            })
+            .collect();
+        Self { columns, rows }
+    }
+
+    /// A table of KPI buckets, one row per world and bucket.
+    pub fn from_kpis(recorders: &[&KpiRecorder]) -> Self {
+        let columns = ["world", "start", "events", "delivered", "active_agents"]
+            .into_iter()
+            .map(|name| Column::new(name, ColumnType::UInt))
+            .collect();
+        let rows = recorders
+            .iter()
+            .flat_map(|recorder| {
+                recorder.buckets().iter().map(|bucket| {
+                    [
+                        recorder.world() as u64,
+                        bucket.start,
+                        bucket.events,
+                        bucket.delivered,
+                        bucket.active_agents,
+                    ]
+                    .into_iter()
+                    .map(Value::UInt)
+                    .collect()
+                })
+            })
+            .collect();
+        Self { columns, rows }
+    }
+
+    /// A table of the blocks of a `BlockLedger`, one row per planet and block that saw any mail.
+    pub fn from_blocks(ledger: &BlockLedger) -> Self {
+        let columns = [
+            "world",
+            "start",
+            "sends",
+            "recvs",
+            "stragglers",
+            "cancelled",
+        ]
+        .into_iter()
+        .map(|name| Column::new(name, ColumnType::UInt))
+        .collect();
+        let rows = ledger
+            .blocks()
+            .map(|(world, start, stats)| {
+                [
+                    world as u64,
+                    start,
+                    stats.sends,
+                    stats.recvs,
+                    stats.stragglers,
+                    stats.cancelled,
+                ]
+                .into_iter()
+                .map(Value::UInt)
+                .collect()
+            })
+            .collect();
+        Self { columns, rows }
+    }
+
+    /// A table of flat records, with columns named and typed after the fields of the first one.
+    #[cfg(feature = "serde")]
+    pub fn from_serialize<T: serde::Serialize>(records: &[T]) -> Result<Self, AikaError> {
+        use serde_json::Value as Json;
+
+        let mut table: Option<Self> = None;
+        for record in records {
+            let Json::Object(fields) =
+                serde_json::to_value(record).map_err(|err| AikaError::Export(err.to_string()))?
+            else {
+                return Err(AikaError::Export(
+                    "records must serialize to maps".to_string(),
+                ));
+            };
+            let row = fields
+                .iter()
+                .map(|(name, field)| match field {
+                    Json::Bool(value) => Ok(Value::Bool(*value)),
+                    Json::Number(number) => Ok(number
+                        .as_u64()
+                        .map(Value::UInt)
+                        .or_else(|| number.as_i64().map(Value::Int))
+                        .unwrap_or_else(|| Value::Float(number.as_f64().unwrap_or(f64::NAN)))),
+                    Json::String(text) => Ok(Value::Text(text.clone())),
+                    _ => Err(AikaError::Export(format!("field `{name}` is not a scalar"))),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let table = table.get_or_insert_with(|| {
+                Self::new(
+                    fields
+                        .keys()
+                        .zip(&row)
+                        .map(|(name, value)| Column::new(name, value.column_type()))
+                        .collect(),
+                )
+            });
+            table.push_row(row)?;
+        }
+        Ok(table.unwrap_or_default())
+    }
+
+    /// Write the table as CSV with a header line.
+    pub fn write_csv(&self, mut writer: impl Write) -> Result<(), AikaError> {
+        let header = self
+            .columns
+            .iter()
+            .map(|column| Value::Text(column.name.clone()).to_csv())
+            .collect::<Vec<_>>();
+        writeln!(writer, "{}", header.join(","))?;
+        for row in &self.rows {
+            let line = row.iter().map(Value::to_csv).collect::<Vec<_>>();
+            writeln!(writer, "{}", line.join(","))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn write_csv_file(&self, path: impl AsRef<Path>) -> Result<(), AikaError> {
+        self.write_csv(File::create(path)?)
+    }
+
+    /// Write the table as a Parquet file.
+    #[cfg(feature = "parquet")]
+    pub fn write_parquet(&self, mut writer: impl Write) -> Result<(), AikaError> {
+        writer.write_all(&parquet::encode(self)?)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    #[cfg(feature = "parquet")]
+    pub fn write_parquet_file(&self, path: impl AsRef<Path>) -> Result<(), AikaError> {
+        self.write_parquet(File::create(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::offset_of;
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Stock {
+        units: u64,
+        price: f32,
+        delta: i16,
+        open: u8,
+        _pad: u8,
+    }
+
+    unsafe impl Pod for Stock {}
+    unsafe impl Zeroable for Stock {}
+
+    fn layout() -> PodLayout {
+        PodLayout::new()
+            .field("units", Scalar::U64, offset_of!(Stock, units))
+            .field("price", Scalar::F32, offset_of!(Stock, price))
+            .field("delta", Scalar::I16, offset_of!(Stock, delta))
+            .field("open", Scalar::Bool, offset_of!(Stock, open))
+    }
+
+    // Sells one unit a step at a rising price
+    struct Shop;
+
+    impl ThreadedAgent<128, Stock> for Shop {
+        fn step(&mut self, context: &mut PlanetContext<128, Stock>, agent_id: usize) -> Event {
+            let time = context.time;
+            let journal = &mut context.agent_states[agent_id];
+            let units = journal
+                .read_state::<Stock>()
+                .map_or(10, |stock| stock.units);
+            let stock = Stock {
+                units: units - 1,
+                price: 1.5 * time as f32,
+                delta: -1,
+                open: (units > 8) as u8,
+                _pad: 0,
+            };
+            journal.write(stock, time, None);
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Stock>,
+            _msg: Msg<Stock>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_state_histories_to_csv() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(4.0, 1.0)
+            .with_optimistic_sync(2, 4)
+            .with_uniform_worlds(16, 1, 256)
+            .with_causal_log(true);
+        let mut engine = HybridEngine::<128, 128, 1, Stock>::create(config).unwrap();
+        engine.spawn_agent(1, Box::new(Shop)).unwrap();
+        engine.schedule(1, 0, 1).unwrap();
+        let engine = engine.run().unwrap();
+
+        let table = engine.agent_state_table::<Stock>(&layout()).unwrap();
+        let mut csv = Vec::new();
+        table.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "world,agent,time,units,price,delta,open\n\
+             1,0,1,9,1.5,-1,true\n\
+             1,0,2,8,3,-1,true\n\
+             1,0,3,7,4.5,-1,false\n"
+        );
+        let events = engine.event_table().unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(Table::from_stats(&engine.stats()).len(), 2);
+
+        let mut notes = Table::new(vec![Column::new("note", ColumnType::Text)]);
+        notes
+            .push_row(vec![Value::Text("a, \"b\"".into())])
+            .unwrap();
+        assert!(notes.push_row(vec![Value::UInt(1)]).is_err());
+        let mut csv = Vec::new();
+        notes.write_csv(&mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "note\n\"a, \"\"b\"\"\"\n");
+        assert!(PodLayout::new()
+            .field("far", Scalar::U64, 16)
+            .values(&Stock::zeroed())
+            .is_err());
+    }
+}
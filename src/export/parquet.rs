@@ -0,0 +1,374 @@
+//! A minimal Parquet encoder, so the `parquet` feature needs no extra dependencies.
+//! Every column is `REQUIRED` and written as a single uncompressed, `PLAIN`-encoded data page in one row group.
+//! Page headers and the footer are Thrift structs in the compact protocol; only the fields this layout needs are
+//! written.
+use crate::{
+    export::{ColumnType, Table, Value},
+    AikaError,
+};
+
+const MAGIC: &[u8] = b"PAR1";
+
+// Thrift compact field types
+const I32: u8 = 5;
+const I64: u8 = 6;
+const BINARY: u8 = 8;
+const LIST: u8 = 9;
+const STRUCT: u8 = 12;
+
+// Parquet physical types
+const BOOLEAN: i32 = 0;
+const INT64: i32 = 2;
+const DOUBLE: i32 = 5;
+const BYTE_ARRAY: i32 = 6;
+
+// Parquet converted types
+const UTF8: i32 = 0;
+const UINT_64: i32 = 14;
+
+const PLAIN: i32 = 0;
+const RLE: i32 = 3;
+const REQUIRED: i32 = 0;
+const DATA_PAGE: i32 = 0;
+const UNCOMPRESSED: i32 = 0;
+
+/// Writer for the Thrift compact protocol.
+#[derive(Default)]
+struct Compact {
+    out: Vec<u8>,
+    /// last field id of every struct being written, innermost last
+    fields: Vec<i16>,
+    last: i16,
+}
+
+impl Compact {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.out.push(value as u8);
+    }
+
+    fn zigzag(&mut self, value: i64) {
+        self.varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn field(&mut self, id: i16, ty: u8) {
+        let delta = id - self.last;
+        if (1..=15).contains(&delta) {
+            self.out.push((delta as u8) << 4 | ty);
+        } else {
+            self.out.push(ty);
+            self.zigzag(id as i64);
+        }
+        self.last = id;
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, I32);
+        self.zigzag(value as i64);
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, I64);
+        self.zigzag(value);
+    }
+
+    fn string(&mut self, id: i16, value: &str) {
+        self.field(id, BINARY);
+        self.bytes(value.as_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.varint(bytes.len() as u64);
+        self.out.extend_from_slice(bytes);
+    }
+
+    fn list(&mut self, id: i16, element: u8, len: usize) {
+        self.field(id, LIST);
+        if len < 15 {
+            self.out.push((len as u8) << 4 | element);
+        } else {
+            self.out.push(0xF0 | element);
+            self.varint(len as u64);
+        }
+    }
+
+    /// Open a struct; `id` is `None` for a list element or the outermost struct.
+    fn begin(&mut self, id: Option<i16>) {
+        if let Some(id) = id {
+            self.field(id, STRUCT);
+        }
+        self.fields.push(self.last);
+        self.last = 0;
+    }
+
+    fn end(&mut self) {
+        self.out.push(0);
+        self.last = self.fields.pop().unwrap_or_default();
+    }
+}
+
+fn physical_type(ty: ColumnType) -> i32 {
+    match ty {
+        ColumnType::UInt | ColumnType::Int => INT64,
+        ColumnType::Float => DOUBLE,
+        ColumnType::Bool => BOOLEAN,
+        ColumnType::Text => BYTE_ARRAY,
+    }
+}
+
+/// `PLAIN` encoding of one column.
+fn plain(table: &Table, column: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (row, values) in table.rows.iter().enumerate() {
+        match &values[column] {
+            Value::UInt(value) => out.extend_from_slice(&value.to_le_bytes()),
+            Value::Int(value) => out.extend_from_slice(&value.to_le_bytes()),
+            Value::Float(value) => out.extend_from_slice(&value.to_le_bytes()),
+            Value::Text(text) => {
+                out.extend_from_slice(&(text.len() as u32).to_le_bytes());
+                out.extend_from_slice(text.as_bytes());
+            }
+            // booleans are bit-packed, least significant bit first
+            Value::Bool(value) => {
+                if row % 8 == 0 {
+                    out.push(0);
+                }
+                if *value {
+                    *out.last_mut().unwrap() |= 1 << (row % 8);
+                }
+            }
+        }
+    }
+    out
+}
+
+fn page_header(rows: usize, size: usize) -> Vec<u8> {
+    let mut header = Compact::default();
+    header.begin(None);
+    header.i32(1, DATA_PAGE);
+    header.i32(2, size as i32);
+    header.i32(3, size as i32);
+    header.begin(Some(5));
+    header.i32(1, rows as i32);
+    header.i32(2, PLAIN);
+    header.i32(3, RLE);
+    header.i32(4, RLE);
+    header.end();
+    header.end();
+    header.out
+}
+
+/// Encode `table` as a complete Parquet file.
+pub(super) fn encode(table: &Table) -> Result<Vec<u8>, AikaError> {
+    if table.columns.is_empty() {
+        return Err(AikaError::Export(
+            "a Parquet file needs at least one column".to_string(),
+        ));
+    }
+    let rows = table.rows.len();
+    let mut file = MAGIC.to_vec();
+    // `(offset, size)` of every column chunk
+    let mut chunks = Vec::new();
+    if rows > 0 {
+        for column in 0..table.columns.len() {
+            let data = plain(table, column);
+            let offset = file.len();
+            file.extend(page_header(rows, data.len()));
+            file.extend(data);
+            chunks.push((offset, file.len() - offset));
+        }
+    }
+
+    let mut footer = Compact::default();
+    footer.begin(None);
+    footer.i32(1, 1);
+    footer.list(2, STRUCT, table.columns.len() + 1);
+    footer.begin(None);
+    footer.string(4, "schema");
+    footer.i32(5, table.columns.len() as i32);
+    footer.end();
+    for column in &table.columns {
+        footer.begin(None);
+        footer.i32(1, physical_type(column.ty));
+        footer.i32(3, REQUIRED);
+        footer.string(4, &column.name);
+        match column.ty {
+            ColumnType::Text => footer.i32(6, UTF8),
+            ColumnType::UInt => footer.i32(6, UINT_64),
+            _ => {}
+        }
+        footer.end();
+    }
+    footer.i64(3, rows as i64);
+    footer.list(4, STRUCT, chunks.len().min(1));
+    if !chunks.is_empty() {
+        footer.begin(None);
+        footer.list(1, STRUCT, chunks.len());
+        for (column, (offset, size)) in table.columns.iter().zip(&chunks) {
+            footer.begin(None);
+            footer.i64(2, *offset as i64);
+            footer.begin(Some(3));
+            footer.i32(1, physical_type(column.ty));
+            footer.list(2, I32, 2);
+            footer.zigzag(PLAIN as i64);
+            footer.zigzag(RLE as i64);
+            footer.list(3, BINARY, 1);
+            footer.bytes(column.name.as_bytes());
+            footer.i32(4, UNCOMPRESSED);
+            footer.i64(5, rows as i64);
+            footer.i64(6, *size as i64);
+            footer.i64(7, *size as i64);
+            footer.i64(9, *offset as i64);
+            footer.end();
+            footer.end();
+        }
+        let total = chunks.iter().map(|(_, size)| *size as i64).sum();
+        footer.i64(2, total);
+        footer.i64(3, rows as i64);
+        footer.end();
+    }
+    footer.string(6, concat!("aika version ", env!("CARGO_PKG_VERSION")));
+    footer.end();
+
+    file.extend_from_slice(&footer.out);
+    file.extend_from_slice(&(footer.out.len() as u32).to_le_bytes());
+    file.extend_from_slice(MAGIC);
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::Column;
+
+    #[test]
+    fn test_parquet_layout() {
+        let mut table = Table::new(vec![
+            Column::new("time", ColumnType::UInt),
+            Column::new("open", ColumnType::Bool),
+            Column::new("name", ColumnType::Text),
+        ]);
+        for (time, open, name) in [(1, true, "ab"), (2, false, "c"), (3, true, "")] {
+            let row = vec![
+                Value::UInt(time),
+                Value::Bool(open),
+                Value::Text(name.to_string()),
+            ];
+            table.push_row(row).unwrap();
+        }
+        let file = encode(&table).unwrap();
+
+        assert_eq!(&file[..4], MAGIC);
+        assert_eq!(&file[file.len() - 4..], MAGIC);
+        let footer_len =
+            u32::from_le_bytes(file[file.len() - 8..file.len() - 4].try_into().unwrap());
+        let footer = &file[file.len() - 8 - footer_len as usize..file.len() - 8];
+        // FileMetaData opens with version 1, then a list of four schema elements
+        assert_eq!(&footer[..4], &[0x15, 0x02, 0x19, 0x4c]);
+        for name in ["time", "open", "name"] {
+            assert!(footer
+                .windows(name.len())
+                .any(|window| window == name.as_bytes()));
+        }
+
+        // the first page header is followed by the three times, the booleans pack into 0b101
+        let times = [1u64, 2, 3].map(u64::to_le_bytes).concat();
+        let header = page_header(3, times.len());
+        assert_eq!(&file[4..4 + header.len()], header.as_slice());
+        assert_eq!(
+            &file[4 + header.len()..4 + header.len() + 24],
+            times.as_slice()
+        );
+        assert_eq!(plain(&table, 1), vec![0b101]);
+        assert_eq!(
+            plain(&table, 2),
+            vec![2, 0, 0, 0, b'a', b'b', 1, 0, 0, 0, b'c', 0, 0, 0, 0]
+        );
+        assert!(encode(&Table::new(Vec::new())).is_err());
+    }
+
+    #[test]
+    fn test_parquet_reads_back() {
+        use ::parquet::{
+            basic::{ConvertedType, Type},
+            file::reader::{FileReader, SerializedFileReader},
+            record::RowAccessor,
+        };
+
+        let mut table = Table::new(vec![
+            Column::new("time", ColumnType::UInt),
+            Column::new("delta", ColumnType::Int),
+            Column::new("load", ColumnType::Float),
+            Column::new("open", ColumnType::Bool),
+            Column::new("name", ColumnType::Text),
+        ]);
+        let rows = [
+            (1, -4, 0.5, true, "ab"),
+            (2, 0, 1.25, false, "c"),
+            (u64::MAX, i64::MIN, -3.0, true, ""),
+        ];
+        for (time, delta, load, open, name) in rows {
+            let row = vec![
+                Value::UInt(time),
+                Value::Int(delta),
+                Value::Float(load),
+                Value::Bool(open),
+                Value::Text(name.to_string()),
+            ];
+            table.push_row(row).unwrap();
+        }
+        let path =
+            std::env::temp_dir().join(format!("aika-parquet-{}.parquet", std::process::id()));
+        table.write_parquet_file(&path).unwrap();
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let metadata = reader.metadata().file_metadata();
+        assert_eq!(metadata.num_rows(), 3);
+        let schema = metadata.schema_descr();
+        let columns = schema
+            .columns()
+            .iter()
+            .map(|column| {
+                (
+                    column.name().to_string(),
+                    column.physical_type(),
+                    column.converted_type(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            columns,
+            vec![
+                ("time".to_string(), Type::INT64, ConvertedType::UINT_64),
+                ("delta".to_string(), Type::INT64, ConvertedType::NONE),
+                ("load".to_string(), Type::DOUBLE, ConvertedType::NONE),
+                ("open".to_string(), Type::BOOLEAN, ConvertedType::NONE),
+                ("name".to_string(), Type::BYTE_ARRAY, ConvertedType::UTF8),
+            ]
+        );
+
+        let read = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| {
+                let row = row.unwrap();
+                (
+                    row.get_ulong(0).unwrap(),
+                    row.get_long(1).unwrap(),
+                    row.get_double(2).unwrap(),
+                    row.get_bool(3).unwrap(),
+                    row.get_string(4).unwrap().clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let expected = rows
+            .iter()
+            .map(|&(time, delta, load, open, name)| (time, delta, load, open, name.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(read, expected);
+    }
+}
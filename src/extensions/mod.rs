@@ -0,0 +1,155 @@
+//! Composable world extensions for domain packs.
+//! A `WorldExtension` adds world-level machinery, such as resources, feeds, a spatial index or queues, to a `World`
+//! or `Planet` without touching the engine, so a domain pack for finance, logistics or epidemiology can live in its
+//! own crate. Extensions are registered under a unique name and reached by agents through the `extensions` of their
+//! context. The world calls their lifecycle hooks around every step, and journals their state after each step that
+//! changed it: when a `Planet` rolls back, every extension is restored to the state it had at the rollback time
+//! before `on_rollback()` is called. Journal entries older than GVT are dropped, keeping the one still in force.
+//! `queueing` is an in-crate reference pack.
+use std::any::Any;
+
+use crate::AikaError;
+
+pub mod queueing;
+
+/// World-level machinery plugged into a `World` or `Planet`.
+pub trait WorldExtension: Any + Send {
+    /// Name the extension is registered under, unique per world.
+    fn name(&self) -> &'static str;
+
+    /// The extension was registered on world `world_id`.
+    fn on_register(&mut self, _world_id: usize) {}
+
+    /// The world is about to process the step at `time`.
+    fn before_step(&mut self, _time: u64) {}
+
+    /// The world processed the step at `time`.
+    fn after_step(&mut self, _time: u64) {}
+
+    /// The world rolled back to `time`, and the extension's state was already restored to that time.
+    fn on_rollback(&mut self, _time: u64) {}
+
+    /// The world finished its run at `time`.
+    fn on_finish(&mut self, _time: u64) {}
+
+    /// The state to journal.
+    fn save(&self) -> Result<Vec<u8>, AikaError>;
+
+    /// Restore a state returned by `save()`.
+    fn restore(&mut self, state: &[u8]) -> Result<(), AikaError>;
+}
+
+struct Registered {
+    extension: Box<dyn WorldExtension>,
+    /// `(time, state)` in force from `time` on, oldest first
+    journal: Vec<(u64, Vec<u8>)>,
+}
+
+/// The extensions registered on one world.
+#[derive(Default)]
+pub struct Extensions {
+    registered: Vec<Registered>,
+}
+
+impl std::fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+impl Extensions {
+    /// Register `extension` on world `world_id`, journaling its state from `time` on.
+    pub fn register(
+        &mut self,
+        world_id: usize,
+        time: u64,
+        mut extension: Box<dyn WorldExtension>,
+    ) -> Result<(), AikaError> {
+        let name = extension.name();
+        if self.names().any(|registered| registered == name) {
+            return Err(AikaError::ConfigError(format!(
+                "an extension named {name} is already registered on world {world_id}"
+            )));
+        }
+        extension.on_register(world_id);
+        let state = extension.save()?;
+        self.registered.push(Registered {
+            extension,
+            journal: vec![(time, state)],
+        });
+        Ok(())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.registered
+            .iter()
+            .map(|registered| registered.extension.name())
+    }
+
+    /// The registered extension of type `T`, if any.
+    pub fn get<T: WorldExtension>(&self) -> Option<&T> {
+        self.registered
+            .iter()
+            .find_map(|registered| (registered.extension.as_ref() as &dyn Any).downcast_ref::<T>())
+    }
+
+    /// The registered extension of type `T`, if any, for changing its state.
+    pub fn get_mut<T: WorldExtension>(&mut self) -> Option<&mut T> {
+        self.registered.iter_mut().find_map(|registered| {
+            (registered.extension.as_mut() as &mut dyn Any).downcast_mut::<T>()
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registered.is_empty()
+    }
+
+    pub(crate) fn before_step(&mut self, time: u64) {
+        for registered in &mut self.registered {
+            registered.extension.before_step(time);
+        }
+    }
+
+    /// Run the `after_step()` hooks and journal every changed state, dropping entries superseded before `gvt`.
+    pub(crate) fn after_step(&mut self, time: u64, gvt: u64) -> Result<(), AikaError> {
+        for registered in &mut self.registered {
+            registered.extension.after_step(time);
+            let state = registered.extension.save()?;
+            if registered
+                .journal
+                .last()
+                .is_none_or(|(_, last)| *last != state)
+            {
+                registered.journal.push((time.saturating_add(1), state));
+            }
+            let superseded = registered
+                .journal
+                .partition_point(|(from, _)| *from <= gvt)
+                .saturating_sub(1);
+            registered.journal.drain(..superseded);
+        }
+        Ok(())
+    }
+
+    /// Restore every extension to the state in force at `time`.
+    pub(crate) fn rollback(&mut self, time: u64) -> Result<(), AikaError> {
+        for registered in &mut self.registered {
+            let keep = registered
+                .journal
+                .partition_point(|(from, _)| *from <= time)
+                .max(1);
+            registered.journal.truncate(keep);
+            if let Some((_, state)) = registered.journal.last() {
+                registered.extension.restore(state)?;
+            }
+            registered.extension.on_rollback(time);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(&mut self, time: u64) {
+        for registered in &mut self.registered {
+            registered.extension.on_finish(time);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Reference domain pack: a multi-server FIFO service queue, as found in logistics, retail and hospital models.
+//! Arriving customers wait in line until one of the servers is free. The queue tracks how many customers it served,
+//! its longest line and its mean line length over the steps of the run, and journals all of it through
+//! `WorldExtension::save()`, so a rolled-back `Planet` sees the line as it was.
+use std::collections::VecDeque;
+
+use crate::{extensions::WorldExtension, AikaError};
+
+/// A line of customers in front of `servers` identical servers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ServiceQueue {
+    servers: u64,
+    busy: u64,
+    waiting: VecDeque<u64>,
+    served: u64,
+    longest: u64,
+    /// line lengths summed over the steps so far
+    line_area: u64,
+    steps: u64,
+}
+
+impl ServiceQueue {
+    pub fn new(servers: u64) -> Self {
+        Self {
+            servers,
+            ..Default::default()
+        }
+    }
+
+    /// Put `customer` at the back of the line.
+    pub fn arrive(&mut self, customer: u64) {
+        self.waiting.push_back(customer);
+        self.longest = self.longest.max(self.waiting.len() as u64);
+    }
+
+    /// Take the customer at the front of the line to a free server, if there is one.
+    pub fn start_service(&mut self) -> Option<u64> {
+        if self.busy == self.servers {
+            return None;
+        }
+        let customer = self.waiting.pop_front()?;
+        self.busy += 1;
+        Some(customer)
+    }
+
+    /// Free a server after it finished with its customer.
+    pub fn finish_service(&mut self) -> Result<(), AikaError> {
+        if self.busy == 0 {
+            return Err(AikaError::ConfigError(
+                "no server of the queue is busy".to_string(),
+            ));
+        }
+        self.busy -= 1;
+        self.served += 1;
+        Ok(())
+    }
+
+    pub fn waiting(&self) -> usize {
+        self.waiting.len()
+    }
+
+    pub fn busy(&self) -> u64 {
+        self.busy
+    }
+
+    pub fn served(&self) -> u64 {
+        self.served
+    }
+
+    pub fn longest(&self) -> u64 {
+        self.longest
+    }
+
+    /// Mean line length at the end of each step so far.
+    pub fn mean_line(&self) -> f64 {
+        if self.steps == 0 {
+            return 0.0;
+        }
+        self.line_area as f64 / self.steps as f64
+    }
+}
+
+impl WorldExtension for ServiceQueue {
+    fn name(&self) -> &'static str {
+        "queueing.service_queue"
+    }
+
+    fn after_step(&mut self, _time: u64) {
+        self.line_area += self.waiting.len() as u64;
+        self.steps += 1;
+    }
+
+    fn save(&self) -> Result<Vec<u8>, AikaError> {
+        let words = [
+            self.servers,
+            self.busy,
+            self.served,
+            self.longest,
+            self.line_area,
+            self.steps,
+        ];
+        Ok(words
+            .into_iter()
+            .chain(self.waiting.iter().copied())
+            .flat_map(u64::to_le_bytes)
+            .collect())
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), AikaError> {
+        let words = state
+            .chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        let [servers, busy, served, longest, line_area, steps, ref waiting @ ..] = words[..] else {
+            return Err(AikaError::StateCodec(format!(
+                "service queue state is {} bytes, expected at least 48",
+                state.len()
+            )));
+        };
+        *self = Self {
+            servers,
+            busy,
+            waiting: waiting.iter().copied().collect(),
+            served,
+            longest,
+            line_area,
+            steps,
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agents::{Agent, WorldContext},
+        extensions::Extensions,
+        objects::{Action, Event, Msg},
+        st::World,
+    };
+
+    // Brings a new customer every step
+    struct Door {
+        next: u64,
+    }
+
+    impl Agent<8, Msg<u8>> for Door {
+        fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, agent_id: usize) -> Event {
+            let queue = context.extensions.get_mut::<ServiceQueue>().unwrap();
+            queue.arrive(self.next);
+            self.next += 1;
+            Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+        }
+    }
+
+    // Takes two steps per customer
+    struct Clerk {
+        serving: Option<u64>,
+    }
+
+    impl Agent<8, Msg<u8>> for Clerk {
+        fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, agent_id: usize) -> Event {
+            let queue = context.extensions.get_mut::<ServiceQueue>().unwrap();
+            match self.serving.take() {
+                Some(_) => queue.finish_service().unwrap(),
+                None => self.serving = queue.start_service(),
+            }
+            Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+        }
+    }
+
+    #[test]
+    fn test_service_queue_pack() {
+        let mut world = World::<8, 16, 1, u8>::init(10.0, 1.0, 0).unwrap();
+        world.spawn_agent(Box::new(Door { next: 0 }));
+        world.spawn_agent(Box::new(Clerk { serving: None }));
+        world.init_support_layers(None).unwrap();
+        world
+            .register_extension(Box::new(ServiceQueue::new(1)))
+            .unwrap();
+        assert!(world
+            .register_extension(Box::new(ServiceQueue::new(2)))
+            .is_err());
+        world.schedule_all_agents(0).unwrap();
+        world.run().unwrap();
+
+        // the door steps before the clerk, who takes a customer every other step
+        let queue = world
+            .world_context
+            .extensions
+            .get::<ServiceQueue>()
+            .unwrap();
+        assert_eq!(queue.served(), 5);
+        assert_eq!(queue.waiting(), 5);
+        assert_eq!(queue.longest(), 5);
+        assert!(queue.mean_line() > 2.0);
+
+        // a rollback restores the line as it was at the rollback time
+        let mut extensions = Extensions::default();
+        extensions
+            .register(0, 0, Box::new(ServiceQueue::new(1)))
+            .unwrap();
+        for time in 0..6 {
+            extensions.before_step(time);
+            let queue = extensions.get_mut::<ServiceQueue>().unwrap();
+            queue.arrive(time);
+            extensions.after_step(time, 2).unwrap();
+        }
+        extensions.rollback(4).unwrap();
+        let queue = extensions.get::<ServiceQueue>().unwrap();
+        assert_eq!((queue.waiting(), queue.mean_line()), (4, 2.5));
+    }
+}
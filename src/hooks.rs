@@ -0,0 +1,109 @@
+//! Observation hooks for metrics integrations.
+//! A `SimHook` registered on a `World`, `Planet` or `Galaxy` is called on every processed event, rollback,
+//! GVT advance and checkpoint block submission. Every callback defaults to a no-op, and nothing is called
+//! when no hook is registered, so the hot path is unaffected for simulations that don't use them.
+use crate::mt::hybrid::reports::RollbackReport;
+
+/// Callbacks for exporting simulation metrics, e.g. into Prometheus or OpenTelemetry.
+pub trait SimHook: Send {
+    /// An agent was stepped at `time` on world `world_id`.
+    fn on_event(&mut self, _world_id: usize, _agent: usize, _time: u64) {}
+
+    /// A `Planet` rolled back from local time `from` to `to`.
+    fn on_rollback(&mut self, _world_id: usize, _from: u64, _to: u64) {}
+
+    /// A `Planet` summed up the rollbacks of a GVT window, when rollback reports are enabled.
+    fn on_rollback_report(&mut self, _report: &RollbackReport) {}
+
+    /// The `Galaxy` advanced GVT from `from` to `to`.
+    fn on_gvt_advance(&mut self, _from: u64, _to: u64) {}
+
+    /// A `Planet` finished its block of time up to `checkpoint` and handed it to the `Galaxy`.
+    fn on_block_submit(&mut self, _world_id: usize, _checkpoint: u64) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Tick {
+        value: u32,
+    }
+
+    unsafe impl Pod for Tick {}
+    unsafe impl Zeroable for Tick {}
+
+    struct Ticker;
+
+    impl ThreadedAgent<16, Tick> for Ticker {
+        fn step(&mut self, context: &mut PlanetContext<16, Tick>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, Tick>,
+            _msg: Msg<Tick>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Counters {
+        events: Arc<AtomicUsize>,
+        blocks: Arc<AtomicUsize>,
+        gvt: Arc<AtomicU64>,
+    }
+
+    impl SimHook for Counters {
+        fn on_event(&mut self, _world_id: usize, _agent: usize, _time: u64) {
+            self.events.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn on_gvt_advance(&mut self, _from: u64, to: u64) {
+            self.gvt.store(to, Ordering::Relaxed);
+        }
+
+        fn on_block_submit(&mut self, _world_id: usize, _checkpoint: u64) {
+            self.blocks.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_hooks_observe_hybrid_run() {
+        let config = HybridConfig::new(2, 16)
+            .with_time_bounds(30.0, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<16, 128, 1, Tick>::create(config).unwrap();
+        let counters = Counters::default();
+        for planet in 0..2 {
+            engine.spawn_agent(planet, Box::new(Ticker)).unwrap();
+            engine.planets[planet].set_hook(Box::new(counters.clone()));
+        }
+        engine.galaxy.set_hook(Box::new(counters.clone()));
+        engine.schedule_all_agents(1).unwrap();
+        engine.run().unwrap();
+
+        // each agent steps at 1..30 with no rollbacks, as nothing is sent
+        assert_eq!(counters.events.load(Ordering::Relaxed), 58);
+        assert_eq!(counters.gvt.load(Ordering::Relaxed), 30);
+        // both planets submit the blocks ending at 10, 20 and 30
+        assert_eq!(counters.blocks.load(Ordering::Relaxed), 6);
+    }
+}
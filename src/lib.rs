@@ -9,18 +9,44 @@
 //! - [`mt::hybrid`] - Multi-threaded optimistic synchronization
 //! - [`agents`] - Agent traits and execution contexts
 //! - [`objects`] - Core simulation data structures
+//! - [`dynclock`] - Timing wheels sized at runtime
+//! - [`env`](mod@env) - Gym-style reinforcement learning environments over hybrid runs
+//! - [`analysis`] - Post-run analysis of completed simulations
+//! - [`extensions`] - Composable world extensions for domain packs
+//! - [`export`] - CSV and Parquet export of simulation results
+//! - [`experiments`] - Parameter sweeps over repeated runs
+//! - [`hooks`] - Metrics callbacks for steps, rollbacks and GVT updates
+//! - [`mailbox`] - Overflow policies for full mailboxes and inboxes
+//! - [`overflow`] - Bounded growth for far-future events beyond the timing wheels
+//! - [`testing`] - Test utilities such as message conservation checks and golden-file comparisons
+//! - [`time`] - Typed simulation times and durations
+//! - [`tracing`] - Chrome/Perfetto timeline traces of hybrid runs
 
 use mesocarp::MesoError;
 use thiserror::Error;
 
 pub mod agents;
+pub mod analysis;
+pub mod dynclock;
+pub mod env;
+pub mod experiments;
+pub mod export;
+pub mod extensions;
+pub mod hooks;
+pub mod mailbox;
 pub mod mt;
 pub mod objects;
+pub mod overflow;
 pub mod st;
+pub mod testing;
+pub mod time;
+pub mod tracing;
 
 pub mod prelude {
     pub use crate::agents::{Agent, AgentSupport, PlanetContext, ThreadedAgent, WorldContext};
-    pub use crate::objects::{Action, AntiMsg, Event, Msg};
+    pub use crate::mt::hybrid::routing::AgentHandle;
+    pub use crate::objects::{Action, AntiMsg, CausalId, Event, EventId, GroupId, Msg};
+    pub use crate::time::{SimDuration, SimTime, TimeScale};
     pub use crate::AikaError;
     pub use bytemuck::{Pod, Zeroable};
 }
@@ -40,6 +66,10 @@ pub enum AikaError {
     NotAllAgentsRegistered,
     #[error("Thread panicked!")]
     ThreadPanic,
+    #[error("Thread panicked!{}", mt::hybrid::panics::describe(.0))]
+    ThreadPanicked(Vec<mt::hybrid::panics::PanicReport>),
+    #[error("A lock was poisoned by a thread that panicked while holding it.")]
+    PoisonedLock,
     #[error("Mail delivered to the wrong address, fire the mail man.")]
     MismatchedDeliveryAddress,
     #[error("Error found when utilizing `mesocarp`: {0}.")]
@@ -50,4 +80,76 @@ pub enum AikaError {
     InvalidWorldId(usize),
     #[error("Configuration error: {0}")]
     ConfigError(String),
+    #[error("Control channel to planet {0} is closed.")]
+    ControlChannelClosed(usize),
+    #[error("State codec error: {0}")]
+    StateCodec(String),
+    #[error("Payload schema mismatch: {0}")]
+    SchemaMismatch(String),
+    #[error("Unknown agent handle: {0}")]
+    InvalidAgentHandle(u64),
+    #[error("Overflow heap is full, refused event for agent {0} at {1}.")]
+    OverflowFull(usize, u64),
+    #[error("Mailbox {0} is full.")]
+    MailboxFull(usize),
+    #[error("Overflow spill error: {0}")]
+    OverflowSpill(String),
+    #[error("Consistent cut at {0} was never taken.")]
+    CutNotTaken(u64),
+    #[error("Barrier at {0} can't be reached by every planet.")]
+    BarrierUnreachable(u64),
+    #[error("Bridge error: {0}")]
+    Bridge(String),
+    #[error("Agent {agent} on planet {world} panicked at {time}: {message}")]
+    AgentPanicked {
+        world: usize,
+        agent: usize,
+        time: u64,
+        message: String,
+    },
+    #[error("Unknown agent template: {0}")]
+    InvalidTemplate(usize),
+    #[error("Scenario failed validation: {0}")]
+    InvalidScenario(crate::mt::hybrid::scenario::ScenarioViolations),
+    #[error("Agent {agent} overflowed simulation time, asking for {delay} steps after {time}.")]
+    TimeOverflow { agent: usize, time: u64, delay: u64 },
+    #[error("No route from planet {0} to planet {1}.")]
+    NoRoute(usize, usize),
+    #[error("Msg from agent {0} on planet {1} is not a request.")]
+    NotARequest(usize, usize),
+    #[error("No payload held for {0:?}, it was never sent or is past GVT.")]
+    MissingParcel(crate::mt::hybrid::parcels::Parcel),
+    #[error("Unknown resource: {0}")]
+    InvalidResource(usize),
+    #[error("Agent {agent} released a unit of resource {resource} it doesn't hold.")]
+    ResourceNotHeld { resource: usize, agent: usize },
+    #[error("Unknown shared partition: {0}")]
+    InvalidPartition(usize),
+    #[error("Agent {agent} is not a member of shared partition {partition}.")]
+    NotAPartitionMember { partition: usize, agent: usize },
+    #[error("Index {index} is outside shared partition {partition}.")]
+    PartitionIndex { partition: usize, index: usize },
+    #[error("Unknown agent: {0}")]
+    InvalidAgentId(usize),
+    #[error("Planet {world} used more than {limit} bytes at {time}: {usage}.")]
+    MemoryLimitExceeded {
+        world: usize,
+        time: u64,
+        limit: usize,
+        usage: mt::hybrid::memory::MemoryUsage,
+    },
+    #[error("State at {time} is no longer kept, the oldest is at {floor}.")]
+    HistoryPruned { time: u64, floor: u64 },
+    #[error("Agent {0} has no state journal.")]
+    NoStateJournal(usize),
+    #[error("Capability denied: {0:?}")]
+    CapabilityDenied(crate::agents::sandbox::Violation),
+    #[error("Not enough data for an estimate: {0}")]
+    InsufficientData(String),
+    #[error("Export error: {0}")]
+    Export(String),
+    #[error("Golden file error: {0}")]
+    Golden(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
 }
@@ -0,0 +1,138 @@
+//! What becomes of mail that finds its recipient's inbox full.
+//! A `World` delivers `Msg`s into its agents' mailboxes, and the `Galaxy` delivers bundles of `Mail` into its
+//! planets' inboxes, each a ring of `MESSAGE_SLOTS` or `INTER_SLOTS` entries. A letter that doesn't fit is handled
+//! by the engine's `OverflowPolicy`: held until there is room, dropped, or made to fail the run. Held letters are
+//! retried ahead of newer mail every round, so an inbox still receives its mail in the order it was sent, and on a
+//! `HybridEngine` they count as in flight, holding GVT back until they are delivered.
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::AikaError;
+
+/// How an engine treats a letter for a full inbox.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// hold it, and every later letter for the inbox, until there is room
+    Block,
+    /// drop it, keeping the older mail already waiting
+    DropNewest,
+    /// hold it, dropping the oldest held letters for the inbox once more than a full inbox is waiting
+    DropOldest,
+    /// fail with `AikaError::MailboxFull`
+    Error,
+}
+
+/// Mail dropped or held by an `OverflowPolicy`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MailboxStats {
+    /// `Msg`s dropped for full inboxes
+    pub dropped: u64,
+    /// letters waiting for room now
+    pub held: usize,
+    /// most letters ever waiting for room at once
+    pub peak_held: usize,
+}
+
+/// Letters waiting for room in their inboxes, by inbox.
+#[derive(Debug)]
+pub(crate) struct Backlog<T> {
+    policy: OverflowPolicy,
+    /// most letters held for one inbox under `DropOldest`
+    limit: usize,
+    held: BTreeMap<usize, VecDeque<T>>,
+    stats: MailboxStats,
+}
+
+impl<T> Backlog<T> {
+    pub(crate) fn new(policy: OverflowPolicy, limit: usize) -> Self {
+        Self {
+            policy,
+            limit: limit.max(1),
+            held: BTreeMap::new(),
+            stats: MailboxStats::default(),
+        }
+    }
+
+    pub(crate) fn set_policy(&mut self, policy: OverflowPolicy) {
+        self.policy = policy;
+    }
+
+    /// Apply the policy to `letter`, which found inbox `idx` full, returning the letters it drops. Dropped letters
+    /// are not counted, since a letter may carry any number of `Msg`s; see `record_dropped()`.
+    pub(crate) fn overflow(&mut self, idx: usize, letter: T) -> Result<Vec<T>, AikaError> {
+        let dropped = match self.policy {
+            OverflowPolicy::Error => return Err(AikaError::MailboxFull(idx)),
+            OverflowPolicy::DropNewest => vec![letter],
+            OverflowPolicy::Block => {
+                self.held.entry(idx).or_default().push_back(letter);
+                Vec::new()
+            }
+            OverflowPolicy::DropOldest => {
+                let held = self.held.entry(idx).or_default();
+                held.push_back(letter);
+                let excess = held.len().saturating_sub(self.limit);
+                held.drain(..excess).collect()
+            }
+        };
+        self.stats.held = self.held.values().map(VecDeque::len).sum();
+        self.stats.peak_held = self.stats.peak_held.max(self.stats.held);
+        Ok(dropped)
+    }
+
+    pub(crate) fn record_dropped(&mut self, msgs: u64) {
+        self.stats.dropped += msgs;
+    }
+
+    /// Take every held letter to retry, oldest first for each inbox.
+    pub(crate) fn take(&mut self) -> Vec<(usize, T)> {
+        self.stats.held = 0;
+        std::mem::take(&mut self.held)
+            .into_iter()
+            .flat_map(|(idx, letters)| letters.into_iter().map(move |letter| (idx, letter)))
+            .collect()
+    }
+
+    /// Whether letters are waiting for room in inbox `idx`, so that a newer letter must wait behind them.
+    pub(crate) fn holds(&self, idx: usize) -> bool {
+        self.held.contains_key(&idx)
+    }
+
+    pub(crate) fn stats(&self) -> MailboxStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policies() {
+        let mut backlog = Backlog::new(OverflowPolicy::DropOldest, 2);
+        for letter in 0..4 {
+            let dropped = backlog.overflow(1, letter).unwrap();
+            assert_eq!(
+                dropped,
+                (letter >= 2)
+                    .then_some(letter - 2)
+                    .into_iter()
+                    .collect::<Vec<_>>()
+            );
+        }
+        backlog.overflow(0, 9).unwrap();
+        assert_eq!(backlog.stats().peak_held, 3);
+        assert!(backlog.holds(0) && !backlog.holds(2));
+        assert_eq!(backlog.take(), [(0, 9), (1, 2), (1, 3)]);
+        assert!(!backlog.holds(1));
+
+        backlog.set_policy(OverflowPolicy::DropNewest);
+        assert_eq!(backlog.overflow(1, 5).unwrap(), [5]);
+        backlog.set_policy(OverflowPolicy::Block);
+        assert!(backlog.overflow(1, 6).unwrap().is_empty());
+        backlog.set_policy(OverflowPolicy::Error);
+        assert!(matches!(
+            backlog.overflow(1, 7),
+            Err(AikaError::MailboxFull(1))
+        ));
+        assert_eq!(backlog.stats().held, 1);
+    }
+}
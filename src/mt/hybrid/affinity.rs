@@ -0,0 +1,196 @@
+//! Pinning the threads of a hybrid run to cores.
+//! Left to the OS scheduler, the `Galaxy` and `Planet` threads hop between cores, and the jitter shows in benchmark
+//! runs. A `ThreadPlacement` picks a core for every thread instead, the `Galaxy` first and then each `Planet` in
+//! order, among the cores the process may run on. Each thread pins itself when its loop starts. Planets spawned by
+//! splits are left unpinned. Pinning is only supported on Linux.
+use crate::AikaError;
+
+/// How `HybridEngine::run()` places its threads on cores.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ThreadPlacement {
+    /// Pin thread `i` to the `i`-th listed core, wrapping around if there are more threads than cores.
+    Pinned(Vec<usize>),
+    /// Spread the threads evenly over the available cores, leaving gaps between them.
+    Spread,
+    /// Pack the threads onto adjacent cores, starting from the lowest.
+    Compact,
+}
+
+impl ThreadPlacement {
+    /// The core of each of `threads` threads, among `available` cores in ascending order.
+    pub fn assign(&self, threads: usize, available: &[usize]) -> Result<Vec<usize>, AikaError> {
+        if available.is_empty() {
+            return Err(AikaError::ConfigError(
+                "no core is available to pin threads to".to_string(),
+            ));
+        }
+        match self {
+            ThreadPlacement::Pinned(cores) => {
+                if cores.is_empty() {
+                    return Err(AikaError::ConfigError(
+                        "a pinned placement needs at least one core".to_string(),
+                    ));
+                }
+                if let Some(core) = cores.iter().find(|core| !available.contains(core)) {
+                    return Err(AikaError::ConfigError(format!(
+                        "core {core} isn't available to this process, which may run on {available:?}"
+                    )));
+                }
+                Ok((0..threads).map(|i| cores[i % cores.len()]).collect())
+            }
+            ThreadPlacement::Spread => {
+                let stride = (available.len() / threads.max(1)).max(1);
+                Ok((0..threads)
+                    .map(|i| available[(i * stride) % available.len()])
+                    .collect())
+            }
+            ThreadPlacement::Compact => Ok((0..threads)
+                .map(|i| available[i % available.len()])
+                .collect()),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    /// Words of a `cpu_set_t`, which holds 1024 cores.
+    pub(super) const SET_WORDS: usize = 16;
+
+    extern "C" {
+        pub(super) fn sched_setaffinity(pid: i32, size: usize, mask: *const u64) -> i32;
+        pub(super) fn sched_getaffinity(pid: i32, size: usize, mask: *mut u64) -> i32;
+    }
+}
+
+/// Cores the calling thread may run on, in ascending order.
+#[cfg(target_os = "linux")]
+pub fn available_cores() -> Result<Vec<usize>, AikaError> {
+    let mut mask = [0u64; sys::SET_WORDS];
+    // pid 0 is the calling thread, and the mask is as large as the size passed
+    let status = unsafe { sys::sched_getaffinity(0, size_of_val(&mask), mask.as_mut_ptr()) };
+    if status != 0 {
+        return Err(AikaError::Io(std::io::Error::last_os_error()));
+    }
+    Ok((0..sys::SET_WORDS * 64)
+        .filter(|core| mask[core / 64] & (1 << (core % 64)) != 0)
+        .collect())
+}
+
+/// Cores the calling thread may run on, in ascending order.
+#[cfg(not(target_os = "linux"))]
+pub fn available_cores() -> Result<Vec<usize>, AikaError> {
+    Err(AikaError::ConfigError(
+        "thread placement is only supported on Linux".to_string(),
+    ))
+}
+
+/// Pin the calling thread to `core`.
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(core: usize) -> Result<(), AikaError> {
+    if core >= sys::SET_WORDS * 64 {
+        return Err(AikaError::ConfigError(format!(
+            "core {core} is past the last core that can be pinned to"
+        )));
+    }
+    let mut mask = [0u64; sys::SET_WORDS];
+    mask[core / 64] = 1 << (core % 64);
+    // pid 0 is the calling thread, and the mask is as large as the size passed
+    let status = unsafe { sys::sched_setaffinity(0, size_of_val(&mask), mask.as_ptr()) };
+    if status != 0 {
+        return Err(AikaError::Io(std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+/// Pin the calling thread to `core`.
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(_core: usize) -> Result<(), AikaError> {
+    Err(AikaError::ConfigError(
+        "thread placement is only supported on Linux".to_string(),
+    ))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    // Notes the cores its planet's thread may run on
+    struct Witness {
+        cores: Arc<Mutex<Vec<Vec<usize>>>>,
+    }
+
+    impl ThreadedAgent<128, u64> for Witness {
+        fn step(&mut self, context: &mut PlanetContext<128, u64>, agent_id: usize) -> Event {
+            if context.time == 1 {
+                self.cores.lock().unwrap().push(available_cores().unwrap());
+            }
+            Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, u64>,
+            _msg: Msg<u64>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_thread_placement() {
+        let available = [0, 1, 2, 3, 4, 5, 6, 7];
+        assert_eq!(
+            ThreadPlacement::Compact.assign(3, &available).unwrap(),
+            vec![0, 1, 2]
+        );
+        assert_eq!(
+            ThreadPlacement::Spread.assign(3, &available).unwrap(),
+            vec![0, 2, 4]
+        );
+        assert_eq!(
+            ThreadPlacement::Spread.assign(10, &available[..2]).unwrap(),
+            vec![0, 1, 0, 1, 0, 1, 0, 1, 0, 1]
+        );
+        assert_eq!(
+            ThreadPlacement::Pinned(vec![5, 7])
+                .assign(3, &available)
+                .unwrap(),
+            vec![5, 7, 5]
+        );
+        assert!(ThreadPlacement::Pinned(vec![9])
+            .assign(1, &available)
+            .is_err());
+
+        let first = available_cores().unwrap()[0];
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(10.0, 1.0)
+            .with_optimistic_sync(2, 5)
+            .with_uniform_worlds(16, 1, 16)
+            .with_thread_placement(ThreadPlacement::Pinned(vec![first]));
+        let mut engine = HybridEngine::<128, 128, 1, u64>::create(config).unwrap();
+        let cores = Arc::new(Mutex::new(Vec::new()));
+        for planet in 0..2 {
+            let witness = Witness {
+                cores: cores.clone(),
+            };
+            engine.spawn_agent(planet, Box::new(witness)).unwrap();
+        }
+        engine.schedule_all_agents(1).unwrap();
+        engine.run().unwrap();
+        assert_eq!(*cores.lock().unwrap(), vec![vec![first]; 2]);
+
+        let config = HybridConfig::new(1, 64)
+            .with_time_bounds(10.0, 1.0)
+            .with_optimistic_sync(2, 5)
+            .with_uniform_worlds(16, 1, 16)
+            .with_thread_placement(ThreadPlacement::Pinned(vec![4096]));
+        assert!(HybridEngine::<128, 128, 1, u64>::create(config).is_err());
+    }
+}
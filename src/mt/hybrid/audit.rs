@@ -0,0 +1,253 @@
+//! Determinism audits of hybrid runs.
+//! With `HybridConfig::with_determinism_audit()` the engine registers a consistent cut at every checkpoint the run
+//! passes, every `checkpoint_frequency` ticks. Each `Planet` stalls there until GVT catches up and hashes its
+//! committed state as a `PlanetCut`: its agents' snapshots, its shared partitions, its pending events and its unread
+//! mail. Agent journals and the world state are type-erased, so they enter the hash through
+//! `ThreadedAgent::snapshot()`. Each planet's digests roll over the checkpoints, and the engine collects them into a
+//! `Fingerprint` of the run. Two runs of a reproducible scenario, see `HybridConfig::with_determinism()`, have the
+//! same fingerprint, and `Fingerprint::divergence()` names the checkpoint and planet where two runs first part.
+use std::{
+    collections::VecDeque,
+    sync::mpsc::{Receiver, Sender},
+};
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::mt::hybrid::cut::{Digest, PlanetCut};
+
+/// Rolling digests of the committed state of every `Planet`, at each checkpoint of a run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// `(checkpoint, rolling digest of every planet by world id)`, in time order
+    pub checkpoints: Vec<(u64, Vec<u64>)>,
+}
+
+impl Fingerprint {
+    /// Digest of the run so far, linking every planet's rolling digest at the last checkpoint.
+    pub fn head(&self) -> u64 {
+        self.checkpoints.last().map_or(0, |(checkpoint, digests)| {
+            let digest = Digest::new().word(*checkpoint);
+            digests
+                .iter()
+                .fold(digest, |digest, planet| digest.word(*planet))
+                .0
+        })
+    }
+
+    /// The first checkpoint at which `self` and `other` differ, if they differ before either ends.
+    pub fn divergence(&self, other: &Fingerprint) -> Option<Divergence> {
+        for ((at, mine), (theirs_at, theirs)) in self.checkpoints.iter().zip(&other.checkpoints) {
+            if at != theirs_at || mine.len() != theirs.len() {
+                return Some(Divergence {
+                    checkpoint: *at.min(theirs_at),
+                    planet: None,
+                });
+            }
+            if let Some(planet) = mine.iter().zip(theirs).position(|(a, b)| a != b) {
+                return Some(Divergence {
+                    checkpoint: *at,
+                    planet: Some(planet),
+                });
+            }
+        }
+        let common = self.checkpoints.len().min(other.checkpoints.len());
+        let longer = if self.checkpoints.len() > common {
+            self
+        } else {
+            other
+        };
+        longer
+            .checkpoints
+            .get(common)
+            .map(|(checkpoint, _)| Divergence {
+                checkpoint: *checkpoint,
+                planet: None,
+            })
+    }
+}
+
+/// Where two runs first differ.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub checkpoint: u64,
+    /// world id of the first `Planet` whose state differs, `None` if only one run has the checkpoint or the runs
+    /// have different numbers of planets
+    pub planet: Option<usize>,
+}
+
+/// A checkpoint not every `Planet` has recorded yet.
+struct Watched<MessageType: Pod + Zeroable + Clone> {
+    checkpoint: u64,
+    receiver: Receiver<PlanetCut<MessageType>>,
+    /// `(world id, digest)` of the planets recorded so far
+    recorded: Vec<(usize, u64)>,
+}
+
+/// The cuts of an audited run, collected into its `Fingerprint` as every `Planet` records them.
+pub(crate) struct Audit<MessageType: Pod + Zeroable + Clone> {
+    planets: usize,
+    pending: VecDeque<Watched<MessageType>>,
+    rolling: Vec<u64>,
+    fingerprint: Fingerprint,
+}
+
+impl<MessageType: Pod + Zeroable + Clone> Audit<MessageType> {
+    pub(crate) fn new(planets: usize) -> Self {
+        Self {
+            planets,
+            pending: VecDeque::new(),
+            rolling: vec![Digest::new().0; planets],
+            fingerprint: Fingerprint::default(),
+        }
+    }
+
+    /// Watch the checkpoint at `time`, after every one watched so far, returning where planets send their cuts.
+    pub(crate) fn watch(&mut self, time: u64) -> Sender<PlanetCut<MessageType>> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.pending.push_back(Watched {
+            checkpoint: time,
+            receiver,
+            recorded: Vec::new(),
+        });
+        sender
+    }
+
+    /// Fold the checkpoints every `Planet` has recorded into the fingerprint, in time order.
+    pub(crate) fn collect(&mut self) {
+        while let Some(watched) = self.pending.front_mut() {
+            while let Ok(cut) = watched.receiver.try_recv() {
+                watched.recorded.push((cut.world_id, cut.digest));
+            }
+            if watched.recorded.len() < self.planets {
+                return;
+            }
+            watched.recorded.sort_unstable();
+            for (world, digest) in &watched.recorded {
+                self.rolling[*world] = Digest(self.rolling[*world]).word(*digest).0;
+            }
+            self.fingerprint
+                .checkpoints
+                .push((watched.checkpoint, self.rolling.clone()));
+            self.pending.pop_front();
+        }
+    }
+
+    pub(crate) fn fingerprint(&self) -> &Fingerprint {
+        &self.fingerprint
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+        AikaError,
+    };
+
+    // Mails planet 1 its time every step, five ticks ahead, and twice its time from `doubled` on
+    struct Ticker {
+        doubled: u64,
+    }
+
+    impl ThreadedAgent<16, u64> for Ticker {
+        fn step(&mut self, context: &mut PlanetContext<16, u64>, id: usize) -> Event {
+            let time = context.time;
+            let data = if time >= self.doubled { 2 * time } else { time };
+            context
+                .send_mail(Msg::new(data, time, time + 5, id, Some(0)), 1)
+                .unwrap();
+            Event::new(time, time, id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, u64>,
+            _msg: Msg<u64>,
+            _id: usize,
+        ) {
+        }
+    }
+
+    // Sums the mail of planet 1
+    #[derive(Default)]
+    struct Sum {
+        total: u64,
+    }
+
+    impl ThreadedAgent<16, u64> for Sum {
+        fn step(&mut self, context: &mut PlanetContext<16, u64>, id: usize) -> Event {
+            Event::new(context.time, context.time, id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, u64>,
+            msg: Msg<u64>,
+            _id: usize,
+        ) {
+            self.total += msg.data;
+        }
+
+        fn snapshot(&self, _context: &PlanetContext<16, u64>, _id: usize) -> Vec<u8> {
+            self.total.to_le_bytes().to_vec()
+        }
+    }
+
+    fn audited_run(doubled: u64) -> Result<Fingerprint, AikaError> {
+        // a throttle horizon short of the lookahead keeps the runs free of rollbacks
+        let config = HybridConfig::new(2, 1 << 16)
+            .with_time_bounds(50.0, 1.0)
+            .with_optimistic_sync(4, 10)
+            .with_uniform_worlds(16, 1, 16)
+            .with_determinism(7)
+            .with_determinism_audit();
+        let mut engine = HybridEngine::<16, 16, 2, u64>::create(config)?;
+        engine.spawn_agent(0, Box::new(Ticker { doubled }))?;
+        engine.spawn_agent(1, Box::new(Sum::default()))?;
+        engine.schedule_all_agents(1)?;
+        let engine = engine.run()?;
+        engine
+            .fingerprint()
+            .cloned()
+            .ok_or(AikaError::ConfigError("no audit".to_string()))
+    }
+
+    #[test]
+    fn test_fingerprints_match_and_diverge() {
+        let first = audited_run(u64::MAX).unwrap();
+        let times = first
+            .checkpoints
+            .iter()
+            .map(|(checkpoint, _)| *checkpoint)
+            .collect::<Vec<_>>();
+        assert_eq!(times, [10, 20, 30, 40]);
+        let second = audited_run(u64::MAX).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first.head(), second.head());
+        assert_eq!(first.divergence(&second), None);
+
+        // the first doubled letter is sent at 25, and still unread on planet 1 at the checkpoint at 30
+        let doubled = audited_run(25).unwrap();
+        assert_ne!(first.head(), doubled.head());
+        assert_eq!(
+            first.divergence(&doubled),
+            Some(Divergence {
+                checkpoint: 30,
+                planet: Some(1),
+            })
+        );
+
+        let mut short = first.clone();
+        short.checkpoints.truncate(2);
+        assert_eq!(
+            first.divergence(&short),
+            Some(Divergence {
+                checkpoint: 30,
+                planet: None,
+            })
+        );
+    }
+}
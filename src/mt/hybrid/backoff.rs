@@ -0,0 +1,172 @@
+//! Adaptive idling for the `Galaxy` thread.
+//! While nothing changes between two rounds of mail delivery and GVT calculation, the `Galaxy` backs off: it spins
+//! for a few rounds, then yields its time slice, then parks. On a single core spinning only delays the `Planet`s, so
+//! it is skipped. Every `Planet` rings a shared `Wakeup` when it publishes a new local time or sends mail, which
+//! unparks the `Galaxy` at once. Parking also times out, so progress that rings no bell, like a migrated agent
+//! landing, is picked up within `PARK_TIMEOUT`. Any progress resets the backoff.
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread::Thread,
+    time::{Duration, Instant},
+};
+
+use crate::mailbox::MailboxStats;
+
+/// Idle rounds spent spinning before yielding, with more than one core.
+const SPIN_ROUNDS: u32 = 64;
+/// Idle rounds spent yielding before parking.
+const YIELD_ROUNDS: u32 = 256;
+/// Longest the `Galaxy` parks without being woken.
+pub const PARK_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// Doorbell the `Planet`s ring to wake a parked `Galaxy`.
+#[derive(Debug, Default)]
+pub(crate) struct Wakeup {
+    rung: AtomicBool,
+    thread: Mutex<Option<Thread>>,
+}
+
+impl Wakeup {
+    /// Make the calling thread the one woken by `notify()`, in place of the thread of an earlier round of the run.
+    pub(crate) fn register(&self) {
+        if let Ok(mut thread) = self.thread.lock() {
+            *thread = Some(std::thread::current());
+        }
+    }
+
+    pub(crate) fn notify(&self) {
+        if !self.rung.swap(true, Ordering::AcqRel) {
+            if let Some(thread) = self.thread.lock().ok().as_deref().and_then(Option::as_ref) {
+                thread.unpark();
+            }
+        }
+    }
+
+    /// Park until `notify()` or `timeout`, returning whether the bell was rung.
+    fn wait(&self, timeout: Duration) -> bool {
+        if self.rung.swap(false, Ordering::AcqRel) {
+            return true;
+        }
+        std::thread::park_timeout(timeout);
+        self.rung.swap(false, Ordering::AcqRel)
+    }
+}
+
+/// How the `Galaxy` thread spent its time, and what became of mail for full inboxes.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct GalaxyStats {
+    /// share of the run spent spinning, yielding or parked, in percent
+    pub idle_percent: f64,
+    pub parks: u64,
+    /// parks ended by a `Planet` rather than the timeout
+    pub wakeups: u64,
+    /// letters bounced or held back for full planet inboxes
+    pub mailbox: MailboxStats,
+}
+
+/// Escalating backoff of the `Galaxy` loop.
+#[derive(Debug, Default)]
+pub(crate) struct Backoff {
+    rounds: u32,
+    spin_rounds: u32,
+    started: Option<Instant>,
+    /// length of the run, once it is over
+    total: Option<Duration>,
+    idle: Duration,
+    parks: u64,
+    wakeups: u64,
+}
+
+impl Backoff {
+    pub(crate) fn start(&mut self) {
+        self.started.get_or_insert_with(Instant::now);
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        self.spin_rounds = if cores > 1 { SPIN_ROUNDS } else { 0 };
+    }
+
+    pub(crate) fn finish(&mut self) {
+        self.total = self.started.map(|started| started.elapsed());
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.rounds = 0;
+    }
+
+    /// Wait out one idle round, longer the longer nothing has changed.
+    pub(crate) fn idle(&mut self, wakeup: &Wakeup) {
+        let start = Instant::now();
+        if self.rounds < self.spin_rounds {
+            std::hint::spin_loop();
+        } else if self.rounds < self.spin_rounds + YIELD_ROUNDS {
+            std::thread::yield_now();
+        } else {
+            self.parks += 1;
+            if wakeup.wait(PARK_TIMEOUT) {
+                self.wakeups += 1;
+            }
+        }
+        self.rounds = self.rounds.saturating_add(1);
+        self.idle += start.elapsed();
+    }
+
+    pub(crate) fn stats(&self) -> GalaxyStats {
+        let total = self
+            .total
+            .or_else(|| self.started.map(|started| started.elapsed()))
+            .unwrap_or_default();
+        let idle_percent = if total.is_zero() {
+            0.0
+        } else {
+            100.0 * self.idle.as_secs_f64() / total.as_secs_f64()
+        };
+        GalaxyStats {
+            idle_percent: idle_percent.min(100.0),
+            parks: self.parks,
+            wakeups: self.wakeups,
+            ..GalaxyStats::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn test_backoff_parks_until_woken() {
+        let wakeup = Arc::new(Wakeup::default());
+        wakeup.register();
+        let mut backoff = Backoff::default();
+        backoff.start();
+        for _ in 0..backoff.spin_rounds + YIELD_ROUNDS {
+            backoff.idle(&wakeup);
+        }
+        assert_eq!(backoff.stats().parks, 0);
+
+        // a bell rung before parking isn't lost
+        wakeup.notify();
+        backoff.idle(&wakeup);
+        let bell = Arc::clone(&wakeup);
+        let ringer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_micros(200));
+            bell.notify();
+        });
+        backoff.idle(&wakeup);
+        ringer.join().unwrap();
+        // without a bell the park times out
+        backoff.idle(&wakeup);
+
+        let stats = backoff.stats();
+        assert_eq!(stats.parks, 3);
+        assert!(stats.wakeups >= 1 && stats.wakeups <= 2);
+        assert!(stats.idle_percent > 0.0 && stats.idle_percent <= 100.0);
+        backoff.reset();
+        backoff.idle(&wakeup);
+        assert_eq!(backoff.stats().parks, 3);
+    }
+}
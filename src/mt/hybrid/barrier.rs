@@ -0,0 +1,256 @@
+//! Global barriers requested by agents mid-run, such as an end-of-day settlement.
+//! An agent asks for a barrier at `T` with `PlanetContext::request_barrier()`. Like a reduction contribution, the
+//! request is handed over before the local time it was made at is published, and withdrawn again if its `Planet`
+//! rolls back past it. Every `Planet` reaching a requested `T` stalls there until GVT does too, at which point no
+//! request before `T` can still be rolled back and no mail is in transit. The `Galaxy` then folds the reductions made
+//! before `T`, runs the callbacks registered with `HybridEngine::on_barrier()` and releases the planets.
+//! A barrier has to be requested further ahead than the throttle horizon, so no `Planet` can have run past it yet.
+//! Should one have anyway, the run stops with `AikaError::BarrierUnreachable` instead of waiting forever.
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use crate::AikaError;
+
+/// A barrier at `time`, requested by `agent` on `world` at local time `requested`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BarrierRequest {
+    pub requested: u64,
+    pub time: u64,
+    pub world: usize,
+    pub agent: usize,
+}
+
+/// A released barrier, as handed to the barrier callbacks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BarrierRecord {
+    pub time: u64,
+    /// every request for the barrier, ordered by world, agent and request time
+    pub requests: Vec<BarrierRequest>,
+    /// the global aggregates over every contribution made before `time`
+    pub aggregates: BTreeMap<usize, f64>,
+}
+
+/// Called by the `Galaxy` at every barrier, while every `Planet` waits on it. An error stops the run.
+pub type BarrierCallback = Box<dyn FnMut(&BarrierRecord) -> Result<(), AikaError> + Send>;
+
+/// Barrier requests and callbacks shared by the `Galaxy` and every `Planet` of a run.
+pub(crate) struct Barriers {
+    /// a barrier must lie more than this many steps after its request
+    horizon: u64,
+    /// first step past the end of the run
+    terminal: u64,
+    pending: Mutex<Vec<BarrierRequest>>,
+    /// time of the earliest pending barrier, `u64::MAX` if there is none
+    earliest: AtomicU64,
+    callbacks: Mutex<Vec<BarrierCallback>>,
+    log: Mutex<Vec<BarrierRecord>>,
+}
+
+impl Barriers {
+    pub(crate) fn new(horizon: u64, terminal: u64) -> Self {
+        Self {
+            horizon,
+            terminal,
+            pending: Mutex::new(Vec::new()),
+            earliest: AtomicU64::new(u64::MAX),
+            callbacks: Mutex::new(Vec::new()),
+            log: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Check that a barrier at `time` requested at `now` can be reached by every `Planet` before the run ends.
+    pub(crate) fn check(&self, now: u64, time: u64) -> Result<(), AikaError> {
+        if time <= now.saturating_add(self.horizon) || time >= self.terminal {
+            return Err(AikaError::BarrierUnreachable(time));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn add_callback(&self, callback: BarrierCallback) -> Result<(), AikaError> {
+        self.callbacks
+            .lock()
+            .map_err(|_| AikaError::PoisonedLock)?
+            .push(callback);
+        Ok(())
+    }
+
+    /// Hand over the requests a `Planet` made since it last did.
+    pub(crate) fn request(&self, requests: Vec<BarrierRequest>) -> Result<(), AikaError> {
+        let mut pending = self.pending.lock().map_err(|_| AikaError::PoisonedLock)?;
+        pending.extend(requests);
+        self.update_earliest(&pending);
+        Ok(())
+    }
+
+    /// Withdraw the requests `world` made at or after `time`.
+    pub(crate) fn rollback(&self, world: usize, time: u64) -> Result<(), AikaError> {
+        let mut pending = self.pending.lock().map_err(|_| AikaError::PoisonedLock)?;
+        pending.retain(|request| request.world != world || request.requested < time);
+        self.update_earliest(&pending);
+        Ok(())
+    }
+
+    fn update_earliest(&self, pending: &[BarrierRequest]) {
+        let earliest = pending.iter().map(|request| request.time).min();
+        self.earliest
+            .store(earliest.unwrap_or(u64::MAX), Ordering::SeqCst);
+    }
+
+    /// Whether a `Planet` at `now` has to wait on a barrier.
+    pub(crate) fn holds(&self, now: u64) -> Result<bool, AikaError> {
+        if self.earliest.load(Ordering::SeqCst) > now {
+            return Ok(false);
+        }
+        let pending = self.pending.lock().map_err(|_| AikaError::PoisonedLock)?;
+        Ok(pending.iter().any(|request| request.time == now))
+    }
+
+    /// The earliest pending barrier, if any.
+    pub(crate) fn earliest(&self) -> Option<u64> {
+        let earliest = self.earliest.load(Ordering::SeqCst);
+        (earliest != u64::MAX).then_some(earliest)
+    }
+
+    /// Run the callbacks of the barrier at `time` with `aggregates` and release it.
+    pub(crate) fn release(
+        &self,
+        time: u64,
+        aggregates: BTreeMap<usize, f64>,
+    ) -> Result<(), AikaError> {
+        let mut requests = self
+            .pending
+            .lock()
+            .map_err(|_| AikaError::PoisonedLock)?
+            .iter()
+            .filter(|request| request.time == time)
+            .copied()
+            .collect::<Vec<_>>();
+        requests.sort_by_key(|request| (request.world, request.agent, request.requested));
+        let record = BarrierRecord {
+            time,
+            requests,
+            aggregates,
+        };
+        let mut callbacks = self.callbacks.lock().map_err(|_| AikaError::PoisonedLock)?;
+        for callback in callbacks.iter_mut() {
+            callback(&record)?;
+        }
+        self.log
+            .lock()
+            .map_err(|_| AikaError::PoisonedLock)?
+            .push(record);
+        // the planets waiting on the barrier only go on once the callbacks are done
+        let mut pending = self.pending.lock().map_err(|_| AikaError::PoisonedLock)?;
+        pending.retain(|request| request.time != time);
+        self.update_earliest(&pending);
+        Ok(())
+    }
+
+    /// Every barrier released so far, in time order.
+    pub(crate) fn log(&self) -> Vec<BarrierRecord> {
+        self.log.lock().map(|log| log.clone()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Trade {
+        volume: u64,
+    }
+
+    unsafe impl Pod for Trade {}
+    unsafe impl Zeroable for Trade {}
+
+    const VOLUME: usize = 0;
+
+    // Trades every step, asking for a settlement at 10 once
+    struct Trader {
+        volume: f64,
+        steps: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl ThreadedAgent<128, Trade> for Trader {
+        fn step(&mut self, context: &mut PlanetContext<128, Trade>, agent_id: usize) -> Event {
+            let time = context.time;
+            if time == 2 && context.world_id == 0 {
+                assert!(matches!(
+                    context.request_barrier(agent_id, 4),
+                    Err(AikaError::BarrierUnreachable(4))
+                ));
+                context.request_barrier(agent_id, 10).unwrap();
+            }
+            context.reduce(VOLUME, self.volume);
+            self.steps.lock().unwrap().push(time);
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Trade>,
+            _msg: Msg<Trade>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_barrier_holds_every_planet() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(2, 4)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<128, 128, 1, Trade>::create(config).unwrap();
+        let steps = Arc::new(Mutex::new(Vec::new()));
+        for (planet, volume) in [(0, 1.0), (1, 2.0)] {
+            let trader = Trader {
+                volume,
+                steps: steps.clone(),
+            };
+            engine.spawn_agent(planet, Box::new(trader)).unwrap();
+            engine.schedule(planet, 0, 1).unwrap();
+        }
+        let settled = Arc::new(Mutex::new(Vec::new()));
+        let (seen, log) = (steps.clone(), settled.clone());
+        engine
+            .on_barrier(Box::new(move |record| {
+                // no `Planet` stepped past the barrier while the callback runs
+                let latest = seen.lock().unwrap().iter().max().copied();
+                log.lock().unwrap().push((record.time, latest));
+                Ok(())
+            }))
+            .unwrap();
+        let engine = engine.run().unwrap();
+
+        assert_eq!(*settled.lock().unwrap(), vec![(10, Some(9))]);
+        let records = engine.barrier_log();
+        assert_eq!(records.len(), 1);
+        let request = BarrierRequest {
+            requested: 2,
+            time: 10,
+            world: 0,
+            agent: 0,
+        };
+        assert_eq!(records[0].requests, vec![request]);
+        // steps 1..=9 of both traders are settled
+        assert_eq!(records[0].aggregates[&VOLUME], 27.0);
+        assert!(steps.lock().unwrap().contains(&19));
+    }
+}
@@ -0,0 +1,253 @@
+//! Batching of outgoing interplanetary mail.
+//! Without batching every `send_mail()` is its own write into the `ThreadedMessenger`, so many small messages
+//! contend for the same ring buffers. With `MailBatching` a `Planet` collects its outgoing `Mail` per destination
+//! and hands each destination's letters over as one `MailBundle`, which the receiving `Planet` unpacks in order.
+//! A batch goes out once it holds `max_batch` letters or its oldest letter has waited `flush_interval` steps, and
+//! every batch goes out before the `Planet` stalls or rolls back. Buffered letters already count as in flight, so
+//! GVT can't pass them.
+use std::collections::BTreeMap;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::objects::{Mail, MailBundle};
+
+/// When a `Planet` sends its batched outgoing mail.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MailBatching {
+    /// letters a batch holds before it is sent at once
+    pub max_batch: usize,
+    /// steps a batch may wait for more letters; `0` sends every step's mail at the end of the step
+    pub flush_interval: u64,
+}
+
+impl MailBatching {
+    pub fn new(max_batch: usize, flush_interval: u64) -> Self {
+        Self {
+            max_batch,
+            flush_interval,
+        }
+    }
+}
+
+/// Outgoing mail of a `Planet`, batched per destination world.
+#[derive(Clone, Debug)]
+pub(crate) struct Outbox<T: Pod + Zeroable + Clone> {
+    batching: MailBatching,
+    /// `(local time of the oldest letter, letters)` per destination world
+    batches: BTreeMap<Option<usize>, (u64, Vec<Mail<T>>)>,
+}
+
+impl<T: Pod + Zeroable + Clone> Outbox<T> {
+    pub(crate) fn new(batching: MailBatching) -> Self {
+        Self {
+            batching,
+            batches: BTreeMap::new(),
+        }
+    }
+
+    /// Add a letter written at local time `now`, returning its destination's batch if that is now full.
+    pub(crate) fn push(&mut self, mail: Mail<T>, now: u64) -> Option<MailBundle<T>> {
+        let (_, letters) = self
+            .batches
+            .entry(mail.to_world)
+            .or_insert_with(|| (now, Vec::new()));
+        letters.push(mail);
+        if letters.len() < self.batching.max_batch.max(1) {
+            return None;
+        }
+        let to_world = mail.to_world;
+        let (_, letters) = self.batches.remove(&to_world)?;
+        Some(Self::bundle(letters, to_world))
+    }
+
+    /// Take every batch whose oldest letter has waited `flush_interval` steps by local time `now`.
+    pub(crate) fn due(&mut self, now: u64) -> Vec<MailBundle<T>> {
+        let interval = self.batching.flush_interval;
+        let due = self
+            .batches
+            .iter()
+            .filter(|(_, (since, _))| now.saturating_sub(*since) >= interval)
+            .map(|(to_world, _)| *to_world)
+            .collect::<Vec<_>>();
+        due.into_iter()
+            .filter_map(|to_world| {
+                let (_, letters) = self.batches.remove(&to_world)?;
+                Some(Self::bundle(letters, to_world))
+            })
+            .collect()
+    }
+
+    /// Take every batch.
+    pub(crate) fn drain(&mut self) -> Vec<MailBundle<T>> {
+        std::mem::take(&mut self.batches)
+            .into_iter()
+            .map(|(to_world, (_, letters))| Self::bundle(letters, to_world))
+            .collect()
+    }
+
+    pub(crate) fn batching(&self) -> MailBatching {
+        self.batching
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+
+    fn bundle(letters: Vec<Mail<T>>, to_world: Option<usize>) -> MailBundle<T> {
+        MailBundle {
+            from_world: letters[0].from_world,
+            letters,
+            scatter: None,
+            to_world,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, AntiMsg, Event, Msg, Transfer},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Reading {
+        value: u32,
+    }
+
+    unsafe impl Pod for Reading {}
+    unsafe impl Zeroable for Reading {}
+
+    fn letter(to_world: usize, sent: u64) -> Mail<Reading> {
+        let anti = AntiMsg::new(sent, sent + 1, 0, Some(0));
+        Mail::write_letter(Transfer::AntiMsg(anti), 0, Some(to_world))
+    }
+
+    #[test]
+    fn test_outbox_flushes_full_and_due_batches() {
+        let mut outbox = Outbox::new(MailBatching::new(3, 2));
+        assert!(outbox.push(letter(1, 4), 4).is_none());
+        assert!(outbox.push(letter(2, 4), 4).is_none());
+        assert!(outbox.push(letter(1, 5), 5).is_none());
+        // the third letter for world 1 fills its batch
+        let full = outbox.push(letter(1, 5), 5).unwrap();
+        assert_eq!((full.to_world, full.letters.len()), (Some(1), 3));
+        assert_eq!(full.commit_time(), 4);
+
+        // world 2's letter was written at 4, so it is due two steps later
+        assert!(outbox.due(5).is_empty());
+        let due = outbox.due(6);
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].to_world, Some(2));
+        assert!(outbox.is_empty());
+
+        outbox.push(letter(1, 7), 7);
+        outbox.push(letter(2, 7), 7);
+        assert_eq!(outbox.drain().len(), 2);
+        assert!(outbox.is_empty());
+    }
+
+    // Sends a reading to planet 1 from each of its agents every step
+    struct Sensor;
+
+    impl ThreadedAgent<128, Reading> for Sensor {
+        fn step(&mut self, context: &mut PlanetContext<128, Reading>, agent_id: usize) -> Event {
+            let time = context.time;
+            let reading = Reading {
+                value: time as u32 * 10 + agent_id as u32,
+            };
+            let msg = Msg::new(reading, time, time + 2, agent_id, Some(0));
+            context.send_mail(msg, 1).unwrap();
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Reading>,
+            _msg: Msg<Reading>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    struct Collector {
+        received: Arc<Mutex<Vec<(u32, u64)>>>,
+    }
+
+    impl ThreadedAgent<128, Reading> for Collector {
+        fn step(&mut self, context: &mut PlanetContext<128, Reading>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Reading>,
+            msg: Msg<Reading>,
+            _agent_id: usize,
+        ) {
+            self.received
+                .lock()
+                .unwrap()
+                .push((msg.data.value, msg.recv));
+        }
+    }
+
+    fn run_sensors(batching: Option<MailBatching>) -> (Vec<(u32, u64)>, u64) {
+        let mut config = HybridConfig::new(2, 512)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(1, 10)
+            .with_world(0, 16, vec![16; 4])
+            .unwrap()
+            .with_world(1, 16, vec![16])
+            .unwrap();
+        if let Some(batching) = batching {
+            config = config.with_mail_batching(batching.max_batch, batching.flush_interval);
+        }
+        let mut engine = HybridEngine::<128, 128, 1, Reading>::create(config).unwrap();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        for _ in 0..4 {
+            engine.spawn_agent(0, Box::new(Sensor)).unwrap();
+        }
+        let collector = Collector {
+            received: Arc::clone(&received),
+        };
+        engine.spawn_agent(1, Box::new(collector)).unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+
+        let mut received = received.lock().unwrap().clone();
+        received.sort();
+        received.dedup();
+        let stats = engine.stats();
+        assert!((0.0..=100.0).contains(&stats.galaxy.idle_percent));
+        (received, stats.planets[0].bundles_sent)
+    }
+
+    #[test]
+    fn test_batched_mail_matches_unbatched() {
+        let (unbatched, transfers) = run_sensors(None);
+        let (per_step, step_bundles) = run_sensors(Some(MailBatching::new(64, 0)));
+        let (windowed, window_bundles) = run_sensors(Some(MailBatching::new(64, 3)));
+
+        // four readings sent at each of 1..=17 are read two steps later, however the mail was carried
+        let expected = (1..=17u64)
+            .flat_map(|time| (0..4).map(move |agent| (time as u32 * 10 + agent, time + 2)))
+            .collect::<Vec<_>>();
+        let mut sorted = expected.clone();
+        sorted.sort();
+        assert_eq!(unbatched, sorted);
+        assert_eq!(per_step, sorted);
+        assert_eq!(windowed, sorted);
+        // planet 0 never rolls back, so it sends each of its 76 letters once
+        assert_eq!(transfers, 4 * 19);
+        assert!(step_bundles <= 19);
+        assert!(window_bundles <= 19);
+    }
+}
@@ -0,0 +1,231 @@
+//! Per-planet histories of the interplanetary mail, block by block.
+//! Planets run in blocks of `checkpoint_frequency` ticks, submitted to the `Galaxy` at checkpoints. The `Galaxy`
+//! records every letter it hands to a planet's inbox in a `BlockLedger`: a send for the planet it came from, in the
+//! block of its send time, and a receive for the planet it goes to, in the block of its receive time. A receive is
+//! a straggler if that planet had already run past its receive time, so that it has to roll back. Anti-messages
+//! count as cancellations of the sender. Every copy of a broadcast or `Scatter` counts, while mail for a bridged
+//! engine and mail bounced from a full inbox are never delivered here and don't.
+use std::collections::BTreeMap;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::objects::{MailBundle, Transfer};
+
+/// The mail of one planet in one block.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockStats {
+    /// `Msg`s sent
+    pub sends: u64,
+    /// `Msg`s received
+    pub recvs: u64,
+    /// received `Msg`s and anti-messages that arrived after their receive time
+    pub stragglers: u64,
+    /// anti-messages sent
+    pub cancelled: u64,
+}
+
+impl BlockStats {
+    fn add(&mut self, other: &BlockStats) {
+        self.sends += other.sends;
+        self.recvs += other.recvs;
+        self.stragglers += other.stragglers;
+        self.cancelled += other.cancelled;
+    }
+}
+
+/// Block histories of every planet of a run, and the `Msg`s between each pair of planets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockLedger {
+    block_len: u64,
+    /// stats by `(planet, block)`
+    blocks: BTreeMap<(usize, u64), BlockStats>,
+    /// `Msg`s by `(from, to)` planet
+    pairs: BTreeMap<(usize, usize), u64>,
+    /// `Msg`s by their receive time less their send time
+    lags: BTreeMap<u64, u64>,
+}
+
+impl BlockLedger {
+    pub(crate) fn new(block_len: u64) -> Self {
+        Self {
+            block_len: block_len.max(1),
+            blocks: BTreeMap::new(),
+            pairs: BTreeMap::new(),
+            lags: BTreeMap::new(),
+        }
+    }
+
+    /// Ticks in a block.
+    pub fn block_len(&self) -> u64 {
+        self.block_len
+    }
+
+    fn entry(&mut self, planet: usize, time: u64) -> &mut BlockStats {
+        self.blocks
+            .entry((planet, time / self.block_len))
+            .or_default()
+    }
+
+    /// Record `bundle`, delivered to planet `to` while it was at local time `lvt`.
+    pub(crate) fn record<T: Pod + Zeroable + Clone>(
+        &mut self,
+        bundle: &MailBundle<T>,
+        to: usize,
+        lvt: u64,
+    ) {
+        for mail in &bundle.letters {
+            match &mail.transfer {
+                Transfer::Msg(msg) => self.record_msg(mail.from_world, to, msg.sent, msg.recv, lvt),
+                Transfer::AntiMsg(anti) => {
+                    self.entry(mail.from_world, anti.sent).cancelled += 1;
+                    if anti.received < lvt {
+                        self.entry(to, anti.received).stragglers += 1;
+                    }
+                }
+            }
+        }
+        if let Some(scatter) = &bundle.scatter {
+            for _ in &scatter.to {
+                let msg = &scatter.msg;
+                self.record_msg(bundle.from_world, to, msg.sent, msg.recv, lvt);
+            }
+        }
+    }
+
+    fn record_msg(&mut self, from: usize, to: usize, sent: u64, recv: u64, lvt: u64) {
+        self.entry(from, sent).sends += 1;
+        let received = self.entry(to, recv);
+        received.recvs += 1;
+        if recv < lvt {
+            received.stragglers += 1;
+        }
+        *self.pairs.entry((from, to)).or_default() += 1;
+        *self.lags.entry(recv.saturating_sub(sent)).or_default() += 1;
+    }
+
+    /// Number of planets seen, one past the highest id.
+    pub fn planets(&self) -> usize {
+        let blocks = self.blocks.keys().map(|(planet, _)| planet + 1);
+        let pairs = self.pairs.keys().map(|(from, to)| from.max(to) + 1);
+        blocks.chain(pairs).max().unwrap_or(0)
+    }
+
+    /// The blocks of `planet` that saw any mail, as `(start time, stats)` in time order.
+    pub fn history(&self, planet: usize) -> Vec<(u64, BlockStats)> {
+        self.blocks
+            .range((planet, 0)..=(planet, u64::MAX))
+            .map(|((_, block), stats)| (block * self.block_len, *stats))
+            .collect()
+    }
+
+    /// Every `(planet, start time, stats)`, by planet and then time.
+    pub fn blocks(&self) -> impl Iterator<Item = (usize, u64, BlockStats)> + '_ {
+        self.blocks
+            .iter()
+            .map(|((planet, block), stats)| (*planet, block * self.block_len, *stats))
+    }
+
+    /// Number of `Msg`s by how many ticks after they were sent they are received, the lookahead the planets had.
+    pub fn lags(&self) -> &BTreeMap<u64, u64> {
+        &self.lags
+    }
+
+    /// The stats of `planet` over the whole run.
+    pub fn totals(&self, planet: usize) -> BlockStats {
+        let mut totals = BlockStats::default();
+        for (_, stats) in self.history(planet) {
+            totals.add(&stats);
+        }
+        totals
+    }
+
+    /// `Msg`s sent from planet `from` to planet `to`, in `[from][to]`.
+    pub fn traffic(&self) -> Vec<Vec<u64>> {
+        let planets = self.planets();
+        let mut traffic = vec![vec![0; planets]; planets];
+        for ((from, to), msgs) in &self.pairs {
+            traffic[*from][*to] = *msgs;
+        }
+        traffic
+    }
+
+    /// How many more `Msg`s planet `a` sent to planet `b` than it got back, in `[a][b]`: a heatmap of the imbalance
+    /// between every pair of planets, antisymmetric and zero on the diagonal.
+    pub fn imbalance(&self) -> Vec<Vec<i64>> {
+        let traffic = self.traffic();
+        (0..traffic.len())
+            .map(|a| {
+                (0..traffic.len())
+                    .map(|b| traffic[a][b] as i64 - traffic[b][a] as i64)
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The pair of different planets with the largest imbalance, as `(sender, receiver, excess)`, if any differ.
+    pub fn most_imbalanced(&self) -> Option<(usize, usize, i64)> {
+        self.imbalance()
+            .into_iter()
+            .enumerate()
+            .flat_map(|(a, row)| {
+                row.into_iter()
+                    .enumerate()
+                    .map(move |(b, excess)| (a, b, excess))
+            })
+            .filter(|(_, _, excess)| *excess > 0)
+            .max_by_key(|(a, b, excess)| (*excess, std::cmp::Reverse((*a, *b))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{AntiMsg, Mail, Msg, Scatter};
+
+    fn letter(from_world: usize, to_world: usize, sent: u64, recv: u64) -> Mail<u8> {
+        let msg = Msg::new(0u8, sent, recv, 0, Some(0));
+        Mail::write_letter(Transfer::Msg(msg), from_world, Some(to_world))
+    }
+
+    #[test]
+    fn test_blocks_and_imbalance() {
+        let mut ledger = BlockLedger::new(10);
+        ledger.record(&MailBundle::single(letter(0, 1, 3, 12)), 1, 5);
+        ledger.record(&MailBundle::single(letter(0, 1, 14, 15)), 1, 18);
+        ledger.record(&MailBundle::single(letter(1, 0, 16, 17)), 0, 2);
+        let anti = AntiMsg::new(14, 15, 0, Some(0));
+        let cancel = Mail::<u8>::write_letter(Transfer::AntiMsg(anti), 0, Some(1));
+        ledger.record(&MailBundle::single(cancel), 1, 19);
+        let scatter = Scatter {
+            msg: Msg::new(0u8, 21, 22, 0, None),
+            to: vec![0, 1, 2],
+            #[cfg(feature = "validate-causality")]
+            clock: Default::default(),
+        };
+        ledger.record(&MailBundle::scatter(scatter, 0, 2), 2, 0);
+
+        let block = |sends, recvs, stragglers, cancelled| BlockStats {
+            sends,
+            recvs,
+            stragglers,
+            cancelled,
+        };
+        assert_eq!(
+            ledger.history(0),
+            [
+                (0, block(1, 0, 0, 0)),
+                (10, block(1, 1, 0, 1)),
+                (20, block(3, 0, 0, 0))
+            ]
+        );
+        // the letter received at 15 and its anti-message both arrived after planet 1 passed 15
+        assert_eq!(ledger.history(1), [(10, block(1, 2, 2, 0))]);
+        assert_eq!(ledger.totals(2), block(0, 3, 0, 0));
+        assert_eq!(ledger.planets(), 3);
+        assert_eq!(ledger.traffic(), [[0, 2, 3], [1, 0, 0], [0, 0, 0]]);
+        assert_eq!(ledger.imbalance()[0], [0, 1, 3]);
+        assert_eq!(ledger.imbalance()[2][0], -3);
+        assert_eq!(ledger.most_imbalanced(), Some((0, 2, 3)));
+        assert_eq!(ledger.lags(), &BTreeMap::from([(1, 5), (9, 1)]));
+    }
+}
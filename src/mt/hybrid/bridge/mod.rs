@@ -0,0 +1,573 @@
+//! Bridging two `HybridEngine`s across a process boundary.
+//! A `RemotePlanet`, attached with `HybridEngine::bridge()`, stands in for the world slots reserved for the peer's
+//! planets, relaying `Mail` and GVT floors over a `Transport` so the two Galaxies run as one simulation.
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::sleep,
+    time::Duration,
+};
+
+use bytemuck::{Pod, Zeroable};
+use mesocarp::scheduling::Scheduleable;
+
+use crate::{
+    mt::hybrid::{
+        backoff::Wakeup,
+        bridge::{transport::Transport, wire::Frame},
+        planet::RegistryOutput,
+        schema::{MigrationPath, SchemaRegistry},
+    },
+    objects::{DeliveryFailure, Mail, MailBundle, Transfer},
+    AikaError,
+};
+
+pub mod transport;
+pub(crate) mod wire;
+
+/// Traffic over a bridge, counted by one end.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BridgeStats {
+    pub mail_sent: u64,
+    pub mail_received: u64,
+    /// `Msg`s handed back to the peer because a local inbox was full
+    pub bounced: u64,
+    pub floors_sent: u64,
+}
+
+/// `Mail` passed between the `Galaxy` and a `RemotePlanet`, by world id. It stands in for the messenger so the
+/// relay's busy loop never contends with the `Galaxy` over the messenger's buffers.
+pub(crate) struct Exchange<MessageType: Pod + Zeroable + Clone> {
+    /// world id of the first remote slot
+    first: usize,
+    /// bundles the `Galaxy` routed to a remote slot
+    outgoing: Mutex<Vec<(usize, MailBundle<MessageType>)>>,
+    /// bundles from the peer, for the `Galaxy` to deliver to its planets
+    incoming: Mutex<Vec<(usize, MailBundle<MessageType>)>>,
+}
+
+impl<MessageType: Pod + Zeroable + Clone> Exchange<MessageType> {
+    pub(crate) fn new(first: usize) -> Self {
+        Self {
+            first,
+            outgoing: Mutex::new(Vec::new()),
+            incoming: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn first(&self) -> usize {
+        self.first
+    }
+
+    pub(crate) fn is_remote(&self, world: usize) -> bool {
+        world >= self.first
+    }
+
+    /// Hand a bundle for remote slot `world` to the `RemotePlanet`.
+    pub(crate) fn route(
+        &self,
+        world: usize,
+        bundle: MailBundle<MessageType>,
+    ) -> Result<(), AikaError> {
+        self.outgoing
+            .lock()
+            .map_err(|_| AikaError::PoisonedLock)?
+            .push((world, bundle));
+        Ok(())
+    }
+
+    /// Take the bundles from the peer, for the `Galaxy` to deliver.
+    pub(crate) fn take_incoming(&self) -> Result<Vec<(usize, MailBundle<MessageType>)>, AikaError> {
+        let mut incoming = self.incoming.lock().map_err(|_| AikaError::PoisonedLock)?;
+        Ok(std::mem::take(&mut *incoming))
+    }
+}
+
+/// Stands in for every planet of a peer engine, relaying `Mail` and GVT floors over a `Transport`.
+///
+/// Each engine reserves a world slot for every planet of its peer with `HybridConfig::with_remote_worlds()`, after
+/// its own planets, so an agent reaches a remote planet by sending to its slot. The `RemotePlanet` forwards the
+/// `Mail` the `Galaxy` routes to those slots, and hands the `Mail` it receives to the `Galaxy` for delivery. On the
+/// wire, world ids are those of the sending engine, and the receiving end shifts the peer's ids past its own planets.
+///
+/// GVT is computed in two tiers. Each end reports a floor to the other, the lowest local time of its own planets
+/// taken while nothing is in flight locally, leaving out the `RemotePlanet` so the two Galaxies don't wait on each
+/// other. A floor also acknowledges the `Mail` taken in so far, and travels behind every frame sent before it, so
+/// each end publishes as the local time of its remote slots the peer's floor, lowered to the earliest `Mail` the peer
+/// hasn't acknowledged and to the earliest `Mail` from the peer that hasn't reached its planet yet. Each `Galaxy` then
+/// folds the remote slots into its GVT like any other planet.
+///
+/// A bridged engine needs a fixed set of planets, so auto-scaling and migration are refused. Cuts, barriers and
+/// reductions stay local to each engine.
+pub struct RemotePlanet<const INTER_SLOTS: usize, MessageType: Pod + Zeroable + Clone> {
+    transport: Box<dyn Transport>,
+    exchange: Arc<Exchange<MessageType>>,
+    /// local times published for the remote slots, in world order
+    lvts: Vec<Arc<AtomicU64>>,
+    /// local times of the engine's own planets
+    clocks: Vec<Arc<AtomicU64>>,
+    /// delivery failures of the engine's own planets, then of the remote slots
+    failures: Vec<Arc<Mutex<Vec<DeliveryFailure<MessageType>>>>>,
+    gvt: Arc<AtomicU64>,
+    counter: Arc<AtomicUsize>,
+    halt: Arc<AtomicBool>,
+    wakeup: Arc<Wakeup>,
+    /// number of the engine's own planets, the world id of the first remote slot
+    local: usize,
+    terminal: u64,
+    /// `Mail` frames sent, and `(frame, recv time)` of those the peer hasn't acknowledged
+    sent: u64,
+    unacked: VecDeque<(u64, u64)>,
+    /// `Mail` frames handed to the `Galaxy`
+    received: u64,
+    /// earliest `Mail` handed to the `Galaxy` since nothing was last in flight
+    injected: Option<u64>,
+    peer_floor: u64,
+    /// floor and acknowledgement last reported
+    reported: Option<(u64, u64)>,
+    /// payload schema of this engine, and the migrations from the peer's if it sends an older one
+    schemas: SchemaRegistry,
+    migration: Option<MigrationPath>,
+    /// whether the peer's `Hello` has arrived
+    greeted: bool,
+    /// whether the peer is done and gone
+    closed: bool,
+    stats: BridgeStats,
+}
+
+unsafe impl<const INTER_SLOTS: usize, MessageType: Pod + Zeroable + Clone> Send
+    for RemotePlanet<INTER_SLOTS, MessageType>
+{
+}
+
+impl<const INTER_SLOTS: usize, MessageType: Pod + Zeroable + Clone>
+    RemotePlanet<INTER_SLOTS, MessageType>
+{
+    pub(crate) fn new(
+        transport: Box<dyn Transport>,
+        exchange: Arc<Exchange<MessageType>>,
+        slots: Vec<RegistryOutput<INTER_SLOTS, MessageType>>,
+        clocks: Vec<Arc<AtomicU64>>,
+        failures: Vec<Arc<Mutex<Vec<DeliveryFailure<MessageType>>>>>,
+        schemas: SchemaRegistry,
+        terminal: u64,
+    ) -> Result<Self, AikaError> {
+        let first = slots.first().ok_or_else(|| {
+            AikaError::ConfigError("no remote worlds are reserved to bridge".to_string())
+        })?;
+        let (gvt, counter) = (first.gvt_handle(), first.counter_handle());
+        let (halt, wakeup) = (first.halt_handle(), first.wakeup_handle());
+        let local = exchange.first();
+        let mut failures = failures;
+        failures.extend(slots.iter().map(|slot| slot.failures_handle()));
+        let lvts = slots.iter().map(|slot| slot.lvt_handle()).collect();
+        Ok(Self {
+            transport,
+            exchange,
+            lvts,
+            clocks,
+            failures,
+            gvt,
+            counter,
+            halt,
+            wakeup,
+            local,
+            terminal,
+            sent: 0,
+            unacked: VecDeque::new(),
+            received: 0,
+            injected: None,
+            peer_floor: 0,
+            reported: None,
+            schemas,
+            migration: None,
+            greeted: false,
+            closed: false,
+            stats: BridgeStats::default(),
+        })
+    }
+
+    pub fn stats(&self) -> &BridgeStats {
+        &self.stats
+    }
+
+    /// Relay until the run is over. An error stops every other thread of the run.
+    pub fn run(&mut self) -> Result<(), AikaError> {
+        let result = self.relay();
+        if result.is_err() {
+            self.halt.store(true, Ordering::Release);
+        }
+        result
+    }
+
+    fn relay(&mut self) -> Result<(), AikaError> {
+        let hello = Frame::<MessageType>::Hello {
+            schema: self.schemas.expected().hash(),
+            local: self.local as u64,
+            remote: self.lvts.len() as u64,
+            terminal: self.terminal,
+        };
+        self.transport.send(&hello.encode())?;
+        loop {
+            if self.halt.load(Ordering::Acquire) {
+                return Ok(());
+            }
+            // whatever was handed to the `Galaxy` before has reached its planet
+            if self.counter.load(Ordering::SeqCst) == 0 {
+                self.injected = None;
+            }
+            let mut busy = self.receive()?;
+            if self.greeted {
+                busy |= self.forward_mail()?;
+                busy |= self.forward_failures()?;
+                self.publish();
+                busy |= self.report()?;
+            }
+            let queued = self.flush()?;
+            let gvt = self.gvt.load(Ordering::Acquire);
+            // the last floor acknowledges everything the peer sent
+            let finished = self.reported.is_some_and(|(floor, received)| {
+                floor >= self.terminal && received == self.received
+            });
+            if gvt >= self.terminal && finished && !queued {
+                return Ok(());
+            }
+            if !busy {
+                sleep(Duration::from_micros(10));
+            }
+        }
+    }
+
+    /// Take in every frame that has arrived, in order, returning whether there were any.
+    fn receive(&mut self) -> Result<bool, AikaError> {
+        let mut any = false;
+        while !self.closed {
+            let received = self.transport.try_recv();
+            let Some(bytes) = self.hang_up(received)? else {
+                break;
+            };
+            any = true;
+            match Frame::decode(&bytes, self.migration.as_ref())? {
+                Frame::Hello {
+                    schema,
+                    local,
+                    remote,
+                    terminal,
+                } => self.greet(schema, local, remote, terminal)?,
+                _ if !self.greeted => {
+                    return Err(AikaError::Bridge("the peer skipped its hello".to_string()));
+                }
+                Frame::Mail(mail) => {
+                    // mail at or past the terminal time is never read
+                    if mail.transfer.time() < self.terminal {
+                        self.inject(mail)?;
+                    }
+                    self.received += 1;
+                    self.stats.mail_received += 1;
+                }
+                Frame::Bounced { to_world, msg } => {
+                    let failures = self
+                        .failures
+                        .get(msg.from_world)
+                        .filter(|_| msg.from_world < self.local)
+                        .ok_or(AikaError::InvalidWorldId(msg.from_world))?;
+                    let to_world = self.local + to_world as usize;
+                    self.counter.fetch_add(1, Ordering::SeqCst);
+                    failures
+                        .lock()
+                        .map_err(|_| AikaError::PoisonedLock)?
+                        .push(DeliveryFailure { to_world, msg });
+                }
+                Frame::Floor { time, received } => {
+                    while self
+                        .unacked
+                        .front()
+                        .is_some_and(|(frame, _)| *frame < received)
+                    {
+                        self.unacked.pop_front();
+                    }
+                    self.peer_floor = time;
+                }
+            }
+        }
+        if any {
+            self.wakeup.notify();
+        }
+        Ok(any)
+    }
+
+    /// Check the peer's `Hello` against this engine, taking up the migrations from an older schema of the peer.
+    fn greet(
+        &mut self,
+        schema: u64,
+        local: u64,
+        remote: u64,
+        terminal: u64,
+    ) -> Result<(), AikaError> {
+        let expected = self.schemas.expected();
+        if schema != expected.hash() {
+            let migration = self.schemas.migration_from(schema).ok_or_else(|| {
+                AikaError::SchemaMismatch(format!(
+                    "the peer's payload doesn't match {expected}, with no migration to it"
+                ))
+            })?;
+            self.migration = Some(migration);
+        }
+        if local != self.lvts.len() as u64 || remote != self.local as u64 {
+            return Err(AikaError::Bridge(format!(
+                "the peer runs {local} planets and reserved {remote} remote worlds, expected {} and {}",
+                self.lvts.len(),
+                self.local
+            )));
+        }
+        if terminal != self.terminal {
+            return Err(AikaError::Bridge(format!(
+                "the peer ends at {terminal}, this engine at {}",
+                self.terminal
+            )));
+        }
+        self.greeted = true;
+        Ok(())
+    }
+
+    /// Send the peer the `Mail` the `Galaxy` routed to the remote slots, returning whether there was any.
+    fn forward_mail(&mut self) -> Result<bool, AikaError> {
+        let outgoing = std::mem::take(
+            &mut *self
+                .exchange
+                .outgoing
+                .lock()
+                .map_err(|_| AikaError::PoisonedLock)?,
+        );
+        if outgoing.is_empty() {
+            return Ok(false);
+        }
+        let in_flight = outgoing
+            .iter()
+            .map(|(_, bundle)| bundle.in_flight())
+            .sum::<usize>();
+        for (world, bundle) in outgoing {
+            for mut mail in bundle.into_letters() {
+                mail.to_world = Some(world - self.local);
+                let time = mail.transfer.time();
+                self.write(Frame::Mail(mail))?;
+                self.unacked.push_back((self.sent, time));
+                self.sent += 1;
+                self.stats.mail_sent += 1;
+            }
+        }
+        // the slots only stop counting the mail once their local times hold GVT back in its place
+        self.publish();
+        self.counter.fetch_sub(in_flight, Ordering::SeqCst);
+        Ok(true)
+    }
+
+    /// Hand the peer's `Msg`s that found a local inbox full back to it, returning whether there were any.
+    fn forward_failures(&mut self) -> Result<bool, AikaError> {
+        let mut any = false;
+        for slot in self.local..self.failures.len() {
+            let failures = std::mem::take(
+                &mut *self.failures[slot]
+                    .lock()
+                    .map_err(|_| AikaError::PoisonedLock)?,
+            );
+            for failure in &failures {
+                let mut msg = failure.msg;
+                msg.from_world -= self.local;
+                let to_world = failure.to_world as u64;
+                self.write(Frame::Bounced { to_world, msg })?;
+                self.stats.bounced += 1;
+            }
+            self.counter.fetch_sub(failures.len(), Ordering::SeqCst);
+            any |= !failures.is_empty();
+        }
+        Ok(any)
+    }
+
+    /// Hand `mail` from the peer to the `Galaxy`, to deliver from the slot it came from.
+    fn inject(&mut self, mut mail: Mail<MessageType>) -> Result<(), AikaError> {
+        let to_world =
+            mail.to_world
+                .filter(|to| *to < self.local)
+                .ok_or(AikaError::InvalidWorldId(
+                    mail.to_world.unwrap_or(usize::MAX),
+                ))?;
+        if mail.from_world >= self.lvts.len() {
+            return Err(AikaError::InvalidWorldId(mail.from_world));
+        }
+        mail.from_world += self.local;
+        if let Transfer::Msg(msg) = &mut mail.transfer {
+            msg.from_world += self.local;
+        }
+        // like a planet sending it, the slots' local times bound the mail before it counts as in flight, so a
+        // `Galaxy` that just found nothing in flight can't take GVT past it
+        let time = mail.transfer.time();
+        self.injected = Some(self.injected.map_or(time, |injected| injected.min(time)));
+        for lvt in &self.lvts {
+            lvt.fetch_min(time, Ordering::SeqCst);
+        }
+        self.counter.fetch_add(1, Ordering::SeqCst);
+        self.exchange
+            .incoming
+            .lock()
+            .map_err(|_| AikaError::PoisonedLock)?
+            .push((to_world, MailBundle::single(mail)));
+        Ok(())
+    }
+
+    /// Publish the peer's floor, lowered to the earliest `Mail` still on its way either way, as the local time of
+    /// every remote slot.
+    fn publish(&mut self) {
+        let pending = self
+            .unacked
+            .iter()
+            .map(|(_, time)| *time)
+            .chain(self.injected)
+            .min();
+        let floor = pending.map_or(self.peer_floor, |time| time.min(self.peer_floor));
+        for lvt in &self.lvts {
+            lvt.store(floor, Ordering::Release);
+        }
+    }
+
+    /// Report the floor of the engine's own planets if nothing is in flight locally and it changed, returning whether
+    /// it did. Every `Mail` polled from the remote slots is already written, so the floor travels behind it.
+    fn report(&mut self) -> Result<bool, AikaError> {
+        let received = self.received;
+        if self.counter.load(Ordering::SeqCst) != 0 {
+            return Ok(false);
+        }
+        let floor = self
+            .clocks
+            .iter()
+            .map(|clock| clock.load(Ordering::SeqCst))
+            .min()
+            .unwrap_or(self.terminal);
+        if self.counter.load(Ordering::SeqCst) != 0 || self.reported == Some((floor, received)) {
+            return Ok(false);
+        }
+        self.write(Frame::Floor {
+            time: floor,
+            received,
+        })?;
+        self.reported = Some((floor, received));
+        self.stats.floors_sent += 1;
+        Ok(true)
+    }
+
+    fn write(&mut self, frame: Frame<MessageType>) -> Result<(), AikaError> {
+        if self.closed {
+            return Ok(());
+        }
+        let sent = self.transport.send(&frame.encode());
+        self.hang_up(sent)
+    }
+
+    /// Push out the frames queued on the transport, returning whether any are still queued.
+    fn flush(&mut self) -> Result<bool, AikaError> {
+        if self.closed {
+            return Ok(false);
+        }
+        let queued = self.transport.flush();
+        self.hang_up(queued)
+    }
+
+    /// Stop using the transport once it fails, if the peer is done and may have hung up first.
+    fn hang_up<T: Default>(&mut self, result: Result<T, AikaError>) -> Result<T, AikaError> {
+        match result {
+            Err(_) if self.peer_floor >= self.terminal => {
+                self.closed = true;
+                Ok(T::default())
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{collections::BTreeSet, os::unix::net::UnixStream, thread};
+
+    use super::{transport::StreamTransport, *};
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Ping {
+        sent: u64,
+    }
+
+    unsafe impl Pod for Ping {}
+    unsafe impl Zeroable for Ping {}
+
+    // Pings the agent on the peer's planet every step, and notes the pings it hears
+    struct Pinger {
+        heard: Arc<Mutex<BTreeSet<u64>>>,
+    }
+
+    impl ThreadedAgent<128, Ping> for Pinger {
+        fn step(&mut self, context: &mut PlanetContext<128, Ping>, agent_id: usize) -> Event {
+            let time = context.time;
+            let msg = Msg::new(Ping { sent: time }, time, time + 3, agent_id, Some(0));
+            // world 1 is the slot of the peer's only planet
+            context.send_mail(msg, 1).unwrap();
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Ping>,
+            msg: Msg<Ping>,
+            _agent_id: usize,
+        ) {
+            self.heard.lock().unwrap().insert(msg.data.sent);
+        }
+    }
+
+    fn run_end(stream: UnixStream) -> (BTreeSet<u64>, BridgeStats) {
+        let config = HybridConfig::new(1, 64)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(2, 5)
+            .with_uniform_worlds(16, 1, 16)
+            .with_remote_worlds(1);
+        let mut engine = HybridEngine::<128, 128, 1, Ping>::create(config).unwrap();
+        let heard = Arc::new(Mutex::new(BTreeSet::new()));
+        let pinger = Pinger {
+            heard: heard.clone(),
+        };
+        engine.spawn_agent(0, Box::new(pinger)).unwrap();
+        engine.schedule(0, 0, 1).unwrap();
+        let transport = StreamTransport::unix(stream).unwrap();
+        engine.bridge(Box::new(transport)).unwrap();
+        let engine = engine.run().unwrap();
+        let stats = engine.remote().unwrap().stats().clone();
+        let heard = heard.lock().unwrap().clone();
+        (heard, stats)
+    }
+
+    #[test]
+    fn test_bridged_engines_exchange_mail() {
+        let (left, right) = UnixStream::pair().unwrap();
+        let left = thread::spawn(move || run_end(left));
+        let right = thread::spawn(move || run_end(right));
+        let (left, right) = (left.join().unwrap(), right.join().unwrap());
+
+        // every ping received before the end of the run is heard on both ends, unless an inbox handed it back
+        let expected = (1..=16).collect::<BTreeSet<u64>>();
+        for (heard, stats) in [&left, &right] {
+            assert!(heard.is_subset(&expected));
+            assert!(heard.len() as u64 + stats.bounced >= 16);
+        }
+        assert_eq!(left.1.mail_sent, right.1.mail_received);
+        assert_eq!(right.1.mail_sent, left.1.mail_received);
+        assert!(left.1.mail_sent >= 19);
+        assert!(left.1.floors_sent > 0 && right.1.floors_sent > 0);
+    }
+}
@@ -0,0 +1,125 @@
+//! Pluggable transports carrying bridge frames between two processes.
+//! A `Transport` moves whole frames in order and never blocks the `RemotePlanet` polling it. `StreamTransport`
+//! length-prefixes frames over any non-blocking byte stream, and is built over TCP with `StreamTransport::tcp()` or,
+//! on Unix, over a domain socket with `StreamTransport::unix()`.
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::TcpStream,
+};
+
+use crate::AikaError;
+
+/// An ordered, reliable channel of frames to the other end of a bridge.
+pub trait Transport: Send {
+    /// Queue `frame` for sending, after every frame sent before it.
+    fn send(&mut self, frame: &[u8]) -> Result<(), AikaError>;
+    /// The next frame received in full, if there is one, without blocking.
+    fn try_recv(&mut self) -> Result<Option<Vec<u8>>, AikaError>;
+    /// Push out queued frames without blocking, returning whether any are still queued.
+    fn flush(&mut self) -> Result<bool, AikaError>;
+}
+
+/// Frames prefixed with their length as a little-endian `u32`, over a non-blocking byte stream.
+pub struct StreamTransport<S: Read + Write + Send> {
+    stream: S,
+    /// bytes written by `send()` the stream didn't take yet
+    outgoing: Vec<u8>,
+    /// bytes read that don't make up a whole frame yet
+    incoming: Vec<u8>,
+    /// whether the other end closed the stream, after the frames already read
+    hung_up: bool,
+}
+
+impl StreamTransport<TcpStream> {
+    /// Carry frames over a connected TCP stream, switching it to non-blocking mode.
+    pub fn tcp(stream: TcpStream) -> Result<Self, AikaError> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self::new(stream))
+    }
+}
+
+#[cfg(unix)]
+impl StreamTransport<std::os::unix::net::UnixStream> {
+    /// Carry frames over a connected Unix domain socket, switching it to non-blocking mode.
+    pub fn unix(stream: std::os::unix::net::UnixStream) -> Result<Self, AikaError> {
+        stream.set_nonblocking(true)?;
+        Ok(Self::new(stream))
+    }
+}
+
+impl<S: Read + Write + Send> StreamTransport<S> {
+    /// Carry frames over `stream`, which must already be non-blocking.
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            outgoing: Vec::new(),
+            incoming: Vec::new(),
+            hung_up: false,
+        }
+    }
+}
+
+impl<S: Read + Write + Send> Transport for StreamTransport<S> {
+    fn send(&mut self, frame: &[u8]) -> Result<(), AikaError> {
+        let len = u32::try_from(frame.len())
+            .map_err(|_| AikaError::Bridge(format!("frame of {} bytes", frame.len())))?;
+        self.outgoing.extend_from_slice(&len.to_le_bytes());
+        self.outgoing.extend_from_slice(frame);
+        self.flush()?;
+        Ok(())
+    }
+
+    fn try_recv(&mut self) -> Result<Option<Vec<u8>>, AikaError> {
+        if !self.hung_up && !has_frame(&self.incoming) {
+            let mut buffer = [0u8; 4096];
+            loop {
+                match self.stream.read(&mut buffer) {
+                    Ok(0) => {
+                        self.hung_up = true;
+                        break;
+                    }
+                    Ok(read) => self.incoming.extend_from_slice(&buffer[..read]),
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                    Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                    Err(err) => return Err(err.into()),
+                }
+            }
+        }
+        if !has_frame(&self.incoming) {
+            if self.hung_up {
+                return Err(AikaError::Bridge("the other end hung up".to_string()));
+            }
+            return Ok(None);
+        }
+        let len = frame_len(&self.incoming);
+        let frame = self.incoming[4..4 + len].to_vec();
+        self.incoming.drain(..4 + len);
+        Ok(Some(frame))
+    }
+
+    fn flush(&mut self) -> Result<bool, AikaError> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(AikaError::Bridge("the other end hung up".to_string())),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(!self.outgoing.is_empty())
+    }
+}
+
+/// Whether `bytes` starts with a whole frame.
+fn has_frame(bytes: &[u8]) -> bool {
+    bytes.len() >= 4 && bytes.len() >= 4 + frame_len(bytes)
+}
+
+/// Length of the frame at the head of `bytes`, which holds at least its prefix.
+fn frame_len(bytes: &[u8]) -> usize {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+}
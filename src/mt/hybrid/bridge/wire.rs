@@ -0,0 +1,279 @@
+//! Frames exchanged between the two ends of a bridge.
+//! Every field is written as a little-endian word, so both ends agree on the layout whatever their platform or
+//! build. Payloads travel as their raw `Pod` bytes, checked against the `PayloadSchema` hash of the `Hello`, and a
+//! peer with an older schema has its payloads carried through a `MigrationPath` as they are read.
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    agents::rpc::{RequestId, Rpc},
+    mt::hybrid::schema::MigrationPath,
+    objects::{AntiMsg, CausalId, GroupId, Mail, Msg, Transfer},
+    AikaError,
+};
+
+/// A frame on the wire. World ids are those of the sending engine. The vector clock `validate-causality` adds to
+/// `Mail` isn't written, so mail crossing a bridge isn't validated.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "validate-causality", allow(clippy::large_enum_variant))]
+pub(crate) enum Frame<T: Pod + Zeroable + Clone> {
+    /// First frame on both ends, checked against the receiving engine before anything else.
+    Hello {
+        schema: u64,
+        /// planets of the sender
+        local: u64,
+        /// world slots the sender reserved for the receiver's planets
+        remote: u64,
+        terminal: u64,
+    },
+    /// `Mail` for the receiver's world `to_world`.
+    Mail(Mail<T>),
+    /// A `Msg` the receiver sent to world `to_world` that found its inbox full.
+    Bounced { to_world: u64, msg: Msg<T> },
+    /// No planet of the sender can roll back before `time`, and it has taken in `received` frames of `Mail`.
+    Floor { time: u64, received: u64 },
+}
+
+const HELLO: u8 = 0;
+const MAIL: u8 = 1;
+const BOUNCED: u8 = 2;
+const FLOOR: u8 = 3;
+
+const MSG: u8 = 0;
+const ANTI_MSG: u8 = 1;
+
+const NO_RPC: u8 = 0;
+const REQUEST: u8 = 1;
+const REPLY: u8 = 2;
+const TIMEOUT: u8 = 3;
+const DISCOVERY: u8 = 4;
+
+impl<T: Pod + Zeroable + Clone> Frame<T> {
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer(Vec::new());
+        match self {
+            Frame::Hello {
+                schema,
+                local,
+                remote,
+                terminal,
+            } => {
+                writer.byte(HELLO);
+                writer.word(*schema);
+                writer.word(*local);
+                writer.word(*remote);
+                writer.word(*terminal);
+            }
+            Frame::Mail(mail) => {
+                writer.byte(MAIL);
+                writer.option(mail.to_world.map(|to| to as u64));
+                writer.word(mail.from_world as u64);
+                match &mail.transfer {
+                    Transfer::Msg(msg) => {
+                        writer.byte(MSG);
+                        writer.msg(msg);
+                    }
+                    Transfer::AntiMsg(anti_msg) => {
+                        writer.byte(ANTI_MSG);
+                        writer.word(anti_msg.sent);
+                        writer.word(anti_msg.received);
+                        writer.word(anti_msg.from as u64);
+                        writer.option(anti_msg.to.map(|to| to as u64));
+                        writer.word(anti_msg.id.0);
+                    }
+                }
+            }
+            Frame::Bounced { to_world, msg } => {
+                writer.byte(BOUNCED);
+                writer.word(*to_world);
+                writer.msg(msg);
+            }
+            Frame::Floor { time, received } => {
+                writer.byte(FLOOR);
+                writer.word(*time);
+                writer.word(*received);
+            }
+        }
+        writer.0
+    }
+
+    /// Read a frame, migrating its payload through `migration` if the peer sends an older schema.
+    pub(crate) fn decode(
+        bytes: &[u8],
+        migration: Option<&MigrationPath>,
+    ) -> Result<Self, AikaError> {
+        let mut reader = Reader(bytes, migration);
+        let frame = match reader.byte()? {
+            HELLO => Frame::Hello {
+                schema: reader.word()?,
+                local: reader.word()?,
+                remote: reader.word()?,
+                terminal: reader.word()?,
+            },
+            MAIL => {
+                let to_world = reader.option()?.map(|to| to as usize);
+                let from_world = reader.word()? as usize;
+                let transfer = match reader.byte()? {
+                    MSG => Transfer::Msg(reader.msg()?),
+                    ANTI_MSG => Transfer::AntiMsg(AntiMsg {
+                        sent: reader.word()?,
+                        received: reader.word()?,
+                        from: reader.word()? as usize,
+                        to: reader.option()?.map(|to| to as usize),
+                        id: CausalId(reader.word()?),
+                    }),
+                    tag => return Err(AikaError::Bridge(format!("unknown transfer tag {tag}"))),
+                };
+                Frame::Mail(Mail::write_letter(transfer, from_world, to_world))
+            }
+            BOUNCED => Frame::Bounced {
+                to_world: reader.word()?,
+                msg: reader.msg()?,
+            },
+            FLOOR => Frame::Floor {
+                time: reader.word()?,
+                received: reader.word()?,
+            },
+            tag => return Err(AikaError::Bridge(format!("unknown frame tag {tag}"))),
+        };
+        if !reader.0.is_empty() {
+            return Err(AikaError::Bridge(format!(
+                "{} trailing bytes after a frame",
+                reader.0.len()
+            )));
+        }
+        Ok(frame)
+    }
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn byte(&mut self, byte: u8) {
+        self.0.push(byte);
+    }
+
+    fn word(&mut self, word: u64) {
+        self.0.extend_from_slice(&word.to_le_bytes());
+    }
+
+    fn option(&mut self, word: Option<u64>) {
+        self.byte(u8::from(word.is_some()));
+        self.word(word.unwrap_or_default());
+    }
+
+    fn rpc(&mut self, rpc: Option<Rpc>) {
+        let (tag, id) = match rpc {
+            None => {
+                self.byte(NO_RPC);
+                return;
+            }
+            Some(Rpc::Request(id)) => (REQUEST, id),
+            Some(Rpc::Reply(id)) => (REPLY, id),
+            Some(Rpc::Timeout(id)) => (TIMEOUT, id),
+            Some(Rpc::Discovery(id)) => (DISCOVERY, id),
+        };
+        self.byte(tag);
+        self.word(id.world as u64);
+        self.word(id.agent as u64);
+        self.word(id.time);
+        self.word(id.seq as u64);
+    }
+
+    fn msg<T: Pod + Zeroable + Clone>(&mut self, msg: &Msg<T>) {
+        self.word(msg.from as u64);
+        self.option(msg.to.map(|to| to as u64));
+        self.word(msg.sent);
+        self.word(msg.recv);
+        self.option(msg.group.map(|group| group.0));
+        self.word(msg.offset.to_bits());
+        self.word(msg.from_world as u64);
+        self.word(msg.seq as u64);
+        self.byte(u8::from(msg.timer));
+        self.rpc(msg.rpc);
+        self.byte(u8::from(msg.wake));
+        self.word(msg.id.0);
+        self.0.extend_from_slice(bytemuck::bytes_of(&msg.data));
+    }
+}
+
+struct Reader<'a>(&'a [u8], Option<&'a MigrationPath>);
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], AikaError> {
+        if self.0.len() < len {
+            return Err(AikaError::Bridge("truncated frame".to_string()));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn byte(&mut self) -> Result<u8, AikaError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn word(&mut self) -> Result<u64, AikaError> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap_or_default()))
+    }
+
+    fn option(&mut self) -> Result<Option<u64>, AikaError> {
+        let some = self.byte()? != 0;
+        let word = self.word()?;
+        Ok(some.then_some(word))
+    }
+
+    fn rpc(&mut self) -> Result<Option<Rpc>, AikaError> {
+        let tag = self.byte()?;
+        if tag == NO_RPC {
+            return Ok(None);
+        }
+        let id = RequestId {
+            world: self.word()? as usize,
+            agent: self.word()? as usize,
+            time: self.word()?,
+            seq: self.word()? as u32,
+        };
+        match tag {
+            REQUEST => Ok(Some(Rpc::Request(id))),
+            REPLY => Ok(Some(Rpc::Reply(id))),
+            TIMEOUT => Ok(Some(Rpc::Timeout(id))),
+            DISCOVERY => Ok(Some(Rpc::Discovery(id))),
+            _ => Err(AikaError::Bridge(format!("unknown rpc tag {tag}"))),
+        }
+    }
+
+    fn msg<T: Pod + Zeroable + Clone>(&mut self) -> Result<Msg<T>, AikaError> {
+        let from = self.word()? as usize;
+        let to = self.option()?.map(|to| to as usize);
+        let sent = self.word()?;
+        let recv = self.word()?;
+        let group = self.option()?.map(GroupId);
+        let offset = f64::from_bits(self.word()?);
+        let from_world = self.word()? as usize;
+        let seq = self.word()? as u32;
+        let timer = self.byte()? != 0;
+        let rpc = self.rpc()?;
+        let wake = self.byte()? != 0;
+        let id = CausalId(self.word()?);
+        let data = match self.1 {
+            Some(migration) => migration.migrate(self.take(migration.from().size)?)?,
+            None => bytemuck::pod_read_unaligned(self.take(std::mem::size_of::<T>())?),
+        };
+        Ok(Msg {
+            from,
+            to,
+            sent,
+            recv,
+            group,
+            offset,
+            from_world,
+            seq,
+            timer,
+            rpc,
+            wake,
+            id,
+            data,
+        })
+    }
+}
@@ -0,0 +1,291 @@
+//! Building a `HybridEngine` from runtime configuration.
+//! The const generics of `HybridEngine` are fixed at compile time, so they show up in every type naming the engine
+//! and can't come from a config file. An `EngineBuilder` picks `INTER_SLOTS` at runtime instead, among
+//! `INTER_SLOT_SIZES`, each of which is compiled once, and hands back an `AnyEngine` that dispatches to the engine of
+//! that size. Events are scheduled on a `DynClock` sized from the run, so the clock's own const generics are fixed.
+//! Agents are spawned as `SizedAgent`s: any agent generic over its slot count is one.
+use std::collections::BTreeMap;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    agents::ThreadedAgent,
+    extensions::WorldExtension,
+    mt::hybrid::{
+        cancel::EngineHandle, config::HybridConfig, control::ControlHandle, routing::AgentHandle,
+        stats::RunStats, HybridEngine,
+    },
+    time::SimTime,
+    AikaError,
+};
+
+/// `INTER_SLOTS` an `EngineBuilder` can pick from, smallest first.
+pub const INTER_SLOT_SIZES: [usize; 4] = [16, 64, 256, 1024];
+const CLOCK_SLOTS: usize = 128;
+const CLOCK_HEIGHT: usize = 2;
+
+/// A `ThreadedAgent` for every size in `INTER_SLOT_SIZES`, implemented by every agent that is for all of them.
+pub trait SizedAgent<MessageType: Pod + Zeroable + Clone>:
+    ThreadedAgent<16, MessageType>
+    + ThreadedAgent<64, MessageType>
+    + ThreadedAgent<256, MessageType>
+    + ThreadedAgent<1024, MessageType>
+{
+}
+
+impl<A, MessageType: Pod + Zeroable + Clone> SizedAgent<MessageType> for A where
+    A: ThreadedAgent<16, MessageType>
+        + ThreadedAgent<64, MessageType>
+        + ThreadedAgent<256, MessageType>
+        + ThreadedAgent<1024, MessageType>
+{
+}
+
+/// A `HybridEngine` whose `INTER_SLOTS` was picked at runtime.
+pub enum AnyEngine<MessageType: Pod + Zeroable + Clone> {
+    Slots16(HybridEngine<16, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>),
+    Slots64(HybridEngine<64, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>),
+    Slots256(HybridEngine<256, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>),
+    Slots1024(HybridEngine<1024, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>),
+}
+
+/// Run `$body` on the engine inside `$any`, and with `wrap`, put the engine it returns back in the same variant.
+macro_rules! dispatch {
+    ($any:expr, $engine:ident => $body:expr) => {
+        match $any {
+            AnyEngine::Slots16($engine) => $body,
+            AnyEngine::Slots64($engine) => $body,
+            AnyEngine::Slots256($engine) => $body,
+            AnyEngine::Slots1024($engine) => $body,
+        }
+    };
+    ($any:expr, $engine:ident => $body:expr, wrap) => {
+        match $any {
+            AnyEngine::Slots16($engine) => $body.map(AnyEngine::Slots16),
+            AnyEngine::Slots64($engine) => $body.map(AnyEngine::Slots64),
+            AnyEngine::Slots256($engine) => $body.map(AnyEngine::Slots256),
+            AnyEngine::Slots1024($engine) => $body.map(AnyEngine::Slots1024),
+        }
+    };
+}
+
+impl<MessageType: Pod + Zeroable + Clone> AnyEngine<MessageType> {
+    /// The `INTER_SLOTS` picked for this engine.
+    pub fn inter_slots(&self) -> usize {
+        match self {
+            AnyEngine::Slots16(_) => 16,
+            AnyEngine::Slots64(_) => 64,
+            AnyEngine::Slots256(_) => 256,
+            AnyEngine::Slots1024(_) => 1024,
+        }
+    }
+
+    pub fn config(&self) -> &HybridConfig {
+        dispatch!(self, engine => &engine.config)
+    }
+
+    /// Spawn an agent on planet `planet_id`, see `HybridEngine::spawn_agent()`.
+    pub fn spawn_agent(
+        &mut self,
+        planet_id: usize,
+        agent: Box<dyn SizedAgent<MessageType>>,
+    ) -> Result<AgentHandle, AikaError> {
+        dispatch!(self, engine => engine.spawn_agent(planet_id, agent))
+    }
+
+    /// Spawn an agent on the planet with the fewest agents, see `HybridEngine::spawn_agent_autobalance()`.
+    pub fn spawn_agent_autobalance(
+        &mut self,
+        agent: Box<dyn SizedAgent<MessageType>>,
+    ) -> Result<AgentHandle, AikaError> {
+        dispatch!(self, engine => engine.spawn_agent_autobalance(agent))
+    }
+
+    pub fn register_extension(
+        &mut self,
+        planet_id: usize,
+        extension: Box<dyn WorldExtension>,
+    ) -> Result<(), AikaError> {
+        dispatch!(self, engine => engine.register_extension(planet_id, extension))
+    }
+
+    pub fn schedule(
+        &mut self,
+        planet_id: usize,
+        agent_id: usize,
+        time: impl Into<SimTime>,
+    ) -> Result<(), AikaError> {
+        let time = time.into();
+        dispatch!(self, engine => engine.schedule(planet_id, agent_id, time))
+    }
+
+    pub fn schedule_many<T: Into<SimTime> + Copy>(
+        &mut self,
+        events: &[(usize, usize, T)],
+    ) -> Result<(), AikaError> {
+        dispatch!(self, engine => engine.schedule_many(events))
+    }
+
+    pub fn schedule_all_agents(&mut self, time: impl Into<SimTime>) -> Result<(), AikaError> {
+        let time = time.into();
+        dispatch!(self, engine => engine.schedule_all_agents(time))
+    }
+
+    pub fn control_handle(&self) -> ControlHandle {
+        dispatch!(self, engine => engine.control_handle())
+    }
+
+    /// Run the engine to its terminal time, see `HybridEngine::run()`.
+    pub fn run(self) -> Result<Self, AikaError> {
+        dispatch!(self, engine => engine.run(), wrap)
+    }
+
+    /// Run the engine until GVT has advanced by `gvt_delta` ticks, see `HybridEngine::advance()`.
+    pub fn advance(self, gvt_delta: u64) -> Result<Self, AikaError> {
+        dispatch!(self, engine => engine.advance(gvt_delta), wrap)
+    }
+
+    pub fn gvt(&self) -> u64 {
+        dispatch!(self, engine => engine.gvt())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        dispatch!(self, engine => engine.is_finished())
+    }
+
+    pub fn handle(&self) -> EngineHandle {
+        dispatch!(self, engine => engine.handle())
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        dispatch!(self, engine => engine.is_cancelled())
+    }
+
+    pub fn stats(&self) -> RunStats {
+        dispatch!(self, engine => engine.stats())
+    }
+
+    pub fn aggregates(&self) -> (u64, BTreeMap<usize, f64>) {
+        dispatch!(self, engine => engine.aggregates())
+    }
+
+    pub fn locate(&self, handle: AgentHandle) -> Option<(usize, usize)> {
+        dispatch!(self, engine => engine.locate(handle))
+    }
+}
+
+/// Builds an `AnyEngine` from a `HybridConfig` and sizes known only at runtime.
+#[derive(Clone, Debug)]
+pub struct EngineBuilder {
+    config: HybridConfig,
+    inter_slots: usize,
+}
+
+impl EngineBuilder {
+    pub fn new(config: HybridConfig) -> Self {
+        Self {
+            config,
+            inter_slots: 0,
+        }
+    }
+
+    /// Ask for at least `inter_slots` slots per interplanetary ring buffer. The engine gets the smallest size in
+    /// `INTER_SLOT_SIZES` covering both this and what the config needs.
+    pub fn with_inter_slots(mut self, inter_slots: usize) -> Self {
+        self.inter_slots = inter_slots;
+        self
+    }
+
+    pub fn build<MessageType: Pod + Zeroable + Clone>(
+        self,
+    ) -> Result<AnyEngine<MessageType>, AikaError> {
+        let wanted = self
+            .inter_slots
+            .max(self.config.const_requirements().inter_slots);
+        let config = self.config.with_dynamic_clock(true);
+        let engine = match INTER_SLOT_SIZES.into_iter().find(|size| *size >= wanted) {
+            Some(16) => AnyEngine::Slots16(HybridEngine::create(config)?),
+            Some(64) => AnyEngine::Slots64(HybridEngine::create(config)?),
+            Some(256) => AnyEngine::Slots256(HybridEngine::create(config)?),
+            Some(1024) => AnyEngine::Slots1024(HybridEngine::create(config)?),
+            _ => {
+                return Err(AikaError::ConfigError(format!(
+                    "{wanted} interplanetary slots are needed, but an EngineBuilder supports at most {}",
+                    INTER_SLOT_SIZES[INTER_SLOT_SIZES.len() - 1]
+                )))
+            }
+        };
+        Ok(engine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    };
+
+    use super::*;
+    use crate::{
+        agents::PlanetContext,
+        objects::{Action, Event, Msg},
+    };
+
+    // Steps every tick whatever the engine's slot count
+    struct Walker {
+        steps: Arc<AtomicU64>,
+    }
+
+    impl<const SLOTS: usize> ThreadedAgent<SLOTS, u64> for Walker {
+        fn step(&mut self, context: &mut PlanetContext<SLOTS, u64>, agent_id: usize) -> Event {
+            self.steps.fetch_add(1, Ordering::Relaxed);
+            Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<SLOTS, u64>,
+            _msg: Msg<u64>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_engine_builder() {
+        // as read from a config file
+        let (planets, inter_slots) = (3, 100);
+        let config = HybridConfig::new(planets, 64)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(4, 10)
+            .with_uniform_worlds(16, 2, 16);
+        let mut engine = EngineBuilder::new(config.clone())
+            .with_inter_slots(inter_slots)
+            .build::<u64>()
+            .unwrap();
+        assert_eq!(engine.inter_slots(), 256);
+        assert!(engine.config().dynamic_clock);
+
+        let steps = Arc::new(AtomicU64::new(0));
+        for planet in 0..planets {
+            for _ in 0..2 {
+                let walker = Walker {
+                    steps: steps.clone(),
+                };
+                engine.spawn_agent(planet, Box::new(walker)).unwrap();
+            }
+        }
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+        assert_eq!(steps.load(Ordering::Relaxed), 6 * 19);
+        assert_eq!(engine.stats().planets.len(), planets);
+
+        let small = EngineBuilder::new(config.clone()).build::<u64>().unwrap();
+        assert_eq!(small.inter_slots(), 16);
+        assert!(EngineBuilder::new(config)
+            .with_inter_slots(5000)
+            .build::<u64>()
+            .is_err());
+    }
+}
@@ -0,0 +1,32 @@
+//! Cooperative cancellation of a running `HybridEngine`.
+//! An `EngineHandle` is taken from the engine before it runs and moved to whichever thread decides to stop it, such
+//! as one waiting for Ctrl-C or another signal. Cancelling asks the `Galaxy` to park the run at the checkpoint every
+//! `Planet` is held at, as `HybridEngine::advance()` would: nothing past it has run, and once GVT reaches it nothing
+//! before it can roll back. Every `Planet` then finishes its extensions as at the end of a run and hands itself back,
+//! and `run()` returns the engine as it stood at that cut rather than losing it. A cancelled engine is never resumed.
+use std::sync::Arc;
+
+use crate::mt::hybrid::{backoff::Wakeup, galaxy::Parking};
+
+/// Cancels the run of the `HybridEngine` it was taken from. Cheap to clone and `Send`.
+#[derive(Clone, Debug)]
+pub struct EngineHandle {
+    parking: Arc<Parking>,
+    wakeup: Arc<Wakeup>,
+}
+
+impl EngineHandle {
+    pub(crate) fn new(parking: Arc<Parking>, wakeup: Arc<Wakeup>) -> Self {
+        Self { parking, wakeup }
+    }
+
+    /// Stop the run at the next consistent cut. Cancelling before the run starts keeps it from starting at all.
+    pub fn cancel(&self) {
+        self.parking.cancel();
+        self.wakeup.notify();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.parking.cancelled()
+    }
+}
@@ -1,7 +1,65 @@
 //! Configuration management for hybrid multi-threaded simulations.
 //! Provides `HybridConfig` for specifying world counts, memory arena sizes, synchronization
 //! parameters, and agent distribution across planets with validation and helper methods.
-use crate::AikaError;
+use std::collections::BTreeMap;
+
+use crate::{
+    agents::subscriptions::NotifyAt,
+    dynclock::ClockSizing,
+    mailbox::OverflowPolicy,
+    mt::hybrid::{
+        affinity::ThreadPlacement, batch::MailBatching, debug::DebugFilter, faults::FaultInjection,
+        link::LinkModel, memory::MemoryLimits, reduce::ReduceOp, reports::RollbackReporting,
+        schema::SchemaRegistry, throttle::AdaptiveThrottle,
+    },
+    overflow::OverflowStrategy,
+    AikaError,
+};
+
+/// Parameters for splitting lagging planets at GVT checkpoints.
+#[derive(Debug, Clone, Copy)]
+pub struct AutoScaling {
+    /// number of planet slots reserved in the `Galaxy` for planets created at runtime
+    pub spare_planets: usize,
+    /// mean LVT lag (in steps) behind the fastest planet over a checkpoint window that marks a planet as hot
+    pub lag_threshold: u64,
+    /// minimum number of agents a planet needs before it can be split
+    pub min_agents: usize,
+}
+
+/// Smallest const generics a `HybridEngine` needs for a given `HybridConfig`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConstRequirements {
+    /// `INTER_SLOTS`: one `Galaxy` poll may route `Mail` from every planet, spares included, into the same inbox
+    pub inter_slots: usize,
+    /// `CLOCK_SLOTS`: the timing wheels index levels by dividing by `CLOCK_SLOTS - 1`
+    pub clock_slots: usize,
+    /// `CLOCK_HEIGHT`: the timing wheels need at least one level
+    pub clock_height: usize,
+}
+
+/// What a `Planet` does when one of its agents panics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Stop the `Planet` with `AikaError::AgentPanicked`.
+    #[default]
+    Abort,
+    /// Drop the agent and its pending events and keep running, raising `SimWarning::AgentRemoved`.
+    /// Removal is not undone by rollbacks.
+    Remove,
+}
+
+/// How a `Planet` interleaves mail and events within a tick.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TickOrder {
+    /// Read every `Msg` of the tick, then step its events.
+    #[default]
+    MessagesFirst,
+    /// Step every event of the tick, then read its mail.
+    EventsFirst,
+    /// Handle mail and events in one pass ordered by offset, mail first on ties.
+    MergedByPriority,
+}
 
 #[derive(Debug, Clone)]
 pub struct HybridConfig {
@@ -10,9 +68,71 @@ pub struct HybridConfig {
     pub agent_states_asizes: Vec<Vec<usize>>,
     pub anti_message_asize: usize,
     pub throttle_horizon: u64,
+    /// bounds and controller of a throttle horizon following the rollback rate, see `with_adaptive_throttle()`
+    pub adaptive_throttle: Option<AdaptiveThrottle>,
     pub checkpoint_frequency: u64,
     pub terminal: f64,
     pub timestep: f64,
+    pub record_causality: bool,
+    /// give events and mail `CausalId`s and record their causes, see `with_causality()`
+    pub causal_ids: bool,
+    pub record_trace: bool,
+    pub auto_scaling: Option<AutoScaling>,
+    pub max_rollback_depth: Option<u64>,
+    /// aggregation of rollbacks into per-window reports, see `with_rollback_reports()`
+    pub rollback_reports: Option<RollbackReporting>,
+    pub migration_imbalance: Option<f64>,
+    pub overflow: OverflowStrategy,
+    /// what becomes of interplanetary mail for a full planet inbox, see `with_mailbox_overflow()`
+    pub mailbox_overflow: OverflowPolicy,
+    /// bandwidth models of directed `(from, to)` planet links
+    pub links: BTreeMap<(usize, usize), LinkModel>,
+    /// batching of outgoing interplanetary mail, see `with_mail_batching()`
+    pub mail_batching: Option<MailBatching>,
+    /// seeded drops, duplicates and delays of interplanetary mail, see `with_faults()`
+    pub faults: Option<FaultInjection>,
+    /// keep `Parcel` payloads on a heap shared by every planet, see `with_heap_payloads()`
+    pub heap_payloads: bool,
+    pub panic_policy: PanicPolicy,
+    pub tick_order: TickOrder,
+    /// when subscribers hear of a change to the world state, see `with_notify_at()`
+    pub notify_at: NotifyAt,
+    /// schedule events on wheels sized from the run rather than the const generics, see `with_dynamic_clock()`
+    pub dynamic_clock: bool,
+    /// jump over ticks with nothing to do, see `with_fast_forward()`
+    pub fast_forward: bool,
+    /// end the run once nothing is left to do anywhere, see `with_end_when_quiescent()`
+    pub end_when_quiescent: bool,
+    /// cores the `Galaxy` and `Planet` threads are pinned to, see `with_thread_placement()`
+    pub placement: Option<ThreadPlacement>,
+    /// ticks between wheel occupancy samples on every `Planet`, see `with_wheel_occupancy()`
+    pub occupancy_every: Option<u64>,
+    /// width in ticks of the KPI buckets of every `Planet`, see `with_kpis()`
+    pub kpi_width: Option<u64>,
+    /// soft and hard limits on the memory of every `Planet`, see `with_memory_limits()`
+    pub memory_limits: Option<MemoryLimits>,
+    /// worker threads of every `Planet` for the parallel part of its agents' steps, see `with_parallel_steps()`
+    pub parallel_steps: Option<usize>,
+    /// process every tick in a canonical order so runs are reproducible, see `with_determinism()`
+    pub deterministic: bool,
+    /// seed for the tie-breaks of the canonical order
+    pub seed: u64,
+    /// hash every `Planet`'s committed state at each checkpoint, see `with_determinism_audit()`
+    pub determinism_audit: bool,
+    /// simulation times at which planets come online, for planets that don't start at 0
+    pub activations: BTreeMap<usize, u64>,
+    /// base timesteps per step of planets that don't step every timestep, see `with_world_rate()`
+    pub rates: BTreeMap<usize, u64>,
+    /// ticks left out of the statistics at the start of the run, see `with_warmup()`
+    pub warmup: u64,
+    /// debug output of the `Galaxy` and every `Planet`, see `with_debug()`
+    pub debug: DebugFilter,
+    /// how the contributions to each reduced key are combined, `ReduceOp::Sum` if unlisted
+    pub reductions: BTreeMap<usize, ReduceOp>,
+    /// world slots standing in for the planets of a bridged engine, see `with_remote_worlds()`
+    pub remote_worlds: usize,
+    /// versioned payload schema and its migrations, see `with_payload_schema()`
+    pub payload_schema: Option<SchemaRegistry>,
 }
 
 impl HybridConfig {
@@ -24,9 +144,44 @@ impl HybridConfig {
             agent_states_asizes: vec![Vec::new(); number_of_worlds],
             anti_message_asize,
             throttle_horizon: 0,
+            adaptive_throttle: None,
             checkpoint_frequency: 0,
             terminal: 0.0,
             timestep: 0.0,
+            record_causality: false,
+            causal_ids: false,
+            record_trace: false,
+            auto_scaling: None,
+            max_rollback_depth: None,
+            rollback_reports: None,
+            migration_imbalance: None,
+            overflow: OverflowStrategy::Unbounded,
+            mailbox_overflow: OverflowPolicy::DropNewest,
+            links: BTreeMap::new(),
+            mail_batching: None,
+            faults: None,
+            heap_payloads: false,
+            panic_policy: PanicPolicy::Abort,
+            tick_order: TickOrder::MessagesFirst,
+            notify_at: NotifyAt::SameTick,
+            dynamic_clock: false,
+            fast_forward: false,
+            end_when_quiescent: false,
+            placement: None,
+            occupancy_every: None,
+            kpi_width: None,
+            parallel_steps: None,
+            memory_limits: None,
+            deterministic: false,
+            seed: 0,
+            determinism_audit: false,
+            activations: BTreeMap::new(),
+            rates: BTreeMap::new(),
+            warmup: 0,
+            debug: DebugFilter::default(),
+            reductions: BTreeMap::new(),
+            remote_worlds: 0,
+            payload_schema: None,
         }
     }
 
@@ -48,6 +203,283 @@ impl HybridConfig {
         self
     }
 
+    /// Let every `Planet` move its throttle horizon within the bounds of `throttle` as its rollback rate changes,
+    /// starting from the horizon set with `with_optimistic_sync()`
+    pub fn with_adaptive_throttle(mut self, throttle: AdaptiveThrottle) -> Self {
+        self.adaptive_throttle = Some(throttle);
+        self
+    }
+
+    /// Furthest any `Planet` may run ahead of GVT, with or without the adaptive throttle
+    pub fn horizon_ceiling(&self) -> u64 {
+        self.adaptive_throttle
+            .map_or(self.throttle_horizon, |throttle| throttle.max_horizon)
+    }
+
+    /// Record the causal graph of the run on every `Planet` for critical-path analysis. Each `Planet` keeps at most
+    /// `analysis::critical_path::CAUSAL_LOG_LIMIT` activations and as many links, dropping later ones, see
+    /// `CausalLog::dropped()`
+    pub fn with_causal_log(mut self, enabled: bool) -> Self {
+        self.record_causality = enabled;
+        self
+    }
+
+    /// Give every event and `Msg` a simulation-wide `CausalId` and record which event or `Msg` caused it, so the chain
+    /// behind each rollback can be rebuilt with `HybridEngine::causality()`
+    pub fn with_causality(mut self) -> Self {
+        self.causal_ids = true;
+        self
+    }
+
+    /// Record a Chrome/Perfetto timeline of every `Planet` and the `Galaxy`, see `HybridEngine::trace()`
+    pub fn with_tracing(mut self, enabled: bool) -> Self {
+        self.record_trace = enabled;
+        self
+    }
+
+    /// Allow lagging planets to be split in two at GVT checkpoints, using up to `spare_planets` extra threads
+    pub fn with_auto_scaling(
+        mut self,
+        spare_planets: usize,
+        lag_threshold: u64,
+        min_agents: usize,
+    ) -> Self {
+        self.auto_scaling = Some(AutoScaling {
+            spare_planets,
+            lag_threshold,
+            min_agents,
+        });
+        self
+    }
+
+    /// Treat rollbacks deeper than `depth` steps as a storm: the offending link runs conservatively until the next checkpoint
+    pub fn with_max_rollback_depth(mut self, depth: u64) -> Self {
+        self.max_rollback_depth = Some(depth);
+        self
+    }
+
+    /// Collapse the rollbacks of every `Planet` into one report per `reporting.window` ticks of GVT, see `RunStats::rollback_reports()`
+    pub fn with_rollback_reports(mut self, reporting: RollbackReporting) -> Self {
+        self.rollback_reports = Some(reporting);
+        self
+    }
+
+    /// Move one agent per checkpoint from the busiest to the idlest planet while the busiest processed more than
+    /// `imbalance` times as many events over the last checkpoint window
+    pub fn with_agent_migration(mut self, imbalance: f64) -> Self {
+        self.migration_imbalance = Some(imbalance);
+        self
+    }
+
+    /// Bound every `Planet`'s overflow queue of far-future events
+    pub fn with_overflow_strategy(mut self, strategy: OverflowStrategy) -> Self {
+        self.overflow = strategy;
+        self
+    }
+
+    /// Choose what the `Galaxy` does with mail for a planet whose inbox is full. Under the default,
+    /// `OverflowPolicy::DropNewest`, the letters go back to their senders as `DeliveryFailure`s
+    pub fn with_mailbox_overflow(mut self, policy: OverflowPolicy) -> Self {
+        self.mailbox_overflow = policy;
+        self
+    }
+
+    /// Choose what happens to a `Planet` when one of its agents panics
+    pub fn with_panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.panic_policy = policy;
+        self
+    }
+
+    /// Choose how every `Planet` interleaves each tick's mail and events. Under anything but
+    /// `TickOrder::MessagesFirst`, wake-ups requested while reading mail can't join the tick they were requested for
+    pub fn with_tick_order(mut self, order: TickOrder) -> Self {
+        self.tick_order = order;
+        self
+    }
+
+    /// Pick when agents subscribed to a world state cell hear of a change, at the end of the tick of the write or of
+    /// the next one
+    pub fn with_notify_at(mut self, notify_at: NotifyAt) -> Self {
+        self.notify_at = notify_at;
+        self
+    }
+
+    /// Schedule every `Planet`'s events on a `DynClock` sized from the time bounds and throttle horizon, growing it
+    /// whenever its overflow queue fills up, instead of on wheels sized by `CLOCK_SLOTS` and `CLOCK_HEIGHT`
+    pub fn with_dynamic_clock(mut self, enabled: bool) -> Self {
+        self.dynamic_clock = enabled;
+        self
+    }
+
+    /// Let every `Planet` jump straight to its next tick with anything due instead of stepping through empty ones,
+    /// within the throttle horizon and never past a checkpoint, cut or barrier, see `Planet::set_fast_forward()`
+    pub fn with_fast_forward(mut self, enabled: bool) -> Self {
+        self.fast_forward = enabled;
+        self
+    }
+
+    /// End the run early, with GVT at the terminal time, once no `Planet` has anything left to do and no mail is in
+    /// flight, see `Planet::set_end_when_quiescent()`. Control actions sent after that are never applied. A bridged
+    /// engine always runs to the terminal time
+    pub fn with_end_when_quiescent(mut self, enabled: bool) -> Self {
+        self.end_when_quiescent = enabled;
+        self
+    }
+
+    /// Count the events, deliveries and active agents of every `Planet` in buckets of `width` ticks, committed as GVT
+    /// passes them, see `HybridEngine::kpi_table()`
+    pub fn with_kpis(mut self, width: u64) -> Self {
+        self.kpi_width = Some(width);
+        self
+    }
+
+    /// Sample the memory every `Planet` holds in its journals, event overflow and pending mail, holding it at GVT past
+    /// the soft limit and failing the run past the hard limit, see `mt::hybrid::memory`
+    pub fn with_memory_limits(mut self, limits: MemoryLimits) -> Self {
+        self.memory_limits = Some(limits);
+        self
+    }
+
+    /// Run the `ParallelStep` of the agents due in a tick across `workers` threads of their `Planet` before they step,
+    /// see `mt::hybrid::parallel`. Under `TickOrder::MergedByPriority` events are stepped one at a time, mail between
+    /// them, so nothing runs in parallel
+    pub fn with_parallel_steps(mut self, workers: usize) -> Self {
+        self.parallel_steps = Some(workers.max(1));
+        self
+    }
+
+    /// Sample the wheel occupancy of every `Planet` every `every` ticks, see `HybridEngine::occupancy_table()`
+    pub fn with_wheel_occupancy(mut self, every: u64) -> Self {
+        self.occupancy_every = Some(every);
+        self
+    }
+
+    /// Pin the `Galaxy` thread and every `Planet` thread to a core picked by `placement`, on Linux
+    pub fn with_thread_placement(mut self, placement: ThreadPlacement) -> Self {
+        self.placement = Some(placement);
+        self
+    }
+
+    /// Sizing of the `DynClock` every `Planet` starts with, if enabled
+    pub fn clock_sizing(&self) -> Option<ClockSizing> {
+        self.dynamic_clock
+            .then(|| ClockSizing::for_run(self.terminal, self.timestep, self.horizon_ceiling()))
+    }
+
+    /// Make runs reproducible regardless of thread timing: every `Planet` reads each tick's mail sorted by offset,
+    /// sender and send order, and steps its events sorted by offset and agent, with ties between senders and agents
+    /// broken by a ranking seeded with `seed`. Mail arriving for a tick the `Planet` already processed rolls the whole
+    /// tick back, so it runs again with all of its mail in that order. Auto-scaling and migration depend on timing
+    /// and can't be combined with it
+    pub fn with_determinism(mut self, seed: u64) -> Self {
+        self.deterministic = true;
+        self.seed = seed;
+        self
+    }
+
+    /// Hash every `Planet`'s committed state at each checkpoint into a `Fingerprint` of the run, to compare runs that
+    /// should be identical, see `HybridEngine::fingerprint()`. Every `Planet` waits at each checkpoint until GVT
+    /// reaches it. Auto-scaling can't be combined with it
+    pub fn with_determinism_audit(mut self) -> Self {
+        self.determinism_audit = true;
+        self
+    }
+
+    /// Keep planet `world_id` dormant until GVT reaches `time`. Its agents' initial schedules and any mail sent to
+    /// it before then are deferred to `time`
+    pub fn with_activation(mut self, world_id: usize, time: u64) -> Self {
+        self.activations.insert(world_id, time);
+        self
+    }
+
+    /// Step planet `world_id` every `rate` timesteps, for parts of a model that change slowly. Times stay in base
+    /// timesteps everywhere, GVT included; the planet's timeouts count its own steps and mail sent to it is read on
+    /// the first of its steps at or after the receive time. See `Planet::set_rate()`
+    pub fn with_world_rate(mut self, world_id: usize, rate: u64) -> Self {
+        self.rates.insert(world_id, rate);
+        self
+    }
+
+    /// Leave the first `time` ticks out of the statistics for steady-state analysis: wheel occupancy, KPIs, the causal
+    /// log and state histories skip them, and each `Planet`'s counters are reset once GVT passes `time`, so all
+    /// planets drop the same warm-up whatever their progress. See `Planet::set_warmup()`
+    pub fn with_warmup(mut self, time: u64) -> Self {
+        self.warmup = time;
+        self
+    }
+
+    /// Report rollbacks, GVT updates and opened mail as `filter` allows, through the `log` facade with the `log`
+    /// feature. Rollbacks are logged at `Info`, GVT updates at `Debug` and mail at `Trace`, see `DebugKind::level()`
+    pub fn with_debug(mut self, filter: DebugFilter) -> Self {
+        self.debug = filter;
+        self
+    }
+
+    /// Reserve a world slot for each of the `count` planets of a peer engine, numbered after this engine's own, to be
+    /// bridged with `HybridEngine::bridge()`
+    pub fn with_remote_worlds(mut self, count: usize) -> Self {
+        self.remote_worlds = count;
+        self
+    }
+
+    /// Expect payloads of the schema of `schemas` rather than the bare layout of the message type, accepting peers
+    /// with an older schema through its migrations, see `mt::hybrid::schema`
+    pub fn with_payload_schema(mut self, schemas: SchemaRegistry) -> Self {
+        self.payload_schema = Some(schemas);
+        self
+    }
+
+    /// Combine the contributions to the global aggregate `key` with `op` rather than summing them
+    pub fn with_reduction(mut self, key: usize, op: ReduceOp) -> Self {
+        self.reductions.insert(key, op);
+        self
+    }
+
+    /// Give the directed link from planet `from` to planet `to` a latency and bandwidth
+    pub fn with_link(mut self, from: usize, to: usize, model: LinkModel) -> Self {
+        self.links.insert((from, to), model);
+        self
+    }
+
+    /// Give every directed link between two configured planets the same latency and bandwidth
+    pub fn with_uniform_links(mut self, model: LinkModel) -> Self {
+        for from in 0..self.number_of_worlds {
+            for to in (0..self.number_of_worlds).filter(|to| *to != from) {
+                self.links.insert((from, to), model);
+            }
+        }
+        self
+    }
+
+    /// The models of every link leaving planet `from`, keyed by destination
+    pub fn links_from(&self, from: usize) -> BTreeMap<usize, LinkModel> {
+        self.links
+            .range((from, 0)..=(from, usize::MAX))
+            .map(|((_, to), model)| (*to, *model))
+            .collect()
+    }
+
+    /// Send each planet's outgoing mail to a destination as one bundle once `max_batch` letters are waiting or the
+    /// oldest has waited `flush_interval` steps. An interval of 0 bundles each step's mail
+    pub fn with_mail_batching(mut self, max_batch: usize, flush_interval: u64) -> Self {
+        self.mail_batching = Some(MailBatching::new(max_batch, flush_interval));
+        self
+    }
+
+    /// Drop, duplicate or delay each planet's outgoing interplanetary mail at random, with the probabilities and seed
+    /// of `faults`. A run with the same seed hits the same letters
+    pub fn with_faults(mut self, faults: FaultInjection) -> Self {
+        self.faults = Some(faults);
+        self
+    }
+
+    /// Keep the payloads sent with `PlanetContext::send_parcel()` on a heap shared by every planet, for an engine with
+    /// `Parcel` as its message type. Lets models send types that aren't `Pod`, at the cost of a lock per send and read
+    pub fn with_heap_payloads(mut self) -> Self {
+        self.heap_payloads = true;
+        self
+    }
+
     /// Configure a specific world's state and agent arena sizes
     pub fn with_world(
         mut self,
@@ -117,12 +549,73 @@ impl HybridConfig {
             ));
         }
 
+        if let Some(throttle) = self.adaptive_throttle {
+            if throttle.min_horizon == 0 || throttle.min_horizon > throttle.max_horizon {
+                return Err(AikaError::ConfigError(
+                    "Adaptive throttle needs 0 < min_horizon <= max_horizon".to_string(),
+                ));
+            }
+        }
+
         if self.checkpoint_frequency == 0 {
             return Err(AikaError::ConfigError(
                 "Checkpoint frequency must be set".to_string(),
             ));
         }
 
+        if self
+            .migration_imbalance
+            .is_some_and(|imbalance| imbalance <= 1.0)
+        {
+            return Err(AikaError::ConfigError(
+                "Migration imbalance must be greater than 1".to_string(),
+            ));
+        }
+
+        if self
+            .mail_batching
+            .is_some_and(|batching| batching.max_batch == 0)
+        {
+            return Err(AikaError::ConfigError(
+                "Mail batches must hold at least one letter".to_string(),
+            ));
+        }
+
+        if let Some(fault) = self.faults.as_ref().and_then(FaultInjection::invalid) {
+            return Err(AikaError::ConfigError(format!(
+                "Fault probability {fault} must be within [0, 1]"
+            )));
+        }
+
+        for (world, time) in &self.activations {
+            if *world >= self.number_of_worlds {
+                return Err(AikaError::InvalidWorldId(*world));
+            }
+            if *time as f64 * self.timestep >= self.terminal {
+                return Err(AikaError::ConfigError(format!(
+                    "World {world} activates at {time}, past the terminal time"
+                )));
+            }
+        }
+
+        if self.warmup as f64 * self.timestep >= self.terminal {
+            return Err(AikaError::ConfigError(format!(
+                "Warm-up of {} ticks reaches past the terminal time",
+                self.warmup
+            )));
+        }
+
+        for (world, rate) in &self.rates {
+            if *world >= self.number_of_worlds {
+                return Err(AikaError::InvalidWorldId(*world));
+            }
+            if *rate == 0 {
+                return Err(AikaError::ConfigError(format!(
+                    "World {world} must step at least every timestep"
+                )));
+            }
+        }
+
         // Check that all worlds have been configured
         for (i, world_size) in self.world_state_asizes.iter().enumerate() {
             if *world_size == 0 {
@@ -135,6 +628,53 @@ impl HybridConfig {
         Ok(())
     }
 
+    /// Planets the `Galaxy` will register, including spares reserved for auto-scaling and remote worlds.
+    pub fn total_planets(&self) -> usize {
+        self.number_of_worlds
+            + self.auto_scaling.map_or(0, |scaling| scaling.spare_planets)
+            + self.remote_worlds
+    }
+
+    /// Compute the minimum const generics required by this configuration.
+    pub fn const_requirements(&self) -> ConstRequirements {
+        ConstRequirements {
+            inter_slots: self.total_planets().max(1),
+            clock_slots: 2,
+            clock_height: 1,
+        }
+    }
+
+    /// Check the engine's const generics against this configuration, naming the parameter to raise on failure.
+    pub fn check_consts<
+        const INTER_SLOTS: usize,
+        const CLOCK_SLOTS: usize,
+        const CLOCK_HEIGHT: usize,
+    >(
+        &self,
+    ) -> Result<(), AikaError> {
+        let required = self.const_requirements();
+        if INTER_SLOTS < required.inter_slots {
+            return Err(AikaError::ConfigError(format!(
+                "INTER_SLOTS is {INTER_SLOTS} but {} planets are configured (including spares); raise INTER_SLOTS to at least {}",
+                self.total_planets(),
+                required.inter_slots
+            )));
+        }
+        if CLOCK_SLOTS < required.clock_slots {
+            return Err(AikaError::ConfigError(format!(
+                "CLOCK_SLOTS is {CLOCK_SLOTS}; raise CLOCK_SLOTS to at least {}",
+                required.clock_slots
+            )));
+        }
+        if CLOCK_HEIGHT < required.clock_height {
+            return Err(AikaError::ConfigError(format!(
+                "CLOCK_HEIGHT is {CLOCK_HEIGHT}; raise CLOCK_HEIGHT to at least {}",
+                required.clock_height
+            )));
+        }
+        Ok(())
+    }
+
     /// Get configuration for a specific world
     pub fn world_config(&self, world_id: usize) -> Result<(usize, usize, &Vec<usize>), AikaError> {
         if world_id >= self.number_of_worlds {
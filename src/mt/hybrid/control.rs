@@ -0,0 +1,244 @@
+//! Control-plane channel for `Planet`s.
+//! Control actions travel on a small per-planet channel that is polled every loop iteration, ahead of checkpoint
+//! and throttle stalls, so they are never queued behind interplanetary `Mail`. Every applied action is recorded
+//! with the local time it took effect, so a run can be replayed deterministically.
+use std::sync::{
+    mpsc::{Receiver, Sender},
+    Arc,
+};
+
+use crate::{
+    mt::hybrid::topology::{RouteChange, Topology},
+    AikaError,
+};
+
+/// A control-plane action applied by a `Planet` as soon as it is received.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ControlAction {
+    /// Stop stepping the `Planet` until `Resume` arrives. Mail and control are still polled.
+    Pause,
+    Resume,
+    /// Hand a parameter update to one agent, or every agent with `None`, via `ThreadedAgent::on_control()`.
+    Parameter {
+        agent: Option<usize>,
+        key: usize,
+        value: f64,
+    },
+    /// Schedule an agent to step at `time`, or at the `Planet`'s local time if that has already passed.
+    Intervene {
+        agent: usize,
+        time: u64,
+    },
+    /// Change a route between planets at the next GVT checkpoint. Sent to the `Galaxy` whichever `Planet` it is
+    /// addressed to, and handed to the agents of both ends of the route via `ThreadedAgent::on_control()` once applied.
+    Rewire(RouteChange),
+}
+
+/// A `ControlAction` as applied by a `Planet`, for replay.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ControlRecord {
+    /// Local time of the `Planet` when the action took effect.
+    pub time: u64,
+    pub action: ControlAction,
+}
+
+/// Sends `ControlAction`s to `Planet`s. Cheap to clone and hand to privileged agents.
+#[derive(Clone, Debug)]
+pub struct ControlHandle {
+    senders: Vec<Sender<ControlAction>>,
+    topology: Arc<Topology>,
+}
+
+impl ControlHandle {
+    pub(crate) fn new(senders: Vec<Sender<ControlAction>>, topology: Arc<Topology>) -> Self {
+        Self { senders, topology }
+    }
+
+    /// Number of `Planet`s reachable through this handle.
+    pub fn planets(&self) -> usize {
+        self.senders.len()
+    }
+
+    /// Send an action to a single `Planet`.
+    pub fn send(&self, planet_id: usize, action: ControlAction) -> Result<(), AikaError> {
+        if let ControlAction::Rewire(change) = action {
+            return self.rewire(change);
+        }
+        let sender = self
+            .senders
+            .get(planet_id)
+            .ok_or(AikaError::InvalidWorldId(planet_id))?;
+        sender
+            .send(action)
+            .map_err(|_| AikaError::ControlChannelClosed(planet_id))
+    }
+
+    /// Send an action to every `Planet`.
+    pub fn broadcast(&self, action: ControlAction) -> Result<(), AikaError> {
+        if let ControlAction::Rewire(change) = action {
+            return self.rewire(change);
+        }
+        for planet_id in 0..self.senders.len() {
+            self.send(planet_id, action)?;
+        }
+        Ok(())
+    }
+
+    /// Queue a route change with the `Galaxy`, to be applied at the next GVT checkpoint.
+    pub fn rewire(&self, change: RouteChange) -> Result<(), AikaError> {
+        self.topology.request(change)
+    }
+}
+
+/// Receiving end of a `Planet`'s control channel, plus the log of applied actions.
+pub(crate) struct ControlPlane {
+    sender: Sender<ControlAction>,
+    receiver: Receiver<ControlAction>,
+    pub(crate) paused: bool,
+    pub(crate) log: Vec<ControlRecord>,
+}
+
+impl ControlPlane {
+    pub(crate) fn new() -> Self {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        Self {
+            sender,
+            receiver,
+            paused: false,
+            log: Vec::new(),
+        }
+    }
+
+    pub(crate) fn sender(&self) -> Sender<ControlAction> {
+        self.sender.clone()
+    }
+
+    /// Drain every pending action without blocking.
+    pub(crate) fn drain(&mut self) -> Vec<ControlAction> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Empty {
+        value: u32,
+    }
+
+    unsafe impl Pod for Empty {}
+    unsafe impl Zeroable for Empty {}
+
+    // Privileged agent that intervenes on planet 1 once
+    struct Operator {
+        control: ControlHandle,
+    }
+
+    impl ThreadedAgent<16, Empty> for Operator {
+        fn step(&mut self, context: &mut PlanetContext<16, Empty>, agent_id: usize) -> Event {
+            let time = context.time;
+            let update = ControlAction::Parameter {
+                agent: Some(0),
+                key: 1,
+                value: 2.5,
+            };
+            self.control.send(1, update).unwrap();
+            let wake = ControlAction::Intervene { agent: 0, time: 20 };
+            self.control.send(1, wake).unwrap();
+            Event::new(time, time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, Empty>,
+            _msg: Msg<Empty>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    // Agent that is only ever woken by the control plane
+    struct Dormant {
+        log: Arc<Mutex<Vec<(u64, f64)>>>,
+    }
+
+    impl ThreadedAgent<16, Empty> for Dormant {
+        fn step(&mut self, context: &mut PlanetContext<16, Empty>, agent_id: usize) -> Event {
+            let time = context.time;
+            self.log.lock().unwrap().push((time, 0.0));
+            Event::new(time, time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, Empty>,
+            _msg: Msg<Empty>,
+            _agent_id: usize,
+        ) {
+        }
+
+        fn on_control(
+            &mut self,
+            _context: &mut PlanetContext<16, Empty>,
+            action: ControlAction,
+            _agent_id: usize,
+        ) {
+            if let ControlAction::Parameter { value, .. } = action {
+                self.log.lock().unwrap().push((u64::MAX, value));
+            }
+        }
+    }
+
+    #[test]
+    fn test_control_plane_delivery() {
+        let config = HybridConfig::new(2, 16)
+            .with_time_bounds(40.0, 1.0)
+            .with_optimistic_sync(1, 10)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<16, 128, 1, Empty>::create(config).unwrap();
+        let control = engine.control_handle();
+        assert_eq!(control.planets(), 2);
+        assert!(matches!(
+            control.send(2, ControlAction::Pause),
+            Err(AikaError::InvalidWorldId(2))
+        ));
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let operator = Operator {
+            control: control.clone(),
+        };
+        let dormant = Dormant {
+            log: Arc::clone(&log),
+        };
+        engine.spawn_agent(0, Box::new(operator)).unwrap();
+        engine.spawn_agent(1, Box::new(dormant)).unwrap();
+        engine.schedule(0, 0, 5).unwrap();
+        control.send(1, ControlAction::Pause).unwrap();
+        control.send(1, ControlAction::Resume).unwrap();
+        let engine = engine.run().unwrap();
+
+        // the parameter update arrives first, then the intervention wakes the agent at 20
+        assert_eq!(*log.lock().unwrap(), vec![(u64::MAX, 2.5), (20, 0.0)]);
+        let actions = engine.planets[1]
+            .control_log()
+            .iter()
+            .map(|record| record.action)
+            .collect::<Vec<_>>();
+        assert_eq!(actions.len(), 4);
+        assert_eq!(actions[0], ControlAction::Pause);
+        assert_eq!(actions[1], ControlAction::Resume);
+        assert!(engine.planets[0].control_log().is_empty());
+    }
+}
@@ -0,0 +1,405 @@
+//! Consistent cuts of a hybrid run for external audit.
+//! A cut at time `T` is registered with `HybridEngine::consistent_cut()` before the run. Every `Planet` stalls on
+//! reaching `T` until GVT does too, so nothing before `T` can still be rolled back and no mail is in transit
+//! between planets. Each `Planet` then records its agents' snapshots, the values of its shared partitions, its
+//! pending events and the mail sent before `T` that it has not yet read, and resumes. The records are hash-chained
+//! in world order into a `CutSnapshot` whose `verify()` detects any later edit. The chain starts from the schema hash
+//! of the engine's payloads, so a snapshot taken under an older schema is refused or migrated by `migrate()`.
+use std::sync::mpsc::{Receiver, Sender};
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{mt::hybrid::schema::SchemaRegistry, objects::Msg, AikaError};
+
+/// FNV-1a over little-endian words, stable across platforms and releases.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Digest(pub(crate) u64);
+
+impl Digest {
+    pub(crate) fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    pub(crate) fn bytes(mut self, bytes: &[u8]) -> Self {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+        self
+    }
+
+    pub(crate) fn word(self, word: u64) -> Self {
+        self.bytes(&word.to_le_bytes())
+    }
+}
+
+/// The state of one `Planet` at a cut.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlanetCut<MessageType: Pod + Zeroable + Clone> {
+    pub world_id: usize,
+    /// `(agent, ThreadedAgent::snapshot())` for every agent living on the `Planet`
+    pub agents: Vec<(usize, Vec<u8>)>,
+    /// the values of every shared partition of the `Planet`, as bytes, in the order they were created
+    pub partitions: Vec<Vec<u8>>,
+    /// `(agent, time)` of every pending step at or after the cut
+    pub events: Vec<(usize, u64)>,
+    /// mail sent before the cut that had not been read
+    pub in_flight: Vec<Msg<MessageType>>,
+    pub digest: u64,
+}
+
+impl<MessageType: Pod + Zeroable + Clone> PlanetCut<MessageType> {
+    pub(crate) fn new(
+        world_id: usize,
+        time: u64,
+        agents: Vec<(usize, Vec<u8>)>,
+        partitions: Vec<Vec<u8>>,
+        events: Vec<(usize, u64)>,
+        in_flight: Vec<Msg<MessageType>>,
+    ) -> Self {
+        let mut cut = Self {
+            world_id,
+            agents,
+            partitions,
+            events,
+            in_flight,
+            digest: 0,
+        };
+        cut.digest = cut.compute_digest(time);
+        cut
+    }
+
+    fn compute_digest(&self, time: u64) -> u64 {
+        let mut digest = Digest::new().word(self.world_id as u64).word(time);
+        for (agent, bytes) in &self.agents {
+            digest = digest
+                .word(*agent as u64)
+                .word(bytes.len() as u64)
+                .bytes(bytes);
+        }
+        for bytes in &self.partitions {
+            digest = digest.word(bytes.len() as u64).bytes(bytes);
+        }
+        for (agent, at) in &self.events {
+            digest = digest.word(*agent as u64).word(*at);
+        }
+        for msg in &self.in_flight {
+            digest = digest
+                .word(msg.from as u64)
+                .word(msg.to.map_or(u64::MAX, |to| to as u64))
+                .word(msg.sent)
+                .word(msg.recv)
+                .word(msg.group.map_or(u64::MAX, |group| group.0))
+                .word(msg.offset.to_bits())
+                .bytes(bytemuck::bytes_of(&msg.data));
+        }
+        digest.0
+    }
+}
+
+/// A hash-chained record of every `Planet` at time `time`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CutSnapshot<MessageType: Pod + Zeroable + Clone> {
+    pub time: u64,
+    /// `PayloadSchema::hash()` of the payloads in flight
+    pub schema: u64,
+    /// ordered by world id
+    pub planets: Vec<PlanetCut<MessageType>>,
+    /// `chain[i]` links `chain[i - 1]` (or the cut time and schema) to `planets[i].digest`
+    pub chain: Vec<u64>,
+}
+
+impl<MessageType: Pod + Zeroable + Clone> CutSnapshot<MessageType> {
+    fn new(time: u64, schema: u64, mut planets: Vec<PlanetCut<MessageType>>) -> Self {
+        planets.sort_by_key(|planet| planet.world_id);
+        let chain = Self::link(time, schema, planets.iter().map(|planet| planet.digest));
+        Self {
+            time,
+            schema,
+            planets,
+            chain,
+        }
+    }
+
+    fn link(time: u64, schema: u64, digests: impl Iterator<Item = u64>) -> Vec<u64> {
+        let mut head = Digest::new().word(time).word(schema).0;
+        digests
+            .map(|digest| {
+                head = Digest(head).word(digest).0;
+                head
+            })
+            .collect()
+    }
+
+    /// The last link of the chain, committing to the whole cut.
+    pub fn head(&self) -> u64 {
+        self.chain.last().copied().unwrap_or_default()
+    }
+
+    /// Recompute every digest and link, returning whether they all match.
+    pub fn verify(&self) -> bool {
+        let digests = self
+            .planets
+            .iter()
+            .map(|planet| planet.compute_digest(self.time))
+            .collect::<Vec<_>>();
+        self.planets
+            .iter()
+            .zip(&digests)
+            .all(|(planet, digest)| planet.digest == *digest)
+            && Self::link(self.time, self.schema, digests.into_iter()) == self.chain
+    }
+
+    /// Read the snapshot as payloads of the schema `schemas` expects, carrying the mail in flight through its
+    /// migrations if the snapshot was taken under an older schema. A migrated snapshot is chained anew, so verify the
+    /// snapshot before migrating it.
+    pub fn migrate<New: Pod + Zeroable + Clone>(
+        self,
+        schemas: &SchemaRegistry,
+    ) -> Result<CutSnapshot<New>, AikaError> {
+        let expected = schemas.expected();
+        let migration = schemas.migration_from(self.schema);
+        if migration.is_none()
+            && (self.schema != expected.hash() || expected.size != std::mem::size_of::<New>())
+        {
+            return Err(AikaError::SchemaMismatch(format!(
+                "the cut at {} holds payloads of schema {:#x}, with no migration to {expected}",
+                self.time, self.schema
+            )));
+        }
+        let mut planets = Vec::with_capacity(self.planets.len());
+        for planet in self.planets {
+            let in_flight = planet
+                .in_flight
+                .into_iter()
+                .map(|msg| {
+                    let bytes = bytemuck::bytes_of(&msg.data);
+                    let data = match &migration {
+                        Some(migration) => migration.migrate(bytes)?,
+                        None => bytemuck::pod_read_unaligned(bytes),
+                    };
+                    Ok(Msg {
+                        from: msg.from,
+                        to: msg.to,
+                        sent: msg.sent,
+                        recv: msg.recv,
+                        group: msg.group,
+                        offset: msg.offset,
+                        from_world: msg.from_world,
+                        seq: msg.seq,
+                        timer: msg.timer,
+                        rpc: msg.rpc,
+                        wake: msg.wake,
+                        id: msg.id,
+                        data,
+                    })
+                })
+                .collect::<Result<Vec<_>, AikaError>>()?;
+            planets.push(PlanetCut::new(
+                planet.world_id,
+                self.time,
+                planet.agents,
+                planet.partitions,
+                planet.events,
+                in_flight,
+            ));
+        }
+        Ok(CutSnapshot::new(self.time, expected.hash(), planets))
+    }
+}
+
+/// A registered cut, to be collected once every `Planet` has recorded it.
+pub struct PendingCut<MessageType: Pod + Zeroable + Clone> {
+    time: u64,
+    schema: u64,
+    planets: usize,
+    receiver: Receiver<PlanetCut<MessageType>>,
+}
+
+impl<MessageType: Pod + Zeroable + Clone> PendingCut<MessageType> {
+    pub(crate) fn new(
+        time: u64,
+        schema: u64,
+        planets: usize,
+    ) -> (Self, Sender<PlanetCut<MessageType>>) {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let pending = Self {
+            time,
+            schema,
+            planets,
+            receiver,
+        };
+        (pending, sender)
+    }
+
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    /// Block until every `Planet` has recorded the cut.
+    pub fn wait(self) -> Result<CutSnapshot<MessageType>, AikaError> {
+        let mut planets = Vec::with_capacity(self.planets);
+        for _ in 0..self.planets {
+            let planet = self
+                .receiver
+                .recv()
+                .map_err(|_| AikaError::CutNotTaken(self.time))?;
+            planets.push(planet);
+        }
+        Ok(CutSnapshot::new(self.time, self.schema, planets))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{
+            config::HybridConfig,
+            schema::{Migration, PayloadSchema},
+            HybridEngine,
+        },
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Tally {
+        steps: u64,
+    }
+
+    unsafe impl Pod for Tally {}
+    unsafe impl Zeroable for Tally {}
+
+    // Counts its steps in its journal and tells the other planet about each one
+    struct Counter {
+        other: usize,
+    }
+
+    impl ThreadedAgent<128, Tally> for Counter {
+        fn step(&mut self, context: &mut PlanetContext<128, Tally>, agent_id: usize) -> Event {
+            let time = context.time;
+            let journal = &mut context.agent_states[agent_id];
+            let steps = journal.read_state::<Tally>().map_or(0, |tally| tally.steps) + 1;
+            journal.write(Tally { steps }, time, None);
+            let msg = Msg::new(Tally { steps }, time, time + 3, agent_id, Some(0));
+            context.send_mail(msg, self.other).unwrap();
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Tally>,
+            _msg: Msg<Tally>,
+            _agent_id: usize,
+        ) {
+        }
+
+        fn snapshot(&self, context: &PlanetContext<128, Tally>, agent_id: usize) -> Vec<u8> {
+            let tally = context.agent_states[agent_id]
+                .read_state::<Tally>()
+                .map_or(0, |tally| tally.steps);
+            tally.to_le_bytes().to_vec()
+        }
+    }
+
+    #[test]
+    fn test_consistent_cut() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(40.0, 1.0)
+            .with_optimistic_sync(1, 10)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<128, 128, 1, Tally>::create(config).unwrap();
+        engine
+            .spawn_agent(0, Box::new(Counter { other: 1 }))
+            .unwrap();
+        engine
+            .spawn_agent(1, Box::new(Counter { other: 0 }))
+            .unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        assert!(matches!(
+            engine.consistent_cut(40),
+            Err(AikaError::ConfigError(_))
+        ));
+        let pending = engine.consistent_cut(15).unwrap();
+        let engine = engine.run().unwrap();
+        let mut cut = pending.wait().unwrap();
+
+        // both agents stepped at 1..15 exactly once, and the run went on past the cut
+        assert_eq!(cut.time, 15);
+        for (world, planet) in cut.planets.iter().enumerate() {
+            assert_eq!(planet.world_id, world);
+            assert_eq!(planet.agents, vec![(0, 14u64.to_le_bytes().to_vec())]);
+            assert_eq!(planet.events, vec![(0, 15)]);
+            assert!(!planet.in_flight.is_empty());
+            assert!(planet
+                .in_flight
+                .iter()
+                .all(|msg| msg.sent < 15 && msg.recv >= 15));
+        }
+        assert!(engine.planets.iter().all(|planet| planet.now() >= 39));
+        assert!(cut.verify());
+
+        cut.planets[1].in_flight[0].data.steps += 1;
+        assert!(!cut.verify());
+    }
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct TallyV2 {
+        steps: u64,
+        doubled: u64,
+    }
+
+    unsafe impl Pod for TallyV2 {}
+    unsafe impl Zeroable for TallyV2 {}
+
+    #[test]
+    fn test_cut_migrates_to_a_newer_schema() {
+        let v1 = PayloadSchema::of::<Tally>().with_version(1);
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(1, 10)
+            .with_uniform_worlds(16, 1, 16)
+            .with_payload_schema(SchemaRegistry::new(v1));
+        let mut engine = HybridEngine::<128, 128, 1, Tally>::create(config).unwrap();
+        for (planet, other) in [(0, 1), (1, 0)] {
+            engine
+                .spawn_agent(planet, Box::new(Counter { other }))
+                .unwrap();
+        }
+        engine.schedule_all_agents(1).unwrap();
+        let pending = engine.consistent_cut(10).unwrap();
+        engine.run().unwrap();
+        let cut = pending.wait().unwrap();
+        assert_eq!(cut.schema, v1.hash());
+
+        let v2 = PayloadSchema::of::<TallyV2>().with_version(2);
+        let unrelated = SchemaRegistry::new(v2);
+        assert!(matches!(
+            cut.clone().migrate::<TallyV2>(&unrelated),
+            Err(AikaError::SchemaMismatch(_))
+        ));
+
+        let migration = Migration::new(v1, v2, |tally: Tally| TallyV2 {
+            steps: tally.steps,
+            doubled: tally.steps * 2,
+        })
+        .unwrap();
+        let schemas = SchemaRegistry::new(v2).with_migration(migration);
+        let migrated = cut.clone().migrate::<TallyV2>(&schemas).unwrap();
+        assert_eq!(migrated.schema, v2.hash());
+        assert!(migrated.verify());
+        for (old, new) in cut.planets.iter().zip(&migrated.planets) {
+            assert_eq!(old.in_flight.len(), new.in_flight.len());
+            for (old, new) in old.in_flight.iter().zip(&new.in_flight) {
+                assert_eq!(new.data.steps, old.data.steps);
+                assert_eq!(new.data.doubled, old.data.steps * 2);
+                assert_eq!((new.sent, new.recv), (old.sent, old.recv));
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Filtered debug output from `Planet`s and the `Galaxy`.
+//! Rollbacks, GVT updates and opened mail are reported to a `DebugFilter`, which drops those from planets or of
+//! kinds it filters out. With the `log` feature, what passes goes to the `log` facade at the kind's level under its
+//! target, e.g. `aika::rollback`, prefixed with its `DebugSpan`, and the logger's level filter decides what is
+//! written; without it nothing is, so a run never prints on its own.
+use std::{collections::BTreeSet, fmt};
+
+/// What a debug message is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DebugKind {
+    Rollback,
+    Gvt,
+    Mail,
+}
+
+impl DebugKind {
+    /// `log` target messages of this kind are written under.
+    pub fn target(&self) -> &'static str {
+        match self {
+            DebugKind::Rollback => "aika::rollback",
+            DebugKind::Gvt => "aika::gvt",
+            DebugKind::Mail => "aika::mail",
+        }
+    }
+
+    /// `log` level messages of this kind are written at.
+    #[cfg(feature = "log")]
+    pub fn level(&self) -> log::Level {
+        match self {
+            DebugKind::Rollback => log::Level::Info,
+            DebugKind::Gvt => log::Level::Debug,
+            DebugKind::Mail => log::Level::Trace,
+        }
+    }
+}
+
+/// Where a debug message comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugSpan {
+    /// a `Planet` at its local time
+    Planet { world: usize, time: u64 },
+    /// the `Galaxy` at the GVT
+    Galaxy { gvt: u64 },
+}
+
+impl fmt::Display for DebugSpan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DebugSpan::Planet { world, time } => write!(f, "planet {world} @ {time}"),
+            DebugSpan::Galaxy { gvt } => write!(f, "galaxy @ {gvt}"),
+        }
+    }
+}
+
+/// Which debug messages are reported.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugFilter {
+    /// planets reported on, all if `None`. The `Galaxy` is always reported on.
+    planets: Option<BTreeSet<usize>>,
+    /// kinds reported, all if `None`
+    kinds: Option<BTreeSet<DebugKind>>,
+}
+
+impl DebugFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report only on `planets`.
+    pub fn with_planets(mut self, planets: impl IntoIterator<Item = usize>) -> Self {
+        self.planets = Some(planets.into_iter().collect());
+        self
+    }
+
+    /// Report only `kinds`.
+    pub fn with_kinds(mut self, kinds: impl IntoIterator<Item = DebugKind>) -> Self {
+        self.kinds = Some(kinds.into_iter().collect());
+        self
+    }
+
+    /// Whether a message of `kind` from `span` is reported.
+    pub fn allows(&self, span: DebugSpan, kind: DebugKind) -> bool {
+        if let (DebugSpan::Planet { world, .. }, Some(planets)) = (span, &self.planets) {
+            if !planets.contains(&world) {
+                return false;
+            }
+        }
+        self.kinds
+            .as_ref()
+            .is_none_or(|kinds| kinds.contains(&kind))
+    }
+
+    /// Report the message built by `message` if the filter and the logger allow it. The message is only built if it
+    /// is written.
+    pub(crate) fn emit(&self, span: DebugSpan, kind: DebugKind, message: impl FnOnce() -> String) {
+        if !self.allows(span, kind) {
+            return;
+        }
+        #[cfg(feature = "log")]
+        if log::log_enabled!(target: kind.target(), kind.level()) {
+            log::log!(target: kind.target(), kind.level(), "{span}: {}", message());
+        }
+        #[cfg(not(feature = "log"))]
+        let _ = message;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filters() {
+        let planet = |world| DebugSpan::Planet { world, time: 3 };
+        let galaxy = DebugSpan::Galaxy { gvt: 2 };
+        assert!(DebugFilter::default().allows(planet(0), DebugKind::Rollback));
+
+        let filter = DebugFilter::new().with_planets([1]);
+        assert!(filter.allows(planet(1), DebugKind::Rollback));
+        assert!(filter.allows(galaxy, DebugKind::Gvt));
+        assert!(!filter.allows(planet(0), DebugKind::Rollback));
+
+        let filter = DebugFilter::new().with_kinds([DebugKind::Mail]);
+        assert!(filter.allows(planet(0), DebugKind::Mail));
+        assert!(!filter.allows(galaxy, DebugKind::Gvt));
+        assert_eq!(planet(4).to_string(), "planet 4 @ 3");
+    }
+}
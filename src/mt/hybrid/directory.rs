@@ -0,0 +1,245 @@
+//! The run-wide directory of agent attributes, for peer discovery across planets, see `agents::discovery`.
+//! Agents publish attributes with `PlanetContext::publish()`. Each `Planet` hands its publications to a per-world
+//! slot before publishing the local time they were made at, and drops them again when it rolls back past them. At
+//! every GVT checkpoint the `Galaxy` applies the publications made before the checkpoint to the committed attributes,
+//! in the order each `Planet` made them, which no rollback can touch anymore.
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    agents::discovery::{Attributes, Peer, Query},
+    mt::hybrid::routing::RoutingTable,
+    AikaError,
+};
+
+/// An attribute published, or withdrawn if it has no value, by `(world, agent)` at local time `time`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Publication {
+    pub(crate) time: u64,
+    pub(crate) world: usize,
+    pub(crate) agent: usize,
+    pub(crate) key: usize,
+    pub(crate) value: Option<u64>,
+}
+
+/// Publications and committed attributes shared by the `Galaxy` and every `Planet` of a run.
+#[derive(Debug)]
+pub(crate) struct Directory {
+    /// uncommitted publications, per world
+    slots: Vec<Mutex<Vec<Publication>>>,
+    committed: Mutex<Attributes>,
+}
+
+impl Directory {
+    pub(crate) fn new(worlds: usize) -> Self {
+        Self {
+            slots: (0..worlds).map(|_| Mutex::new(Vec::new())).collect(),
+            committed: Mutex::new(Attributes::default()),
+        }
+    }
+
+    fn slot(&self, world: usize) -> Result<std::sync::MutexGuard<'_, Vec<Publication>>, AikaError> {
+        self.slots
+            .get(world)
+            .ok_or(AikaError::InvalidWorldId(world))?
+            .lock()
+            .map_err(|_| AikaError::PoisonedLock)
+    }
+
+    /// Hand over the publications `world` made since it last did.
+    pub(crate) fn publish(
+        &self,
+        world: usize,
+        publications: Vec<Publication>,
+    ) -> Result<(), AikaError> {
+        self.slot(world)?.extend(publications);
+        Ok(())
+    }
+
+    /// Drop the publications `world` made at or after `time`.
+    pub(crate) fn rollback(&self, world: usize, time: u64) -> Result<(), AikaError> {
+        self.slot(world)?
+            .retain(|publication| publication.time < time);
+        Ok(())
+    }
+
+    /// Apply every publication made before `until` to the committed attributes, world by world.
+    pub(crate) fn commit(&self, until: u64) -> Result<(), AikaError> {
+        let mut committed = self.committed.lock().map_err(|_| AikaError::PoisonedLock)?;
+        for world in 0..self.slots.len() {
+            let mut slot = self.slot(world)?;
+            let (done, pending) = std::mem::take(&mut *slot)
+                .into_iter()
+                .partition::<Vec<_>, _>(|publication| publication.time < until);
+            *slot = pending;
+            for publication in done {
+                let Publication {
+                    world, agent, key, ..
+                } = publication;
+                match publication.value {
+                    Some(value) => committed.set(world, agent, key, value),
+                    None => committed.remove(world, agent, key),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The committed peers matching `query`, at their current address if `routes` are given.
+    pub(crate) fn peers(
+        &self,
+        query: &Query,
+        routes: Option<&Arc<Mutex<RoutingTable>>>,
+    ) -> Vec<Peer> {
+        let Ok(committed) = self.committed.lock() else {
+            return Vec::new();
+        };
+        let mut peers = committed.peers(query);
+        drop(committed);
+        if let Some(routes) = routes.and_then(|routes| routes.lock().ok()) {
+            for peer in &mut peers {
+                (peer.world, peer.agent) = routes.resolve(peer.world, peer.agent);
+            }
+            peers.sort();
+            peers.dedup_by_key(|peer| (peer.world, peer.agent));
+        }
+        peers
+    }
+
+    /// Every committed attribute.
+    pub(crate) fn snapshot(&self) -> Attributes {
+        self.committed
+            .lock()
+            .map(|committed| committed.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        agents::{rpc::RequestId, PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Ping {
+        value: u64,
+    }
+
+    unsafe impl Pod for Ping {}
+    unsafe impl Zeroable for Ping {}
+
+    const SERVICE: usize = 3;
+
+    // Offers the service at price 7 from its first step on
+    struct Provider;
+
+    impl ThreadedAgent<128, Ping> for Provider {
+        fn step(&mut self, context: &mut PlanetContext<128, Ping>, agent_id: usize) -> Event {
+            context.publish(agent_id, SERVICE, 7);
+            Event::new(context.time, context.time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Ping>,
+            _msg: Msg<Ping>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    type Found = Arc<Mutex<Vec<(u64, Vec<Peer>)>>>;
+
+    // Looks for providers of the service at step 10, noting when and whom it finds
+    struct Customer {
+        found: Found,
+    }
+
+    impl ThreadedAgent<128, Ping> for Customer {
+        fn step(&mut self, context: &mut PlanetContext<128, Ping>, agent_id: usize) -> Event {
+            context.discover(agent_id, Query::new(SERVICE));
+            context.discover(agent_id, Query::new(SERVICE).with_value(8));
+            Event::new(context.time, context.time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Ping>,
+            _msg: Msg<Ping>,
+            _agent_id: usize,
+        ) {
+        }
+
+        fn on_peers(
+            &mut self,
+            context: &mut PlanetContext<128, Ping>,
+            _id: RequestId,
+            _query: Query,
+            peers: Vec<Peer>,
+            _agent_id: usize,
+        ) {
+            self.found.lock().unwrap().push((context.time, peers));
+        }
+    }
+
+    #[test]
+    fn test_publications_commit_at_checkpoints() {
+        let directory = Directory::new(2);
+        let publication = |time, value| Publication {
+            time,
+            world: 1,
+            agent: 0,
+            key: SERVICE,
+            value,
+        };
+        directory
+            .publish(1, vec![publication(2, Some(5)), publication(6, None)])
+            .unwrap();
+        directory.commit(4).unwrap();
+        assert_eq!(directory.peers(&Query::new(SERVICE), None).len(), 1);
+
+        // the withdrawal is rolled back before it commits
+        directory.rollback(1, 5).unwrap();
+        directory.commit(8).unwrap();
+        assert_eq!(directory.snapshot().len(), 1);
+    }
+
+    #[test]
+    fn test_peers_are_found_across_planets() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(16.0, 1.0)
+            .with_optimistic_sync(2, 4)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<128, 128, 1, Ping>::create(config).unwrap();
+        let found = Found::default();
+        let customer = Customer {
+            found: found.clone(),
+        };
+        engine.spawn_agent(0, Box::new(customer)).unwrap();
+        engine.spawn_agent(1, Box::new(Provider)).unwrap();
+        engine.schedule(0, 0, 10).unwrap();
+        engine.schedule(1, 0, 1).unwrap();
+        let engine = engine.run().unwrap();
+
+        // the publication at step 1 was committed at the checkpoint at step 8
+        let provider = Peer {
+            world: 1,
+            agent: 0,
+            value: 7,
+        };
+        let mut found = found.lock().unwrap().clone();
+        found.sort();
+        found.dedup();
+        assert_eq!(found, vec![(11, vec![]), (11, vec![provider])]);
+        assert_eq!(engine.peers(&Query::new(SERVICE)), vec![provider]);
+        assert_eq!(engine.attributes().len(), 1);
+    }
+}
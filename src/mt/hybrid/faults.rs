@@ -0,0 +1,244 @@
+//! Seeded fault injection on interplanetary mail, for testing how a model copes with an unreliable network.
+//! With `FaultInjection` every `Planet` drops, duplicates or delays the mail it sends to other planets, each with its
+//! own probability. Whether a letter is hit is a function of the seed and the letter's sender, send time, sequence
+//! number and destination only, so a run is reproducible and the re-execution after a rollback makes the same
+//! decisions. A delayed letter is sent with a later receive time, and a duplicate shares the anti-message of its
+//! original, so a rollback cancels both. The counts of each fault are journaled by local time like link queues,
+//! and reported in `PlanetStats::faults`.
+use crate::objects::seeded_rank;
+
+/// Probabilities of each fault, see `HybridConfig::with_faults()`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FaultInjection {
+    pub seed: u64,
+    /// chance a letter is lost
+    pub drop: f64,
+    /// chance a letter that isn't lost arrives twice
+    pub duplicate: f64,
+    /// chance a letter that isn't lost arrives late
+    pub delay: f64,
+    /// most steps a late letter is held back, at least 1
+    pub max_delay: u64,
+}
+
+impl FaultInjection {
+    /// No faults yet, drawn from `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            drop: 0.0,
+            duplicate: 0.0,
+            delay: 0.0,
+            max_delay: 1,
+        }
+    }
+
+    /// Lose each letter with probability `probability`.
+    pub fn with_drop(mut self, probability: f64) -> Self {
+        self.drop = probability;
+        self
+    }
+
+    /// Deliver each letter twice with probability `probability`.
+    pub fn with_duplicate(mut self, probability: f64) -> Self {
+        self.duplicate = probability;
+        self
+    }
+
+    /// Hold each letter back by 1 to `max_delay` steps with probability `probability`.
+    pub fn with_delay(mut self, probability: f64, max_delay: u64) -> Self {
+        self.delay = probability;
+        self.max_delay = max_delay.max(1);
+        self
+    }
+
+    /// Name of the first probability outside `[0, 1]`, if any.
+    pub(crate) fn invalid(&self) -> Option<&'static str> {
+        [
+            ("drop", self.drop),
+            ("duplicate", self.duplicate),
+            ("delay", self.delay),
+        ]
+        .into_iter()
+        .find(|(_, p)| !(0.0..=1.0).contains(p))
+        .map(|(name, _)| name)
+    }
+}
+
+/// Faults injected into the mail of one `Planet`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FaultStats {
+    pub dropped: u64,
+    pub duplicated: u64,
+    pub delayed: u64,
+    /// steps added to receive times by delays
+    pub total_delay: u64,
+}
+
+/// What happens to one letter.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Fault {
+    Deliver { copies: usize, recv: u64 },
+    Drop,
+}
+
+/// The identity of a letter, which decides its fate.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Letter {
+    pub(crate) world: usize,
+    pub(crate) from: usize,
+    pub(crate) sent: u64,
+    pub(crate) seq: u32,
+    pub(crate) to_world: usize,
+}
+
+/// Draws the faults of one `Planet`, journaling their counts by local time.
+#[derive(Clone, Debug)]
+pub(crate) struct FaultInjector {
+    params: FaultInjection,
+    /// `(local time, counts so far)` after every letter that was hit
+    history: Vec<(u64, FaultStats)>,
+}
+
+impl FaultInjector {
+    pub(crate) fn new(params: FaultInjection) -> Self {
+        Self {
+            params,
+            history: Vec::new(),
+        }
+    }
+
+    pub(crate) fn params(&self) -> FaultInjection {
+        self.params
+    }
+
+    /// Decide the fate of `letter`, due at `recv`, sent at local time `now`.
+    pub(crate) fn inject(&mut self, letter: Letter, recv: u64, now: u64) -> Fault {
+        let key = [
+            letter.world as u64,
+            letter.from as u64,
+            letter.sent,
+            letter.seq as u64,
+            letter.to_world as u64,
+        ]
+        .into_iter()
+        .fold(self.params.seed, seeded_rank);
+        let draw = |stream: u64| (seeded_rank(key, stream) >> 11) as f64 / (1u64 << 53) as f64;
+        let mut counts = self
+            .history
+            .last()
+            .map(|(_, counts)| *counts)
+            .unwrap_or_default();
+        let fault = if draw(0) < self.params.drop {
+            counts.dropped += 1;
+            Fault::Drop
+        } else {
+            let copies = if draw(1) < self.params.duplicate {
+                counts.duplicated += 1;
+                2
+            } else {
+                1
+            };
+            let delay = if draw(2) < self.params.delay {
+                1 + seeded_rank(key, 3) % self.params.max_delay
+            } else {
+                0
+            };
+            if delay > 0 {
+                counts.delayed += 1;
+                counts.total_delay += delay;
+            }
+            Fault::Deliver {
+                copies,
+                recv: recv.saturating_add(delay),
+            }
+        };
+        if fault != (Fault::Deliver { copies: 1, recv }) {
+            self.history.push((now, counts));
+        }
+        fault
+    }
+
+    /// Forget the faults injected at or after `time`.
+    pub(crate) fn rollback(&mut self, time: u64) {
+        let keep = self.history.partition_point(|(at, _)| *at < time);
+        self.history.truncate(keep);
+    }
+
+    /// Drop the entries before `gvt`, which no rollback can reach, but the last one, which the counts go on from.
+    pub(crate) fn prune(&mut self, gvt: u64) {
+        let settled = self.history.partition_point(|(at, _)| *at < gvt);
+        self.history.drain(..settled.saturating_sub(1));
+    }
+
+    pub(crate) fn stats(&self) -> FaultStats {
+        self.history
+            .last()
+            .map(|(_, counts)| *counts)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn letter(seq: u32) -> Letter {
+        Letter {
+            world: 1,
+            from: 0,
+            sent: 5,
+            seq,
+            to_world: 2,
+        }
+    }
+
+    #[test]
+    fn test_faults_follow_the_seed() {
+        let params = FaultInjection::new(11)
+            .with_drop(0.2)
+            .with_duplicate(0.1)
+            .with_delay(0.3, 4);
+        let mut injector = FaultInjector::new(params);
+        let faults = (0..10_000)
+            .map(|seq| injector.inject(letter(seq), 8, 5))
+            .collect::<Vec<_>>();
+        let stats = injector.stats();
+        let rate = |count: u64| count as f64 / 10_000.0;
+        assert!((rate(stats.dropped) - 0.2).abs() < 0.02);
+        assert!((rate(stats.duplicated) - 0.08).abs() < 0.02);
+        assert!((rate(stats.delayed) - 0.24).abs() < 0.02);
+        assert!(faults.iter().all(|fault| match fault {
+            Fault::Deliver { recv, .. } => (8..=12).contains(recv),
+            Fault::Drop => true,
+        }));
+
+        // another injector with the same seed hits the same letters, another seed doesn't
+        let mut again = FaultInjector::new(params);
+        assert!((0..10_000).all(|seq| again.inject(letter(seq), 8, 5) == faults[seq as usize]));
+        let mut other = FaultInjector::new(FaultInjection { seed: 12, ..params });
+        assert!((0..100).any(|seq| other.inject(letter(seq), 8, 5) != faults[seq as usize]));
+
+        assert_eq!(
+            FaultInjection::new(0).with_delay(1.5, 2).invalid(),
+            Some("delay")
+        );
+        assert_eq!(params.invalid(), None);
+    }
+
+    #[test]
+    fn test_counts_follow_rollback_and_prune() {
+        let mut injector = FaultInjector::new(FaultInjection::new(0).with_drop(1.0));
+        for now in 0..10 {
+            assert_eq!(injector.inject(letter(0), now + 2, now), Fault::Drop);
+        }
+        injector.prune(6);
+        assert_eq!(injector.stats().dropped, 10);
+        injector.rollback(8);
+        assert_eq!(injector.stats().dropped, 8);
+        injector.rollback(6);
+        assert_eq!(injector.stats().dropped, 6);
+        injector.inject(letter(1), 8, 6);
+        assert_eq!(injector.stats().dropped, 7);
+    }
+}
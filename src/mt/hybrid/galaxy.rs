@@ -1,15 +1,107 @@
 //! Central coordinator managing global virtual time (GVT) and checkpointing across planets.
 //! The `Galaxy` handles inter-planetary message delivery, GVT calculation, and throttling to
 //! maintain causality constraints in the optimistic parallel simulation.
-use std::sync::{
-    atomic::{fence, AtomicU64, AtomicUsize, Ordering},
-    Arc,
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{fence, AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
 };
 
 use bytemuck::{Pod, Zeroable};
-use mesocarp::{comms::mailbox::ThreadedMessenger, scheduling::Scheduleable, MesoError};
+use mesocarp::{comms::mailbox::ThreadedMessenger, MesoError};
 
-use crate::{mt::hybrid::planet::RegistryOutput, objects::Mail, st::TimeInfo, AikaError};
+#[cfg(feature = "validate-causality")]
+use crate::mt::hybrid::vclock::Incarnations;
+use crate::{
+    hooks::SimHook,
+    mailbox::{Backlog, OverflowPolicy},
+    mt::hybrid::{
+        affinity,
+        backoff::{Backoff, GalaxyStats, Wakeup},
+        barrier::Barriers,
+        blocks::BlockLedger,
+        bridge::Exchange,
+        cancel::EngineHandle,
+        config::AutoScaling,
+        debug::{DebugFilter, DebugKind, DebugSpan},
+        directory::Directory,
+        panics,
+        planet::RegistryOutput,
+        reduce::Reductions,
+        routing::{AgentHandle, RoutingTable},
+        schema::{PayloadSchema, SchemaRegistry},
+        shared::SharedData,
+        topology::Topology,
+    },
+    objects::{DeliveryFailure, Mail, MailBundle, Transfer},
+    st::TimeInfo,
+    tracing::TraceRecorder,
+    AikaError,
+};
+
+/// Local times and delivery failures of a run's planets, in world order.
+pub(crate) type WorldHandles<MessageType> = (
+    Vec<Arc<AtomicU64>>,
+    Vec<Arc<Mutex<Vec<DeliveryFailure<MessageType>>>>>,
+);
+
+/// Where the threads of a run stop for `HybridEngine::advance()`. Every `Planet` stops stepping at the horizon, and
+/// once GVT reaches it the `Galaxy` releases them all to hand themselves back to the engine. A cancelled run is
+/// parked at the next checkpoint instead, see `EngineHandle`.
+#[derive(Debug)]
+pub(crate) struct Parking {
+    horizon: AtomicU64,
+    released: AtomicBool,
+    cancelled: AtomicBool,
+}
+
+impl Default for Parking {
+    fn default() -> Self {
+        Self {
+            horizon: AtomicU64::new(u64::MAX),
+            released: AtomicBool::new(false),
+            cancelled: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Parking {
+    /// Park the next round of the run at `horizon`, `u64::MAX` running it to the end.
+    pub(crate) fn park_at(&self, horizon: u64) {
+        self.horizon.store(horizon, Ordering::SeqCst);
+        self.released.store(false, Ordering::SeqCst);
+    }
+
+    pub(crate) fn horizon(&self) -> u64 {
+        self.horizon.load(Ordering::SeqCst)
+    }
+
+    fn release(&self) {
+        self.released.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the threads of this round were released at the horizon.
+    pub(crate) fn released(&self) -> bool {
+        self.released.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub(crate) fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Bring the horizon forward to `checkpoint`, which no `Planet` has passed.
+    fn park_at_checkpoint(&self, checkpoint: u64) {
+        self.horizon.fetch_min(checkpoint, Ordering::SeqCst);
+    }
+}
 
 /// A `Galaxy` updates the global synchronization checkpoint and handles interplanetary message passing.
 pub struct Galaxy<
@@ -18,7 +110,7 @@ pub struct Galaxy<
     const CLOCK_HEIGHT: usize,
     MessageType: Pod + Zeroable + Clone,
 > {
-    pub messenger: ThreadedMessenger<INTER_SLOTS, Mail<MessageType>>,
+    pub messenger: ThreadedMessenger<INTER_SLOTS, MailBundle<MessageType>>,
     pub lvts: Vec<Arc<AtomicU64>>,
     pub gvt: Arc<AtomicU64>,
     pub counter: Arc<AtomicUsize>,
@@ -27,6 +119,58 @@ pub struct Galaxy<
     pub throttle_horizon: u64,
     pub registered: usize,
     time_info: TimeInfo,
+    active: Vec<Arc<AtomicBool>>,
+    agent_counts: Vec<Arc<AtomicUsize>>,
+    split_requests: Vec<Arc<AtomicUsize>>,
+    scaling: Option<AutoScaling>,
+    spare_worlds: Vec<usize>,
+    lag_sums: Vec<u64>,
+    lag_samples: u64,
+    /// mail that couldn't be delivered, reported back to each sending planet by world id
+    failures: Vec<Arc<Mutex<Vec<DeliveryFailure<MessageType>>>>>,
+    /// anti-messages waiting for room in their destination's inbox, as `(inbox, bundle)`
+    held: Vec<(usize, MailBundle<MessageType>)>,
+    /// bundles waiting for room in their destination's inbox under the overflow policy
+    backlog: Backlog<MailBundle<MessageType>>,
+    hook: Option<Box<dyn SimHook>>,
+    debug: DebugFilter,
+    schemas: SchemaRegistry,
+    event_counts: Vec<Arc<AtomicU64>>,
+    /// the quiet word of every planet, see `Planet::set_end_when_quiescent()`
+    quiet: Vec<Arc<AtomicU64>>,
+    end_when_quiescent: bool,
+    migrate_requests: Vec<Arc<AtomicUsize>>,
+    migration: Option<(f64, Arc<AtomicUsize>)>,
+    /// the checkpoint splits and migrations were last planned for
+    planned: Option<u64>,
+    routes: Arc<Mutex<RoutingTable>>,
+    /// routes between planets, re-wired at checkpoints
+    topology: Option<Arc<Topology>>,
+    /// global aggregates, committed at checkpoints
+    reductions: Option<Arc<Reductions>>,
+    /// attributes published for peer discovery, committed at checkpoints
+    directory: Option<Arc<Directory>>,
+    /// barriers requested by agents, released once every planet waits on them
+    barriers: Option<Arc<Barriers>>,
+    /// mail to and from the planets of a bridged engine, whose world slots are registered last
+    exchange: Option<Arc<Exchange<MessageType>>>,
+    /// read-only data handed to every spawned `Planet`
+    shared: Option<SharedData>,
+    trace: Option<TraceRecorder>,
+    /// set by whichever thread of the run fails first, stopping the others
+    halt: Arc<AtomicBool>,
+    /// rung by the `Planet`s to wake this thread when it is parked
+    wakeup: Arc<Wakeup>,
+    /// where every thread of the run stops, see `HybridEngine::advance()`
+    parking: Arc<Parking>,
+    /// mail delivered to each planet, block by block
+    blocks: BlockLedger,
+    backoff: Backoff,
+    /// core this thread pins itself to when the run starts
+    core: Option<usize>,
+    /// rollbacks of every planet, for validating causality
+    #[cfg(feature = "validate-causality")]
+    incarnations: Arc<Incarnations>,
 }
 
 impl<
@@ -57,11 +201,116 @@ impl<
             next_checkpoint: Arc::new(AtomicU64::new(checkpoint_frequency)),
             checkpoint_frequency,
             throttle_horizon,
-            time_info: TimeInfo { timestep, terminal },
+            time_info: TimeInfo::new(terminal, timestep),
             registered: 0,
+            active: Vec::new(),
+            agent_counts: Vec::new(),
+            split_requests: Vec::new(),
+            scaling: None,
+            spare_worlds: Vec::new(),
+            lag_sums: Vec::new(),
+            lag_samples: 0,
+            failures: Vec::new(),
+            held: Vec::new(),
+            backlog: Backlog::new(OverflowPolicy::DropNewest, INTER_SLOTS),
+            hook: None,
+            debug: DebugFilter::default(),
+            schemas: SchemaRegistry::new(PayloadSchema::of::<MessageType>()),
+            event_counts: Vec::new(),
+            quiet: Vec::new(),
+            end_when_quiescent: false,
+            migrate_requests: Vec::new(),
+            migration: None,
+            planned: None,
+            routes: Arc::new(Mutex::new(RoutingTable::default())),
+            topology: None,
+            shared: None,
+            reductions: None,
+            directory: None,
+            barriers: None,
+            exchange: None,
+            trace: None,
+            halt: Arc::new(AtomicBool::new(false)),
+            wakeup: Arc::new(Wakeup::default()),
+            parking: Arc::default(),
+            blocks: BlockLedger::new(checkpoint_frequency),
+            backoff: Backoff::default(),
+            core: None,
+            #[cfg(feature = "validate-causality")]
+            incarnations: Arc::default(),
         })
     }
 
+    /// Pin the thread running `gvt_daemon()` to `core`.
+    pub(crate) fn pin_to(&mut self, core: usize) {
+        self.core = Some(core);
+    }
+
+    /// Set what happens to a bundle for a full inbox, `OverflowPolicy::DropNewest` by default, which hands its
+    /// letters back to their senders.
+    pub(crate) fn set_mailbox_overflow(&mut self, policy: OverflowPolicy) {
+        self.backlog.set_policy(policy);
+    }
+
+    /// Register a `SimHook` called on every GVT advance.
+    pub fn set_hook(&mut self, hook: Box<dyn SimHook>) {
+        self.hook = Some(hook);
+    }
+
+    /// Report GVT updates as `filter` allows.
+    pub(crate) fn set_debug(&mut self, filter: DebugFilter) {
+        self.debug = filter;
+    }
+
+    /// Share `data` read-only with every `Planet` spawned from now on, through `PlanetContext::shared_data()`.
+    pub fn with_shared_data<T: Any + Send + Sync>(mut self, data: Arc<T>) -> Self {
+        self.shared = Some(SharedData::new(data));
+        self
+    }
+
+    /// Commit the contributions to `reductions` at every checkpoint.
+    pub(crate) fn set_reductions(&mut self, reductions: Arc<Reductions>) {
+        self.reductions = Some(reductions);
+    }
+
+    /// Commit the publications to `directory` at every checkpoint.
+    pub(crate) fn set_directory(&mut self, directory: Arc<Directory>) {
+        self.directory = Some(directory);
+    }
+
+    /// Release the barriers in `barriers` once every planet waits on them.
+    pub(crate) fn set_barriers(&mut self, barriers: Arc<Barriers>) {
+        self.barriers = Some(barriers);
+    }
+
+    /// Apply the route changes queued in `topology` at every checkpoint.
+    pub(crate) fn set_topology(&mut self, topology: Arc<Topology>) {
+        self.topology = Some(topology);
+    }
+
+    /// Record GVT and checkpoint releases on a timeline.
+    pub(crate) fn enable_tracing(&mut self, recorder: TraceRecorder) {
+        self.trace = Some(recorder);
+    }
+
+    /// The timeline recorded so far, if tracing is enabled.
+    pub fn trace(&self) -> Option<&TraceRecorder> {
+        self.trace.as_ref()
+    }
+
+    /// Reserve messenger slots for planets that are split off at runtime. Must be called before any world is spawned.
+    pub fn enable_auto_scaling(&mut self, scaling: AutoScaling) -> Result<(), AikaError> {
+        if self.registered != 0 {
+            return Err(AikaError::ConfigError(
+                "Auto-scaling must be enabled before any planet is registered".to_string(),
+            ));
+        }
+        let total = self.messenger.agents().len() + scaling.spare_planets;
+        self.messenger = ThreadedMessenger::new((0..total).collect())?;
+        self.scaling = Some(scaling);
+        Ok(())
+    }
+
     pub fn spawn_world(&mut self) -> Result<RegistryOutput<INTER_SLOTS, MessageType>, AikaError> {
         let arc = Arc::clone(&self.gvt);
 
@@ -72,6 +321,7 @@ impl<
 
         let user = self.messenger.get_user(self.registered)?;
         let world_id = self.registered;
+        self.schemas.register(world_id, self.schemas.expected())?;
         self.registered += 1;
         let output = RegistryOutput::new(
             arc,
@@ -80,32 +330,223 @@ impl<
             Arc::clone(&self.next_checkpoint),
             user,
             world_id,
-        );
+        )
+        .with_halt(Arc::clone(&self.halt))
+        .with_wakeup(Arc::clone(&self.wakeup))
+        .with_parking(Arc::clone(&self.parking))
+        .with_shared_data(self.shared.clone());
+        #[cfg(feature = "validate-causality")]
+        let output = output.with_incarnations(Arc::clone(&self.incarnations));
+        self.active.push(output.active_handle());
+        self.agent_counts.push(output.agent_count_handle());
+        self.split_requests.push(output.split_request_handle());
+        self.event_counts.push(output.events_handle());
+        self.quiet.push(output.quiet_handle());
+        self.migrate_requests.push(output.migrate_request_handle());
+        self.failures.push(output.failures_handle());
+        self.lag_sums.push(0);
         Ok(output)
     }
 
-    fn deliver_the_mail(&mut self) -> Result<u64, AikaError> {
+    /// Reserve messenger slots for the planets of a bridged engine. Must be called before any world is spawned.
+    pub(crate) fn enable_remote_worlds(&mut self, count: usize) -> Result<(), AikaError> {
+        if self.registered != 0 {
+            return Err(AikaError::ConfigError(
+                "Remote worlds must be reserved before any planet is registered".to_string(),
+            ));
+        }
+        let first = self.messenger.agents().len();
+        self.messenger = ThreadedMessenger::new((0..first + count).collect())?;
+        self.exchange = Some(Arc::new(Exchange::new(first)));
+        Ok(())
+    }
+
+    pub(crate) fn exchange(&self) -> Option<Arc<Exchange<MessageType>>> {
+        self.exchange.clone()
+    }
+
+    /// The local times and delivery failures of the first `count` worlds, for a `RemotePlanet` reporting their floor.
+    pub(crate) fn planet_handles(&self, count: usize) -> WorldHandles<MessageType> {
+        (
+            self.lvts.iter().take(count).cloned().collect(),
+            self.failures.iter().take(count).cloned().collect(),
+        )
+    }
+
+    /// Register a reserved world that stays dormant, excluded from GVT, until a `Planet` is split into it.
+    pub fn spawn_spare_world(
+        &mut self,
+    ) -> Result<RegistryOutput<INTER_SLOTS, MessageType>, AikaError> {
+        let output = self.spawn_world()?;
+        output.active_handle().store(false, Ordering::Release);
+        self.spare_worlds.push(output.world_id());
+        Ok(output)
+    }
+
+    /// Move agents from the busiest to the idlest planet at checkpoints, see `HybridConfig::with_agent_migration()`.
+    /// `in_progress` counts migrations that haven't landed yet, and holds the checkpoint until they have.
+    pub(crate) fn enable_migration(&mut self, imbalance: f64, in_progress: Arc<AtomicUsize>) {
+        self.migration = Some((imbalance, in_progress));
+    }
+
+    /// Allocate an `AgentHandle` for an agent spawned at `(world, agent)`.
+    pub fn register_agent(&self, world: usize, agent: usize) -> Result<AgentHandle, AikaError> {
+        let mut routes = self.routes.lock().map_err(|_| AikaError::PoisonedLock)?;
+        Ok(routes.register(world, agent))
+    }
+
+    /// A snapshot of where every agent lives.
+    pub fn routing_table(&self) -> RoutingTable {
+        self.routes
+            .lock()
+            .map(|routes| routes.clone())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn routes(&self) -> Arc<Mutex<RoutingTable>> {
+        Arc::clone(&self.routes)
+    }
+
+    /// Expect the schema of `schemas`, accepting its migrations. It must describe the layout of `MessageType`, under
+    /// any name and version, and be set before any world is spawned.
+    pub fn set_schemas(&mut self, schemas: SchemaRegistry) -> Result<(), AikaError> {
+        let (expected, actual) = (schemas.expected(), PayloadSchema::of::<MessageType>());
+        if (expected.size, expected.align) != (actual.size, actual.align) {
+            return Err(AikaError::SchemaMismatch(format!(
+                "the engine's payload is {actual}, not {expected}"
+            )));
+        }
+        if self.registered > 0 {
+            return Err(AikaError::ConfigError(
+                "the payload schema can't change once worlds are spawned".to_string(),
+            ));
+        }
+        self.schemas = schemas;
+        Ok(())
+    }
+
+    /// Check the payload schema presented by a remote peer for `world_id` before exchanging any `Mail`.
+    pub fn handshake(&mut self, world_id: usize, schema: PayloadSchema) -> Result<(), AikaError> {
+        self.schemas.register(world_id, schema)
+    }
+
+    /// Payload schemas registered so far.
+    pub fn schemas(&self) -> &SchemaRegistry {
+        &self.schemas
+    }
+
+    /// Route the mail polled from the planets and the exchange, fanning broadcasts out with a copy for every planet.
+    /// Returns the earliest commit time routed.
+    pub(crate) fn deliver_the_mail(&mut self) -> Result<u64, AikaError> {
         fence(Ordering::SeqCst);
+        let mut msgs = self.backlog.take();
+        msgs.append(&mut self.held);
         match self.messenger.poll() {
-            Ok(msgs) => {
-                let mut lowest = u64::MAX;
-                for (_, mail) in &msgs {
-                    let time = mail.transfer.commit_time();
-                    if time < lowest {
-                        lowest = time;
-                    }
-                }
-                self.messenger.deliver(msgs)?;
-                Ok(lowest)
-            }
-            Err(err) => {
-                if let MesoError::NoDirectCommsToShare = err {
-                    Ok(u64::MAX)
-                } else {
-                    Err(AikaError::MesoError(err))
+            Ok(polled) => msgs.extend(polled),
+            Err(MesoError::NoDirectCommsToShare) => {}
+            Err(err) => return Err(AikaError::MesoError(err)),
+        }
+        if let Some(exchange) = &self.exchange {
+            msgs.extend(exchange.take_incoming()?);
+        }
+        let mut lowest = u64::MAX;
+        for (idx, bundle) in msgs {
+            lowest = lowest.min(bundle.commit_time());
+            if bundle.to_world.is_some() {
+                self.route(idx, bundle)?;
+                continue;
+            }
+            // a broadcast was counted once by its sender, and each copy is counted off by the planet it reaches
+            let planets = self.messenger.agents().len();
+            self.counter
+                .fetch_add((planets - 1) * bundle.in_flight(), Ordering::SeqCst);
+            for planet in 0..planets {
+                let copy = MailBundle {
+                    to_world: Some(planet),
+                    ..bundle.clone()
+                };
+                self.route(planet, copy)?;
+            }
+        }
+        Ok(lowest)
+    }
+
+    /// Deliver a bundle to inbox `idx`, or hand it to the exchange if that planet is remote. A bundle for a full inbox,
+    /// or for one with bundles already held back, is dealt with by the overflow policy.
+    fn route(&mut self, idx: usize, bundle: MailBundle<MessageType>) -> Result<(), AikaError> {
+        if let Some(exchange) = self
+            .exchange
+            .as_ref()
+            .filter(|exchange| exchange.is_remote(idx))
+        {
+            return exchange.route(idx, bundle);
+        }
+        if !self.backlog.holds(idx) {
+            match self.messenger.deliver(vec![(idx, bundle.clone())]) {
+                Ok(()) => {
+                    let lvt = self.lvts[idx].load(Ordering::Acquire);
+                    self.blocks.record(&bundle, idx, lvt);
+                    return Ok(());
                 }
+                Err(MesoError::BuffersFull) => {}
+                Err(err) => return Err(AikaError::MesoError(err)),
             }
         }
+        for dropped in self.backlog.overflow(idx, bundle)? {
+            self.bounce(idx, dropped)?;
+        }
+        Ok(())
+    }
+
+    /// Take every letter still held, backed up or waiting in the messenger for routing, broadcasts included.
+    pub(crate) fn drain_undelivered(&mut self) -> Vec<Mail<MessageType>> {
+        let mut bundles = self.backlog.take();
+        bundles.append(&mut self.held);
+        while let Ok(polled) = self.messenger.poll() {
+            bundles.extend(polled);
+        }
+        bundles
+            .into_iter()
+            .flat_map(|(_, bundle)| bundle.into_letters())
+            .collect()
+    }
+
+    /// Hand the letters of a bundle that found its destination's inbox full back to their senders. Dropping an
+    /// anti-message would leave its `Msg` standing, so those are held and delivered in a later round instead. Either
+    /// way the letters stay counted as in flight, holding GVT back until they are dealt with. A `Scatter` counted
+    /// as one unit now counts one per recipient.
+    fn bounce(&mut self, idx: usize, bundle: MailBundle<MessageType>) -> Result<(), AikaError> {
+        let to_world = bundle.to_world.unwrap_or(idx);
+        let (from_world, counted) = (bundle.from_world, bundle.in_flight());
+        let letters = bundle.into_letters();
+        self.counter
+            .fetch_add(letters.len() - counted, Ordering::SeqCst);
+        let mut anti_msgs = Vec::new();
+        for mail in letters {
+            let Transfer::Msg(msg) = mail.transfer else {
+                anti_msgs.push(mail);
+                continue;
+            };
+            self.backlog.record_dropped(1);
+            let failures = self
+                .failures
+                .get(mail.from_world)
+                .ok_or(AikaError::InvalidWorldId(mail.from_world))?;
+            failures
+                .lock()
+                .map_err(|_| AikaError::PoisonedLock)?
+                .push(DeliveryFailure { to_world, msg });
+        }
+        if !anti_msgs.is_empty() {
+            let held = MailBundle {
+                letters: anti_msgs,
+                scatter: None,
+                to_world: Some(to_world),
+                from_world,
+            };
+            self.held.push((idx, held));
+        }
+        Ok(())
     }
 
     fn recalc_gvt(&mut self, in_transit_floor: u64) -> Result<(), AikaError> {
@@ -118,64 +559,340 @@ impl<
 
         let mut lowest = u64::MAX;
         let mut all = Vec::new();
-        for local in &self.lvts {
+        for (local, active) in self.lvts.iter().zip(&self.active) {
+            if !active.load(Ordering::Acquire) {
+                continue;
+            }
             let load = local.load(Ordering::Acquire);
             if load < lowest {
                 lowest = load;
             }
             all.push(load);
         }
+        // a planet may have sent mail and moved its clock past it since the first look
+        if self.counter.load(Ordering::Acquire) > 0 {
+            return Ok(());
+        }
 
         if in_transit_floor < lowest {
-            println!("in transit");
             lowest = in_transit_floor;
         }
-        println!("local clocks: {all:?}, gvt: {new_time}, lowest: {lowest}");
-        //println!("new_gvt: {lowest}");
+        let span = DebugSpan::Galaxy { gvt: new_time };
+        self.debug.emit(span, DebugKind::Gvt, || {
+            format!("local clocks: {all:?}, in transit: {in_transit_floor}, lowest: {lowest}")
+        });
         if new_time > lowest {
-            println!("local clocks: {all:?}, gvt: {new_time}, lowest: {lowest}");
             return Err(AikaError::TimeTravel);
         }
         if lowest == u64::MAX {
             return Ok(());
         }
         self.gvt.store(lowest, Ordering::Release);
+        if lowest > new_time {
+            if let Some(hook) = &mut self.hook {
+                hook.on_gvt_advance(new_time, lowest);
+            }
+            if let Some(trace) = &mut self.trace {
+                trace.counter("gvt", lowest);
+            }
+        }
         Ok(())
     }
 
-    fn check_mail_and_gvt(&mut self) -> Result<(), AikaError> {
+    /// Deliver pending mail and recalculate GVT, returning whether either made progress.
+    fn check_mail_and_gvt(&mut self) -> Result<bool, AikaError> {
         let transit_time = self.deliver_the_mail()?;
         //std::thread::sleep(Duration::from_nanos(30));
+        let gvt = self.gvt.load(Ordering::Acquire);
         self.recalc_gvt(transit_time)?;
-        Ok(())
+        Ok(transit_time != u64::MAX || self.gvt.load(Ordering::Acquire) != gvt)
+    }
+
+    /// Accumulate how far each active planet trails the fastest one.
+    fn sample_lag(&mut self) {
+        let loads = self
+            .lvts
+            .iter()
+            .zip(&self.active)
+            .map(|(lvt, active)| {
+                active
+                    .load(Ordering::Acquire)
+                    .then(|| lvt.load(Ordering::Acquire))
+            })
+            .collect::<Vec<_>>();
+        let Some(fastest) = loads.iter().flatten().max().copied() else {
+            return;
+        };
+        for (sum, load) in self.lag_sums.iter_mut().zip(loads) {
+            if let Some(load) = load {
+                *sum += fastest - load;
+            }
+        }
+        self.lag_samples += 1;
+    }
+
+    /// At a checkpoint, ask the most lagging planet to split into a spare world if it is hot enough.
+    fn plan_split(&mut self, scaling: AutoScaling) {
+        if self.lag_samples > 0 && !self.spare_worlds.is_empty() {
+            let candidate = (0..self.lvts.len())
+                .filter(|i| self.active[*i].load(Ordering::Acquire))
+                .filter(|i| {
+                    self.agent_counts[*i].load(Ordering::Acquire) >= scaling.min_agents.max(2)
+                })
+                .map(|i| (i, self.lag_sums[i]))
+                .max_by_key(|(_, lag)| *lag);
+            if let Some((planet, lag)) = candidate {
+                if lag > scaling.lag_threshold * self.lag_samples {
+                    let spare = self.spare_worlds.remove(0);
+                    self.split_requests[planet].store(spare + 1, Ordering::SeqCst);
+                }
+            }
+        }
+        self.lag_sums.iter_mut().for_each(|sum| *sum = 0);
+        self.lag_samples = 0;
+    }
+
+    /// At a checkpoint, ask the busiest planet to hand an agent to the idlest if the event counts of the last
+    /// window are further apart than the configured imbalance.
+    fn plan_migration(&mut self, checkpoint: u64) {
+        let Some((imbalance, in_progress)) = self.migration.clone() else {
+            return;
+        };
+        // planets don't wait at a checkpoint on the terminal time
+        if self.time_info.reached(checkpoint) {
+            return;
+        }
+        let loads = (0..self.lvts.len())
+            .filter(|i| self.active[*i].load(Ordering::Acquire))
+            .map(|i| (i, self.event_counts[i].swap(0, Ordering::AcqRel)))
+            .collect::<Vec<_>>();
+        let busiest = loads.iter().max_by_key(|(_, load)| *load);
+        let idlest = loads.iter().min_by_key(|(_, load)| *load);
+        if let (Some(&(from, busy)), Some(&(to, idle))) = (busiest, idlest) {
+            if from != to
+                && busy as f64 > imbalance * idle.max(1) as f64
+                && self.agent_counts[from].load(Ordering::Acquire) > 1
+            {
+                in_progress.fetch_add(1, Ordering::SeqCst);
+                self.migrate_requests[from].store(to + 1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Once GVT reaches the earliest barrier, fold the contributions made before it and release it, returning whether
+    /// it did. Every active planet waits on the barrier by then, unless one ran past it before the request was in place.
+    fn release_barrier(&mut self, gvt: u64) -> Result<bool, AikaError> {
+        let Some(time) = self
+            .barriers
+            .as_ref()
+            .and_then(|barriers| barriers.earliest())
+        else {
+            return Ok(false);
+        };
+        if gvt < time {
+            return Ok(false);
+        }
+        // a bridged engine doesn't wait on this one's barriers
+        let planets = self
+            .exchange
+            .as_ref()
+            .map_or(self.lvts.len(), |exchange| exchange.first());
+        let past = self.lvts[..planets]
+            .iter()
+            .zip(&self.active)
+            .any(|(lvt, active)| {
+                active.load(Ordering::Acquire) && lvt.load(Ordering::Acquire) > time
+            });
+        if past {
+            return Err(AikaError::BarrierUnreachable(time));
+        }
+        let aggregates = match &self.reductions {
+            Some(reductions) => {
+                reductions.commit(time)?;
+                reductions.snapshot().1
+            }
+            None => BTreeMap::new(),
+        };
+        if let Some(directory) = &self.directory {
+            directory.commit(time)?;
+        }
+        if let Some(barriers) = &self.barriers {
+            barriers.release(time, aggregates)?;
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.instant("barrier", &[("time", time)]);
+        }
+        Ok(true)
+    }
+
+    /// End the run as soon as it is quiescent, see `Planet::set_end_when_quiescent()`.
+    pub(crate) fn set_end_when_quiescent(&mut self, enabled: bool) {
+        self.end_when_quiescent = enabled;
+    }
+
+    /// Whether every active planet is quiet with no mail in flight, so that nothing can happen any more. The quiet
+    /// words are read on both sides of the in-flight count: a planet that woke up in between has moved its epoch on.
+    /// The planets of a bridged engine are out of sight, so such a run never ends early.
+    fn quiescent(&self) -> bool {
+        if !self.end_when_quiescent || self.exchange.is_some() || self.migrations_in_progress() {
+            return false;
+        }
+        let collect = || {
+            self.quiet
+                .iter()
+                .zip(&self.active)
+                .filter(|(_, active)| active.load(Ordering::Acquire))
+                .map(|(quiet, _)| quiet.load(Ordering::SeqCst))
+                .collect::<Vec<_>>()
+        };
+        let before = collect();
+        if before.iter().any(|word| word.is_multiple_of(2)) {
+            return false;
+        }
+        self.counter.load(Ordering::SeqCst) == 0 && collect() == before
+    }
+
+    fn migrations_in_progress(&self) -> bool {
+        self.migration
+            .as_ref()
+            .is_some_and(|(_, in_progress)| in_progress.load(Ordering::SeqCst) > 0)
+    }
+
+    pub(crate) fn parking(&self) -> &Parking {
+        &self.parking
     }
 
+    pub(crate) fn engine_handle(&self) -> EngineHandle {
+        EngineHandle::new(Arc::clone(&self.parking), Arc::clone(&self.wakeup))
+    }
+
+    /// Mail delivered to each planet so far, block by block.
+    pub fn block_ledger(&self) -> &BlockLedger {
+        &self.blocks
+    }
+
+    /// Whether GVT has reached the terminal time.
+    pub fn finished(&self) -> bool {
+        self.time_info.reached(self.gvt.load(Ordering::Acquire))
+    }
+
+    /// How this thread spent its time so far.
+    pub fn stats(&self) -> GalaxyStats {
+        GalaxyStats {
+            mailbox: self.backlog.stats(),
+            ..self.backoff.stats()
+        }
+    }
+
+    /// Advance GVT and checkpoints until every planet is done, or GVT reaches the horizon the run is parked at. An
+    /// error stops every `Planet` of the run.
+    /// Backs off while nothing changes, see `backoff`.
     pub fn gvt_daemon(&mut self) -> Result<(), AikaError> {
+        self.wakeup.register();
+        self.backoff.start();
+        let result = self
+            .core
+            .map_or(Ok(()), affinity::pin_current_thread)
+            .and_then(|_| {
+                catch_unwind(AssertUnwindSafe(|| self.advance_until_terminal()))
+                    .unwrap_or_else(|_| Err(panics::error(panics::take())))
+            });
+        self.backoff.finish();
+        if result.is_err() {
+            self.halt.store(true, Ordering::Release);
+        }
+        result
+    }
+
+    fn advance_until_terminal(&mut self) -> Result<(), AikaError> {
         loop {
+            if self.halt.load(Ordering::Acquire) {
+                break;
+            }
             //std::thread::sleep(Duration::from_nanos(30));
 
-            self.check_mail_and_gvt()?;
+            let mut progress = self.check_mail_and_gvt()?;
 
             let current_gvt = self.gvt.load(Ordering::Acquire);
+            panics::set_time(current_gvt);
+            progress |= self.release_barrier(current_gvt)?;
 
             // Check if all LPs have reached terminal
-            let all_terminal = self.lvts.iter().all(|lvt| {
+            let all_terminal = self.lvts.iter().zip(&self.active).all(|(lvt, active)| {
                 let lvt_val = lvt.load(Ordering::Acquire);
-                lvt_val as f64 * self.time_info.timestep >= self.time_info.terminal
+                !active.load(Ordering::Acquire) || self.time_info.reached(lvt_val)
                 // assuming you store this somewhere
             });
 
-            if all_terminal {
+            // GVT only reaches the terminal time once no mail is left in flight
+            if all_terminal && self.time_info.reached(current_gvt) {
                 //println!("All LPs reached terminal time, shutting down");
                 break;
             }
+            // with nothing left to do anywhere, every tick up to the terminal time is empty
+            if self.quiescent() {
+                self.gvt
+                    .store(self.time_info.terminal_step(), Ordering::SeqCst);
+                if let Some(trace) = &mut self.trace {
+                    trace.instant("quiescent", &[("gvt", current_gvt)]);
+                }
+                break;
+            }
+            // only this thread moves the checkpoint on, so no `Planet` can pass it before they all park there
+            if self.parking.cancelled() {
+                self.parking
+                    .park_at_checkpoint(self.next_checkpoint.load(Ordering::Acquire));
+            }
+            // nothing before the horizon can roll back any more, and no agent is left halfway between planets
+            if current_gvt >= self.parking.horizon() && !self.migrations_in_progress() {
+                self.parking.release();
+                break;
+            }
+
+            if self.scaling.is_some() {
+                self.sample_lag();
+            }
 
             // Handle checkpointing
-            if current_gvt >= self.next_checkpoint.load(Ordering::Acquire) {
-                self.next_checkpoint
-                    .store(current_gvt + self.checkpoint_frequency, Ordering::Release);
+            let checkpoint = self.next_checkpoint.load(Ordering::Acquire);
+            if current_gvt >= checkpoint {
+                if self.planned != Some(checkpoint) {
+                    self.planned = Some(checkpoint);
+                    // every planet waits at the checkpoint, so the new routes apply everywhere at once
+                    if let Some(topology) = &self.topology {
+                        topology.apply(checkpoint)?;
+                    }
+                    // nothing before the checkpoint can roll back, and every contribution before it is handed over
+                    if let Some(reductions) = &self.reductions {
+                        reductions.commit(checkpoint)?;
+                    }
+                    if let Some(directory) = &self.directory {
+                        directory.commit(checkpoint)?;
+                    }
+                    if let Some(scaling) = self.scaling {
+                        self.plan_split(scaling);
+                    }
+                    self.plan_migration(checkpoint);
+                }
+                // every planet waits at the checkpoint until migrated agents have landed
+                if self.migrations_in_progress() {
+                    self.backoff.idle(&self.wakeup);
+                    continue;
+                }
+                self.next_checkpoint.store(
+                    current_gvt.saturating_add(self.checkpoint_frequency),
+                    Ordering::SeqCst,
+                );
+                if let Some(trace) = &mut self.trace {
+                    trace.instant("checkpoint", &[("gvt", current_gvt)]);
+                }
+                progress = true;
+            }
+            if progress {
+                self.backoff.reset();
+            } else {
+                self.backoff.idle(&self.wakeup);
             }
-            std::thread::yield_now();
         }
         Ok(())
     }
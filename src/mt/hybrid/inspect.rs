@@ -0,0 +1,167 @@
+//! Read-only views of the past of a `Planet`.
+//! Agent and world states are journaled, and shared partitions keep the values their writes replaced back to GVT,
+//! so a `Planet` can reconstruct its state as of an earlier tick without rolling back. `Planet::state_at()` borrows
+//! its journals as a `PlanetState` of the end of that tick: the last state each journal logged at or before it, and
+//! the partition values with every later write reverted. Nothing is read until asked for and the run is left
+//! untouched, so a view can be taken between `HybridEngine::advance()` calls as well as after the run. Journals keep
+//! every state unless an agent passes a horizon to its writes, while partitions only reach back to the GVT they were
+//! last pruned at, see `Partitions::floor()`.
+use bytemuck::{Pod, Zeroable};
+use mesocarp::logging::journal::Journal;
+
+use crate::{
+    agents::partitions::{Partition, Partitions},
+    analysis::history::journal_state_at,
+    AikaError,
+};
+
+/// The state of a `Planet` as of the end of one tick.
+pub struct PlanetState<'a> {
+    time: u64,
+    agent_states: &'a [Journal],
+    world_state: &'a Journal,
+    partitions: &'a Partitions,
+}
+
+impl<'a> PlanetState<'a> {
+    pub(crate) fn new(
+        time: u64,
+        agent_states: &'a [Journal],
+        world_state: &'a Journal,
+        partitions: &'a Partitions,
+    ) -> Self {
+        Self {
+            time,
+            agent_states,
+            world_state,
+            partitions,
+        }
+    }
+
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    /// The state of `agent`, `None` if it had logged none by then.
+    pub fn agent<T: Pod + Zeroable + 'static>(&self, agent: usize) -> Result<Option<T>, AikaError> {
+        let journal = self
+            .agent_states
+            .get(agent)
+            .ok_or(AikaError::InvalidAgentId(agent))?;
+        Ok(journal_state_at(journal, self.time))
+    }
+
+    /// The state of every agent, by agent.
+    pub fn agents<T: Pod + Zeroable + 'static>(&self) -> Vec<Option<T>> {
+        self.agent_states
+            .iter()
+            .map(|journal| journal_state_at(journal, self.time))
+            .collect()
+    }
+
+    /// The world state, `None` if none had been logged by then.
+    pub fn world<T: Pod + Zeroable + 'static>(&self) -> Option<T> {
+        journal_state_at(self.world_state, self.time)
+    }
+
+    /// The values of `partition`.
+    pub fn partition<T: Pod + 'static>(
+        &self,
+        partition: Partition<T>,
+    ) -> Result<Vec<T>, AikaError> {
+        self.partitions.values_at(partition, self.time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    // Logs its step count and adds it to the world state and the partition every step
+    struct Counter {
+        output: Partition<u64>,
+        steps: u64,
+    }
+
+    impl ThreadedAgent<16, u64> for Counter {
+        fn step(&mut self, context: &mut PlanetContext<16, u64>, id: usize) -> Event {
+            self.steps += 1;
+            context.agent_states[id].write(self.steps, context.time, None);
+            let total = context
+                .world_state
+                .read_state::<u64>()
+                .copied()
+                .unwrap_or(0);
+            context
+                .world_state
+                .write(total + self.steps, context.time, None);
+            context.join_partition(self.output, id);
+            context
+                .write_partition(self.output, id, 0, self.steps)
+                .unwrap();
+            Event::new(context.time, context.time, id, Action::Timeout(2))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, u64>,
+            _msg: Msg<u64>,
+            _id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_state_at_past_ticks() {
+        let config = HybridConfig::new(1, 64)
+            .with_time_bounds(30.0, 1.0)
+            .with_optimistic_sync(4, 10)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<16, 16, 2, u64>::create(config).unwrap();
+        let output = engine.create_shared_partition(0, 1).unwrap();
+        engine
+            .spawn_agent(0, Box::new(Counter { output, steps: 0 }))
+            .unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.advance(10).unwrap();
+
+        // steps at 1, 3, 5, ...
+        let planet = &engine.planets[0];
+        let state = planet.state_at(5).unwrap();
+        assert_eq!(state.time(), 5);
+        assert_eq!(state.agent::<u64>(0).unwrap(), Some(3));
+        assert_eq!(state.agents::<u64>(), [Some(3)]);
+        assert_eq!(state.world::<u64>(), Some(6));
+        assert!(matches!(
+            state.agent::<u64>(1),
+            Err(AikaError::InvalidAgentId(1))
+        ));
+        assert_eq!(planet.state_at(0).unwrap().agent::<u64>(0).unwrap(), None);
+        assert!(planet.state_at(planet.now() + 1).is_err());
+
+        // GVT has pruned the writes to the partition before it
+        let floor = planet.context.partitions.floor();
+        assert!(floor > 0);
+        let steps = floor.div_ceil(2);
+        assert_eq!(
+            planet.state_at(floor).unwrap().partition(output).unwrap(),
+            [steps]
+        );
+        assert!(matches!(
+            planet.state_at(floor - 1).unwrap().partition(output),
+            Err(AikaError::HistoryPruned { .. })
+        ));
+
+        // the view leaves the run untouched
+        let engine = engine.run().unwrap();
+        let state = engine.state_at(0, 29).unwrap();
+        assert_eq!(state.agent::<u64>(0).unwrap(), Some(15));
+        assert_eq!(state.world::<u64>(), Some(120));
+        assert!(engine.state_at(1, 0).is_err());
+    }
+}
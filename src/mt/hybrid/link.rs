@@ -0,0 +1,246 @@
+//! Bandwidth and queuing on interplanetary links.
+//! A `LinkModel` gives a directed link between two planets a fixed latency and a capacity in messages per step.
+//! Mail beyond a step's capacity queues for the next free step, so its receive time shifts with the link's load.
+//! The model runs on the sending `Planet`, which is the only writer to its outgoing links, so queue state is
+//! journaled by local time and rolled back with the `Planet`; re-sent mail gets the same delivery times.
+use std::collections::BTreeMap;
+
+/// Latency and bandwidth of a directed link.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LinkModel {
+    /// steps added to every message's receive time
+    pub latency: u64,
+    /// messages the link carries per step
+    pub capacity: u32,
+}
+
+impl LinkModel {
+    pub fn new(latency: u64, capacity: u32) -> Self {
+        Self { latency, capacity }
+    }
+}
+
+/// Utilization of a directed link over a run.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    pub messages: u64,
+    /// messages that had to wait for a later step
+    pub queued: u64,
+    /// total steps messages spent queued
+    pub queue_delay: u64,
+    pub max_queue_delay: u64,
+    /// steps in which the link carried at least one message
+    pub busy_steps: u64,
+}
+
+impl LinkStats {
+    /// Fraction of the link's capacity used over `steps` steps.
+    pub fn utilization(&self, model: LinkModel, steps: u64) -> f64 {
+        if steps == 0 || model.capacity == 0 {
+            return 0.0;
+        }
+        self.messages as f64 / (steps as f64 * model.capacity as f64)
+    }
+}
+
+/// Queue state of one link: the step the next message departs in and how much of it is used.
+#[derive(Copy, Clone, Debug, Default)]
+struct LinkState {
+    slot: u64,
+    used: u32,
+    stats: LinkStats,
+}
+
+/// The outgoing links of a `Planet`, with their journaled queue state.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Links {
+    models: BTreeMap<usize, LinkModel>,
+    /// `(local time, state after the send)` per destination world
+    history: BTreeMap<usize, Vec<(u64, LinkState)>>,
+}
+
+impl Links {
+    pub(crate) fn new(models: BTreeMap<usize, LinkModel>) -> Self {
+        Self {
+            models,
+            history: BTreeMap::new(),
+        }
+    }
+
+    /// Model the link to `to_world` with `model` from now on, or stop modelling it.
+    pub(crate) fn set_model(&mut self, to_world: usize, model: Option<LinkModel>) {
+        match model {
+            Some(model) => self.models.insert(to_world, model),
+            None => self.models.remove(&to_world),
+        };
+    }
+
+    pub(crate) fn model(&self, to_world: usize) -> Option<LinkModel> {
+        self.models.get(&to_world).copied()
+    }
+
+    /// Queue a message sent at `sent` for `recv` on the link to `to_world`, returning its shifted receive time.
+    /// The send is journaled at the `Planet`'s local time `now`.
+    pub(crate) fn transmit(&mut self, to_world: usize, now: u64, sent: u64, recv: u64) -> u64 {
+        let Some(model) = self.model(to_world) else {
+            return recv;
+        };
+        let history = self.history.entry(to_world).or_default();
+        let mut state = history.last().map(|(_, state)| *state).unwrap_or_default();
+        if state.slot < sent {
+            state.slot = sent;
+            state.used = 0;
+        }
+        if state.used >= model.capacity.max(1) {
+            state.slot += 1;
+            state.used = 0;
+        }
+        if state.used == 0 {
+            state.stats.busy_steps += 1;
+        }
+        state.used += 1;
+
+        let delay = state.slot - sent;
+        state.stats.messages += 1;
+        if delay > 0 {
+            state.stats.queued += 1;
+            state.stats.queue_delay += delay;
+            state.stats.max_queue_delay = state.stats.max_queue_delay.max(delay);
+        }
+        history.push((now, state));
+        recv.max(state.slot.saturating_add(model.latency))
+    }
+
+    /// Forget every send at or after `time`.
+    pub(crate) fn rollback(&mut self, time: u64) {
+        for history in self.history.values_mut() {
+            let keep = history.partition_point(|(at, _)| *at < time);
+            history.truncate(keep);
+        }
+    }
+
+    pub(crate) fn stats(&self) -> BTreeMap<usize, LinkStats> {
+        self.history
+            .iter()
+            .filter_map(|(world, history)| Some((*world, history.last()?.1.stats)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Packet {
+        seq: u32,
+    }
+
+    unsafe impl Pod for Packet {}
+    unsafe impl Zeroable for Packet {}
+
+    // Sends a burst of three packets to planet 1 at time 1, all due a step later
+    struct Burst;
+
+    impl ThreadedAgent<128, Packet> for Burst {
+        fn step(&mut self, context: &mut PlanetContext<128, Packet>, agent_id: usize) -> Event {
+            let time = context.time;
+            for seq in 0..3 {
+                let msg = Msg::new(Packet { seq }, time, time + 1, agent_id, Some(0));
+                context.send_mail(msg, 1).unwrap();
+            }
+            Event::new(time, time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Packet>,
+            _msg: Msg<Packet>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    struct Sink {
+        received: Arc<Mutex<Vec<(u32, u64)>>>,
+    }
+
+    impl ThreadedAgent<128, Packet> for Sink {
+        fn step(&mut self, context: &mut PlanetContext<128, Packet>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Packet>,
+            msg: Msg<Packet>,
+            _agent_id: usize,
+        ) {
+            self.received.lock().unwrap().push((msg.data.seq, msg.recv));
+        }
+    }
+
+    #[test]
+    fn test_bandwidth_shifts_delivery() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(1, 10)
+            .with_uniform_worlds(16, 1, 16)
+            .with_link(0, 1, LinkModel::new(1, 1));
+        let mut engine = HybridEngine::<128, 128, 1, Packet>::create(config).unwrap();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        engine.spawn_agent(0, Box::new(Burst)).unwrap();
+        let sink = Sink {
+            received: Arc::clone(&received),
+        };
+        engine.spawn_agent(1, Box::new(sink)).unwrap();
+        engine.schedule(0, 0, 1).unwrap();
+        engine.schedule(1, 0, 0).unwrap();
+        let engine = engine.run().unwrap();
+
+        // one packet per step, each a step in flight
+        let mut received = received.lock().unwrap().clone();
+        received.sort();
+        assert_eq!(received, vec![(0, 2), (1, 3), (2, 4)]);
+        let links = engine.stats().links();
+        let stats = links[&(0, 1)];
+        assert_eq!((stats.messages, stats.queued, stats.queue_delay), (3, 2, 3));
+        assert_eq!(stats.utilization(LinkModel::new(1, 1), 20), 0.15);
+        assert!(!links.contains_key(&(1, 0)));
+    }
+
+    #[test]
+    fn test_link_queue_rolls_back() {
+        let mut links = Links::new(BTreeMap::from([(1, LinkModel::new(2, 2))]));
+        // three messages in one step: the third waits a step
+        assert_eq!(links.transmit(1, 5, 5, 6), 7);
+        assert_eq!(links.transmit(1, 5, 5, 6), 7);
+        assert_eq!(links.transmit(1, 5, 5, 6), 8);
+        // a message sent later than the backlog departs at once, and a late recv is kept
+        assert_eq!(links.transmit(1, 9, 9, 20), 20);
+        // links without a model are untouched
+        assert_eq!(links.transmit(0, 5, 5, 6), 6);
+
+        let stats = links.stats()[&1];
+        assert_eq!((stats.messages, stats.queued, stats.queue_delay), (4, 1, 1));
+        assert_eq!(stats.busy_steps, 3);
+        assert_eq!(stats.utilization(LinkModel::new(2, 2), 10), 0.2);
+
+        links.rollback(9);
+        assert_eq!(links.stats()[&1].messages, 3);
+        assert_eq!(links.transmit(1, 9, 9, 10), 11);
+        links.rollback(5);
+        assert_eq!(links.transmit(1, 5, 5, 6), 7);
+        assert_eq!(links.stats()[&1].messages, 1);
+    }
+}
@@ -0,0 +1,239 @@
+//! Memory accounting and limits of every `Planet`.
+//! State journals, the event overflow behind the timing wheels and pending mail all grow with the run, so a
+//! misconfigured model can exhaust the host. With `HybridConfig::with_memory_limits()` every `Planet` samples its
+//! `MemoryUsage` every `every` ticks. Past the soft limit it raises `SimWarning::MemoryPressure` and stops running
+//! ahead of GVT until a sample finds it back under the limit, so the speculative state it keeps for rollbacks is
+//! collected as GVT passes rather than growing further. Past the hard limit the run fails with
+//! `AikaError::MemoryLimitExceeded`, naming where the memory went. A `Journal` doesn't reveal the size of its
+//! entries, so journal bytes are estimated as `entry_bytes` per entry; set it to the size of the states the agents
+//! log plus some 48 bytes of bookkeeping for a close estimate.
+use std::{fmt, mem::size_of};
+
+use mesocarp::logging::journal::Journal;
+
+use crate::objects::{Event, Msg};
+
+/// What a `Planet` holds in memory, as of its last sample.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// entries in the agent and world state journals
+    pub journal_entries: usize,
+    /// estimated bytes of those entries, see `MemoryLimits::entry_bytes`
+    pub journal_bytes: usize,
+    /// events waiting in memory in the overflow behind the timing wheels
+    pub overflow_events: usize,
+    pub overflow_bytes: usize,
+    /// mail scheduled for this `Planet`'s agents and not yet delivered
+    pub pending_mail: usize,
+    pub mail_bytes: usize,
+}
+
+impl MemoryUsage {
+    /// Account for `journals` at `entry_bytes` per entry, `overflow_events` and `pending_mail` of payload `T`.
+    pub(crate) fn measure<'a, T: Clone>(
+        journals: impl IntoIterator<Item = &'a Journal>,
+        entry_bytes: usize,
+        overflow_events: usize,
+        pending_mail: usize,
+    ) -> Self {
+        // the tape of a journal can only be read typed, and bytes are typed as anything
+        let journal_entries = journals
+            .into_iter()
+            .map(|journal| journal.read_all::<u8>().len())
+            .sum::<usize>();
+        Self {
+            journal_entries,
+            journal_bytes: journal_entries.saturating_mul(entry_bytes),
+            overflow_events,
+            overflow_bytes: overflow_events.saturating_mul(size_of::<Event>()),
+            pending_mail,
+            mail_bytes: pending_mail.saturating_mul(size_of::<Msg<T>>()),
+        }
+    }
+
+    /// Bytes accounted for in all.
+    pub fn total(&self) -> usize {
+        self.journal_bytes
+            .saturating_add(self.overflow_bytes)
+            .saturating_add(self.mail_bytes)
+    }
+}
+
+impl fmt::Display for MemoryUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes in {} journal entries, {} bytes in {} overflow events, {} bytes in {} pending letters",
+            self.journal_bytes,
+            self.journal_entries,
+            self.overflow_bytes,
+            self.overflow_events,
+            self.mail_bytes,
+            self.pending_mail
+        )
+    }
+}
+
+/// Limits on the `MemoryUsage` of every `Planet`, in bytes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MemoryLimits {
+    /// usage past which a `Planet` stops running ahead of GVT
+    pub soft: Option<usize>,
+    /// usage past which the run fails
+    pub hard: Option<usize>,
+    /// estimated bytes of a journal entry
+    pub entry_bytes: usize,
+    /// ticks between two samples
+    pub every: u64,
+}
+
+impl Default for MemoryLimits {
+    fn default() -> Self {
+        Self {
+            soft: None,
+            hard: None,
+            entry_bytes: 64,
+            every: 64,
+        }
+    }
+}
+
+impl MemoryLimits {
+    /// No limits, sampling every 64 ticks and estimating 64 bytes per journal entry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_soft_limit(mut self, bytes: usize) -> Self {
+        self.soft = Some(bytes);
+        self
+    }
+
+    pub fn with_hard_limit(mut self, bytes: usize) -> Self {
+        self.hard = Some(bytes);
+        self
+    }
+
+    /// Estimate each journal entry at `bytes`.
+    pub fn with_entry_bytes(mut self, bytes: usize) -> Self {
+        self.entry_bytes = bytes;
+        self
+    }
+
+    /// Sample the usage every `every` ticks.
+    pub fn with_sample_every(mut self, every: u64) -> Self {
+        self.every = every.max(1);
+        self
+    }
+
+    /// Whether `usage` lies past the soft limit.
+    pub fn pressed(&self, usage: &MemoryUsage) -> bool {
+        self.soft.is_some_and(|soft| usage.total() > soft)
+    }
+
+    /// Whether `usage` lies past the hard limit.
+    pub fn exceeded(&self, usage: &MemoryUsage) -> bool {
+        self.hard.is_some_and(|hard| usage.total() > hard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, stats::SimWarning, HybridEngine},
+        objects::Action,
+        AikaError,
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Tally {
+        count: u64,
+    }
+
+    unsafe impl Pod for Tally {}
+    unsafe impl Zeroable for Tally {}
+
+    // Logs its count every step
+    struct Counter;
+
+    impl ThreadedAgent<128, Tally> for Counter {
+        fn step(&mut self, context: &mut PlanetContext<128, Tally>, agent_id: usize) -> Event {
+            let time = context.time;
+            let journal = &mut context.agent_states[agent_id];
+            let count = journal.read_state::<Tally>().map_or(0, |tally| tally.count) + 1;
+            journal.write(Tally { count }, time, None);
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Tally>,
+            _msg: Msg<Tally>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    fn engine(limits: MemoryLimits) -> HybridEngine<128, 128, 1, Tally> {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(100.0, 1.0)
+            .with_optimistic_sync(10, 20)
+            .with_uniform_worlds(16, 1, 256)
+            .with_memory_limits(limits);
+        let mut engine = HybridEngine::create(config).unwrap();
+        for planet in 0..2 {
+            engine.spawn_agent(planet, Box::new(Counter)).unwrap();
+        }
+        engine.schedule_all_agents(1).unwrap();
+        engine
+    }
+
+    #[test]
+    fn test_usage_is_sampled() {
+        let limits = MemoryLimits::new()
+            .with_entry_bytes(56)
+            .with_sample_every(10);
+        let engine = engine(limits).run().unwrap();
+        let stats = engine.stats();
+        for planet in &stats.planets {
+            // the last sample, after the last step, finds the states logged at 1..=99
+            assert_eq!(planet.memory.journal_entries, 99);
+            assert_eq!(planet.memory.journal_bytes, 99 * 56);
+        }
+        assert_eq!(stats.warnings().count(), 0);
+    }
+
+    #[test]
+    fn test_soft_limit_raises_pressure() {
+        let limits = MemoryLimits::new()
+            .with_soft_limit(32 * 64)
+            .with_sample_every(10);
+        let engine = engine(limits).run().unwrap();
+        let stats = engine.stats();
+        let pressed = stats
+            .warnings()
+            .filter(|warning| matches!(warning, SimWarning::MemoryPressure { .. }))
+            .count();
+        // once per planet, as the journals never shrink
+        assert_eq!(pressed, 2);
+        assert!(engine.planets.iter().all(|planet| planet.now() >= 100));
+    }
+
+    #[test]
+    fn test_hard_limit_fails_the_run() {
+        let limits = MemoryLimits::new()
+            .with_hard_limit(32 * 64)
+            .with_sample_every(10);
+        let err = engine(limits).run().err().unwrap();
+        let AikaError::MemoryLimitExceeded { limit, usage, .. } = err else {
+            panic!("unexpected error {err}");
+        };
+        assert_eq!(limit, 32 * 64);
+        assert!(usage.journal_entries > 32);
+    }
+}
@@ -0,0 +1,88 @@
+//! Agent migration between `Planet`s at GVT checkpoints.
+//! While every `Planet` is held at a checkpoint, the `Galaxy` compares the events each processed over the last
+//! window and asks the busiest to hand one agent to the idlest. The agent moves with its state `Journal`, pending
+//! events, pending `Msg`s, group memberships and sandbox `Capabilities`, and the `Galaxy`'s `RoutingTable` records
+//! its new address so `send_mail` and mail already in flight still reach it. The checkpoint is only released once it
+//! has landed.
+use std::sync::{atomic::AtomicUsize, Arc, Mutex};
+
+use bytemuck::{Pod, Zeroable};
+use mesocarp::logging::journal::Journal;
+
+use crate::{
+    agents::{sandbox::Capabilities, PlanetContext, ThreadedAgent},
+    mt::hybrid::routing::RoutingTable,
+    objects::{Action, Event, GroupId, Msg},
+};
+
+/// An agent in transit between two `Planet`s.
+pub(crate) struct Migrant<const INTER_SLOTS: usize, MessageType: Pod + Zeroable + Clone> {
+    pub(crate) from: (usize, usize),
+    pub(crate) agent: Box<dyn ThreadedAgent<INTER_SLOTS, MessageType>>,
+    pub(crate) state: Journal,
+    pub(crate) groups: Vec<GroupId>,
+    pub(crate) capabilities: Option<Capabilities>,
+    pub(crate) events: Vec<u64>,
+    /// pending `Msg`s for the agent, broadcasts included, still addressed to its old index
+    pub(crate) msgs: Vec<Msg<MessageType>>,
+    pub(crate) load: u64,
+}
+
+unsafe impl<const INTER_SLOTS: usize, MessageType: Pod + Zeroable + Clone> Send
+    for Migrant<INTER_SLOTS, MessageType>
+{
+}
+
+/// Inboxes, routing table and in-flight counter shared by the `Galaxy` and every `Planet`.
+pub(crate) struct MigrationSupport<const INTER_SLOTS: usize, MessageType: Pod + Zeroable + Clone> {
+    pub(crate) inboxes: Arc<Vec<Mutex<Vec<Migrant<INTER_SLOTS, MessageType>>>>>,
+    pub(crate) routes: Arc<Mutex<RoutingTable>>,
+    pub(crate) in_progress: Arc<AtomicUsize>,
+}
+
+impl<const INTER_SLOTS: usize, MessageType: Pod + Zeroable + Clone>
+    MigrationSupport<INTER_SLOTS, MessageType>
+{
+    pub(crate) fn new(worlds: usize, routes: Arc<Mutex<RoutingTable>>) -> Self {
+        Self {
+            inboxes: Arc::new((0..worlds).map(|_| Mutex::new(Vec::new())).collect()),
+            routes,
+            in_progress: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+impl<const INTER_SLOTS: usize, MessageType: Pod + Zeroable + Clone> Clone
+    for MigrationSupport<INTER_SLOTS, MessageType>
+{
+    fn clone(&self) -> Self {
+        Self {
+            inboxes: Arc::clone(&self.inboxes),
+            routes: Arc::clone(&self.routes),
+            in_progress: Arc::clone(&self.in_progress),
+        }
+    }
+}
+
+/// Placeholder left at a migrated agent's old index, so the indices of the remaining agents never shift.
+pub(crate) struct Departed;
+
+impl<const INTER_SLOTS: usize, MessageType: Pod + Zeroable + Clone>
+    ThreadedAgent<INTER_SLOTS, MessageType> for Departed
+{
+    fn step(
+        &mut self,
+        context: &mut PlanetContext<INTER_SLOTS, MessageType>,
+        agent_id: usize,
+    ) -> Event {
+        Event::new(context.time, context.time, agent_id, Action::Wait)
+    }
+
+    fn read_message(
+        &mut self,
+        _context: &mut PlanetContext<INTER_SLOTS, MessageType>,
+        _msg: Msg<MessageType>,
+        _agent_id: usize,
+    ) {
+    }
+}
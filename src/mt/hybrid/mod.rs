@@ -1,17 +1,103 @@
 //! Hybrid synchronization engine for multi-threaded discrete event simulation.
 //! Implements a modified Clustered Time Warp protocol with `HybridEngine` coordinating multiple
 //! `Planet` instances, supporting inter-planetary messaging with optimistic execution and rollback.
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    sync::{atomic::Ordering, Arc, Mutex},
+    time::Instant,
+};
+
 use bytemuck::{Pod, Zeroable};
 
+#[cfg(feature = "agent-profiling")]
+use crate::analysis::profiling::ProfileReport;
+#[cfg(feature = "rollback-export")]
+use crate::analysis::rollbacks::{RollbackExporter, RollbackSink};
 use crate::{
-    agents::ThreadedAgent,
-    mt::hybrid::{config::HybridConfig, galaxy::Galaxy, planet::Planet},
+    agents::{
+        discovery::{Attributes, Peer, Query},
+        partitions::Partition,
+        resources::QueueDiscipline,
+        sandbox::Capabilities,
+        ThreadedAgent,
+    },
+    analysis::{
+        causality::CausalityLog,
+        critical_path::{CausalLog, CriticalPath},
+    },
+    export::{PodLayout, Table},
+    extensions::WorldExtension,
+    mt::hybrid::{
+        audit::{Audit, Fingerprint},
+        barrier::{BarrierCallback, BarrierRecord, Barriers},
+        blocks::BlockLedger,
+        bridge::{transport::Transport, RemotePlanet},
+        cancel::EngineHandle,
+        config::HybridConfig,
+        control::ControlHandle,
+        cut::PendingCut,
+        directory::Directory,
+        galaxy::Galaxy,
+        inspect::PlanetState,
+        migration::MigrationSupport,
+        panics::PanicSink,
+        params::ParameterTimeline,
+        parcels::ParcelStore,
+        planet::{Planet, PlanetHandle, RegistryOutput, ScalingSupport},
+        reduce::Reductions,
+        routing::{AgentHandle, RoutingTable},
+        scenario::{PlanetScenario, Scenario, ScenarioValidator},
+        shared::SharedData,
+        spawn::{AgentFactory, AgentTemplate},
+        stats::RunStats,
+        topology::{Topology, TopologyRecord},
+    },
+    objects::Mail,
+    testing::MessageLedger,
+    time::SimTime,
+    tracing::{Trace, TraceRecorder, GALAXY_TID},
     AikaError,
 };
 
+pub mod affinity;
+pub mod audit;
+pub mod backoff;
+pub mod barrier;
+pub mod batch;
+pub mod blocks;
+pub mod bridge;
+pub mod builder;
+pub mod cancel;
 pub mod config;
+pub mod control;
+pub mod cut;
+pub mod debug;
+pub mod directory;
+pub mod faults;
 pub mod galaxy;
+pub mod inspect;
+pub mod link;
+pub mod memory;
+pub mod migration;
+pub mod panics;
+pub mod parallel;
+pub mod params;
+pub mod parcels;
 pub mod planet;
+pub mod reduce;
+pub mod reports;
+pub mod routing;
+pub mod scenario;
+pub mod schema;
+pub mod shared;
+pub mod spawn;
+pub mod stats;
+pub mod throttle;
+pub mod topology;
+pub mod tuning;
+#[cfg(feature = "validate-causality")]
+pub mod vclock;
 
 /// Hybrid synchronization engine for multi-threaded execution environments.
 pub struct HybridEngine<
@@ -23,6 +109,16 @@ pub struct HybridEngine<
     pub galaxy: Galaxy<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
     pub planets: Vec<Planet<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>>,
     pub config: HybridConfig,
+    scaling: Option<ScalingSupport<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>>,
+    validators: Vec<Box<dyn ScenarioValidator>>,
+    topology: Arc<Topology>,
+    reductions: Arc<Reductions>,
+    directory: Arc<Directory>,
+    barriers: Arc<Barriers>,
+    /// reserved world slots of a bridged engine's planets, until `bridge()` hands them to a `RemotePlanet`
+    remote_slots: Vec<RegistryOutput<INTER_SLOTS, MessageType>>,
+    remote: Option<RemotePlanet<INTER_SLOTS, MessageType>>,
+    audit: Option<Audit<MessageType>>,
 }
 
 impl<
@@ -34,6 +130,28 @@ impl<
 {
     /// Create a new synchronization engine from the provided config.
     pub fn create(config: HybridConfig) -> Result<Self, AikaError> {
+        config.check_consts::<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT>()?;
+        if config.deterministic
+            && (config.auto_scaling.is_some() || config.migration_imbalance.is_some())
+        {
+            return Err(AikaError::ConfigError(
+                "auto-scaling and migration depend on thread timing and can't run deterministically"
+                    .to_string(),
+            ));
+        }
+        if config.remote_worlds > 0
+            && (config.auto_scaling.is_some() || config.migration_imbalance.is_some())
+        {
+            return Err(AikaError::ConfigError(
+                "a bridged engine needs a fixed set of planets, without auto-scaling or migration"
+                    .to_string(),
+            ));
+        }
+        if config.remote_worlds > 0 && config.heap_payloads {
+            return Err(AikaError::ConfigError(
+                "heap payloads can't cross the bridge to a remote engine".to_string(),
+            ));
+        }
         let mut galaxy = Galaxy::new(
             config.number_of_worlds,
             config.throttle_horizon,
@@ -41,43 +159,460 @@ impl<
             config.terminal,
             config.timestep,
         )?;
+        if let Some(schemas) = config.payload_schema.clone() {
+            galaxy.set_schemas(schemas)?;
+        }
+        if let Some(scaling) = config.auto_scaling {
+            galaxy.enable_auto_scaling(scaling)?;
+        }
+        if config.remote_worlds > 0 {
+            galaxy.enable_remote_worlds(config.remote_worlds)?;
+        }
+        let origin = config.record_trace.then(Instant::now);
+        if let Some(origin) = origin {
+            galaxy.enable_tracing(TraceRecorder::new(origin, GALAXY_TID));
+        }
+        galaxy.set_debug(config.debug.clone());
+        galaxy.set_mailbox_overflow(config.mailbox_overflow);
+        let parcels = config.heap_payloads.then(ParcelStore::default);
         let mut planets = Vec::new();
         for i in 0..config.number_of_worlds {
             let registry = galaxy.spawn_world()?;
-            let planet = Planet::from_config(
+            let mut planet = Planet::from_config(
                 config.world_config(i)?,
                 config.terminal,
                 config.timestep,
                 config.throttle_horizon,
                 registry,
             )?;
+            if config.record_causality {
+                planet.enable_causal_log();
+            }
+            if config.causal_ids {
+                planet.enable_causality();
+            }
+            if let Some(origin) = origin {
+                planet.enable_tracing(TraceRecorder::new(origin, i));
+            }
+            if let Some(every) = config.occupancy_every {
+                planet.enable_occupancy_recording(every);
+            }
+            if let Some(width) = config.kpi_width {
+                planet.enable_kpis(width);
+            }
+            planet.set_max_rollback_depth(config.max_rollback_depth);
+            if let Some(reporting) = config.rollback_reports {
+                planet.set_rollback_reporting(reporting);
+            }
+            if let Some(throttle) = config.adaptive_throttle {
+                planet.set_adaptive_throttle(throttle);
+            }
+            planet.set_panic_policy(config.panic_policy);
+            planet.set_tick_order(config.tick_order);
+            planet.set_notify_at(config.notify_at);
+            planet.set_deterministic(config.deterministic.then_some(config.seed));
+            planet.set_fast_forward(config.fast_forward);
+            planet.set_end_when_quiescent(config.end_when_quiescent);
+            planet.set_parallel_steps(config.parallel_steps);
+            planet.set_memory_limits(config.memory_limits);
+            if let Some(sizing) = config.clock_sizing() {
+                planet.set_dynamic_clock(sizing)?;
+            }
+            if let Some(&time) = config.activations.get(&i) {
+                planet.set_activation(time)?;
+            }
+            if let Some(&rate) = config.rates.get(&i) {
+                planet.set_rate(rate)?;
+            }
+            planet.set_warmup(config.warmup);
+            planet.set_debug(config.debug.clone());
+            planet.set_overflow_strategy(config.overflow.clone());
+            let links = config.links_from(i);
+            if !links.is_empty() {
+                planet.set_links(links);
+            }
+            if let Some(batching) = config.mail_batching {
+                planet.set_mail_batching(batching);
+            }
+            if let Some(faults) = config.faults {
+                planet.set_faults(faults);
+            }
+            planet.context.parcels = parcels.clone();
             planets.push(planet);
         }
-        Ok(Self {
+        if let Some(placement) = &config.placement {
+            let cores = placement.assign(planets.len() + 1, &affinity::available_cores()?)?;
+            galaxy.pin_to(cores[0]);
+            for (planet, &core) in planets.iter_mut().zip(&cores[1..]) {
+                planet.pin_to(core);
+            }
+        }
+        let mut scaling = None;
+        if let Some(auto_scaling) = config.auto_scaling {
+            let mut spares = Vec::new();
+            for _ in 0..auto_scaling.spare_planets {
+                spares.push(galaxy.spawn_spare_world()?);
+            }
+            let support = ScalingSupport {
+                spares: Arc::new(Mutex::new(spares)),
+                spawned: Arc::new(Mutex::new(Vec::new())),
+            };
+            for planet in &mut planets {
+                planet.enable_auto_scaling(support.clone());
+            }
+            scaling = Some(support);
+        }
+        let remote_slots = (0..config.remote_worlds)
+            .map(|_| galaxy.spawn_world())
+            .collect::<Result<Vec<_>, _>>()?;
+        let topology = Arc::new(Topology::new(config.total_planets(), config.links.clone()));
+        galaxy.set_topology(Arc::clone(&topology));
+        galaxy.set_end_when_quiescent(config.end_when_quiescent);
+        let reductions = Arc::new(Reductions::new(
+            config.total_planets(),
+            config.reductions.clone(),
+        ));
+        galaxy.set_reductions(Arc::clone(&reductions));
+        let directory = Arc::new(Directory::new(config.total_planets()));
+        galaxy.set_directory(Arc::clone(&directory));
+        let barriers = Arc::new(Barriers::new(
+            config.horizon_ceiling(),
+            (config.terminal / config.timestep) as u64,
+        ));
+        galaxy.set_barriers(Arc::clone(&barriers));
+        for planet in &mut planets {
+            planet.set_routes(galaxy.routes());
+            planet.set_topology(Arc::clone(&topology))?;
+            planet.set_reductions(Arc::clone(&reductions));
+            planet.set_directory(Arc::clone(&directory));
+            planet.set_barriers(Arc::clone(&barriers));
+        }
+        if let Some(imbalance) = config.migration_imbalance {
+            let support = MigrationSupport::new(config.total_planets(), galaxy.routes());
+            galaxy.enable_migration(imbalance, Arc::clone(&support.in_progress));
+            for planet in &mut planets {
+                planet.enable_migration(support.clone());
+            }
+        }
+        let mut engine = Self {
             galaxy,
             planets,
             config,
+            scaling,
+            validators: Vec::new(),
+            topology,
+            reductions,
+            directory,
+            barriers,
+            remote_slots,
+            remote: None,
+            audit: None,
+        };
+        if engine.config.determinism_audit {
+            engine.start_audit()?;
+        }
+        Ok(engine)
+    }
+
+    /// Register a cut for the audit at every checkpoint before the terminal time, where the `Galaxy` puts them.
+    fn start_audit(&mut self) -> Result<(), AikaError> {
+        if self.config.auto_scaling.is_some() {
+            return Err(AikaError::ConfigError(
+                "a determinism audit needs a fixed set of planets".to_string(),
+            ));
+        }
+        let frequency = self.config.checkpoint_frequency.max(1);
+        let terminal = (self.config.terminal / self.config.timestep) as u64;
+        let mut audit = Audit::new(self.planets.len());
+        for time in (1..)
+            .map(|k| k * frequency)
+            .take_while(|time| *time < terminal)
+        {
+            let sender = audit.watch(time);
+            for planet in &mut self.planets {
+                planet.add_cut(time, sender.clone());
+            }
+        }
+        self.audit = Some(audit);
+        Ok(())
+    }
+
+    /// Rolling digests of every `Planet`'s committed state at the checkpoints passed so far, if the run is audited
+    /// with `HybridConfig::with_determinism_audit()`.
+    pub fn fingerprint(&self) -> Option<&Fingerprint> {
+        self.audit.as_ref().map(Audit::fingerprint)
+    }
+
+    /// Check the scenario with `validator` before `run()` starts it.
+    pub fn add_validator(&mut self, validator: Box<dyn ScenarioValidator>) {
+        self.validators.push(validator);
+    }
+
+    /// Run every registered `ScenarioValidator`, reporting all problems they find at once.
+    pub fn validate_scenario(&self) -> Result<(), AikaError> {
+        if self.validators.is_empty() {
+            return Ok(());
+        }
+        let planets = self
+            .planets
+            .iter()
+            .map(|planet| {
+                let agents = planet.agents.len();
+                let mut scheduled = Vec::new();
+                for agent in 0..agents {
+                    let pending = planet.pending_events(agent, u64::MAX)?;
+                    scheduled.extend(pending.iter().map(|event| (event.agent, event.time)));
+                }
+                scheduled.sort_by_key(|&(agent, time)| (time, agent));
+                Ok(PlanetScenario {
+                    world_id: planet.context.world_id,
+                    agents,
+                    scheduled,
+                    world_state: &planet.context.world_state,
+                    agent_states: &planet.context.agent_states,
+                })
+            })
+            .collect::<Result<_, AikaError>>()?;
+        let scenario = Scenario {
+            config: &self.config,
+            planets,
+        };
+        let violations = scenario::validate(&self.validators, &scenario);
+        if violations.0.is_empty() {
+            return Ok(());
+        }
+        Err(AikaError::InvalidScenario(violations))
+    }
+
+    /// Every agent that is a `T`, with its `(planet, agent)` address, in planet order. Call it after `run()` to read
+    /// results out of the agents.
+    pub fn agents_of<T: Any>(&self) -> impl Iterator<Item = ((usize, usize), &T)> {
+        self.planets.iter().enumerate().flat_map(|(planet, world)| {
+            world
+                .agents_of::<T>()
+                .map(move |(agent, found)| ((planet, agent), found))
         })
     }
 
-    /// Spawn a `ThreadedAgent` on a specific `Planet`.
+    /// Collect every `Planet`'s run statistics, along with how busy the `Galaxy` thread was.
+    pub fn stats(&self) -> RunStats {
+        RunStats {
+            planets: self
+                .planets
+                .iter()
+                .map(|planet| planet.stats().clone())
+                .collect(),
+            galaxy: self.galaxy.stats(),
+        }
+    }
+
+    /// Take every letter the run left undelivered or unread, e.g. to check that no `Msg` was lost: first the mail
+    /// the `Galaxy` never routed, then each `Planet`'s, see `Planet::drain_unprocessed()`. Each letter's
+    /// `to_world` and the `to` of its `Transfer` name its intended recipient.
+    pub fn drain_unprocessed(&mut self) -> Result<Vec<Mail<MessageType>>, AikaError> {
+        let mut letters = self.galaxy.drain_undelivered();
+        for planet in &mut self.planets {
+            letters.extend(planet.drain_unprocessed()?);
+        }
+        Ok(letters)
+    }
+
+    /// Where every agent lives now.
+    pub fn routing_table(&self) -> RoutingTable {
+        self.galaxy.routing_table()
+    }
+
+    /// The current `(planet, agent)` address of the agent behind `handle`.
+    pub fn locate(&self, handle: AgentHandle) -> Option<(usize, usize)> {
+        self.galaxy.routing_table().locate(handle)
+    }
+
+    /// Share `data` read-only with every `Planet` of the run, including those split off later. Agents read it
+    /// through `PlanetContext::shared_data::<T>()`.
+    pub fn with_shared_data<T: Any + Send + Sync>(mut self, data: Arc<T>) -> Self {
+        self.galaxy = self.galaxy.with_shared_data(Arc::clone(&data));
+        let shared = SharedData::new(data);
+        for planet in &mut self.planets {
+            planet.context.shared = Some(shared.clone());
+        }
+        self
+    }
+
+    /// Get a handle for sending `ControlAction`s to the `Planet`s, before or during `run()`.
+    pub fn control_handle(&self) -> ControlHandle {
+        ControlHandle::new(
+            self.planets
+                .iter()
+                .map(|planet| planet.control_sender())
+                .collect(),
+            Arc::clone(&self.topology),
+        )
+    }
+
+    /// Every parameter change applied through the control plane, across all `Planet`s.
+    pub fn parameter_timeline(&self) -> ParameterTimeline {
+        ParameterTimeline::new(
+            self.planets
+                .iter()
+                .flat_map(|planet| planet.parameter_changes().iter().copied()),
+        )
+    }
+
+    /// The committed global aggregates, with the time before which every contribution is folded in. After `run()`
+    /// that is the terminal time.
+    pub fn aggregates(&self) -> (u64, BTreeMap<usize, f64>) {
+        self.reductions.snapshot()
+    }
+
+    /// The committed attributes of every agent, published for peer discovery. After `run()` that includes every
+    /// publication.
+    pub fn attributes(&self) -> Attributes {
+        self.directory.snapshot()
+    }
+
+    /// The agents matching `query` among the committed attributes, at their current address.
+    pub fn peers(&self, query: &Query) -> Vec<Peer> {
+        self.directory.peers(query, Some(&self.galaxy.routes()))
+    }
+
+    /// Call `callback` at every barrier agents request with `PlanetContext::request_barrier()`, while every `Planet`
+    /// waits on it.
+    pub fn on_barrier(&mut self, callback: BarrierCallback) -> Result<(), AikaError> {
+        self.barriers.add_callback(callback)
+    }
+
+    /// Every barrier released so far, in time order.
+    pub fn barrier_log(&self) -> Vec<BarrierRecord> {
+        self.barriers.log()
+    }
+
+    /// Bridge this engine to a peer over `transport`, standing in for the remote worlds reserved with
+    /// `HybridConfig::with_remote_worlds()`. The peer has to reserve a slot for each of this engine's planets in turn.
+    pub fn bridge(&mut self, transport: Box<dyn Transport>) -> Result<(), AikaError> {
+        if self.remote_slots.is_empty() {
+            return Err(AikaError::ConfigError(
+                "no remote worlds are reserved to bridge".to_string(),
+            ));
+        }
+        let exchange = self.galaxy.exchange().ok_or_else(|| {
+            AikaError::ConfigError("no remote worlds are reserved to bridge".to_string())
+        })?;
+        let (clocks, failures) = self.galaxy.planet_handles(self.planets.len());
+        let remote = RemotePlanet::new(
+            transport,
+            exchange,
+            std::mem::take(&mut self.remote_slots),
+            clocks,
+            failures,
+            self.galaxy.schemas().clone(),
+            (self.config.terminal / self.config.timestep) as u64,
+        )?;
+        self.remote = Some(remote);
+        Ok(())
+    }
+
+    /// The `RemotePlanet` relaying to the bridged peer, if `bridge()` was called.
+    pub fn remote(&self) -> Option<&RemotePlanet<INTER_SLOTS, MessageType>> {
+        self.remote.as_ref()
+    }
+
+    /// Every route change handled so far, in the order it was requested.
+    pub fn topology_log(&self) -> Vec<TopologyRecord> {
+        self.topology.log()
+    }
+
+    /// Spawn a `ThreadedAgent` on a specific `Planet`, returning its stable handle.
     pub fn spawn_agent(
         &mut self,
         planet_id: usize,
         agent: Box<dyn ThreadedAgent<INTER_SLOTS, MessageType>>,
-    ) -> Result<(), AikaError> {
+    ) -> Result<AgentHandle, AikaError> {
         if planet_id >= self.planets.len() {
             return Err(AikaError::InvalidWorldId(planet_id));
         }
-        self.planets[planet_id].spawn_agent_preconfigured(agent);
-        Ok(())
+        let agent_id = self.planets[planet_id].spawn_agent_preconfigured(agent);
+        self.galaxy.register_agent(planet_id, agent_id)
     }
 
-    /// Spawn a `ThreadedAgent` on any `Planet`
-    pub fn spawn_agent_autobalance(
+    /// Add a resource with `capacity` units to a specific `Planet`, returning its id there. Only the agents of that
+    /// `Planet` can acquire it, and its state is read from `PlanetContext::resources`.
+    pub fn add_resource(
+        &mut self,
+        planet_id: usize,
+        capacity: u64,
+        discipline: QueueDiscipline,
+    ) -> Result<usize, AikaError> {
+        let planet = self
+            .planets
+            .get_mut(planet_id)
+            .ok_or(AikaError::InvalidWorldId(planet_id))?;
+        Ok(planet.context.resources.add(capacity, discipline))
+    }
+
+    /// Create a partition of `size` zeroed values on a specific `Planet`, returning its handle there. Only the agents
+    /// of that `Planet` that join it can read and write it, see `PlanetContext::create_shared_partition()`.
+    pub fn create_shared_partition<T: Pod + Zeroable + Send>(
+        &mut self,
+        planet_id: usize,
+        size: usize,
+    ) -> Result<Partition<T>, AikaError> {
+        let planet = self
+            .planets
+            .get_mut(planet_id)
+            .ok_or(AikaError::InvalidWorldId(planet_id))?;
+        Ok(planet.context.create_shared_partition(size))
+    }
+
+    /// Spawn an untrusted `ThreadedAgent` on a specific `Planet` that may only do what `capabilities` allow,
+    /// returning its stable handle. Refused calls are reported by `RunStats::violations()`.
+    pub fn spawn_sandboxed(
         &mut self,
+        planet_id: usize,
         agent: Box<dyn ThreadedAgent<INTER_SLOTS, MessageType>>,
+        capabilities: Capabilities,
+    ) -> Result<AgentHandle, AikaError> {
+        if planet_id >= self.planets.len() {
+            return Err(AikaError::InvalidWorldId(planet_id));
+        }
+        let agent_id = self.planets[planet_id].spawn_sandboxed(agent, capabilities);
+        self.galaxy.register_agent(planet_id, agent_id)
+    }
+
+    /// Plug a `WorldExtension` into planet `planet_id`.
+    pub fn register_extension(
+        &mut self,
+        planet_id: usize,
+        extension: Box<dyn WorldExtension>,
     ) -> Result<(), AikaError> {
+        self.planets
+            .get_mut(planet_id)
+            .ok_or(AikaError::InvalidWorldId(planet_id))?
+            .register_extension(extension)
+    }
+
+    /// Register a factory every `Planet` can build agents from with `PlanetContext::spawn_from_template()`, giving
+    /// each agent a state arena of `state_arena_size` bytes. Returns the template id.
+    pub fn register_template(
+        &mut self,
+        factory: AgentFactory<INTER_SLOTS, MessageType>,
+        state_arena_size: usize,
+    ) -> usize {
+        let template = AgentTemplate {
+            factory,
+            state_arena_size,
+        };
+        let mut id = 0;
+        for planet in &mut self.planets {
+            id = planet.register_template(template.clone());
+        }
+        id
+    }
+
+    /// Spawn a `ThreadedAgent` on any `Planet`, returning its stable handle.
+    pub fn spawn_agent_autobalance(
+        &mut self,
+        agent: Box<dyn ThreadedAgent<INTER_SLOTS, MessageType>>,
+    ) -> Result<AgentHandle, AikaError> {
         let mut lowest = (usize::MAX, usize::MAX);
         for (i, planet) in self.planets.iter().enumerate() {
             let count = planet.agents.len();
@@ -85,8 +620,8 @@ impl<
                 lowest = (i, count)
             }
         }
-        self.planets[lowest.0].spawn_agent_preconfigured(agent);
-        Ok(())
+        let agent_id = self.planets[lowest.0].spawn_agent_preconfigured(agent);
+        self.galaxy.register_agent(lowest.0, agent_id)
     }
 
     /// Schedule a step() event for a particular `ThreadedAgent` on a given `Planet`.
@@ -94,7 +629,7 @@ impl<
         &mut self,
         planet_id: usize,
         agent_id: usize,
-        time: u64,
+        time: impl Into<SimTime>,
     ) -> Result<(), AikaError> {
         if planet_id >= self.planets.len() {
             return Err(AikaError::InvalidWorldId(planet_id));
@@ -102,48 +637,414 @@ impl<
         self.planets[planet_id].schedule(time, agent_id)
     }
 
-    /// Run synchronization engine.
+    /// Schedule a batch of `(planet_id, agent_id, time)` step() events, grouped into a single insertion pass per `Planet`.
+    /// Every entry is validated on every `Planet` before anything is inserted, so a failed batch schedules nothing.
+    pub fn schedule_many<T: Into<SimTime> + Copy>(
+        &mut self,
+        events: &[(usize, usize, T)],
+    ) -> Result<(), AikaError> {
+        let mut per_planet = vec![Vec::new(); self.planets.len()];
+        for &(planet_id, agent_id, time) in events {
+            if planet_id >= self.planets.len() {
+                return Err(AikaError::InvalidWorldId(planet_id));
+            }
+            per_planet[planet_id].push((agent_id, time.into()));
+        }
+        let batches = self
+            .planets
+            .iter()
+            .zip(per_planet)
+            .map(|(planet, batch)| planet.validate_batch(&batch))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (planet, batch) in self.planets.iter_mut().zip(batches) {
+            if !batch.is_empty() {
+                planet.insert_batch(batch)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Schedule every `ThreadedAgent` on every `Planet` to step at the given time. Like `schedule_many()`, nothing is
+    /// scheduled if any `Planet` can't take it.
+    pub fn schedule_all_agents(&mut self, time: impl Into<SimTime>) -> Result<(), AikaError> {
+        let time = time.into();
+        let events = self
+            .planets
+            .iter()
+            .enumerate()
+            .flat_map(|(planet_id, planet)| {
+                (0..planet.agents.len()).map(move |agent_id| (planet_id, agent_id, time))
+            })
+            .collect::<Vec<_>>();
+        self.schedule_many(&events)
+    }
+
+    /// Stream a `RollbackRecord` for every rollback of the run to `writer`, in blocks of `block_rows` records per
+    /// `Planet`. Call before `run()`.
+    #[cfg(feature = "rollback-export")]
+    pub fn export_rollbacks(
+        &mut self,
+        writer: impl std::io::Write + Send + 'static,
+        block_rows: usize,
+    ) {
+        let sink = RollbackSink::new(writer);
+        for planet in &mut self.planets {
+            planet.export_rollbacks(RollbackExporter::new(sink.clone(), block_rows));
+        }
+    }
+
+    /// Time every call of an agent's `step()` and `read_message()` on every `Planet`, see `analysis::profiling`.
+    /// Call before `run()`.
+    #[cfg(feature = "agent-profiling")]
+    pub fn enable_agent_profiling(&mut self) {
+        for planet in &mut self.planets {
+            planet.enable_agent_profiling();
+        }
+    }
+
+    /// The calls timed on every `Planet` so far, if profiling was enabled with `enable_agent_profiling()`.
+    #[cfg(feature = "agent-profiling")]
+    pub fn agent_profile(&self) -> Option<ProfileReport> {
+        ProfileReport::new(self.planets.iter().filter_map(Planet::agent_profiler))
+    }
+
+    /// Register a consistent cut at `time`: every `Planet` stalls there until GVT catches up, records its state and
+    /// resumes. Call before `run()`; the returned `PendingCut` can be waited on from another thread.
+    pub fn consistent_cut(&mut self, time: u64) -> Result<PendingCut<MessageType>, AikaError> {
+        if self.config.auto_scaling.is_some() {
+            return Err(AikaError::ConfigError(
+                "consistent cuts need a fixed set of planets".to_string(),
+            ));
+        }
+        if time as f64 * self.config.timestep >= self.config.terminal {
+            return Err(AikaError::ConfigError(format!(
+                "cut at {time} is not before the terminal time"
+            )));
+        }
+        if let Some(planet) = self.planets.iter().find(|planet| planet.now() > time) {
+            return Err(AikaError::ConfigError(format!(
+                "planet {} is already past the cut at {time}",
+                planet.context.world_id
+            )));
+        }
+        let schema = self.galaxy.schemas().expected().hash();
+        let (pending, sender) = PendingCut::new(time, schema, self.planets.len());
+        for planet in &mut self.planets {
+            planet.add_cut(time, sender.clone());
+        }
+        Ok(pending)
+    }
+
+    /// Merge the causal logs of all `Planet`s, if the causal log was enabled in the config.
+    pub fn causal_log(&self) -> Option<CausalLog> {
+        if !self.config.record_causality {
+            return None;
+        }
+        let mut log = CausalLog::new();
+        for planet in &self.planets {
+            if let Some(planet_log) = planet.causal_log() {
+                log.merge(planet_log);
+            }
+        }
+        Some(log)
+    }
+
+    /// Merge the `CausalId`s handed out by all `Planet`s, if enabled in the config with `with_causality()`.
+    pub fn causality(&self) -> Option<CausalityLog> {
+        if !self.config.causal_ids {
+            return None;
+        }
+        let mut log = CausalityLog::default();
+        for planet in &self.planets {
+            if let Some(planet_log) = planet.causality() {
+                log.merge(planet_log);
+            }
+        }
+        Some(log)
+    }
+
+    /// Compute the critical path across all `Planet`s, if the causal log was enabled in the config.
+    pub fn critical_path(&self) -> Option<CriticalPath> {
+        self.causal_log().map(|log| log.critical_path())
+    }
+
+    /// Tabulate the state history of every agent on every `Planet` with the columns of `layout`.
+    pub fn agent_state_table<T: Pod + Zeroable + 'static>(
+        &self,
+        layout: &PodLayout,
+    ) -> Result<Table, AikaError> {
+        let states = self.planets.iter().flat_map(|planet| {
+            let world = planet.context.world_id;
+            (0..planet.context.agent_states.len()).flat_map(move |agent| {
+                planet
+                    .agent_history::<T>(agent)
+                    .map(move |(time, state)| (world, agent, time, state))
+            })
+        });
+        Table::from_states(layout, states)
+    }
+
+    /// Tabulate every committed event, if the causal log was enabled in the config.
+    pub fn event_table(&self) -> Option<Table> {
+        self.causal_log()
+            .map(|log| Table::from_events(&log.activations()))
+    }
+
+    /// Merge the timelines of the `Galaxy` and every `Planet`, if tracing was enabled in the config.
+    pub fn trace(&self) -> Option<Trace> {
+        if !self.config.record_trace {
+            return None;
+        }
+        let recorders = self
+            .planets
+            .iter()
+            .filter_map(|planet| planet.trace())
+            .chain(self.galaxy.trace())
+            .cloned();
+        Some(Trace::merge(recorders))
+    }
+
+    /// Tabulate the KPI buckets of every `Planet`, if they were enabled in the config.
+    pub fn kpi_table(&self) -> Option<Table> {
+        self.config.kpi_width?;
+        let recorders = self
+            .planets
+            .iter()
+            .filter_map(|planet| planet.kpis())
+            .collect::<Vec<_>>();
+        Some(Table::from_kpis(&recorders))
+    }
+
+    /// Tabulate the wheel occupancy of every `Planet`, if it was enabled in the config.
+    pub fn occupancy_table(&self) -> Option<Table> {
+        self.config.occupancy_every?;
+        let recorders = self
+            .planets
+            .iter()
+            .filter_map(|planet| planet.occupancy())
+            .collect::<Vec<_>>();
+        Some(Table::from_occupancy(&recorders))
+    }
+
+    /// Count interplanetary `Msg`s per agent pair on every `Planet`.
+    pub fn enable_message_ledger(&mut self) {
+        for planet in &mut self.planets {
+            planet.enable_message_ledger();
+        }
+    }
+
+    /// Merge the message ledgers of all `Planet`s, if enabled.
+    pub fn message_ledger(&self) -> Option<MessageLedger> {
+        let mut merged: Option<MessageLedger> = None;
+        for ledger in self
+            .planets
+            .iter()
+            .filter_map(|planet| planet.message_ledger())
+        {
+            merged.get_or_insert_with(MessageLedger::new).merge(ledger);
+        }
+        merged
+    }
+
+    /// Current GVT: every tick before it is committed.
+    pub fn gvt(&self) -> u64 {
+        self.galaxy.gvt.load(Ordering::Acquire)
+    }
+
+    /// The state of planet `world` as of the end of tick `time`, see `Planet::state_at()`.
+    pub fn state_at(&self, world: usize, time: u64) -> Result<PlanetState<'_>, AikaError> {
+        self.planets
+            .get(world)
+            .ok_or(AikaError::InvalidWorldId(world))?
+            .state_at(time)
+    }
+
+    /// Mail delivered to each planet, block by block, with `Table::from_blocks()` to export it.
+    pub fn block_ledger(&self) -> &BlockLedger {
+        self.galaxy.block_ledger()
+    }
+
+    /// Whether the run has reached its terminal time.
+    pub fn is_finished(&self) -> bool {
+        self.galaxy.finished()
+    }
+
+    /// A handle for cancelling the run from another thread, e.g. on Ctrl-C.
+    pub fn handle(&self) -> EngineHandle {
+        self.galaxy.engine_handle()
+    }
+
+    /// Whether the run was cancelled through an `EngineHandle`, in which case it is parked at GVT for good.
+    pub fn is_cancelled(&self) -> bool {
+        self.galaxy.parking().cancelled()
+    }
+
+    /// Run synchronization engine, resuming where the last `advance()` parked it. With auto-scaling enabled, the
+    /// returned engine also holds the `Planet`s split off during the run, ordered by world id.
     pub fn run(self) -> Result<Self, AikaError> {
+        self.run_until(u64::MAX)
+    }
+
+    /// Run until GVT has advanced by `gvt_delta` ticks, or the run has reached its terminal time, and hand the
+    /// engine back with every thread parked. Agents, stats and aggregates can then be read and control actions sent
+    /// before the next `advance()` or `run()` resumes from the new GVT. Ticks past GVT may already have run
+    /// optimistically, but every `Planet` stops at the new GVT. Not supported on a bridged engine.
+    pub fn advance(self, gvt_delta: u64) -> Result<Self, AikaError> {
+        if self.remote.is_some() {
+            return Err(AikaError::ConfigError(
+                "a bridged engine can only run to the end".to_string(),
+            ));
+        }
+        let horizon = self.gvt().saturating_add(gvt_delta);
+        self.run_until(horizon)
+    }
+
+    fn run_until(self, horizon: u64) -> Result<Self, AikaError> {
+        if self.is_finished() || self.is_cancelled() {
+            return Ok(self);
+        }
+        // later rounds resume a scenario that was already checked
+        if self.gvt() == 0 {
+            self.validate_scenario()?;
+        }
+        if !self.remote_slots.is_empty() {
+            return Err(AikaError::ConfigError(
+                "remote worlds are reserved but the engine was never bridged".to_string(),
+            ));
+        }
+        self.galaxy.parking().park_at(horizon);
         let HybridEngine {
             galaxy,
             planets,
             config,
+            scaling,
+            validators,
+            topology,
+            reductions,
+            directory,
+            barriers,
+            remote_slots,
+            remote,
+            mut audit,
         } = self;
+        // agents may have been scheduled since the last round parked
+        for planet in &planets {
+            planet.mark_busy();
+        }
+        let sink = PanicSink::default();
+        let galaxy_sink = sink.clone();
         let galaxy_handle = std::thread::spawn(move || {
+            galaxy_sink.enter(None);
             let mut galaxy = galaxy;
             galaxy.gvt_daemon().map(|_| galaxy)
         });
 
         let mut planet_handles = Vec::new();
         for planet in planets {
+            let planet_sink = sink.clone();
             let handle = std::thread::spawn(move || {
+                planet_sink.enter(Some(planet.context.world_id));
                 let mut planet = planet;
                 planet.run().map(|_| planet)
             });
             planet_handles.push(handle);
         }
-        let mut final_planets = Vec::new();
-        for handle in planet_handles {
-            let planet = handle.join().map_err(|_| AikaError::ThreadPanic)??;
-            final_planets.push(planet);
+        let remote_handle = remote.map(|remote| {
+            let remote_sink = sink.clone();
+            std::thread::spawn(move || {
+                remote_sink.enter(None);
+                let mut remote = remote;
+                remote.run().map(|_| remote)
+            })
+        });
+        let mut final_planets = Self::join_planets(planet_handles, scaling.as_ref(), &sink)?;
+        final_planets.sort_by_key(|planet| planet.context.world_id);
+        let final_galaxy = galaxy_handle
+            .join()
+            .map_err(|_| panics::error(sink.take()))??;
+        let final_remote = remote_handle
+            .map(|handle| handle.join().map_err(|_| panics::error(sink.take()))?)
+            .transpose()?;
+        // every `Planet` is done, so the contributions after the last checkpoint are final too
+        if final_galaxy.finished() {
+            reductions.commit((config.terminal / config.timestep) as u64)?;
+            directory.commit((config.terminal / config.timestep) as u64)?;
+        } else if final_galaxy.parking().cancelled() {
+            reductions.commit(final_galaxy.gvt.load(Ordering::Acquire))?;
+            directory.commit(final_galaxy.gvt.load(Ordering::Acquire))?;
+        }
+        if let Some(audit) = &mut audit {
+            audit.collect();
         }
-        let final_galaxy = galaxy_handle.join().map_err(|_| AikaError::ThreadPanic)??;
         Ok(Self {
             galaxy: final_galaxy,
             planets: final_planets,
             config,
+            scaling,
+            validators,
+            topology,
+            reductions,
+            directory,
+            barriers,
+            remote_slots,
+            remote: final_remote,
+            audit,
         })
     }
+
+    /// Join every `Planet` thread, including those spawned by splits while joining.
+    fn join_planets(
+        mut handles: Vec<PlanetHandle<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>>,
+        scaling: Option<&ScalingSupport<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>>,
+        sink: &PanicSink,
+    ) -> Result<Vec<Planet<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>>, AikaError> {
+        let mut planets = Vec::new();
+        while !handles.is_empty() {
+            for handle in handles.drain(..) {
+                let planet = handle.join().map_err(|_| panics::error(sink.take()))??;
+                planets.push(planet);
+            }
+            if let Some(support) = scaling {
+                let mut spawned = support
+                    .spawned
+                    .lock()
+                    .map_err(|_| AikaError::PoisonedLock)?;
+                handles.append(&mut spawned);
+            }
+        }
+        Ok(planets)
+    }
 }
 
 #[cfg(test)]
 mod hybrid_engine_tests {
     use crate::{
-        agents::{PlanetContext, ThreadedAgent},
-        mt::hybrid::{config::HybridConfig, HybridEngine},
-        objects::{Action, Event, Msg},
+        agents::{
+            partitions::Partition,
+            sandbox::{Capabilities, ViolationKind},
+            PlanetContext, ThreadedAgent,
+        },
+        analysis::causality::CausalKind,
+        mt::hybrid::{
+            config::{HybridConfig, PanicPolicy, TickOrder},
+            faults::{FaultInjection, FaultStats},
+            reports::RollbackReporting,
+            stats::SimWarning,
+            throttle::AdaptiveThrottle,
+            HybridEngine,
+        },
+        objects::{Action, Event, Msg, Transfer},
+        AikaError,
     };
     use bytemuck::{Pod, Zeroable};
+    use std::{
+        collections::BTreeSet,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::{Duration, Instant},
+    };
 
     // Simple test message type
     #[derive(Copy, Clone, Debug, PartialEq)]
@@ -152,23 +1053,1487 @@ mod hybrid_engine_tests {
         value: u8,
     }
 
-    unsafe impl Pod for TestData {}
-    unsafe impl Zeroable for TestData {}
+    unsafe impl Pod for TestData {}
+    unsafe impl Zeroable for TestData {}
+
+    // Simple agent that just schedules timeouts (similar to st::World TestAgent)
+    struct SimpleSchedulingAgent {}
+
+    impl SimpleSchedulingAgent {
+        pub fn new() -> Self {
+            SimpleSchedulingAgent {}
+        }
+    }
+
+    impl ThreadedAgent<128, TestData> for SimpleSchedulingAgent {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            let time = context.time;
+            // Just timeout for 1 time unit
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, TestData>,
+            _msg: Msg<TestData>,
+            _agent_id: usize,
+        ) {
+            // Simple agent doesn't process messages
+        }
+    }
+
+    #[test]
+    fn test_hybrid_engine_basic_run() {
+        // Configuration
+        const NUM_PLANETS: usize = 14;
+        const AGENTS_PER_PLANET: usize = 100;
+        const TOTAL_AGENTS: usize = NUM_PLANETS * AGENTS_PER_PLANET;
+        const EVENTS: u64 = 10000;
+        // Create configuration
+        let config = HybridConfig::new(NUM_PLANETS, 16) // 512 bytes for anti-message arena
+            .with_time_bounds(EVENTS as f64, 1.0) // terminal=1000, timestep=1.0
+            .with_optimistic_sync(50, 100) // throttle_horizon=50, checkpoint_frequency=100
+            .with_uniform_worlds(
+                16, // world state arena size
+                AGENTS_PER_PLANET,
+                16, // agent state arena size
+            );
+
+        // Validate config
+        assert!(config.validate().is_ok());
+        assert_eq!(config.total_agents(), TOTAL_AGENTS);
+
+        // Create the hybrid engine
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+
+        // Spawn agents using autobalancing
+        for _i in 0..TOTAL_AGENTS {
+            let agent = SimpleSchedulingAgent::new();
+            engine.spawn_agent_autobalance(Box::new(agent)).unwrap();
+        }
+
+        // Schedule initial events for each planet
+        // Each planet should have approximately AGENTS_PER_PLANET agents due to autobalancing
+        for planet_id in 0..NUM_PLANETS {
+            // Schedule first few agents in each planet to start at time 1
+            for agent_id in 0..10 {
+                // Just schedule first 5 agents per planet
+                let _ = engine.schedule(planet_id, agent_id, 1);
+            }
+        }
+
+        // Run the simulation
+        println!(
+            "Starting hybrid simulation with {NUM_PLANETS} planets and {TOTAL_AGENTS} total agents"
+        );
+
+        let result = engine.run();
+
+        // Verify the simulation ran successfully
+        assert!(
+            result.is_ok(),
+            "Hybrid engine run failed: {:?}",
+            result.err()
+        );
+
+        let final_engine = result.unwrap();
+
+        // Basic verification that the simulation progressed
+        println!("Simulation completed successfully");
+
+        // Verify we still have all our planets
+        assert_eq!(final_engine.planets.len(), NUM_PLANETS);
+
+        // Verify agents are distributed (autobalancing should give roughly equal distribution)
+        let mut total_agent_count = 0;
+        for planet in final_engine.planets.iter() {
+            let agent_count = planet.agents.len();
+            total_agent_count += agent_count;
+            //println!("Planet {} has {} agents", i, agent_count);
+
+            // With perfect autobalancing, each should have AGENTS_PER_PLANET
+            // Allow some variance due to integer division
+            assert!(agent_count >= AGENTS_PER_PLANET - 1);
+            assert!(agent_count <= AGENTS_PER_PLANET + 1);
+        }
+
+        assert_eq!(
+            total_agent_count, TOTAL_AGENTS,
+            "Total agent count mismatch"
+        );
+
+        println!(
+            "Test passed: {TOTAL_AGENTS} agents distributed across {NUM_PLANETS} planets, with {EVENTS} events per agent"
+        );
+    }
+
+    #[test]
+    fn test_hybrid_critical_path() {
+        let config = HybridConfig::new(2, 16)
+            .with_time_bounds(50.0, 1.0)
+            .with_optimistic_sync(50, 100)
+            .with_uniform_worlds(16, 2, 16)
+            .with_causal_log(true);
+
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        for planet_id in 0..2 {
+            for _ in 0..2 {
+                engine
+                    .spawn_agent(planet_id, Box::new(SimpleSchedulingAgent::new()))
+                    .unwrap();
+            }
+        }
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+
+        // four independent agents stepping every tick: each is its own chain
+        let path = engine.critical_path().unwrap();
+        assert!(!path.is_empty());
+        assert_eq!(path.total_activations, 4 * path.len());
+        assert!((path.max_speedup() - 4.0).abs() < 1e-9);
+        assert_eq!(path.agent_share.len(), 1);
+    }
+
+    #[test]
+    fn test_schedule_many_is_all_or_nothing() {
+        let config = HybridConfig::new(2, 16)
+            .with_time_bounds(50.0, 1.0)
+            .with_optimistic_sync(50, 100)
+            .with_uniform_worlds(16, 1, 16);
+
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        for planet_id in 0..2 {
+            engine
+                .spawn_agent(planet_id, Box::new(SimpleSchedulingAgent::new()))
+                .unwrap();
+        }
+        // planet 1's entry is past the terminal, so planet 0's must not be scheduled either
+        let err = engine.schedule_many(&[(0, 0, 5), (1, 0, 80)]).unwrap_err();
+        assert!(matches!(err, AikaError::PastTerminal));
+        assert!(engine.planets[0].pending_events(0, 50).unwrap().is_empty());
+        assert!(engine.planets[1].pending_events(0, 50).unwrap().is_empty());
+
+        engine.schedule_many(&[(0, 0, 5), (1, 0, 7)]).unwrap();
+        assert_eq!(engine.planets[0].pending_events(0, 50).unwrap()[0].time, 5);
+        assert_eq!(engine.planets[1].pending_events(0, 50).unwrap()[0].time, 7);
+    }
+
+    #[test]
+    fn test_schedule_all_agents_is_all_or_nothing() {
+        let config = HybridConfig::new(2, 16)
+            .with_time_bounds(50.0, 1.0)
+            .with_optimistic_sync(50, 100)
+            .with_uniform_worlds(16, 1, 16)
+            .with_world_rate(1, 8);
+
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        for planet_id in 0..2 {
+            engine
+                .spawn_agent(planet_id, Box::new(SimpleSchedulingAgent::new()))
+                .unwrap();
+        }
+        // planet 1 steps every 8 timesteps, so 49 lands on 56, past the terminal, and planet 0 keeps nothing either
+        let err = engine.schedule_all_agents(49).unwrap_err();
+        assert!(matches!(err, AikaError::PastTerminal));
+        assert!(engine.planets[0].pending_events(0, 50).unwrap().is_empty());
+
+        engine.schedule_all_agents(9).unwrap();
+        assert_eq!(engine.planets[0].pending_events(0, 50).unwrap()[0].time, 9);
+        assert_eq!(engine.planets[1].pending_events(0, 50).unwrap()[0].time, 16);
+    }
+
+    #[test]
+    fn test_create_rejects_small_consts() {
+        let config = HybridConfig::new(3, 16)
+            .with_time_bounds(10.0, 1.0)
+            .with_optimistic_sync(1, 5)
+            .with_uniform_worlds(16, 1, 16)
+            .with_auto_scaling(1, 0, 2);
+        assert_eq!(config.const_requirements().inter_slots, 4);
+
+        let err = HybridEngine::<2, 128, 1, TestData>::create(config.clone())
+            .err()
+            .unwrap();
+        assert!(
+            matches!(err, AikaError::ConfigError(msg) if msg.contains("raise INTER_SLOTS to at least 4"))
+        );
+        let err = HybridEngine::<4, 1, 1, TestData>::create(config.clone())
+            .err()
+            .unwrap();
+        assert!(matches!(err, AikaError::ConfigError(msg) if msg.starts_with("CLOCK_SLOTS")));
+        assert!(HybridEngine::<4, 128, 1, TestData>::create(config).is_ok());
+    }
+
+    // Steps slowly in wall-clock time and sends one straggler-prone message to planet 1 at time 5
+    struct SlowSender;
+
+    impl ThreadedAgent<128, TestData> for SlowSender {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            let time = context.time;
+            std::thread::sleep(Duration::from_millis(2));
+            if time == 5 {
+                let msg = Msg::new(TestData { value: 1 }, time, time + 1, agent_id, Some(0));
+                context.send_mail(msg, 1).unwrap();
+            }
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, TestData>,
+            _msg: Msg<TestData>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_rollback_storm_is_reported() {
+        let config = HybridConfig::new(2, 16)
+            .with_time_bounds(60.0, 1.0)
+            .with_optimistic_sync(40, 100)
+            .with_uniform_worlds(16, 1, 16)
+            .with_max_rollback_depth(10);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        engine.spawn_agent(0, Box::new(SlowSender)).unwrap();
+        engine
+            .spawn_agent(1, Box::new(SimpleSchedulingAgent::new()))
+            .unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+
+        // planet 1 runs far ahead while planet 0 is slow, so the message at 6 rolls it back past the limit
+        let stats = engine.stats();
+        assert!(stats.rollbacks() >= 1);
+        let storm = stats.warnings().next().copied().unwrap();
+        let SimWarning::RollbackStorm {
+            world,
+            from_world,
+            time,
+            depth,
+            ..
+        } = storm
+        else {
+            panic!("expected a rollback storm, got {storm:?}");
+        };
+        assert_eq!((world, from_world, time), (1, 0, 6));
+        assert!(depth > 10);
+    }
+
+    #[cfg(feature = "validate-causality")]
+    #[test]
+    fn test_rollbacks_keep_causality() {
+        // passes every step on to planet 2, so a rollback of planet 1 has mail there to cancel
+        struct Forwarder;
+
+        impl ThreadedAgent<128, TestData> for Forwarder {
+            fn step(
+                &mut self,
+                context: &mut PlanetContext<128, TestData>,
+                agent_id: usize,
+            ) -> Event {
+                let time = context.time;
+                let msg = Msg::new(TestData { value: 2 }, time, time + 1, agent_id, Some(0));
+                context.send_mail(msg, 2).unwrap();
+                Event::new(time, time, agent_id, Action::Timeout(1))
+            }
+
+            fn read_message(
+                &mut self,
+                _context: &mut PlanetContext<128, TestData>,
+                _msg: Msg<TestData>,
+                _agent_id: usize,
+            ) {
+            }
+        }
+
+        let config = HybridConfig::new(3, 1 << 16)
+            .with_time_bounds(60.0, 1.0)
+            .with_optimistic_sync(40, 100)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        engine.spawn_agent(0, Box::new(SlowSender)).unwrap();
+        engine.spawn_agent(1, Box::new(Forwarder)).unwrap();
+        engine
+            .spawn_agent(2, Box::new(SimpleSchedulingAgent::new()))
+            .unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+
+        // planet 2 read mail planet 1 sent before the straggler, all of it cancelled before GVT passed it
+        let stats = engine.stats();
+        assert!(stats.planets[1].rollbacks >= 1);
+        let violations = stats
+            .warnings()
+            .filter(|warning| matches!(warning, SimWarning::CausalityViolation { .. }))
+            .collect::<Vec<_>>();
+        assert!(violations.is_empty(), "{violations:?}");
+    }
+
+    // Counts its steps in a partition
+    struct Counter {
+        steps: Partition<u64>,
+    }
+
+    impl ThreadedAgent<128, TestData> for Counter {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            let time = context.time;
+            context.join_partition(self.steps, agent_id);
+            let steps = context.read_partition(self.steps, agent_id).unwrap()[0];
+            context
+                .write_partition(self.steps, agent_id, 0, steps + 1)
+                .unwrap();
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, TestData>,
+            _msg: Msg<TestData>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_advance_in_steps() {
+        let config = HybridConfig::new(2, 1 << 16)
+            .with_time_bounds(40.0, 1.0)
+            .with_optimistic_sync(8, 10)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        let mut counts = Vec::new();
+        for planet in 0..2 {
+            let steps = engine.create_shared_partition::<u64>(planet, 1).unwrap();
+            engine
+                .spawn_agent(planet, Box::new(Counter { steps }))
+                .unwrap();
+            counts.push(steps);
+        }
+        engine.schedule_all_agents(1).unwrap();
+        let read = |engine: &HybridEngine<128, 128, 1, TestData>| {
+            (0..2)
+                .map(|planet| {
+                    let partitions = &engine.planets[planet].context.partitions;
+                    partitions.values(counts[planet]).unwrap()[0]
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for horizon in [7, 14, 21, 28, 35] {
+            engine = engine.advance(7).unwrap();
+            assert_eq!(engine.gvt(), horizon);
+            assert!(!engine.is_finished());
+            // nothing at or past the horizon has run, and everything before it is committed
+            assert_eq!(read(&engine), [horizon - 1, horizon - 1]);
+        }
+        let engine = engine.advance(7).unwrap();
+        assert!(engine.is_finished());
+        assert_eq!(read(&engine), [39, 39]);
+        let gvt = engine.gvt();
+        assert_eq!(engine.advance(7).unwrap().gvt(), gvt);
+    }
+
+    #[test]
+    fn test_cancel_parks_at_a_cut() {
+        let config = HybridConfig::new(2, 1 << 16)
+            .with_time_bounds(1e9, 1.0)
+            .with_optimistic_sync(8, 10)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        let mut counts = Vec::new();
+        for planet in 0..2 {
+            let steps = engine.create_shared_partition::<u64>(planet, 1).unwrap();
+            engine
+                .spawn_agent(planet, Box::new(Counter { steps }))
+                .unwrap();
+            counts.push(steps);
+        }
+        engine.schedule_all_agents(1).unwrap();
+        let handle = engine.handle();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            handle.cancel();
+        });
+        let engine = engine.run().unwrap();
+        canceller.join().unwrap();
+
+        assert!(engine.is_cancelled() && !engine.is_finished());
+        let gvt = engine.gvt();
+        assert!(gvt > 0);
+        for (planet, steps) in engine.planets.iter().zip(counts) {
+            assert_eq!(planet.context.partitions.values(steps).unwrap()[0], gvt - 1);
+        }
+        // a cancelled engine stays where it stopped
+        assert_eq!(engine.run().unwrap().gvt(), gvt);
+    }
+
+    #[test]
+    fn test_agents_of_after_run() {
+        let config = HybridConfig::new(2, 16)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(5, 10)
+            .with_uniform_worlds(16, 2, 16);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        let steps = Arc::new(AtomicUsize::new(0));
+        for planet in 0..2 {
+            engine
+                .spawn_agent(planet, Box::new(SimpleSchedulingAgent::new()))
+                .unwrap();
+        }
+        engine
+            .spawn_agent(
+                1,
+                Box::new(BusyAgent {
+                    steps: Arc::clone(&steps),
+                }),
+            )
+            .unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+
+        let simple = engine
+            .agents_of::<SimpleSchedulingAgent>()
+            .map(|(address, _)| address)
+            .collect::<Vec<_>>();
+        assert_eq!(simple, vec![(0, 0), (1, 0)]);
+        let busy = engine.agents_of::<BusyAgent>().collect::<Vec<_>>();
+        assert_eq!(busy.len(), 1);
+        assert_eq!(busy[0].0, (1, 1));
+        assert!(Arc::ptr_eq(&busy[0].1.steps, &steps));
+    }
+
+    #[test]
+    fn test_adaptive_throttle_widens_without_rollbacks() {
+        let throttle = AdaptiveThrottle::new(2, 40).with_window(5);
+        let config = HybridConfig::new(2, 16)
+            .with_time_bounds(60.0, 1.0)
+            .with_optimistic_sync(4, 100)
+            .with_uniform_worlds(16, 1, 16)
+            .with_adaptive_throttle(throttle);
+        assert_eq!(config.horizon_ceiling(), 40);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        engine.spawn_agent(0, Box::new(SlowSender)).unwrap();
+        engine
+            .spawn_agent(1, Box::new(SimpleSchedulingAgent::new()))
+            .unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+
+        // planet 0 never rolls back, so its horizon climbs to the ceiling in steps of 4
+        let stats = engine.stats();
+        assert_eq!(stats.planets[0].throttle_horizon, 40);
+        assert_eq!(stats.planets[0].horizon_changes, 9);
+        assert!(stats.planets[1].throttle_horizon <= 40);
+
+        let invalid = HybridConfig::new(1, 16)
+            .with_time_bounds(60.0, 1.0)
+            .with_optimistic_sync(4, 100)
+            .with_uniform_worlds(16, 1, 16)
+            .with_adaptive_throttle(AdaptiveThrottle::new(0, 40));
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_rollbacks_are_reported_per_window() {
+        let config = HybridConfig::new(2, 16)
+            .with_time_bounds(60.0, 1.0)
+            .with_optimistic_sync(40, 100)
+            .with_uniform_worlds(16, 1, 16)
+            .with_rollback_reports(RollbackReporting::new(20, 1).with_debug());
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        engine.spawn_agent(0, Box::new(SlowSender)).unwrap();
+        engine
+            .spawn_agent(1, Box::new(SimpleSchedulingAgent::new()))
+            .unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+
+        // every rollback lands in exactly one report, blamed on planet 0's only sender
+        let stats = engine.stats();
+        let reports = stats.rollback_reports().collect::<Vec<_>>();
+        assert!(!reports.is_empty());
+        assert_eq!(
+            reports.iter().map(|report| report.count).sum::<u64>(),
+            stats.rollbacks()
+        );
+        for report in reports {
+            assert_eq!(report.world, 1);
+            assert_eq!(report.top_senders, vec![((0, 0), report.count)]);
+            assert_eq!(report.details.len() as u64, report.count);
+            assert!(report.min_depth <= report.max_depth);
+        }
+    }
+
+    #[cfg(feature = "rollback-export")]
+    #[test]
+    fn test_rollbacks_are_exported() {
+        #[derive(Clone, Default)]
+        struct Shared(Arc<Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let config = HybridConfig::new(2, 16)
+            .with_time_bounds(60.0, 1.0)
+            .with_optimistic_sync(40, 100)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        engine.spawn_agent(0, Box::new(SlowSender)).unwrap();
+        engine
+            .spawn_agent(1, Box::new(SimpleSchedulingAgent::new()))
+            .unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        let out = Shared::default();
+        engine.export_rollbacks(out.clone(), 64);
+        let engine = engine.run().unwrap();
+
+        // the one block holds a row per rollback, the first caused by planet 0's message sent at 5 for 6
+        let rollbacks = engine.stats().rollbacks();
+        assert!(rollbacks >= 1);
+        let text = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 1);
+        let worlds = vec!["1"; rollbacks as usize].join(",");
+        assert!(lines[0].starts_with(&format!("{{\"world\":[{worlds}]")));
+        assert!(lines[0].contains("\"cause_world\":[0"));
+        assert!(lines[0].contains("\"cause_sent\":[5"));
+        assert!(lines[0].contains("\"to\":[6"));
+    }
+
+    // Agent that burns wall-clock time on every step, so its planet lags behind
+    struct BusyAgent {
+        steps: Arc<AtomicUsize>,
+    }
+
+    impl ThreadedAgent<128, TestData> for BusyAgent {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_micros(200) {
+                std::hint::spin_loop();
+            }
+            self.steps.fetch_add(1, Ordering::SeqCst);
+            let time = context.time;
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, TestData>,
+            _msg: Msg<TestData>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_auto_scaling_splits_lagging_planet() {
+        let config = HybridConfig::new(2, 16)
+            .with_time_bounds(200.0, 1.0)
+            .with_optimistic_sync(50, 10)
+            .with_world(0, 16, vec![16; 4])
+            .unwrap()
+            .with_world(1, 16, vec![16])
+            .unwrap()
+            .with_auto_scaling(1, 0, 2);
+
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        let counters = (0..4)
+            .map(|_| Arc::new(AtomicUsize::new(0)))
+            .collect::<Vec<_>>();
+        for steps in &counters {
+            let agent = BusyAgent {
+                steps: Arc::clone(steps),
+            };
+            engine.spawn_agent(0, Box::new(agent)).unwrap();
+        }
+        engine
+            .spawn_agent(1, Box::new(SimpleSchedulingAgent::new()))
+            .unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+
+        // planet 0 was split into the spare world 2, keeping every agent's schedule intact
+        assert_eq!(engine.planets.len(), 3);
+        assert_eq!(engine.planets[2].context.world_id, 2);
+        assert_eq!(engine.planets[0].agents.len(), 2);
+        assert_eq!(engine.planets[2].agents.len(), 2);
+        assert_eq!(engine.planets[2].context.agent_states.len(), 2);
+        for steps in &counters {
+            assert_eq!(steps.load(Ordering::SeqCst), 199);
+        }
+    }
+
+    // Steps busily every tick until 150, then triggers agent 3 for the next tick and stops
+    struct Alarm;
+
+    impl ThreadedAgent<128, TestData> for Alarm {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_micros(200) {
+                std::hint::spin_loop();
+            }
+            let time = context.time;
+            match time {
+                150 => Event::new(time, time, agent_id, Action::Trigger { time: 151, idx: 3 }),
+                _ => Event::new(time, time, agent_id, Action::Timeout(1)),
+            }
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, TestData>,
+            _msg: Msg<TestData>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    // Waits after every step, noting where and when it stepped
+    struct Dozer {
+        woken: Arc<Mutex<Vec<(usize, u64)>>>,
+    }
+
+    impl ThreadedAgent<128, TestData> for Dozer {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            self.woken
+                .lock()
+                .unwrap()
+                .push((context.world_id, context.time));
+            Event::new(context.time, context.time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, TestData>,
+            _msg: Msg<TestData>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_triggers_reach_split_agents() {
+        let config = HybridConfig::new(2, 16)
+            .with_time_bounds(200.0, 1.0)
+            .with_optimistic_sync(50, 10)
+            .with_world(0, 16, vec![16; 4])
+            .unwrap()
+            .with_world(1, 16, vec![16])
+            .unwrap()
+            .with_auto_scaling(1, 0, 2);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        engine.spawn_agent(0, Box::new(Alarm)).unwrap();
+        for _ in 0..2 {
+            let steps = Arc::new(AtomicUsize::new(0));
+            engine
+                .spawn_agent(0, Box::new(BusyAgent { steps }))
+                .unwrap();
+        }
+        let woken = Arc::new(Mutex::new(Vec::new()));
+        let sleeper = Dozer {
+            woken: Arc::clone(&woken),
+        };
+        engine.spawn_agent(0, Box::new(sleeper)).unwrap();
+        engine
+            .spawn_agent(1, Box::new(SimpleSchedulingAgent::new()))
+            .unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+
+        // the sleeper moved to the spare world before the alarm went off, and was woken there all the same
+        assert_eq!(engine.planets.len(), 3);
+        assert_eq!(engine.planets[2].agents.len(), 2);
+        assert_eq!(*woken.lock().unwrap(), vec![(0, 1), (2, 151)]);
+    }
+
+    // Steps every tick and records when mail reaches it
+    struct Tenant {
+        steps: Arc<AtomicUsize>,
+        mail: Arc<Mutex<BTreeSet<u64>>>,
+    }
+
+    impl ThreadedAgent<128, TestData> for Tenant {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            self.steps.fetch_add(1, Ordering::SeqCst);
+            let time = context.time;
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, TestData>,
+            msg: Msg<TestData>,
+            _agent_id: usize,
+        ) {
+            self.mail.lock().unwrap().insert(msg.recv);
+        }
+    }
+
+    // Mails agent 3 of planet 0 every 5 ticks, arriving 4 ticks later
+    struct Landlord;
+
+    impl ThreadedAgent<128, TestData> for Landlord {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            let time = context.time;
+            let msg = Msg::new(TestData { value: 1 }, time, time + 4, agent_id, Some(3));
+            context.send_mail(msg, 0).unwrap();
+            Event::new(time, time, agent_id, Action::Timeout(5))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, TestData>,
+            _msg: Msg<TestData>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_agent_migration_follows_load() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(60.0, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_world(0, 16, vec![16; 4])
+            .unwrap()
+            .with_world(1, 16, vec![16])
+            .unwrap()
+            .with_agent_migration(2.0);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        let counters = (0..4)
+            .map(|_| Arc::new(AtomicUsize::new(0)))
+            .collect::<Vec<_>>();
+        let mail = Arc::new(Mutex::new(BTreeSet::new()));
+        for steps in &counters {
+            let tenant = Tenant {
+                steps: Arc::clone(steps),
+                mail: Arc::clone(&mail),
+            };
+            engine.spawn_agent(0, Box::new(tenant)).unwrap();
+        }
+        engine.spawn_agent(1, Box::new(Landlord)).unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+
+        // planet 0 does 4 events a tick against planet 1's one every 5, so agent 3 moves at the first checkpoint
+        let routes = engine.routing_table();
+        assert_eq!(routes.resolve(0, 3), (1, 1));
+        assert!(engine.stats().planets[1].migrated_in >= 1);
+        assert_eq!(engine.planets[0].agents.len(), 4);
+        // nothing was lost in transit: every step ran once and all mail found the moved agent
+        for steps in &counters {
+            assert_eq!(steps.load(Ordering::SeqCst), 59);
+        }
+        assert_eq!(
+            *mail.lock().unwrap(),
+            (1..=11).map(|i| i * 5).collect::<BTreeSet<u64>>()
+        );
+    }
+
+    // Logs its steps and reads; the agent on planet 0 also writes to planet 1 at 2
+    type OpenerLog = Arc<Mutex<Vec<(usize, &'static str, u64)>>>;
+
+    struct Opener {
+        log: OpenerLog,
+    }
+
+    impl ThreadedAgent<128, TestData> for Opener {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            let time = context.time;
+            self.log
+                .lock()
+                .unwrap()
+                .push((context.world_id, "step", time));
+            if context.world_id == 0 && time == 2 {
+                let msg = Msg::new(TestData { value: 1 }, time, time + 1, agent_id, Some(0));
+                context.send_mail(msg, 1).unwrap();
+            }
+            Event::new(time, time, agent_id, Action::Timeout(4))
+        }
+
+        fn read_message(
+            &mut self,
+            context: &mut PlanetContext<128, TestData>,
+            msg: Msg<TestData>,
+            _agent_id: usize,
+        ) {
+            self.log
+                .lock()
+                .unwrap()
+                .push((context.world_id, "read", msg.recv));
+        }
+    }
+
+    #[test]
+    fn test_staggered_activation() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(1, 10)
+            .with_uniform_worlds(16, 1, 16)
+            .with_activation(1, 12);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        assert_eq!(engine.planets[1].now(), 12);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        for planet in 0..2 {
+            let opener = Opener {
+                log: Arc::clone(&log),
+            };
+            engine.spawn_agent(planet, Box::new(opener)).unwrap();
+        }
+        engine.schedule_all_agents(2).unwrap();
+        engine.run().unwrap();
+
+        // planet 1 sleeps through the mail sent at 2 and starts its schedule at 12
+        let mut dormant = log
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(world, ..)| *world == 1)
+            .map(|(_, kind, time)| (*kind, *time))
+            .collect::<Vec<_>>();
+        dormant.sort();
+        assert_eq!(dormant, vec![("read", 12), ("step", 12), ("step", 16)]);
+    }
+
+    #[test]
+    fn test_world_rates() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(30.0, 1.0)
+            .with_optimistic_sync(1, 10)
+            .with_uniform_worlds(16, 1, 16)
+            .with_world_rate(1, 5);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        assert_eq!(engine.planets[1].rate(), 5);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        for planet in 0..2 {
+            let opener = Opener {
+                log: Arc::clone(&log),
+            };
+            engine.spawn_agent(planet, Box::new(opener)).unwrap();
+        }
+        engine.schedule_all_agents(2).unwrap();
+        engine.run().unwrap();
+
+        // planet 1 starts on its first step after 2, reads the mail due at 3 there, and times out 4 of its steps later
+        let mut slow = log
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(world, ..)| *world == 1)
+            .map(|(_, kind, time)| (*kind, *time))
+            .collect::<Vec<_>>();
+        slow.sort();
+        slow.dedup();
+        assert_eq!(slow, vec![("read", 5), ("step", 5), ("step", 25)]);
+        let fast = log
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(world, ..)| *world == 0)
+            .count();
+        assert_eq!(fast, 7);
+
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(30.0, 1.0)
+            .with_optimistic_sync(1, 10)
+            .with_uniform_worlds(16, 1, 16)
+            .with_world_rate(1, 0);
+        assert!(matches!(
+            HybridEngine::<128, 128, 1, TestData>::create(config),
+            Err(AikaError::ConfigError(_))
+        ));
+    }
+
+    // Starts a letter on planet 0 at 1 and passes it back and forth between the planets until 10
+    struct Relay;
+
+    impl ThreadedAgent<128, TestData> for Relay {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            let time = context.time;
+            if context.world_id == 0 && time == 1 {
+                let msg = Msg::new(TestData { value: 0 }, time, time + 3, agent_id, Some(0));
+                context.send_mail(msg, 1).unwrap();
+            }
+            Event::new(time, time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            context: &mut PlanetContext<128, TestData>,
+            msg: Msg<TestData>,
+            agent_id: usize,
+        ) {
+            if msg.recv < 10 {
+                let reply = Msg::new(msg.data, msg.recv, msg.recv + 3, agent_id, Some(0));
+                context.send_mail(reply, 1 - context.world_id).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_causal_chain() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_uniform_worlds(16, 1, 16)
+            .with_causality();
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        for planet in 0..2 {
+            engine.spawn_agent(planet, Box::new(Relay)).unwrap();
+        }
+        engine.schedule(0, 0, 1).unwrap();
+        let engine = engine.run().unwrap();
+
+        let log = engine.causality().unwrap();
+        assert!(log.rollbacks().is_empty());
+        let last = log
+            .records()
+            .filter(|record| record.kind == CausalKind::Msg)
+            .max_by_key(|record| record.time)
+            .unwrap();
+        // the initial schedule, then each letter read and passed on
+        let chain = log
+            .chain(last.id)
+            .iter()
+            .map(|record| (record.kind, record.id.planet(), record.time))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            chain,
+            [
+                (CausalKind::Event, 0, 1),
+                (CausalKind::Msg, 0, 1),
+                (CausalKind::Msg, 1, 4),
+                (CausalKind::Msg, 0, 7)
+            ]
+        );
+        assert!(HybridEngine::<128, 128, 1, TestData>::create(
+            HybridConfig::new(1, 64)
+                .with_time_bounds(20.0, 1.0)
+                .with_optimistic_sync(2, 10)
+                .with_uniform_worlds(16, 1, 16)
+        )
+        .unwrap()
+        .causality()
+        .is_none());
+    }
+
+    // Sends two messages to planet 0 every step, all due at the same tick
+    struct Chatter;
+
+    impl ThreadedAgent<128, TestData> for Chatter {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            let time = context.time;
+            for value in 0..2 {
+                let value = (context.world_id * 2 + value) as u8;
+                let msg = Msg::new(TestData { value }, time, time + 2, agent_id, Some(0));
+                context.send_mail(msg, 0).unwrap();
+            }
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, TestData>,
+            _msg: Msg<TestData>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    // Folds the order it reads mail in into a digest, journaled once per tick as a `Journal` keeps one state per tick
+    #[derive(Default)]
+    struct Folder {
+        read: Vec<u8>,
+    }
+
+    impl ThreadedAgent<128, TestData> for Folder {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            let journal = &mut context.agent_states[agent_id];
+            let digest = journal.read_state::<u64>().copied().unwrap_or(0);
+            let digest = self.read.drain(..).fold(digest, |digest, value| {
+                digest.wrapping_mul(31).wrapping_add(value as u64 + 1)
+            });
+            journal.write(digest, context.time, None);
+            Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, TestData>,
+            msg: Msg<TestData>,
+            _agent_id: usize,
+        ) {
+            self.read.push(msg.data.value);
+        }
+    }
+
+    // mail takes two steps and planets may run four past GVT, so stragglers re-enter ticks planet 0 already read
+    #[test]
+    fn test_deterministic_mail_order() {
+        let run = |seed| {
+            let config = HybridConfig::new(3, 256)
+                .with_time_bounds(30.0, 1.0)
+                .with_optimistic_sync(4, 10)
+                .with_uniform_worlds(16, 1, 16)
+                .with_determinism(seed);
+            let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+            engine.spawn_agent(0, Box::new(Folder::default())).unwrap();
+            engine.spawn_agent(1, Box::new(Chatter)).unwrap();
+            engine.spawn_agent(2, Box::new(Chatter)).unwrap();
+            engine.schedule(0, 0, 1).unwrap();
+            engine.schedule(1, 0, 1).unwrap();
+            engine.schedule(2, 0, 1).unwrap();
+            let engine = engine.run().unwrap();
+            *engine.planets[0].context.agent_states[0]
+                .read_state::<u64>()
+                .unwrap()
+        };
+        let digest = run(7);
+        assert_ne!(digest, 0);
+        for _ in 0..8 {
+            assert_eq!(run(7), digest);
+        }
+
+        let config = HybridConfig::new(2, 64).with_determinism(7);
+        let config = config.with_agent_migration(2.0);
+        assert!(matches!(
+            HybridEngine::<128, 128, 1, TestData>::create(config),
+            Err(AikaError::ConfigError(_))
+        ));
+    }
+
+    // Sends two messages to planet 0 every step before 20, each due three steps later
+    struct Burst;
+
+    impl ThreadedAgent<128, TestData> for Burst {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            let time = context.time;
+            if time >= 20 {
+                return Event::new(time, time, agent_id, Action::Wait);
+            }
+            for value in 0..2 {
+                let msg = Msg::new(TestData { value }, time, time + 3, agent_id, Some(0));
+                context.send_mail(msg, 0).unwrap();
+            }
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, TestData>,
+            _msg: Msg<TestData>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    // Counts the mail it reads in a journaled total
+    struct Tally;
+
+    impl ThreadedAgent<128, TestData> for Tally {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            context: &mut PlanetContext<128, TestData>,
+            _msg: Msg<TestData>,
+            agent_id: usize,
+        ) {
+            let journal = &mut context.agent_states[agent_id];
+            let count = journal.read_state::<u64>().copied().unwrap_or(0);
+            journal.write(count + 1, context.time, None);
+        }
+    }
+
+    #[test]
+    fn test_faults_are_seeded_and_counted() {
+        let run = |faults: FaultInjection| {
+            let config = HybridConfig::new(2, 256)
+                .with_time_bounds(40.0, 1.0)
+                .with_optimistic_sync(2, 10)
+                .with_uniform_worlds(16, 1, 16)
+                .with_faults(faults);
+            let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+            engine.spawn_agent(0, Box::new(Tally)).unwrap();
+            engine.spawn_agent(1, Box::new(Burst)).unwrap();
+            engine.schedule(1, 0, 1).unwrap();
+            let engine = engine.run().unwrap();
+            let received = engine.planets[0].context.agent_states[0]
+                .read_state::<u64>()
+                .copied()
+                .unwrap_or(0);
+            (received, engine.stats().faults())
+        };
+
+        let (received, faults) = run(FaultInjection::new(3));
+        assert_eq!(received, 38);
+        assert_eq!(faults, FaultStats::default());
+
+        let params = FaultInjection::new(3)
+            .with_drop(0.25)
+            .with_duplicate(0.25)
+            .with_delay(0.5, 4);
+        let (received, faults) = run(params);
+        assert!(faults.dropped > 0 && faults.duplicated > 0 && faults.delayed > 0);
+        assert!(faults.total_delay >= faults.delayed);
+        assert_eq!(received, 38 - faults.dropped + faults.duplicated);
+        assert_eq!(run(params), (received, faults));
+
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(40.0, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_uniform_worlds(16, 1, 16)
+            .with_faults(FaultInjection::new(3).with_drop(1.5));
+        assert!(matches!(config.validate(), Err(AikaError::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_drain_unprocessed_mail() {
+        let config = HybridConfig::new(2, 256)
+            .with_time_bounds(12.0, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        engine.spawn_agent(0, Box::new(Tally)).unwrap();
+        engine.spawn_agent(1, Box::new(Burst)).unwrap();
+        engine.schedule(1, 0, 1).unwrap();
+        let mut engine = engine.run().unwrap();
+        let received = engine.planets[0].context.agent_states[0]
+            .read_state::<u64>()
+            .copied()
+            .unwrap_or(0);
+
+        // Burst sent two letters on each of steps 1 to 11, and those due at or past 12 were never read
+        let unprocessed = engine.drain_unprocessed().unwrap();
+        assert_eq!(unprocessed.len(), 6);
+        assert_eq!(received + unprocessed.len() as u64, 22);
+        assert!(unprocessed.iter().all(|mail| {
+            mail.to_world == Some(0)
+                && matches!(mail.transfer, Transfer::Msg(msg) if msg.to == Some(0) && msg.recv >= 12)
+        }));
+        assert!(engine.drain_unprocessed().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_kpis_hold_committed_work() {
+        let config = HybridConfig::new(2, 256)
+            .with_time_bounds(40.0, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_uniform_worlds(16, 1, 16)
+            .with_kpis(10);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        engine.spawn_agent(0, Box::new(Tally)).unwrap();
+        engine.spawn_agent(1, Box::new(Burst)).unwrap();
+        engine.schedule(1, 0, 1).unwrap();
+        let engine = engine.run().unwrap();
+
+        let counts = |planet: usize| {
+            engine.planets[planet]
+                .kpis()
+                .unwrap()
+                .buckets()
+                .iter()
+                .map(|bucket| {
+                    (
+                        bucket.start,
+                        bucket.events,
+                        bucket.delivered,
+                        bucket.active_agents,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        // Burst steps at 1 to 20, and its letters sent before 20 are read three steps later
+        assert_eq!(counts(1), [(0, 9, 0, 1), (10, 10, 0, 1), (20, 1, 0, 1)]);
+        assert_eq!(counts(0), [(0, 0, 12, 0), (10, 0, 20, 0), (20, 0, 6, 0)]);
+
+        let table = engine.kpi_table().unwrap();
+        let names = table
+            .columns()
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["world", "start", "events", "delivered", "active_agents"]
+        );
+        assert_eq!(table.rows().len(), 6);
+    }
+
+    #[test]
+    fn test_warmup_is_left_out() {
+        let config = HybridConfig::new(2, 256)
+            .with_time_bounds(40.0, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_uniform_worlds(16, 1, 16)
+            .with_kpis(10)
+            .with_warmup(15);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        engine.spawn_agent(0, Box::new(Tally)).unwrap();
+        engine.spawn_agent(1, Box::new(Burst)).unwrap();
+        engine.schedule(1, 0, 1).unwrap();
+        let engine = engine.run().unwrap();
+
+        let counts = |planet: usize| {
+            engine.planets[planet]
+                .kpis()
+                .unwrap()
+                .buckets()
+                .iter()
+                .map(|bucket| (bucket.start, bucket.events, bucket.delivered))
+                .collect::<Vec<_>>()
+        };
+        // only the steps and reads from 15 on are counted
+        assert_eq!(counts(1), [(10, 5, 0), (20, 1, 0)]);
+        assert_eq!(counts(0), [(10, 0, 10), (20, 0, 6)]);
+        let history = engine.planets[0]
+            .agent_history::<u64>(0)
+            .collect::<Vec<_>>();
+        assert_eq!(history.first().map(|(time, _)| *time), Some(15));
+        // the counters restart once GVT passes 15, after Burst's letters from 15 on started going out
+        assert!(engine.stats().planets[1].bundles_sent <= 10);
+
+        let config = HybridConfig::new(2, 256)
+            .with_time_bounds(40.0, 1.0)
+            .with_uniform_worlds(16, 1, 16)
+            .with_warmup(40);
+        assert!(matches!(config.validate(), Err(AikaError::ConfigError(_))));
+    }
+
+    // Tries, on each of its first four steps, to write the world state, send three letters to planet 1 and wake and
+    // trigger agent 1
+    struct Rogue;
+
+    impl ThreadedAgent<128, TestData> for Rogue {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            let time = context.time;
+            if time >= 5 {
+                return Event::new(time, time, agent_id, Action::Wait);
+            }
+            context.write_world_state(time);
+            for value in 0..3 {
+                let msg = Msg::new(TestData { value }, time, time + 3, agent_id, Some(0));
+                let _ = context.send_mail(msg, 1);
+            }
+            context.schedule_wakeup(1, time + 1);
+            context.schedule_wakeup(agent_id, time + 1);
+            Event::new(
+                time,
+                time,
+                agent_id,
+                Action::Trigger {
+                    time: time + 1,
+                    idx: 1,
+                },
+            )
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, TestData>,
+            _msg: Msg<TestData>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_sandboxed_agents_are_restricted() {
+        let config = HybridConfig::new(2, 256)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        let capabilities = Capabilities::isolated().with_send_limit(1);
+        engine
+            .spawn_sandboxed(0, Box::new(Rogue), capabilities)
+            .unwrap();
+        engine.spawn_agent(0, Box::new(Tally)).unwrap();
+        engine.spawn_agent(1, Box::new(Tally)).unwrap();
+        engine.schedule(0, 0, 1).unwrap();
+        let engine = engine.run().unwrap();
+
+        let count = |world: usize, agent: usize| {
+            engine.planets[world].context.agent_states[agent]
+                .read_state::<u64>()
+                .copied()
+                .unwrap_or(0)
+        };
+        assert_eq!(count(1, 0), 4);
+        assert!(engine.planets[0]
+            .context
+            .world_state
+            .read_state::<u64>()
+            .is_err());
+        let stats = engine.stats();
+        let kinds = stats
+            .violations()
+            .map(|violation| (violation.time, violation.kind))
+            .collect::<Vec<_>>();
+        let expected = (1..5)
+            .flat_map(|time| {
+                [
+                    ViolationKind::WorldStateWrite,
+                    ViolationKind::SendLimit { limit: 1 },
+                    ViolationKind::SendLimit { limit: 1 },
+                    ViolationKind::Trigger { target: 1 },
+                    ViolationKind::Trigger { target: 1 },
+                ]
+                .map(|kind| (time, kind))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, expected);
+    }
+
+    // Panics on its first step at or after 5
+    struct Fragile;
+
+    impl ThreadedAgent<128, TestData> for Fragile {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            let time = context.time;
+            if time >= 5 {
+                panic!("fragile agent broke");
+            }
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, TestData>,
+            _msg: Msg<TestData>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    fn fragile_engine(policy: PanicPolicy) -> HybridEngine<128, 128, 1, TestData> {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(1, 10)
+            .with_uniform_worlds(16, 2, 16)
+            .with_panic_policy(policy);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        engine
+            .spawn_agent(0, Box::new(SimpleSchedulingAgent::new()))
+            .unwrap();
+        engine.spawn_agent(1, Box::new(Fragile)).unwrap();
+        engine
+            .spawn_agent(1, Box::new(SimpleSchedulingAgent::new()))
+            .unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        engine
+    }
+
+    #[test]
+    fn test_agent_panic_isolation() {
+        let err = fragile_engine(PanicPolicy::Abort).run().err().unwrap();
+        let AikaError::AgentPanicked {
+            world,
+            agent,
+            time,
+            message,
+        } = err
+        else {
+            panic!("expected AgentPanicked, got {err:?}");
+        };
+        assert_eq!((world, agent, time), (1, 0, 5));
+        assert_eq!(message, "fragile agent broke");
+
+        // under `Remove` the planet drops the agent and finishes the run
+        let engine = fragile_engine(PanicPolicy::Remove).run().unwrap();
+        let warnings = engine.stats().warnings().copied().collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            vec![SimWarning::AgentRemoved {
+                world: 1,
+                agent: 0,
+                time: 5
+            }]
+        );
+        assert!(engine.planets.iter().all(|planet| planet.now() >= 19));
+    }
+
+    type OrderLog = Arc<Mutex<Vec<(&'static str, usize)>>>;
+
+    // On its first step, mails agent 1 for tick 4 at offset 0.5 and wakes itself at 4 + `offset`
+    struct Ticker {
+        offset: f64,
+        log: OrderLog,
+    }
+
+    impl ThreadedAgent<128, TestData> for Ticker {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            let time = context.time;
+            if time == 4 {
+                self.log.lock().unwrap().push(("event", agent_id));
+                return Event::new(time, time, agent_id, Action::Wait);
+            }
+            if agent_id == 0 {
+                let msg = Msg::new(TestData { value: 0 }, time, 4, agent_id, Some(1));
+                context.send_mail(msg.with_offset(0.5), 0).unwrap();
+            }
+            Event::new(time, time, agent_id, Action::Schedule(4)).with_offset(self.offset)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, TestData>,
+            _msg: Msg<TestData>,
+            agent_id: usize,
+        ) {
+            self.log.lock().unwrap().push(("mail", agent_id));
+        }
+    }
+
+    #[test]
+    fn test_tick_order() {
+        let run = |order| {
+            let config = HybridConfig::new(1, 64)
+                .with_time_bounds(8.0, 1.0)
+                .with_optimistic_sync(1, 10)
+                .with_uniform_worlds(16, 2, 16)
+                .with_tick_order(order);
+            let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+            let log = OrderLog::default();
+            for offset in [0.75, 0.25] {
+                let log = log.clone();
+                engine
+                    .spawn_agent(0, Box::new(Ticker { offset, log }))
+                    .unwrap();
+            }
+            engine.schedule_all_agents(1).unwrap();
+            engine.run().unwrap();
+            let log = log.lock().unwrap().clone();
+            log
+        };
+        let (mail, early, late) = (("mail", 1), ("event", 1), ("event", 0));
+        assert_eq!(run(TickOrder::MessagesFirst), vec![mail, early, late]);
+        assert_eq!(run(TickOrder::EventsFirst), vec![early, late, mail]);
+        assert_eq!(run(TickOrder::MergedByPriority), vec![early, mail, late]);
+    }
 
-    // Simple agent that just schedules timeouts (similar to st::World TestAgent)
-    struct SimpleSchedulingAgent {}
+    type TimerLog = Arc<Mutex<Vec<(usize, u64, u8)>>>;
 
-    impl SimpleSchedulingAgent {
-        pub fn new() -> Self {
-            SimpleSchedulingAgent {}
-        }
+    // Sets a timer on its first step and re-arms it from `on_timer()` twice
+    struct Reminder {
+        log: TimerLog,
     }
 
-    impl ThreadedAgent<128, TestData> for SimpleSchedulingAgent {
+    impl ThreadedAgent<128, TestData> for Reminder {
         fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
-            let time = context.time;
-            // Just timeout for 1 time unit
-            Event::new(time, time, agent_id, Action::Timeout(1))
+            context.set_timer(agent_id, 4, TestData { value: 1 });
+            Event::new(context.time, context.time, agent_id, Action::Wait)
         }
 
         fn read_message(
@@ -177,93 +2542,208 @@ mod hybrid_engine_tests {
             _msg: Msg<TestData>,
             _agent_id: usize,
         ) {
-            // Simple agent doesn't process messages
+        }
+
+        fn on_timer(
+            &mut self,
+            context: &mut PlanetContext<128, TestData>,
+            data: TestData,
+            agent_id: usize,
+        ) {
+            let entry = (context.world_id, context.time, data.value);
+            self.log.lock().unwrap().push(entry);
+            if data.value < 3 {
+                let next = TestData {
+                    value: data.value + 1,
+                };
+                context.set_timer(agent_id, 4, next);
+            }
         }
     }
 
-    #[test]
-    fn test_hybrid_engine_basic_run() {
-        // Configuration
-        const NUM_PLANETS: usize = 14;
-        const AGENTS_PER_PLANET: usize = 100;
-        const TOTAL_AGENTS: usize = NUM_PLANETS * AGENTS_PER_PLANET;
-        const EVENTS: u64 = 10000;
-        // Create configuration
-        let config = HybridConfig::new(NUM_PLANETS, 16) // 512 bytes for anti-message arena
-            .with_time_bounds(EVENTS as f64, 1.0) // terminal=1000, timestep=1.0
-            .with_optimistic_sync(50, 100) // throttle_horizon=50, checkpoint_frequency=100
-            .with_uniform_worlds(
-                16, // world state arena size
-                AGENTS_PER_PLANET,
-                16, // agent state arena size
-            );
+    // Relies on the default `on_timer()`, which hands the payload to `read_message()`
+    struct Sleeper {
+        log: TimerLog,
+    }
 
-        // Validate config
-        assert!(config.validate().is_ok());
-        assert_eq!(config.total_agents(), TOTAL_AGENTS);
+    impl ThreadedAgent<128, TestData> for Sleeper {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            context.set_timer(agent_id, 3, TestData { value: 9 });
+            Event::new(context.time, context.time, agent_id, Action::Wait)
+        }
 
-        // Create the hybrid engine
+        fn read_message(
+            &mut self,
+            context: &mut PlanetContext<128, TestData>,
+            msg: Msg<TestData>,
+            agent_id: usize,
+        ) {
+            assert_eq!(msg.from, agent_id);
+            let entry = (context.world_id, msg.recv, msg.data.value);
+            self.log.lock().unwrap().push(entry);
+        }
+    }
+
+    #[test]
+    fn test_timers_with_payloads() {
+        let config = HybridConfig::new(2, 256)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_uniform_worlds(16, 1, 16);
         let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        let log = TimerLog::default();
+        let reminder = Reminder {
+            log: Arc::clone(&log),
+        };
+        engine.spawn_agent(0, Box::new(reminder)).unwrap();
+        let sleeper = Sleeper {
+            log: Arc::clone(&log),
+        };
+        engine.spawn_agent(1, Box::new(sleeper)).unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        engine.run().unwrap();
+
+        let mut log = log.lock().unwrap().clone();
+        log.sort();
+        log.dedup();
+        assert_eq!(log, vec![(0, 5, 1), (0, 9, 2), (0, 13, 3), (1, 4, 9)]);
+    }
 
-        // Spawn agents using autobalancing
-        for _i in 0..TOTAL_AGENTS {
-            let agent = SimpleSchedulingAgent::new();
-            engine.spawn_agent_autobalance(Box::new(agent)).unwrap();
-        }
+    // Wakes every 50 ticks, counting its steps
+    struct Sparse {
+        steps: Arc<AtomicUsize>,
+    }
 
-        // Schedule initial events for each planet
-        // Each planet should have approximately AGENTS_PER_PLANET agents due to autobalancing
-        for planet_id in 0..NUM_PLANETS {
-            // Schedule first few agents in each planet to start at time 1
-            for agent_id in 0..10 {
-                // Just schedule first 5 agents per planet
-                let _ = engine.schedule(planet_id, agent_id, 1);
-            }
+    impl ThreadedAgent<128, TestData> for Sparse {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            self.steps.fetch_add(1, Ordering::Relaxed);
+            Event::new(context.time, context.time, agent_id, Action::Timeout(50))
         }
 
-        // Run the simulation
-        println!(
-            "Starting hybrid simulation with {NUM_PLANETS} planets and {TOTAL_AGENTS} total agents"
-        );
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, TestData>,
+            _msg: Msg<TestData>,
+            _agent_id: usize,
+        ) {
+        }
+    }
 
-        let result = engine.run();
+    #[test]
+    fn test_fast_forward_skips_empty_ticks() {
+        let config = HybridConfig::new(2, 256)
+            .with_time_bounds(2000.0, 1.0)
+            .with_optimistic_sync(100, 500)
+            .with_uniform_worlds(16, 2, 16)
+            .with_fast_forward(true);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        let log = TimerLog::default();
+        let steps = Arc::new(AtomicUsize::new(0));
+        let reminder = Reminder {
+            log: Arc::clone(&log),
+        };
+        engine.spawn_agent(0, Box::new(reminder)).unwrap();
+        let sleeper = Sleeper {
+            log: Arc::clone(&log),
+        };
+        engine.spawn_agent(1, Box::new(sleeper)).unwrap();
+        for planet in 0..2 {
+            let sparse = Sparse {
+                steps: Arc::clone(&steps),
+            };
+            engine.spawn_agent(planet, Box::new(sparse)).unwrap();
+        }
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+
+        // timers still stop the jump, and the sparse agents step at 1, 51, ..., 1951
+        let mut log = log.lock().unwrap().clone();
+        log.sort();
+        log.dedup();
+        assert_eq!(log, vec![(0, 5, 1), (0, 9, 2), (0, 13, 3), (1, 4, 9)]);
+        assert_eq!(steps.load(Ordering::Relaxed), 80);
+        for planet in &engine.stats().planets {
+            assert!(planet.skipped_ticks > 1500);
+        }
+    }
 
-        // Verify the simulation ran successfully
-        assert!(
-            result.is_ok(),
-            "Hybrid engine run failed: {:?}",
-            result.err()
-        );
+    // Mails planet 1 on its first five steps, three ticks ahead, then stops
+    struct FiveLetters {
+        sent: u8,
+    }
 
-        let final_engine = result.unwrap();
+    impl ThreadedAgent<128, TestData> for FiveLetters {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            let time = context.time;
+            let msg = Msg::new(
+                TestData { value: self.sent },
+                time,
+                time + 3,
+                agent_id,
+                Some(0),
+            );
+            context.send_mail(msg, 1).unwrap();
+            self.sent += 1;
+            match self.sent {
+                5 => Event::new(time, time, agent_id, Action::Wait),
+                _ => Event::new(time, time, agent_id, Action::Timeout(1)),
+            }
+        }
 
-        // Basic verification that the simulation progressed
-        println!("Simulation completed successfully");
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, TestData>,
+            _msg: Msg<TestData>,
+            _agent_id: usize,
+        ) {
+        }
+    }
 
-        // Verify we still have all our planets
-        assert_eq!(final_engine.planets.len(), NUM_PLANETS);
+    // Counts the mail it reads, and never steps on its own
+    struct Inbox {
+        read: Arc<AtomicUsize>,
+    }
 
-        // Verify agents are distributed (autobalancing should give roughly equal distribution)
-        let mut total_agent_count = 0;
-        for planet in final_engine.planets.iter() {
-            let agent_count = planet.agents.len();
-            total_agent_count += agent_count;
-            //println!("Planet {} has {} agents", i, agent_count);
+    impl ThreadedAgent<128, TestData> for Inbox {
+        fn step(&mut self, context: &mut PlanetContext<128, TestData>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Wait)
+        }
 
-            // With perfect autobalancing, each should have AGENTS_PER_PLANET
-            // Allow some variance due to integer division
-            assert!(agent_count >= AGENTS_PER_PLANET - 1);
-            assert!(agent_count <= AGENTS_PER_PLANET + 1);
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, TestData>,
+            _msg: Msg<TestData>,
+            _agent_id: usize,
+        ) {
+            self.read.fetch_add(1, Ordering::Relaxed);
         }
+    }
 
-        assert_eq!(
-            total_agent_count, TOTAL_AGENTS,
-            "Total agent count mismatch"
-        );
+    #[test]
+    fn test_quiescent_run_ends_early() {
+        // a throttle horizon short of the lookahead keeps the run free of rollbacks
+        let config = HybridConfig::new(2, 256)
+            .with_time_bounds(1e12, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_uniform_worlds(16, 1, 16)
+            .with_end_when_quiescent(true);
+        let mut engine = HybridEngine::<128, 128, 1, TestData>::create(config).unwrap();
+        engine
+            .spawn_agent(0, Box::new(FiveLetters { sent: 0 }))
+            .unwrap();
+        let read = Arc::new(AtomicUsize::new(0));
+        let inbox = Inbox {
+            read: Arc::clone(&read),
+        };
+        engine.spawn_agent(1, Box::new(inbox)).unwrap();
+        engine.schedule(0, 0, 1).unwrap();
+        let engine = engine.run().unwrap();
 
-        println!(
-            "Test passed: {TOTAL_AGENTS} agents distributed across {NUM_PLANETS} planets, with {EVENTS} events per agent"
-        );
+        assert!(engine.is_finished());
+        assert_eq!(engine.gvt(), 1_000_000_000_000);
+        assert_eq!(read.load(Ordering::Relaxed), 5);
+        // nobody stepped through the empty ticks to the terminal time
+        assert!(engine.planets.iter().all(|planet| planet.now() < 1_000_000));
     }
 }
 
@@ -272,7 +2752,8 @@ mod inter_planetary_message_tests {
     use crate::{
         agents::{PlanetContext, ThreadedAgent},
         mt::hybrid::{config::HybridConfig, HybridEngine},
-        objects::{Action, Event, Msg},
+        objects::{Action, Event, GroupId, Msg},
+        testing::run_hybrid_checked,
     };
     use bytemuck::{Pod, Zeroable};
     use std::sync::{Arc, Mutex};
@@ -599,6 +3080,35 @@ mod inter_planetary_message_tests {
         }
     }
 
+    #[test]
+    fn test_block_ledger_counts_one_way_mail() {
+        let message_log = Arc::new(Mutex::new(Vec::new()));
+        let config = HybridConfig::new(3, 512)
+            .with_time_bounds(200.0, 1.0)
+            .with_optimistic_sync(1000, 2000)
+            .with_uniform_worlds(1024, 2, 256);
+        let mut engine =
+            HybridEngine::<128, 128, 2, InterPlanetaryMessage>::create(config).unwrap();
+
+        let sender = InterPlanetarySender::new(0, 0, 1, 0, 5, 1);
+        engine.spawn_agent(0, Box::new(sender)).unwrap();
+        for planet in 1..3 {
+            let receiver = InterPlanetaryReceiver::new(planet, 0, message_log.clone());
+            engine.spawn_agent(planet, Box::new(receiver)).unwrap();
+        }
+        for planet in 0..3 {
+            engine.schedule(planet, 0, 1).unwrap();
+        }
+        let engine = engine.run().unwrap();
+
+        // all the mail went one way, from planet 0 to planet 1
+        let blocks = engine.block_ledger();
+        assert_eq!(blocks.traffic()[0][1], 5);
+        assert_eq!(blocks.totals(0).sends, 5);
+        assert_eq!(blocks.totals(1).recvs, 5);
+        assert_eq!(blocks.most_imbalanced(), Some((0, 1, 5)));
+    }
+
     #[test]
     fn test_inter_planetary_broadcast() {
         const NUM_PLANETS: usize = 4;
@@ -682,6 +3192,217 @@ mod inter_planetary_message_tests {
         }
     }
 
+    // Agent 0 of every planet broadcasts to the galaxy every 10 steps, and every agent counts what it hears
+    struct Shouter;
+
+    impl ThreadedAgent<128, InterPlanetaryMessage> for Shouter {
+        fn step(
+            &mut self,
+            context: &mut PlanetContext<128, InterPlanetaryMessage>,
+            agent_id: usize,
+        ) -> Event {
+            let time = context.time;
+            if agent_id == 0 && time % 10 == 1 && time < 50 {
+                let data = InterPlanetaryMessage {
+                    value: 1,
+                    sender_planet: context.world_id as u32,
+                    sender_agent: 0,
+                    target_planet: u32::MAX,
+                    target_agent: u32::MAX,
+                };
+                let msg = Msg::new(data, time, time + 5, agent_id, None);
+                context.broadcast_mail(msg).unwrap();
+            }
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            context: &mut PlanetContext<128, InterPlanetaryMessage>,
+            msg: Msg<InterPlanetaryMessage>,
+            agent_id: usize,
+        ) {
+            let mut heard = context.typed_state::<u64>(agent_id).unwrap();
+            heard.set(heard.get_or(0) + msg.data.value as u64);
+        }
+    }
+
+    #[test]
+    fn test_galaxy_broadcast() {
+        let config = HybridConfig::new(3, 512)
+            .with_time_bounds(100.0, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_uniform_worlds(1024, 2, 1024);
+        let mut engine =
+            HybridEngine::<128, 128, 2, InterPlanetaryMessage>::create(config).unwrap();
+        for planet in 0..3 {
+            for _ in 0..2 {
+                engine.spawn_agent(planet, Box::new(Shouter)).unwrap();
+            }
+        }
+        engine.schedule_all_agents(1).unwrap();
+        let (mut engine, ledger) = run_hybrid_checked(engine).unwrap();
+        // five broadcasts from each of the three planets, each heard once by every agent, the sender included
+        for planet in &mut engine.planets {
+            for agent in 0..2 {
+                let heard = planet.context.typed_state::<u64>(agent).unwrap().get();
+                assert_eq!(heard, Some(15));
+            }
+        }
+        let delivered = ledger
+            .pairs()
+            .map(|(_, count)| count.delivered)
+            .sum::<usize>();
+        assert_eq!(delivered, 3 * 15);
+    }
+
+    // Sends one multicast to a group on planet 1
+    struct GroupSender {
+        group: GroupId,
+    }
+
+    impl ThreadedAgent<128, InterPlanetaryMessage> for GroupSender {
+        fn step(
+            &mut self,
+            context: &mut PlanetContext<128, InterPlanetaryMessage>,
+            agent_id: usize,
+        ) -> Event {
+            let time = context.time;
+            let data = InterPlanetaryMessage {
+                value: 7,
+                sender_planet: 0,
+                sender_agent: agent_id as u32,
+                target_planet: 1,
+                target_agent: u32::MAX,
+            };
+            let msg = Msg::multicast(data, time, time + 2, agent_id, self.group);
+            context.send_mail(msg, 1).unwrap();
+            Event::new(time, time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, InterPlanetaryMessage>,
+            _msg: Msg<InterPlanetaryMessage>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_inter_planetary_multicast() {
+        let message_log = Arc::new(Mutex::new(Vec::new()));
+        let config = HybridConfig::new(2, 512)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(5, 10)
+            .with_world(0, 1024, vec![256])
+            .unwrap()
+            .with_world(1, 1024, vec![256; 4])
+            .unwrap();
+        let mut engine =
+            HybridEngine::<128, 128, 2, InterPlanetaryMessage>::create(config).unwrap();
+        let group = GroupId::named("odd");
+        engine
+            .spawn_agent(0, Box::new(GroupSender { group }))
+            .unwrap();
+        for agent_id in 0..4 {
+            let receiver = InterPlanetaryReceiver::new(1, agent_id, message_log.clone());
+            engine.spawn_agent(1, Box::new(receiver)).unwrap();
+        }
+        engine.planets[1].context.join_group(group, 1);
+        engine.planets[1].context.join_group(group, 3);
+        engine.schedule(0, 0, 1).unwrap();
+        engine.run().unwrap();
+
+        let mut receivers = message_log
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, _, msg)| msg.value == 7)
+            .map(|(planet, agent, _)| (*planet, *agent))
+            .collect::<Vec<_>>();
+        receivers.sort();
+        assert_eq!(receivers, vec![(1, 1), (1, 3)]);
+    }
+
+    // Sends one payload to an explicit list of agents
+    struct ScatterSender {
+        recipients: Vec<(usize, usize)>,
+    }
+
+    impl ThreadedAgent<128, InterPlanetaryMessage> for ScatterSender {
+        fn step(
+            &mut self,
+            context: &mut PlanetContext<128, InterPlanetaryMessage>,
+            agent_id: usize,
+        ) -> Event {
+            let time = context.time;
+            let data = InterPlanetaryMessage {
+                value: 9,
+                sender_planet: 0,
+                sender_agent: agent_id as u32,
+                target_planet: u32::MAX,
+                target_agent: u32::MAX,
+            };
+            context
+                .send_many(agent_id, &self.recipients, data, time + 2)
+                .unwrap();
+            Event::new(time, time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, InterPlanetaryMessage>,
+            _msg: Msg<InterPlanetaryMessage>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_inter_planetary_scatter() {
+        let message_log = Arc::new(Mutex::new(Vec::new()));
+        let config = HybridConfig::new(3, 512)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(5, 10)
+            .with_uniform_worlds(1024, 2, 256);
+        let mut engine =
+            HybridEngine::<128, 128, 2, InterPlanetaryMessage>::create(config).unwrap();
+        let recipients = vec![(1, 0), (2, 1), (0, 1), (1, 1), (2, 0)];
+        let sender = ScatterSender {
+            recipients: recipients.clone(),
+        };
+        engine.spawn_agent(0, Box::new(sender)).unwrap();
+        engine
+            .spawn_agent(
+                0,
+                Box::new(InterPlanetaryReceiver::new(0, 1, message_log.clone())),
+            )
+            .unwrap();
+        for planet in 1..3 {
+            for agent_id in 0..2 {
+                let receiver = InterPlanetaryReceiver::new(planet, agent_id, message_log.clone());
+                engine.spawn_agent(planet, Box::new(receiver)).unwrap();
+            }
+        }
+        engine.schedule(0, 0, 1).unwrap();
+        let engine = engine.run().unwrap();
+
+        let mut receivers = message_log
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, _, msg)| msg.value == 9)
+            .map(|(planet, agent, _)| (*planet, *agent))
+            .collect::<Vec<_>>();
+        receivers.sort();
+        let mut expected = recipients;
+        expected.sort();
+        assert_eq!(receivers, expected);
+        // one transfer per destination planet
+        assert_eq!(engine.stats().planets[0].bundles_sent, 3);
+    }
+
     #[test]
     fn test_bidirectional_inter_planetary_communication() {
         const NUM_PLANETS: usize = 2;
@@ -0,0 +1,220 @@
+//! Diagnostics for threads of a hybrid run that panic outside of an agent.
+//! Agent panics are caught by the `PanicPolicy`, but a panic in a hook or in the engine itself used to surface as a
+//! bare `AikaError::ThreadPanic`. Every thread of a `HybridEngine::run()` now enters the run's `PanicSink`, and a
+//! process-wide panic hook, installed once, records each panic on such a thread with its planet, simulation time,
+//! message and the top of its backtrace before handing over to the previously installed hook. Threads outside of a
+//! run are left to that hook alone. The reports are attached to the `ThreadPanicked` error the run returns.
+use std::{
+    backtrace::Backtrace,
+    cell::{Cell, RefCell},
+    fmt,
+    panic::PanicHookInfo,
+    sync::{Arc, Mutex, Once},
+};
+
+use crate::AikaError;
+
+/// Lines of the backtrace kept in a `PanicReport`.
+const BACKTRACE_LINES: usize = 24;
+
+static HOOK: Once = Once::new();
+
+thread_local! {
+    static SCOPE: RefCell<Option<(PanicSink, Option<usize>)>> = const { RefCell::new(None) };
+    static TIME: Cell<u64> = const { Cell::new(0) };
+}
+
+/// A panic on one of the threads of a run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PanicReport {
+    /// planet the thread ran, `None` for the `Galaxy`
+    pub world: Option<usize>,
+    pub thread: String,
+    /// local time of the planet, or GVT for the `Galaxy`, when it panicked
+    pub time: u64,
+    /// panic payload and source location
+    pub message: String,
+    /// top of the backtrace
+    pub backtrace: String,
+}
+
+impl fmt::Display for PanicReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.world {
+            Some(world) => write!(f, "planet {world}")?,
+            None => write!(f, "galaxy")?,
+        }
+        write!(
+            f,
+            " (thread '{}') panicked at time {}: {}",
+            self.thread, self.time, self.message
+        )
+    }
+}
+
+/// Describe `reports` for the `ThreadPanicked` error message.
+pub(crate) fn describe(reports: &[PanicReport]) -> String {
+    reports
+        .iter()
+        .map(|report| format!("\n  {report}"))
+        .collect()
+}
+
+/// Panic reports collected from every thread of a run.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PanicSink(Arc<Mutex<Vec<PanicReport>>>);
+
+impl PanicSink {
+    /// Report panics on the calling thread to this sink, as the thread of planet `world` or of the `Galaxy`.
+    pub(crate) fn enter(&self, world: Option<usize>) {
+        HOOK.call_once(|| {
+            let previous = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                record(info);
+                previous(info);
+            }));
+        });
+        SCOPE.with(|scope| *scope.borrow_mut() = Some((self.clone(), world)));
+        TIME.with(|time| time.set(0));
+    }
+
+    /// Take every report collected so far.
+    pub(crate) fn take(&self) -> Vec<PanicReport> {
+        self.0
+            .lock()
+            .map(|mut reports| std::mem::take(&mut *reports))
+            .unwrap_or_default()
+    }
+}
+
+/// The sink the calling thread reports to, for threads it spawns.
+pub(crate) fn current() -> Option<(PanicSink, Option<usize>)> {
+    SCOPE.with(|scope| scope.try_borrow().ok()?.clone())
+}
+
+/// Note the simulation time of the calling thread, for its reports.
+pub(crate) fn set_time(time: u64) {
+    TIME.with(|cell| cell.set(time));
+}
+
+/// The error for a thread that panicked: `ThreadPanicked` with `reports`, or a bare `ThreadPanic` without any.
+pub(crate) fn error(reports: Vec<PanicReport>) -> AikaError {
+    if reports.is_empty() {
+        AikaError::ThreadPanic
+    } else {
+        AikaError::ThreadPanicked(reports)
+    }
+}
+
+/// Take the reports of the calling thread's run, if it is in one.
+pub(crate) fn take() -> Vec<PanicReport> {
+    current().map(|(sink, _)| sink.take()).unwrap_or_default()
+}
+
+fn record(info: &PanicHookInfo<'_>) {
+    let Some((sink, world)) = current() else {
+        return;
+    };
+    let payload = info.payload();
+    let mut message = payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_string());
+    if let Some(location) = info.location() {
+        message = format!("{message} ({location})");
+    }
+    let thread = std::thread::current();
+    let report = PanicReport {
+        world,
+        thread: thread
+            .name()
+            .map_or_else(|| format!("{:?}", thread.id()), str::to_string),
+        time: TIME.with(Cell::get),
+        message,
+        backtrace: Backtrace::force_capture()
+            .to_string()
+            .lines()
+            .take(BACKTRACE_LINES)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+    let Ok(mut reports) = sink.0.lock() else {
+        return;
+    };
+    reports.push(report);
+}
+
+#[cfg(test)]
+mod tests {
+    use bytemuck::{Pod, Zeroable};
+
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        hooks::SimHook,
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+        AikaError,
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Tick {
+        value: u32,
+    }
+
+    unsafe impl Pod for Tick {}
+    unsafe impl Zeroable for Tick {}
+
+    struct Ticker;
+
+    impl ThreadedAgent<128, Tick> for Ticker {
+        fn step(&mut self, context: &mut PlanetContext<128, Tick>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Tick>,
+            _msg: Msg<Tick>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    // Breaks outside of any agent, in the planet's own thread
+    struct BrokenHook;
+
+    impl SimHook for BrokenHook {
+        fn on_event(&mut self, _world_id: usize, _agent: usize, time: u64) {
+            if time == 6 {
+                panic!("metrics exporter went away");
+            }
+        }
+    }
+
+    #[test]
+    fn test_thread_panic_is_diagnosable() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<128, 128, 1, Tick>::create(config).unwrap();
+        for planet in 0..2 {
+            engine.spawn_agent(planet, Box::new(Ticker)).unwrap();
+        }
+        engine.planets[1].set_hook(Box::new(BrokenHook));
+        engine.schedule_all_agents(1).unwrap();
+
+        let Err(AikaError::ThreadPanicked(reports)) = engine.run() else {
+            panic!("expected a ThreadPanicked");
+        };
+        assert_eq!(reports.len(), 1);
+        let report = &reports[0];
+        assert_eq!((report.world, report.time), (Some(1), 6));
+        assert!(report.message.starts_with("metrics exporter went away"));
+        assert!(report.message.contains("panics.rs"));
+        let error = AikaError::ThreadPanicked(reports.clone()).to_string();
+        assert!(error.contains("planet 1"));
+    }
+}
@@ -0,0 +1,196 @@
+//! Data-parallel agent steps within a `Planet`.
+//! With `HybridConfig::with_parallel_steps()` a `Planet` splits each tick's events in two phases. First, every agent
+//! due in the tick that offers a `ParallelStep` through `ThreadedAgent::parallel()` has `ParallelStep::prepare()`
+//! called on one of a few scoped worker threads, with nothing but its own state at hand. Then the agents `step()`
+//! one at a time in the tick's usual order, applying what they prepared through the `PlanetContext`. As the workers
+//! only touch disjoint agents and every effect on the `Planet` happens in the second phase, the run is the same
+//! however the agents were spread over the workers. A panic in `prepare()` is raised again on the `Planet` thread
+//! and handled under its `PanicPolicy`, as if the agent's next call had panicked.
+use std::{
+    any::Any,
+    panic::{catch_unwind, AssertUnwindSafe},
+    thread,
+};
+
+/// The part of an agent's step that reads and writes only its own state, such as an expensive decision, run on a
+/// worker thread ahead of `ThreadedAgent::step()`.
+pub trait ParallelStep: Send {
+    /// Do the work of the step at `time`, keeping its outcome for `step()`. An event dropped by an interrupted wait
+    /// or cut short by `Action::Break` skips the `step()`, so the outcome must be safe to leave unused. `prepare()` is
+    /// left out without parallel steps and in ticks with a single agent due, so `step()` does the work itself then.
+    fn prepare(&mut self, time: u64, agent_id: usize);
+}
+
+/// Call `prepare()` of every agent in `agents` at `time`, across at most `workers` threads. Returns the panic of
+/// each agent whose `prepare()` panicked, in agent order.
+pub(crate) fn prepare_all(
+    agents: Vec<(usize, &mut dyn ParallelStep)>,
+    time: u64,
+    workers: usize,
+) -> Vec<(usize, Box<dyn Any + Send>)> {
+    let chunk = agents.len().div_ceil(workers.max(1)).max(1);
+    let mut agents = agents;
+    let mut chunks = Vec::new();
+    while agents.len() > chunk {
+        let rest = agents.split_off(chunk);
+        chunks.push(std::mem::replace(&mut agents, rest));
+    }
+    chunks.push(agents);
+    thread::scope(|scope| {
+        let handles = chunks
+            .into_iter()
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut panics = Vec::new();
+                    for (id, agent) in chunk {
+                        let outcome = catch_unwind(AssertUnwindSafe(|| agent.prepare(time, id)));
+                        if let Err(payload) = outcome {
+                            panics.push((id, payload));
+                        }
+                    }
+                    panics
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_default())
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        thread::ThreadId,
+    };
+
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Score {
+        value: u64,
+    }
+
+    unsafe impl Pod for Score {}
+    unsafe impl Zeroable for Score {}
+
+    type Threads = Arc<Mutex<Vec<(ThreadId, ThreadId)>>>;
+
+    // Hashes its score forward, in `prepare()` if it can, noting the threads of both phases
+    struct Hasher {
+        score: u64,
+        next: u64,
+        prepared_on: Option<ThreadId>,
+        threads: Threads,
+    }
+
+    impl Hasher {
+        fn hash(&self, time: u64, agent_id: usize) -> u64 {
+            (0..1_000).fold(self.score ^ time ^ agent_id as u64, |hash, round| {
+                hash.wrapping_mul(0x100_0000_01b3).wrapping_add(round)
+            })
+        }
+    }
+
+    impl ParallelStep for Hasher {
+        fn prepare(&mut self, time: u64, agent_id: usize) {
+            self.next = self.hash(time, agent_id);
+            self.prepared_on = Some(thread::current().id());
+        }
+    }
+
+    impl ThreadedAgent<128, Score> for Hasher {
+        fn step(&mut self, context: &mut PlanetContext<128, Score>, agent_id: usize) -> Event {
+            match self.prepared_on.take() {
+                Some(worker) => {
+                    let planet = thread::current().id();
+                    self.threads.lock().unwrap().push((worker, planet));
+                }
+                None => self.next = self.hash(context.time, agent_id),
+            }
+            self.score = self.next;
+            Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Score>,
+            _msg: Msg<Score>,
+            _agent_id: usize,
+        ) {
+        }
+
+        fn parallel(&mut self) -> Option<&mut dyn ParallelStep> {
+            Some(self)
+        }
+    }
+
+    fn run(workers: Option<usize>, threads: &Threads) -> Vec<u64> {
+        let mut config = HybridConfig::new(1, 64)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_uniform_worlds(16, 1, 16);
+        if let Some(workers) = workers {
+            config = config.with_parallel_steps(workers);
+        }
+        let mut engine = HybridEngine::<128, 128, 1, Score>::create(config).unwrap();
+        for _ in 0..8 {
+            let hasher = Hasher {
+                score: 1,
+                next: 1,
+                prepared_on: None,
+                threads: threads.clone(),
+            };
+            engine.spawn_agent(0, Box::new(hasher)).unwrap();
+        }
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+        engine
+            .agents_of::<Hasher>()
+            .map(|(_, hasher)| hasher.score)
+            .collect()
+    }
+
+    #[test]
+    fn test_parallel_steps_match_sequential() {
+        let threads = Threads::default();
+        let sequential = run(None, &threads);
+        assert!(threads.lock().unwrap().is_empty());
+
+        assert_eq!(run(Some(3), &threads), sequential);
+        let threads = threads.lock().unwrap();
+        assert!(!threads.is_empty());
+        assert!(threads.iter().all(|(worker, planet)| worker != planet));
+    }
+
+    #[test]
+    fn test_panics_are_collected_by_agent() {
+        struct Faulty;
+
+        impl ParallelStep for Faulty {
+            fn prepare(&mut self, _time: u64, agent_id: usize) {
+                assert!(agent_id.is_multiple_of(2), "odd agent");
+            }
+        }
+
+        let mut agents = (0..5).map(|_| Faulty).collect::<Vec<_>>();
+        let agents = agents
+            .iter_mut()
+            .enumerate()
+            .map(|(id, agent)| (id, agent as &mut dyn ParallelStep))
+            .collect();
+        let panics = prepare_all(agents, 0, 2);
+        let ids = panics.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 3]);
+    }
+}
@@ -0,0 +1,193 @@
+//! Provenance of the parameters set through the control plane.
+//! Every `ControlAction::Parameter` a `Planet` applies is journaled with the local time it took effect. If the
+//! `Planet` later rolls back past that time, the re-executed steps already see the new value, so the change is moved
+//! back to the rollback time. A change is final once GVT reaches it. After a run, `HybridEngine::parameter_timeline()`
+//! collects the changes of every `Planet` into a `ParameterTimeline`, which answers which value was in force for an
+//! agent at any simulation time and exports as a `Table` alongside the other run artifacts.
+use std::collections::BTreeMap;
+
+/// A parameter update as applied by a `Planet`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ParameterChange {
+    pub world: usize,
+    /// `None` if the update went to every agent of the `Planet`
+    pub agent: Option<usize>,
+    pub key: usize,
+    pub value: f64,
+    /// simulation time the value is in force from
+    pub time: u64,
+}
+
+/// The parameter changes of one `Planet`, ordered by time.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ParameterJournal {
+    changes: Vec<ParameterChange>,
+}
+
+impl ParameterJournal {
+    pub(crate) fn record(&mut self, change: ParameterChange) {
+        self.changes.push(change);
+    }
+
+    /// Move every change after `time` back to it, as the steps re-executed from `time` see the new values.
+    pub(crate) fn rollback(&mut self, time: u64) {
+        for change in self.changes.iter_mut().rev() {
+            if change.time <= time {
+                break;
+            }
+            change.time = time;
+        }
+    }
+
+    /// The changes no rollback can move anymore.
+    pub(crate) fn committed(&self, gvt: u64) -> &[ParameterChange] {
+        let end = self.changes.partition_point(|change| change.time <= gvt);
+        &self.changes[..end]
+    }
+
+    pub(crate) fn changes(&self) -> &[ParameterChange] {
+        &self.changes
+    }
+}
+
+/// Every parameter change of a run, ordered by time and world.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParameterTimeline {
+    changes: Vec<ParameterChange>,
+}
+
+impl ParameterTimeline {
+    pub fn new(changes: impl IntoIterator<Item = ParameterChange>) -> Self {
+        let mut changes = changes.into_iter().collect::<Vec<_>>();
+        changes.sort_by_key(|change| (change.time, change.world));
+        Self { changes }
+    }
+
+    pub fn changes(&self) -> &[ParameterChange] {
+        &self.changes
+    }
+
+    /// The value of `key` in force for `agent` on `world` at `time`, set either for that agent or for all of them.
+    pub fn value_at(&self, world: usize, agent: usize, key: usize, time: u64) -> Option<f64> {
+        self.changes
+            .iter()
+            .take_while(|change| change.time <= time)
+            .filter(|change| {
+                change.world == world
+                    && change.key == key
+                    && change.agent.is_none_or(|to| to == agent)
+            })
+            .last()
+            .map(|change| change.value)
+    }
+
+    /// Every value in force at `time`, keyed by `(world, agent, key)`.
+    pub fn snapshot_at(&self, time: u64) -> BTreeMap<(usize, Option<usize>, usize), f64> {
+        self.changes
+            .iter()
+            .take_while(|change| change.time <= time)
+            .map(|change| ((change.world, change.agent, change.key), change.value))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        export::Table,
+        mt::hybrid::{config::HybridConfig, control::ControlAction, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Price {
+        cents: u64,
+    }
+
+    unsafe impl Pod for Price {}
+    unsafe impl Zeroable for Price {}
+
+    struct Shop;
+
+    impl ThreadedAgent<128, Price> for Shop {
+        fn step(&mut self, context: &mut PlanetContext<128, Price>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Price>,
+            _msg: Msg<Price>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    fn change(agent: Option<usize>, value: f64, time: u64) -> ParameterChange {
+        ParameterChange {
+            world: 0,
+            agent,
+            key: 0,
+            value,
+            time,
+        }
+    }
+
+    #[test]
+    fn test_parameter_timeline() {
+        let mut journal = ParameterJournal::default();
+        for (value, time) in [(1.0, 3), (2.0, 7), (3.0, 9)] {
+            journal.record(change(None, value, time));
+        }
+        journal.rollback(5);
+        let times = journal.changes().iter().map(|c| c.time).collect::<Vec<_>>();
+        assert_eq!(times, vec![3, 5, 5]);
+        assert_eq!(journal.committed(4).len(), 1);
+
+        let timeline = ParameterTimeline::new([change(Some(1), 0.5, 4), change(None, 2.0, 2)]);
+        assert_eq!(timeline.value_at(0, 1, 0, 1), None);
+        assert_eq!(timeline.value_at(0, 1, 0, 3), Some(2.0));
+        assert_eq!(timeline.value_at(0, 1, 0, 4), Some(0.5));
+        assert_eq!(timeline.value_at(0, 0, 0, 4), Some(2.0));
+        assert_eq!(timeline.snapshot_at(4).len(), 2);
+
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(10.0, 1.0)
+            .with_optimistic_sync(4, 8)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<128, 128, 1, Price>::create(config).unwrap();
+        for planet in 0..2 {
+            engine.spawn_agent(planet, Box::new(Shop)).unwrap();
+            engine.schedule(planet, 0, 1).unwrap();
+        }
+        let control = engine.control_handle();
+        let markup = |agent, value| ControlAction::Parameter {
+            agent,
+            key: 7,
+            value,
+        };
+        control.send(0, markup(None, 1.1)).unwrap();
+        control.send(1, markup(Some(0), 1.3)).unwrap();
+        let engine = engine.run().unwrap();
+
+        let timeline = engine.parameter_timeline();
+        assert_eq!(timeline.changes().len(), 2);
+        assert_eq!(timeline.value_at(0, 0, 7, 6), Some(1.1));
+        assert_eq!(timeline.value_at(1, 0, 7, 6), Some(1.3));
+        assert_eq!(timeline.value_at(1, 0, 8, 6), None);
+        let mut csv = Vec::new();
+        Table::from_parameters(&timeline)
+            .write_csv(&mut csv)
+            .unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(
+            csv,
+            "world,agent,key,value,time\n0,-1,7,1.1,0\n1,0,7,1.3,0\n"
+        );
+    }
+}
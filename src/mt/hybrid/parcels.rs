@@ -0,0 +1,271 @@
+//! Heap payloads for interplanetary mail, for models whose messages aren't `Pod`, such as `String`s, `Vec`s or enums
+//! with data. An engine run with `Parcel` as its message type and `HybridConfig::with_heap_payloads()` keeps the
+//! payloads in a `ParcelStore` shared by every `Planet`, and only the `Parcel` handle travels in the `Msg` envelope,
+//! through the same wheels, anti-messages and rollbacks as any other payload. Agents send with
+//! `PlanetContext::send_parcel()` and read with `PlanetContext::open_parcel()`, which hands out an `Arc<P>`, so a
+//! step re-executed after a rollback reads the same value again. A `Parcel` is named after its sender's world, send
+//! time and position among the tick's mail, so the re-execution overwrites the payload it sent the first time, and a
+//! sender drops its payloads once GVT passes their receive time.
+use std::{
+    any::{type_name, Any},
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::AikaError;
+
+/// Handle to a payload in the `ParcelStore`, sent as the data of a `Msg`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(C)]
+pub struct Parcel {
+    pub world: u64,
+    pub sent: u64,
+    pub seq: u64,
+}
+
+unsafe impl Pod for Parcel {}
+unsafe impl Zeroable for Parcel {}
+
+struct Entry {
+    /// latest receive time of the `Msg`s carrying it, `None` until one is sent
+    recv: Option<u64>,
+    payload: Arc<dyn Any + Send + Sync>,
+}
+
+/// Payloads of the `Parcel`s in flight, shared by every `Planet` of a run.
+#[derive(Clone, Default)]
+pub struct ParcelStore(Arc<Mutex<BTreeMap<Parcel, Entry>>>);
+
+impl ParcelStore {
+    /// Hold `payload` as `parcel`. A payload sent under the same name before a rollback is replaced, but its receive
+    /// time is kept until the `Msg` that carried it has been cancelled.
+    pub(crate) fn put<P: Any + Send + Sync>(
+        &self,
+        parcel: Parcel,
+        payload: P,
+    ) -> Result<(), AikaError> {
+        let mut parcels = self.lock()?;
+        let recv = parcels.get(&parcel).and_then(|entry| entry.recv);
+        let entry = Entry {
+            recv,
+            payload: Arc::new(payload),
+        };
+        parcels.insert(parcel, entry);
+        Ok(())
+    }
+
+    /// Note that a `Msg` carrying `parcel` is due at `recv`. `None` means it was lost or never sent, which drops the
+    /// payload if no other `Msg` carries it.
+    pub(crate) fn settle(&self, parcel: &Parcel, recv: Option<u64>) -> Result<(), AikaError> {
+        let mut parcels = self.lock()?;
+        let Some(entry) = parcels.get_mut(parcel) else {
+            return Ok(());
+        };
+        match recv {
+            Some(recv) => entry.recv = Some(entry.recv.map_or(recv, |latest| latest.max(recv))),
+            None if entry.recv.is_none() => {
+                parcels.remove(parcel);
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// The payload of `parcel`, if it is still held and is a `P`.
+    pub fn open<P: Any + Send + Sync>(&self, parcel: &Parcel) -> Result<Arc<P>, AikaError> {
+        let payload = self
+            .lock()?
+            .get(parcel)
+            .map(|entry| Arc::clone(&entry.payload))
+            .ok_or(AikaError::MissingParcel(*parcel))?;
+        payload.downcast::<P>().map_err(|_| {
+            AikaError::SchemaMismatch(format!("parcel doesn't hold a `{}`", type_name::<P>()))
+        })
+    }
+
+    /// Drop the payloads `world` sent that were due before `gvt`, which no rollback can read again.
+    pub(crate) fn prune(&self, world: usize, gvt: u64) -> Result<(), AikaError> {
+        let start = Parcel {
+            world: world as u64,
+            ..Parcel::default()
+        };
+        let end = Parcel {
+            world: world as u64 + 1,
+            ..Parcel::default()
+        };
+        let mut parcels = self.lock()?;
+        let settled = parcels
+            .range(start..end)
+            .filter(|(_, entry)| entry.recv.is_some_and(|recv| recv < gvt))
+            .map(|(parcel, _)| *parcel)
+            .collect::<Vec<_>>();
+        for parcel in settled {
+            parcels.remove(&parcel);
+        }
+        Ok(())
+    }
+
+    /// Number of payloads held.
+    pub fn len(&self) -> usize {
+        self.0.lock().map_or(0, |parcels| parcels.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, BTreeMap<Parcel, Entry>>, AikaError> {
+        self.0.lock().map_err(|_| AikaError::PoisonedLock)
+    }
+}
+
+impl fmt::Debug for ParcelStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ParcelStore").field(&self.len()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[test]
+    fn test_store_holds_payloads_until_gvt() {
+        let store = ParcelStore::default();
+        let parcel = |world: u64, seq: u64| Parcel {
+            world,
+            sent: 4,
+            seq,
+        };
+        store.put(parcel(0, 0), "far".to_string()).unwrap();
+        store.settle(&parcel(0, 0), Some(9)).unwrap();
+        store.put(parcel(0, 1), vec![1u8, 2]).unwrap();
+        store.settle(&parcel(0, 1), Some(6)).unwrap();
+        store.put(parcel(1, 0), 7u64).unwrap();
+        store.settle(&parcel(1, 0), Some(6)).unwrap();
+        // lost before any `Msg` carried it
+        store.put(parcel(0, 2), 'x').unwrap();
+        store.settle(&parcel(0, 2), None).unwrap();
+        assert_eq!(store.len(), 3);
+
+        assert_eq!(*store.open::<Vec<u8>>(&parcel(0, 1)).unwrap(), vec![1, 2]);
+        assert!(matches!(
+            store.open::<String>(&parcel(0, 1)),
+            Err(AikaError::SchemaMismatch(_))
+        ));
+        assert!(matches!(
+            store.open::<char>(&parcel(0, 2)),
+            Err(AikaError::MissingParcel(_))
+        ));
+
+        // a re-sent payload keeps the receive time of the `Msg` that carried its predecessor
+        store.put(parcel(0, 0), "near".to_string()).unwrap();
+        store.settle(&parcel(0, 0), None).unwrap();
+        store.prune(0, 8).unwrap();
+        assert_eq!(store.len(), 2);
+        assert_eq!(*store.open::<String>(&parcel(0, 0)).unwrap(), "near");
+        assert_eq!(*store.open::<u64>(&parcel(1, 0)).unwrap(), 7);
+    }
+
+    // Tells planet 0 a rumor, with the steps it has heard so far, every step before 10
+    struct Gossip {
+        heard: Vec<u64>,
+    }
+
+    impl ThreadedAgent<128, Parcel> for Gossip {
+        fn step(&mut self, context: &mut PlanetContext<128, Parcel>, agent_id: usize) -> Event {
+            let time = context.time;
+            if time >= 10 {
+                return Event::new(time, time, agent_id, Action::Wait);
+            }
+            self.heard.push(time);
+            let rumor = (format!("rumor {time}"), self.heard.clone());
+            let msg = Msg::new(Parcel::default(), time, time + 3, agent_id, Some(0));
+            context.send_parcel(rumor, msg, 0).unwrap();
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Parcel>,
+            _msg: Msg<Parcel>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    struct Listener {
+        rumors: Arc<Mutex<Vec<(String, usize)>>>,
+    }
+
+    impl ThreadedAgent<128, Parcel> for Listener {
+        fn step(&mut self, context: &mut PlanetContext<128, Parcel>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            context: &mut PlanetContext<128, Parcel>,
+            msg: Msg<Parcel>,
+            _agent_id: usize,
+        ) {
+            let rumor = context.open_parcel::<(String, Vec<u64>)>(&msg).unwrap();
+            assert!(context.open_parcel::<String>(&msg).is_err());
+            self.rumors
+                .lock()
+                .unwrap()
+                .push((rumor.0.clone(), rumor.1.len()));
+        }
+    }
+
+    #[test]
+    fn test_planets_exchange_heap_payloads() {
+        let config = HybridConfig::new(2, 256)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_uniform_worlds(16, 1, 16)
+            .with_heap_payloads();
+        let mut engine = HybridEngine::<128, 128, 1, Parcel>::create(config).unwrap();
+        let rumors = Arc::new(Mutex::new(Vec::new()));
+        let listener = Listener {
+            rumors: rumors.clone(),
+        };
+        engine.spawn_agent(0, Box::new(listener)).unwrap();
+        engine
+            .spawn_agent(1, Box::new(Gossip { heard: Vec::new() }))
+            .unwrap();
+        engine.schedule(1, 0, 1).unwrap();
+        engine.run().unwrap();
+
+        let mut rumors = rumors.lock().unwrap().clone();
+        rumors.sort_by_key(|(_, heard)| *heard);
+        rumors.dedup();
+        let expected = (1..10)
+            .map(|time| (format!("rumor {time}"), time as usize))
+            .collect::<Vec<_>>();
+        assert_eq!(rumors, expected);
+
+        // without the heap, there is nowhere to put the payload
+        let config = HybridConfig::new(1, 64)
+            .with_time_bounds(4.0, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<128, 128, 1, Parcel>::create(config).unwrap();
+        let context = &mut engine.planets[0].context;
+        let msg = Msg::new(Parcel::default(), 0, 1, 0, Some(0));
+        assert!(matches!(
+            context.send_parcel(vec![0u8], msg, 0),
+            Err(AikaError::ConfigError(_))
+        ));
+    }
+}
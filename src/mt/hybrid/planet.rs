@@ -2,14 +2,17 @@
 //! Each `Planet` runs independently with its own local time, handling agent execution, local
 //! messaging, and rollback operations when causality violations are detected.
 use std::{
-    cmp::Reverse,
-    collections::{BTreeSet, BinaryHeap},
+    any::Any,
+    collections::{BTreeMap, BTreeSet},
+    ops::RangeBounds,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
     sync::{
-        atomic::{AtomicU64, AtomicUsize, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc::Sender,
+        Arc, Mutex,
     },
-    thread::sleep,
-    time::Duration,
+    thread::{sleep, JoinHandle},
+    time::{Duration, Instant},
 };
 
 use bytemuck::{Pod, Zeroable};
@@ -19,10 +22,68 @@ use mesocarp::{
     scheduling::{htw::Clock, Scheduleable},
 };
 
+#[cfg(feature = "agent-profiling")]
+use crate::analysis::profiling::{self, AgentProfiler, Call};
+#[cfg(feature = "rollback-export")]
+use crate::analysis::rollbacks::{RollbackExporter, RollbackRecord};
+#[cfg(feature = "validate-causality")]
+use crate::mt::hybrid::vclock::{CausalValidator, Incarnations};
 use crate::{
-    agents::{PlanetContext, ThreadedAgent},
-    objects::{Action, AntiMsg, Event, LocalEventSystem, LocalMailSystem, Mail, Msg, Transfer},
+    agents::{
+        interrupts::Wake,
+        rpc::{Reply, Rpc},
+        sandbox::Capabilities,
+        subscriptions::NotifyAt,
+        PlanetContext, ThreadedAgent,
+    },
+    analysis::{
+        causality::{CausalKind, CausalityLog},
+        critical_path::{CausalLog, CausalNode},
+        history::journal_history,
+        kpi::KpiRecorder,
+        occupancy::OccupancyRecorder,
+    },
+    dynclock::ClockSizing,
+    extensions::WorldExtension,
+    hooks::SimHook,
+    mt::hybrid::{
+        affinity,
+        backoff::Wakeup,
+        barrier::Barriers,
+        batch::{MailBatching, Outbox},
+        config::{PanicPolicy, TickOrder},
+        control::{ControlAction, ControlPlane, ControlRecord},
+        cut::PlanetCut,
+        debug::{DebugFilter, DebugKind, DebugSpan},
+        directory::Directory,
+        faults::{FaultInjection, FaultInjector},
+        galaxy::Parking,
+        inspect::PlanetState,
+        link::{LinkModel, Links},
+        memory::{MemoryLimits, MemoryUsage},
+        migration::{Departed, Migrant, MigrationSupport},
+        panics, parallel,
+        params::{ParameterChange, ParameterJournal},
+        reduce::Reductions,
+        reports::{RollbackAggregator, RollbackDetail, RollbackReport, RollbackReporting},
+        routing::RoutingTable,
+        shared::SharedData,
+        spawn::AgentTemplate,
+        stats::{PlanetStats, SimWarning},
+        throttle::{AdaptiveThrottle, ThrottleController},
+        topology::{RouteChange, Topology},
+    },
+    objects::{
+        align, checked_later, clock_at, clock_next_due, order_mail_canonically,
+        order_tick_canonically, order_within_tick, skip_clock_to, Action, AntiMsg, CausalId,
+        DeliveryFailure, Event, LocalEventSystem, LocalMailSystem, Mail, MailBundle, Msg,
+        TickSequences, Transfer,
+    },
+    overflow::OverflowStrategy,
     st::TimeInfo,
+    testing::{Address, MessageLedger},
+    time::{SimTime, TimeScale},
+    tracing::TraceRecorder,
     AikaError,
 };
 
@@ -32,8 +93,21 @@ pub struct RegistryOutput<const SLOTS: usize, MessageType: Pod + Zeroable + Clon
     counter: Arc<AtomicUsize>,
     lvt: Arc<AtomicU64>,
     checkpoint: Arc<AtomicU64>,
-    user: ThreadedMessengerUser<SLOTS, Mail<MessageType>>,
+    user: ThreadedMessengerUser<SLOTS, MailBundle<MessageType>>,
     world_id: usize,
+    active: Arc<AtomicBool>,
+    agent_count: Arc<AtomicUsize>,
+    split_request: Arc<AtomicUsize>,
+    events: Arc<AtomicU64>,
+    quiet: Arc<AtomicU64>,
+    migrate_request: Arc<AtomicUsize>,
+    halt: Arc<AtomicBool>,
+    wakeup: Arc<Wakeup>,
+    parking: Arc<Parking>,
+    shared: Option<SharedData>,
+    failures: Arc<Mutex<Vec<DeliveryFailure<MessageType>>>>,
+    #[cfg(feature = "validate-causality")]
+    incarnations: Arc<Incarnations>,
 }
 
 impl<const SLOTS: usize, MessageType: Pod + Zeroable + Clone> RegistryOutput<SLOTS, MessageType> {
@@ -42,7 +116,7 @@ impl<const SLOTS: usize, MessageType: Pod + Zeroable + Clone> RegistryOutput<SLO
         lvt: Arc<AtomicU64>,
         counter: Arc<AtomicUsize>,
         checkpoint: Arc<AtomicU64>,
-        user: ThreadedMessengerUser<SLOTS, Mail<MessageType>>,
+        user: ThreadedMessengerUser<SLOTS, MailBundle<MessageType>>,
         world_id: usize,
     ) -> Self {
         Self {
@@ -52,6 +126,137 @@ impl<const SLOTS: usize, MessageType: Pod + Zeroable + Clone> RegistryOutput<SLO
             checkpoint,
             user,
             world_id,
+            active: Arc::new(AtomicBool::new(true)),
+            agent_count: Arc::new(AtomicUsize::new(0)),
+            split_request: Arc::new(AtomicUsize::new(0)),
+            events: Arc::new(AtomicU64::new(0)),
+            quiet: Arc::new(AtomicU64::new(0)),
+            migrate_request: Arc::new(AtomicUsize::new(0)),
+            halt: Arc::new(AtomicBool::new(false)),
+            wakeup: Arc::new(Wakeup::default()),
+            parking: Arc::default(),
+            shared: None,
+            failures: Arc::new(Mutex::new(Vec::new())),
+            #[cfg(feature = "validate-causality")]
+            incarnations: Arc::default(),
+        }
+    }
+
+    /// Share the rollbacks of every planet of the run, for validating causality.
+    #[cfg(feature = "validate-causality")]
+    pub(crate) fn with_incarnations(mut self, incarnations: Arc<Incarnations>) -> Self {
+        self.incarnations = incarnations;
+        self
+    }
+
+    /// Share the flag that stops every thread of the run once one of them fails.
+    pub(crate) fn with_halt(mut self, halt: Arc<AtomicBool>) -> Self {
+        self.halt = halt;
+        self
+    }
+
+    /// Share the doorbell that wakes the `Galaxy` thread when the `Planet` makes progress.
+    pub(crate) fn with_wakeup(mut self, wakeup: Arc<Wakeup>) -> Self {
+        self.wakeup = wakeup;
+        self
+    }
+
+    /// Share where the threads of the run stop, see `HybridEngine::advance()`.
+    pub(crate) fn with_parking(mut self, parking: Arc<Parking>) -> Self {
+        self.parking = parking;
+        self
+    }
+
+    /// Hand the run's shared read-only data to the `Planet`.
+    pub(crate) fn with_shared_data(mut self, shared: Option<SharedData>) -> Self {
+        self.shared = shared;
+        self
+    }
+
+    pub fn world_id(&self) -> usize {
+        self.world_id
+    }
+
+    pub(crate) fn active_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.active)
+    }
+
+    pub(crate) fn agent_count_handle(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.agent_count)
+    }
+
+    pub(crate) fn split_request_handle(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.split_request)
+    }
+
+    pub(crate) fn events_handle(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.events)
+    }
+
+    pub(crate) fn quiet_handle(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.quiet)
+    }
+
+    pub(crate) fn migrate_request_handle(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.migrate_request)
+    }
+
+    pub(crate) fn failures_handle(&self) -> Arc<Mutex<Vec<DeliveryFailure<MessageType>>>> {
+        Arc::clone(&self.failures)
+    }
+
+    pub(crate) fn gvt_handle(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.gvt)
+    }
+
+    pub(crate) fn counter_handle(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.counter)
+    }
+
+    pub(crate) fn halt_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.halt)
+    }
+
+    pub(crate) fn wakeup_handle(&self) -> Arc<Wakeup> {
+        Arc::clone(&self.wakeup)
+    }
+
+    pub(crate) fn lvt_handle(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.lvt)
+    }
+}
+
+/// Handle to a `Planet` thread, which hands the `Planet` back once it has run to completion.
+pub type PlanetHandle<
+    const INTER_SLOTS: usize,
+    const CLOCK_SLOTS: usize,
+    const CLOCK_HEIGHT: usize,
+    MessageType,
+> = JoinHandle<Result<Planet<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>, AikaError>>;
+
+/// Shared pools a `Planet` needs to split itself into a spare world at runtime.
+pub(crate) struct ScalingSupport<
+    const INTER_SLOTS: usize,
+    const CLOCK_SLOTS: usize,
+    const CLOCK_HEIGHT: usize,
+    MessageType: Pod + Zeroable + Clone,
+> {
+    pub(crate) spares: Arc<Mutex<Vec<RegistryOutput<INTER_SLOTS, MessageType>>>>,
+    pub(crate) spawned:
+        Arc<Mutex<Vec<PlanetHandle<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>>>>,
+}
+
+impl<
+        const INTER_SLOTS: usize,
+        const CLOCK_SLOTS: usize,
+        const CLOCK_HEIGHT: usize,
+        MessageType: Pod + Zeroable + Clone,
+    > Clone for ScalingSupport<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>
+{
+    fn clone(&self) -> Self {
+        Self {
+            spares: Arc::clone(&self.spares),
+            spawned: Arc::clone(&self.spawned),
         }
     }
 }
@@ -72,6 +277,87 @@ pub struct Planet<
     next_checkpoint: Arc<AtomicU64>,
     local_time: Arc<AtomicU64>,
     throttle_horizon: u64,
+    causal_log: Option<CausalLog>,
+    world_arena_size: usize,
+    anti_msg_arena_size: usize,
+    agent_count: Arc<AtomicUsize>,
+    split_request: Arc<AtomicUsize>,
+    scaling: Option<ScalingSupport<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>>,
+    /// agents moved off this `Planet` by a split, as `(first index, end index, world id)`
+    splits: Vec<(usize, usize, usize)>,
+    control: ControlPlane,
+    hook: Option<Box<dyn SimHook>>,
+    stats: PlanetStats,
+    max_rollback_depth: Option<u64>,
+    /// sender worlds whose stragglers exceeded `max_rollback_depth`, with the GVT at which they are trusted again
+    conservative_links: BTreeMap<usize, u64>,
+    rollback_reports: Option<RollbackAggregator>,
+    /// moves `throttle_horizon` with the rollback rate, if enabled
+    throttle: Option<ThrottleController>,
+    /// whether to jump over ticks with nothing to do, see `set_fast_forward()`
+    fast_forward: bool,
+    /// events processed since the `Galaxy` last sampled the load
+    events: Arc<AtomicU64>,
+    /// whether to tell the `Galaxy` when nothing is left to do, see `set_end_when_quiescent()`
+    end_when_quiescent: bool,
+    /// `2 * epoch + 1` while nothing is left to do, `2 * epoch` otherwise, where the epoch counts the wake-ups
+    quiet: Arc<AtomicU64>,
+    migrate_request: Arc<AtomicUsize>,
+    /// set by whichever thread of the run fails first, stopping the others
+    halt: Arc<AtomicBool>,
+    /// rung whenever this `Planet` publishes a new local time or sends mail
+    wakeup: Arc<Wakeup>,
+    /// where the threads of the run stop, see `HybridEngine::advance()`
+    parking: Arc<Parking>,
+    /// mail of this `Planet` the `Galaxy` couldn't deliver
+    failures: Arc<Mutex<Vec<DeliveryFailure<MessageType>>>>,
+    migration: Option<MigrationSupport<INTER_SLOTS, MessageType>>,
+    /// events processed per agent, for picking which agent to migrate
+    agent_load: Vec<u64>,
+    /// indices of agents that migrated away, now holding a `Departed` placeholder
+    departed: BTreeSet<usize>,
+    panic_policy: PanicPolicy,
+    tick_order: TickOrder,
+    /// worker threads for the parallel part of the agents' steps, see `set_parallel_steps()`
+    parallel_steps: Option<usize>,
+    /// limits on the memory held, see `set_memory_limits()`
+    memory_limits: Option<MemoryLimits>,
+    /// whether the last memory sample lay past the soft limit
+    memory_pressed: bool,
+    /// core this thread pins itself to when the run starts
+    core: Option<usize>,
+    templates: Vec<AgentTemplate<INTER_SLOTS, MessageType>>,
+    /// `(local time, index)` of every agent spawned from a template, in spawn order
+    spawn_log: Vec<(u64, usize)>,
+    /// seed for canonical tick ordering, if running deterministically
+    deterministic: Option<u64>,
+    /// mail read and events run since GVT when running deterministically, to run a re-entered tick again in full
+    read_log: Vec<Msg<MessageType>>,
+    ran_log: Vec<Event>,
+    /// simulation time the `Planet` comes online at, staying dormant until GVT reaches it
+    activation: u64,
+    /// base timesteps per step of this `Planet`, see `set_rate()`
+    rate: u64,
+    /// end of the warm-up, see `set_warmup()`
+    warmup: u64,
+    /// whether GVT has passed the warm-up and the counters were reset
+    warmed_up: bool,
+    debug: DebugFilter,
+    /// indices of agents removed after panicking, now holding a `Departed` placeholder
+    failed: BTreeSet<usize>,
+    sequences: TickSequences,
+    trace: Option<TraceRecorder>,
+    occupancy: Option<OccupancyRecorder>,
+    kpis: Option<KpiRecorder>,
+    /// consistent cuts still to record, by time
+    cuts: Vec<(u64, Sender<PlanetCut<MessageType>>)>,
+    /// routes shared with the `Galaxy`, with the number of changes already applied here
+    topology: Option<(Arc<Topology>, usize)>,
+    parameters: ParameterJournal,
+    #[cfg(feature = "rollback-export")]
+    rollback_export: Option<RollbackExporter>,
+    #[cfg(feature = "agent-profiling")]
+    profiler: Option<AgentProfiler>,
 }
 
 unsafe impl<
@@ -107,22 +393,86 @@ impl<
         anti_msg_arena_size: usize,
         registry: RegistryOutput<INTER_SLOTS, MessageType>,
     ) -> Result<Self, AikaError> {
+        let mut context = PlanetContext::new(
+            world_arena_size,
+            anti_msg_arena_size,
+            registry.user,
+            registry.world_id,
+            registry.counter,
+        );
+        context.timestep = timestep;
+        context.shared = registry.shared;
+        #[cfg(feature = "validate-causality")]
+        {
+            context.causal = CausalValidator::new(registry.world_id, registry.incarnations);
+        }
         Ok(Self {
             agents: Vec::new(),
-            context: PlanetContext::new(
-                world_arena_size,
-                anti_msg_arena_size,
-                registry.user,
-                registry.world_id,
-                registry.counter,
-            ),
-            time_info: TimeInfo { terminal, timestep },
+            context,
+            time_info: TimeInfo::new(terminal, timestep),
             event_system: LocalEventSystem::<CLOCK_SLOTS, CLOCK_HEIGHT>::new()?,
             local_messages: LocalMailSystem::new()?,
             gvt: registry.gvt,
             next_checkpoint: registry.checkpoint,
             local_time: registry.lvt,
             throttle_horizon,
+            causal_log: None,
+            world_arena_size,
+            anti_msg_arena_size,
+            agent_count: registry.agent_count,
+            split_request: registry.split_request,
+            scaling: None,
+            splits: Vec::new(),
+            control: ControlPlane::new(),
+            hook: None,
+            stats: PlanetStats {
+                world_id: registry.world_id,
+                ..Default::default()
+            },
+            max_rollback_depth: None,
+            conservative_links: BTreeMap::new(),
+            rollback_reports: None,
+            throttle: None,
+            fast_forward: false,
+            events: registry.events,
+            end_when_quiescent: false,
+            quiet: registry.quiet,
+            migrate_request: registry.migrate_request,
+            halt: registry.halt,
+            wakeup: registry.wakeup,
+            parking: registry.parking,
+            failures: registry.failures,
+            migration: None,
+            agent_load: Vec::new(),
+            departed: BTreeSet::new(),
+            panic_policy: PanicPolicy::Abort,
+            tick_order: TickOrder::MessagesFirst,
+            parallel_steps: None,
+            memory_limits: None,
+            memory_pressed: false,
+            core: None,
+            templates: Vec::new(),
+            spawn_log: Vec::new(),
+            deterministic: None,
+            read_log: Vec::new(),
+            ran_log: Vec::new(),
+            activation: 0,
+            rate: 1,
+            warmup: 0,
+            warmed_up: true,
+            debug: DebugFilter::default(),
+            failed: BTreeSet::new(),
+            sequences: TickSequences::default(),
+            trace: None,
+            occupancy: None,
+            kpis: None,
+            cuts: Vec::new(),
+            topology: None,
+            parameters: ParameterJournal::default(),
+            #[cfg(feature = "rollback-export")]
+            rollback_export: None,
+            #[cfg(feature = "agent-profiling")]
+            profiler: None,
         })
     }
     /// Creates a new `Planet` from registry, time, and HybridConfig information.
@@ -133,300 +483,2451 @@ impl<
         throttle_horizon: u64,
         registry: RegistryOutput<INTER_SLOTS, MessageType>,
     ) -> Result<Self, AikaError> {
-        let mut context = PlanetContext::new(
+        let mut planet = Self::create(
+            terminal,
+            timestep,
+            throttle_horizon,
             world_consts.0,
             world_consts.1,
-            registry.user,
-            registry.world_id,
-            registry.counter,
-        );
+            registry,
+        )?;
         for i in world_consts.2 {
-            context.agent_states.push(Journal::init(*i));
+            planet.context.agent_states.push(Journal::init(*i));
         }
-        Ok(Self {
-            agents: Vec::new(),
-            context,
-            time_info: TimeInfo { terminal, timestep },
-            event_system: LocalEventSystem::<CLOCK_SLOTS, CLOCK_HEIGHT>::new()?,
-            local_messages: LocalMailSystem::new()?,
-            gvt: registry.gvt,
-            next_checkpoint: registry.checkpoint,
-            local_time: registry.lvt,
-            throttle_horizon,
-        })
+        Ok(planet)
     }
 
-    fn commit(&mut self, event: Event) {
-        self.event_system.insert(event)
+    /// Register a `SimHook` called on every event, rollback and checkpoint block. Planets split off at runtime
+    /// start without one.
+    pub fn set_hook(&mut self, hook: Box<dyn SimHook>) {
+        self.hook = Some(hook);
+    }
+
+    /// Limit how far a single straggler may roll this `Planet` back before its link is run conservatively.
+    pub fn set_max_rollback_depth(&mut self, depth: Option<u64>) {
+        self.max_rollback_depth = depth;
+    }
+
+    /// Move the throttle horizon within the bounds of `throttle` as the rollback rate changes.
+    pub fn set_adaptive_throttle(&mut self, throttle: AdaptiveThrottle) {
+        let controller = ThrottleController::new(throttle, self.throttle_horizon);
+        self.throttle_horizon = controller.horizon();
+        self.throttle = Some(controller);
+    }
+
+    /// Collapse rollbacks into one report per GVT window, see `RollbackReporting`.
+    pub fn set_rollback_reporting(&mut self, reporting: RollbackReporting) {
+        self.rollback_reports = Some(RollbackAggregator::new(self.context.world_id, reporting));
+    }
+
+    /// Choose what happens when an agent panics inside `step()` or while reading mail.
+    pub fn set_panic_policy(&mut self, policy: PanicPolicy) {
+        self.panic_policy = policy;
     }
 
-    fn commit_mail(&mut self, msg: Msg<MessageType>) {
-        let msg = self.local_messages.schedule.insert(msg);
-        if msg.is_err() {
-            self.local_messages
-                .overflow
-                .push(Reverse(msg.err().unwrap()));
+    /// Schedule events on a `DynClock` of `sizing` instead of the const generic wheels. Call it before scheduling
+    /// anything.
+    pub fn set_dynamic_clock(&mut self, sizing: ClockSizing) -> Result<(), AikaError> {
+        self.event_system.set_dynamic_clock(sizing)
+    }
+
+    /// Plug a `WorldExtension` into this `Planet`, reachable by agents through `PlanetContext::extensions`. Agents a
+    /// split moves to another `Planet` no longer see it.
+    pub fn register_extension(
+        &mut self,
+        extension: Box<dyn WorldExtension>,
+    ) -> Result<(), AikaError> {
+        let now = self.now();
+        let world_id = self.context.world_id;
+        self.context.extensions.register(world_id, now, extension)
+    }
+
+    /// Pick when subscribers hear of a change to the world state, at the end of the tick of the write by default.
+    pub fn set_notify_at(&mut self, notify_at: NotifyAt) {
+        self.context.subscriptions.set_notify_at(notify_at);
+    }
+
+    /// Choose how each tick's mail and events are interleaved.
+    pub fn set_tick_order(&mut self, order: TickOrder) {
+        self.tick_order = order;
+    }
+
+    /// Pin the thread running `run()` to `core`.
+    pub(crate) fn pin_to(&mut self, core: usize) {
+        self.core = Some(core);
+    }
+
+    /// Make a template available to `PlanetContext::spawn_from_template()`, returning its id.
+    pub(crate) fn register_template(
+        &mut self,
+        template: AgentTemplate<INTER_SLOTS, MessageType>,
+    ) -> usize {
+        self.templates.push(template);
+        self.context.templates = self.templates.len();
+        self.templates.len() - 1
+    }
+
+    /// Index the next spawned agent gets, past any indices handed to a split-off `Planet`.
+    fn next_agent(&self) -> usize {
+        self.splits
+            .iter()
+            .map(|(_, end, _)| *end)
+            .fold(self.agents.len(), usize::max)
+    }
+
+    /// Build the agents requested by the handler that just returned.
+    fn apply_spawns(&mut self) {
+        for request in std::mem::take(&mut self.context.spawns) {
+            while self.agents.len() < request.agent {
+                // indices that moved away in a split keep a placeholder
+                self.departed.insert(self.agents.len());
+                self.agents.push(Box::new(Departed));
+                self.context.agent_states.push(Journal::init(0));
+            }
+            let template = &self.templates[request.template];
+            self.agents.push((template.factory)(request.init));
+            self.context
+                .agent_states
+                .push(Journal::init(template.state_arena_size));
+            self.spawn_log.push((self.now(), request.agent));
         }
+        self.agent_count
+            .store(self.live_agents(), Ordering::Release);
     }
 
-    /// Schedule an event for an agent at a given time.
-    pub fn schedule(&mut self, time: u64, agent: usize) -> Result<(), AikaError> {
-        if time < self.now() {
-            return Err(AikaError::TimeTravel);
-        } else if time as f64 * self.time_info.timestep > self.time_info.terminal {
+    /// Remove every agent spawned at or after `time`, with its pending events and mail.
+    fn unspawn(&mut self, time: u64) -> Result<(), AikaError> {
+        let Some(pos) = self.spawn_log.iter().position(|(at, _)| *at >= time) else {
+            return Ok(());
+        };
+        let keep = self.spawn_log[pos].1;
+        self.spawn_log.truncate(pos);
+        for idx in keep..self.agents.len() {
+            self.context.groups.remove_agent(idx);
+            self.context.subscriptions.remove_agent(idx);
+            self.context.partitions.remove_agent(idx);
+            self.context.interrupts.remove_agent(idx);
+        }
+        self.agents.truncate(keep);
+        self.context.agent_states.truncate(keep);
+        self.agent_load.truncate(keep);
+        self.departed.retain(|idx| *idx < keep);
+        self.failed.retain(|idx| *idx < keep);
+        self.event_system.drain(|event| event.agent >= keep)?;
+        self.local_messages
+            .drain(|msg| msg.to.is_some_and(|to| to >= keep));
+        self.agent_count
+            .store(self.live_agents(), Ordering::Release);
+        Ok(())
+    }
+
+    /// Process every tick's mail and events in a canonical order, breaking ties with `seed`, so the final state
+    /// doesn't depend on thread timing. `None` keeps arrival and scheduling order.
+    pub fn set_deterministic(&mut self, seed: Option<u64>) {
+        self.deterministic = seed;
+    }
+
+    /// Keep this `Planet` dormant until GVT reaches `time`, holding its LVT there. Call it before scheduling anything;
+    /// earlier schedules and mail are deferred to `time`.
+    pub fn set_activation(&mut self, time: u64) -> Result<(), AikaError> {
+        if self.time_info.reached(time) {
             return Err(AikaError::PastTerminal);
         }
-        let now = self.now();
-        self.commit(Event::new(now, time, agent, Action::Wait));
+        self.activation = time;
+        self.event_system.set_clock(clock_at(time)?);
+        self.local_messages.reset(time)?;
+        self.context.time = time;
+        self.local_time.store(time, Ordering::Release);
         Ok(())
     }
 
-    /// Get the current time of the simulation.
-    #[inline(always)]
-    pub fn now(&self) -> u64 {
-        self.event_system.local_clock.time
+    /// Step every `rate` base timesteps. Its clock still counts base timesteps, so GVT and mail times stay comparable
+    /// with every other `Planet`, but wake-ups land on multiples of `rate`: `Action::Timeout` and the delay of
+    /// `Action::Release` count this `Planet`'s steps, and every other wake-up or incoming mail falls due on the first
+    /// multiple at or after its time. The ticks in between are empty, and skipped with `set_fast_forward()`.
+    pub fn set_rate(&mut self, rate: u64) -> Result<(), AikaError> {
+        if rate == 0 {
+            return Err(AikaError::ConfigError(
+                "A world rate must be at least one timestep".to_string(),
+            ));
+        }
+        self.rate = rate;
+        Ok(())
     }
 
-    /// Get the time information of the simulation.
-    pub fn time_info(&self) -> (f64, f64) {
-        (self.time_info.timestep, self.time_info.terminal)
+    /// Leave the ticks before `time` out of the statistics: the wheel occupancy, KPIs and causal log skip them, the
+    /// state histories start at `time`, and the counters in `stats()` are reset once GVT reaches `time`, when nothing
+    /// before it can roll back any more. Agent state is still journaled during the warm-up, as rollbacks need it.
+    pub fn set_warmup(&mut self, time: u64) {
+        self.warmup = time;
+        self.warmed_up = time == 0;
+        if let Some(recorder) = &mut self.occupancy {
+            recorder.start_at(time);
+        }
+        if let Some(recorder) = &mut self.kpis {
+            recorder.start_at(time);
+        }
+        if let Some(log) = &mut self.causal_log {
+            log.start_at(time);
+        }
     }
 
-    /// Spawn a new `ThreadedAgent` on the `Planet` with the provided agent state arena allocation size.
-    pub fn spawn_agent(
-        &mut self,
-        agent: Box<dyn ThreadedAgent<INTER_SLOTS, MessageType>>,
-        state_arena_size: usize,
-    ) -> usize {
-        self.agents.push(agent);
-        self.context
+    /// Report rollbacks and opened mail as `filter` allows.
+    pub fn set_debug(&mut self, filter: DebugFilter) {
+        self.debug = filter;
+    }
+
+    /// End of the warm-up, 0 if there is none.
+    pub fn warmup(&self) -> u64 {
+        self.warmup
+    }
+
+    /// Reset the counters once GVT has passed the warm-up.
+    fn end_warmup(&mut self, gvt: u64) {
+        if self.warmed_up || gvt < self.warmup {
+            return;
+        }
+        self.warmed_up = true;
+        self.stats = PlanetStats {
+            world_id: self.stats.world_id,
+            ..PlanetStats::default()
+        };
+        self.context.bundles_sent = 0;
+    }
+
+    /// Base timesteps per step of this `Planet`.
+    pub fn rate(&self) -> u64 {
+        self.rate
+    }
+
+    /// Bound the overflow queue of far-future events. Call it before scheduling anything.
+    pub fn set_overflow_strategy(&mut self, strategy: OverflowStrategy) {
+        self.event_system.set_overflow_strategy(strategy);
+    }
+
+    /// Record a consistent cut at `time`, sending it on `sender`.
+    pub(crate) fn add_cut(&mut self, time: u64, sender: Sender<PlanetCut<MessageType>>) {
+        let at = self.cuts.partition_point(|(other, _)| *other <= time);
+        self.cuts.insert(at, (time, sender));
+    }
+
+    /// Snapshot agents, pending events and unread mail sent before `time`. Only sound once GVT has reached `time`.
+    fn record_cut(&mut self, time: u64) -> Result<PlanetCut<MessageType>, AikaError> {
+        let agents = self
+            .agents
+            .iter()
+            .enumerate()
+            .filter(|(id, _)| !self.departed.contains(id) && !self.failed.contains(id))
+            .map(|(id, agent)| (id, agent.snapshot(&self.context, id)))
+            .collect();
+        let events = self
+            .event_system
+            .pending(|_| true)?
+            .into_iter()
+            .map(|event| (event.agent, event.time))
+            .collect();
+        let partitions = self.context.partitions.snapshot();
+        let in_flight = self.local_messages.pending(|msg| msg.sent < time);
+        Ok(PlanetCut::new(
+            self.context.world_id,
+            time,
+            agents,
+            partitions,
+            events,
+            in_flight,
+        ))
+    }
+
+    /// Model bandwidth and queuing on this `Planet`'s outgoing links, keyed by destination world.
+    pub fn set_links(&mut self, models: BTreeMap<usize, LinkModel>) {
+        self.context.links = Some(Links::new(models));
+    }
+
+    /// Drop, duplicate or delay this `Planet`'s outgoing interplanetary mail at random, see `HybridConfig::with_faults()`.
+    pub fn set_faults(&mut self, faults: FaultInjection) {
+        self.context.faults = Some(FaultInjector::new(faults));
+    }
+
+    /// Stream a record of every rollback of this `Planet` to `exporter`'s sink.
+    #[cfg(feature = "rollback-export")]
+    pub(crate) fn export_rollbacks(&mut self, exporter: RollbackExporter) {
+        self.rollback_export = Some(exporter);
+    }
+
+    /// Time every call of an agent's `step()` and `read_message()`, see `analysis::profiling`.
+    #[cfg(feature = "agent-profiling")]
+    pub(crate) fn enable_agent_profiling(&mut self) {
+        self.profiler = Some(AgentProfiler::new(self.context.world_id));
+    }
+
+    /// The calls timed so far, if profiling is enabled.
+    #[cfg(feature = "agent-profiling")]
+    pub fn agent_profiler(&self) -> Option<&AgentProfiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Bundle this `Planet`'s outgoing mail per destination.
+    pub fn set_mail_batching(&mut self, batching: MailBatching) {
+        self.context.outbox = Some(Outbox::new(batching));
+    }
+
+    /// Record a timeline of this `Planet`'s steps, rollbacks, polls and stalls.
+    pub(crate) fn enable_tracing(&mut self, recorder: TraceRecorder) {
+        self.trace = Some(recorder);
+    }
+
+    /// The timeline recorded so far, if tracing is enabled.
+    pub fn trace(&self) -> Option<&TraceRecorder> {
+        self.trace.as_ref()
+    }
+
+    /// Sample the events on each level of the wheels every `every` ticks, see `Table::from_occupancy()`.
+    pub fn enable_occupancy_recording(&mut self, every: u64) {
+        let mut recorder = OccupancyRecorder::new(self.context.world_id, every);
+        recorder.start_at(self.warmup);
+        self.occupancy = Some(recorder);
+    }
+
+    /// The wheel occupancy recorded so far, if enabled.
+    pub fn occupancy(&self) -> Option<&OccupancyRecorder> {
+        self.occupancy.as_ref()
+    }
+
+    /// Count events, deliveries and active agents in buckets of `width` ticks as GVT commits them, see
+    /// `Table::from_kpis()`.
+    pub fn enable_kpis(&mut self, width: u64) {
+        let mut recorder = KpiRecorder::new(self.context.world_id, width);
+        recorder.start_at(self.warmup);
+        self.kpis = Some(recorder);
+    }
+
+    /// The KPI buckets committed so far, if enabled.
+    pub fn kpis(&self) -> Option<&KpiRecorder> {
+        self.kpis.as_ref()
+    }
+
+    /// Every agent on this `Planet` that is a `T`, with its index. Agents that migrated away are skipped.
+    pub fn agents_of<T: Any>(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.agents
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, agent)| Some((idx, agent.as_ref().as_any().downcast_ref::<T>()?)))
+    }
+
+    /// Jump straight to the next tick with an event, mail, notice or occupancy sample due instead of stepping through
+    /// every empty tick, never past the throttle horizon, a checkpoint, cut or barrier. Nothing is skipped while an
+    /// extension is registered, as extensions see every step, or while batched mail waits in the outbox.
+    pub fn set_fast_forward(&mut self, enabled: bool) {
+        self.fast_forward = enabled;
+    }
+
+    /// Sample the memory held every `limits.every` ticks, holding this `Planet` at GVT past the soft limit and failing
+    /// past the hard one, see `mt::hybrid::memory`.
+    pub fn set_memory_limits(&mut self, limits: Option<MemoryLimits>) {
+        self.memory_limits = limits;
+    }
+
+    /// The memory held in journals, the event overflow and pending mail, estimating journal entries as set with
+    /// `set_memory_limits()`.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let entry_bytes = self.memory_limits.unwrap_or_default().entry_bytes;
+        let journals = self
+            .context
             .agent_states
-            .push(Journal::init(state_arena_size));
-        self.agents.len() - 1
+            .iter()
+            .chain([&self.context.world_state]);
+        MemoryUsage::measure::<MessageType>(
+            journals,
+            entry_bytes,
+            self.event_system.overflow_stats().in_memory,
+            self.local_messages.len(),
+        )
     }
 
-    /// Spawn a preconfigured `ThreadedAgent`.
-    pub fn spawn_agent_preconfigured(
-        &mut self,
-        agent: Box<dyn ThreadedAgent<INTER_SLOTS, MessageType>>,
-    ) -> usize {
-        self.agents.push(agent);
-        self.agents.len() - 1
+    /// Take a memory sample if one is due, raising the pressure past the soft limit.
+    fn check_memory(&mut self) -> Result<(), AikaError> {
+        let Some(limits) = self.memory_limits else {
+            return Ok(());
+        };
+        let now = self.now();
+        if !now.is_multiple_of(limits.every) {
+            return Ok(());
+        }
+        let usage = self.memory_usage();
+        self.stats.memory = usage;
+        if limits.exceeded(&usage) {
+            return Err(AikaError::MemoryLimitExceeded {
+                world: self.context.world_id,
+                time: now,
+                limit: limits.hard.unwrap_or_default(),
+                usage,
+            });
+        }
+        let pressed = limits.pressed(&usage);
+        if pressed && !self.memory_pressed {
+            self.stats.warnings.push(SimWarning::MemoryPressure {
+                world: self.context.world_id,
+                time: now,
+                bytes: usage.total(),
+            });
+        }
+        self.memory_pressed = pressed;
+        Ok(())
     }
 
-    fn rollback(&mut self, time: u64) -> Result<(), AikaError> {
-        if time > self.event_system.local_clock.time {
-            return Err(AikaError::TimeTravel);
+    /// Run `ParallelStep::prepare()` of the agents due in a tick across `workers` threads before any of them steps,
+    /// see `mt::hybrid::parallel`. `None` steps every agent wholly on this thread.
+    pub fn set_parallel_steps(&mut self, workers: Option<usize>) {
+        self.parallel_steps = workers;
+    }
+
+    /// Tell the `Galaxy` whenever nothing is left to do on this `Planet`: no event, mail, notice or wake-up pending,
+    /// and no batched mail waiting to go out. Once every `Planet` is quiet with no mail in flight, nothing can happen
+    /// any more, and the `Galaxy` ends the run early. The `Planet` keeps stepping until then.
+    pub fn set_end_when_quiescent(&mut self, enabled: bool) {
+        self.end_when_quiescent = enabled;
+    }
+
+    /// Whether nothing is left to do unless mail or a control action arrives.
+    fn is_quiescent(&self) -> bool {
+        self.event_system.next_due().is_none()
+            && clock_next_due(&self.local_messages.schedule, &self.local_messages.overflow)
+                .is_none()
+            && self.context.subscriptions.next_due().is_none()
+            && self.context.wakeups.is_empty()
+            && self
+                .context
+                .outbox
+                .as_ref()
+                .is_none_or(|outbox| outbox.is_empty())
+    }
+
+    /// Publish that nothing is left to do.
+    fn mark_quiet(&self) {
+        let word = self.quiet.load(Ordering::Acquire);
+        if word.is_multiple_of(2) {
+            self.quiet.store(word + 1, Ordering::SeqCst);
         }
-        self.context.world_state.rollback(time);
-        for i in &mut self.context.agent_states {
-            i.rollback(time);
+    }
+
+    /// Publish that there is work again, before counting off the mail that brought it, so the `Galaxy` can't see
+    /// this `Planet` quiet with nothing in flight in between.
+    pub(crate) fn mark_busy(&self) {
+        let word = self.quiet.load(Ordering::Acquire);
+        if word % 2 == 1 {
+            self.quiet.store(word + 1, Ordering::SeqCst);
         }
-        self.local_messages
-            .schedule
-            .rollback(&mut self.local_messages.overflow, time);
-        let anti_msgs: Vec<(Mail<MessageType>, u64)> = self.context.anti_msgs.rollback_return(time);
-        for (anti, _) in anti_msgs {
-            if let Some(to) = anti.to_world {
-                if to == self.context.world_id {
-                    let anti = anti.open_letter();
-                    if let Transfer::AntiMsg(anti) = anti {
-                        self.annihilate(anti);
-                    }
-                    continue;
-                }
-            }
-            self.context.user.send(anti)?;
+    }
+
+    /// Counters and warnings collected so far.
+    pub fn stats(&self) -> &PlanetStats {
+        &self.stats
+    }
+
+    /// Whether any link is still in conservative mode at `gvt`, dropping links that have recovered.
+    fn is_conservative(&mut self, gvt: u64) -> bool {
+        self.conservative_links.retain(|_, until| *until > gvt);
+        !self.conservative_links.is_empty()
+    }
+
+    /// Flag `from_world` if a straggler at `time` rolls back further than allowed.
+    fn check_rollback_depth(&mut self, from_world: usize, time: u64) {
+        let Some(max) = self.max_rollback_depth else {
+            return;
+        };
+        let depth = self.now() - time;
+        if depth <= max {
+            return;
         }
+        let until = self.next_checkpoint.load(Ordering::Acquire).max(self.now());
+        self.conservative_links.insert(from_world, until);
+        self.stats.warnings.push(SimWarning::RollbackStorm {
+            world: self.context.world_id,
+            from_world,
+            time,
+            depth,
+            until,
+        });
+    }
 
-        self.event_system.local_clock = Clock::new()?;
-        self.event_system.local_clock.set_time(time);
+    fn record_rollback(&mut self, from_world: usize, cause: &Transfer<MessageType>, time: u64) {
+        let Some(reports) = &mut self.rollback_reports else {
+            return;
+        };
+        let agent = match cause {
+            Transfer::Msg(msg) => msg.from,
+            Transfer::AntiMsg(anti) => anti.from,
+        };
+        reports.record(RollbackDetail {
+            from: self.event_system.time(),
+            to: time,
+            sender: (from_world, agent),
+        });
+    }
 
-        self.local_time.store(time, Ordering::Release);
-        println!("ROLLBACK!!!!! rolling back! {:?}", self.context.world_id);
+    /// Raise a `SimWarning::CausalityViolation` for every letter read before `gvt` whose causal past was undone.
+    #[cfg(feature = "validate-causality")]
+    fn check_causality(&mut self, gvt: u64) -> Result<(), AikaError> {
+        for warning in self.context.causal.commit(gvt)? {
+            let span = DebugSpan::Planet {
+                world: self.context.world_id,
+                time: self.now(),
+            };
+            self.debug.emit(span, DebugKind::Rollback, || {
+                format!("causality violated: {warning:?}")
+            });
+            self.stats.warnings.push(warning);
+        }
         Ok(())
     }
 
-    fn annihilate(&mut self, anti_msg: AntiMsg) {
-        let time = anti_msg.time();
-        let idxs = self.local_messages.schedule.current_idxs;
-        let diff = (time - self.local_messages.schedule.time) as usize;
-        for (k, idx) in idxs.iter().enumerate().take(CLOCK_HEIGHT) {
-            let startidx = ((CLOCK_SLOTS).pow(1 + k as u32) - CLOCK_SLOTS) / (CLOCK_SLOTS - 1); // start index for each level
-            let endidx = ((CLOCK_SLOTS).pow(2 + k as u32) - CLOCK_SLOTS) / (CLOCK_SLOTS - 1) - 1; // end index for each level
-            if diff >= startidx {
-                if diff
-                    >= (((CLOCK_SLOTS).pow(1 + CLOCK_HEIGHT as u32) - CLOCK_SLOTS)
-                        / (CLOCK_SLOTS - 1))
-                {
-                    break;
+    /// Hand the report of a finished GVT window, if any, to the hook and the stats.
+    fn emit_rollback_report(&mut self, report: Option<RollbackReport>) {
+        let Some(report) = report else {
+            return;
+        };
+        if let Some(hook) = &mut self.hook {
+            hook.on_rollback_report(&report);
+        }
+        self.stats.rollback_reports.push(report);
+    }
+
+    /// Get a sender for this `Planet`'s control channel.
+    pub fn control_sender(&self) -> Sender<ControlAction> {
+        self.control.sender()
+    }
+
+    /// Every `ControlAction` applied so far, with the local time it took effect.
+    pub fn control_log(&self) -> &[ControlRecord] {
+        &self.control.log
+    }
+
+    /// Every parameter change applied so far, with the time it is in force from.
+    pub fn parameter_changes(&self) -> &[ParameterChange] {
+        self.parameters.changes()
+    }
+
+    /// The parameter changes GVT has already passed, which no rollback can move anymore.
+    pub fn committed_parameter_changes(&self) -> &[ParameterChange] {
+        self.parameters.committed(self.gvt.load(Ordering::Acquire))
+    }
+
+    /// Apply all pending control actions. Runs every loop iteration, regardless of throttling.
+    fn poll_control(&mut self) {
+        let actions = self.control.drain();
+        if !actions.is_empty() {
+            self.mark_busy();
+        }
+        for action in actions {
+            let now = self.now();
+            match action {
+                ControlAction::Pause => self.control.paused = true,
+                ControlAction::Resume => self.control.paused = false,
+                ControlAction::Parameter { agent, key, value } => {
+                    self.parameters.record(ParameterChange {
+                        world: self.context.world_id,
+                        agent,
+                        key,
+                        value,
+                        time: now,
+                    });
+                    self.context.time = now;
+                    match agent {
+                        Some(id) if id < self.agents.len() => {
+                            self.agents[id].on_control(&mut self.context, action, id)
+                        }
+                        Some(_) => {}
+                        None => {
+                            for i in 0..self.agents.len() {
+                                self.agents[i].on_control(&mut self.context, action, i);
+                            }
+                        }
+                    }
+                }
+                ControlAction::Intervene { agent, time } => {
+                    if agent < self.agents.len() {
+                        let _ = self.schedule(time.max(now), agent);
+                    }
+                }
+                // applied through the `Galaxy` at checkpoints
+                ControlAction::Rewire(_) => {}
+            }
+            self.control.log.push(ControlRecord { time: now, action });
+        }
+    }
+
+    fn commit(&mut self, mut event: Event) -> Result<(), AikaError> {
+        event.time = align(event.time, self.rate);
+        if self.time_info.past(event.time) {
+            return Ok(());
+        }
+        if let Some(log) = &mut self.context.causality {
+            event.id = log.record(
+                CausalKind::Event,
+                self.context.cause,
+                event.agent,
+                event.time,
+            );
+        }
+        self.event_system.insert(event)
+    }
+
+    /// The time `steps` of this `Planet`'s steps after `now`.
+    fn steps_later(&self, agent: usize, now: u64, steps: u64) -> Result<u64, AikaError> {
+        let delay = steps
+            .checked_mul(self.rate)
+            .ok_or(AikaError::TimeOverflow {
+                agent,
+                time: now,
+                delay: steps,
+            })?;
+        checked_later(agent, now, delay)
+    }
+
+    fn record_link(&mut self, cause: CausalNode, agent: usize, time: u64) {
+        if let Some(log) = &mut self.causal_log {
+            log.link(cause, CausalNode::new(self.context.world_id, agent, time));
+        }
+    }
+
+    /// Wake an agent granted a resource at `now` on the next tick.
+    fn wake_granted(&mut self, cause: CausalNode, agent: usize, now: u64) -> Result<(), AikaError> {
+        let time = checked_later(agent, now, 1)?;
+        if self.time_info.past(time) {
+            return Ok(());
+        }
+        self.commit(Event::new(now, time, agent, Action::Wait))?;
+        self.record_link(cause, agent, time);
+        Ok(())
+    }
+
+    /// Record agent activations, scheduling links and interplanetary message deliveries for critical-path analysis.
+    pub fn enable_causal_log(&mut self) {
+        let mut log = CausalLog::new();
+        log.start_at(self.warmup);
+        self.causal_log = Some(log);
+    }
+
+    /// Get the recorded causal log, if enabled.
+    pub fn causal_log(&self) -> Option<&CausalLog> {
+        self.causal_log.as_ref()
+    }
+
+    /// Give every event scheduled and `Msg` sent from now on a `CausalId`, recording what caused it and the
+    /// straggler behind every rollback.
+    pub fn enable_causality(&mut self) {
+        self.context.causality = Some(CausalityLog::new(self.context.world_id));
+    }
+
+    /// The `CausalId`s handed out so far, if enabled.
+    pub fn causality(&self) -> Option<&CausalityLog> {
+        self.context.causality.as_ref()
+    }
+
+    /// Count sent, delivered and dead-lettered interplanetary `Msg`s per agent pair.
+    pub fn enable_message_ledger(&mut self) {
+        self.context.ledger = Some(MessageLedger::new());
+    }
+
+    /// Get the recorded message ledger, if enabled.
+    pub fn message_ledger(&self) -> Option<&MessageLedger> {
+        self.context.ledger.as_ref()
+    }
+
+    /// Allow the `Galaxy` to split this `Planet` into one of the shared spare worlds.
+    pub(crate) fn enable_auto_scaling(
+        &mut self,
+        support: ScalingSupport<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
+    ) {
+        self.scaling = Some(support);
+    }
+
+    /// Let the `Galaxy` move agents between this `Planet` and others at checkpoints.
+    pub(crate) fn enable_migration(&mut self, support: MigrationSupport<INTER_SLOTS, MessageType>) {
+        self.migration = Some(support);
+    }
+
+    /// Share the `Galaxy`'s routing table, used to address agents by handle and to follow moved agents.
+    pub(crate) fn set_routes(&mut self, routes: Arc<Mutex<RoutingTable>>) {
+        self.context.routes = Some(routes);
+    }
+
+    /// Share the run's global aggregates, which this `Planet` contributes to.
+    pub(crate) fn set_reductions(&mut self, reductions: Arc<Reductions>) {
+        self.context.reductions = Some(reductions);
+    }
+
+    /// Hand the contributions made so far to the `Galaxy`. Runs before the local time is published, so every
+    /// contribution before GVT is in place when the `Galaxy` commits a checkpoint.
+    fn flush_contributions(&mut self) -> Result<(), AikaError> {
+        let contributions = std::mem::take(&mut self.context.contributions);
+        match &self.context.reductions {
+            Some(reductions) if !contributions.is_empty() => {
+                reductions.contribute(self.context.world_id, contributions)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Share the run's directory of agent attributes, which this `Planet` publishes to and queries.
+    pub(crate) fn set_directory(&mut self, directory: Arc<Directory>) {
+        self.context.directory = Some(directory);
+    }
+
+    /// Hand the publications made so far to the `Galaxy`, before the local time is published like contributions.
+    fn flush_publications(&mut self) -> Result<(), AikaError> {
+        let publications = std::mem::take(&mut self.context.publications);
+        match &self.context.directory {
+            Some(directory) if !publications.is_empty() => {
+                directory.publish(self.context.world_id, publications)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Share the run's barriers, which this `Planet` requests and waits on.
+    pub(crate) fn set_barriers(&mut self, barriers: Arc<Barriers>) {
+        self.context.barriers = Some(barriers);
+    }
+
+    /// Hand the barrier requests made so far to the `Galaxy`, before the local time is published like contributions.
+    fn flush_barrier_requests(&mut self) -> Result<(), AikaError> {
+        let requests = std::mem::take(&mut self.context.barrier_requests);
+        match &self.context.barriers {
+            Some(barriers) if !requests.is_empty() => barriers.request(requests),
+            _ => Ok(()),
+        }
+    }
+
+    /// Share the run's routes, closing the ones out of this `Planet` that are already removed.
+    pub(crate) fn set_topology(&mut self, topology: Arc<Topology>) -> Result<(), AikaError> {
+        self.context.closed_routes = topology.closed_from(self.context.world_id)?;
+        let seen = topology.handled();
+        self.topology = Some((topology, seen));
+        Ok(())
+    }
+
+    /// Apply the route changes the `Galaxy` made at the last checkpoint to the routes touching this `Planet`.
+    fn rewire(&mut self) -> Result<(), AikaError> {
+        let Some((topology, seen)) = &mut self.topology else {
+            return Ok(());
+        };
+        if topology.handled() <= *seen {
+            return Ok(());
+        }
+        let records = topology.since(*seen)?;
+        *seen += records.len();
+        let me = self.context.world_id;
+        let now = self.now();
+        for record in records.into_iter().filter(|record| record.applied) {
+            let change = record.change;
+            let (from, to) = change.route();
+            if from != me && to != me {
+                continue;
+            }
+            if from == me {
+                let model = match change {
+                    RouteChange::Add { link, .. } => {
+                        self.context.closed_routes.remove(&to);
+                        link
+                    }
+                    RouteChange::Remove { .. } => {
+                        self.context.closed_routes.insert(to);
+                        None
+                    }
+                };
+                if model.is_some() || self.context.links.is_some() {
+                    self.context
+                        .links
+                        .get_or_insert_with(|| Links::new(BTreeMap::new()))
+                        .set_model(to, model);
+                }
+            } else if let RouteChange::Remove { .. } = change {
+                // nothing more can arrive over a removed route
+                self.conservative_links.remove(&from);
+            }
+            let action = ControlAction::Rewire(change);
+            self.context.time = now;
+            for i in 0..self.agents.len() {
+                self.agents[i].on_control(&mut self.context, action, i);
+            }
+            self.control.log.push(ControlRecord { time: now, action });
+        }
+        Ok(())
+    }
+
+    /// Number of agents still living on this `Planet`.
+    fn live_agents(&self) -> usize {
+        self.agents.len() - self.departed.len() - self.failed.len()
+    }
+
+    /// Hand one agent to `target`: the busiest one carrying at most half of this `Planet`'s load, so the move
+    /// can't simply shift the hotspot. Its index keeps a `Departed` placeholder and mail for it is forwarded.
+    fn emigrate(&mut self, target: usize) -> Result<(), AikaError> {
+        let Some(support) = self.migration.clone() else {
+            return Ok(());
+        };
+        self.agent_load.resize(self.agents.len(), 0);
+        let total = self.agent_load.iter().sum::<u64>();
+        let candidate = (0..self.agents.len())
+            .filter(|i| !self.departed.contains(i) && !self.failed.contains(i))
+            .filter(|i| self.agent_load[*i] * 2 <= total)
+            .max_by_key(|i| self.agent_load[*i]);
+        let Some(idx) = candidate.filter(|_| self.live_agents() > 1) else {
+            support.in_progress.fetch_sub(1, Ordering::SeqCst);
+            return Ok(());
+        };
+
+        let agent = std::mem::replace(&mut self.agents[idx], Box::new(Departed));
+        let state = match self.context.agent_states.get_mut(idx) {
+            Some(state) => std::mem::replace(state, Journal::init(0)),
+            None => Journal::init(0),
+        };
+        let mut msgs = self.local_messages.pending(|msg| {
+            msg.to.is_none()
+                && msg
+                    .group
+                    .is_none_or(|group| self.context.groups.members(group).contains(&idx))
+        });
+        msgs.iter_mut().for_each(|msg| msg.to = Some(idx));
+        msgs.extend(self.local_messages.drain(|msg| msg.to == Some(idx)));
+        let mut events = self
+            .event_system
+            .drain(|event| event.agent == idx)?
+            .into_iter()
+            .map(|event| event.time)
+            .collect::<Vec<_>>();
+        self.context.wakeups.retain(|(agent, time, _)| {
+            if *agent == idx {
+                events.push(*time);
+            }
+            *agent != idx
+        });
+        // subscriptions are to this world's state, so they stay behind
+        self.context.subscriptions.remove_agent(idx);
+        self.context.partitions.remove_agent(idx);
+        self.context.interrupts.remove_agent(idx);
+        let migrant = Migrant {
+            from: (self.context.world_id, idx),
+            agent,
+            state,
+            groups: self.context.groups.remove_agent(idx),
+            capabilities: self.context.sandbox.release(idx),
+            events,
+            msgs,
+            load: std::mem::take(&mut self.agent_load[idx]),
+        };
+        self.departed.insert(idx);
+        self.agent_count
+            .store(self.live_agents(), Ordering::Release);
+        self.stats.migrated_out += 1;
+        support.inboxes[target]
+            .lock()
+            .map_err(|_| AikaError::PoisonedLock)?
+            .push(migrant);
+        Ok(())
+    }
+
+    /// Take in every agent handed to this `Planet`, recording its new address in the routing table.
+    fn immigrate(&mut self) -> Result<(), AikaError> {
+        let Some(support) = self.migration.clone() else {
+            return Ok(());
+        };
+        let migrants = std::mem::take(
+            &mut *support.inboxes[self.context.world_id]
+                .lock()
+                .map_err(|_| AikaError::PoisonedLock)?,
+        );
+        for migrant in migrants {
+            let idx = self.agents.len();
+            self.agents.push(migrant.agent);
+            self.context
+                .agent_states
+                .resize_with(idx, || Journal::init(0));
+            self.context.agent_states.push(migrant.state);
+            self.agent_load.resize(idx, 0);
+            self.agent_load.push(migrant.load);
+            for group in migrant.groups {
+                self.context.groups.join(group, idx);
+            }
+            if let Some(capabilities) = migrant.capabilities {
+                self.context.sandbox.restrict(idx, capabilities);
+            }
+            let now = self.now();
+            self.event_system.insert_batch(
+                migrant.events.into_iter().map(|time| {
+                    Event::new(now, align(time.max(now), self.rate), idx, Action::Wait)
+                }),
+            )?;
+            for mut msg in migrant.msgs {
+                msg.to = Some(idx);
+                self.commit_mail(msg);
+            }
+            support
+                .routes
+                .lock()
+                .map_err(|_| AikaError::PoisonedLock)?
+                .insert(migrant.from, (self.context.world_id, idx));
+            self.agent_count
+                .store(self.live_agents(), Ordering::Release);
+            self.stats.migrated_in += 1;
+            support.in_progress.fetch_sub(1, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+
+    /// Get the world a moved agent now lives on, along with its index there.
+    fn moved_to(&self, agent: usize) -> Option<(usize, usize)> {
+        if let Some(routes) = &self.context.routes {
+            if let Ok(routes) = routes.lock() {
+                if routes.has_moved(self.context.world_id, agent) {
+                    return Some(routes.resolve(self.context.world_id, agent));
+                }
+            }
+        }
+        self.splits
+            .iter()
+            .find(|(start, end, _)| (*start..*end).contains(&agent))
+            .map(|(start, _, world)| (*world, agent - start))
+    }
+
+    fn forward(
+        &mut self,
+        transfer: Transfer<MessageType>,
+        from_world: usize,
+        to_world: usize,
+    ) -> Result<(), AikaError> {
+        self.context
+            .post(Mail::write_letter(transfer, from_world, Some(to_world)))
+    }
+
+    /// Move the upper half of the agents, their journals and pending events onto the spare world `spare`,
+    /// and start it on its own thread. Mail and triggers for moved agents keep arriving here and are forwarded.
+    /// Extensions and the `SimHook` stay with this `Planet`; the new one starts without them.
+    fn split(&mut self, spare: usize) -> Result<(), AikaError> {
+        let Some(support) = self.scaling.clone() else {
+            return Ok(());
+        };
+        let registry = {
+            let mut spares = support.spares.lock().map_err(|_| AikaError::PoisonedLock)?;
+            match spares.iter().position(|r| r.world_id == spare) {
+                Some(idx) => spares.swap_remove(idx),
+                None => return Ok(()),
+            }
+        };
+        let start = self.agents.len() / 2;
+        let end = self.agents.len();
+        if start == 0 {
+            return Ok(());
+        }
+        let now = self.now();
+        let active = registry.active_handle();
+
+        let mut child = Planet::create(
+            self.time_info.terminal,
+            self.time_info.timestep,
+            self.throttle_horizon,
+            self.world_arena_size,
+            self.anti_msg_arena_size,
+            registry,
+        )?;
+        child.scaling = Some(support.clone());
+        child.max_rollback_depth = self.max_rollback_depth;
+        if let Some(reports) = &self.rollback_reports {
+            child.set_rollback_reporting(reports.reporting());
+        }
+        child.throttle = self.throttle.clone();
+        child.fast_forward = self.fast_forward;
+        child.end_when_quiescent = self.end_when_quiescent;
+        child.panic_policy = self.panic_policy;
+        child.tick_order = self.tick_order;
+        child.parallel_steps = self.parallel_steps;
+        child.memory_limits = self.memory_limits;
+        child.deterministic = self.deterministic;
+        child.context.outbox = self
+            .context
+            .outbox
+            .as_ref()
+            .map(|outbox| Outbox::new(outbox.batching()));
+        child.context.faults = self
+            .context
+            .faults
+            .as_ref()
+            .map(|faults| FaultInjector::new(faults.params()));
+        for template in &self.templates {
+            child.register_template(template.clone());
+        }
+        if let Some(migration) = self.migration.clone() {
+            child.enable_migration(migration);
+        }
+        if let Some(routes) = self.context.routes.clone() {
+            let mut table = routes.lock().map_err(|_| AikaError::PoisonedLock)?;
+            for idx in start..end {
+                table.insert((self.context.world_id, idx), (spare, idx - start));
+            }
+            drop(table);
+            child.set_routes(routes);
+        }
+        if let Some((topology, _)) = &self.topology {
+            child.set_topology(Arc::clone(topology))?;
+        }
+        child.rate = self.rate;
+        child.warmup = self.warmup;
+        child.warmed_up = self.warmed_up;
+        child.debug = self.debug.clone();
+        child.context.shared = self.context.shared.clone();
+        child.context.parcels = self.context.parcels.clone();
+        child.context.reductions = self.context.reductions.clone();
+        child.context.directory = self.context.directory.clone();
+        child.context.barriers = self.context.barriers.clone();
+        if self.causal_log.is_some() {
+            child.enable_causal_log();
+        }
+        if self.context.causality.is_some() {
+            child.enable_causality();
+        }
+        if let Some(trace) = &self.trace {
+            child.enable_tracing(trace.fork(spare));
+        }
+        if let Some(recorder) = &self.occupancy {
+            child.occupancy = Some(recorder.fork(spare));
+        }
+        if let Some(recorder) = &self.kpis {
+            child.kpis = Some(recorder.fork(spare));
+        }
+        if self.context.ledger.is_some() {
+            child.enable_message_ledger();
+        }
+        #[cfg(feature = "rollback-export")]
+        if let Some(export) = &self.rollback_export {
+            child.export_rollbacks(export.fork());
+        }
+        #[cfg(feature = "agent-profiling")]
+        {
+            child.profiler = self.profiler.as_ref().map(|profiler| profiler.fork(spare));
+        }
+        // agents spawned before the split move with it for good
+        self.spawn_log.retain(|(_, idx)| *idx < start);
+        child.agents = self.agents.split_off(start);
+        child.context.agent_states = self.context.agent_states.split_off(start);
+        child.context.groups = self.context.groups.split_off(start);
+        child.context.subscriptions = self.context.subscriptions.split_off(start);
+        child.context.interrupts = self.context.interrupts.split_off(start);
+        self.context.partitions.split_off(start);
+        child.context.sandbox = self.context.sandbox.split_off(start);
+        self.agent_load.resize(end, 0);
+        child.agent_load = self.agent_load.split_off(start);
+        child.departed = self
+            .departed
+            .split_off(&start)
+            .into_iter()
+            .map(|idx| idx - start)
+            .collect();
+        child.failed = self
+            .failed
+            .split_off(&start)
+            .into_iter()
+            .map(|idx| idx - start)
+            .collect();
+        child.context.time = now;
+        if let Some(clock) = &self.event_system.dynamic {
+            child.event_system.set_dynamic_clock(clock.sizing())?;
+        }
+        child.event_system.set_clock(clock_at(now)?);
+        child.local_messages.reset(now)?;
+
+        let events = self.event_system.drain(|event| event.agent >= start)?;
+        child
+            .event_system
+            .insert_batch(events.into_iter().map(|mut event| {
+                event.agent -= start;
+                event
+            }))?;
+        child
+            .event_system
+            .set_overflow_strategy(self.event_system.spill.strategy().clone());
+        let msgs = self
+            .local_messages
+            .drain(|msg| msg.to.is_none_or(|to| to >= start));
+        for mut msg in msgs {
+            match msg.to {
+                Some(to) => msg.to = Some(to - start),
+                None => self.commit_mail(msg),
+            }
+            child.commit_mail(msg);
+        }
+
+        child.local_time.store(now, Ordering::Release);
+        child
+            .agent_count
+            .store(child.live_agents(), Ordering::Release);
+        self.agent_count
+            .store(self.live_agents(), Ordering::Release);
+        self.splits.push((start, end, spare));
+        active.store(true, Ordering::Release);
+
+        let sink = panics::current();
+        let handle = std::thread::spawn(move || {
+            if let Some((sink, _)) = sink {
+                sink.enter(Some(child.context.world_id));
+            }
+            let mut child = child;
+            child.run().map(|_| child)
+        });
+        support
+            .spawned
+            .lock()
+            .map_err(|_| AikaError::PoisonedLock)?
+            .push(handle);
+        Ok(())
+    }
+
+    fn commit_mail(&mut self, mut msg: Msg<MessageType>) {
+        msg.recv = align(msg.recv, self.rate);
+        self.local_messages.insert(msg);
+    }
+
+    /// The current time as a `SimTime`.
+    pub fn sim_time(&self) -> SimTime {
+        SimTime(self.now())
+    }
+
+    /// Conversions between ticks and seconds with this `Planet`'s timestep.
+    pub fn scale(&self) -> TimeScale {
+        TimeScale::new(self.time_info.timestep)
+    }
+
+    /// Schedule an event for an agent at a given time.
+    pub fn schedule(&mut self, time: impl Into<SimTime>, agent: usize) -> Result<(), AikaError> {
+        let time = time.into().ticks().max(self.activation);
+        if time < self.now() {
+            return Err(AikaError::TimeTravel);
+        } else if self.time_info.past(time) {
+            return Err(AikaError::PastTerminal);
+        }
+        let now = self.now();
+        self.context.cause = CausalId::NONE;
+        self.commit(Event::new(now, time, agent, Action::Wait))?;
+        Ok(())
+    }
+
+    /// Schedule a batch of `(agent, time)` events. The whole batch is validated before anything is inserted.
+    pub fn schedule_many<T: Into<SimTime> + Copy>(
+        &mut self,
+        events: &[(usize, T)],
+    ) -> Result<(), AikaError> {
+        let batch = self.validate_batch(events)?;
+        self.insert_batch(batch)
+    }
+
+    /// Turn a batch of `(agent, time)` events into `Event`s this `Planet` would take, without inserting any.
+    pub(crate) fn validate_batch<T: Into<SimTime> + Copy>(
+        &self,
+        events: &[(usize, T)],
+    ) -> Result<Vec<Event>, AikaError> {
+        let now = self.now();
+        let mut batch = Vec::with_capacity(events.len());
+        for &(agent, time) in events {
+            let time = align(time.into().ticks().max(self.activation), self.rate);
+            if time < now {
+                return Err(AikaError::TimeTravel);
+            } else if self.time_info.past(time) {
+                return Err(AikaError::PastTerminal);
+            }
+            batch.push(Event::new(now, time, agent, Action::Wait));
+        }
+        self.event_system.admits(&batch)?;
+        Ok(batch)
+    }
+
+    /// Insert a batch checked by `validate_batch()`.
+    pub(crate) fn insert_batch(&mut self, batch: Vec<Event>) -> Result<(), AikaError> {
+        self.event_system.insert_batch(batch)
+    }
+
+    /// Schedule every `ThreadedAgent` on the `Planet` to step at the given time.
+    pub fn schedule_all_agents(&mut self, time: impl Into<SimTime>) -> Result<(), AikaError> {
+        let time = time.into();
+        let events = (0..self.agents.len())
+            .map(|agent| (agent, time))
+            .collect::<Vec<_>>();
+        self.schedule_many(&events)
+    }
+
+    /// Get the current time of the simulation.
+    #[inline(always)]
+    pub fn now(&self) -> u64 {
+        self.event_system.time()
+    }
+
+    /// Every state logged by `agent` after the warm-up, oldest first, as `(time, state)`. Call it after `run()`;
+    /// only the committed history is left by then.
+    pub fn agent_history<T: Pod + Zeroable + 'static>(
+        &self,
+        agent: usize,
+    ) -> impl Iterator<Item = (u64, T)> + '_ {
+        let warmup = self.warmup;
+        self.context
+            .agent_states
+            .get(agent)
+            .into_iter()
+            .flat_map(journal_history)
+            .filter(move |(time, _)| *time >= warmup)
+    }
+
+    /// The agent and world state as of the end of tick `time`, at most `now()`, read off the journals without rolling
+    /// back. Ticks past GVT are speculative, and may yet be rolled back.
+    pub fn state_at(&self, time: u64) -> Result<PlanetState<'_>, AikaError> {
+        if time > self.now() {
+            return Err(AikaError::TimeTravel);
+        }
+        Ok(PlanetState::new(
+            time,
+            &self.context.agent_states,
+            &self.context.world_state,
+            &self.context.partitions,
+        ))
+    }
+
+    /// The states logged by `agent` at a time within `window`, oldest first.
+    pub fn agent_history_between<T: Pod + Zeroable + 'static>(
+        &self,
+        agent: usize,
+        window: impl RangeBounds<u64> + 'static,
+    ) -> impl Iterator<Item = (u64, T)> + '_ {
+        self.agent_history(agent)
+            .filter(move |(time, _)| window.contains(time))
+    }
+
+    /// Every world state logged after the warm-up, oldest first, as `(time, state)`.
+    pub fn world_history<T: Pod + Zeroable + 'static>(
+        &self,
+    ) -> impl Iterator<Item = (u64, T)> + '_ {
+        let warmup = self.warmup;
+        journal_history(&self.context.world_state).filter(move |(time, _)| *time >= warmup)
+    }
+
+    /// The world states logged at a time within `window`, oldest first.
+    pub fn world_history_between<T: Pod + Zeroable + 'static>(
+        &self,
+        window: impl RangeBounds<u64> + 'static,
+    ) -> impl Iterator<Item = (u64, T)> + '_ {
+        self.world_history()
+            .filter(move |(time, _)| window.contains(time))
+    }
+
+    /// List the events pending for `agent` within the next `steps` steps, without disturbing the schedule.
+    /// Call it before or after `run()`, while no thread owns the `Planet`. Fails if spilled events can't be read back.
+    pub fn pending_events(&self, agent: usize, steps: u64) -> Result<Vec<Event>, AikaError> {
+        let horizon = self.now().saturating_add(steps);
+        let mut pending = self
+            .event_system
+            .pending(|event| event.agent == agent && event.time <= horizon)?;
+        pending.extend(
+            self.context
+                .wakeups
+                .iter()
+                .filter(|(id, time, _)| *id == agent && *time <= horizon)
+                .map(|(id, time, _)| Event::new(self.now(), *time, *id, Action::Wait)),
+        );
+        pending.sort_by_key(|event| event.time);
+        Ok(pending)
+    }
+
+    /// List the `Msg`s (direct or broadcast) scheduled for delivery to `agent` within the next `steps` steps.
+    pub fn pending_messages(&self, agent: usize, steps: u64) -> Vec<Msg<MessageType>> {
+        let horizon = self.now().saturating_add(steps);
+        self.local_messages
+            .pending(|msg| msg.to.is_none_or(|to| to == agent) && msg.recv <= horizon)
+    }
+
+    /// Take every letter this `Planet` holds but never processed: mail scheduled past the point it stopped at, mail
+    /// left in its inbox or outbox, and mail the `Galaxy` couldn't deliver that wasn't yet handed back. Mail already
+    /// on the wheels is addressed to this `Planet`, and anti-messages still in flight are included as well.
+    pub fn drain_unprocessed(&mut self) -> Result<Vec<Mail<MessageType>>, AikaError> {
+        let world_id = self.context.world_id;
+        let mut letters = self
+            .local_messages
+            .drain(|_| true)
+            .into_iter()
+            .map(|msg| Mail::write_letter(Transfer::Msg(msg), msg.from_world, Some(world_id)))
+            .collect::<Vec<_>>();
+        while let Some(bundles) = self.context.user.poll() {
+            letters.extend(bundles.into_iter().flat_map(MailBundle::into_letters));
+        }
+        if let Some(outbox) = &mut self.context.outbox {
+            letters.extend(
+                outbox
+                    .drain()
+                    .into_iter()
+                    .flat_map(MailBundle::into_letters),
+            );
+        }
+        let failures =
+            std::mem::take(&mut *self.failures.lock().map_err(|_| AikaError::PoisonedLock)?);
+        letters.extend(failures.into_iter().map(|failure| {
+            Mail::write_letter(Transfer::Msg(failure.msg), world_id, Some(failure.to_world))
+        }));
+        Ok(letters)
+    }
+
+    /// Get the time information of the simulation.
+    pub fn time_info(&self) -> (f64, f64) {
+        (self.time_info.timestep, self.time_info.terminal)
+    }
+
+    /// Spawn a new `ThreadedAgent` on the `Planet` with the provided agent state arena allocation size.
+    pub fn spawn_agent(
+        &mut self,
+        agent: Box<dyn ThreadedAgent<INTER_SLOTS, MessageType>>,
+        state_arena_size: usize,
+    ) -> usize {
+        self.agents.push(agent);
+        self.context
+            .agent_states
+            .push(Journal::init(state_arena_size));
+        self.agents.len() - 1
+    }
+
+    /// Spawn a preconfigured `ThreadedAgent` that may only do what `capabilities` allow, see `Capabilities`.
+    pub fn spawn_sandboxed(
+        &mut self,
+        agent: Box<dyn ThreadedAgent<INTER_SLOTS, MessageType>>,
+        capabilities: Capabilities,
+    ) -> usize {
+        let idx = self.spawn_agent_preconfigured(agent);
+        self.context.sandbox.restrict(idx, capabilities);
+        idx
+    }
+
+    /// Spawn a preconfigured `ThreadedAgent`.
+    pub fn spawn_agent_preconfigured(
+        &mut self,
+        agent: Box<dyn ThreadedAgent<INTER_SLOTS, MessageType>>,
+    ) -> usize {
+        self.agents.push(agent);
+        self.agents.len() - 1
+    }
+
+    fn rollback(&mut self, time: u64) -> Result<(), AikaError> {
+        if time > self.event_system.time() {
+            return Err(AikaError::TimeTravel);
+        }
+        let start = Instant::now();
+        let from = self.event_system.time();
+        self.unspawn(time)?;
+        // a deterministic `Planet` runs the whole tick at `time` again, so undo what it logged in that tick too
+        let kept = match self.deterministic {
+            Some(_) => time.saturating_sub(1),
+            None => time,
+        };
+        self.context.world_state.rollback(kept);
+        for i in &mut self.context.agent_states {
+            i.rollback(kept);
+        }
+        self.local_messages
+            .schedule
+            .rollback(&mut self.local_messages.overflow, time);
+        let replay = match self.deterministic {
+            Some(_) => self.replay_from(time)?,
+            None => Vec::new(),
+        };
+        let anti_msgs: Vec<(Mail<MessageType>, u64)> = self.context.anti_msgs.rollback_return(time);
+        for (anti, _) in anti_msgs {
+            if let Some(to) = anti.to_world {
+                if to == self.context.world_id {
+                    let anti = anti.open_letter();
+                    if let Transfer::AntiMsg(anti) = anti {
+                        self.annihilate(anti, self.context.world_id);
+                    }
+                    continue;
+                }
+            }
+            self.context.post(anti)?;
+        }
+        // anti-messages must not wait behind a rollback
+        self.context.flush_mail()?;
+
+        if let Some(hook) = &mut self.hook {
+            hook.on_rollback(self.context.world_id, self.event_system.time(), time);
+        }
+        self.stats.rollbacks += 1;
+        if let Some(throttle) = &mut self.throttle {
+            throttle.on_rollback();
+        }
+        self.sequences.clear();
+        if let Some(links) = &mut self.context.links {
+            links.rollback(time);
+        }
+        if let Some(faults) = &mut self.context.faults {
+            faults.rollback(time);
+        }
+        self.context.sandbox.rollback(time);
+        self.parameters.rollback(time);
+        self.context.subscriptions.rollback(time);
+        self.context.rpc.rollback(time);
+        self.context.interrupts.rollback(time);
+        self.context.resources.rollback(time);
+        self.context.partitions.rollback(time);
+        if let Some(recorder) = &mut self.occupancy {
+            recorder.rollback(time);
+        }
+        if let Some(recorder) = &mut self.kpis {
+            recorder.rollback(time);
+        }
+        self.context.extensions.rollback(time)?;
+        self.context
+            .contributions
+            .retain(|contribution| contribution.time < time);
+        if let Some(reductions) = &self.context.reductions {
+            reductions.rollback(self.context.world_id, time)?;
+        }
+        self.context
+            .publications
+            .retain(|publication| publication.time < time);
+        if let Some(directory) = &self.context.directory {
+            directory.rollback(self.context.world_id, time)?;
+        }
+        self.context.queries.retain(|id, _| id.time < time);
+        self.context
+            .barrier_requests
+            .retain(|request| request.requested < time);
+        if let Some(barriers) = &self.context.barriers {
+            barriers.rollback(self.context.world_id, time)?;
+        }
+        let depth = self.event_system.time() - time;
+        self.stats.rollback_steps += depth;
+        *self.stats.rollback_depths.entry(depth).or_default() += 1;
+        let mut clock = Clock::new()?;
+        clock.set_time(time);
+        self.event_system.set_clock(clock);
+        self.event_system.insert_batch(replay)?;
+
+        if let Some(log) = &mut self.causal_log {
+            log.rollback(self.context.world_id, time);
+        }
+        #[cfg(feature = "validate-causality")]
+        self.context.causal.rollback(time)?;
+        self.local_time.store(time, Ordering::Release);
+        if let Some(trace) = &mut self.trace {
+            trace.span("rollback", start, &[("from", from), ("to", time)]);
+        }
+        Ok(())
+    }
+
+    /// Put the mail read and the events run at or after `time` back to be handled again, in canonical order with
+    /// whatever arrives for those ticks later. Returns the events to run again, with the pending events committed
+    /// before `time`; those committed later are scheduled anew as their ticks run again. Mail this `Planet` sent
+    /// itself from those ticks is sent again too, so it isn't put back.
+    fn replay_from(&mut self, time: u64) -> Result<Vec<Event>, AikaError> {
+        let world_id = self.context.world_id;
+        let (read, kept) = std::mem::take(&mut self.read_log)
+            .into_iter()
+            .partition::<Vec<_>, _>(|msg| msg.recv >= time);
+        self.read_log = kept;
+        for msg in read {
+            if msg.from_world != world_id || msg.sent < time {
+                self.local_messages.insert(msg);
+            }
+        }
+        let (ran, kept) = std::mem::take(&mut self.ran_log)
+            .into_iter()
+            .partition::<Vec<_>, _>(|event| event.time >= time);
+        self.ran_log = kept;
+        let mut replay = self.event_system.drain(|event| event.commit_time < time)?;
+        replay.extend(ran.into_iter().filter(|event| event.commit_time < time));
+        Ok(replay)
+    }
+
+    fn annihilate(&mut self, anti_msg: AntiMsg, from_world: usize) {
+        if let Some(log) = &mut self.causal_log {
+            log.cancel_message(self.context.world_id, from_world, &anti_msg);
+        }
+        let removed = self.local_messages.annihilate(&anti_msg, from_world);
+        if let Some(ledger) = &mut self.context.ledger {
+            let from = Address::new(from_world, Some(anti_msg.from));
+            let to = Address::new(self.context.world_id, anti_msg.to);
+            for _ in 0..removed {
+                ledger.record_cancelled(from, to);
+            }
+        }
+    }
+
+    pub(crate) fn poll_interplanetary_messenger(&mut self) -> Result<(), AikaError> {
+        let mut counter = 0;
+        let maybe = self.context.user.poll();
+        if maybe.is_none() {
+            return Ok(());
+        }
+        let start = Instant::now();
+        let bundles = maybe.unwrap();
+        self.mark_busy();
+        let in_flight = bundles.iter().map(MailBundle::in_flight).sum::<usize>();
+        for mut msg in bundles.into_iter().flat_map(MailBundle::into_letters) {
+            if let Some(to) = msg.to_world {
+                if to != self.context.world_id {
+                    return Err(AikaError::MismatchedDeliveryAddress);
+                }
+            }
+            let from_world = msg.from_world;
+            let to = match &msg.transfer {
+                Transfer::Msg(msg) => msg.to,
+                Transfer::AntiMsg(anti_msg) => anti_msg.to,
+            };
+            match to {
+                Some(to) => {
+                    if let Some((world, idx)) = self.moved_to(to) {
+                        let transfer = match msg.open_letter() {
+                            Transfer::Msg(mut msg) => {
+                                if let Some(ledger) = &mut self.context.ledger {
+                                    ledger.record_forwarded(
+                                        Address::new(from_world, Some(msg.from)),
+                                        Address::new(self.context.world_id, Some(to)),
+                                        Address::new(world, Some(idx)),
+                                    );
+                                }
+                                msg.to = Some(idx);
+                                Transfer::Msg(msg)
+                            }
+                            Transfer::AntiMsg(mut anti_msg) => {
+                                anti_msg.to = Some(idx);
+                                Transfer::AntiMsg(anti_msg)
+                            }
+                        };
+                        self.forward(transfer, from_world, world)?;
+                        counter += 1;
+                        continue;
+                    }
+                }
+                // a galaxy-wide broadcast has a copy of its own on every planet the agents were split off to
+                None if msg.to_world.is_none() => {
+                    if let (Transfer::Msg(copy), Some(ledger)) =
+                        (&msg.transfer, &mut self.context.ledger)
+                    {
+                        ledger.record_sent(
+                            Address::new(from_world, Some(copy.from)),
+                            Address::new(self.context.world_id, None),
+                        );
+                    }
+                }
+                None => {
+                    let worlds = self.splits.iter().map(|split| split.2).collect::<Vec<_>>();
+                    for world in worlds {
+                        if let (Transfer::Msg(copy), Some(ledger)) =
+                            (&msg.transfer, &mut self.context.ledger)
+                        {
+                            ledger.record_sent(
+                                Address::new(from_world, Some(copy.from)),
+                                Address::new(world, None),
+                            );
+                        }
+                        self.forward(msg.transfer, from_world, world)?;
+                    }
+                }
+            }
+            // mail sent to a dormant `Planet` waits for its activation
+            msg.transfer.defer_to(self.activation);
+            msg.transfer.align_to(self.rate);
+            let time = msg.transfer.time();
+            let span = DebugSpan::Planet {
+                world: self.context.world_id,
+                time: self.now(),
+            };
+            self.debug.emit(span, DebugKind::Mail, || {
+                let kind = match &msg.transfer {
+                    Transfer::Msg(_) => "msg",
+                    Transfer::AntiMsg(_) => "anti-msg",
+                };
+                format!("opened {kind} for {to:?} at {time} from world {from_world}")
+            });
+            if time < self.now() {
+                self.debug.emit(span, DebugKind::Rollback, || {
+                    format!("rolling back to {time} for mail from world {from_world}")
+                });
+                self.check_rollback_depth(from_world, time);
+                self.record_rollback(from_world, &msg.transfer, time);
+                if let Some(log) = &mut self.context.causality {
+                    let (straggler, cancelled) = match &msg.transfer {
+                        Transfer::Msg(msg) => (msg.id, false),
+                        Transfer::AntiMsg(anti_msg) => (anti_msg.id, true),
+                    };
+                    log.rollback(self.event_system.time(), time, straggler, cancelled);
+                }
+                #[cfg(feature = "rollback-export")]
+                self.export_rollback(&msg, time)?;
+                self.rollback(time)?;
+            }
+            #[cfg(feature = "validate-causality")]
+            let clock = msg.clock;
+            match msg.open_letter() {
+                Transfer::Msg(msg) => {
+                    if let Some(log) = &mut self.causal_log {
+                        let cause = CausalNode::new(from_world, msg.from, msg.sent);
+                        let world_id = self.context.world_id;
+                        match msg.to {
+                            Some(to) => log.link(cause, CausalNode::new(world_id, to, msg.recv)),
+                            None => {
+                                for i in 0..self.agents.len() {
+                                    log.link(cause, CausalNode::new(world_id, i, msg.recv));
+                                }
+                            }
+                        }
+                    }
+                    if let Some(ledger) = &mut self.context.ledger {
+                        let from = Address::new(from_world, Some(msg.from));
+                        let to = Address::new(self.context.world_id, msg.to);
+                        // mail at or past the terminal time is never read
+                        if self.time_info.reached(msg.recv) {
+                            ledger.record_dead_letter(from, to);
+                        } else {
+                            ledger.record_delivered(from, to);
+                        }
+                    }
+                    #[cfg(feature = "validate-causality")]
+                    self.context.causal.deliver(&msg, clock);
+                    self.commit_mail(msg)
+                }
+                Transfer::AntiMsg(anti_msg) => self.annihilate(anti_msg, from_world),
+            }
+            counter += 1;
+        }
+        self.context.counter.fetch_sub(in_flight, Ordering::SeqCst);
+        #[cfg(feature = "rollback-export")]
+        if let Some(export) = &mut self.rollback_export {
+            export.receive(counter as u64);
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.span("poll", start, &[("mail", counter as u64)]);
+        }
+        Ok(())
+    }
+
+    /// Hand mail the `Galaxy` couldn't deliver back to the agents that sent it. Mail sent after the current time was
+    /// already taken back by a rollback, and is sent again when its event runs again, so it isn't reported.
+    fn poll_delivery_failures(&mut self) -> Result<(), AikaError> {
+        let failures =
+            std::mem::take(&mut *self.failures.lock().map_err(|_| AikaError::PoisonedLock)?);
+        if failures.is_empty() {
+            return Ok(());
+        }
+        self.mark_busy();
+        let now = self.now();
+        let count = failures.len();
+        for failure in failures {
+            let from = failure.msg.from;
+            if let Some(ledger) = &mut self.context.ledger {
+                ledger.record_dead_letter(
+                    Address::new(self.context.world_id, Some(from)),
+                    Address::new(failure.to_world, failure.msg.to),
+                );
+            }
+            self.stats.undelivered += 1;
+            if failure.msg.sent > now || from >= self.agents.len() {
+                continue;
+            }
+            self.context.time = now;
+            self.isolate(from, |agent, context| {
+                agent.on_send_failed(context, failure, from)
+            })?;
+        }
+        self.context.counter.fetch_sub(count, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Record the rollback to `time` that `mail` is about to cause.
+    #[cfg(feature = "rollback-export")]
+    fn export_rollback(&mut self, mail: &Mail<MessageType>, time: u64) -> Result<(), AikaError> {
+        let Some(export) = &mut self.rollback_export else {
+            return Ok(());
+        };
+        let (cause_agent, cause_to, cause_sent, cause_anti) = match &mail.transfer {
+            Transfer::Msg(msg) => (msg.from, msg.to, msg.sent, false),
+            Transfer::AntiMsg(anti) => (anti.from, anti.to, anti.sent, true),
+        };
+        let (recv_rate, send_rate) = export.rates();
+        export.record(RollbackRecord {
+            world: self.context.world_id,
+            from: self.event_system.time(),
+            to: time,
+            gvt: self.gvt.load(Ordering::Acquire),
+            cause_world: mail.from_world,
+            cause_agent,
+            cause_to,
+            cause_sent,
+            cause_recv: time,
+            cause_anti,
+            recv_rate,
+            send_rate,
+        })
+    }
+
+    fn trace_stall(&mut self, reason: &'static str) {
+        if let Some(trace) = &mut self.trace {
+            trace.stall(reason);
+        }
+    }
+
+    /// Wait for `pause` without stepping. Batched mail goes out first, since GVT can't advance past it.
+    fn stall(&mut self, reason: &'static str, pause: Duration) -> Result<(), AikaError> {
+        let sent = self.context.bundles_sent;
+        self.context.flush_mail()?;
+        if self.context.bundles_sent != sent {
+            self.wakeup.notify();
+        }
+        self.trace_stall(reason);
+        sleep(pause);
+        Ok(())
+    }
+
+    /// Call into an agent, catching a panic and applying the `PanicPolicy`. Returns `None` if the agent was removed.
+    fn isolate<R>(
+        &mut self,
+        id: usize,
+        call: impl FnOnce(
+            &mut dyn ThreadedAgent<INTER_SLOTS, MessageType>,
+            &mut PlanetContext<INTER_SLOTS, MessageType>,
+        ) -> R,
+    ) -> Result<Option<R>, AikaError> {
+        self.context.next_agent = self.next_agent();
+        self.context.sandbox.acting = Some(id);
+        let (agents, context) = (&mut self.agents, &mut self.context);
+        let outcome = catch_unwind(AssertUnwindSafe(|| call(agents[id].as_mut(), context)));
+        self.context.sandbox.acting = None;
+        let payload = match outcome {
+            Ok(result) => {
+                self.apply_spawns();
+                return Ok(Some(result));
+            }
+            Err(payload) => payload,
+        };
+        self.context.spawns.clear();
+        let time = self.context.time;
+        if self.panic_policy == PanicPolicy::Abort {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            return Err(AikaError::AgentPanicked {
+                world: self.context.world_id,
+                agent: id,
+                time,
+                message,
+            });
+        }
+        self.agents[id] = Box::new(Departed);
+        self.failed.insert(id);
+        self.event_system.drain(|event| event.agent == id)?;
+        self.context.wakeups.retain(|(agent, ..)| *agent != id);
+        self.context.timers.retain(|timer| timer.from != id);
+        self.context.interrupts.remove_agent(id);
+        self.agent_count
+            .store(self.live_agents(), Ordering::Release);
+        self.stats.warnings.push(SimWarning::AgentRemoved {
+            world: self.context.world_id,
+            agent: id,
+            time,
+        });
+        Ok(None)
+    }
+
+    /// Drain the mail of the next tick, ordered by offset.
+    fn tick_mail(&mut self) -> Vec<Msg<MessageType>> {
+        let Ok(mut msgs) = self.local_messages.tick() else {
+            return Vec::new();
+        };
+        match self.deterministic {
+            Some(seed) => {
+                order_mail_canonically(&mut msgs, seed);
+                self.read_log.extend_from_slice(&msgs);
+            }
+            None => msgs.sort_by(|a, b| a.offset.total_cmp(&b.offset)),
+        }
+        msgs
+    }
+
+    /// Drain the events of the next tick, ordered by offset.
+    fn tick_events(&mut self) -> Vec<Event> {
+        let Ok(mut events) = self.event_system.tick() else {
+            return Vec::new();
+        };
+        match self.deterministic {
+            Some(seed) => {
+                order_tick_canonically(&mut events, seed);
+                self.ran_log.extend_from_slice(&events);
+            }
+            None => order_within_tick(&mut events),
+        }
+        events
+    }
+
+    /// Hand ordered mail to its recipients.
+    fn read_mail(&mut self, msgs: Vec<Msg<MessageType>>) -> Result<(), AikaError> {
+        // broadcasts are batched per recipient so their relative order doesn't depend on arrival order
+        let mut broadcasts: BTreeMap<usize, Vec<Msg<MessageType>>> = BTreeMap::new();
+        for msg in msgs {
+            let id = msg.to;
+            if id.is_none() {
+                let targets = match msg.group {
+                    Some(group) => self.context.groups.members(group),
+                    None => (0..self.agents.len()).collect(),
+                };
+                for i in targets {
+                    broadcasts.entry(i).or_default().push(msg);
+                }
+                continue;
+            }
+            let id = id.unwrap();
+            if let Some(log) = &mut self.causal_log {
+                log.activate(CausalNode::new(self.context.world_id, id, msg.recv));
+            }
+            #[cfg(feature = "validate-causality")]
+            self.context.causal.read(&msg, id, msg.recv)?;
+            self.context.time = msg.recv;
+            self.context.cause = msg.id;
+            if msg.wake {
+                let event =
+                    Event::new(msg.sent, msg.recv, id, Action::Wait).with_offset(msg.offset);
+                // a trigger's step can't break off a tick it isn't part of
+                self.run_events(vec![event])?;
+                continue;
+            }
+            if let Some(Rpc::Discovery(request)) = msg.rpc {
+                let Some(querier) = self.context.rpc.settle(request, msg.recv) else {
+                    continue;
+                };
+                self.context.interrupts.interrupt(querier, msg.recv);
+                let Some(query) = self.context.queries.get(&request).copied() else {
+                    continue;
+                };
+                let peers = self.context.peers(&query);
+                self.isolate(querier, |agent, context| {
+                    agent.on_peers(context, request, query, peers, querier)
+                })?;
+                continue;
+            }
+            if let Some(Rpc::Reply(request) | Rpc::Timeout(request)) = msg.rpc {
+                // only the first of the reply and the timeout settles the request
+                let Some(requester) = self.context.rpc.settle(request, msg.recv) else {
+                    continue;
+                };
+                self.context.interrupts.interrupt(requester, msg.recv);
+                let reply = match msg.timer {
+                    true => Reply::TimedOut,
+                    false => Reply::Data(msg.data),
+                };
+                self.isolate(requester, |agent, context| {
+                    agent.on_reply(context, request, reply, requester)
+                })?;
+                continue;
+            }
+            self.context.interrupts.interrupt(id, msg.recv);
+            if msg.timer {
+                self.isolate(id, |agent, context| agent.on_timer(context, msg.data, id))?;
+                continue;
+            }
+            if let Some(recorder) = &mut self.kpis {
+                recorder.delivered(msg.recv, 1);
+            }
+            #[cfg(feature = "agent-profiling")]
+            let start = self.profiler.as_ref().map(|_| profiling::cycles());
+            self.isolate(id, |agent, context| agent.read_message(context, msg, id))?;
+            #[cfg(feature = "agent-profiling")]
+            if let (Some(profiler), Some(start)) = (&mut self.profiler, start) {
+                profiler.record(
+                    id,
+                    Call::ReadMessage,
+                    profiling::cycles().wrapping_sub(start),
+                );
+            }
+        }
+        for (i, mut batch) in broadcasts {
+            match self.deterministic {
+                Some(seed) => order_mail_canonically(&mut batch, seed),
+                None => batch.sort_by(|a, b| {
+                    a.offset
+                        .total_cmp(&b.offset)
+                        .then_with(|| (a.from, a.sent).cmp(&(b.from, b.sent)))
+                }),
+            }
+            let recv = batch[0].recv;
+            if let Some(log) = &mut self.causal_log {
+                log.activate(CausalNode::new(self.context.world_id, i, recv));
+            }
+            #[cfg(feature = "validate-causality")]
+            for msg in &batch {
+                self.context.causal.read(msg, i, recv)?;
+            }
+            self.context.time = recv;
+            self.context.cause = batch[0].id;
+            if let Some(recorder) = &mut self.kpis {
+                recorder.delivered(recv, batch.len() as u64);
+            }
+            self.context.interrupts.interrupt(i, recv);
+            self.isolate(i, |agent, context| agent.read_messages(context, batch, i))?;
+        }
+        Ok(())
+    }
+
+    /// Run the parallel part of the steps of the agents due in `events` on `workers` threads, if more than one is due.
+    fn prepare_steps(&mut self, events: &[Event], workers: usize) -> Result<(), AikaError> {
+        let interrupts = &self.context.interrupts;
+        let due = events
+            .iter()
+            .filter(|event| interrupts.wake(event.agent, event.time) != Wake::Dropped)
+            .map(|event| event.agent)
+            .collect::<BTreeSet<_>>();
+        if due.len() < 2 {
+            return Ok(());
+        }
+        let now = self.now();
+        let agents = self
+            .agents
+            .iter_mut()
+            .enumerate()
+            .filter(|(id, _)| due.contains(id))
+            .filter_map(|(id, agent)| Some((id, agent.parallel()?)))
+            .collect::<Vec<_>>();
+        for (id, payload) in parallel::prepare_all(agents, now, workers) {
+            self.isolate(id, |_, _| resume_unwind(payload))?;
+        }
+        Ok(())
+    }
+
+    /// Step ordered events, returning whether an agent broke off the rest of the tick.
+    fn run_events(&mut self, events: Vec<Event>) -> Result<bool, AikaError> {
+        if let Some(workers) = self.parallel_steps {
+            self.prepare_steps(&events, workers)?;
+        }
+        // the clock doesn't move within a tick
+        let (now, world_id) = (self.now(), self.context.world_id);
+        let mut processed = 0;
+        for event in events {
+            let wake = self.context.interrupts.wake(event.agent, event.time);
+            if wake == Wake::Dropped {
+                continue;
+            }
+            let sequence = self.sequences.next(event.agent, event.time);
+            let version = self.agents[event.agent].version();
+            self.context.event_key = (event.agent, event.time, sequence, version);
+            self.context.cause = event.id;
+            let cause = CausalNode::new(world_id, event.agent, event.time);
+            if let Some(log) = &mut self.causal_log {
+                log.activate(cause);
+            }
+            if let Some(hook) = &mut self.hook {
+                hook.on_event(world_id, event.agent, event.time);
+            }
+            processed += 1;
+            if let Some(recorder) = &mut self.kpis {
+                recorder.event(event.time, event.agent);
+            }
+            if event.agent >= self.agent_load.len() {
+                self.agent_load.resize(event.agent + 1, 0);
+            }
+            self.agent_load[event.agent] += 1;
+            self.context.time = event.time;
+            self.context.offset = event.offset;
+            let id = event.agent;
+            self.context.interrupted = wake == Wake::Interrupted;
+            self.context.interrupts.stepped(id, now);
+            #[cfg(feature = "agent-profiling")]
+            let start = self.profiler.as_ref().map(|_| profiling::cycles());
+            let event = self.isolate(id, |agent, context| agent.step(context, id))?;
+            self.context.interrupted = false;
+            #[cfg(feature = "agent-profiling")]
+            if let (Some(profiler), Some(start)) = (&mut self.profiler, start) {
+                profiler.record(id, Call::Step, profiling::cycles().wrapping_sub(start));
+            }
+            let Some(event) = event else {
+                continue;
+            };
+            match event.yield_ {
+                Action::Timeout(delay) => {
+                    let time = self.steps_later(event.agent, now, delay)?;
+                    if self.time_info.past(time) {
+                        continue;
+                    }
+
+                    self.commit(
+                        Event::new(now, time, event.agent, Action::Wait).with_offset(event.offset),
+                    )?;
+                    self.record_link(cause, event.agent, time);
                 }
-                if diff > endidx {
-                    continue;
+                Action::Interruptible(delay) => {
+                    let time = self.steps_later(event.agent, now, delay)?;
+                    if self.time_info.past(time) {
+                        continue;
+                    }
+                    self.context.interrupts.sleep(event.agent, now, time);
+                    self.commit(
+                        Event::new(now, time, event.agent, Action::Wait).with_offset(event.offset),
+                    )?;
+                    self.record_link(cause, event.agent, time);
+                }
+                Action::Schedule(time) => {
+                    self.commit(
+                        Event::new(now, time, event.agent, Action::Wait).with_offset(event.offset),
+                    )?;
+                    self.record_link(cause, event.agent, time);
+                }
+                Action::Trigger { time, idx } => {
+                    let groups = &self.context.groups;
+                    let sandbox = &mut self.context.sandbox;
+                    if sandbox
+                        .check_trigger(world_id, event.agent, idx, now, groups)
+                        .is_err()
+                    {
+                        continue;
+                    }
+                    // an agent moved by a split or a migration is stepped by a wake-up `Msg` on its new planet
+                    if let Some((world, moved)) = self.moved_to(idx) {
+                        let mut wake =
+                            Msg::new(MessageType::zeroed(), now, time, event.agent, Some(moved))
+                                .with_offset(event.offset);
+                        wake.wake = true;
+                        self.context.send_mail(wake, world)?;
+                        continue;
+                    }
+                    self.commit(
+                        Event::new(now, time, idx, Action::Wait).with_offset(event.offset),
+                    )?;
+                    self.record_link(cause, idx, time);
+                }
+                Action::Acquire { resource, priority } => {
+                    let resources = &mut self.context.resources;
+                    if resources.acquire(resource, event.agent, priority, now)? {
+                        self.wake_granted(cause, event.agent, now)?;
+                    }
                 }
-                let offset = ((diff - startidx) / (CLOCK_SLOTS.pow(k as u32)) + idx) % CLOCK_SLOTS;
-                let msgs = &mut self.local_messages.schedule.wheels[k][offset];
-                let mut remaining = Vec::new();
-                while let Some(msg) = msgs.pop() {
-                    if anti_msg.annihilate(&msg) {
+                Action::Release { resource, delay } => {
+                    let resources = &mut self.context.resources;
+                    if let Some(next) = resources.release(resource, event.agent, now)? {
+                        self.wake_granted(cause, next, now)?;
+                    }
+                    let Some(delay) = delay else {
+                        continue;
+                    };
+                    let time = self.steps_later(event.agent, now, delay)?;
+                    if self.time_info.past(time) {
                         continue;
                     }
-                    remaining.push(msg);
+                    self.commit(
+                        Event::new(now, time, event.agent, Action::Wait).with_offset(event.offset),
+                    )?;
+                    self.record_link(cause, event.agent, time);
+                }
+                Action::Wait => {}
+                Action::Break => {
+                    self.events.fetch_add(processed, Ordering::Relaxed);
+                    return Ok(true);
                 }
-                *msgs = remaining;
-                return;
             }
         }
-        // fallback if timestamp beyond clock horizon
-        let mut to_be_removed = BTreeSet::new();
-        for i in self.local_messages.overflow.iter().enumerate() {
-            if anti_msg.annihilate(&i.1 .0) {
-                to_be_removed.insert(Reverse(i.0));
+        self.events.fetch_add(processed, Ordering::Relaxed);
+        Ok(false)
+    }
+
+    /// Merge a tick's ordered mail and events by offset, mail first on ties, and handle them in one pass. Runs of
+    /// mail between two events are read together, so broadcasts among them are still batched per recipient.
+    fn run_merged(
+        &mut self,
+        msgs: Vec<Msg<MessageType>>,
+        events: Vec<Event>,
+    ) -> Result<(), AikaError> {
+        let mut msgs = msgs.into_iter().peekable();
+        let mut broke = false;
+        for event in events {
+            let mut run = Vec::new();
+            while let Some(msg) = msgs.next_if(|msg| msg.offset <= event.offset) {
+                run.push(msg);
+            }
+            self.read_mail(run)?;
+            if !broke {
+                broke = self.run_events(vec![event])?;
             }
         }
-        let current = self.local_messages.overflow.clone();
-        let mut vec = current.into_iter().collect::<Vec<_>>();
-        for i in to_be_removed {
-            let idx = i.0;
-            vec.remove(idx);
-        }
-        self.local_messages.overflow = BinaryHeap::from_iter(vec);
+        self.read_mail(msgs.collect())
     }
 
-    fn poll_interplanetary_messenger(&mut self) -> Result<(), AikaError> {
-        let mut counter = 0;
-        let maybe = self.context.user.poll();
-        if maybe.is_none() {
-            return Ok(());
+    /// step forward one timestamp on all local clocks
+    pub(crate) fn step(&mut self) -> Result<(), AikaError> {
+        self.check_time_validity()?;
+        panics::set_time(self.now());
+        let now = self.now();
+        if let Some(recorder) = self.occupancy.as_mut().filter(|recorder| recorder.due(now)) {
+            let (levels, overflow) = self.event_system.occupancy();
+            recorder.record(now, levels, overflow);
         }
-        for msg in maybe.unwrap() {
-            if let Some(to) = msg.to_world {
-                if to != self.context.world_id {
-                    return Err(AikaError::MismatchedDeliveryAddress);
-                }
+        self.context.extensions.before_step(self.now());
+
+        // process the next tick's mail and events in the configured order
+        self.context.sends = 0;
+        match self.tick_order {
+            TickOrder::MessagesFirst => {
+                let msgs = self.tick_mail();
+                self.read_mail(msgs)?;
+                self.commit_wakeups(self.now())?;
+                let events = self.tick_events();
+                self.run_events(events)?;
             }
-            let time = msg.transfer.time();
-            if time < self.now() {
-                self.rollback(time)?;
+            TickOrder::EventsFirst => {
+                self.commit_wakeups(self.now())?;
+                let events = self.tick_events();
+                self.run_events(events)?;
+                let msgs = self.tick_mail();
+                self.read_mail(msgs)?;
             }
-            match msg.open_letter() {
-                Transfer::Msg(msg) => self.commit_mail(msg),
-                Transfer::AntiMsg(anti_msg) => self.annihilate(anti_msg),
+            TickOrder::MergedByPriority => {
+                self.commit_wakeups(self.now())?;
+                let msgs = self.tick_mail();
+                let events = self.tick_events();
+                self.run_merged(msgs, events)?;
             }
-            counter += 1;
         }
-        self.context.counter.fetch_sub(counter, Ordering::SeqCst);
+        self.notify_subscribers()?;
+        // this tick's events are already consumed
+        self.commit_wakeups(self.now().saturating_add(1))?;
+        let gvt = self.gvt.load(Ordering::Acquire);
+        self.context.extensions.after_step(self.now(), gvt)?;
+        self.context.subscriptions.prune(gvt);
+        self.context.rpc.prune(gvt);
+        self.read_log.retain(|msg| msg.recv >= gvt);
+        self.ran_log.retain(|event| event.time >= gvt);
+        // a query is answered the tick after it was made
+        self.context
+            .queries
+            .retain(|id, _| id.time.saturating_add(1) >= gvt);
+        self.context.interrupts.prune(gvt);
+        self.context.resources.prune(gvt);
+        self.context.partitions.prune(gvt);
+        if let Some(faults) = &mut self.context.faults {
+            faults.prune(gvt);
+        }
+        if let Some(parcels) = &self.context.parcels {
+            parcels.prune(self.context.world_id, gvt)?;
+        }
+        if let Some(recorder) = &mut self.kpis {
+            recorder.commit(gvt);
+        }
+        #[cfg(feature = "validate-causality")]
+        self.check_causality(gvt)?;
+        self.end_warmup(gvt);
+        let report = self
+            .rollback_reports
+            .as_mut()
+            .and_then(|reports| reports.advance(gvt));
+        self.emit_rollback_report(report);
+        #[cfg(feature = "rollback-export")]
+        if let Some(export) = &mut self.rollback_export {
+            export.tick(self.context.sends as u64);
+        }
+        self.event_system.increment()?;
+        self.stats.overflow = self.event_system.overflow_stats();
+        self.local_messages
+            .schedule
+            .increment(&mut self.local_messages.overflow);
+        self.flush_contributions()?;
+        self.flush_publications()?;
+        self.flush_barrier_requests()?;
+        self.local_time.store(self.now(), Ordering::Release);
+        self.wakeup.notify();
+        std::thread::yield_now();
         Ok(())
     }
 
-    /// step forward one timestamp on all local clocks
-    fn step(&mut self) -> Result<(), AikaError> {
-        self.check_time_validity()?;
+    /// Jump to the next tick with anything due, if fast-forwarding is enabled. The jump stops at the throttle horizon,
+    /// at GVT while a link is conservative, and at the next checkpoint, cut, barrier or the terminal tick, where the
+    /// run loop decides whether to stall.
+    fn skip_quiet_ticks(&mut self, checkpoint: u64) -> Result<(), AikaError> {
+        let batched = self
+            .context
+            .outbox
+            .as_ref()
+            .is_some_and(|outbox| !outbox.is_empty());
+        if !self.fast_forward || !self.context.extensions.is_empty() || batched {
+            return Ok(());
+        }
+        let now = self.now();
+        let gvt = self.gvt.load(Ordering::SeqCst);
+        let mut limit = gvt
+            .saturating_add(self.throttle_horizon)
+            .min(self.time_info.terminal_step());
+        if self.is_conservative(gvt) {
+            limit = limit.min(gvt);
+        }
+        let stops = [
+            Some(checkpoint),
+            Some(self.parking.horizon()),
+            self.cuts.first().map(|(cut, _)| *cut),
+            self.context
+                .barriers
+                .as_ref()
+                .and_then(|barriers| barriers.earliest()),
+        ];
+        for stop in stops.into_iter().flatten().filter(|stop| *stop >= now) {
+            limit = limit.min(stop);
+        }
+        let next = [
+            self.event_system.next_due(),
+            clock_next_due(&self.local_messages.schedule, &self.local_messages.overflow),
+            self.context.subscriptions.next_due(),
+            self.occupancy
+                .as_ref()
+                .map(|recorder| recorder.next_due(now)),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(limit, u64::min);
+        if next <= now {
+            return Ok(());
+        }
+        self.event_system.skip_to(next)?;
+        skip_clock_to(
+            &mut self.local_messages.schedule,
+            &mut self.local_messages.overflow,
+            next,
+        )?;
+        self.stats.skipped_ticks += next - now;
+        self.local_time.store(next, Ordering::Release);
+        self.wakeup.notify();
+        Ok(())
+    }
 
-        // process messages at the next time step
-        if let Ok(msgs) = self.local_messages.schedule.tick() {
-            for msg in msgs {
-                let id = msg.to;
-                if id.is_none() {
-                    for i in 0..self.agents.len() {
-                        self.context.time = msg.recv;
-                        self.agents[i].read_message(&mut self.context, msg, i);
-                    }
+    /// Hand every notice due by now to its subscriber, including those raised by the subscribers themselves.
+    fn notify_subscribers(&mut self) -> Result<(), AikaError> {
+        let now = self.now();
+        loop {
+            let notices = self.context.subscriptions.take_due(now);
+            if notices.is_empty() {
+                return Ok(());
+            }
+            for notice in notices {
+                if notice.agent >= self.agents.len() {
                     continue;
                 }
-                let id = id.unwrap();
-                self.agents[id].read_message(&mut self.context, msg, id);
-            }
-        }
-        // process events at the next time step
-        if let Ok(events) = self.event_system.local_clock.tick() {
-            for event in events {
-                self.context.time = event.time;
-                let event = self.agents[event.agent].step(&mut self.context, event.agent);
-                match event.yield_ {
-                    Action::Timeout(time) => {
-                        if (self.now() + time) as f64 * self.time_info.timestep
-                            > self.time_info.terminal
-                        {
-                            continue;
-                        }
+                self.context.time = now;
+                self.context.offset = 0.0;
+                let (id, cell) = (notice.agent, notice.cell);
+                self.isolate(id, |agent, context| {
+                    agent.on_world_change(context, cell, id)
+                })?;
+            }
+        }
+    }
 
-                        self.commit(Event::new(
-                            self.now(),
-                            self.now() + time,
-                            event.agent,
-                            Action::Wait,
-                        ));
-                    }
-                    Action::Schedule(time) => {
-                        self.commit(Event::new(self.now(), time, event.agent, Action::Wait));
-                    }
-                    Action::Trigger { time, idx } => {
-                        self.commit(Event::new(self.now(), time, idx, Action::Wait));
-                    }
-                    Action::Wait => {}
-                    Action::Break => {
-                        break;
-                    }
-                }
+    /// Commit the timers agents set, the wake-ups they requested through `PlanetContext::schedule_wakeup()` and
+    /// the early wake-ups of interrupted waits, dropping any requested wake-up before `earliest`.
+    fn commit_wakeups(&mut self, earliest: u64) -> Result<(), AikaError> {
+        for timer in std::mem::take(&mut self.context.timers) {
+            self.commit_mail(timer);
+        }
+        let now = self.now();
+        for (agent, time, cause) in std::mem::take(&mut self.context.wakeups) {
+            if time < earliest || self.time_info.past(time) {
+                continue;
             }
+            self.context.cause = cause;
+            self.commit(Event::new(now, time, agent, Action::Wait))?;
+        }
+        self.context.cause = CausalId::NONE;
+        for (agent, time) in self.context.interrupts.schedule(earliest) {
+            if self.time_info.past(time) {
+                continue;
+            }
+            self.commit(Event::new(now, time, agent, Action::Wait))?;
         }
-        self.event_system
-            .local_clock
-            .increment(&mut self.event_system.overflow);
-        self.local_messages
-            .schedule
-            .increment(&mut self.local_messages.overflow);
-        self.local_time.store(self.now(), Ordering::Release);
-        std::thread::yield_now();
         Ok(())
     }
 
     fn check_time_validity(&self) -> Result<(), AikaError> {
         let load = self.local_time.load(Ordering::Acquire);
-        if self.local_messages.schedule.time != self.event_system.local_clock.time
+        if self.local_messages.schedule.time != self.event_system.time()
             && self.local_messages.schedule.time != load
         {
             return Err(AikaError::ClockSyncIssue);
         }
-        if self.time_info.terminal <= self.time_info.timestep * load as f64 {
+        if self.time_info.reached(load) {
             return Err(AikaError::PastTerminal);
         }
         let gvt = self.gvt.load(Ordering::Acquire);
-        if gvt as f64 * self.time_info.timestep >= self.time_info.terminal {
+        if self.time_info.reached(gvt) {
             return Err(AikaError::PastTerminal);
         }
         Ok(())
     }
 
-    /// Run the `Planet` optimistically.
+    /// Run the `Planet` optimistically, to the end of the run or until released at the horizon it is parked at. An
+    /// error stops every other thread of the run.
     pub fn run(&mut self) -> Result<(), AikaError> {
+        let result = self
+            .core
+            .map_or(Ok(()), affinity::pin_current_thread)
+            .and_then(|_| {
+                catch_unwind(AssertUnwindSafe(|| self.run_optimistically()))
+                    .unwrap_or_else(|_| Err(panics::error(panics::take())))
+            });
+        match result {
+            // parked halfway, to be resumed by the next round unless the run was cancelled
+            Ok(()) if self.parking.released() && !self.parking.cancelled() => {}
+            Ok(()) => self.context.extensions.finish(self.now()),
+            Err(_) => self.halt.store(true, Ordering::Release),
+        }
+        result
+    }
+
+    fn run_optimistically(&mut self) -> Result<(), AikaError> {
         //let id = self.context.world_id;
+        self.agent_count
+            .store(self.live_agents(), Ordering::Release);
+        let mut submitted = None;
+        // the last checkpoint lands on the terminal step, where nothing is left to stall for
+        let last_checkpoint = (self.time_info.terminal / self.time_info.timestep) as u64;
         loop {
+            if self.halt.load(Ordering::Acquire) {
+                break;
+            }
             let checkpoint = self.next_checkpoint.load(Ordering::SeqCst);
+            // route changes are applied before the checkpoint advances, while this `Planet` still waits at the old one
+            self.rewire()?;
+            // the `Galaxy` requests splits before advancing the checkpoint, so this `Planet` is still at the old one
+            let spare = self.split_request.swap(0, Ordering::SeqCst);
+            if spare != 0 {
+                self.split(spare - 1)?;
+            }
+            // migrations are also requested before the checkpoint advances, and release it once they land
+            let target = self.migrate_request.swap(0, Ordering::SeqCst);
+            if target != 0 {
+                self.emigrate(target - 1)?;
+            }
+            self.immigrate()?;
             let now = self.now();
             self.poll_interplanetary_messenger()?;
-            if now == checkpoint
-                && now != (self.time_info.terminal / self.time_info.timestep) as u64
-            {
+            self.poll_delivery_failures()?;
+            self.poll_control();
+            if self.parking.released() {
+                return Ok(());
+            }
+            // the `Galaxy` moves GVT to the terminal time early once the whole run is quiescent
+            if self.time_info.reached(self.gvt.load(Ordering::SeqCst)) {
+                break;
+            }
+            if self.end_when_quiescent && self.is_quiescent() {
+                self.mark_quiet();
+            }
+            if self.control.paused {
+                self.stall("paused", Duration::from_nanos(100))?;
+                continue;
+            }
+            if self.gvt.load(Ordering::SeqCst) < self.activation {
+                self.stall("dormant", Duration::from_millis(1))?;
+                continue;
+            }
+            if let Some(&(cut, _)) = self.cuts.first() {
+                if now == cut {
+                    // once GVT reaches the cut nothing before it can roll back and no mail is in transit
+                    if self.gvt.load(Ordering::SeqCst) < cut {
+                        self.stall("at cut", Duration::from_nanos(100))?;
+                        continue;
+                    }
+                    let record = self.record_cut(cut)?;
+                    let (_, sender) = self.cuts.remove(0);
+                    let _ = sender.send(record);
+                }
+            }
+            // the `Galaxy` releases a barrier once every `Planet` waits on it
+            if let Some(barriers) = &self.context.barriers {
+                if barriers.holds(now)? {
+                    self.stall("at barrier", Duration::from_nanos(100))?;
+                    continue;
+                }
+            }
+            // at the terminal time the run ends rather than parks
+            if now >= self.parking.horizon() && !self.time_info.reached(now) {
+                self.stall("at horizon", Duration::from_nanos(100))?;
+                continue;
+            }
+            if now == checkpoint && now != last_checkpoint {
                 //println!("world {id} found sleeping");
-                sleep(Duration::from_nanos(100));
+                self.stall("at checkpoint", Duration::from_nanos(100))?;
                 continue;
             }
             let gvt = self.gvt.load(Ordering::SeqCst);
             //println!("world {id} found gvt {gvt}, has local time {now}");
-            if gvt + self.throttle_horizon < self.now() {
+            if gvt.saturating_add(self.throttle_horizon) < self.now() {
                 //println!("world {id} found sleeping");
-                sleep(Duration::from_nanos(100));
+                self.stall("throttled", Duration::from_nanos(100))?;
+                continue;
+            }
+            // under memory pressure nothing speculative is added until GVT commits what is held
+            if self.memory_pressed && gvt < self.now() {
+                self.stall("memory pressure", Duration::from_nanos(100))?;
                 continue;
             }
+            // a link in conservative mode can't send stragglers if this `Planet` never runs ahead of GVT
+            if gvt < self.now() && self.is_conservative(gvt) {
+                self.stall("conservative", Duration::from_nanos(100))?;
+                continue;
+            }
+            let start = self.trace.as_mut().map(|trace| {
+                trace.resume();
+                Instant::now()
+            });
             let step = self.step();
             if let Err(AikaError::PastTerminal) = step {
-                break;
+                // stay responsive to stragglers until the `Galaxy` sees every planet done with nothing in flight
+                let gvt = self.gvt.load(Ordering::SeqCst);
+                if self.time_info.reached(gvt) {
+                    break;
+                }
+                self.context.flush_mail()?;
+                sleep(Duration::from_nanos(100));
+                continue;
             }
             step?;
+            if let Some(horizon) = self.throttle.as_mut().and_then(ThrottleController::on_step) {
+                if horizon != self.throttle_horizon {
+                    self.throttle_horizon = horizon;
+                    self.stats.horizon_changes += 1;
+                }
+            }
+            self.context.flush_due(self.now())?;
+            self.check_memory()?;
+            if let (Some(trace), Some(start)) = (&mut self.trace, start) {
+                trace.span("step", start, &[("time", self.event_system.time() - 1)]);
+            }
+            self.skip_quiet_ticks(checkpoint)?;
+            if self.now() == checkpoint && submitted != Some(checkpoint) {
+                submitted = Some(checkpoint);
+                if let Some(hook) = &mut self.hook {
+                    hook.on_block_submit(self.context.world_id, checkpoint);
+                }
+            }
         }
         //println!("made it here for planet {id}, almost done");
+        if let Some(links) = &self.context.links {
+            self.stats.links = links.stats();
+        }
+        if let Some(faults) = &self.context.faults {
+            self.stats.faults = faults.stats();
+        }
+        self.stats.violations = self.context.sandbox.violations().to_vec();
+        if let Some(recorder) = &mut self.kpis {
+            recorder.commit(u64::MAX);
+        }
+        #[cfg(feature = "validate-causality")]
+        self.check_causality(u64::MAX)?;
+        self.stats.bundles_sent = self.context.bundles_sent;
+        self.stats.throttle_horizon = self.throttle_horizon;
+        let report = self
+            .rollback_reports
+            .as_mut()
+            .and_then(RollbackAggregator::finish);
+        self.emit_rollback_report(report);
+        #[cfg(feature = "rollback-export")]
+        if let Some(export) = &mut self.rollback_export {
+            export.flush()?;
+        }
         Ok(())
     }
 }
@@ -436,14 +2937,17 @@ mod planet_tests {
     use super::*;
     use crate::{
         agents::{PlanetContext, ThreadedAgent},
-        mt::hybrid::planet::{Planet, RegistryOutput},
-        objects::{Action, Event, Mail, Msg},
+        mt::hybrid::{
+            galaxy::Galaxy,
+            planet::{Planet, RegistryOutput},
+        },
+        objects::{Action, DeliveryFailure, Event, EventId, MailBundle, Msg},
     };
     use bytemuck::{Pod, Zeroable};
     use mesocarp::comms::mailbox::ThreadedMessenger;
     use std::sync::{
         atomic::{AtomicU64, Ordering},
-        Arc,
+        Arc, Mutex,
     };
 
     // Simple test message type
@@ -533,7 +3037,7 @@ mod planet_tests {
         let checkpoint = Arc::new(AtomicU64::new(100));
         let counter = Arc::new(AtomicUsize::new(0));
         // Create a simple messenger for testing
-        let messenger = ThreadedMessenger::<16, Mail<TestMessage>>::new(vec![world_id])?;
+        let messenger = ThreadedMessenger::<16, MailBundle<TestMessage>>::new(vec![world_id])?;
         let user = messenger.get_user(world_id)?;
 
         Ok(RegistryOutput::new(
@@ -644,6 +3148,212 @@ mod planet_tests {
         assert!(matches!(result, Err(AikaError::PastTerminal)));
     }
 
+    type BatchLog = Arc<Mutex<Vec<Vec<(usize, u64)>>>>;
+
+    // Records each batch of broadcasts as (from, sent) pairs
+    struct BatchAgent {
+        batches: BatchLog,
+    }
+
+    impl ThreadedAgent<16, TestMessage> for BatchAgent {
+        fn step(&mut self, context: &mut PlanetContext<16, TestMessage>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, TestMessage>,
+            msg: Msg<TestMessage>,
+            _agent_id: usize,
+        ) {
+            self.batches
+                .lock()
+                .unwrap()
+                .push(vec![(msg.from, msg.sent)]);
+        }
+
+        fn read_messages(
+            &mut self,
+            _context: &mut PlanetContext<16, TestMessage>,
+            msgs: Vec<Msg<TestMessage>>,
+            _agent_id: usize,
+        ) {
+            let batch = msgs.iter().map(|msg| (msg.from, msg.sent)).collect();
+            self.batches.lock().unwrap().push(batch);
+        }
+    }
+
+    #[test]
+    fn test_simultaneous_broadcasts_are_batched() {
+        let registry = create_mock_registry(0).unwrap();
+        let mut planet =
+            Planet::<16, 128, 2, TestMessage>::create(1000.0, 1.0, 50, 1024, 512, registry)
+                .unwrap();
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        planet.spawn_agent(
+            Box::new(BatchAgent {
+                batches: Arc::clone(&batches),
+            }),
+            256,
+        );
+        let data = TestMessage {
+            value: 1,
+            sender_id: 0,
+        };
+        for (from, sent) in [(2, 0), (0, 1), (1, 0), (0, 0)] {
+            assert!(planet
+                .local_messages
+                .schedule
+                .insert(Msg::new(data, sent, 2, from, None))
+                .is_ok());
+        }
+        assert!(planet
+            .local_messages
+            .schedule
+            .insert(Msg::new(data, 0, 2, 5, Some(0)))
+            .is_ok());
+        for _ in 0..3 {
+            planet.step().unwrap();
+        }
+
+        // the direct message is read on its own, the broadcasts arrive as one sorted batch
+        assert_eq!(
+            *batches.lock().unwrap(),
+            vec![vec![(5, 0)], vec![(0, 0), (0, 1), (1, 0), (2, 0)]]
+        );
+    }
+
+    // Records the id of every event it is stepped for
+    struct IdRecorder {
+        ids: Arc<Mutex<Vec<EventId>>>,
+    }
+
+    impl ThreadedAgent<16, TestMessage> for IdRecorder {
+        fn step(&mut self, context: &mut PlanetContext<16, TestMessage>, agent_id: usize) -> Event {
+            self.ids.lock().unwrap().push(context.event_id());
+            Event::new(context.time, context.time, agent_id, Action::Wait)
+        }
+
+        fn version(&self) -> u32 {
+            3
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, TestMessage>,
+            _msg: Msg<TestMessage>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_event_ids_are_stable_across_rollbacks() {
+        let registry = create_mock_registry(0).unwrap();
+        let mut planet =
+            Planet::<16, 128, 2, TestMessage>::create(1000.0, 1.0, 50, 1024, 512, registry)
+                .unwrap();
+        let ids = Arc::new(Mutex::new(Vec::new()));
+        for _ in 0..2 {
+            let recorder = IdRecorder {
+                ids: Arc::clone(&ids),
+            };
+            planet.spawn_agent(Box::new(recorder), 256);
+        }
+        let schedule = [(0, 2), (0, 2), (0, 4), (1, 4)];
+        planet.schedule_many(&schedule).unwrap();
+        for _ in 0..6 {
+            planet.step().unwrap();
+        }
+        let mut first = std::mem::take(&mut *ids.lock().unwrap());
+
+        // replay the same schedule after rolling back
+        planet.rollback(1).unwrap();
+        planet.schedule_many(&schedule).unwrap();
+        for _ in 0..5 {
+            planet.step().unwrap();
+        }
+        let mut replay = ids.lock().unwrap().clone();
+
+        first.sort();
+        replay.sort();
+        assert_eq!(first, replay);
+        first.dedup();
+        assert_eq!(first.len(), 4);
+        assert!(first.contains(&EventId::new(0, 0, 2, 1, 3)));
+        assert!(first.contains(&EventId::new(0, 1, 4, 0, 3)));
+    }
+
+    #[test]
+    fn test_pending_inspection() {
+        let registry = create_mock_registry(0).unwrap();
+        let mut planet =
+            Planet::<16, 128, 2, TestMessage>::create(1000.0, 1.0, 50, 1024, 512, registry)
+                .unwrap();
+        for _ in 0..2 {
+            let agent = BasicTestAgent {
+                timeout_count: 0,
+                max_timeouts: 5,
+            };
+            planet.spawn_agent(Box::new(agent), 256);
+        }
+        planet.schedule_many(&[(0, 300), (1, 4), (0, 10)]).unwrap();
+        let data = TestMessage {
+            value: 1,
+            sender_id: 1,
+        };
+        for msg in [
+            Msg::new(data, 0, 6, 1, Some(0)),
+            Msg::new(data, 0, 8, 1, Some(1)),
+            Msg::new(data, 0, 9, 1, None),
+        ] {
+            assert!(planet.local_messages.schedule.insert(msg).is_ok());
+        }
+
+        let times = |events: Vec<Event>| events.iter().map(|e| e.time).collect::<Vec<_>>();
+        assert_eq!(
+            times(planet.pending_events(0, 1000).unwrap()),
+            vec![10, 300]
+        );
+        assert_eq!(times(planet.pending_events(0, 100).unwrap()), vec![10]);
+        let recvs = planet
+            .pending_messages(0, 100)
+            .iter()
+            .map(|msg| msg.recv)
+            .collect::<Vec<_>>();
+        assert_eq!(recvs, vec![6, 9]);
+
+        // inspection leaves the schedule intact
+        assert_eq!(planet.pending_events(0, 1000).unwrap().len(), 2);
+        assert_eq!(planet.pending_messages(1, 100).len(), 2);
+    }
+
+    #[test]
+    fn test_schedule_many() {
+        let registry = create_mock_registry(0).unwrap();
+        let mut planet =
+            Planet::<16, 128, 2, TestMessage>::create(1000.0, 1.0, 50, 1024, 512, registry)
+                .unwrap();
+
+        for _ in 0..4 {
+            let agent = BasicTestAgent {
+                timeout_count: 0,
+                max_timeouts: 5,
+            };
+            planet.spawn_agent(Box::new(agent), 256);
+        }
+
+        let result = planet.schedule_many(&[(0, 10), (1, 2000)]);
+        assert!(matches!(result, Err(AikaError::PastTerminal)));
+        assert!(planet.event_system.local_clock.wheels[0][10].is_empty());
+
+        planet.schedule_many(&[(0, 10), (1, 10)]).unwrap();
+        assert_eq!(planet.event_system.local_clock.wheels[0][10].len(), 2);
+
+        planet.schedule_all_agents(20).unwrap();
+        assert_eq!(planet.event_system.local_clock.wheels[0][20].len(), 4);
+    }
+
     #[test]
     fn test_time_advancement() {
         let registry = create_mock_registry(0).unwrap();
@@ -725,6 +3435,51 @@ mod planet_tests {
         assert!(planet.now() >= 15);
     }
 
+    #[test]
+    fn test_anti_messages_leave_the_rest() {
+        let registry = create_mock_registry(0).unwrap();
+        let mut planet =
+            Planet::<16, 128, 2, TestMessage>::create(1000.0, 1.0, 50, 1024, 512, registry)
+                .unwrap();
+        let msg = |value, from, recv| {
+            let data = TestMessage {
+                value,
+                sender_id: from as u32,
+            };
+            Msg::new(data, 0, recv, from, Some(0))
+        };
+        // two copies of the same letter, another sender's, and one past the wheels
+        for msg in [msg(1, 1, 0), msg(2, 1, 0), msg(3, 2, 0), msg(4, 1, 600)] {
+            planet.local_messages.insert(msg);
+        }
+        assert_eq!(
+            planet
+                .local_messages
+                .annihilate(&AntiMsg::new(0, 0, 1, Some(0)), 0),
+            2
+        );
+        assert_eq!(
+            planet
+                .local_messages
+                .annihilate(&AntiMsg::new(0, 0, 1, Some(0)), 0),
+            0
+        );
+        // the letter sent again after a rollback survives the cancelled copies
+        planet.local_messages.insert(msg(5, 1, 0));
+        let values =
+            |msgs: Vec<Msg<TestMessage>>| msgs.iter().map(|msg| msg.data.value).collect::<Vec<_>>();
+        assert_eq!(values(planet.local_messages.pending(|_| true)), [3, 5, 4]);
+        assert_eq!(values(planet.local_messages.tick().unwrap()), [3, 5]);
+
+        assert_eq!(
+            planet
+                .local_messages
+                .annihilate(&AntiMsg::new(0, 600, 1, Some(0)), 0),
+            1
+        );
+        assert!(planet.local_messages.drain(|_| true).is_empty());
+    }
+
     #[test]
     fn test_gvt_throttling() {
         let registry = create_mock_registry(0).unwrap();
@@ -779,4 +3534,72 @@ mod planet_tests {
         // In actual run(), it would sleep at checkpoint
         assert!(result.is_ok() || result.is_err());
     }
+
+    // Keeps the mail handed back to it
+    struct Sender {
+        failed: Arc<Mutex<Vec<(usize, u32)>>>,
+    }
+
+    impl ThreadedAgent<16, TestMessage> for Sender {
+        fn step(&mut self, context: &mut PlanetContext<16, TestMessage>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, TestMessage>,
+            _msg: Msg<TestMessage>,
+            _agent_id: usize,
+        ) {
+        }
+
+        fn on_send_failed(
+            &mut self,
+            _context: &mut PlanetContext<16, TestMessage>,
+            failure: DeliveryFailure<TestMessage>,
+            _agent_id: usize,
+        ) {
+            let value = failure.msg.data.value;
+            self.failed.lock().unwrap().push((failure.to_world, value));
+        }
+    }
+
+    #[test]
+    fn test_undeliverable_mail_is_handed_back() {
+        let mut galaxy = Galaxy::<16, 128, 2, TestMessage>::new(2, 50, 100, 1000.0, 1.0).unwrap();
+        // world 0 never reads its inbox
+        let _idle = galaxy.spawn_world().unwrap();
+        let registry = galaxy.spawn_world().unwrap();
+        let mut planet =
+            Planet::<16, 128, 2, TestMessage>::create(1000.0, 1.0, 50, 1024, 512, registry)
+                .unwrap();
+        let failed = Arc::new(Mutex::new(Vec::new()));
+        let sender = Sender {
+            failed: failed.clone(),
+        };
+        planet.spawn_agent(Box::new(sender), 256);
+
+        for _ in 0..2 {
+            for value in 0..10 {
+                let data = TestMessage {
+                    value,
+                    sender_id: 0,
+                };
+                planet
+                    .context
+                    .send_mail(Msg::new(data, 0, 5, 0, Some(0)), 0)
+                    .unwrap();
+            }
+            galaxy.deliver_the_mail().unwrap();
+        }
+        // the inbox took 16 letters, and the other 4 stay in flight until their sender heard of them
+        assert_eq!(galaxy.counter.load(Ordering::SeqCst), 20);
+        planet.poll_delivery_failures().unwrap();
+        assert_eq!(
+            *failed.lock().unwrap(),
+            vec![(0, 6), (0, 7), (0, 8), (0, 9)]
+        );
+        assert_eq!(galaxy.counter.load(Ordering::SeqCst), 16);
+        assert_eq!(planet.stats.undelivered, 4);
+    }
 }
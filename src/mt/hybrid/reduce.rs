@@ -0,0 +1,207 @@
+//! Global aggregates reduced across planets, such as total market volume.
+//! Agents add contributions with `PlanetContext::reduce()`. Each `Planet` hands its contributions to a per-world slot
+//! before publishing the local time they were made at, and drops them again when it rolls back past them. At every
+//! GVT checkpoint the `Galaxy` folds the contributions made before the checkpoint into the committed aggregates, which
+//! no rollback can touch anymore. Every `Planet` waits at the checkpoint until the fold is done, so an agent reading
+//! `PlanetContext::aggregate()` sees the same value however far ahead its `Planet` runs.
+use std::{collections::BTreeMap, sync::Mutex};
+
+use crate::AikaError;
+
+/// How the contributions to a key are combined.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ReduceOp {
+    #[default]
+    Sum,
+    Min,
+    Max,
+}
+
+impl ReduceOp {
+    pub fn combine(&self, acc: f64, value: f64) -> f64 {
+        match self {
+            ReduceOp::Sum => acc + value,
+            ReduceOp::Min => acc.min(value),
+            ReduceOp::Max => acc.max(value),
+        }
+    }
+}
+
+/// A contribution to the aggregate of `key`, made at local time `time`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Contribution {
+    pub(crate) time: u64,
+    pub(crate) key: usize,
+    pub(crate) value: f64,
+}
+
+#[derive(Debug, Default)]
+struct Committed {
+    /// every contribution before this time is folded in
+    until: u64,
+    values: BTreeMap<usize, f64>,
+}
+
+/// Contributions and committed aggregates shared by the `Galaxy` and every `Planet` of a run.
+#[derive(Debug)]
+pub(crate) struct Reductions {
+    ops: BTreeMap<usize, ReduceOp>,
+    /// uncommitted contributions, per world
+    slots: Vec<Mutex<Vec<Contribution>>>,
+    committed: Mutex<Committed>,
+}
+
+impl Reductions {
+    pub(crate) fn new(worlds: usize, ops: BTreeMap<usize, ReduceOp>) -> Self {
+        Self {
+            ops,
+            slots: (0..worlds).map(|_| Mutex::new(Vec::new())).collect(),
+            committed: Mutex::new(Committed::default()),
+        }
+    }
+
+    /// Hand over the contributions `world` made since it last did.
+    pub(crate) fn contribute(
+        &self,
+        world: usize,
+        contributions: Vec<Contribution>,
+    ) -> Result<(), AikaError> {
+        let slot = self
+            .slots
+            .get(world)
+            .ok_or(AikaError::InvalidWorldId(world))?;
+        let mut slot = slot.lock().map_err(|_| AikaError::PoisonedLock)?;
+        slot.extend(contributions);
+        Ok(())
+    }
+
+    /// Drop the contributions `world` made at or after `time`.
+    pub(crate) fn rollback(&self, world: usize, time: u64) -> Result<(), AikaError> {
+        let slot = self
+            .slots
+            .get(world)
+            .ok_or(AikaError::InvalidWorldId(world))?;
+        let mut slot = slot.lock().map_err(|_| AikaError::PoisonedLock)?;
+        slot.retain(|contribution| contribution.time < time);
+        Ok(())
+    }
+
+    /// Fold every contribution made before `until` into the committed aggregates, world by world.
+    pub(crate) fn commit(&self, until: u64) -> Result<(), AikaError> {
+        let mut committed = self.committed.lock().map_err(|_| AikaError::PoisonedLock)?;
+        for slot in &self.slots {
+            let mut slot = slot.lock().map_err(|_| AikaError::PoisonedLock)?;
+            let (done, pending) = std::mem::take(&mut *slot)
+                .into_iter()
+                .partition::<Vec<_>, _>(|contribution| contribution.time < until);
+            *slot = pending;
+            for contribution in done {
+                let op = self.ops.get(&contribution.key).copied().unwrap_or_default();
+                committed
+                    .values
+                    .entry(contribution.key)
+                    .and_modify(|acc| *acc = op.combine(*acc, contribution.value))
+                    .or_insert(contribution.value);
+            }
+        }
+        committed.until = committed.until.max(until);
+        Ok(())
+    }
+
+    /// The committed aggregate of `key`, if anything was contributed to it.
+    pub(crate) fn value(&self, key: usize) -> Option<f64> {
+        let committed = self.committed.lock().ok()?;
+        committed.values.get(&key).copied()
+    }
+
+    /// Every committed aggregate, with the time up to which contributions are folded in.
+    pub(crate) fn snapshot(&self) -> (u64, BTreeMap<usize, f64>) {
+        self.committed
+            .lock()
+            .map(|committed| (committed.until, committed.values.clone()))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Trade {
+        volume: u64,
+    }
+
+    unsafe impl Pod for Trade {}
+    unsafe impl Zeroable for Trade {}
+
+    const VOLUME: usize = 0;
+    const LARGEST: usize = 1;
+
+    // Trades `volume` every step, noting the market volume it sees at each checkpoint
+    struct Trader {
+        volume: f64,
+        seen: Arc<Mutex<Vec<(u64, f64)>>>,
+    }
+
+    impl ThreadedAgent<128, Trade> for Trader {
+        fn step(&mut self, context: &mut PlanetContext<128, Trade>, agent_id: usize) -> Event {
+            let time = context.time;
+            if time.is_multiple_of(4) {
+                let total = context.aggregate(VOLUME).unwrap_or(0.0);
+                self.seen.lock().unwrap().push((time, total));
+            }
+            context.reduce(VOLUME, self.volume);
+            context.reduce(LARGEST, self.volume);
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Trade>,
+            _msg: Msg<Trade>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_aggregates_commit_at_checkpoints() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(12.0, 1.0)
+            .with_optimistic_sync(2, 4)
+            .with_uniform_worlds(16, 1, 16)
+            .with_reduction(LARGEST, ReduceOp::Max);
+        let mut engine = HybridEngine::<128, 128, 1, Trade>::create(config).unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        for (planet, volume) in [(0, 1.0), (1, 2.0)] {
+            let trader = Trader {
+                volume,
+                seen: seen.clone(),
+            };
+            engine.spawn_agent(planet, Box::new(trader)).unwrap();
+            engine.schedule(planet, 0, 1).unwrap();
+        }
+        let engine = engine.run().unwrap();
+
+        // both planets see the volume traded before each checkpoint, steps 1..=3 and 1..=7
+        let mut seen = seen.lock().unwrap().clone();
+        seen.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(seen, vec![(4, 9.0), (4, 9.0), (8, 21.0), (8, 21.0)]);
+
+        let (until, aggregates) = engine.aggregates();
+        assert_eq!(until, 12);
+        assert_eq!(aggregates[&VOLUME], 33.0);
+        assert_eq!(aggregates[&LARGEST], 2.0);
+    }
+}
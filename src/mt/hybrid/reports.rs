@@ -0,0 +1,190 @@
+//! Rate-limited, aggregated rollback reports.
+//! During a rollback storm a record per rollback floods whatever sink it is written to. With `RollbackReporting`
+//! a `Planet` instead collapses every rollback it suffers while GVT sits in one window of `window` ticks into a
+//! single `RollbackReport`: how many there were, the shallowest and deepest, and the senders of the most stragglers.
+//! The report is emitted once GVT moves past the window, to `SimHook::on_rollback_report()` and into
+//! `PlanetStats::rollback_reports`, and the last window is emitted when the run ends. A `RollbackDetail` per
+//! rollback is only kept in debug mode, or for one rollback in every `sample_every`.
+use std::collections::BTreeMap;
+
+/// How a `Planet` aggregates its rollbacks into reports, see `HybridConfig::with_rollback_reports()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RollbackReporting {
+    /// ticks of GVT covered by each report
+    pub window: u64,
+    /// number of offending senders listed in each report
+    pub top_senders: usize,
+    /// keep the detail of one rollback in every `sample_every`, or of none
+    pub sample_every: Option<u64>,
+}
+
+impl RollbackReporting {
+    pub fn new(window: u64, top_senders: usize) -> Self {
+        Self {
+            window: window.max(1),
+            top_senders,
+            sample_every: None,
+        }
+    }
+
+    /// Keep the detail of every rollback.
+    pub fn with_debug(self) -> Self {
+        self.with_sampling(1)
+    }
+
+    /// Keep the detail of one rollback in every `every`.
+    pub fn with_sampling(mut self, every: u64) -> Self {
+        self.sample_every = Some(every.max(1));
+        self
+    }
+}
+
+/// A single rollback, kept in a report under debug mode or sampling.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RollbackDetail {
+    /// local time the `Planet` rolled back from
+    pub from: u64,
+    /// time it rolled back to, the receive time of the straggler
+    pub to: u64,
+    /// world and agent that sent the straggler
+    pub sender: (usize, usize),
+}
+
+/// The rollbacks `world` suffered while GVT was in `[start, end)`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RollbackReport {
+    pub world: usize,
+    pub start: u64,
+    pub end: u64,
+    pub count: u64,
+    /// fewest and most steps undone by a single rollback
+    pub min_depth: u64,
+    pub max_depth: u64,
+    /// `(world, agent)` of the senders of the most stragglers with their counts, most first
+    pub top_senders: Vec<((usize, usize), u64)>,
+    pub details: Vec<RollbackDetail>,
+}
+
+/// Collects the rollbacks of the current GVT window of one `Planet`.
+#[derive(Clone, Debug)]
+pub(crate) struct RollbackAggregator {
+    reporting: RollbackReporting,
+    current: RollbackReport,
+    senders: BTreeMap<(usize, usize), u64>,
+    /// rollbacks seen over the whole run, for sampling
+    seen: u64,
+}
+
+impl RollbackAggregator {
+    pub(crate) fn new(world: usize, reporting: RollbackReporting) -> Self {
+        Self {
+            reporting,
+            current: RollbackReport {
+                world,
+                end: reporting.window,
+                ..Default::default()
+            },
+            senders: BTreeMap::new(),
+            seen: 0,
+        }
+    }
+
+    pub(crate) fn reporting(&self) -> RollbackReporting {
+        self.reporting
+    }
+
+    pub(crate) fn record(&mut self, detail: RollbackDetail) {
+        let depth = detail.from - detail.to;
+        let report = &mut self.current;
+        if report.count == 0 {
+            report.min_depth = depth;
+            report.max_depth = depth;
+        } else {
+            report.min_depth = report.min_depth.min(depth);
+            report.max_depth = report.max_depth.max(depth);
+        }
+        report.count += 1;
+        *self.senders.entry(detail.sender).or_default() += 1;
+        if self
+            .reporting
+            .sample_every
+            .is_some_and(|every| self.seen.is_multiple_of(every))
+        {
+            report.details.push(detail);
+        }
+        self.seen += 1;
+    }
+
+    /// The report of the window GVT just left, if it held any rollbacks.
+    pub(crate) fn advance(&mut self, gvt: u64) -> Option<RollbackReport> {
+        if gvt < self.current.end {
+            return None;
+        }
+        let window = self.reporting.window;
+        let start = gvt - gvt % window;
+        self.close(start, start + window)
+    }
+
+    /// The report of the window still open at the end of the run, if it held any rollbacks.
+    pub(crate) fn finish(&mut self) -> Option<RollbackReport> {
+        let (start, end) = (self.current.end, self.current.end + self.reporting.window);
+        self.close(start, end)
+    }
+
+    fn close(&mut self, start: u64, end: u64) -> Option<RollbackReport> {
+        let next = RollbackReport {
+            world: self.current.world,
+            start,
+            end,
+            ..Default::default()
+        };
+        let mut report = std::mem::replace(&mut self.current, next);
+        if report.count == 0 {
+            return None;
+        }
+        let mut senders = std::mem::take(&mut self.senders)
+            .into_iter()
+            .collect::<Vec<_>>();
+        senders.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        senders.truncate(self.reporting.top_senders);
+        report.top_senders = senders;
+        Some(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detail(from: u64, to: u64, sender: (usize, usize)) -> RollbackDetail {
+        RollbackDetail { from, to, sender }
+    }
+
+    #[test]
+    fn test_rollbacks_collapse_per_window() {
+        let mut aggregator =
+            RollbackAggregator::new(1, RollbackReporting::new(10, 1).with_sampling(2));
+        aggregator.record(detail(8, 5, (0, 3)));
+        aggregator.record(detail(9, 2, (0, 4)));
+        aggregator.record(detail(9, 8, (0, 4)));
+        assert_eq!(aggregator.advance(9), None);
+
+        let report = aggregator.advance(23).unwrap();
+        assert_eq!((report.world, report.start, report.end), (1, 0, 10));
+        assert_eq!(report.count, 3);
+        assert_eq!((report.min_depth, report.max_depth), (1, 7));
+        assert_eq!(report.top_senders, vec![((0, 4), 2)]);
+        assert_eq!(
+            report.details,
+            vec![detail(8, 5, (0, 3)), detail(9, 8, (0, 4))]
+        );
+
+        // windows without rollbacks are never reported
+        assert_eq!(aggregator.advance(35), None);
+        aggregator.record(detail(40, 36, (2, 0)));
+        let report = aggregator.finish().unwrap();
+        assert_eq!((report.start, report.end, report.count), (30, 40, 1));
+        assert!(report.details.is_empty());
+        assert_eq!(aggregator.finish(), None);
+    }
+}
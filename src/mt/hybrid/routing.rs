@@ -0,0 +1,195 @@
+//! Stable agent addresses across `Planet`s.
+//! Every agent spawned through a `HybridEngine` gets an `AgentHandle`, and the `Galaxy` keeps a `RoutingTable`
+//! from handles and old `(world, agent)` addresses to where agents live now. Splits and migrations update the
+//! table as they move agents, so mail sent by handle always reaches its target.
+use std::collections::BTreeMap;
+
+/// A global, stable name for an agent, independent of the `Planet` it currently runs on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AgentHandle(pub u64);
+
+/// Where every agent lives, as `(world, agent)` addresses.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RoutingTable {
+    /// address each handle was spawned at, indexed by handle
+    spawned: Vec<(usize, usize)>,
+    /// moves made by splits and migrations, from old to new address
+    routes: BTreeMap<(usize, usize), (usize, usize)>,
+}
+
+impl RoutingTable {
+    /// Allocate a handle for an agent spawned at `(world, agent)`.
+    pub fn register(&mut self, world: usize, agent: usize) -> AgentHandle {
+        self.spawned.push((world, agent));
+        AgentHandle(self.spawned.len() as u64 - 1)
+    }
+
+    /// Record that the agent at `from` now lives at `to`.
+    pub fn insert(&mut self, from: (usize, usize), to: (usize, usize)) {
+        self.routes.insert(from, to);
+    }
+
+    /// The current address of an agent, following every move since it was at `(world, agent)`.
+    pub fn resolve(&self, world: usize, agent: usize) -> (usize, usize) {
+        let mut address = (world, agent);
+        while let Some(next) = self.routes.get(&address) {
+            address = *next;
+        }
+        address
+    }
+
+    /// The current address of the agent behind `handle`.
+    pub fn locate(&self, handle: AgentHandle) -> Option<(usize, usize)> {
+        let (world, agent) = *self.spawned.get(handle.0 as usize)?;
+        Some(self.resolve(world, agent))
+    }
+
+    /// Whether the agent at `(world, agent)` has moved away.
+    pub fn has_moved(&self, world: usize, agent: usize) -> bool {
+        self.routes.contains_key(&(world, agent))
+    }
+
+    /// Number of handles allocated.
+    pub fn handles(&self) -> usize {
+        self.spawned.len()
+    }
+
+    /// Number of moves recorded.
+    pub fn moves(&self) -> usize {
+        self.routes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeSet,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+        AikaError,
+    };
+
+    #[test]
+    fn test_routes_follow_every_move() {
+        let mut table = RoutingTable::default();
+        let first = table.register(0, 0);
+        let second = table.register(0, 1);
+        table.insert((0, 1), (2, 0));
+        table.insert((2, 0), (1, 4));
+        assert_eq!(table.locate(first), Some((0, 0)));
+        assert_eq!(table.locate(second), Some((1, 4)));
+        assert_eq!(table.locate(AgentHandle(2)), None);
+        assert_eq!(table.moves(), 2);
+    }
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Note {
+        value: u32,
+    }
+
+    unsafe impl Pod for Note {}
+    unsafe impl Zeroable for Note {}
+
+    struct Worker {
+        mail: Arc<Mutex<BTreeSet<u64>>>,
+    }
+
+    impl ThreadedAgent<128, Note> for Worker {
+        fn step(&mut self, context: &mut PlanetContext<128, Note>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Note>,
+            msg: Msg<Note>,
+            _agent_id: usize,
+        ) {
+            self.mail.lock().unwrap().insert(msg.recv);
+        }
+    }
+
+    // Mails one agent by handle every 5 ticks, arriving 4 ticks later
+    struct Courier {
+        target: Arc<Mutex<Option<AgentHandle>>>,
+        errors: Arc<AtomicUsize>,
+    }
+
+    impl ThreadedAgent<128, Note> for Courier {
+        fn step(&mut self, context: &mut PlanetContext<128, Note>, agent_id: usize) -> Event {
+            let time = context.time;
+            let target = self.target.lock().unwrap().unwrap();
+            let msg = Msg::new(Note { value: 1 }, time, time + 4, agent_id, None);
+            context.send_mail_to(target, msg).unwrap();
+            if let Err(AikaError::InvalidAgentHandle(_)) =
+                context.send_mail_to(AgentHandle(99), msg)
+            {
+                self.errors.fetch_add(1, Ordering::SeqCst);
+            }
+            Event::new(time, time, agent_id, Action::Timeout(5))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Note>,
+            _msg: Msg<Note>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_handles_survive_migration() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(60.0, 1.0)
+            .with_optimistic_sync(2, 10)
+            .with_world(0, 16, vec![16; 4])
+            .unwrap()
+            .with_world(1, 16, vec![16])
+            .unwrap()
+            .with_agent_migration(2.0);
+        let mut engine = HybridEngine::<128, 128, 1, Note>::create(config).unwrap();
+        let target = Arc::new(Mutex::new(None));
+        let errors = Arc::new(AtomicUsize::new(0));
+        let courier = Courier {
+            target: Arc::clone(&target),
+            errors: Arc::clone(&errors),
+        };
+        let courier = engine.spawn_agent(1, Box::new(courier)).unwrap();
+        let mail = Arc::new(Mutex::new(BTreeSet::new()));
+        let workers = (0..4)
+            .map(|_| {
+                let worker = Worker {
+                    mail: Arc::clone(&mail),
+                };
+                engine.spawn_agent(0, Box::new(worker)).unwrap()
+            })
+            .collect::<Vec<_>>();
+        *target.lock().unwrap() = Some(workers[3]);
+        assert_eq!(courier, AgentHandle(0));
+        assert_eq!(engine.locate(workers[3]), Some((0, 3)));
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+
+        // the last worker moved to planet 1 at the first checkpoint, and its handle moved with it
+        assert_eq!(engine.locate(workers[3]), Some((1, 1)));
+        assert_eq!(engine.locate(workers[0]), Some((0, 0)));
+        assert_eq!(
+            *mail.lock().unwrap(),
+            (1..=11).map(|i| i * 5).collect::<BTreeSet<u64>>()
+        );
+        assert_eq!(errors.load(Ordering::SeqCst), 12);
+    }
+}
@@ -0,0 +1,288 @@
+//! Declarative checks of a hybrid scenario before it runs.
+//! A `ScenarioValidator` looks at the `Scenario` once every agent is spawned and scheduled: the config, and per
+//! `Planet` its agents, their initial schedule and their initial journals. Validators are registered with
+//! `HybridEngine::add_validator()` and run by `run()` before any thread starts; every problem of every validator is
+//! collected into one `AikaError::InvalidScenario`, so a broken setup is reported in full rather than one error at a
+//! time. `HybridEngine::validate_scenario()` runs them on demand.
+use std::{collections::BTreeSet, fmt};
+
+use mesocarp::logging::journal::Journal;
+
+use crate::mt::hybrid::config::HybridConfig;
+
+/// The initial conditions of one `Planet`.
+pub struct PlanetScenario<'a> {
+    pub world_id: usize,
+    pub agents: usize,
+    /// `(agent, time)` of every scheduled step, ordered by time
+    pub scheduled: Vec<(usize, u64)>,
+    pub world_state: &'a Journal,
+    pub agent_states: &'a [Journal],
+}
+
+/// The initial conditions of a hybrid run, as seen by a `ScenarioValidator`.
+pub struct Scenario<'a> {
+    pub config: &'a HybridConfig,
+    /// ordered by world id
+    pub planets: Vec<PlanetScenario<'a>>,
+}
+
+/// An invariant the initial conditions of a run must satisfy.
+pub trait ScenarioValidator: Send {
+    /// Short name used when reporting problems.
+    fn name(&self) -> String;
+
+    /// Describe every way `scenario` breaks the invariant, or nothing if it holds.
+    fn check(&self, scenario: &Scenario) -> Vec<String>;
+}
+
+/// One problem found by a `ScenarioValidator`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScenarioViolation {
+    pub validator: String,
+    pub problem: String,
+}
+
+/// Every problem found in a scenario.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScenarioViolations(pub Vec<ScenarioViolation>);
+
+impl fmt::Display for ScenarioViolations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} problem(s)", self.0.len())?;
+        for violation in &self.0 {
+            write!(f, "\n  - [{}] {}", violation.validator, violation.problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run every validator over `scenario`, collecting all of their problems.
+pub(crate) fn validate(
+    validators: &[Box<dyn ScenarioValidator>],
+    scenario: &Scenario,
+) -> ScenarioViolations {
+    let violations = validators
+        .iter()
+        .flat_map(|validator| {
+            let name = validator.name();
+            validator
+                .check(scenario)
+                .into_iter()
+                .map(move |problem| ScenarioViolation {
+                    validator: name.clone(),
+                    problem,
+                })
+        })
+        .collect();
+    ScenarioViolations(violations)
+}
+
+/// Every `Planet` has at least one agent with a scheduled step.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct EveryPlanetScheduled;
+
+impl ScenarioValidator for EveryPlanetScheduled {
+    fn name(&self) -> String {
+        "every planet scheduled".to_string()
+    }
+
+    fn check(&self, scenario: &Scenario) -> Vec<String> {
+        scenario
+            .planets
+            .iter()
+            .filter_map(|planet| {
+                if planet.agents == 0 {
+                    Some(format!("planet {} has no agents", planet.world_id))
+                } else if planet.scheduled.is_empty() {
+                    Some(format!(
+                        "planet {} has {} agent(s) but none is scheduled",
+                        planet.world_id, planet.agents
+                    ))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// The configured planet links connect every `Planet`, ignoring their direction. Without links every `Planet` can
+/// mail every other, so the check passes.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LinksConnected;
+
+impl ScenarioValidator for LinksConnected {
+    fn name(&self) -> String {
+        "links connected".to_string()
+    }
+
+    fn check(&self, scenario: &Scenario) -> Vec<String> {
+        let worlds = scenario.config.number_of_worlds;
+        if scenario.config.links.is_empty() || worlds == 0 {
+            return Vec::new();
+        }
+        let mut reached = BTreeSet::from([0]);
+        let mut frontier = vec![0];
+        while let Some(world) = frontier.pop() {
+            for &(from, to) in scenario.config.links.keys() {
+                let next = match (from == world, to == world) {
+                    (true, _) => to,
+                    (_, true) => from,
+                    _ => continue,
+                };
+                if next < worlds && reached.insert(next) {
+                    frontier.push(next);
+                }
+            }
+        }
+        let unreached = (0..worlds)
+            .filter(|world| !reached.contains(world))
+            .map(|world| world.to_string())
+            .collect::<Vec<_>>();
+        if unreached.is_empty() {
+            return Vec::new();
+        }
+        vec![format!(
+            "planet(s) {} can't be reached from planet 0 over the configured links",
+            unreached.join(", ")
+        )]
+    }
+}
+
+/// A quantity read from every agent's initial state sums to an expected total, e.g. the initial inventory.
+pub struct InitialTotal {
+    label: String,
+    expected: f64,
+    tolerance: f64,
+    measure: Box<dyn Fn(&Journal) -> f64 + Send>,
+}
+
+impl InitialTotal {
+    /// Sum `measure` over every agent's state journal and compare it to `expected`.
+    pub fn new(
+        label: &str,
+        expected: f64,
+        measure: impl Fn(&Journal) -> f64 + Send + 'static,
+    ) -> Self {
+        Self {
+            label: label.to_string(),
+            expected,
+            tolerance: 1e-9,
+            measure: Box::new(measure),
+        }
+    }
+
+    /// Accept totals within `tolerance` of the expected one.
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+}
+
+impl ScenarioValidator for InitialTotal {
+    fn name(&self) -> String {
+        format!("initial {}", self.label)
+    }
+
+    fn check(&self, scenario: &Scenario) -> Vec<String> {
+        let total = scenario
+            .planets
+            .iter()
+            .flat_map(|planet| planet.agent_states.iter())
+            .map(|journal| (self.measure)(journal))
+            .sum::<f64>();
+        if (total - self.expected).abs() <= self.tolerance {
+            return Vec::new();
+        }
+        vec![format!(
+            "total {} is {total}, expected {}",
+            self.label, self.expected
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{link::LinkModel, HybridEngine},
+        objects::{Action, Event, Msg},
+        AikaError,
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Stock {
+        units: u64,
+    }
+
+    unsafe impl Pod for Stock {}
+    unsafe impl Zeroable for Stock {}
+
+    struct Store;
+
+    impl ThreadedAgent<128, Stock> for Store {
+        fn step(&mut self, context: &mut PlanetContext<128, Stock>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Stock>,
+            _msg: Msg<Stock>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    fn inventory(expected: f64) -> InitialTotal {
+        InitialTotal::new("inventory", expected, |journal: &Journal| {
+            journal
+                .read_state::<Stock>()
+                .map_or(0.0, |stock| stock.units as f64)
+        })
+    }
+
+    #[test]
+    fn test_scenario_problems_are_reported_together() {
+        let config = HybridConfig::new(3, 64)
+            .with_time_bounds(10.0, 1.0)
+            .with_optimistic_sync(4, 8)
+            .with_uniform_worlds(16, 1, 16)
+            .with_link(0, 1, LinkModel::new(1, 4));
+        let mut engine = HybridEngine::<128, 128, 1, Stock>::create(config).unwrap();
+        engine.spawn_agent(0, Box::new(Store)).unwrap();
+        engine.spawn_agent(1, Box::new(Store)).unwrap();
+        engine.schedule(0, 0, 1).unwrap();
+        for (planet, units) in [(0, 30), (1, 20)] {
+            engine.planets[planet].context.agent_states[0].write(Stock { units }, 0, None);
+        }
+        engine.add_validator(Box::new(EveryPlanetScheduled));
+        engine.add_validator(Box::new(LinksConnected));
+        engine.add_validator(Box::new(inventory(60.0)));
+
+        let Err(AikaError::InvalidScenario(violations)) = engine.validate_scenario() else {
+            panic!("scenario should be invalid");
+        };
+        let problems = violations
+            .0
+            .iter()
+            .map(|violation| violation.problem.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            problems,
+            vec![
+                "planet 1 has 1 agent(s) but none is scheduled",
+                "planet 2 has no agents",
+                "planet(s) 2 can't be reached from planet 0 over the configured links",
+                "total inventory is 50, expected 60",
+            ]
+        );
+        assert!(violations.to_string().contains("[initial inventory]"));
+        assert!(matches!(engine.run(), Err(AikaError::InvalidScenario(_))));
+    }
+}
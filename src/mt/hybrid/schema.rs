@@ -0,0 +1,357 @@
+//! Payload schema checks and migrations for interplanetary `Mail`.
+//! Message payloads are raw `Pod` bytes, so two components built with different `MessageType`s would silently
+//! reinterpret each other's messages. Every `Planet` registers a `PayloadSchema` with its `Galaxy`, and remote
+//! peers present theirs in a handshake; anything that doesn't match the `Galaxy`'s schema is rejected up front.
+//!
+//! As a model evolves, its payload changes layout. Give each layout a version with `PayloadSchema::with_version()`
+//! and register a `Migration` from every older schema to the next in a `SchemaRegistry`, handed to the engine with
+//! `HybridConfig::with_payload_schema()`. A bridged peer presenting an older schema is then accepted, and every
+//! payload it sends is carried forward through the chain of migrations to the expected schema as it comes off the
+//! wire. Each end migrates what it receives, so an older peer needs migrations back from the newer schema. Cut
+//! snapshots record the schema hash of their in-flight mail, and `CutSnapshot::migrate()` carries a saved snapshot
+//! forward the same way.
+use std::{collections::BTreeMap, fmt, mem::size_of, sync::Arc};
+
+use bytemuck::Pod;
+
+use crate::AikaError;
+
+/// Layout fingerprint of a message payload type.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PayloadSchema {
+    pub type_name: &'static str,
+    pub size: usize,
+    pub align: usize,
+    /// bumped by the model whenever the meaning of the layout changes, 0 unless set
+    pub version: u32,
+}
+
+impl PayloadSchema {
+    pub fn of<T>() -> Self {
+        Self {
+            type_name: std::any::type_name::<T>(),
+            size: std::mem::size_of::<T>(),
+            align: std::mem::align_of::<T>(),
+            version: 0,
+        }
+    }
+
+    pub fn with_version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Present the schema under `type_name`, e.g. the name an older build gave the type.
+    pub fn named(mut self, type_name: &'static str) -> Self {
+        self.type_name = type_name;
+        self
+    }
+
+    /// FNV-1a hash of the type name, size, alignment and version, for exchanging over the wire.
+    pub fn hash(&self) -> u64 {
+        let size = (self.size as u64).to_le_bytes();
+        let align = (self.align as u64).to_le_bytes();
+        let version = self.version.to_le_bytes();
+        let mut hash = 0xcbf29ce484222325u64;
+        for byte in self
+            .type_name
+            .bytes()
+            .chain(size)
+            .chain(align)
+            .chain(version)
+        {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash
+    }
+}
+
+impl fmt::Display for PayloadSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` v{} ({} bytes, align {})",
+            self.type_name, self.version, self.size, self.align
+        )
+    }
+}
+
+type Convert = Arc<dyn Fn(&[u8]) -> Vec<u8> + Send + Sync>;
+
+/// A user-registered conversion of payloads from one schema to another.
+#[derive(Clone)]
+pub struct Migration {
+    pub from: PayloadSchema,
+    pub to: PayloadSchema,
+    convert: Convert,
+}
+
+impl Migration {
+    /// Convert payloads of schema `from`, read as `Old`, into payloads of schema `to` with `convert`. Fails unless
+    /// the schemas differ and give the sizes of `Old` and `New`.
+    pub fn new<Old: Pod, New: Pod>(
+        from: PayloadSchema,
+        to: PayloadSchema,
+        convert: impl Fn(Old) -> New + Send + Sync + 'static,
+    ) -> Result<Self, AikaError> {
+        if from.hash() == to.hash() {
+            return Err(AikaError::SchemaMismatch(format!(
+                "a migration from {from} leads back to it"
+            )));
+        }
+        if from.size != size_of::<Old>() || to.size != size_of::<New>() {
+            return Err(AikaError::SchemaMismatch(format!(
+                "a migration from {from} to {to} reads {} bytes and writes {}",
+                size_of::<Old>(),
+                size_of::<New>()
+            )));
+        }
+        let convert: Convert = Arc::new(move |bytes| {
+            let new = convert(bytemuck::pod_read_unaligned(bytes));
+            bytemuck::bytes_of(&new).to_vec()
+        });
+        Ok(Self { from, to, convert })
+    }
+}
+
+impl fmt::Debug for Migration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Migration")
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The chain of `Migration`s leading from an older schema to the expected one.
+#[derive(Clone, Debug)]
+pub struct MigrationPath {
+    steps: Vec<Migration>,
+}
+
+impl MigrationPath {
+    /// The schema the path starts from.
+    pub fn from(&self) -> PayloadSchema {
+        self.steps[0].from
+    }
+
+    /// The schema the path leads to.
+    pub fn to(&self) -> PayloadSchema {
+        self.steps[self.steps.len() - 1].to
+    }
+
+    pub fn steps(&self) -> &[Migration] {
+        &self.steps
+    }
+
+    /// Carry the bytes of a `from()` payload through every step, reading the result as `T`.
+    pub fn migrate<T: Pod>(&self, bytes: &[u8]) -> Result<T, AikaError> {
+        if bytes.len() != self.from().size {
+            return Err(AikaError::SchemaMismatch(format!(
+                "{} bytes don't hold a payload of {}",
+                bytes.len(),
+                self.from()
+            )));
+        }
+        if size_of::<T>() != self.to().size {
+            return Err(AikaError::SchemaMismatch(format!(
+                "a payload of {} doesn't fit {} bytes",
+                self.to(),
+                size_of::<T>()
+            )));
+        }
+        let mut bytes = bytes.to_vec();
+        for step in &self.steps {
+            bytes = (step.convert)(&bytes);
+        }
+        Ok(bytemuck::pod_read_unaligned(&bytes))
+    }
+}
+
+/// The payload schema a `Galaxy` expects, the schema each registered world presented, and the migrations from older
+/// schemas to the expected one.
+#[derive(Clone, Debug)]
+pub struct SchemaRegistry {
+    expected: PayloadSchema,
+    registered: BTreeMap<usize, PayloadSchema>,
+    /// by the hash of the schema they migrate from
+    migrations: BTreeMap<u64, Migration>,
+}
+
+impl SchemaRegistry {
+    pub fn new(expected: PayloadSchema) -> Self {
+        Self {
+            expected,
+            registered: BTreeMap::new(),
+            migrations: BTreeMap::new(),
+        }
+    }
+
+    /// Accept payloads of `migration.from`, replacing any migration registered from it before.
+    pub fn with_migration(mut self, migration: Migration) -> Self {
+        self.migrations.insert(migration.from.hash(), migration);
+        self
+    }
+
+    pub fn expected(&self) -> PayloadSchema {
+        self.expected
+    }
+
+    /// The migrations from the schema hashed to `hash` to the expected one, or `None` if there is no such chain or
+    /// `hash` is the expected schema already.
+    pub fn migration_from(&self, hash: u64) -> Option<MigrationPath> {
+        let mut steps = Vec::new();
+        let mut at = hash;
+        while at != self.expected.hash() {
+            // a chain can't be longer than the migrations, or it runs in a cycle
+            if steps.len() == self.migrations.len() {
+                return None;
+            }
+            let step = self.migrations.get(&at)?;
+            at = step.to.hash();
+            steps.push(step.clone());
+        }
+        (!steps.is_empty()).then_some(MigrationPath { steps })
+    }
+
+    /// Record the schema of `world_id`, failing if it neither matches the expected one nor migrates to it.
+    pub fn register(&mut self, world_id: usize, schema: PayloadSchema) -> Result<(), AikaError> {
+        if schema.hash() != self.expected.hash() && self.migration_from(schema.hash()).is_none() {
+            return Err(AikaError::SchemaMismatch(format!(
+                "world {world_id} uses {schema}, expected {}, with no migration to it",
+                self.expected
+            )));
+        }
+        self.registered.insert(world_id, schema);
+        Ok(())
+    }
+
+    pub fn schema_of(&self, world_id: usize) -> Option<PayloadSchema> {
+        self.registered.get(&world_id).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        mt::hybrid::{bridge::wire::Frame, galaxy::Galaxy},
+        objects::{Mail, Msg, Transfer},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Price {
+        value: f64,
+    }
+
+    unsafe impl Pod for Price {}
+    unsafe impl Zeroable for Price {}
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Quantity {
+        value: u32,
+    }
+
+    unsafe impl Pod for Quantity {}
+    unsafe impl Zeroable for Quantity {}
+
+    #[test]
+    fn test_schema_mismatch_fails_fast() {
+        let mut galaxy = Galaxy::<16, 128, 1, Price>::new(2, 10, 10, 40.0, 1.0).unwrap();
+        galaxy.spawn_world().unwrap();
+        assert_eq!(
+            galaxy.schemas().schema_of(0),
+            Some(PayloadSchema::of::<Price>())
+        );
+        assert_ne!(
+            PayloadSchema::of::<Price>().hash(),
+            PayloadSchema::of::<Quantity>().hash()
+        );
+
+        galaxy.handshake(1, PayloadSchema::of::<Price>()).unwrap();
+        assert!(matches!(
+            galaxy.handshake(1, PayloadSchema::of::<Quantity>()),
+            Err(AikaError::SchemaMismatch(_))
+        ));
+    }
+
+    // the same payload in cents, then in dollars
+    fn schemas() -> (PayloadSchema, PayloadSchema, PayloadSchema) {
+        let v1 = PayloadSchema::of::<Quantity>().named("model::Price");
+        let v2 = PayloadSchema::of::<Price>()
+            .named("model::Price")
+            .with_version(2);
+        let v3 = v2.with_version(3);
+        (v1, v2, v3)
+    }
+
+    fn registry() -> SchemaRegistry {
+        let (v1, v2, v3) = schemas();
+        let cents = Migration::new(v1, v2, |cents: Quantity| Price {
+            value: cents.value as f64,
+        })
+        .unwrap();
+        let dollars = Migration::new(v2, v3, |cents: Price| Price {
+            value: cents.value / 100.0,
+        })
+        .unwrap();
+        SchemaRegistry::new(v3)
+            .with_migration(cents)
+            .with_migration(dollars)
+    }
+
+    #[test]
+    fn test_migrations_chain_to_the_expected_schema() {
+        let (v1, v2, v3) = schemas();
+        assert_ne!(v2.hash(), v3.hash());
+        assert!(matches!(
+            Migration::new(v1, v1, |quantity: Quantity| quantity),
+            Err(AikaError::SchemaMismatch(_))
+        ));
+        assert!(matches!(
+            Migration::new(v1, v2, |quantity: Quantity| quantity),
+            Err(AikaError::SchemaMismatch(_))
+        ));
+
+        let mut registry = registry();
+        assert!(registry.migration_from(v3.hash()).is_none());
+        let path = registry.migration_from(v1.hash()).unwrap();
+        assert_eq!((path.from(), path.to(), path.steps().len()), (v1, v3, 2));
+        let bytes = bytemuck::bytes_of(&Quantity { value: 250 });
+        assert_eq!(path.migrate::<Price>(bytes).unwrap(), Price { value: 2.5 });
+        assert!(path.migrate::<Quantity>(bytes).is_err());
+
+        registry.register(1, v1).unwrap();
+        assert_eq!(registry.schema_of(1), Some(v1));
+        let unknown = v1.with_version(7);
+        assert!(matches!(
+            registry.register(2, unknown),
+            Err(AikaError::SchemaMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn test_older_payloads_migrate_off_the_wire() {
+        let (v1, _, _) = schemas();
+        let msg = Msg::new(Quantity { value: 1_999 }, 3, 5, 0, Some(1));
+        let mail = Mail::write_letter(Transfer::Msg(msg), 0, Some(1));
+        let bytes = Frame::Mail(mail).encode();
+
+        let path = registry().migration_from(v1.hash()).unwrap();
+        let Frame::<Price>::Mail(mail) = Frame::decode(&bytes, Some(&path)).unwrap() else {
+            panic!("not a mail frame");
+        };
+        let Transfer::Msg(msg) = mail.transfer else {
+            panic!("not a msg");
+        };
+        assert_eq!((msg.sent, msg.recv, msg.to), (3, 5, Some(1)));
+        assert_eq!(msg.data, Price { value: 19.99 });
+        // read as the expected schema, the frame runs short
+        assert!(Frame::<Price>::decode(&bytes, None).is_err());
+    }
+}
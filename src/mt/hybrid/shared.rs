@@ -0,0 +1,107 @@
+//! Read-only data shared by every `Planet` of a run, such as a road network or a market configuration.
+//! The data is handed to the `Galaxy` once, as an `Arc<T>`, and every `Planet` it spawns reads it in place through
+//! `PlanetContext::shared_data()`, without a copy per agent or per planet. Agents only ever get a `&T`, so the data
+//! can't change during the run and needs no journaling: a rolled-back step that read it reads the same values when
+//! it is re-executed. `T` should avoid interior mutability, since writes through it would survive rollbacks.
+use std::{any::Any, fmt, sync::Arc};
+
+/// Type-erased handle to the data shared by a run.
+#[derive(Clone)]
+pub struct SharedData(Arc<dyn Any + Send + Sync>);
+
+impl SharedData {
+    pub fn new<T: Any + Send + Sync>(data: Arc<T>) -> Self {
+        Self(data)
+    }
+
+    /// The shared data, if it is a `T`.
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.0.downcast_ref::<T>()
+    }
+}
+
+impl fmt::Debug for SharedData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedData").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bytemuck::{Pod, Zeroable};
+
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Trip {
+        length: u64,
+    }
+
+    unsafe impl Pod for Trip {}
+    unsafe impl Zeroable for Trip {}
+
+    struct Roads {
+        lengths: Vec<u64>,
+    }
+
+    // Drives along the shared road with its own index every step, noting where the network lives
+    struct Driver {
+        seen: Arc<Mutex<Vec<(usize, u64, usize)>>>,
+    }
+
+    impl ThreadedAgent<128, Trip> for Driver {
+        fn step(&mut self, context: &mut PlanetContext<128, Trip>, agent_id: usize) -> Event {
+            let roads = context.shared_data::<Roads>().unwrap();
+            let length = roads.lengths[context.world_id];
+            let address = roads as *const Roads as usize;
+            self.seen
+                .lock()
+                .unwrap()
+                .push((context.world_id, length, address));
+            assert!(context.shared_data::<u64>().is_none());
+            Event::new(context.time, context.time, agent_id, Action::Timeout(4))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Trip>,
+            _msg: Msg<Trip>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_planets_read_the_same_data() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(8.0, 1.0)
+            .with_optimistic_sync(4, 8)
+            .with_uniform_worlds(16, 1, 16);
+        let roads = Arc::new(Roads {
+            lengths: vec![3, 5],
+        });
+        let mut engine = HybridEngine::<128, 128, 1, Trip>::create(config)
+            .unwrap()
+            .with_shared_data(Arc::clone(&roads));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        for planet in 0..2 {
+            let driver = Driver { seen: seen.clone() };
+            engine.spawn_agent(planet, Box::new(driver)).unwrap();
+            engine.schedule(planet, 0, 1).unwrap();
+        }
+        engine.run().unwrap();
+
+        let address = Arc::as_ptr(&roads) as usize;
+        let mut seen = seen.lock().unwrap().clone();
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen, vec![(0, 3, address), (1, 5, address)]);
+    }
+}
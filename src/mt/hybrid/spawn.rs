@@ -0,0 +1,171 @@
+//! Agents created at runtime from registered templates.
+//! A template is a factory registered with `HybridEngine::register_template()` under the same id on every
+//! `Planet`. Inside any handler, `PlanetContext::spawn_from_template()` asks for a new agent built from an initial
+//! payload and returns its index right away, so the caller can schedule it or send it mail. Indices are handed out
+//! in processing order, so a deterministic run always assigns the same ones. The `Planet` journals every spawn by
+//! local time and removes spawned agents again if it rolls back past their creation, letting re-execution spawn
+//! them afresh under the same indices.
+use std::sync::Arc;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::agents::ThreadedAgent;
+
+/// Builds a `ThreadedAgent` from an initial payload.
+pub type AgentFactory<const INTER_SLOTS: usize, MessageType> =
+    Arc<dyn Fn(MessageType) -> Box<dyn ThreadedAgent<INTER_SLOTS, MessageType>> + Send + Sync>;
+
+/// A registered factory and the state arena size of the agents it builds.
+pub(crate) struct AgentTemplate<const INTER_SLOTS: usize, MessageType: Pod + Zeroable + Clone> {
+    pub(crate) factory: AgentFactory<INTER_SLOTS, MessageType>,
+    pub(crate) state_arena_size: usize,
+}
+
+impl<const INTER_SLOTS: usize, MessageType: Pod + Zeroable + Clone> Clone
+    for AgentTemplate<INTER_SLOTS, MessageType>
+{
+    fn clone(&self) -> Self {
+        Self {
+            factory: Arc::clone(&self.factory),
+            state_arena_size: self.state_arena_size,
+        }
+    }
+}
+
+/// A spawn requested by a handler, applied by the `Planet` once the handler returns.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct SpawnRequest<MessageType> {
+    pub(crate) template: usize,
+    pub(crate) agent: usize,
+    pub(crate) init: MessageType,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        thread::sleep,
+        time::Duration,
+    };
+
+    use bytemuck::{Pod, Zeroable};
+
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Arrival {
+        customer: u32,
+    }
+
+    unsafe impl Pod for Arrival {}
+    unsafe impl Zeroable for Arrival {}
+
+    // Slowly sends a customer to the shop on planet 0 every other step
+    struct Door {
+        next: u32,
+    }
+
+    impl ThreadedAgent<128, Arrival> for Door {
+        fn step(&mut self, context: &mut PlanetContext<128, Arrival>, agent_id: usize) -> Event {
+            let time = context.time;
+            sleep(Duration::from_micros(200));
+            if time.is_multiple_of(2) && time <= 20 {
+                let customer = Arrival {
+                    customer: self.next,
+                };
+                self.next += 1;
+                let msg = Msg::new(customer, time, time + 1, agent_id, Some(0));
+                context.send_mail(msg, 0).unwrap();
+            }
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Arrival>,
+            _msg: Msg<Arrival>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    // Creates a customer agent for every arrival and wakes it at once
+    struct Shop;
+
+    impl ThreadedAgent<128, Arrival> for Shop {
+        fn step(&mut self, context: &mut PlanetContext<128, Arrival>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            context: &mut PlanetContext<128, Arrival>,
+            msg: Msg<Arrival>,
+            _agent_id: usize,
+        ) {
+            let customer = context.spawn_from_template(0, msg.data).unwrap();
+            context.schedule_wakeup(customer, msg.recv);
+        }
+    }
+
+    struct Customer {
+        customer: u32,
+        log: Arc<Mutex<Vec<(usize, u32, u64)>>>,
+    }
+
+    impl ThreadedAgent<128, Arrival> for Customer {
+        fn step(&mut self, context: &mut PlanetContext<128, Arrival>, agent_id: usize) -> Event {
+            let entry = (agent_id, self.customer, context.time);
+            self.log.lock().unwrap().push(entry);
+            Event::new(context.time, context.time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Arrival>,
+            _msg: Msg<Arrival>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_spawn_from_message() {
+        let config = HybridConfig::new(2, 256)
+            .with_time_bounds(30.0, 1.0)
+            .with_optimistic_sync(8, 10)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<128, 128, 1, Arrival>::create(config).unwrap();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let shared = Arc::clone(&log);
+        let template = engine.register_template(
+            Arc::new(move |init: Arrival| {
+                Box::new(Customer {
+                    customer: init.customer,
+                    log: Arc::clone(&shared),
+                }) as Box<dyn ThreadedAgent<128, Arrival>>
+            }),
+            16,
+        );
+        assert_eq!(template, 0);
+        engine.spawn_agent(0, Box::new(Shop)).unwrap();
+        engine.spawn_agent(1, Box::new(Door { next: 0 })).unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+
+        // ten customers, each under the index after the shop in arrival order, however often planet 0 rolled back
+        assert_eq!(engine.planets[0].agents.len(), 11);
+        let mut steps = log.lock().unwrap().clone();
+        steps.sort();
+        steps.dedup();
+        let expected = (0..10)
+            .map(|i| (i as usize + 1, i, i as u64 * 2 + 3))
+            .collect::<Vec<_>>();
+        assert_eq!(steps, expected);
+    }
+}
@@ -0,0 +1,159 @@
+//! Run statistics collected by each `Planet` and gathered by the `HybridEngine` after a run.
+use std::collections::BTreeMap;
+
+use crate::{
+    agents::sandbox::Violation,
+    mt::hybrid::{
+        backoff::GalaxyStats, faults::FaultStats, link::LinkStats, memory::MemoryUsage,
+        reports::RollbackReport,
+    },
+    overflow::OverflowStats,
+};
+
+/// A condition worth surfacing to the user that didn't stop the run.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SimWarning {
+    /// A straggler from `from_world` rolled `world` back `depth` steps to `time`, past the configured
+    /// `max_rollback_depth`. The link was switched to conservative execution until GVT reached `until`.
+    RollbackStorm {
+        world: usize,
+        from_world: usize,
+        time: u64,
+        depth: u64,
+        until: u64,
+    },
+    /// `agent` on `world` panicked at `time` and was removed under `PanicPolicy::Remove`.
+    AgentRemoved {
+        world: usize,
+        agent: usize,
+        time: u64,
+    },
+    /// `agent` on `world` read a `Msg` from `from_world` at `recv` that depends on the state of `planet` at `time`,
+    /// which a rollback of `planet` to `rolled_back_to` undid, and GVT passed `recv` without the letter being
+    /// cancelled. Only raised with the `validate-causality` feature.
+    CausalityViolation {
+        world: usize,
+        agent: usize,
+        from_world: usize,
+        recv: u64,
+        planet: usize,
+        time: u64,
+        rolled_back_to: u64,
+    },
+    /// `world` used `bytes` at `time`, past the soft limit of its `MemoryLimits`, and held back at GVT until it was
+    /// under the limit again.
+    MemoryPressure {
+        world: usize,
+        time: u64,
+        bytes: usize,
+    },
+}
+
+/// Counters kept by a single `Planet`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlanetStats {
+    pub world_id: usize,
+    pub rollbacks: u64,
+    /// total number of steps undone by rollbacks
+    pub rollback_steps: u64,
+    /// number of rollbacks by the steps each undid
+    pub rollback_depths: BTreeMap<u64, u64>,
+    /// agents handed to and received from other planets by migration
+    pub migrated_out: u64,
+    pub migrated_in: u64,
+    /// occupancy of the event overflow queue as of the last step
+    pub overflow: OverflowStats,
+    /// utilization of each outgoing link with a `LinkModel`, keyed by destination world
+    pub links: BTreeMap<usize, LinkStats>,
+    /// faults injected into the interplanetary mail this `Planet` sent
+    pub faults: FaultStats,
+    /// calls refused to sandboxed agents, by time
+    pub violations: Vec<Violation>,
+    /// transfers written into the interplanetary messenger, each carrying one or more letters
+    pub bundles_sent: u64,
+    /// mail this `Planet` sent that was dropped at a full inbox and handed back to its senders
+    pub undelivered: u64,
+    pub warnings: Vec<SimWarning>,
+    /// one summary per GVT window with rollbacks, when rollback reports are enabled
+    pub rollback_reports: Vec<RollbackReport>,
+    /// throttle horizon in force at the end of the run, and how often the adaptive throttle moved it
+    pub throttle_horizon: u64,
+    pub horizon_changes: u64,
+    /// empty ticks jumped over by fast-forwarding
+    pub skipped_ticks: u64,
+    /// memory held as of the last sample, with `HybridConfig::with_memory_limits()`
+    pub memory: MemoryUsage,
+}
+
+/// Statistics for a whole `HybridEngine` run, one entry per `Planet` in world id order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RunStats {
+    pub planets: Vec<PlanetStats>,
+    pub galaxy: GalaxyStats,
+}
+
+impl RunStats {
+    pub fn rollbacks(&self) -> u64 {
+        self.planets.iter().map(|planet| planet.rollbacks).sum()
+    }
+
+    /// Utilization of every modelled link, keyed by `(from, to)` world.
+    pub fn links(&self) -> BTreeMap<(usize, usize), LinkStats> {
+        self.planets
+            .iter()
+            .flat_map(|planet| {
+                planet
+                    .links
+                    .iter()
+                    .map(|(to, stats)| ((planet.world_id, *to), *stats))
+            })
+            .collect()
+    }
+
+    /// Faults injected into the interplanetary mail of every `Planet`.
+    pub fn faults(&self) -> FaultStats {
+        self.planets
+            .iter()
+            .fold(FaultStats::default(), |mut total, planet| {
+                total.dropped += planet.faults.dropped;
+                total.duplicated += planet.faults.duplicated;
+                total.delayed += planet.faults.delayed;
+                total.total_delay += planet.faults.total_delay;
+                total
+            })
+    }
+
+    /// Every call refused to a sandboxed agent, per `Planet` by time.
+    pub fn violations(&self) -> impl Iterator<Item = &Violation> {
+        self.planets
+            .iter()
+            .flat_map(|planet| planet.violations.iter())
+    }
+
+    /// Every warning raised during the run.
+    pub fn warnings(&self) -> impl Iterator<Item = &SimWarning> {
+        self.planets
+            .iter()
+            .flat_map(|planet| planet.warnings.iter())
+    }
+
+    /// Number of rollbacks of every `Planet` by the steps each undid.
+    pub fn rollback_depths(&self) -> BTreeMap<u64, u64> {
+        let mut depths = BTreeMap::new();
+        for (depth, count) in self
+            .planets
+            .iter()
+            .flat_map(|planet| &planet.rollback_depths)
+        {
+            *depths.entry(*depth).or_default() += count;
+        }
+        depths
+    }
+
+    /// Every rollback report emitted during the run, per `Planet` in window order.
+    pub fn rollback_reports(&self) -> impl Iterator<Item = &RollbackReport> {
+        self.planets
+            .iter()
+            .flat_map(|planet| planet.rollback_reports.iter())
+    }
+}
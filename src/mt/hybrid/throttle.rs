@@ -0,0 +1,148 @@
+//! Adaptive throttle horizon driven by the rollback rate.
+//! A fixed `throttle_horizon` is either too tight, idling planets that could run ahead safely, or too loose,
+//! letting them run so far that stragglers undo a lot of work. With `AdaptiveThrottle` every `Planet` counts its
+//! rollbacks over each window of `window` steps. A rate above `shrink_above` rollbacks per step halves the horizon,
+//! and a rate at or below `grow_below` widens it by `step`, always staying within `[min_horizon, max_horizon]`.
+//! Barriers and clock sizing plan for `max_horizon`, the furthest any `Planet` may run ahead of GVT.
+
+/// Parameters of the throttle controller, see `HybridConfig::with_adaptive_throttle()`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AdaptiveThrottle {
+    pub min_horizon: u64,
+    pub max_horizon: u64,
+    /// steps between two adjustments
+    pub window: u64,
+    /// rollbacks per step above which the horizon is halved
+    pub shrink_above: f64,
+    /// rollbacks per step at or below which the horizon widens
+    pub grow_below: f64,
+    /// ticks the horizon widens by at once
+    pub step: u64,
+}
+
+impl AdaptiveThrottle {
+    /// Keep the horizon within `[min_horizon, max_horizon]`, adjusting it every 64 steps: halved above one rollback
+    /// in 20 steps, widened by an eighth of the range when there were none.
+    pub fn new(min_horizon: u64, max_horizon: u64) -> Self {
+        Self {
+            min_horizon,
+            max_horizon,
+            window: 64,
+            shrink_above: 0.05,
+            grow_below: 0.0,
+            step: ((max_horizon.saturating_sub(min_horizon)) / 8).max(1),
+        }
+    }
+
+    /// Adjust the horizon every `window` steps.
+    pub fn with_window(mut self, window: u64) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    /// Halve the horizon above `shrink_above` rollbacks per step and widen it at or below `grow_below`.
+    pub fn with_thresholds(mut self, shrink_above: f64, grow_below: f64) -> Self {
+        self.shrink_above = shrink_above;
+        self.grow_below = grow_below;
+        self
+    }
+
+    /// Widen the horizon `step` ticks at a time.
+    pub fn with_step(mut self, step: u64) -> Self {
+        self.step = step.max(1);
+        self
+    }
+
+    /// `horizon` moved into `[min_horizon, max_horizon]`.
+    pub fn clamp(&self, horizon: u64) -> u64 {
+        horizon.clamp(self.min_horizon, self.max_horizon)
+    }
+}
+
+/// The horizon of one `Planet` and the rollbacks seen in the current window.
+#[derive(Clone, Debug)]
+pub(crate) struct ThrottleController {
+    params: AdaptiveThrottle,
+    horizon: u64,
+    steps: u64,
+    rollbacks: u64,
+}
+
+impl ThrottleController {
+    pub(crate) fn new(params: AdaptiveThrottle, horizon: u64) -> Self {
+        Self {
+            params,
+            horizon: params.clamp(horizon),
+            steps: 0,
+            rollbacks: 0,
+        }
+    }
+
+    pub(crate) fn horizon(&self) -> u64 {
+        self.horizon
+    }
+
+    pub(crate) fn on_rollback(&mut self) {
+        self.rollbacks += 1;
+    }
+
+    /// Count a step, returning the new horizon at the end of a window.
+    pub(crate) fn on_step(&mut self) -> Option<u64> {
+        self.steps += 1;
+        if self.steps < self.params.window {
+            return None;
+        }
+        let rate = self.rollbacks as f64 / self.steps as f64;
+        if rate > self.params.shrink_above {
+            self.horizon = self.params.clamp(self.horizon / 2);
+        } else if rate <= self.params.grow_below {
+            self.horizon = self
+                .params
+                .clamp(self.horizon.saturating_add(self.params.step));
+        }
+        self.steps = 0;
+        self.rollbacks = 0;
+        Some(self.horizon)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_horizon_follows_rollback_rate() {
+        let params = AdaptiveThrottle::new(4, 40)
+            .with_window(10)
+            .with_thresholds(0.2, 0.05)
+            .with_step(6);
+        let mut controller = ThrottleController::new(params, 100);
+        assert_eq!(controller.horizon(), 40);
+
+        // three rollbacks in ten steps is a storm
+        for step in 0..10 {
+            if step < 3 {
+                controller.on_rollback();
+            }
+            let adjusted = controller.on_step();
+            assert_eq!(adjusted.is_some(), step == 9);
+        }
+        assert_eq!(controller.horizon(), 20);
+        for _ in 0..30 {
+            controller.on_rollback();
+            controller.on_step();
+        }
+        assert_eq!(controller.horizon(), 4);
+
+        // one in ten sits between the thresholds, none widens the horizon again
+        controller.on_rollback();
+        for _ in 0..10 {
+            controller.on_step();
+        }
+        assert_eq!(controller.horizon(), 4);
+        for _ in 0..20 {
+            controller.on_step();
+        }
+        assert_eq!(controller.horizon(), 16);
+    }
+}
@@ -0,0 +1,288 @@
+//! Planet-to-planet routes that can be re-wired while a hybrid run is going.
+//! Every directed route between two planets is open unless it has been removed, and may carry a `LinkModel`.
+//! `RouteChange`s are sent through the `ControlHandle` and queued with the `Galaxy`, which validates and applies
+//! them together at the next GVT checkpoint. Every `Planet` waits at that checkpoint with nothing in flight, so the
+//! new topology takes effect everywhere at the same simulation time and can never be rolled back. Planets at either
+//! end of a changed route then update their links and hand the change to their agents via
+//! `ThreadedAgent::on_control()`. Mail over a removed route fails with `AikaError::NoRoute`.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use crate::{mt::hybrid::link::LinkModel, AikaError};
+
+/// A change to the directed route from planet `from` to planet `to`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RouteChange {
+    /// Open the route, modelled by `link` or unmodelled with `None`.
+    Add {
+        from: usize,
+        to: usize,
+        link: Option<LinkModel>,
+    },
+    /// Close the route, dropping its `LinkModel`.
+    Remove { from: usize, to: usize },
+}
+
+impl RouteChange {
+    /// The `(from, to)` worlds of the route.
+    pub fn route(&self) -> (usize, usize) {
+        match *self {
+            RouteChange::Add { from, to, .. } | RouteChange::Remove { from, to } => (from, to),
+        }
+    }
+}
+
+/// A `RouteChange` as handled by the `Galaxy`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TopologyRecord {
+    /// GVT checkpoint the change was handled at
+    pub checkpoint: u64,
+    pub change: RouteChange,
+    /// `false` if the change no longer made sense by the checkpoint, e.g. removing a route that was already closed
+    pub applied: bool,
+}
+
+#[derive(Debug, Default)]
+struct TopologyState {
+    closed: BTreeSet<(usize, usize)>,
+    links: BTreeMap<(usize, usize), LinkModel>,
+    pending: Vec<RouteChange>,
+    log: Vec<TopologyRecord>,
+}
+
+/// Routes shared by the `Galaxy`, the `Planet`s and every `ControlHandle` of a run.
+#[derive(Debug)]
+pub(crate) struct Topology {
+    worlds: usize,
+    state: Mutex<TopologyState>,
+    /// length of the log, bumped once a checkpoint's changes are all recorded
+    handled: AtomicUsize,
+}
+
+impl Topology {
+    pub(crate) fn new(worlds: usize, links: BTreeMap<(usize, usize), LinkModel>) -> Self {
+        let state = TopologyState {
+            links,
+            ..Default::default()
+        };
+        Self {
+            worlds,
+            state: Mutex::new(state),
+            handled: AtomicUsize::new(0),
+        }
+    }
+
+    /// Queue a change for the next checkpoint.
+    pub(crate) fn request(&self, change: RouteChange) -> Result<(), AikaError> {
+        let (from, to) = change.route();
+        if let Some(world) = [from, to].into_iter().find(|world| *world >= self.worlds) {
+            return Err(AikaError::InvalidWorldId(world));
+        }
+        if from == to {
+            return Err(AikaError::ConfigError(format!(
+                "planet {from} can't have a route to itself"
+            )));
+        }
+        let mut state = self.state.lock().map_err(|_| AikaError::PoisonedLock)?;
+        state.pending.push(change);
+        Ok(())
+    }
+
+    /// Apply every queued change valid against the current routes, in order.
+    pub(crate) fn apply(&self, checkpoint: u64) -> Result<(), AikaError> {
+        let mut state = self.state.lock().map_err(|_| AikaError::PoisonedLock)?;
+        if state.pending.is_empty() {
+            return Ok(());
+        }
+        for change in std::mem::take(&mut state.pending) {
+            let route = change.route();
+            let applied = match change {
+                RouteChange::Add { link, .. } => {
+                    state.closed.remove(&route);
+                    match link {
+                        Some(link) => state.links.insert(route, link),
+                        None => state.links.remove(&route),
+                    };
+                    true
+                }
+                RouteChange::Remove { .. } => {
+                    state.links.remove(&route);
+                    state.closed.insert(route)
+                }
+            };
+            state.log.push(TopologyRecord {
+                checkpoint,
+                change,
+                applied,
+            });
+        }
+        self.handled.store(state.log.len(), Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Number of changes handled so far.
+    pub(crate) fn handled(&self) -> usize {
+        self.handled.load(Ordering::SeqCst)
+    }
+
+    /// The changes handled after the first `seen`.
+    pub(crate) fn since(&self, seen: usize) -> Result<Vec<TopologyRecord>, AikaError> {
+        let state = self.state.lock().map_err(|_| AikaError::PoisonedLock)?;
+        Ok(state.log.get(seen..).unwrap_or_default().to_vec())
+    }
+
+    /// The closed routes out of `world`, by destination.
+    pub(crate) fn closed_from(&self, world: usize) -> Result<BTreeSet<usize>, AikaError> {
+        let state = self.state.lock().map_err(|_| AikaError::PoisonedLock)?;
+        Ok(state
+            .closed
+            .iter()
+            .filter(|(from, _)| *from == world)
+            .map(|(_, to)| *to)
+            .collect())
+    }
+
+    pub(crate) fn log(&self) -> Vec<TopologyRecord> {
+        self.since(0).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, control::ControlAction, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Ping {
+        time: u64,
+    }
+
+    unsafe impl Pod for Ping {}
+    unsafe impl Zeroable for Ping {}
+
+    type Log = Arc<Mutex<Vec<(usize, &'static str, u64)>>>;
+
+    // Pings planet 1 every step until its route is closed, logging rewires and failed sends
+    struct Pinger {
+        log: Log,
+    }
+
+    impl ThreadedAgent<128, Ping> for Pinger {
+        fn step(&mut self, context: &mut PlanetContext<128, Ping>, agent_id: usize) -> Event {
+            let time = context.time;
+            let msg = Msg::new(Ping { time }, time, time + 1, agent_id, Some(0));
+            if let Err(AikaError::NoRoute(0, 1)) = context.send_mail(msg, 1) {
+                self.log.lock().unwrap().push((0, "no route", time));
+            }
+            Event::new(time, time, agent_id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Ping>,
+            _msg: Msg<Ping>,
+            _agent_id: usize,
+        ) {
+        }
+
+        fn on_control(
+            &mut self,
+            context: &mut PlanetContext<128, Ping>,
+            action: ControlAction,
+            _agent_id: usize,
+        ) {
+            if let ControlAction::Rewire(RouteChange::Remove { .. }) = action {
+                self.log.lock().unwrap().push((0, "rewired", context.time));
+            }
+        }
+    }
+
+    struct Ponger {
+        log: Log,
+    }
+
+    impl ThreadedAgent<128, Ping> for Ponger {
+        fn step(&mut self, context: &mut PlanetContext<128, Ping>, agent_id: usize) -> Event {
+            Event::new(context.time, context.time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Ping>,
+            msg: Msg<Ping>,
+            _agent_id: usize,
+        ) {
+            self.log.lock().unwrap().push((1, "ping", msg.data.time));
+        }
+
+        fn on_control(
+            &mut self,
+            context: &mut PlanetContext<128, Ping>,
+            action: ControlAction,
+            _agent_id: usize,
+        ) {
+            if let ControlAction::Rewire(_) = action {
+                self.log.lock().unwrap().push((1, "rewired", context.time));
+            }
+        }
+    }
+
+    #[test]
+    fn test_route_removed_at_checkpoint() {
+        let config = HybridConfig::new(3, 256)
+            .with_time_bounds(16.0, 1.0)
+            .with_optimistic_sync(2, 8)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<128, 128, 1, Ping>::create(config).unwrap();
+        let log = Log::default();
+        engine
+            .spawn_agent(0, Box::new(Pinger { log: log.clone() }))
+            .unwrap();
+        engine
+            .spawn_agent(1, Box::new(Ponger { log: log.clone() }))
+            .unwrap();
+        engine.schedule(0, 0, 1).unwrap();
+        let control = engine.control_handle();
+        assert!(matches!(
+            control.rewire(RouteChange::Remove { from: 0, to: 3 }),
+            Err(AikaError::InvalidWorldId(3))
+        ));
+        control
+            .rewire(RouteChange::Remove { from: 0, to: 1 })
+            .unwrap();
+        // closed twice by the time the checkpoint is reached
+        let again = ControlAction::Rewire(RouteChange::Remove { from: 0, to: 1 });
+        control.send(2, again).unwrap();
+        let engine = engine.run().unwrap();
+
+        // pings sent before the first checkpoint at 8 arrive, later ones are refused
+        let mut log = log.lock().unwrap().clone();
+        log.sort();
+        log.dedup();
+        let mut expected = vec![(0, "rewired", 8)];
+        expected.extend((8..16).map(|time| (0, "no route", time)));
+        expected.push((1, "rewired", 8));
+        expected.extend((1..8).map(|time| (1, "ping", time)));
+        expected.sort();
+        assert_eq!(log, expected);
+
+        let records = engine.topology_log();
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|record| record.checkpoint == 8));
+        assert!(records[0].applied && !records[1].applied);
+    }
+}
@@ -0,0 +1,297 @@
+//! Tuning of the optimistic synchronization parameters from a short pilot run.
+//! A `Tuner` builds an engine from a `HybridConfig`, advances it a few ticks of GVT with `HybridEngine::advance()`
+//! and reads two distributions off it: how many ticks after their send time the interplanetary `Msg`s were received,
+//! the lookahead the planets had, from the `BlockLedger`, and how many steps each rollback undid, from the `RunStats`.
+//! A `PilotReport` turns them into settings:
+//! - the throttle horizon is the lookahead of all but the tightest tenth of the mail, so a `Planet` rarely runs far
+//!   enough ahead of GVT for a straggler to reach it. Without any mail nothing rolls back, and the horizon doubles.
+//! - the checkpoint window is twice the larger of that horizon and the depth of nine in ten rollbacks, so planets
+//!   stall at checkpoints rarely compared with the ground the horizon lets them cover.
+//! - mail is batched for one tick less than the shortest lookahead, where holding it back can't make a straggler,
+//!   in batches of the letters a pair of planets exchanged over that long. Mail due the next tick isn't batched.
+use std::collections::BTreeMap;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    mt::hybrid::{batch::MailBatching, config::HybridConfig, HybridEngine},
+    AikaError,
+};
+
+/// Counts of values, such as lags or rollback depths in ticks.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Distribution {
+    counts: BTreeMap<u64, u64>,
+}
+
+impl Distribution {
+    pub fn new(counts: BTreeMap<u64, u64>) -> Self {
+        Self { counts }
+    }
+
+    /// Number of values counted.
+    pub fn count(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count() == 0
+    }
+
+    pub fn min(&self) -> Option<u64> {
+        self.counts.keys().next().copied()
+    }
+
+    pub fn max(&self) -> Option<u64> {
+        self.counts.keys().next_back().copied()
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        let count = self.count();
+        let sum = self.counts.iter().map(|(value, n)| value * n).sum::<u64>();
+        (count > 0).then(|| sum as f64 / count as f64)
+    }
+
+    /// Smallest value at least a `q` fraction of the values are no greater than.
+    pub fn quantile(&self, q: f64) -> Option<u64> {
+        let rank = (q.clamp(0.0, 1.0) * self.count() as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        self.counts.iter().find_map(|(value, n)| {
+            seen += n;
+            (seen >= rank).then_some(*value)
+        })
+    }
+
+    /// `(value, count)` of every value counted, ascending.
+    pub fn counts(&self) -> &BTreeMap<u64, u64> {
+        &self.counts
+    }
+}
+
+/// Settings recommended by a pilot run.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Recommendation {
+    pub throttle_horizon: u64,
+    pub checkpoint_frequency: u64,
+    pub mail_batching: Option<MailBatching>,
+}
+
+impl Recommendation {
+    /// `config` with the recommended settings, its others untouched.
+    pub fn apply(&self, config: HybridConfig) -> HybridConfig {
+        let config = config.with_optimistic_sync(self.throttle_horizon, self.checkpoint_frequency);
+        match self.mail_batching {
+            Some(batching) => {
+                config.with_mail_batching(batching.max_batch, batching.flush_interval)
+            }
+            None => config,
+        }
+    }
+}
+
+/// What a pilot run measured.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PilotReport {
+    /// ticks of GVT the pilot covered
+    pub ticks: u64,
+    /// receive time less send time of every interplanetary `Msg` delivered
+    pub lags: Distribution,
+    /// steps undone by every rollback
+    pub depths: Distribution,
+    /// pairs of planets that exchanged any `Msg`
+    pub pairs: usize,
+    /// settings the pilot ran with
+    pub throttle_horizon: u64,
+    pub checkpoint_frequency: u64,
+}
+
+impl PilotReport {
+    pub fn recommend(&self) -> Recommendation {
+        let throttle_horizon = match self.lags.quantile(0.1) {
+            Some(lookahead) => lookahead.max(1),
+            None => self.throttle_horizon.saturating_mul(2).max(1),
+        };
+        let depth = self.depths.quantile(0.9).unwrap_or(0);
+        let checkpoint_frequency = throttle_horizon.max(depth).saturating_mul(2);
+        let mail_batching = self
+            .lags
+            .min()
+            .filter(|lookahead| *lookahead >= 2 && self.ticks > 0)
+            .map(|lookahead| {
+                let flush_interval = lookahead - 1;
+                let per_tick =
+                    self.lags.count() as f64 / (self.ticks * self.pairs.max(1) as u64) as f64;
+                let max_batch = (per_tick * flush_interval as f64).ceil().max(1.0) as usize;
+                MailBatching::new(max_batch, flush_interval)
+            });
+        Recommendation {
+            throttle_horizon,
+            checkpoint_frequency,
+            mail_batching,
+        }
+    }
+}
+
+/// Runs pilots of `pilot` ticks of GVT.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Tuner {
+    pilot: u64,
+}
+
+impl Tuner {
+    pub fn new(pilot: u64) -> Self {
+        Self {
+            pilot: pilot.max(1),
+        }
+    }
+
+    /// Run a pilot of the engine `build` makes from `config`, with its agents spawned and scheduled.
+    pub fn pilot<
+        const INTER_SLOTS: usize,
+        const CLOCK_SLOTS: usize,
+        const CLOCK_HEIGHT: usize,
+        MessageType: Pod + Zeroable + Clone + Send + 'static,
+    >(
+        &self,
+        config: &HybridConfig,
+        build: impl FnOnce(
+            HybridConfig,
+        ) -> Result<
+            HybridEngine<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
+            AikaError,
+        >,
+    ) -> Result<PilotReport, AikaError> {
+        let engine = build(config.clone())?.advance(self.pilot)?;
+        let blocks = engine.block_ledger();
+        let pairs = blocks
+            .traffic()
+            .iter()
+            .flatten()
+            .filter(|msgs| **msgs > 0)
+            .count();
+        Ok(PilotReport {
+            ticks: engine.gvt(),
+            lags: Distribution::new(blocks.lags().clone()),
+            depths: Distribution::new(engine.stats().rollback_depths()),
+            pairs,
+            throttle_horizon: config.throttle_horizon,
+            checkpoint_frequency: config.checkpoint_frequency,
+        })
+    }
+
+    /// Run a pilot and return `config` with the settings it recommends.
+    pub fn tune<
+        const INTER_SLOTS: usize,
+        const CLOCK_SLOTS: usize,
+        const CLOCK_HEIGHT: usize,
+        MessageType: Pod + Zeroable + Clone + Send + 'static,
+    >(
+        &self,
+        config: HybridConfig,
+        build: impl FnOnce(
+            HybridConfig,
+        ) -> Result<
+            HybridEngine<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
+            AikaError,
+        >,
+    ) -> Result<HybridConfig, AikaError> {
+        let report = self.pilot(&config, build)?;
+        Ok(report.recommend().apply(config))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        objects::{Action, Event, Msg},
+    };
+
+    // Mails planet 1 every step, five ticks ahead
+    struct Ticker;
+
+    impl ThreadedAgent<16, u64> for Ticker {
+        fn step(&mut self, context: &mut PlanetContext<16, u64>, id: usize) -> Event {
+            let time = context.time;
+            context
+                .send_mail(Msg::new(time, time, time + 5, id, Some(0)), 1)
+                .unwrap();
+            Event::new(time, time, id, Action::Timeout(1))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, u64>,
+            _msg: Msg<u64>,
+            _id: usize,
+        ) {
+        }
+    }
+
+    // Reads the mail of planet 1
+    struct Sink;
+
+    impl ThreadedAgent<16, u64> for Sink {
+        fn step(&mut self, context: &mut PlanetContext<16, u64>, id: usize) -> Event {
+            Event::new(context.time, context.time, id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<16, u64>,
+            _msg: Msg<u64>,
+            _id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_quantiles() {
+        let lags = Distribution::new(BTreeMap::from([(1, 2), (4, 7), (9, 1)]));
+        assert_eq!(lags.count(), 10);
+        assert_eq!(lags.quantile(0.0), Some(1));
+        assert_eq!(lags.quantile(0.2), Some(1));
+        assert_eq!(lags.quantile(0.5), Some(4));
+        assert_eq!(lags.quantile(1.0), Some(9));
+        assert_eq!(lags.mean(), Some(3.9));
+        assert_eq!(Distribution::default().quantile(0.5), None);
+    }
+
+    #[test]
+    fn test_pilot_recommends_settings() {
+        // a throttle horizon short of the lookahead keeps the pilot free of rollbacks
+        let config = HybridConfig::new(2, 1 << 16)
+            .with_time_bounds(1000.0, 1.0)
+            .with_optimistic_sync(4, 10)
+            .with_uniform_worlds(16, 1, 16);
+        let build = |config: HybridConfig| {
+            let mut engine = HybridEngine::<16, 16, 2, u64>::create(config)?;
+            engine.spawn_agent(0, Box::new(Ticker))?;
+            engine.spawn_agent(1, Box::new(Sink))?;
+            engine.schedule_all_agents(1)?;
+            Ok(engine)
+        };
+        let report = Tuner::new(50).pilot(&config, build).unwrap();
+        assert_eq!(report.ticks, 50);
+        assert_eq!(report.lags.min(), Some(5));
+        assert_eq!(report.lags.max(), Some(5));
+        assert!(report.depths.is_empty());
+        assert_eq!(report.pairs, 1);
+
+        let recommended = report.recommend();
+        assert_eq!(recommended.throttle_horizon, 5);
+        assert_eq!(recommended.checkpoint_frequency, 10);
+        let batching = recommended.mail_batching.unwrap();
+        assert_eq!(batching.flush_interval, 4);
+        // about one letter a tick
+        assert!((3..=5).contains(&batching.max_batch));
+
+        let tuned = Tuner::new(50).tune(config, build).unwrap();
+        assert_eq!(tuned.throttle_horizon, 5);
+        assert_eq!(
+            tuned.mail_batching.map(|batching| batching.flush_interval),
+            Some(4)
+        );
+    }
+}
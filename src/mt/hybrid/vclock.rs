@@ -0,0 +1,322 @@
+//! Vector clocks for validating causality across rollbacks, behind the `validate-causality` feature.
+//! Every `Planet` runs through incarnations, a new one starting at each rollback, and every `Mail` it sends carries
+//! a `VectorClock`: for each planet, the incarnation and time of the latest state of that planet the sender's state
+//! depends on. Reading a `Msg` merges its clock into the reader's, and a rollback restores the clock the reader had
+//! at the rollback's target. A rollback to a time re-runs the tick but keeps the anti-messages of the mail sent in it,
+//! so it undoes what was read from that time on and what was sent after it: the state a letter is sent from is
+//! undone once its planet rolls back to before its time in the same or a later incarnation. Reading mail whose
+//! causal past was undone is normal in Time Warp until the anti-messages catch up, so a letter is only checked once
+//! GVT passes its receive time and its reading is committed: if part of its past was undone by then, the `Planet`
+//! raises `SimWarning::CausalityViolation`. Only the first `CLOCK_WIDTH` planets are tracked, and mail forwarded
+//! after a migration or carried over a bridge loses its clock.
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{mt::hybrid::stats::SimWarning, objects::Msg, AikaError};
+
+/// Planets a `VectorClock` tracks.
+pub const CLOCK_WIDTH: usize = 16;
+
+/// A state of one planet: its time within an incarnation, numbered from 1. Incarnation 0 is no state at all.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct ClockEntry {
+    pub incarnation: u64,
+    pub time: u64,
+}
+
+/// The latest state of each planet that a state depends on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub struct VectorClock {
+    pub entries: [ClockEntry; CLOCK_WIDTH],
+}
+
+unsafe impl Pod for ClockEntry {}
+unsafe impl Zeroable for ClockEntry {}
+unsafe impl Pod for VectorClock {}
+unsafe impl Zeroable for VectorClock {}
+
+impl Default for VectorClock {
+    fn default() -> Self {
+        Self::zeroed()
+    }
+}
+
+impl VectorClock {
+    pub fn get(&self, planet: usize) -> Option<ClockEntry> {
+        self.entries
+            .get(planet)
+            .copied()
+            .filter(|entry| entry.incarnation != 0)
+    }
+
+    /// Every planet the clock depends on, with its state.
+    pub fn dependencies(&self) -> impl Iterator<Item = (usize, ClockEntry)> + '_ {
+        (0..CLOCK_WIDTH).filter_map(|planet| Some((planet, self.get(planet)?)))
+    }
+}
+
+/// The rollbacks of every planet of a run, shared by its `Planet`s.
+#[derive(Debug, Default)]
+pub struct Incarnations {
+    /// targets of each planet's rollbacks, the `k`th ending incarnation `k + 1`
+    rollbacks: Mutex<Vec<Vec<u64>>>,
+}
+
+impl Incarnations {
+    /// The incarnation `planet` is in now.
+    pub fn current(&self, planet: usize) -> Result<u64, AikaError> {
+        let rollbacks = self.lock()?;
+        Ok(rollbacks.get(planet).map_or(0, Vec::len) as u64 + 1)
+    }
+
+    /// The target of the first rollback that undid `state` of `planet`, if any did.
+    pub fn undone(&self, planet: usize, state: ClockEntry) -> Result<Option<u64>, AikaError> {
+        let rollbacks = self.lock()?;
+        let Some(targets) = rollbacks.get(planet) else {
+            return Ok(None);
+        };
+        let since = (state.incarnation as usize)
+            .saturating_sub(1)
+            .min(targets.len());
+        Ok(targets[since..]
+            .iter()
+            .copied()
+            .find(|target| *target < state.time))
+    }
+
+    /// Start a new incarnation of `planet`, rolled back to `time`.
+    fn rollback(&self, planet: usize, time: u64) -> Result<(), AikaError> {
+        let mut rollbacks = self.lock()?;
+        if rollbacks.len() <= planet {
+            rollbacks.resize(planet + 1, Vec::new());
+        }
+        rollbacks[planet].push(time);
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Vec<Vec<u64>>>, AikaError> {
+        self.rollbacks.lock().map_err(|_| AikaError::PoisonedLock)
+    }
+}
+
+/// What identifies a delivered `Msg` until it is read.
+type LetterKey = (usize, usize, Option<usize>, u64, u64, u32);
+
+fn key<T: Clone>(msg: &Msg<T>) -> LetterKey {
+    (
+        msg.from_world,
+        msg.from,
+        msg.to,
+        msg.sent,
+        msg.recv,
+        msg.seq,
+    )
+}
+
+/// A `Msg` read from another planet, waiting for GVT to pass its receive time.
+#[derive(Copy, Clone, Debug)]
+struct Reading {
+    agent: usize,
+    from_world: usize,
+    recv: u64,
+    clock: VectorClock,
+}
+
+/// The clock of one `Planet`, and the mail it read that isn't committed yet.
+#[derive(Debug)]
+pub(crate) struct CausalValidator {
+    world: usize,
+    incarnations: Arc<Incarnations>,
+    clock: VectorClock,
+    /// the clock before each merge, by the time of the merge
+    history: Vec<(u64, VectorClock)>,
+    /// clocks of delivered mail not yet read
+    delivered: BTreeMap<LetterKey, VectorClock>,
+    readings: Vec<Reading>,
+}
+
+impl CausalValidator {
+    pub(crate) fn new(world: usize, incarnations: Arc<Incarnations>) -> Self {
+        Self {
+            world,
+            incarnations,
+            clock: VectorClock::default(),
+            history: Vec::new(),
+            delivered: BTreeMap::new(),
+            readings: Vec::new(),
+        }
+    }
+
+    /// The clock of mail sent at `time`.
+    pub(crate) fn stamp(&self, time: u64) -> Result<VectorClock, AikaError> {
+        let mut clock = self.clock;
+        if let Some(entry) = clock.entries.get_mut(self.world) {
+            *entry = ClockEntry {
+                incarnation: self.incarnations.current(self.world)?,
+                time,
+            };
+        }
+        Ok(clock)
+    }
+
+    /// Keep the clock of `msg`, delivered from another planet, until it is read.
+    pub(crate) fn deliver<T: Clone>(&mut self, msg: &Msg<T>, clock: VectorClock) {
+        if msg.from_world != self.world {
+            self.delivered.insert(key(msg), clock);
+        }
+    }
+
+    /// Merge the clock of `msg`, read by `agent` at `time`, into the planet's.
+    pub(crate) fn read<T: Clone>(
+        &mut self,
+        msg: &Msg<T>,
+        agent: usize,
+        time: u64,
+    ) -> Result<(), AikaError> {
+        let Some(clock) = self.delivered.get(&key(msg)).copied() else {
+            return Ok(());
+        };
+        self.readings.push(Reading {
+            agent,
+            from_world: msg.from_world,
+            recv: msg.recv,
+            clock,
+        });
+        self.history.push((time, self.clock));
+        for (planet, theirs) in clock.dependencies() {
+            let merged = match self.clock.get(planet) {
+                Some(ours) => self.merge(planet, ours, theirs)?,
+                None => theirs,
+            };
+            self.clock.entries[planet] = merged;
+        }
+        Ok(())
+    }
+
+    /// The later of two states of `planet`. A state that survived into the current incarnation is the same state in
+    /// it, and a state already undone is kept, so that whatever depends on it is caught.
+    fn merge(
+        &self,
+        planet: usize,
+        ours: ClockEntry,
+        theirs: ClockEntry,
+    ) -> Result<ClockEntry, AikaError> {
+        if self.incarnations.undone(planet, ours)?.is_some() {
+            return Ok(ours);
+        }
+        if self.incarnations.undone(planet, theirs)?.is_some() {
+            return Ok(theirs);
+        }
+        Ok(ClockEntry {
+            incarnation: self.incarnations.current(planet)?,
+            time: ours.time.max(theirs.time),
+        })
+    }
+
+    /// Start a new incarnation at `time`, forgetting what was read from then on.
+    pub(crate) fn rollback(&mut self, time: u64) -> Result<(), AikaError> {
+        self.incarnations.rollback(self.world, time)?;
+        if let Some(first) = self.history.iter().position(|(at, _)| *at >= time) {
+            self.clock = self.history[first].1;
+            self.history.truncate(first);
+        }
+        self.readings.retain(|reading| reading.recv < time);
+        Ok(())
+    }
+
+    /// Check the mail read before `gvt`, whose reading can no longer be undone, returning a warning for every
+    /// letter whose causal past was.
+    pub(crate) fn commit(&mut self, gvt: u64) -> Result<Vec<SimWarning>, AikaError> {
+        let mut warnings = Vec::new();
+        for reading in self.readings.iter().filter(|reading| reading.recv < gvt) {
+            for (planet, state) in reading.clock.dependencies() {
+                if let Some(rolled_back_to) = self.incarnations.undone(planet, state)? {
+                    warnings.push(SimWarning::CausalityViolation {
+                        world: self.world,
+                        agent: reading.agent,
+                        from_world: reading.from_world,
+                        recv: reading.recv,
+                        planet,
+                        time: state.time,
+                        rolled_back_to,
+                    });
+                    break;
+                }
+            }
+        }
+        self.readings.retain(|reading| reading.recv >= gvt);
+        self.delivered.retain(|key, _| key.4 >= gvt);
+        // a rollback can't reach a time before GVT, and only restores clocks from merges at or after its target
+        let committed = self.history.partition_point(|(at, _)| *at < gvt);
+        self.history.drain(..committed);
+        Ok(warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(incarnation: u64, time: u64) -> ClockEntry {
+        ClockEntry { incarnation, time }
+    }
+
+    #[test]
+    fn test_rollbacks_undo_states() {
+        let incarnations = Incarnations::default();
+        assert_eq!(incarnations.current(2).unwrap(), 1);
+        incarnations.rollback(2, 10).unwrap();
+        incarnations.rollback(2, 4).unwrap();
+        assert_eq!(incarnations.current(2).unwrap(), 3);
+        // the first rollback left time 9 of incarnation 1, the second undid it
+        assert_eq!(incarnations.undone(2, entry(1, 9)).unwrap(), Some(4));
+        assert_eq!(incarnations.undone(2, entry(1, 4)).unwrap(), None);
+        assert_eq!(incarnations.undone(2, entry(3, 9)).unwrap(), None);
+        assert_eq!(incarnations.undone(0, entry(1, 9)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_orphans_are_caught_at_commit() {
+        let incarnations = Arc::new(Incarnations::default());
+        let mut sender = CausalValidator::new(0, Arc::clone(&incarnations));
+        let mut reader = CausalValidator::new(1, Arc::clone(&incarnations));
+        let mut msg = Msg::new(0u8, 5, 6, 0, Some(3));
+        msg.from_world = 0;
+        reader.deliver(&msg, sender.stamp(5).unwrap());
+        reader.read(&msg, 3, 6).unwrap();
+        assert_eq!(reader.stamp(7).unwrap().get(0), Some(entry(1, 5)));
+
+        // reading is undone along with the sender's state: nothing to report
+        sender.rollback(4).unwrap();
+        reader.rollback(6).unwrap();
+        assert_eq!(reader.stamp(7).unwrap().get(0), None);
+        assert!(reader.commit(10).unwrap().is_empty());
+
+        // the reader keeps a letter whose sender rolled back past it
+        let mut msg = Msg::new(0u8, 8, 9, 0, Some(3));
+        msg.from_world = 0;
+        reader.deliver(&msg, sender.stamp(8).unwrap());
+        reader.read(&msg, 3, 9).unwrap();
+        sender.rollback(7).unwrap();
+        assert!(reader.commit(9).unwrap().is_empty());
+        let warnings = reader.commit(10).unwrap();
+        assert_eq!(
+            warnings,
+            [SimWarning::CausalityViolation {
+                world: 1,
+                agent: 3,
+                from_world: 0,
+                recv: 9,
+                planet: 0,
+                time: 8,
+                rolled_back_to: 7,
+            }]
+        );
+    }
+}
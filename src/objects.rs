@@ -3,7 +3,7 @@
 //! optimistic rollback, and local event/mail systems for efficient time-based scheduling.
 use std::{
     cmp::{Ordering, Reverse},
-    collections::BinaryHeap,
+    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap},
 };
 
 use bytemuck::{Pod, Zeroable};
@@ -12,7 +12,15 @@ use mesocarp::{
     scheduling::{htw::Clock, Scheduleable},
 };
 
-use crate::AikaError;
+#[cfg(feature = "validate-causality")]
+use crate::mt::hybrid::vclock::VectorClock;
+use crate::{
+    agents::rpc::Rpc,
+    dynclock::{ClockSizing, DynClock},
+    overflow::{OverflowQueue, OverflowStats, OverflowStrategy, SpillStore},
+    time::{SimDuration, SimTime},
+    AikaError,
+};
 
 /// A `Msg` is a direct message between two entities that shares a piece of data of type T
 #[derive(Copy, Clone, Debug)]
@@ -21,25 +29,111 @@ pub struct Msg<T: Clone> {
     pub to: Option<usize>,
     pub sent: u64,
     pub recv: u64,
+    /// multicast group; only used when `to` is `None`
+    pub group: Option<GroupId>,
+    /// fraction of a tick in `[0, 1)` at which the `Msg` arrives, ordering mail within the `recv` tick
+    pub offset: f64,
+    /// world the `Msg` was sent from, set by `PlanetContext::send_mail()`
+    pub from_world: usize,
+    /// position among the `Msg`s its `Planet` sent in the same tick, set by `PlanetContext::send_mail()`
+    pub seq: u32,
+    /// whether the `Msg` is a timer set with `PlanetContext::set_timer()`, handed to `ThreadedAgent::on_timer()`
+    pub timer: bool,
+    /// role of the `Msg` in a request/response call, see `PlanetContext::request()`
+    pub rpc: Option<Rpc>,
+    /// whether the `Msg` only steps `to` as it is read, carrying an `Action::Trigger` to an agent on another planet
+    pub wake: bool,
+    /// id given by the sender's `CausalityLog`, if it keeps one
+    pub id: CausalId,
     pub data: T,
 }
 
 impl<T: Clone> Msg<T> {
     /// Create a new `Msg`. If `to: Option<usize>` is set to None, the `Msg` will be broadcasted to all entities.
-    pub fn new(data: T, sent: u64, recv: u64, from: usize, to: Option<usize>) -> Self {
+    pub fn new(
+        data: T,
+        sent: impl Into<SimTime>,
+        recv: impl Into<SimTime>,
+        from: usize,
+        to: Option<usize>,
+    ) -> Self {
         Self {
             from,
             to,
-            sent,
-            recv,
+            sent: sent.into().ticks(),
+            recv: recv.into().ticks(),
+            group: None,
+            offset: 0.0,
+            from_world: 0,
+            seq: 0,
+            timer: false,
+            rpc: None,
+            wake: false,
+            id: CausalId::NONE,
+            data,
+        }
+    }
+
+    /// Create a new `Msg` sent at `sent` and received `delay` later.
+    pub fn after(
+        data: T,
+        sent: SimTime,
+        delay: SimDuration,
+        from: usize,
+        to: Option<usize>,
+    ) -> Self {
+        Self::new(data, sent, sent + delay, from, to)
+    }
+
+    /// The tick the `Msg` was sent at, as a `SimTime`.
+    pub fn sent_at(&self) -> SimTime {
+        SimTime(self.sent)
+    }
+
+    /// The tick the `Msg` is received at, as a `SimTime`.
+    pub fn received_at(&self) -> SimTime {
+        SimTime(self.recv)
+    }
+
+    /// Create a new `Msg` delivered only to the members of `group`.
+    pub fn multicast(
+        data: T,
+        sent: impl Into<SimTime>,
+        recv: impl Into<SimTime>,
+        from: usize,
+        group: GroupId,
+    ) -> Self {
+        Self {
+            from,
+            to: None,
+            sent: sent.into().ticks(),
+            recv: recv.into().ticks(),
+            group: Some(group),
+            offset: 0.0,
+            from_world: 0,
+            seq: 0,
+            timer: false,
+            rpc: None,
+            wake: false,
+            id: CausalId::NONE,
             data,
         }
     }
+
+    /// Arrive `offset` of a tick after the start of `recv`.
+    pub fn with_offset(mut self, offset: f64) -> Self {
+        self.offset = offset;
+        self
+    }
 }
 
 impl<T: Clone> Message for Msg<T> {
     fn to(&self) -> Option<usize> {
-        self.to
+        // a `World` routes multicast like direct mail back through the sender's slot, then expands it to the group
+        match (self.to, self.group) {
+            (None, Some(_)) => Some(self.from),
+            _ => self.to,
+        }
     }
 
     fn from(&self) -> usize {
@@ -69,6 +163,7 @@ impl<T: Clone> PartialEq for Msg<T> {
             && self.to == other.to
             && self.sent == other.sent
             && self.recv == other.recv
+            && self.offset == other.offset
     }
 }
 
@@ -78,12 +173,184 @@ impl<T: Clone> Ord for Msg<T> {
     fn cmp(&self, other: &Self) -> Ordering {
         self.recv
             .cmp(&other.recv)
+            .then_with(|| self.offset.total_cmp(&other.offset))
             .then_with(|| self.sent.cmp(&other.sent))
             .then_with(|| self.from.cmp(&other.from))
             .then_with(|| self.to.cmp(&other.to))
     }
 }
 
+/// Identifier of a multicast group, derived from its name so it is the same on every `World` and `Planet`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GroupId(pub u64);
+
+impl GroupId {
+    /// Hash a group name (FNV-1a).
+    pub const fn named(name: &str) -> Self {
+        let bytes = name.as_bytes();
+        let mut hash = 0xcbf29ce484222325_u64;
+        let mut i = 0;
+        while i < bytes.len() {
+            hash ^= bytes[i] as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+            i += 1;
+        }
+        Self(hash)
+    }
+}
+
+/// Identifier of a committed event, stable across rollbacks and replays: a hash of the world, agent,
+/// time, the event's sequence among that agent's events in the tick, and the agent's behavior version.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EventId(pub u64);
+
+impl EventId {
+    pub fn new(world: usize, agent: usize, time: u64, sequence: u32, version: u32) -> Self {
+        // one multiply-xorshift round per field, cheap enough to run on every event
+        let mut hash = 0xcbf29ce484222325_u64;
+        for field in [
+            world as u64,
+            agent as u64,
+            time,
+            sequence as u64,
+            version as u64,
+        ] {
+            hash = (hash ^ field).wrapping_mul(0x9e3779b97f4a7c15);
+            hash ^= hash >> 29;
+        }
+        Self(hash)
+    }
+}
+
+/// Simulation-wide unique id of an executed `Event` or a sent `Msg`, handed out by a `Planet`'s `CausalityLog`: the
+/// planet in the top 16 bits and a counter that is never reset, not even by rollbacks, in the rest. Unlike an
+/// `EventId`, a re-executed event gets a new one. `CausalId::NONE` marks anything sent or scheduled while no log
+/// was kept, or from outside an agent.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct CausalId(pub u64);
+
+unsafe impl Pod for CausalId {}
+unsafe impl Zeroable for CausalId {}
+
+impl CausalId {
+    pub const NONE: CausalId = CausalId(0);
+    const COUNTER_BITS: u32 = 48;
+
+    pub fn new(planet: usize, counter: u64) -> Self {
+        Self(((planet as u64) << Self::COUNTER_BITS) | (counter & ((1 << Self::COUNTER_BITS) - 1)))
+    }
+
+    pub fn planet(self) -> usize {
+        (self.0 >> Self::COUNTER_BITS) as usize
+    }
+
+    pub fn counter(self) -> u64 {
+        self.0 & ((1 << Self::COUNTER_BITS) - 1)
+    }
+
+    pub fn is_none(self) -> bool {
+        self == Self::NONE
+    }
+}
+
+/// Numbers each agent's events within a tick, for `EventId`s.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TickSequences {
+    /// `(time + 1, next sequence)` per agent, so a fresh entry never matches a tick
+    last: Vec<(u64, u32)>,
+}
+
+impl TickSequences {
+    pub(crate) fn next(&mut self, agent: usize, time: u64) -> u32 {
+        if agent >= self.last.len() {
+            self.last.resize(agent + 1, (0, 0));
+        }
+        let entry = &mut self.last[agent];
+        if entry.0 != time + 1 {
+            *entry = (time + 1, 0);
+        }
+        entry.1 += 1;
+        entry.1 - 1
+    }
+
+    /// Forget every tick, e.g. after a rollback replays ticks that were already numbered.
+    pub(crate) fn clear(&mut self) {
+        self.last.clear();
+    }
+}
+
+/// Multicast group memberships of the agents on a `World` or `Planet`.
+#[derive(Clone, Debug, Default)]
+pub struct Groups {
+    members: BTreeMap<GroupId, BTreeSet<usize>>,
+}
+
+impl Groups {
+    pub fn join(&mut self, group: GroupId, agent: usize) {
+        self.members.entry(group).or_default().insert(agent);
+    }
+
+    pub fn leave(&mut self, group: GroupId, agent: usize) {
+        if let Some(members) = self.members.get_mut(&group) {
+            members.remove(&agent);
+            if members.is_empty() {
+                self.members.remove(&group);
+            }
+        }
+    }
+
+    /// Members of `group`, in ascending agent order.
+    pub fn members(&self, group: GroupId) -> Vec<usize> {
+        self.members
+            .get(&group)
+            .map(|members| members.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Whether agents `a` and `b` are members of a common group.
+    pub fn share_group(&self, a: usize, b: usize) -> bool {
+        self.members
+            .values()
+            .any(|members| members.contains(&a) && members.contains(&b))
+    }
+
+    /// Drop every membership of `agent`, returning the groups it was in.
+    pub(crate) fn remove_agent(&mut self, agent: usize) -> Vec<GroupId> {
+        let groups = self
+            .members
+            .iter()
+            .filter(|(_, members)| members.contains(&agent))
+            .map(|(group, _)| *group)
+            .collect::<Vec<_>>();
+        for group in &groups {
+            self.leave(*group, agent);
+        }
+        groups
+    }
+
+    /// Move every membership of agents `start..` into a new `Groups`, re-indexed from zero.
+    pub(crate) fn split_off(&mut self, start: usize) -> Groups {
+        let mut moved = Groups::default();
+        for (group, members) in self.members.iter_mut() {
+            for agent in members.split_off(&start) {
+                moved.join(*group, agent - start);
+            }
+        }
+        self.members.retain(|_, members| !members.is_empty());
+        moved
+    }
+
+    /// Take every membership of `other`, its agents re-indexed from `offset`. Groups with the same id are joined.
+    pub(crate) fn append(&mut self, other: Groups, offset: usize) {
+        for (group, members) in other.members {
+            for agent in members {
+                self.join(group, agent + offset);
+            }
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 /// An `AntiMsg` allows you to directly cancel messages with the same metadata in an optimistic execution environment
 pub struct AntiMsg {
@@ -91,6 +358,8 @@ pub struct AntiMsg {
     pub received: u64,
     pub from: usize,
     pub to: Option<usize>,
+    /// `CausalId` of the `Msg` it cancels
+    pub id: CausalId,
 }
 
 impl AntiMsg {
@@ -101,6 +370,7 @@ impl AntiMsg {
             received,
             from,
             to,
+            id: CausalId::NONE,
         }
     }
 
@@ -180,6 +450,24 @@ pub enum Transfer<T: Pod + Zeroable + Clone> {
     AntiMsg(AntiMsg),
 }
 
+impl<T: Pod + Zeroable + Clone> Transfer<T> {
+    /// Push the receive time back to at least `time`.
+    pub(crate) fn defer_to(&mut self, time: u64) {
+        match self {
+            Transfer::Msg(msg) => msg.recv = msg.recv.max(time),
+            Transfer::AntiMsg(anti_msg) => anti_msg.received = anti_msg.received.max(time),
+        }
+    }
+
+    /// Push the receive time back to the next multiple of `rate`.
+    pub(crate) fn align_to(&mut self, rate: u64) {
+        match self {
+            Transfer::Msg(msg) => msg.recv = align(msg.recv, rate),
+            Transfer::AntiMsg(anti_msg) => anti_msg.received = align(anti_msg.received, rate),
+        }
+    }
+}
+
 impl<T: Pod + Zeroable + Clone> Message for Transfer<T> {
     fn to(&self) -> Option<usize> {
         match self {
@@ -247,6 +535,9 @@ pub struct Mail<T: Pod + Zeroable + Clone> {
     pub transfer: Transfer<T>,
     pub to_world: Option<usize>,
     pub from_world: usize,
+    /// states of every planet the sender depended on, stamped when the `Mail` is posted
+    #[cfg(feature = "validate-causality")]
+    pub clock: VectorClock,
 }
 
 impl<T: Pod + Zeroable + Clone> Mail<T> {
@@ -256,6 +547,8 @@ impl<T: Pod + Zeroable + Clone> Mail<T> {
             transfer,
             to_world,
             from_world,
+            #[cfg(feature = "validate-causality")]
+            clock: VectorClock::default(),
         }
     }
     /// Consume to receive a `Transfer`
@@ -277,6 +570,279 @@ impl<T: Pod + Zeroable + Clone> Message for Mail<T> {
 unsafe impl<T: Pod + Zeroable + Clone> Pod for Mail<T> {}
 unsafe impl<T: Pod + Zeroable + Clone> Zeroable for Mail<T> {}
 
+/// A `Msg` the `Galaxy` couldn't deliver because the inbox of its destination `Planet` was full, handed back to the
+/// sending agent through `ThreadedAgent::on_send_failed()`
+#[derive(Debug, Clone, Copy)]
+pub struct DeliveryFailure<T: Pod + Zeroable + Clone> {
+    pub to_world: usize,
+    pub msg: Msg<T>,
+}
+
+unsafe impl<T: Pod + Zeroable + Clone> Send for DeliveryFailure<T> {}
+unsafe impl<T: Pod + Zeroable + Clone> Sync for DeliveryFailure<T> {}
+
+/// One `Msg` for many agents of the same `Planet`, carrying its payload once along with the recipients' indices
+#[derive(Debug, Clone)]
+pub struct Scatter<T: Pod + Zeroable + Clone> {
+    /// the `Msg` every recipient gets, its `to` ignored
+    pub msg: Msg<T>,
+    pub to: Vec<usize>,
+    /// states of every planet the sender depended on, given to each recipient's letter
+    #[cfg(feature = "validate-causality")]
+    pub clock: VectorClock,
+}
+
+unsafe impl<T: Pod + Zeroable + Clone> Send for Scatter<T> {}
+unsafe impl<T: Pod + Zeroable + Clone> Sync for Scatter<T> {}
+
+/// `Mail` from one `Planet` to another carried through the interplanetary messenger as a single transfer
+#[derive(Debug, Clone)]
+pub struct MailBundle<T: Pod + Zeroable + Clone> {
+    pub letters: Vec<Mail<T>>,
+    /// a `Msg` for many recipients, sent with `PlanetContext::send_many()`
+    pub scatter: Option<Scatter<T>>,
+    pub to_world: Option<usize>,
+    pub from_world: usize,
+}
+
+impl<T: Pod + Zeroable + Clone> MailBundle<T> {
+    /// Bundle a single letter.
+    pub fn single(mail: Mail<T>) -> Self {
+        Self {
+            to_world: mail.to_world,
+            from_world: mail.from_world,
+            letters: vec![mail],
+            scatter: None,
+        }
+    }
+
+    /// Bundle a `Scatter` from `from_world` to `to_world`.
+    pub fn scatter(scatter: Scatter<T>, from_world: usize, to_world: usize) -> Self {
+        Self {
+            letters: Vec::new(),
+            scatter: Some(scatter),
+            to_world: Some(to_world),
+            from_world,
+        }
+    }
+
+    /// Units the bundle counts as in flight: one per letter, and one for the whole `Scatter`.
+    pub fn in_flight(&self) -> usize {
+        self.letters.len() + usize::from(self.scatter.is_some())
+    }
+
+    /// Earliest commit time of the bundled letters.
+    pub fn commit_time(&self) -> u64 {
+        self.letters
+            .iter()
+            .map(|mail| mail.transfer.commit_time())
+            .chain(self.scatter.iter().map(|scatter| scatter.msg.commit_time()))
+            .min()
+            .unwrap_or(u64::MAX)
+    }
+
+    /// The bundled letters, followed by a letter for each recipient of the `Scatter`.
+    pub fn into_letters(self) -> Vec<Mail<T>> {
+        let mut letters = self.letters;
+        if let Some(scatter) = self.scatter {
+            letters.extend(scatter.to.into_iter().map(|to| {
+                let msg = Msg {
+                    to: Some(to),
+                    ..scatter.msg
+                };
+                Mail {
+                    transfer: Transfer::Msg(msg),
+                    to_world: self.to_world,
+                    from_world: self.from_world,
+                    #[cfg(feature = "validate-causality")]
+                    clock: scatter.clock,
+                }
+            }));
+        }
+        letters
+    }
+}
+
+impl<T: Pod + Zeroable + Clone> Message for MailBundle<T> {
+    fn to(&self) -> Option<usize> {
+        // the messenger hands a broadcast back to the `Galaxy` through the sender's slot, which fans it out
+        self.to_world.or(Some(self.from_world))
+    }
+
+    fn from(&self) -> usize {
+        self.from_world
+    }
+}
+
+/// Split a continuous time into its tick and the fraction of a tick past it, for `Event::with_offset()` and
+/// `Msg::with_offset()`.
+pub fn split_time(time: f64, timestep: f64) -> (u64, f64) {
+    let ticks = (time / timestep).max(0.0);
+    (ticks.floor() as u64, ticks.fract())
+}
+
+/// Sort a tick's events by their sub-tick offset, keeping the scheduling order of equal offsets.
+pub(crate) fn order_within_tick(events: &mut [Event]) {
+    events.sort_by(|a, b| a.offset.total_cmp(&b.offset));
+}
+
+/// A seeded pseudo-random rank of `key` (SplitMix64), for breaking ties reproducibly.
+pub(crate) fn seeded_rank(seed: u64, key: u64) -> u64 {
+    let mut z = seed ^ key.wrapping_mul(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Sort a tick's events by offset, breaking ties between agents by a seeded rank instead of scheduling order.
+pub(crate) fn order_tick_canonically(events: &mut [Event], seed: u64) {
+    events.sort_by_cached_key(|event| {
+        (
+            event.offset.to_bits(),
+            seeded_rank(seed, event.agent as u64),
+            event.agent,
+        )
+    });
+}
+
+/// Sort a tick's mail by offset, then by a seeded rank of the sender, then by sender and send order, so the
+/// result doesn't depend on arrival order.
+pub(crate) fn order_mail_canonically<T: Clone>(msgs: &mut [Msg<T>], seed: u64) {
+    msgs.sort_by_cached_key(|msg| {
+        let sender = ((msg.from_world as u64) << 32) | msg.from as u64;
+        (
+            msg.offset.to_bits(),
+            seeded_rank(seed, sender),
+            msg.from_world,
+            msg.from,
+            msg.sent,
+            msg.seq,
+            msg.to,
+        )
+    });
+}
+
+/// Build a `Clock` whose wheel indices are aligned to a non-zero starting time.
+pub(crate) fn clock_at<T: Scheduleable, const SLOTS: usize, const HEIGHT: usize>(
+    time: u64,
+) -> Result<Clock<T, SLOTS, HEIGHT>, AikaError> {
+    let mut clock = Clock::new()?;
+    clock.set_time(time);
+    for k in 0..HEIGHT {
+        clock.current_idxs[k] = ((time / (SLOTS as u64).pow(k as u32)) % SLOTS as u64) as usize;
+    }
+    Ok(clock)
+}
+
+/// `delay` steps after `time`, or an `AikaError::TimeOverflow` naming `agent` if that is past `u64::MAX`.
+pub(crate) fn checked_later(agent: usize, time: u64, delay: u64) -> Result<u64, AikaError> {
+    time.checked_add(delay)
+        .ok_or(AikaError::TimeOverflow { agent, time, delay })
+}
+
+/// The first multiple of `rate` at or after `time`, for `Planet`s stepping at a multiple of the base timestep.
+pub(crate) fn align(time: u64, rate: u64) -> u64 {
+    time.div_ceil(rate).saturating_mul(rate)
+}
+
+/// Earliest time of an item on a `Clock` or in its overflow heap. Every item on a level of the wheels is due before
+/// those on the levels above, so only the lowest occupied level is searched.
+pub(crate) fn clock_next_due<T: Scheduleable, const SLOTS: usize, const HEIGHT: usize>(
+    clock: &Clock<T, SLOTS, HEIGHT>,
+    overflow: &BinaryHeap<Reverse<T>>,
+) -> Option<u64> {
+    let wheels = clock
+        .wheels
+        .iter()
+        .find(|level| level.iter().any(|slot| !slot.is_empty()))
+        .and_then(|level| level.iter().flatten().map(Scheduleable::time).min());
+    let heap = overflow.peek().map(|item| item.0.time());
+    wheels.into_iter().chain(heap).min()
+}
+
+/// Move a `Clock` forward to `time` without handing out any item, so nothing pending may be due before `time`. A
+/// short gap is stepped through, a long one rebuilds the wheels at `time`.
+pub(crate) fn skip_clock_to<T: Scheduleable, const SLOTS: usize, const HEIGHT: usize>(
+    clock: &mut Clock<T, SLOTS, HEIGHT>,
+    overflow: &mut BinaryHeap<Reverse<T>>,
+    time: u64,
+) -> Result<(), AikaError> {
+    if time.saturating_sub(clock.time) <= SLOTS as u64 {
+        while clock.time < time {
+            clock.increment(overflow);
+        }
+        return Ok(());
+    }
+    let items = clock
+        .wheels
+        .iter_mut()
+        .flatten()
+        .flat_map(std::mem::take)
+        .collect::<Vec<_>>();
+    *clock = clock_at(time)?;
+    for item in items {
+        if let Err(item) = clock.insert(item) {
+            overflow.push(Reverse(item));
+        }
+    }
+    Ok(())
+}
+
+/// Remove every pending item matching `pred` from a `Clock` and its overflow heap.
+pub(crate) fn drain_matching<T: Scheduleable, const SLOTS: usize, const HEIGHT: usize>(
+    clock: &mut Clock<T, SLOTS, HEIGHT>,
+    overflow: &mut BinaryHeap<Reverse<T>>,
+    pred: impl Fn(&T) -> bool,
+) -> Vec<T> {
+    let mut drained = Vec::new();
+    for wheel in clock.wheels.iter_mut() {
+        for slot in wheel.iter_mut() {
+            let (matching, rest): (Vec<T>, Vec<T>) =
+                std::mem::take(slot).into_iter().partition(&pred);
+            *slot = rest;
+            drained.extend(matching);
+        }
+    }
+    let (matching, rest): (Vec<Reverse<T>>, Vec<Reverse<T>>) = std::mem::take(overflow)
+        .into_iter()
+        .partition(|item| pred(&item.0));
+    *overflow = BinaryHeap::from(rest);
+    drained.extend(matching.into_iter().map(|item| item.0));
+    drained
+}
+
+/// Copy every pending item matching `pred` out of a `Clock` and its overflow heap, sorted by time, leaving both untouched.
+pub(crate) fn pending_matching<T: Scheduleable + Clone, const SLOTS: usize, const HEIGHT: usize>(
+    clock: &Clock<T, SLOTS, HEIGHT>,
+    overflow: &BinaryHeap<Reverse<T>>,
+    pred: impl Fn(&T) -> bool,
+) -> Vec<T> {
+    let mut pending = clock
+        .wheels
+        .iter()
+        .flatten()
+        .flatten()
+        .chain(overflow.iter().map(|item| &item.0))
+        .filter(|item| pred(item))
+        .cloned()
+        .collect::<Vec<_>>();
+    pending.sort_by_key(|item| item.time());
+    pending
+}
+
+/// Sending world, sender, receiver, send time and receive time of a `Msg`, everything an `AntiMsg` matches on.
+type MailKey = (usize, usize, Option<usize>, u64, u64);
+
+/// Pending `Msg`s with the same `MailKey`.
+#[derive(Copy, Clone, Debug, Default)]
+struct MailCount {
+    live: usize,
+    /// annihilated, but still on the wheels until they come due
+    cancelled: usize,
+}
+
+/// Pending mail on timing wheels, indexed by `MailKey` so an `AntiMsg` is matched without searching the wheels.
+/// Annihilated `Msg`s stay where they are and are dropped as they leave, whether they come due or are drained.
 pub(crate) struct LocalMailSystem<
     const CLOCK_SLOTS: usize,
     const CLOCK_HEIGHT: usize,
@@ -284,6 +850,7 @@ pub(crate) struct LocalMailSystem<
 > {
     pub(crate) overflow: BinaryHeap<Reverse<Msg<MessageType>>>,
     pub(crate) schedule: Clock<Msg<MessageType>, CLOCK_SLOTS, CLOCK_HEIGHT>,
+    index: HashMap<MailKey, MailCount>,
 }
 
 impl<const CLOCK_SLOTS: usize, const CLOCK_HEIGHT: usize, MessageType: Clone>
@@ -292,7 +859,112 @@ impl<const CLOCK_SLOTS: usize, const CLOCK_HEIGHT: usize, MessageType: Clone>
     pub(crate) fn new() -> Result<Self, AikaError> {
         let overflow = BinaryHeap::new();
         let schedule = Clock::new()?;
-        Ok(Self { overflow, schedule })
+        Ok(Self {
+            overflow,
+            schedule,
+            index: HashMap::new(),
+        })
+    }
+
+    fn key(msg: &Msg<MessageType>) -> MailKey {
+        (msg.from_world, msg.from, msg.to, msg.sent, msg.recv)
+    }
+
+    /// Drop every pending `Msg` and restart the wheels at `time`.
+    pub(crate) fn reset(&mut self, time: u64) -> Result<(), AikaError> {
+        self.schedule = clock_at(time)?;
+        self.overflow.clear();
+        self.index.clear();
+        Ok(())
+    }
+
+    /// Number of `Msg`s on the wheels and in the overflow, including annihilated ones not yet due.
+    pub(crate) fn len(&self) -> usize {
+        self.index
+            .values()
+            .map(|count| count.live + count.cancelled)
+            .sum()
+    }
+
+    pub(crate) fn insert(&mut self, msg: Msg<MessageType>) {
+        self.index.entry(Self::key(&msg)).or_default().live += 1;
+        if let Err(msg) = self.schedule.insert(msg) {
+            self.overflow.push(Reverse(msg));
+        }
+    }
+
+    /// Annihilate every pending `Msg` from `from_world` matching `anti_msg`, returning how many there were.
+    pub(crate) fn annihilate(&mut self, anti_msg: &AntiMsg, from_world: usize) -> usize {
+        let key = (
+            from_world,
+            anti_msg.from,
+            anti_msg.to,
+            anti_msg.sent,
+            anti_msg.received,
+        );
+        let Some(count) = self.index.get_mut(&key) else {
+            return 0;
+        };
+        let removed = std::mem::take(&mut count.live);
+        count.cancelled += removed;
+        removed
+    }
+
+    /// Keep the `Msg`s of `msgs`, which just left the wheels, that weren't annihilated.
+    fn settle(&mut self, msgs: Vec<Msg<MessageType>>) -> Vec<Msg<MessageType>> {
+        if self.index.is_empty() {
+            return msgs;
+        }
+        msgs.into_iter()
+            .filter(|msg| {
+                let key = Self::key(msg);
+                let Some(count) = self.index.get_mut(&key) else {
+                    return true;
+                };
+                let keep = count.cancelled == 0;
+                match keep {
+                    true => count.live = count.live.saturating_sub(1),
+                    false => count.cancelled -= 1,
+                }
+                if count.live == 0 && count.cancelled == 0 {
+                    self.index.remove(&key);
+                }
+                keep
+            })
+            .collect()
+    }
+
+    /// Take the mail of the current step.
+    pub(crate) fn tick(&mut self) -> Result<Vec<Msg<MessageType>>, AikaError> {
+        let msgs = self.schedule.tick()?;
+        Ok(self.settle(msgs))
+    }
+
+    /// Remove every pending `Msg` matching `pred`.
+    pub(crate) fn drain(
+        &mut self,
+        pred: impl Fn(&Msg<MessageType>) -> bool,
+    ) -> Vec<Msg<MessageType>> {
+        let drained = drain_matching(&mut self.schedule, &mut self.overflow, pred);
+        self.settle(drained)
+    }
+
+    /// Copy every pending `Msg` matching `pred`, sorted by time.
+    pub(crate) fn pending(
+        &self,
+        pred: impl Fn(&Msg<MessageType>) -> bool,
+    ) -> Vec<Msg<MessageType>> {
+        let mut skip = BTreeMap::new();
+        pending_matching(&self.schedule, &self.overflow, pred)
+            .into_iter()
+            .filter(|msg| {
+                let key = Self::key(msg);
+                let cancelled = self.index.get(&key).map_or(0, |count| count.cancelled);
+                let skipped = skip.entry(key).or_insert(0);
+                *skipped += 1;
+                *skipped > cancelled
+            })
+            .collect()
     }
 }
 
@@ -309,12 +981,39 @@ unsafe impl<const CLOCK_SLOTS: usize, const CLOCK_HEIGHT: usize, MessageType: Cl
 #[derive(Copy, Clone, Debug)]
 pub enum Action {
     Timeout(u64),
+    /// time out like `Timeout(delay)`, or wake early when mail arrives, see `agents::interrupts`
+    Interruptible(u64),
     Schedule(u64),
-    Trigger { time: u64, idx: usize },
+    Trigger {
+        time: u64,
+        idx: usize,
+    },
+    /// take a unit of a resource, waking on the tick after it is granted, see `agents::resources`
+    Acquire {
+        resource: usize,
+        priority: u64,
+    },
+    /// hand back a unit of a resource, then time out like `Timeout(delay)` if a delay is given, or wait
+    Release {
+        resource: usize,
+        delay: Option<u64>,
+    },
     Wait,
     Break,
 }
 
+impl Action {
+    /// Wake again `delay` after the current tick.
+    pub fn after(delay: SimDuration) -> Self {
+        Action::Timeout(delay.ticks())
+    }
+
+    /// Wake again at `time`.
+    pub fn at(time: SimTime) -> Self {
+        Action::Schedule(time.ticks())
+    }
+}
+
 /// An event that can be scheduled in a simulation. This is used to trigger an agent, or schedule another event.
 #[derive(Copy, Clone, Debug)]
 #[repr(C)]
@@ -323,26 +1022,49 @@ pub struct Event {
     pub commit_time: u64,
     pub agent: usize,
     pub yield_: Action,
+    /// fraction of a tick in `[0, 1)` at which the event happens, ordering events within the `time` tick. On an
+    /// `Event` returned from `step()`, it is the offset of the wake-up scheduled by `yield_`.
+    pub offset: f64,
+    /// id given by the `Planet`'s `CausalityLog` when the event was scheduled, if it keeps one
+    pub id: CausalId,
 }
 
 impl Event {
-    pub fn new(commit_time: u64, time: u64, agent: usize, yield_: Action) -> Self {
+    pub fn new(
+        commit_time: impl Into<SimTime>,
+        time: impl Into<SimTime>,
+        agent: usize,
+        yield_: Action,
+    ) -> Self {
         Self {
-            commit_time,
-            time,
+            commit_time: commit_time.into().ticks(),
+            time: time.into().ticks(),
             agent,
             yield_,
+            offset: 0.0,
+            id: CausalId::NONE,
         }
     }
 
+    /// Happen `offset` of a tick after the start of `time`.
+    pub fn with_offset(mut self, offset: f64) -> Self {
+        self.offset = offset;
+        self
+    }
+
     pub fn time(&self) -> u64 {
         self.time
     }
+
+    /// The tick the event is due at, as a `SimTime`.
+    pub fn due_at(&self) -> SimTime {
+        SimTime(self.time)
+    }
 }
 
 impl PartialEq for Event {
     fn eq(&self, other: &Self) -> bool {
-        self.time == other.time
+        self.time == other.time && self.offset == other.offset
     }
 }
 impl Eq for Event {}
@@ -354,7 +1076,9 @@ impl PartialOrd for Event {
 }
 impl Ord for Event {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.time.partial_cmp(&other.time).unwrap()
+        self.time
+            .cmp(&other.time)
+            .then_with(|| self.offset.total_cmp(&other.offset))
     }
 }
 
@@ -374,28 +1098,243 @@ unsafe impl Send for Event {}
 unsafe impl Sync for Event {}
 
 pub(crate) struct LocalEventSystem<const CLOCK_SLOTS: usize, const CLOCK_HEIGHT: usize> {
-    pub(crate) overflow: BinaryHeap<Reverse<Event>>,
+    pub(crate) overflow: OverflowQueue,
     pub(crate) local_clock: Clock<Event, CLOCK_SLOTS, CLOCK_HEIGHT>,
+    /// wheels sized at runtime, used instead of `local_clock` when set
+    pub(crate) dynamic: Option<DynClock<Event>>,
+    pub(crate) spill: SpillStore,
 }
 
 impl<const CLOCK_SLOTS: usize, const CLOCK_HEIGHT: usize>
     LocalEventSystem<CLOCK_SLOTS, CLOCK_HEIGHT>
 {
     pub(crate) fn new() -> Result<Self, AikaError> {
-        let overflow = BinaryHeap::new();
+        let overflow = OverflowQueue::new((CLOCK_SLOTS as u64).saturating_pow(CLOCK_HEIGHT as u32));
         let local_clock = Clock::new()?;
         Ok(Self {
             overflow,
             local_clock,
+            dynamic: None,
+            spill: SpillStore::default(),
         })
     }
 
-    pub(crate) fn insert(&mut self, event: Event) {
-        let possible_overflow = self.local_clock.insert(event);
-        if possible_overflow.is_err() {
-            let event = possible_overflow.err().unwrap();
-            self.overflow.push(Reverse(event));
+    /// Schedule on a `DynClock` of `sizing` instead of the const generic wheels. Call it before inserting anything.
+    pub(crate) fn set_dynamic_clock(&mut self, sizing: ClockSizing) -> Result<(), AikaError> {
+        self.dynamic = Some(DynClock::new(sizing, self.local_clock.time)?);
+        self.overflow
+            .set_width(sizing.slots.saturating_pow(sizing.height as u32));
+        Ok(())
+    }
+
+    /// Replace the wheels with `clock`, restarting a `DynClock` at its time.
+    pub(crate) fn set_clock(&mut self, clock: Clock<Event, CLOCK_SLOTS, CLOCK_HEIGHT>) {
+        if let Some(dynamic) = &mut self.dynamic {
+            dynamic.reset(clock.time);
+        }
+        self.local_clock = clock;
+    }
+
+    pub(crate) fn time(&self) -> u64 {
+        match &self.dynamic {
+            Some(clock) => clock.time,
+            None => self.local_clock.time,
+        }
+    }
+
+    /// Take the events of the current step.
+    pub(crate) fn tick(&mut self) -> Result<Vec<Event>, AikaError> {
+        match &mut self.dynamic {
+            Some(clock) => clock.tick(),
+            None => Ok(self.local_clock.tick()?),
+        }
+    }
+
+    /// Number of steps ahead of the clock's time that fit on the wheels.
+    fn horizon() -> u64 {
+        let slots = CLOCK_SLOTS as u64;
+        (slots.saturating_pow(1 + CLOCK_HEIGHT as u32) - slots) / slots.saturating_sub(1).max(1)
+    }
+
+    /// First time that doesn't fit on the wheels.
+    fn until(&self) -> u64 {
+        match &self.dynamic {
+            Some(clock) => clock.until(),
+            None => self.local_clock.time.saturating_add(Self::horizon()),
+        }
+    }
+
+    fn fits(&self, event: &Event) -> bool {
+        event.time < self.until()
+    }
+
+    fn place(&mut self, event: Event) -> Result<(), Event> {
+        match &mut self.dynamic {
+            Some(clock) => clock.insert(event),
+            None => self.local_clock.insert(event),
+        }
+    }
+
+    /// Move the overflow windows that fit onto the wheels, reading spilled runs back first if they do.
+    fn refill(&mut self) -> Result<(), AikaError> {
+        let until = self.until();
+        self.spill.reload(&mut self.overflow, until)?;
+        for event in self.overflow.take_until(until) {
+            let _ = self.place(event);
+        }
+        Ok(())
+    }
+
+    /// Queue whatever a `Clock` handed back while rotating or rebuilding its wheels.
+    fn absorb(&mut self, handed_back: BinaryHeap<Reverse<Event>>) {
+        self.overflow
+            .extend(handed_back.into_iter().map(|event| event.0));
+    }
+
+    /// Grow a `DynClock` whose overflow queue holds more events than one of its wheels.
+    fn relieve(&mut self) -> Result<(), AikaError> {
+        let Some(clock) = &mut self.dynamic else {
+            return Ok(());
+        };
+        if self.overflow.len() as u64 <= clock.sizing().slots || !clock.grow() {
+            return Ok(());
+        }
+        self.refill()?;
+        self.relieve()
+    }
+
+    pub(crate) fn set_overflow_strategy(&mut self, strategy: OverflowStrategy) {
+        self.spill = SpillStore::new(strategy);
+    }
+
+    /// Events on each level of the wheels, lowest first, and past them.
+    pub(crate) fn occupancy(&self) -> (Vec<u64>, u64) {
+        let levels = match &self.dynamic {
+            Some(clock) => clock.occupancy(),
+            None => self
+                .local_clock
+                .wheels
+                .iter()
+                .map(|level| level.iter().map(|slot| slot.len() as u64).sum())
+                .collect(),
+        };
+        let stats = self.spill.stats(&self.overflow);
+        (levels, (stats.in_memory + stats.spilled) as u64)
+    }
+
+    pub(crate) fn overflow_stats(&self) -> OverflowStats {
+        OverflowStats {
+            grown: self.dynamic.as_ref().map_or(0, |clock| clock.grown()),
+            ..self.spill.stats(&self.overflow)
+        }
+    }
+
+    pub(crate) fn insert(&mut self, event: Event) -> Result<(), AikaError> {
+        if let Err(event) = self.place(event) {
+            self.spill.admit(&self.overflow, &[event])?;
+            self.overflow.push(event);
+            self.spill.pushed(&mut self.overflow)?;
+            self.relieve()?;
+        }
+        Ok(())
+    }
+
+    /// Check that `insert_batch` would take all of `events`, without inserting any.
+    pub(crate) fn admits(&self, events: &[Event]) -> Result<(), AikaError> {
+        let mut far = events.iter().filter(|event| !self.fits(event));
+        if let Some(first) = far.next() {
+            if self.spill.refuses(&self.overflow, far.count() + 1) {
+                return Err(AikaError::OverflowFull(first.agent, first.time));
+            }
+        }
+        Ok(())
+    }
+
+    /// Insert a batch of events, collecting anything beyond the clock horizon and pushing it to the overflow queue in one pass.
+    /// Under back-pressure the batch is refused as a whole.
+    pub(crate) fn insert_batch(
+        &mut self,
+        events: impl IntoIterator<Item = Event>,
+    ) -> Result<(), AikaError> {
+        let (near, far): (Vec<_>, Vec<_>) = events.into_iter().partition(|event| self.fits(event));
+        self.spill.admit(&self.overflow, &far)?;
+        for event in near {
+            let _ = self.place(event);
+        }
+        if !far.is_empty() {
+            self.overflow.extend(far);
+            self.spill.pushed(&mut self.overflow)?;
+            self.relieve()?;
+        }
+        Ok(())
+    }
+
+    /// Advance the clock one step, moving overflow events that now fit back onto the wheels.
+    pub(crate) fn increment(&mut self) -> Result<(), AikaError> {
+        match &mut self.dynamic {
+            Some(clock) => clock.increment(),
+            None => {
+                let mut handed_back = BinaryHeap::new();
+                self.local_clock.increment(&mut handed_back);
+                self.absorb(handed_back);
+            }
+        }
+        self.refill()
+    }
+
+    /// Time of the earliest pending event, spilled ones included.
+    pub(crate) fn next_due(&self) -> Option<u64> {
+        let due = match &self.dynamic {
+            Some(clock) => clock.next_due(),
+            None => clock_next_due(&self.local_clock, &BinaryHeap::new()),
+        };
+        due.into_iter()
+            .chain(self.overflow.first())
+            .chain(self.spill.first())
+            .min()
+    }
+
+    /// Move the clock forward to `time`, which no pending event may precede, skipping the steps in between.
+    pub(crate) fn skip_to(&mut self, time: u64) -> Result<(), AikaError> {
+        match &mut self.dynamic {
+            Some(clock) => clock.skip_to(time),
+            None => {
+                let mut handed_back = BinaryHeap::new();
+                skip_clock_to(&mut self.local_clock, &mut handed_back, time)?;
+                self.absorb(handed_back);
+            }
+        }
+        self.refill()?;
+        self.relieve()
+    }
+
+    /// Copy every pending event matching `pred`, spilled ones included, sorted by time.
+    pub(crate) fn pending(&self, pred: impl Fn(&Event) -> bool) -> Result<Vec<Event>, AikaError> {
+        let mut pending = pending_matching(&self.local_clock, &BinaryHeap::new(), &pred);
+        let spilled = self.spill.spilled()?;
+        let dynamic = self.dynamic.iter().flat_map(|clock| clock.iter());
+        let extra = spilled
+            .into_iter()
+            .chain(self.overflow.iter().copied())
+            .chain(dynamic.copied())
+            .filter(&pred)
+            .collect::<Vec<_>>();
+        if !extra.is_empty() {
+            pending.extend(extra);
+            pending.sort_by_key(|event| event.time);
+        }
+        Ok(pending)
+    }
+
+    /// Remove every pending event matching `pred`, spilled ones included.
+    pub(crate) fn drain(&mut self, pred: impl Fn(&Event) -> bool) -> Result<Vec<Event>, AikaError> {
+        self.spill.reload(&mut self.overflow, u64::MAX)?;
+        let mut drained = drain_matching(&mut self.local_clock, &mut BinaryHeap::new(), &pred);
+        drained.extend(self.overflow.drain_matching(&pred));
+        if let Some(clock) = &mut self.dynamic {
+            drained.extend(clock.drain(&pred));
         }
+        Ok(drained)
     }
 }
 
@@ -0,0 +1,430 @@
+//! Bounded growth for the overflow queue behind each timing wheel.
+//! Events scheduled past the wheel horizon wait in an `OverflowQueue` until the clock catches up to them. The queue
+//! is a calendar of windows as wide as the span of the wheels, so an insert costs `O(log n)` in the number of occupied
+//! windows and a window is moved onto the wheels in one batch once it lies wholly within their horizon, rather than
+//! popping events off a heap one at a time as the horizon slides past them. Under adversarial scheduling the queue
+//! can grow without bound, so an `OverflowStrategy` caps it: `Spill` writes the
+//! farthest events to disk as sorted runs and reads a run back once the clock gets near its first event, while
+//! `BackPressure` refuses new far-future events with `AikaError::OverflowFull`.
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use bytemuck::Zeroable;
+
+use crate::{objects::Event, AikaError};
+
+static SPILL_STORES: AtomicU64 = AtomicU64::new(0);
+
+/// How an overflow queue may grow.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum OverflowStrategy {
+    #[default]
+    Unbounded,
+    /// Keep at most `limit` events in memory, writing the farthest ones to sorted run files under `dir`.
+    Spill { limit: usize, dir: PathBuf },
+    /// Refuse events that would grow the queue past `limit` events, with `AikaError::OverflowFull`.
+    BackPressure { limit: usize },
+}
+
+/// Occupancy of an overflow queue and its spilled runs.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct OverflowStats {
+    /// events waiting in memory
+    pub in_memory: usize,
+    /// events waiting on disk
+    pub spilled: usize,
+    /// most events ever waiting in memory at once
+    pub peak_in_memory: usize,
+    pub runs_written: u64,
+    /// events refused under `BackPressure`
+    pub refused: u64,
+    /// levels a `DynClock` grew by to take events off the heap
+    pub grown: u64,
+}
+
+/// The events of one window of an `OverflowQueue`, in the order they were pushed.
+#[derive(Clone, Debug)]
+struct Window {
+    first: u64,
+    events: Vec<Event>,
+}
+
+/// Events past the wheel horizon, bucketed by the window of `width` ticks they fall due in.
+#[derive(Clone, Debug)]
+pub(crate) struct OverflowQueue {
+    width: u64,
+    windows: BTreeMap<u64, Window>,
+    len: usize,
+    /// end of the earliest window, so a tick with nothing to move costs one comparison
+    ready: u64,
+}
+
+impl OverflowQueue {
+    /// A queue of windows `width` ticks wide. Windows must be no wider than the horizon of the wheels they feed, and
+    /// for wheels whose horizon moves in whole spans, like a `DynClock`'s, must divide that span.
+    pub(crate) fn new(width: u64) -> Self {
+        Self {
+            width: width.max(1),
+            windows: BTreeMap::new(),
+            len: 0,
+            ready: u64::MAX,
+        }
+    }
+
+    fn end(&self, window: u64) -> u64 {
+        window.saturating_add(1).saturating_mul(self.width)
+    }
+
+    fn update_ready(&mut self) {
+        self.ready = self
+            .windows
+            .first_key_value()
+            .map_or(u64::MAX, |(window, _)| self.end(*window));
+    }
+
+    /// Re-bucket every event into windows `width` ticks wide.
+    pub(crate) fn set_width(&mut self, width: u64) {
+        let events = self.take_all();
+        self.width = width.max(1);
+        self.extend(events);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn push(&mut self, event: Event) {
+        let key = event.time / self.width;
+        self.ready = self.ready.min(self.end(key));
+        let window = self.windows.entry(key).or_insert(Window {
+            first: event.time,
+            events: Vec::new(),
+        });
+        window.first = window.first.min(event.time);
+        window.events.push(event);
+        self.len += 1;
+    }
+
+    pub(crate) fn extend(&mut self, events: impl IntoIterator<Item = Event>) {
+        for event in events {
+            self.push(event);
+        }
+    }
+
+    /// Time of the earliest event.
+    pub(crate) fn first(&self) -> Option<u64> {
+        self.windows
+            .first_key_value()
+            .map(|(_, window)| window.first)
+    }
+
+    /// Take every window lying wholly before `until`, earliest first.
+    pub(crate) fn take_until(&mut self, until: u64) -> Vec<Event> {
+        let mut due = Vec::new();
+        while self.ready <= until || (until == u64::MAX && !self.windows.is_empty()) {
+            let Some((_, window)) = self.windows.pop_first() else {
+                break;
+            };
+            self.len -= window.events.len();
+            due.extend(window.events);
+            self.update_ready();
+        }
+        due
+    }
+
+    /// Take every event, sorted by time.
+    pub(crate) fn take_all(&mut self) -> Vec<Event> {
+        self.len = 0;
+        self.ready = u64::MAX;
+        let mut events = std::mem::take(&mut self.windows)
+            .into_values()
+            .flat_map(|window| window.events)
+            .collect::<Vec<_>>();
+        events.sort_by_key(|event| event.time);
+        events
+    }
+
+    /// Keep the `keep` earliest events and take the rest, sorted by time.
+    pub(crate) fn split_off_far(&mut self, keep: usize) -> Vec<Event> {
+        let mut events = self.take_all();
+        let far = events.split_off(keep.min(events.len()));
+        self.extend(events);
+        far
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Event> {
+        self.windows.values().flat_map(|window| &window.events)
+    }
+
+    /// Remove every event matching `pred`.
+    pub(crate) fn drain_matching(&mut self, pred: impl Fn(&Event) -> bool) -> Vec<Event> {
+        let mut drained = Vec::new();
+        self.windows.retain(|_, window| {
+            let (matching, rest) = std::mem::take(&mut window.events)
+                .into_iter()
+                .partition::<Vec<_>, _>(&pred);
+            drained.extend(matching);
+            window.first = rest
+                .iter()
+                .map(|event| event.time)
+                .min()
+                .unwrap_or(u64::MAX);
+            window.events = rest;
+            !window.events.is_empty()
+        });
+        self.len -= drained.len();
+        self.update_ready();
+        drained
+    }
+}
+
+/// A sorted run of events on disk, starting at `first`.
+struct SpillRun {
+    path: PathBuf,
+    first: u64,
+    len: usize,
+}
+
+/// Applies an `OverflowStrategy` to an `OverflowQueue`.
+#[derive(Default)]
+pub(crate) struct SpillStore {
+    strategy: OverflowStrategy,
+    id: u64,
+    runs: Vec<SpillRun>,
+    stats: OverflowStats,
+}
+
+impl SpillStore {
+    pub(crate) fn new(strategy: OverflowStrategy) -> Self {
+        Self {
+            strategy,
+            id: SPILL_STORES.fetch_add(1, Ordering::Relaxed),
+            runs: Vec::new(),
+            stats: OverflowStats::default(),
+        }
+    }
+
+    pub(crate) fn strategy(&self) -> &OverflowStrategy {
+        &self.strategy
+    }
+
+    pub(crate) fn stats(&self, queue: &OverflowQueue) -> OverflowStats {
+        OverflowStats {
+            in_memory: queue.len(),
+            spilled: self.runs.iter().map(|run| run.len).sum(),
+            ..self.stats
+        }
+    }
+
+    /// Check that `incoming` more events may be pushed onto `queue`, refusing the first of them under `BackPressure`.
+    pub(crate) fn admit(
+        &mut self,
+        queue: &OverflowQueue,
+        incoming: &[Event],
+    ) -> Result<(), AikaError> {
+        if let Some(first) = incoming.first() {
+            if self.refuses(queue, incoming.len()) {
+                self.stats.refused += incoming.len() as u64;
+                return Err(AikaError::OverflowFull(first.agent, first.time));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `BackPressure` would refuse `incoming` more events on top of `queue`.
+    pub(crate) fn refuses(&self, queue: &OverflowQueue, incoming: usize) -> bool {
+        match self.strategy {
+            OverflowStrategy::BackPressure { limit } => queue.len() + incoming > limit,
+            _ => false,
+        }
+    }
+
+    /// Record the queue's size after a push, spilling its farthest half to disk if it is over the limit.
+    pub(crate) fn pushed(&mut self, queue: &mut OverflowQueue) -> Result<(), AikaError> {
+        self.stats.peak_in_memory = self.stats.peak_in_memory.max(queue.len());
+        let OverflowStrategy::Spill { limit, dir } = &self.strategy else {
+            return Ok(());
+        };
+        if queue.len() <= *limit {
+            return Ok(());
+        }
+        let far = queue.split_off_far(*limit / 2);
+
+        let path = dir.join(format!(
+            "aika-overflow-{}-{}-{}.bin",
+            std::process::id(),
+            self.id,
+            self.stats.runs_written
+        ));
+        std::fs::write(&path, bytemuck::cast_slice::<Event, u8>(&far))
+            .map_err(|err| AikaError::OverflowSpill(err.to_string()))?;
+        self.runs.push(SpillRun {
+            path,
+            first: far[0].time,
+            len: far.len(),
+        });
+        self.stats.runs_written += 1;
+        Ok(())
+    }
+
+    /// Move every run starting before `until` back into `queue`.
+    pub(crate) fn reload(
+        &mut self,
+        queue: &mut OverflowQueue,
+        until: u64,
+    ) -> Result<(), AikaError> {
+        if self.runs.iter().all(|run| run.first >= until) {
+            return Ok(());
+        }
+        let (due, rest) = std::mem::take(&mut self.runs)
+            .into_iter()
+            .partition::<Vec<_>, _>(|run| run.first < until);
+        self.runs = rest;
+        for run in due {
+            queue.extend(read_run(&run)?);
+            let _ = std::fs::remove_file(&run.path);
+        }
+        self.stats.peak_in_memory = self.stats.peak_in_memory.max(queue.len());
+        Ok(())
+    }
+
+    /// Time of the earliest spilled event.
+    pub(crate) fn first(&self) -> Option<u64> {
+        self.runs.iter().map(|run| run.first).min()
+    }
+
+    /// Copy every spilled event, leaving the runs on disk.
+    pub(crate) fn spilled(&self) -> Result<Vec<Event>, AikaError> {
+        let mut events = Vec::new();
+        for run in &self.runs {
+            events.extend(read_run(run)?);
+        }
+        Ok(events)
+    }
+}
+
+impl Drop for SpillStore {
+    fn drop(&mut self) {
+        for run in &self.runs {
+            let _ = std::fs::remove_file(&run.path);
+        }
+    }
+}
+
+fn read_run(run: &SpillRun) -> Result<Vec<Event>, AikaError> {
+    let bytes =
+        std::fs::read(&run.path).map_err(|err| AikaError::OverflowSpill(err.to_string()))?;
+    let mut events = vec![Event::zeroed(); bytes.len() / std::mem::size_of::<Event>()];
+    bytemuck::cast_slice_mut::<Event, u8>(&mut events).copy_from_slice(&bytes);
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::{
+        agents::{Agent, WorldContext},
+        objects::{Action, Msg},
+        st::World,
+    };
+
+    struct Recorder {
+        steps: Rc<RefCell<Vec<u64>>>,
+    }
+
+    impl Agent<8, Msg<u8>> for Recorder {
+        fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+            self.steps.borrow_mut().push(context.time);
+            Event::new(context.time, context.time, id, Action::Wait)
+        }
+    }
+
+    fn world(strategy: OverflowStrategy) -> (World<8, 16, 1, u8>, Rc<RefCell<Vec<u64>>>) {
+        let steps = Rc::new(RefCell::new(Vec::new()));
+        let mut world = World::<8, 16, 1, u8>::init(200.0, 1.0, 0).unwrap();
+        world.spawn_agent(Box::new(Recorder {
+            steps: Rc::clone(&steps),
+        }));
+        world.init_support_layers(None).unwrap();
+        world.set_overflow_strategy(strategy);
+        (world, steps)
+    }
+
+    #[test]
+    fn test_spilled_events_come_back_in_order() {
+        let dir = std::env::temp_dir();
+        let (mut world, steps) = world(OverflowStrategy::Spill { limit: 4, dir });
+        let times = (0..12).map(|i| 150 - i * 10).collect::<Vec<_>>();
+        for &time in &times {
+            world.schedule(time, 0).unwrap();
+        }
+        let stats = world.overflow_stats();
+        assert!(stats.runs_written >= 2);
+        assert!(stats.in_memory <= 4);
+        assert_eq!(stats.in_memory + stats.spilled, 12);
+        assert_eq!(world.pending_events(0, 200).unwrap().len(), 12);
+
+        world.run().unwrap();
+        let mut expected = times;
+        expected.sort();
+        assert_eq!(*steps.borrow(), expected);
+        let stats = world.overflow_stats();
+        assert_eq!((stats.in_memory, stats.spilled), (0, 0));
+    }
+
+    #[test]
+    fn test_windows_leave_whole() {
+        let mut queue = OverflowQueue::new(8);
+        for time in [30, 9, 17, 8, 16, 40] {
+            queue.push(Event::new(0, time, 0, Action::Wait));
+        }
+        assert_eq!(queue.first(), Some(8));
+        // the window [16, 24) only partly fits before 20 and stays queued
+        let times = |events: Vec<Event>| events.iter().map(|event| event.time).collect::<Vec<_>>();
+        assert_eq!(times(queue.take_until(20)), [9, 8]);
+        assert_eq!(queue.first(), Some(16));
+        assert_eq!(times(queue.drain_matching(|event| event.time == 17)), [17]);
+        assert_eq!(times(queue.split_off_far(1)), [30, 40]);
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn test_far_future_events_run_in_order() {
+        let (mut world, steps) = world(OverflowStrategy::Unbounded);
+        let times = (0..60).map(|i| 190 - (i * 37) % 180).collect::<Vec<_>>();
+        world
+            .schedule_many(&times.iter().map(|&time| (0, time)).collect::<Vec<_>>())
+            .unwrap();
+        assert!(world.overflow_stats().in_memory > 0);
+        world.run().unwrap();
+        let mut expected = times;
+        expected.sort();
+        assert_eq!(*steps.borrow(), expected);
+    }
+
+    #[test]
+    fn test_back_pressure_refuses_far_events() {
+        let (mut world, steps) = world(OverflowStrategy::BackPressure { limit: 2 });
+        world.schedule(40, 0).unwrap();
+        world.schedule(50, 0).unwrap();
+        assert!(matches!(
+            world.schedule(60, 0),
+            Err(AikaError::OverflowFull(0, 60))
+        ));
+        // events on the wheels are unaffected
+        world.schedule(5, 0).unwrap();
+        assert!(matches!(
+            world.schedule_many(&[(0, 6), (0, 70)]),
+            Err(AikaError::OverflowFull(0, 70))
+        ));
+        assert_eq!(world.overflow_stats().refused, 2);
+
+        world.run().unwrap();
+        assert_eq!(*steps.borrow(), vec![5, 40, 50]);
+    }
+}
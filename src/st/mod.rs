@@ -1,19 +1,114 @@
 //! Single-threaded simulation world supporting multiple agents with message passing capabilities.
 //! Provides a `World` struct that manages agent execution, event scheduling, and local message
 //! delivery in a deterministic single-threaded environment with configurable time bounds.
-use mesocarp::comms::mailbox::ThreadedMessenger;
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    ops::RangeBounds,
+    time::{Duration, Instant},
+};
+
+use bytemuck::{Pod, Zeroable};
+use mesocarp::{
+    comms::mailbox::{Message, ThreadedMessenger},
+    MesoError,
+};
 
 use crate::{
-    agents::{Agent, AgentSupport, WorldContext},
-    objects::{Action, Event, LocalEventSystem, Msg},
+    agents::{
+        discovery::Query,
+        interrupts::Wake,
+        resources::{QueueDiscipline, Resources},
+        subscriptions::NotifyAt,
+        Agent, AgentSupport, WorldContext,
+    },
+    analysis::{
+        critical_path::{CausalLog, CausalNode, CriticalPath},
+        history::journal_history,
+        kpi::KpiRecorder,
+        occupancy::OccupancyRecorder,
+    },
+    extensions::WorldExtension,
+    hooks::SimHook,
+    mailbox::{Backlog, MailboxStats, OverflowPolicy},
+    objects::{
+        checked_later, order_within_tick, Action, Event, LocalEventSystem, Msg, TickSequences,
+    },
+    overflow::{OverflowStats, OverflowStrategy},
+    testing::{Address, MessageLedger},
+    time::{SimTime, TimeScale},
     AikaError,
 };
 
+pub mod sequential;
+
 pub(crate) struct TimeInfo {
     pub timestep: f64,
     pub terminal: f64,
+    /// first step `t` with `t * timestep > terminal`
+    past_step: u64,
+    /// first step `t` with `t * timestep >= terminal`
+    terminal_step: u64,
 }
 
+impl TimeInfo {
+    /// Bounds of a run to `terminal` in steps of `timestep`, with the terminal steps worked out once so the hot paths
+    /// compare integers instead of converting every step to `f64`.
+    pub(crate) fn new(terminal: f64, timestep: f64) -> Self {
+        Self {
+            timestep,
+            terminal,
+            past_step: first_step(terminal, timestep, |t| t as f64 * timestep > terminal),
+            terminal_step: first_step(terminal, timestep, |t| t as f64 * timestep >= terminal),
+        }
+    }
+
+    /// Whether `step` lies past the terminal time, where nothing is scheduled.
+    #[inline(always)]
+    pub(crate) fn past(&self, step: u64) -> bool {
+        step >= self.past_step
+    }
+
+    /// Whether `step` lies at or past the terminal time, where nothing is processed.
+    #[inline(always)]
+    pub(crate) fn reached(&self, step: u64) -> bool {
+        step >= self.terminal_step
+    }
+
+    /// First step at or past the terminal time.
+    pub(crate) fn terminal_step(&self) -> u64 {
+        self.terminal_step
+    }
+
+    /// First step that can't be processed, the one after the last step not past the terminal time.
+    pub(crate) fn end(&self) -> u64 {
+        self.past_step.saturating_sub(1)
+    }
+}
+
+/// First step from 0 on for which the monotone `past` holds, `u64::MAX` if none does. The estimate
+/// `terminal / timestep` lands within a step or two of it, off only by rounding.
+fn first_step(terminal: f64, timestep: f64, past: impl Fn(u64) -> bool) -> u64 {
+    let estimate = terminal / timestep;
+    if timestep <= 0.0 || !estimate.is_finite() {
+        return if past(0) { 0 } else { u64::MAX };
+    }
+    let mut step = estimate.clamp(0.0, u64::MAX as f64) as u64;
+    while step > 0 && past(step - 1) {
+        step -= 1;
+    }
+    while !past(step) {
+        if step == u64::MAX {
+            return u64::MAX;
+        }
+        step += 1;
+    }
+    step
+}
+
+/// `(agent, msg)` pairs by the time they are due.
+type Due<MessageType> = BTreeMap<u64, Vec<(usize, Msg<MessageType>)>>;
+
 /// A world that can contain multiple agents and run a simulation.
 pub struct World<
     const MESSAGE_SLOTS: usize,
@@ -24,8 +119,59 @@ pub struct World<
     pub agents: Vec<Box<dyn Agent<MESSAGE_SLOTS, Msg<MessageType>>>>,
     pub world_context: WorldContext<MESSAGE_SLOTS, Msg<MessageType>>,
     mailbox: Option<ThreadedMessenger<MESSAGE_SLOTS, Msg<MessageType>>>,
+    /// mail waiting for room in a full mailbox
+    backlog: Backlog<Msg<MessageType>>,
     event_system: LocalEventSystem<CLOCK_SLOTS, CLOCK_HEIGHT>,
     time_info: TimeInfo,
+    causal_log: Option<CausalLog>,
+    occupancy: Option<OccupancyRecorder>,
+    kpis: Option<KpiRecorder>,
+    boundary: Option<usize>,
+    boundary_mail: Vec<Msg<MessageType>>,
+    ledger: Option<MessageLedger>,
+    hook: Option<Box<dyn SimHook>>,
+    sequences: TickSequences,
+    /// `(agent, payload)` of pending timers, by the time they fire
+    timers: Due<MessageType>,
+    /// `(agent, query)` of pending discovery queries, by the time they are answered
+    queries: BTreeMap<u64, Vec<(usize, Query)>>,
+    /// `(agent, msg)` of mail pushed to `Agent::on_message()`, by the time it is handed over, see
+    /// `enable_push_delivery()`
+    inbox: Option<Due<MessageType>>,
+    /// events processed since the `World` was created
+    processed: u64,
+    /// whether `run()` and `advance_to()` jump over ticks with nothing to do
+    fast_forward: bool,
+    /// whether `run()` and `advance_to()` stop once nothing is left to do
+    end_when_quiescent: bool,
+    /// ticks jumped over so far
+    skipped: u64,
+    /// wall time of the slowest recent tick, for `advance_for()`
+    tick_cost: Duration,
+    /// end of the warm-up, see `set_warmup()`
+    warmup: u64,
+}
+
+/// What a call to `World::advance_by()` or `World::advance_for()` processed, and what it left.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Quantum {
+    pub ticks: u64,
+    pub events: u64,
+    /// simulation time covered, `ticks` steps of the timestep
+    pub elapsed: f64,
+    /// events still scheduled
+    pub pending: usize,
+    /// whether the `World` reached its terminal time
+    pub finished: bool,
+}
+
+/// Passive stand-in agent whose mail is held at the `World` boundary instead of being delivered.
+struct BoundaryAgent;
+
+impl<const SLOTS: usize, T: Message> Agent<SLOTS, T> for BoundaryAgent {
+    fn step(&mut self, context: &mut WorldContext<SLOTS, T>, agent_id: usize) -> Event {
+        Event::new(context.time, context.time, agent_id, Action::Wait)
+    }
 }
 
 unsafe impl<
@@ -55,12 +201,32 @@ impl<
     /// Initialize a new world with the provided time information and world state arena allocation size
     pub fn init(terminal: f64, timestep: f64, world_arena_size: usize) -> Result<Self, AikaError> {
         let event_system = LocalEventSystem::<CLOCK_SLOTS, CLOCK_HEIGHT>::new()?;
+        let mut world_context = WorldContext::new(world_arena_size);
+        world_context.timestep = timestep;
         Ok(Self {
             agents: Vec::new(),
-            world_context: WorldContext::new(world_arena_size),
+            world_context,
             mailbox: None,
+            backlog: Backlog::new(OverflowPolicy::Error, MESSAGE_SLOTS),
             event_system,
-            time_info: TimeInfo { timestep, terminal },
+            time_info: TimeInfo::new(terminal, timestep),
+            causal_log: None,
+            occupancy: None,
+            kpis: None,
+            boundary: None,
+            boundary_mail: Vec::new(),
+            ledger: None,
+            hook: None,
+            sequences: TickSequences::default(),
+            timers: BTreeMap::new(),
+            queries: BTreeMap::new(),
+            inbox: None,
+            processed: 0,
+            fast_forward: false,
+            end_when_quiescent: false,
+            skipped: 0,
+            tick_cost: Duration::ZERO,
+            warmup: 0,
         })
     }
     /// Spawn a new `Agent` to the `World`.
@@ -69,6 +235,136 @@ impl<
         self.agents.len() - 1
     }
 
+    /// Add a resource with `capacity` units that agents acquire and release, returning its id.
+    pub fn add_resource(&mut self, capacity: u64, discipline: QueueDiscipline) -> usize {
+        self.world_context.resources.add(capacity, discipline)
+    }
+
+    /// The resources of the `World`, with their queues and statistics.
+    pub fn resources(&self) -> &Resources {
+        &self.world_context.resources
+    }
+
+    /// Every agent that is a `T`, with its index, in spawn order.
+    pub fn agents_of<T: Any>(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.agents
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, agent)| Some((idx, agent.as_ref().as_any().downcast_ref::<T>()?)))
+    }
+
+    /// Spawn the boundary address of the `World`. `Msg`s sent to it are held for `take_boundary_mail()`
+    /// instead of being delivered, letting an embedding simulation route them outward.
+    pub fn spawn_boundary(&mut self) -> usize {
+        let id = self.spawn_agent(Box::new(BoundaryAgent));
+        self.boundary = Some(id);
+        id
+    }
+
+    /// Take all `Msg`s sent to the boundary address so far.
+    pub fn take_boundary_mail(&mut self) -> Vec<Msg<MessageType>> {
+        std::mem::take(&mut self.boundary_mail)
+    }
+
+    /// Take every `Msg` sent but never read, paired with the agent it was meant for: first the mail the `World`
+    /// hadn't routed yet, with group mail copied to each member, then whatever is left in each agent's mailbox, then
+    /// the mail held back for a full mailbox, then the mail waiting to be pushed to its agents.
+    /// Unrouted mail for the boundary is held for `take_boundary_mail()` like routed mail.
+    pub fn drain_unprocessed(&mut self) -> Vec<(usize, Msg<MessageType>)> {
+        let Some(mailbox) = self.mailbox.as_mut() else {
+            return Vec::new();
+        };
+        let mut unprocessed = Vec::new();
+        while let Ok(mail) = mailbox.poll() {
+            for (idx, msg) in mail {
+                match (msg.to, msg.group) {
+                    (None, Some(group)) => unprocessed.extend(
+                        self.world_context
+                            .groups
+                            .members(group)
+                            .into_iter()
+                            .map(|member| (member, msg.clone())),
+                    ),
+                    (Some(to), _) if Some(to) == self.boundary => self.boundary_mail.push(msg),
+                    _ => unprocessed.push((idx, msg)),
+                }
+            }
+        }
+        for (idx, support) in self.world_context.agent_states.iter_mut().enumerate() {
+            let Some(user) = support.mailbox.as_mut() else {
+                continue;
+            };
+            while let Some(msgs) = user.poll() {
+                unprocessed.extend(msgs.into_iter().map(|msg| (idx, msg)));
+            }
+        }
+        unprocessed.extend(self.backlog.take());
+        for mail in std::mem::take(&mut self.inbox)
+            .into_iter()
+            .flat_map(BTreeMap::into_values)
+        {
+            unprocessed.extend(mail);
+        }
+        unprocessed
+    }
+
+    /// Deliver a `Msg` from outside the `World` straight into its recipients' mailboxes.
+    /// A `Msg` without a recipient goes to every agent except the boundary.
+    pub fn deliver(&mut self, msg: Msg<MessageType>) -> Result<(), AikaError> {
+        let boundary = self.boundary;
+        let len = self.agents.len();
+        let Some(mailbox) = self.mailbox.as_mut() else {
+            return Err(AikaError::ConfigError(
+                "Support layers must be initialized before delivering mail".to_string(),
+            ));
+        };
+        let targets = match (msg.to, msg.group) {
+            (Some(to), _) if to >= len => return Err(AikaError::MismatchedDeliveryAddress),
+            (Some(to), _) => vec![to],
+            (None, Some(group)) => self.world_context.groups.members(group),
+            (None, None) => (0..len).filter(|i| Some(*i) != boundary).collect(),
+        };
+        let mail = targets.into_iter().map(|i| (i, msg.clone())).collect();
+        match self.inbox.as_mut() {
+            Some(inbox) => Self::push_mail(inbox, mail, self.world_context.time),
+            None => {
+                Self::deliver_mail(mailbox, &mut self.backlog, mail)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Deliver `mail` into the agents' mailboxes after the mail held back for room, applying the overflow policy to
+    /// whatever doesn't fit. Returns the `Msg`s dropped.
+    fn deliver_mail(
+        mailbox: &mut ThreadedMessenger<MESSAGE_SLOTS, Msg<MessageType>>,
+        backlog: &mut Backlog<Msg<MessageType>>,
+        mail: Vec<(usize, Msg<MessageType>)>,
+    ) -> Result<Vec<Msg<MessageType>>, AikaError> {
+        let mut dropped = Vec::new();
+        for (idx, msg) in backlog.take().into_iter().chain(mail) {
+            if backlog.holds(idx) {
+                dropped.extend(backlog.overflow(idx, msg)?);
+                continue;
+            }
+            match mailbox.deliver(vec![(idx, msg.clone())]) {
+                Ok(()) => {}
+                Err(MesoError::BuffersFull) => dropped.extend(backlog.overflow(idx, msg)?),
+                Err(err) => return Err(AikaError::MesoError(err)),
+            }
+        }
+        backlog.record_dropped(dropped.len() as u64);
+        Ok(dropped)
+    }
+
+    /// Queue `mail` for `Agent::on_message()` at its receive time, or the tick after `now` if that has passed.
+    fn push_mail(inbox: &mut Due<MessageType>, mail: Vec<(usize, Msg<MessageType>)>, now: u64) {
+        for (idx, msg) in mail {
+            let time = msg.recv.max(now + 1);
+            inbox.entry(time).or_default().push((idx, msg));
+        }
+    }
+
     /// Initialize support layers for each agent. if `arena_size: Option<usize>` is set to `None`, no agent state arenas will be allocated.
     pub fn init_support_layers(&mut self, arena_size: Option<usize>) -> Result<(), AikaError> {
         let agent_ids = self
@@ -90,100 +386,811 @@ impl<
         Ok(())
     }
 
-    fn commit(&mut self, event: Event) {
+    /// Merge `other`, a sub-model set up on its own, into this `World` before either runs, returning the id its first
+    /// agent gets. The agents of `other` are numbered after those of this `World`, and their states, group
+    /// memberships, published attributes, pending events and timers and the mail waiting for them follow, every agent id in them shifted.
+    /// Each agent hears of the shift through `Agent::renumber()`. Both worlds must share their time bounds and either
+    /// both or neither have support layers, at most one of them can have a boundary, and `other` can't have resources,
+    /// whose ids its agents hold. This `World` keeps its world state, extensions, subscriptions, recorders and hook;
+    /// those of `other` are dropped.
+    pub fn merge(&mut self, mut other: Self) -> Result<usize, AikaError> {
+        let invalid = |reason: &str| Err(AikaError::ConfigError(reason.to_string()));
+        if self.now() > 0 || other.now() > 0 {
+            return invalid("worlds can only be merged before they run");
+        } else if self.time_info() != other.time_info() {
+            return invalid("merged worlds must share their time bounds");
+        } else if self.mailbox.is_some() != other.mailbox.is_some() {
+            return invalid("either both merged worlds or neither must have support layers");
+        } else if self.boundary.is_some() && other.boundary.is_some() {
+            return invalid("only one of the merged worlds can have a boundary");
+        } else if !other.world_context.resources.is_empty() {
+            return invalid("resources must be added to the merged world");
+        }
+        let offset = self.agents.len();
+        let shift = |msg: &mut Msg<MessageType>| {
+            msg.from += offset;
+            if let Some(to) = msg.to.as_mut() {
+                *to += offset;
+            }
+        };
+
+        let mut mail = self.drain_unprocessed();
+        for (idx, mut msg) in other.drain_unprocessed() {
+            shift(&mut msg);
+            mail.push((idx + offset, msg));
+        }
+        for mut msg in other.take_boundary_mail() {
+            shift(&mut msg);
+            self.boundary_mail.push(msg);
+        }
+        let events = other.event_system.pending(|_| true)?.into_iter();
+        self.event_system.insert_batch(events.map(|event| Event {
+            agent: event.agent + offset,
+            ..event
+        }))?;
+        for (time, timers) in std::mem::take(&mut other.timers) {
+            let timers = timers.into_iter().map(|(agent, mut msg)| {
+                shift(&mut msg);
+                (agent + offset, msg)
+            });
+            self.timers.entry(time).or_default().extend(timers);
+        }
+        let groups = std::mem::take(&mut other.world_context.groups);
+        self.world_context.groups.append(groups, offset);
+        let attributes = std::mem::take(&mut other.world_context.attributes);
+        self.world_context.attributes.append(attributes, offset);
+        if let Some(boundary) = other.boundary {
+            self.boundary = Some(boundary + offset);
+        }
+        for mut agent in other.agents.drain(..) {
+            agent.renumber(offset);
+            self.agents.push(agent);
+        }
+        if self.mailbox.is_none() {
+            return Ok(offset);
+        }
+
+        // every agent needs a mailbox on one messenger, so the mail drained is delivered again on a new one
+        let states = std::mem::take(&mut self.world_context.agent_states)
+            .into_iter()
+            .chain(std::mem::take(&mut other.world_context.agent_states))
+            .map(|support| support.state);
+        let messenger = ThreadedMessenger::<MESSAGE_SLOTS, Msg<MessageType>>::new(
+            (0..self.agents.len()).collect(),
+        )?;
+        self.world_context.agent_states = states
+            .enumerate()
+            .map(|(idx, state)| {
+                Ok(AgentSupport {
+                    mailbox: Some(messenger.get_user(idx)?),
+                    state,
+                })
+            })
+            .collect::<Result<_, AikaError>>()?;
+        let mailbox = self.mailbox.insert(messenger);
+        match self.inbox.as_mut() {
+            // nothing has run, so all of it is still due at its receive time
+            Some(inbox) => Self::push_mail(inbox, mail, 0),
+            None => {
+                Self::deliver_mail(mailbox, &mut self.backlog, mail)?;
+            }
+        }
+        Ok(offset)
+    }
+
+    fn commit(&mut self, event: Event) -> Result<(), AikaError> {
         self.event_system.insert(event)
     }
 
+    fn record_link(&mut self, cause: CausalNode, agent: usize, time: u64) {
+        if let Some(log) = &mut self.causal_log {
+            log.link(cause, CausalNode::new(0, agent, time));
+        }
+    }
+
+    /// Wake an agent granted a resource at `now` on the next tick.
+    fn wake_granted(&mut self, cause: CausalNode, agent: usize, now: u64) -> Result<(), AikaError> {
+        let time = checked_later(agent, now, 1)?;
+        if self.time_info.past(time) {
+            return Ok(());
+        }
+        self.commit(Event::new(now, time, agent, Action::Wait))?;
+        self.record_link(cause, agent, time);
+        Ok(())
+    }
+
+    /// Record agent activations, scheduling links and direct message deliveries for critical-path analysis.
+    /// Broadcasts bypass the `World`'s routing and are not recorded.
+    pub fn enable_causal_log(&mut self) {
+        let mut log = CausalLog::new();
+        log.start_at(self.warmup);
+        self.causal_log = Some(log);
+    }
+
+    /// Get the recorded causal log, if enabled.
+    pub fn causal_log(&self) -> Option<&CausalLog> {
+        self.causal_log.as_ref()
+    }
+
+    /// Compute the critical path of the run so far, if the causal log is enabled.
+    pub fn critical_path(&self) -> Option<CriticalPath> {
+        self.causal_log.as_ref().map(|log| log.critical_path())
+    }
+
+    /// Register a `SimHook` called on every processed event.
+    pub fn set_hook(&mut self, hook: Box<dyn SimHook>) {
+        self.hook = Some(hook);
+    }
+
+    /// Plug a `WorldExtension` into the world, reachable by agents through `WorldContext::extensions`.
+    pub fn register_extension(
+        &mut self,
+        extension: Box<dyn WorldExtension>,
+    ) -> Result<(), AikaError> {
+        let now = self.now();
+        self.world_context.extensions.register(0, now, extension)
+    }
+
+    /// Count sent and delivered direct `Msg`s per agent pair.
+    pub fn enable_message_ledger(&mut self) {
+        self.ledger = Some(MessageLedger::new());
+    }
+
+    /// Get the recorded message ledger, if enabled.
+    pub fn message_ledger(&self) -> Option<&MessageLedger> {
+        self.ledger.as_ref()
+    }
+
+    /// Pick when subscribers hear of a change to the world state, at the end of the tick of the write by default.
+    pub fn set_notify_at(&mut self, notify_at: NotifyAt) {
+        self.world_context.subscriptions.set_notify_at(notify_at);
+    }
+
+    /// Set what happens to mail for a full mailbox, `OverflowPolicy::Error` by default.
+    pub fn set_mailbox_overflow(&mut self, policy: OverflowPolicy) {
+        self.backlog.set_policy(policy);
+    }
+
+    /// Mail dropped or held back for full mailboxes so far.
+    pub fn mailbox_stats(&self) -> MailboxStats {
+        self.backlog.stats()
+    }
+
+    /// Bound the overflow queue of far-future events. Call it before scheduling anything.
+    pub fn set_overflow_strategy(&mut self, strategy: OverflowStrategy) {
+        self.event_system.set_overflow_strategy(strategy);
+    }
+
+    /// Occupancy of the overflow queue and its spilled runs.
+    pub fn overflow_stats(&self) -> OverflowStats {
+        self.event_system.overflow_stats()
+    }
+
+    /// Sample the events on each level of the wheels every `every` ticks, see `Table::from_occupancy()`.
+    pub fn enable_occupancy_recording(&mut self, every: u64) {
+        let mut recorder = OccupancyRecorder::new(0, every);
+        recorder.start_at(self.warmup);
+        self.occupancy = Some(recorder);
+    }
+
+    /// Get the recorded wheel occupancy, if enabled.
+    pub fn occupancy(&self) -> Option<&OccupancyRecorder> {
+        self.occupancy.as_ref()
+    }
+
+    /// Count the events, deliveries and active agents in buckets of `width` ticks, see `Table::from_kpis()`.
+    pub fn enable_kpis(&mut self, width: u64) {
+        let mut recorder = KpiRecorder::new(0, width);
+        recorder.start_at(self.warmup);
+        self.kpis = Some(recorder);
+    }
+
+    /// Get the KPI buckets recorded so far, if enabled.
+    pub fn kpis(&self) -> Option<&KpiRecorder> {
+        self.kpis.as_ref()
+    }
+
+    /// Let `run()` and `advance_to()` jump straight to the next tick with an event, timer, pushed `Msg`, notice or
+    /// occupancy sample due, instead of stepping through every empty tick. Nothing is skipped while an extension is
+    /// registered, as extensions see every step.
+    pub fn enable_fast_forward(&mut self) {
+        self.fast_forward = true;
+    }
+
+    /// Let `run()` and `advance_to()` stop as soon as the `World` is quiescent, see `is_quiescent()`, instead of
+    /// stepping on through empty ticks to the terminal time. The clock stays at the first tick with nothing left.
+    pub fn enable_end_when_quiescent(&mut self) {
+        self.end_when_quiescent = true;
+    }
+
+    /// Whether nothing can happen any more: no event is pending on the wheels or in the overflow, and no timer,
+    /// pushed `Msg` or notice is due. Mail left in a mailbox can't wake anyone, as only a stepping agent reads it.
+    pub fn is_quiescent(&self) -> bool {
+        self.event_system.next_due().is_none()
+            && self.timers.is_empty()
+            && self.queries.is_empty()
+            && self.inbox.as_ref().is_none_or(BTreeMap::is_empty)
+            && self.world_context.subscriptions.next_due().is_none()
+    }
+
+    #[inline(always)]
+    fn quiesced(&self) -> bool {
+        self.end_when_quiescent && self.is_quiescent()
+    }
+
+    /// Hand every `Msg` to `Agent::on_message()` of its recipient at its receive time, rather than leaving it in the
+    /// recipient's mailbox for the agent to poll, so a receiver needs no event of its own every tick to read its mail.
+    /// Mail due in the tick it was sent in, or earlier, is handed over the next tick. Within a tick, mail goes after
+    /// the timers and before the events, ordered by sub-tick offset.
+    pub fn enable_push_delivery(&mut self) {
+        self.inbox.get_or_insert_with(BTreeMap::new);
+    }
+
+    /// Ticks jumped over by fast-forwarding so far, after the warm-up.
+    pub fn skipped_ticks(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Events processed since the `World` was created.
+    pub fn processed(&self) -> u64 {
+        self.processed
+    }
+
+    /// Leave the ticks before `time` out of the statistics: the wheel occupancy, KPIs and causal log skip them, the
+    /// state histories start at `time`, and only ticks skipped after it are counted.
+    pub fn set_warmup(&mut self, time: u64) {
+        self.warmup = time;
+        if let Some(recorder) = &mut self.occupancy {
+            recorder.start_at(time);
+        }
+        if let Some(recorder) = &mut self.kpis {
+            recorder.start_at(time);
+        }
+        if let Some(log) = &mut self.causal_log {
+            log.start_at(time);
+        }
+    }
+
+    /// End of the warm-up, 0 if there is none.
+    pub fn warmup(&self) -> u64 {
+        self.warmup
+    }
+
     /// Get the current time of the simulation.
     #[inline(always)]
     pub fn now(&self) -> u64 {
         self.event_system.local_clock.time
     }
 
+    /// Every state logged by `agent` after the warm-up, oldest first, as `(time, state)`. Empty for an agent without
+    /// a state journal.
+    pub fn agent_history<T: Pod + Zeroable + 'static>(
+        &self,
+        agent: usize,
+    ) -> impl Iterator<Item = (u64, T)> + '_ {
+        let warmup = self.warmup;
+        self.world_context
+            .agent_states
+            .get(agent)
+            .and_then(|support| support.state.as_ref())
+            .into_iter()
+            .flat_map(journal_history)
+            .filter(move |(time, _)| *time >= warmup)
+    }
+
+    /// The states logged by `agent` at a time within `window`, oldest first.
+    pub fn agent_history_between<T: Pod + Zeroable + 'static>(
+        &self,
+        agent: usize,
+        window: impl RangeBounds<u64> + 'static,
+    ) -> impl Iterator<Item = (u64, T)> + '_ {
+        self.agent_history(agent)
+            .filter(move |(time, _)| window.contains(time))
+    }
+
+    /// Every world state logged after the warm-up, oldest first, as `(time, state)`.
+    pub fn world_history<T: Pod + Zeroable + 'static>(
+        &self,
+    ) -> impl Iterator<Item = (u64, T)> + '_ {
+        let warmup = self.warmup;
+        journal_history(&self.world_context.world_state).filter(move |(time, _)| *time >= warmup)
+    }
+
+    /// The world states logged at a time within `window`, oldest first.
+    pub fn world_history_between<T: Pod + Zeroable + 'static>(
+        &self,
+        window: impl RangeBounds<u64> + 'static,
+    ) -> impl Iterator<Item = (u64, T)> + '_ {
+        self.world_history()
+            .filter(move |(time, _)| window.contains(time))
+    }
+
+    /// List the events pending for `agent` within the next `steps` steps, without disturbing the schedule.
+    /// Mail already delivered to the agent's mailbox is not visible here. Fails if spilled events can't be read back.
+    pub fn pending_events(&self, agent: usize, steps: u64) -> Result<Vec<Event>, AikaError> {
+        let horizon = self.now().saturating_add(steps);
+        self.event_system
+            .pending(|event| event.agent == agent && event.time <= horizon)
+    }
+
     /// Get the time information of the simulation.
     pub fn time_info(&self) -> (f64, f64) {
         (self.time_info.timestep, self.time_info.terminal)
     }
 
+    /// The current time as a `SimTime`.
+    pub fn sim_time(&self) -> SimTime {
+        SimTime(self.now())
+    }
+
+    /// Conversions between ticks and seconds with this `World`'s timestep.
+    pub fn scale(&self) -> TimeScale {
+        TimeScale::new(self.time_info.timestep)
+    }
+
     /// Schedule an event for an agent at a given time.
-    pub fn schedule(&mut self, time: u64, agent: usize) -> Result<(), AikaError> {
+    pub fn schedule(&mut self, time: impl Into<SimTime>, agent: usize) -> Result<(), AikaError> {
+        let time = time.into().ticks();
         if time < self.now() {
             return Err(AikaError::TimeTravel);
-        } else if time as f64 * self.time_info.timestep > self.time_info.terminal {
+        } else if self.time_info.past(time) {
             return Err(AikaError::PastTerminal);
         }
         let now = self.now();
-        self.commit(Event::new(now, time, agent, Action::Wait));
+        self.commit(Event::new(now, time, agent, Action::Wait))?;
         Ok(())
     }
 
-    /// Run the simulation.
-    pub fn run(&mut self) -> Result<(), AikaError> {
+    /// Schedule a batch of `(agent, time)` events. The whole batch is validated before anything is inserted.
+    pub fn schedule_many<T: Into<SimTime> + Copy>(
+        &mut self,
+        events: &[(usize, T)],
+    ) -> Result<(), AikaError> {
+        let now = self.now();
+        for &(_, time) in events {
+            let time = time.into().ticks();
+            if time < now {
+                return Err(AikaError::TimeTravel);
+            } else if self.time_info.past(time) {
+                return Err(AikaError::PastTerminal);
+            }
+        }
+        self.event_system.insert_batch(
+            events
+                .iter()
+                .map(|&(agent, time)| Event::new(now, time, agent, Action::Wait)),
+        )
+    }
+
+    /// Schedule every spawned agent to step at the given time.
+    pub fn schedule_all_agents(&mut self, time: impl Into<SimTime>) -> Result<(), AikaError> {
+        let time = time.into();
+        let events = (0..self.agents.len())
+            .map(|agent| (agent, time))
+            .collect::<Vec<_>>();
+        self.schedule_many(&events)
+    }
+
+    #[inline(always)]
+    fn can_step(&self) -> bool {
+        self.now() < self.time_info.end()
+    }
+
+    /// Jump to the next tick with anything due, no further than `limit`, if fast-forwarding is enabled.
+    fn skip_quiet_ticks(&mut self, limit: u64) -> Result<(), AikaError> {
+        if !self.fast_forward || !self.world_context.extensions.is_empty() || self.quiesced() {
+            return Ok(());
+        }
+        let now = self.now();
+        let next = [
+            self.event_system.next_due(),
+            self.timers.keys().next().copied(),
+            self.queries.keys().next().copied(),
+            self.inbox
+                .as_ref()
+                .and_then(|inbox| inbox.keys().next().copied()),
+            self.world_context.subscriptions.next_due(),
+            self.occupancy
+                .as_ref()
+                .map(|recorder| recorder.next_due(now)),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(limit, u64::min);
+        if next > now {
+            self.event_system.skip_to(next)?;
+            self.skipped += next.saturating_sub(now.max(self.warmup));
+        }
+        Ok(())
+    }
+
+    /// Keep the timers and discovery queries set by the last handler, dropping any due past the terminal time.
+    fn take_timers(&mut self) {
+        for (agent, time, data) in std::mem::take(&mut self.world_context.timers) {
+            if self.time_info.past(time) {
+                continue;
+            }
+            self.timers.entry(time).or_default().push((agent, data));
+        }
+        for (agent, time, query) in std::mem::take(&mut self.world_context.queries) {
+            if self.time_info.past(time) {
+                continue;
+            }
+            self.queries.entry(time).or_default().push((agent, query));
+        }
+    }
+
+    /// Commit the wake-ups requested by the last handler, dropping any before the next tick.
+    fn take_wakeups(&mut self, cause: CausalNode) -> Result<(), AikaError> {
+        let now = self.now();
+        for (agent, time) in std::mem::take(&mut self.world_context.wakeups) {
+            if time <= now || self.time_info.past(time) || agent >= self.agents.len() {
+                continue;
+            }
+            self.commit(Event::new(now, time, agent, Action::Wait))?;
+            self.record_link(cause, agent, time);
+        }
+        Ok(())
+    }
+
+    /// Hand every notice due by now to its subscriber, including those raised by the subscribers themselves.
+    fn notify_subscribers(&mut self) {
+        let now = self.now();
         loop {
-            if (self.now() + 1) as f64 * self.time_info.timestep > self.time_info.terminal {
+            let notices = self.world_context.subscriptions.take_due(now);
+            if notices.is_empty() {
                 break;
             }
+            for notice in notices {
+                if notice.agent >= self.agents.len() {
+                    continue;
+                }
+                self.world_context.time = now;
+                self.world_context.offset = 0.0;
+                self.agents[notice.agent].on_world_change(
+                    &mut self.world_context,
+                    notice.cell,
+                    notice.agent,
+                );
+                self.take_timers();
+            }
+        }
+        self.world_context.subscriptions.prune(now);
+    }
 
-            if let Ok(events) = self.event_system.local_clock.tick() {
-                for event in events {
-                    if event.time as f64 * self.time_info.timestep > self.time_info.terminal {
-                        break;
-                    }
-
-                    let supports = &mut self.world_context;
-                    supports.time = event.time;
-                    let event = self.agents[event.agent].step(supports, event.agent);
-                    match event.yield_ {
-                        Action::Timeout(time) => {
-                            if (self.now() + time) as f64 * self.time_info.timestep
-                                > self.time_info.terminal
-                            {
-                                continue;
-                            }
+    /// Process a single tick of simulation time and deliver the mail sent during it.
+    #[allow(clippy::unnecessary_unwrap)]
+    pub fn step(&mut self) -> Result<(), AikaError> {
+        let boundary = self.boundary;
+        // timers set in earlier ticks fire before the tick's events
+        self.take_timers();
+        let now = self.now();
+        if let Some(recorder) = self.occupancy.as_mut().filter(|recorder| recorder.due(now)) {
+            let (levels, overflow) = self.event_system.occupancy();
+            recorder.record(now, levels, overflow);
+        }
+        self.world_context.extensions.before_step(now);
+        for (agent, data) in self.timers.remove(&now).unwrap_or_default() {
+            self.world_context.time = now;
+            self.world_context.offset = 0.0;
+            self.world_context.interrupts.interrupt(agent, now);
+            self.agents[agent].on_timer(&mut self.world_context, data, agent);
+            self.take_timers();
+            self.take_wakeups(CausalNode::new(0, agent, now))?;
+        }
+        // queries are answered after the timers, from every attribute published so far
+        for (agent, query) in self.queries.remove(&now).unwrap_or_default() {
+            let peers = self.world_context.peers(&query);
+            self.world_context.time = now;
+            self.world_context.offset = 0.0;
+            self.world_context.interrupts.interrupt(agent, now);
+            self.agents[agent].on_peers(&mut self.world_context, query, peers, agent);
+            self.take_timers();
+            self.take_wakeups(CausalNode::new(0, agent, now))?;
+        }
+        let mut mail = self
+            .inbox
+            .as_mut()
+            .and_then(|inbox| inbox.remove(&now))
+            .unwrap_or_default();
+        mail.sort_by(|(_, a), (_, b)| a.offset.total_cmp(&b.offset));
+        for (agent, msg) in mail {
+            self.world_context.time = now;
+            self.world_context.offset = msg.offset;
+            self.world_context.interrupts.interrupt(agent, now);
+            self.agents[agent].on_message(&mut self.world_context, msg, agent);
+            self.take_timers();
+            self.take_wakeups(CausalNode::new(0, agent, now))?;
+        }
+        for (agent, time) in self.world_context.interrupts.schedule(now) {
+            self.commit(Event::new(now, time, agent, Action::Wait))?;
+        }
+        if let Ok(mut events) = self.event_system.local_clock.tick() {
+            // every event of the tick is due now, so either all of them lie past the terminal time or none does
+            if self.time_info.past(now) {
+                events.clear();
+            }
+            order_within_tick(&mut events);
+            for event in events {
+                let wake = self.world_context.interrupts.wake(event.agent, event.time);
+                if wake == Wake::Dropped {
+                    continue;
+                }
+                self.processed += 1;
+                let sequence = self.sequences.next(event.agent, event.time);
+                let version = self.agents[event.agent].version();
+                self.world_context.event_key = (event.agent, event.time, sequence, version);
+
+                let cause = CausalNode::new(0, event.agent, event.time);
+                if let Some(log) = &mut self.causal_log {
+                    log.activate(cause);
+                }
+                if let Some(hook) = &mut self.hook {
+                    hook.on_event(0, event.agent, event.time);
+                }
+                if let Some(kpis) = &mut self.kpis {
+                    kpis.event(now, event.agent);
+                }
+                let supports = &mut self.world_context;
+                supports.time = event.time;
+                supports.offset = event.offset;
+                supports.interrupted = wake == Wake::Interrupted;
+                supports.interrupts.stepped(event.agent, now);
+                let event = self.agents[event.agent].step(supports, event.agent);
+                self.world_context.interrupted = false;
+                self.take_timers();
+                self.take_wakeups(cause)?;
+                match event.yield_ {
+                    Action::Timeout(delay) => {
+                        let time = checked_later(event.agent, now, delay)?;
+                        if self.time_info.past(time) {
+                            continue;
+                        }
 
-                            self.commit(Event::new(
-                                self.now(),
-                                self.now() + time,
-                                event.agent,
-                                Action::Wait,
-                            ));
+                        self.commit(
+                            Event::new(now, time, event.agent, Action::Wait)
+                                .with_offset(event.offset),
+                        )?;
+                        self.record_link(cause, event.agent, time);
+                    }
+                    Action::Interruptible(delay) => {
+                        let time = checked_later(event.agent, now, delay)?;
+                        if self.time_info.past(time) {
+                            continue;
                         }
-                        Action::Schedule(time) => {
-                            self.commit(Event::new(self.now(), time, event.agent, Action::Wait));
+                        self.world_context.interrupts.sleep(event.agent, now, time);
+                        self.commit(
+                            Event::new(now, time, event.agent, Action::Wait)
+                                .with_offset(event.offset),
+                        )?;
+                        self.record_link(cause, event.agent, time);
+                    }
+                    Action::Schedule(time) => {
+                        self.commit(
+                            Event::new(now, time, event.agent, Action::Wait)
+                                .with_offset(event.offset),
+                        )?;
+                        self.record_link(cause, event.agent, time);
+                    }
+                    Action::Trigger { time, idx } => {
+                        self.commit(
+                            Event::new(now, time, idx, Action::Wait).with_offset(event.offset),
+                        )?;
+                        self.record_link(cause, idx, time);
+                    }
+                    Action::Acquire { resource, priority } => {
+                        let resources = &mut self.world_context.resources;
+                        if resources.acquire(resource, event.agent, priority, now)? {
+                            self.wake_granted(cause, event.agent, now)?;
                         }
-                        Action::Trigger { time, idx } => {
-                            self.commit(Event::new(self.now(), time, idx, Action::Wait));
+                    }
+                    Action::Release { resource, delay } => {
+                        let resources = &mut self.world_context.resources;
+                        if let Some(next) = resources.release(resource, event.agent, now)? {
+                            self.wake_granted(cause, next, now)?;
                         }
-                        Action::Wait => {}
-                        Action::Break => {
-                            break;
+                        let Some(delay) = delay else {
+                            continue;
+                        };
+                        let time = checked_later(event.agent, now, delay)?;
+                        if self.time_info.past(time) {
+                            continue;
                         }
+                        self.commit(
+                            Event::new(now, time, event.agent, Action::Wait)
+                                .with_offset(event.offset),
+                        )?;
+                        self.record_link(cause, event.agent, time);
+                    }
+                    Action::Wait => {}
+                    Action::Break => {
+                        break;
                     }
                 }
-
-                if self.mailbox.is_some() {
-                    let mailbox = self.mailbox.as_mut().unwrap();
-                    for _ in 0..MESSAGE_SLOTS {
-                        match mailbox.poll() {
-                            Ok(mail) => {
-                                mailbox.deliver(mail)?;
+            }
+            self.notify_subscribers();
+            self.world_context.interrupts.prune(now);
+
+            if self.mailbox.is_some() {
+                let mailbox = self.mailbox.as_mut().unwrap();
+                let groups = &self.world_context.groups;
+                // mail held back for room goes first, even in a tick where nothing new was sent
+                let mut dropped = Self::deliver_mail(mailbox, &mut self.backlog, Vec::new())?;
+                for _ in 0..MESSAGE_SLOTS {
+                    match mailbox.poll() {
+                        Ok(mail) => {
+                            if let Some(log) = &mut self.causal_log {
+                                for (_, msg) in &mail {
+                                    if let Some(to) = msg.to {
+                                        log.link(
+                                            CausalNode::new(0, msg.from, msg.sent),
+                                            CausalNode::new(0, to, msg.recv),
+                                        );
+                                    }
+                                }
+                            }
+                            let pairs = mail
+                                .iter()
+                                .map(|(_, msg)| {
+                                    (Address::new(0, Some(msg.from)), Address::new(0, msg.to))
+                                })
+                                .collect::<Vec<_>>();
+                            if let Some(ledger) = &mut self.ledger {
+                                for (from, to) in &pairs {
+                                    ledger.record_sent(*from, *to);
+                                }
+                            }
+                            let (held, mail): (Vec<_>, Vec<_>) = mail
+                                .into_iter()
+                                .flat_map(|(idx, msg)| match (msg.to, msg.group) {
+                                    (None, Some(group)) => groups
+                                        .members(group)
+                                        .into_iter()
+                                        .map(|member| (member, msg.clone()))
+                                        .collect(),
+                                    _ => vec![(idx, msg)],
+                                })
+                                .partition(|(_, msg)| msg.to.is_some() && msg.to == boundary);
+                            self.boundary_mail
+                                .extend(held.into_iter().map(|(_, msg)| msg));
+                            if let Some(kpis) = &mut self.kpis {
+                                kpis.delivered(now, mail.len() as u64);
+                            }
+                            match self.inbox.as_mut() {
+                                Some(inbox) => Self::push_mail(inbox, mail, now),
+                                None => dropped.extend(Self::deliver_mail(
+                                    mailbox,
+                                    &mut self.backlog,
+                                    mail,
+                                )?),
+                            }
+                            if let Some(ledger) = &mut self.ledger {
+                                for (from, to) in pairs {
+                                    ledger.record_delivered(from, to);
+                                }
                             }
-                            Err(_) => break,
                         }
+                        Err(_) => break,
+                    }
+                }
+                if let Some(ledger) = &mut self.ledger {
+                    for msg in dropped {
+                        ledger.record_cancelled(
+                            Address::new(0, Some(msg.from)),
+                            Address::new(0, msg.to),
+                        );
                     }
                 }
             }
-            self.event_system
-                .local_clock
-                .increment(&mut self.event_system.overflow);
+        }
+        // a `World` never rolls back, so only the latest state of each extension is kept
+        self.world_context.extensions.after_step(now, now)?;
+        self.world_context.resources.prune(now + 1);
+        if let Some(kpis) = &mut self.kpis {
+            kpis.commit(now + 1);
+        }
+        self.event_system.increment()?;
+        Ok(())
+    }
+
+    /// Events due at the earliest pending time, without disturbing the schedule.
+    pub fn peek_next_events(&self) -> Result<Vec<Event>, AikaError> {
+        let pending = self.event_system.pending(|_| true)?;
+        let Some(next) = pending.first().map(|event| event.time) else {
+            return Ok(Vec::new());
+        };
+        Ok(pending
+            .into_iter()
+            .take_while(|event| event.time == next)
+            .collect())
+    }
+
+    /// Process exactly one tick, returning the events that were due in it. Agents and their state can be
+    /// inspected or modified between calls.
+    pub fn step_once(&mut self) -> Result<Vec<Event>, AikaError> {
+        if !self.can_step() {
+            return Err(AikaError::PastTerminal);
+        }
+        let now = self.now();
+        let due = self.event_system.pending(|event| event.time == now)?;
+        self.step()?;
+        Ok(due)
+    }
+
+    /// Run the simulation.
+    pub fn run(&mut self) -> Result<(), AikaError> {
+        let end = self.time_info.end();
+        self.skip_quiet_ticks(end)?;
+        while self.can_step() && !self.quiesced() {
+            self.step()?;
+            self.skip_quiet_ticks(end)?;
+        }
+        let now = self.now();
+        self.world_context.extensions.finish(now);
+        Ok(())
+    }
+
+    /// Run every tick up to and including `time`, stopping early at the terminal time.
+    pub fn advance_to(&mut self, time: u64) -> Result<(), AikaError> {
+        let limit = self.time_info.end().min(time.saturating_add(1));
+        self.skip_quiet_ticks(limit)?;
+        while self.now() <= time && self.can_step() && !self.quiesced() {
+            self.step()?;
+            self.skip_quiet_ticks(limit)?;
         }
         Ok(())
     }
+
+    /// Run as many whole ticks as fit in `sim_duration` units of simulation time, stopping early at the terminal
+    /// time. A fixed-timestep game loop carries the rest, `sim_duration - elapsed`, over to its next frame.
+    pub fn advance_by(&mut self, sim_duration: f64) -> Result<Quantum, AikaError> {
+        // a hair of slack, so that e.g. 0.3 / 0.1 covers three ticks
+        let ticks = (sim_duration / self.time_info.timestep + 1e-9).floor();
+        let ticks = if ticks.is_finite() && ticks > 0.0 {
+            ticks as u64
+        } else {
+            0
+        };
+        let processed = self.processed;
+        let mut done = 0;
+        while done < ticks && self.can_step() {
+            self.step()?;
+            done += 1;
+        }
+        self.quantum(done, processed)
+    }
+
+    /// Run ticks for at most `wall_budget` of wall time, stopping before any tick that wouldn't fit if it took as
+    /// long as the slowest recent one.
+    pub fn advance_for(&mut self, wall_budget: Duration) -> Result<Quantum, AikaError> {
+        let start = Instant::now();
+        let processed = self.processed;
+        let mut done = 0;
+        while self.can_step() && start.elapsed() + self.tick_cost < wall_budget {
+            let tick = Instant::now();
+            self.step()?;
+            // the estimate decays, so one slow tick doesn't throttle every later frame
+            self.tick_cost = tick.elapsed().max(self.tick_cost * 15 / 16);
+            done += 1;
+        }
+        self.quantum(done, processed)
+    }
+
+    fn quantum(&self, ticks: u64, processed: u64) -> Result<Quantum, AikaError> {
+        Ok(Quantum {
+            ticks,
+            events: self.processed - processed,
+            elapsed: ticks as f64 * self.time_info.timestep,
+            pending: self.event_system.pending(|_| true)?.len(),
+            finished: !self.can_step(),
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::objects::{split_time, GroupId};
     use std::cell::RefCell;
     use std::rc::Rc;
 
@@ -252,6 +1259,11 @@ mod tests {
                 Event::new(time, time, self.id, Action::Wait)
             }
         }
+
+        fn renumber(&mut self, offset: usize) {
+            self.id += offset;
+            self.target += offset;
+        }
     }
 
     // Agent that receives and counts messages
@@ -387,6 +1399,246 @@ mod tests {
         world.run().unwrap();
     }
 
+    #[test]
+    fn test_schedule_many() {
+        let mut world = World::<8, 16, 1, u8>::init(100.0, 1.0, 0).unwrap();
+        for i in 0..3 {
+            world.spawn_agent(Box::new(TestAgent::new(i)));
+        }
+        world.init_support_layers(None).unwrap();
+
+        // A batch containing an invalid entry should be rejected as a whole
+        let result = world.schedule_many(&[(0, 1), (1, 500)]);
+        assert!(matches!(result, Err(AikaError::PastTerminal)));
+        assert!(world.event_system.local_clock.wheels[0][1].is_empty());
+
+        // Far-future events spill into the overflow queue
+        world.schedule_many(&[(0, 1), (1, 2), (2, 99)]).unwrap();
+        assert_eq!(world.event_system.local_clock.wheels[0][1].len(), 1);
+        assert_eq!(world.event_system.overflow.len(), 1);
+
+        world.schedule_all_agents(5).unwrap();
+        assert_eq!(world.event_system.local_clock.wheels[0][5].len(), 3);
+        world.run().unwrap();
+    }
+
+    #[test]
+    fn test_pending_events() {
+        let mut world = World::<8, 16, 1, u8>::init(100.0, 1.0, 0).unwrap();
+        for i in 0..2 {
+            world.spawn_agent(Box::new(TestAgent::new(i)));
+        }
+        world.init_support_layers(None).unwrap();
+        world.schedule_many(&[(0, 3), (1, 2), (0, 50)]).unwrap();
+
+        let times = |events: Vec<Event>| events.iter().map(|e| e.time).collect::<Vec<_>>();
+        assert_eq!(times(world.pending_events(0, 100).unwrap()), vec![3, 50]);
+        assert_eq!(times(world.pending_events(0, 10).unwrap()), vec![3]);
+        assert_eq!(times(world.pending_events(1, 100).unwrap()), vec![2]);
+        assert_eq!(world.event_system.overflow.len(), 1);
+    }
+
+    #[test]
+    fn test_step_once() {
+        let mut world = World::<8, 16, 1, u8>::init(4.0, 1.0, 0).unwrap();
+        for i in 0..2 {
+            world.spawn_agent(Box::new(TestAgent::new(i)));
+        }
+        world.init_support_layers(None).unwrap();
+        world.schedule_many(&[(0, 2), (1, 2)]).unwrap();
+
+        let due = |mut events: Vec<Event>| {
+            events.sort_by_key(|e| (e.time, e.agent));
+            events.iter().map(|e| (e.agent, e.time)).collect::<Vec<_>>()
+        };
+        assert_eq!(due(world.peek_next_events().unwrap()), vec![(0, 2), (1, 2)]);
+        assert!(world.step_once().unwrap().is_empty());
+        assert!(world.step_once().unwrap().is_empty());
+        assert_eq!(due(world.step_once().unwrap()), vec![(0, 2), (1, 2)]);
+        assert_eq!(world.now(), 3);
+        // both agents timed out by one tick
+        assert_eq!(due(world.peek_next_events().unwrap()), vec![(0, 3), (1, 3)]);
+        assert_eq!(due(world.step_once().unwrap()), vec![(0, 3), (1, 3)]);
+        assert!(matches!(world.step_once(), Err(AikaError::PastTerminal)));
+    }
+
+    #[test]
+    fn test_sub_tick_ordering() {
+        struct Poisson {
+            offset: f64,
+            log: Rc<RefCell<Vec<(usize, u64, f64)>>>,
+        }
+
+        impl Agent<8, Msg<u8>> for Poisson {
+            fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+                self.log
+                    .borrow_mut()
+                    .push((id, context.time, context.offset));
+                Event::new(context.time, context.time, id, Action::Timeout(1))
+                    .with_offset(self.offset)
+            }
+        }
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut world = World::<8, 16, 1, u8>::init(4.0, 1.0, 0).unwrap();
+        for offset in [0.7, 0.2] {
+            world.spawn_agent(Box::new(Poisson {
+                offset,
+                log: Rc::clone(&log),
+            }));
+        }
+        world.init_support_layers(None).unwrap();
+        world.schedule_all_agents(1).unwrap();
+        world.run().unwrap();
+
+        // both start on the tick boundary in spawn order, then agent 1's earlier offset goes first
+        let order = log.borrow().clone();
+        assert_eq!(
+            order,
+            vec![
+                (0, 1, 0.0),
+                (1, 1, 0.0),
+                (1, 2, 0.2),
+                (0, 2, 0.7),
+                (1, 3, 0.2),
+                (0, 3, 0.7),
+            ]
+        );
+        assert_eq!(split_time(7.5, 2.0), (3, 0.75));
+    }
+
+    #[test]
+    fn test_timers_with_payloads() {
+        // Asks to be reminded of a token three times, each time four steps after the last
+        struct Reminder {
+            fired: Rc<RefCell<Vec<(u64, u8)>>>,
+        }
+
+        impl Agent<8, Msg<u8>> for Reminder {
+            fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+                let token = Msg::new(1, context.time, context.time, id, Some(id));
+                context.set_timer(id, 4, token);
+                Event::new(context.time, context.time, id, Action::Wait)
+            }
+
+            fn on_timer(
+                &mut self,
+                context: &mut WorldContext<8, Msg<u8>>,
+                data: Msg<u8>,
+                id: usize,
+            ) {
+                self.fired.borrow_mut().push((context.time, data.data));
+                if data.data < 3 {
+                    let token = Msg::new(data.data + 1, context.time, context.time, id, Some(id));
+                    context.set_timer(id, 4, token);
+                }
+            }
+        }
+
+        let mut world = World::<8, 128, 1, u8>::init(12.0, 1.0, 0).unwrap();
+        let fired = Rc::new(RefCell::new(Vec::new()));
+        let reminder = Reminder {
+            fired: Rc::clone(&fired),
+        };
+        world.spawn_agent(Box::new(reminder));
+        world.init_support_layers(None).unwrap();
+        world.schedule(1, 0).unwrap();
+        world.run().unwrap();
+
+        // the third reminder would fire at 13, past the terminal time
+        assert_eq!(*fired.borrow(), vec![(5, 1), (9, 2)]);
+    }
+
+    #[test]
+    fn test_peer_discovery() {
+        use crate::agents::discovery::{Peer, Query};
+
+        // Offers service 3 at its own id as price, until step 4
+        struct Provider;
+
+        impl Agent<8, Msg<u8>> for Provider {
+            fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+                if context.time < 4 {
+                    context.publish(id, 3, id as u64);
+                    return Event::new(context.time, context.time, id, Action::Timeout(3));
+                }
+                context.unpublish(id, 3);
+                Event::new(context.time, context.time, id, Action::Wait)
+            }
+        }
+
+        type Found = Rc<RefCell<Vec<(u64, Vec<Peer>)>>>;
+
+        // Asks for the providers every other step
+        struct Customer {
+            found: Found,
+        }
+
+        impl Agent<8, Msg<u8>> for Customer {
+            fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+                context.discover(id, Query::new(3));
+                Event::new(context.time, context.time, id, Action::Timeout(2))
+            }
+
+            fn on_peers(
+                &mut self,
+                context: &mut WorldContext<8, Msg<u8>>,
+                _query: Query,
+                peers: Vec<Peer>,
+                _id: usize,
+            ) {
+                self.found.borrow_mut().push((context.time, peers));
+            }
+        }
+
+        let mut world = World::<8, 128, 1, u8>::init(8.0, 1.0, 0).unwrap();
+        let found = Found::default();
+        world.spawn_agent(Box::new(Customer {
+            found: Rc::clone(&found),
+        }));
+        world.spawn_agent(Box::new(Provider));
+        world.spawn_agent(Box::new(Provider));
+        world.init_support_layers(None).unwrap();
+        world.schedule(0, 0).unwrap();
+        world.schedule(1, 1).unwrap();
+        world.schedule(1, 2).unwrap();
+        world.run().unwrap();
+
+        let provider = |agent| Peer {
+            world: 0,
+            agent,
+            value: agent as u64,
+        };
+        // the providers withdraw at 4, so the answer at 5 finds none
+        assert_eq!(
+            *found.borrow(),
+            vec![
+                (1, vec![]),
+                (3, vec![provider(1), provider(2)]),
+                (5, vec![]),
+                (7, vec![])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_critical_path() {
+        let mut world = World::<8, 128, 1, u8>::init(10.0, 1.0, 0).unwrap();
+        world.spawn_agent(Box::new(TestAgent::new(0)));
+        world.spawn_agent(Box::new(TriggeringAgent::new(1, 0, vec![])));
+        world.init_support_layers(None).unwrap();
+        world.enable_causal_log();
+        world.schedule(1, 0).unwrap();
+        world.schedule(5, 1).unwrap();
+        world.run().unwrap();
+
+        // agent 0 times out every tick from 1 through 9, agent 1 runs once off the path
+        let path = world.critical_path().unwrap();
+        assert_eq!(path.len(), 9);
+        assert_eq!(path.total_activations, 10);
+        assert_eq!(path.agent_share, vec![((0, 0), 9)]);
+    }
+
     #[test]
     fn test_simple_message_passing() {
         let mut world = World::<8, 128, 1, u8>::init(100.0, 1.0, 0).unwrap();
@@ -416,6 +1668,275 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_merge_worlds() {
+        // a sub-model of a sender and a receiver, with its sender's ids renumbered on merging
+        let model = |receiver: &ReceivingAgent| {
+            let mut world = World::<8, 128, 1, u8>::init(100.0, 1.0, 0).unwrap();
+            world.spawn_agent(Box::new(SendingAgent::new(0, 1, 3)));
+            world.spawn_agent(Box::new(ReceivingAgent {
+                _id: 1,
+                messages_received: receiver.messages_received.clone(),
+            }));
+            world.init_support_layers(None).unwrap();
+            world.schedule_all_agents(1).unwrap();
+            world
+        };
+        let (first, second) = (ReceivingAgent::new(1), ReceivingAgent::new(1));
+        let mut world = model(&first);
+        let mut other = model(&second);
+        other.deliver(Msg::new(7, 0, 0, 0, Some(1))).unwrap();
+        other.world_context.groups.join(GroupId(1), 0);
+
+        assert_eq!(world.merge(other).unwrap(), 2);
+        assert_eq!(world.world_context.groups.members(GroupId(1)), [2]);
+        assert_eq!(world.pending_events(3, 1).unwrap().len(), 1);
+        world.run().unwrap();
+
+        let received = |receiver: &ReceivingAgent| {
+            let messages = receiver.messages_received.borrow();
+            messages
+                .iter()
+                .map(|msg| (msg.data, msg.from, msg.to))
+                .collect::<Vec<_>>()
+        };
+        let sent = |from, to| (0..3).map(|i| (i, from, Some(to))).collect::<Vec<_>>();
+        assert_eq!(received(&first), sent(0, 1));
+        assert_eq!(received(&second)[0], (7, 2, Some(3)));
+        assert_eq!(received(&second)[1..], sent(2, 3));
+
+        let idle = World::<8, 128, 1, u8>::init(100.0, 1.0, 0).unwrap();
+        assert!(world.merge(idle).is_err());
+    }
+
+    // Notes the mail pushed to it, never stepping
+    struct Listener {
+        heard: Rc<RefCell<Vec<(u64, u8, usize)>>>,
+    }
+
+    impl Agent<8, Msg<u8>> for Listener {
+        fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+            Event::new(context.time, context.time, id, Action::Wait)
+        }
+
+        fn on_message(&mut self, context: &mut WorldContext<8, Msg<u8>>, msg: Msg<u8>, _id: usize) {
+            self.heard
+                .borrow_mut()
+                .push((context.time, msg.data, msg.from));
+        }
+    }
+
+    #[test]
+    fn test_push_delivery() {
+        let mut world = World::<8, 128, 1, u8>::init(100.0, 1.0, 0).unwrap();
+        let heard = Rc::new(RefCell::new(Vec::new()));
+        world.spawn_agent(Box::new(SendingAgent::new(0, 1, 3)));
+        world.spawn_agent(Box::new(Listener {
+            heard: heard.clone(),
+        }));
+        world.init_support_layers(None).unwrap();
+        world.enable_push_delivery();
+        world.enable_fast_forward();
+        world.schedule(1, 0).unwrap();
+        world.deliver(Msg::new(9, 0, 0, 7, Some(1))).unwrap();
+        world.run().unwrap();
+
+        // sent at 1, 6 and 11 for ten ticks later, and the mail from outside the tick after it was due
+        assert_eq!(
+            *heard.borrow(),
+            [(1, 9, 7), (11, 0, 0), (16, 1, 0), (21, 2, 0)]
+        );
+        assert!(world.drain_unprocessed().is_empty());
+    }
+
+    // Wakes every second of simulated time, noting when
+    struct Metronome {
+        beats: Rc<RefCell<Vec<SimTime>>>,
+    }
+
+    impl Agent<8, Msg<u8>> for Metronome {
+        fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+            let now = context.sim_time();
+            self.beats.borrow_mut().push(now);
+            Event::new(now, now, id, Action::after(context.scale().duration(1.0)))
+        }
+    }
+
+    #[test]
+    fn test_typed_time() {
+        let mut world = World::<8, 128, 1, u8>::init(5.0, 0.5, 0).unwrap();
+        let beats = Rc::new(RefCell::new(Vec::new()));
+        world.spawn_agent(Box::new(Metronome {
+            beats: beats.clone(),
+        }));
+        world.init_support_layers(None).unwrap();
+        world.schedule(world.scale().time(0.5), 0).unwrap();
+        world.run().unwrap();
+        let secs = beats
+            .borrow()
+            .iter()
+            .map(|beat| world.scale().secs(*beat))
+            .collect::<Vec<_>>();
+        assert_eq!(secs, [0.5, 1.5, 2.5, 3.5, 4.5]);
+    }
+
+    #[test]
+    fn test_agents_of() {
+        let mut world = World::<8, 128, 1, u8>::init(100.0, 1.0, 0).unwrap();
+        world.spawn_agent(Box::new(TestAgent::new(0)));
+        world.spawn_agent(Box::new(SendingAgent::new(1, 2, 3)));
+        world.spawn_agent(Box::new(ReceivingAgent::new(2)));
+        world.init_support_layers(None).unwrap();
+        world.schedule_all_agents(1).unwrap();
+        world.run().unwrap();
+
+        // results are read straight off the agents, without a shared log
+        let senders = world
+            .agents_of::<SendingAgent>()
+            .map(|(idx, agent)| (idx, agent.messages_sent))
+            .collect::<Vec<_>>();
+        assert_eq!(senders, vec![(1, 3)]);
+        assert_eq!(world.agents_of::<TestAgent>().count(), 1);
+        assert_eq!(world.agents_of::<u8>().count(), 0);
+    }
+
+    #[test]
+    fn test_terminal_steps_match_float_checks() {
+        for (terminal, timestep) in [
+            (100.0, 1.0),
+            (10.5, 1.0),
+            (1.0, 0.1),
+            (0.3, 0.1),
+            (7.0, 3.0),
+            (0.0, 1.0),
+        ] {
+            let info = TimeInfo::new(terminal, timestep);
+            for step in 0..200u64 {
+                assert_eq!(info.past(step), step as f64 * timestep > terminal);
+                assert_eq!(info.reached(step), step as f64 * timestep >= terminal);
+            }
+        }
+        assert!(!TimeInfo::new(f64::INFINITY, 1.0).past(u64::MAX - 1));
+        assert!(TimeInfo::new(-1.0, 1.0).past(0));
+    }
+
+    #[test]
+    fn test_fast_forward_skips_empty_ticks() {
+        // Wakes every 5000 ticks, setting a timer 777 ticks out the first time
+        struct Sparse {
+            seen: Rc<RefCell<Vec<u64>>>,
+        }
+
+        impl Agent<8, Msg<u8>> for Sparse {
+            fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+                self.seen.borrow_mut().push(context.time);
+                if context.time == 1 {
+                    context.set_timer(id, 777, Msg::new(0, 1, 1, id, Some(id)));
+                }
+                Event::new(context.time, context.time, id, Action::Timeout(5000))
+            }
+
+            fn on_timer(
+                &mut self,
+                context: &mut WorldContext<8, Msg<u8>>,
+                _data: Msg<u8>,
+                _id: usize,
+            ) {
+                self.seen.borrow_mut().push(context.time);
+            }
+        }
+
+        let mut runs = Vec::new();
+        for fast in [false, true] {
+            let mut world = World::<8, 16, 2, u8>::init(40_000.0, 1.0, 0).unwrap();
+            let seen = Rc::new(RefCell::new(Vec::new()));
+            world.spawn_agent(Box::new(Sparse { seen: seen.clone() }));
+            world.init_support_layers(None).unwrap();
+            world.enable_occupancy_recording(10_000);
+            if fast {
+                world.enable_fast_forward();
+            }
+            world.schedule(1, 0).unwrap();
+            world.run().unwrap();
+            assert_eq!(world.now(), 40_000);
+            let samples = world.occupancy().unwrap().samples().len();
+            runs.push((seen.take(), samples, world.skipped_ticks()));
+        }
+        let (slow, fast) = (&runs[0], &runs[1]);
+        assert_eq!(slow.0[..3], [1, 778, 5001]);
+        assert_eq!((slow.0.len(), slow.1, slow.2), (9, 4, 0));
+        assert_eq!((&fast.0, fast.1), (&slow.0, slow.1));
+        // only the agent's 8 steps, the timer and the 4 samples are stepped through
+        assert_eq!(fast.2, 40_000 - 13);
+    }
+
+    #[test]
+    fn test_run_ends_when_quiescent() {
+        // Steps three times, the last time leaving a timer behind
+        struct Brief;
+
+        impl Agent<8, Msg<u8>> for Brief {
+            fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+                match context.time {
+                    3 => {
+                        context.set_timer(id, 4, Msg::new(0, 3, 3, id, Some(id)));
+                        Event::new(context.time, context.time, id, Action::Wait)
+                    }
+                    _ => Event::new(context.time, context.time, id, Action::Timeout(1)),
+                }
+            }
+        }
+
+        for fast in [false, true] {
+            let mut world = World::<8, 16, 2, u8>::init(1_000_000.0, 1.0, 0).unwrap();
+            world.spawn_agent(Box::new(Brief));
+            world.init_support_layers(None).unwrap();
+            world.enable_end_when_quiescent();
+            if fast {
+                world.enable_fast_forward();
+            }
+            assert!(world.is_quiescent());
+            world.schedule(1, 0).unwrap();
+            assert!(!world.is_quiescent());
+            world.run().unwrap();
+            // the timer fires at 7, and nothing is left after it
+            assert_eq!(world.now(), 8);
+            assert!(world.is_quiescent());
+        }
+    }
+
+    #[test]
+    fn test_warmup_is_left_out() {
+        struct Ticker;
+
+        impl Agent<8, Msg<u8>> for Ticker {
+            fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+                Event::new(context.time, context.time, id, Action::Timeout(1))
+            }
+        }
+
+        let mut world = World::<8, 16, 2, u8>::init(30.0, 1.0, 0).unwrap();
+        world.spawn_agent(Box::new(Ticker));
+        world.init_support_layers(None).unwrap();
+        world.enable_kpis(10);
+        world.set_warmup(12);
+        world.enable_causal_log();
+        world.schedule(1, 0).unwrap();
+        world.run().unwrap();
+
+        let events = world
+            .kpis()
+            .unwrap()
+            .buckets()
+            .iter()
+            .map(|bucket| (bucket.start, bucket.events))
+            .collect::<Vec<_>>();
+        assert_eq!(events, [(10, 8), (20, 10)]);
+        let activations = world.causal_log().unwrap().activations();
+        assert_eq!(activations.len(), 18);
+        assert_eq!(activations[0].time, 12);
+    }
+
     #[test]
     fn test_broadcast_messages() {
         let mut world = World::<8, 128, 1, u8>::init(100.0, 1.0, 0).unwrap();
@@ -455,6 +1976,151 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_multicast_messages() {
+        struct Multicaster;
+
+        impl Agent<8, Msg<u8>> for Multicaster {
+            fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+                let time = context.time;
+                if let Some(mailbox) = &context.agent_states[id].mailbox {
+                    let msg = Msg::multicast(42, time, time + 1, id, GroupId::named("evens"));
+                    mailbox.send(msg).unwrap();
+                }
+                Event::new(time, time, id, Action::Wait)
+            }
+        }
+
+        let mut world = World::<8, 128, 1, u8>::init(20.0, 1.0, 0).unwrap();
+        world.spawn_agent(Box::new(Multicaster));
+        let receivers = (1..4)
+            .map(|i| {
+                let receiver = ReceivingAgent::new(i);
+                let received = receiver.messages_received.clone();
+                world.spawn_agent(Box::new(receiver));
+                received
+            })
+            .collect::<Vec<_>>();
+        world.init_support_layers(None).unwrap();
+        world.world_context.join_group(GroupId::named("evens"), 2);
+        world.schedule_all_agents(1).unwrap();
+        world.run().unwrap();
+
+        // only agent 2 is in the group
+        let counts = receivers
+            .iter()
+            .map(|received| received.borrow().len())
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![0, 1, 0]);
+    }
+
+    #[test]
+    fn test_drain_unprocessed_mail() {
+        // sends agent 1 its step time on the first three steps
+        struct Sender;
+
+        impl Agent<8, Msg<u8>> for Sender {
+            fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+                let time = context.time;
+                if let Some(mailbox) = &context.agent_states[id].mailbox {
+                    let msg = Msg::new(time as u8, time, time + 1, id, Some(1));
+                    mailbox.send(msg).unwrap();
+                }
+                match time < 3 {
+                    true => Event::new(time, time, id, Action::Timeout(1)),
+                    false => Event::new(time, time, id, Action::Wait),
+                }
+            }
+        }
+
+        // never reads its mail
+        struct Deaf;
+
+        impl Agent<8, Msg<u8>> for Deaf {
+            fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+                Event::new(context.time, context.time, id, Action::Wait)
+            }
+        }
+
+        let mut world = World::<8, 128, 1, u8>::init(20.0, 1.0, 0).unwrap();
+        world.spawn_agent(Box::new(Sender));
+        world.spawn_agent(Box::new(Deaf));
+        world.init_support_layers(None).unwrap();
+        world.schedule(1, 0).unwrap();
+        world.run().unwrap();
+
+        // sent after the last tick, so never routed
+        let late = Msg::new(9, 20, 21, 0, Some(1));
+        let mailbox = world.world_context.agent_states[0].mailbox.as_ref();
+        mailbox.unwrap().send(late).unwrap();
+
+        let unprocessed = world.drain_unprocessed();
+        let data = unprocessed
+            .iter()
+            .map(|(to, msg)| (*to, msg.data))
+            .collect::<Vec<_>>();
+        assert_eq!(data, vec![(1, 9), (1, 1), (1, 2), (1, 3)]);
+        assert!(world.drain_unprocessed().is_empty());
+    }
+
+    #[test]
+    fn test_mailbox_overflow() {
+        // sends agent 1 one letter a step, far more than its mailbox holds
+        struct Flood;
+
+        impl Agent<8, Msg<u8>> for Flood {
+            fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+                let time = context.time;
+                if let Some(mailbox) = &context.agent_states[id].mailbox {
+                    mailbox
+                        .send(Msg::new(time as u8, time, time + 1, id, Some(1)))
+                        .unwrap();
+                }
+                Event::new(time, time, id, Action::Timeout(1))
+            }
+        }
+
+        // never reads its mail
+        struct Deaf;
+
+        impl Agent<8, Msg<u8>> for Deaf {
+            fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+                Event::new(context.time, context.time, id, Action::Wait)
+            }
+        }
+
+        let run = |policy| {
+            let mut world = World::<8, 128, 1, u8>::init(20.0, 1.0, 0).unwrap();
+            world.spawn_agent(Box::new(Flood));
+            world.spawn_agent(Box::new(Deaf));
+            world.init_support_layers(None).unwrap();
+            world.set_mailbox_overflow(policy);
+            world.schedule(1, 0).unwrap();
+            let result = world.run();
+            (world, result)
+        };
+
+        let (_, result) = run(OverflowPolicy::Error);
+        assert!(matches!(result, Err(AikaError::MailboxFull(1))));
+
+        let (world, result) = run(OverflowPolicy::DropNewest);
+        result.unwrap();
+        let stats = world.mailbox_stats();
+        assert_eq!((stats.dropped, stats.held), (11, 0));
+
+        // held letters come out after those that found room, in the order they were sent
+        let (mut world, result) = run(OverflowPolicy::Block);
+        result.unwrap();
+        let stats = world.mailbox_stats();
+        assert_eq!((stats.dropped, stats.held), (0, 11));
+        let data = world
+            .drain_unprocessed()
+            .iter()
+            .map(|(_, msg)| msg.data)
+            .collect::<Vec<_>>();
+        assert_eq!(data, (1..20).collect::<Vec<_>>());
+    }
+
     #[test]
     fn test_agent_triggering() {
         let mut world = World::<8, 128, 1, u8>::init(100.0, 1.0, 0).unwrap();
@@ -568,4 +2234,64 @@ mod tests {
         // This should run without panicking
         world.run().unwrap();
     }
+
+    #[test]
+    fn test_time_overflow_is_an_error() {
+        // Sleeps for good with a timer, then asks for a timeout past `u64::MAX`
+        struct Sleeper;
+
+        impl Agent<8, Msg<u8>> for Sleeper {
+            fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+                let time = context.time;
+                context.set_timer(id, u64::MAX, Msg::new(0, time, time, id, Some(id)));
+                Event::new(time, time, id, Action::Timeout(u64::MAX))
+            }
+        }
+
+        let mut world = World::<8, 128, 1, u8>::init(50.0, 1.0, 0).unwrap();
+        world.spawn_agent(Box::new(Sleeper));
+        world.init_support_layers(None).unwrap();
+        world.schedule(3, 0).unwrap();
+        let result = world.run();
+        assert!(matches!(
+            result,
+            Err(AikaError::TimeOverflow {
+                agent: 0,
+                time: 3,
+                delay: u64::MAX
+            })
+        ));
+    }
+
+    #[test]
+    fn test_cooperative_stepping() {
+        let mut world = World::<8, 128, 1, u8>::init(10.0, 0.5, 0).unwrap();
+        for id in 0..2 {
+            world.spawn_agent(Box::new(TestAgent::new(id)));
+        }
+        world.init_support_layers(None).unwrap();
+        world.schedule_all_agents(0).unwrap();
+
+        // a 1.2 frame covers two ticks of 0.5, and the 0.2 left over carries into the next frame
+        let quantum = world.advance_by(1.2).unwrap();
+        assert_eq!((quantum.ticks, quantum.events, quantum.pending), (2, 4, 2));
+        assert_eq!(quantum.elapsed, 1.0);
+        assert_eq!(world.advance_by(0.3 + 0.2).unwrap().ticks, 1);
+        assert_eq!(world.advance_by(0.0).unwrap().ticks, 0);
+        assert_eq!(world.now(), 3);
+
+        assert_eq!(world.advance_for(Duration::ZERO).unwrap().ticks, 0);
+        let quantum = world.advance_for(Duration::from_secs(5)).unwrap();
+        assert!(quantum.finished);
+        assert_eq!(quantum.ticks, 17);
+        assert_eq!(quantum.events, 34);
+        assert_eq!(
+            world.advance_by(100.0).unwrap(),
+            Quantum {
+                finished: true,
+                pending: quantum.pending,
+                ..Default::default()
+            }
+        );
+    }
 }
@@ -0,0 +1,176 @@
+//! Sequential execution of a `HybridConfig`.
+//! `SequentialRunner` builds the same `Planet`s as a `HybridEngine`, but treats them as logical partitions
+//! stepped one at a time on the calling thread: the `Planet` with the lowest local time always goes next
+//! (ties by world id), with interplanetary mail routed between steps. `ThreadedAgent`s run unchanged.
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    agents::ThreadedAgent,
+    mt::hybrid::{config::HybridConfig, HybridEngine},
+    AikaError,
+};
+
+/// Runs a `HybridConfig` on a single thread.
+pub struct SequentialRunner<
+    const INTER_SLOTS: usize,
+    const CLOCK_SLOTS: usize,
+    const CLOCK_HEIGHT: usize,
+    MessageType: Pod + Zeroable + Clone,
+> {
+    engine: HybridEngine<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
+}
+
+impl<
+        const INTER_SLOTS: usize,
+        const CLOCK_SLOTS: usize,
+        const CLOCK_HEIGHT: usize,
+        MessageType: Pod + Zeroable + Clone,
+    > SequentialRunner<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>
+{
+    /// Build the `Planet`s described by `config`, spawning `agents[i]` on planet `i`.
+    /// Auto-scaling is ignored, as splitting a `Planet` needs a thread of its own.
+    pub fn from_hybrid_config(
+        mut config: HybridConfig,
+        agents: Vec<Vec<Box<dyn ThreadedAgent<INTER_SLOTS, MessageType>>>>,
+    ) -> Result<Self, AikaError> {
+        if agents.len() > config.number_of_worlds {
+            return Err(AikaError::InvalidWorldId(agents.len() - 1));
+        }
+        config.auto_scaling = None;
+        let mut engine = HybridEngine::create(config)?;
+        for (planet_id, planet_agents) in agents.into_iter().enumerate() {
+            for agent in planet_agents {
+                engine.spawn_agent(planet_id, agent)?;
+            }
+        }
+        Ok(Self { engine })
+    }
+
+    /// Access the underlying engine, e.g. to schedule agents or enable ledgers before running.
+    pub fn engine(
+        &mut self,
+    ) -> &mut HybridEngine<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType> {
+        &mut self.engine
+    }
+
+    /// Run every `Planet` to the terminal time, returning the engine for inspection.
+    pub fn run(
+        mut self,
+    ) -> Result<HybridEngine<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>, AikaError> {
+        let (terminal, timestep) = (self.engine.config.terminal, self.engine.config.timestep);
+        loop {
+            self.engine.galaxy.deliver_the_mail()?;
+            for planet in &mut self.engine.planets {
+                planet.poll_interplanetary_messenger()?;
+            }
+            let next = self
+                .engine
+                .planets
+                .iter()
+                .enumerate()
+                .filter(|(_, planet)| (planet.now() as f64 * timestep) < terminal)
+                .min_by_key(|(id, planet)| (planet.now(), *id))
+                .map(|(id, planet)| (id, planet.now()));
+            let Some((id, now)) = next else {
+                break;
+            };
+            self.engine
+                .galaxy
+                .gvt
+                .store(now, std::sync::atomic::Ordering::Release);
+            match self.engine.planets[id].step() {
+                Ok(()) | Err(AikaError::PastTerminal) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(self.engine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{
+        agents::PlanetContext,
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Ball {
+        hits: u32,
+    }
+
+    unsafe impl Pod for Ball {}
+    unsafe impl Zeroable for Ball {}
+
+    // Returns every ball it receives to the other planet, 3 ticks later
+    struct Player {
+        other: usize,
+        log: Arc<Mutex<Vec<(usize, u64, u32)>>>,
+    }
+
+    impl ThreadedAgent<16, Ball> for Player {
+        fn step(&mut self, context: &mut PlanetContext<16, Ball>, agent_id: usize) -> Event {
+            let time = context.time;
+            let msg = Msg::new(Ball { hits: 0 }, time, time + 3, agent_id, Some(0));
+            context.send_mail(msg, self.other).unwrap();
+            Event::new(time, time, agent_id, Action::Wait)
+        }
+
+        fn read_message(
+            &mut self,
+            context: &mut PlanetContext<16, Ball>,
+            msg: Msg<Ball>,
+            agent_id: usize,
+        ) {
+            self.log
+                .lock()
+                .unwrap()
+                .push((context.world_id, msg.recv, msg.data.hits));
+            let ball = Ball {
+                hits: msg.data.hits + 1,
+            };
+            let reply = Msg::new(ball, msg.recv, msg.recv + 3, agent_id, Some(0));
+            context.send_mail(reply, self.other).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_sequential_hybrid_config() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(20.0, 1.0)
+            .with_optimistic_sync(5, 10)
+            .with_uniform_worlds(16, 1, 16);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let player = |other| -> Box<dyn ThreadedAgent<16, Ball>> {
+            Box::new(Player {
+                other,
+                log: Arc::clone(&log),
+            })
+        };
+        let mut runner = SequentialRunner::<16, 128, 1, Ball>::from_hybrid_config(
+            config,
+            vec![vec![player(1)], vec![player(0)]],
+        )
+        .unwrap();
+        runner.engine().schedule(0, 0, 1).unwrap();
+        let engine = runner.run().unwrap();
+
+        // served at 1, then bounced every 3 ticks until the terminal time
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                (1, 4, 0),
+                (0, 7, 1),
+                (1, 10, 2),
+                (0, 13, 3),
+                (1, 16, 4),
+                (0, 19, 5)
+            ]
+        );
+        assert!(engine.stats().rollbacks() == 0);
+    }
+}
@@ -0,0 +1,387 @@
+//! Golden-file regression tests of simulation outputs.
+//! A `Golden` is a canonical digest of a finished run: named counts, compared exactly, and named values, compared
+//! within a `Tolerance`. `Golden::of_world()` and `Golden::of_hybrid()` record the final time and committed event
+//! counts of every world; the final states of the agents are added by the model with `Golden::agent()` and
+//! `Golden::planet_agent()`, e.g. from `agents_of()`. `Golden::check()` compares the digest against a golden file
+//! stored with the model, writing the file instead if it doesn't exist yet or `AIKA_UPDATE_GOLDEN` is set, so an
+//! intended change of the model is accepted by running its tests once with the variable set. The file holds one
+//! `count` or `value` line per entry, sorted by name, so it diffs well under version control.
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{mt::hybrid::HybridEngine, st::World, AikaError};
+
+/// Environment variable that makes `Golden::check()` rewrite the golden file instead of comparing against it.
+pub const UPDATE_GOLDEN: &str = "AIKA_UPDATE_GOLDEN";
+
+/// A recorded entry of a `Golden`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Entry {
+    /// compared exactly
+    Count(u64),
+    /// compared within the `Tolerance`
+    Value(f64),
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Entry::Count(count) => write!(f, "count {count}"),
+            // `Debug` prints the shortest representation that parses back to the same value
+            Entry::Value(value) => write!(f, "value {value:?}"),
+        }
+    }
+}
+
+/// How far a recorded value may stray from the golden one: it matches if it lies within `absolute` or within
+/// `relative` times the golden value of it. Counts always match exactly.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Tolerance {
+    pub absolute: f64,
+    pub relative: f64,
+}
+
+impl Tolerance {
+    /// Values must match to the bit, bar the sign of zero.
+    pub fn exact() -> Self {
+        Self::default()
+    }
+
+    pub fn absolute(absolute: f64) -> Self {
+        Self {
+            absolute,
+            relative: 0.0,
+        }
+    }
+
+    pub fn relative(relative: f64) -> Self {
+        Self {
+            absolute: 0.0,
+            relative,
+        }
+    }
+
+    /// Whether `actual` is close enough to `expected`. NaN matches only NaN.
+    pub fn accepts(&self, expected: f64, actual: f64) -> bool {
+        if expected.is_nan() || actual.is_nan() {
+            return expected.is_nan() && actual.is_nan();
+        }
+        let difference = (actual - expected).abs();
+        difference <= self.absolute
+            || difference <= self.relative * expected.abs()
+            || actual == expected
+    }
+}
+
+/// An entry in which a run differs from its golden file. `None` on either side means the entry is missing there.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mismatch<'a> {
+    pub key: &'a str,
+    pub expected: Option<Entry>,
+    pub actual: Option<Entry>,
+}
+
+impl fmt::Display for Mismatch<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let side =
+            |entry: Option<Entry>| entry.map_or("missing".to_string(), |entry| entry.to_string());
+        write!(
+            f,
+            "{}: expected {}, got {}",
+            self.key,
+            side(self.expected),
+            side(self.actual)
+        )
+    }
+}
+
+/// Canonical digest of a finished run, by entry name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Golden {
+    entries: BTreeMap<String, Entry>,
+}
+
+impl Golden {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The final time and number of events processed of `world`, as world 0.
+    pub fn of_world<
+        const MESSAGE_SLOTS: usize,
+        const CLOCK_SLOTS: usize,
+        const CLOCK_HEIGHT: usize,
+        MessageType: Clone,
+    >(
+        world: &World<MESSAGE_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
+    ) -> Self {
+        let mut golden = Self::new();
+        golden.count("world.0.time", world.now());
+        golden.count("world.0.events", world.processed());
+        golden
+    }
+
+    /// The final time of every `Planet` of `engine`, and with `HybridConfig::with_kpis()` the events it committed and
+    /// the `Msg`s it delivered. Rollbacks and other counts of speculative work differ from run to run and are left
+    /// out.
+    pub fn of_hybrid<
+        const INTER_SLOTS: usize,
+        const CLOCK_SLOTS: usize,
+        const CLOCK_HEIGHT: usize,
+        MessageType: Pod + Zeroable + Clone,
+    >(
+        engine: &HybridEngine<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
+    ) -> Self {
+        let mut golden = Self::new();
+        for (world, planet) in engine.planets.iter().enumerate() {
+            golden.count(format!("world.{world}.time"), planet.now());
+            if let Some(kpis) = planet.kpis() {
+                let buckets = kpis.buckets();
+                golden.count(
+                    format!("world.{world}.events"),
+                    buckets.iter().map(|bucket| bucket.events).sum(),
+                );
+                golden.count(
+                    format!("world.{world}.delivered"),
+                    buckets.iter().map(|bucket| bucket.delivered).sum(),
+                );
+            }
+        }
+        golden
+    }
+
+    /// Record `count` under `key`, replacing what was recorded there.
+    pub fn count(&mut self, key: impl Into<String>, count: u64) -> &mut Self {
+        self.entries.insert(key.into(), Entry::Count(count));
+        self
+    }
+
+    /// Record `value` under `key`, replacing what was recorded there.
+    pub fn value(&mut self, key: impl Into<String>, value: f64) -> &mut Self {
+        self.entries.insert(key.into(), Entry::Value(value));
+        self
+    }
+
+    /// Record `value` of the state of agent `agent` of a `World` under `agent.{agent}.{key}`.
+    pub fn agent(&mut self, agent: usize, key: &str, value: f64) -> &mut Self {
+        self.value(format!("agent.{agent}.{key}"), value)
+    }
+
+    /// Record `value` of the state of the agent at `(world, agent)` of a `HybridEngine` under
+    /// `agent.{world}.{agent}.{key}`, e.g. for each agent `HybridEngine::agents_of()` yields.
+    pub fn planet_agent(
+        &mut self,
+        (world, agent): (usize, usize),
+        key: &str,
+        value: f64,
+    ) -> &mut Self {
+        self.value(format!("agent.{world}.{agent}.{key}"), value)
+    }
+
+    pub fn get(&self, key: &str) -> Option<Entry> {
+        self.entries.get(key).copied()
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&str, Entry)> {
+        self.entries
+            .iter()
+            .map(|(key, entry)| (key.as_str(), *entry))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Every entry in which `self` differs from `expected` beyond `tolerance`, by name.
+    pub fn compare<'a>(&'a self, expected: &'a Golden, tolerance: Tolerance) -> Vec<Mismatch<'a>> {
+        let mut keys = self
+            .entries
+            .keys()
+            .chain(expected.entries.keys())
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        keys.sort_unstable();
+        keys.dedup();
+        keys.into_iter()
+            .filter_map(|key| {
+                let (expected, actual) = (expected.get(key), self.get(key));
+                let matches = match (expected, actual) {
+                    (Some(Entry::Count(expected)), Some(Entry::Count(actual))) => {
+                        expected == actual
+                    }
+                    (Some(Entry::Value(expected)), Some(Entry::Value(actual))) => {
+                        tolerance.accepts(expected, actual)
+                    }
+                    _ => false,
+                };
+                (!matches).then_some(Mismatch {
+                    key,
+                    expected,
+                    actual,
+                })
+            })
+            .collect()
+    }
+
+    /// The contents of a golden file holding `self`.
+    pub fn to_text(&self) -> String {
+        self.entries
+            .iter()
+            .map(|(key, entry)| format!("{key} {entry}\n"))
+            .collect()
+    }
+
+    /// Read the contents of a golden file, skipping blank lines and `#` comments.
+    pub fn parse(text: &str) -> Result<Self, AikaError> {
+        let mut golden = Self::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || AikaError::Golden(format!("line {}: `{line}`", idx + 1));
+            let mut words = line.split_whitespace();
+            let (Some(key), Some(kind), Some(number), None) =
+                (words.next(), words.next(), words.next(), words.next())
+            else {
+                return Err(invalid());
+            };
+            match kind {
+                "count" => golden.count(key, number.parse().map_err(|_| invalid())?),
+                "value" => golden.value(key, number.parse().map_err(|_| invalid())?),
+                _ => return Err(invalid()),
+            };
+        }
+        Ok(golden)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AikaError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), AikaError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_text())?;
+        Ok(())
+    }
+
+    /// Compare `self` against the golden file at `path`, returning a report of every mismatch. The file is written
+    /// instead if it doesn't exist yet or `AIKA_UPDATE_GOLDEN` is set.
+    pub fn check(
+        &self,
+        path: impl AsRef<Path>,
+        tolerance: Tolerance,
+    ) -> Result<Vec<String>, AikaError> {
+        let path = path.as_ref();
+        if std::env::var_os(UPDATE_GOLDEN).is_some() || !path.exists() {
+            self.save(path)?;
+            return Ok(Vec::new());
+        }
+        let expected = Self::load(path)?;
+        Ok(self
+            .compare(&expected, tolerance)
+            .iter()
+            .map(Mismatch::to_string)
+            .collect())
+    }
+
+    /// Panic with every mismatch if `self` differs from the golden file at `path`, see `check()`.
+    pub fn assert_matches(&self, path: impl AsRef<Path>, tolerance: Tolerance) {
+        let path = path.as_ref();
+        let mismatches = match self.check(path, tolerance) {
+            Ok(mismatches) => mismatches,
+            Err(err) => panic!("golden file {} unreadable: {err}", path.display()),
+        };
+        if mismatches.is_empty() {
+            return;
+        }
+        panic!(
+            "run differs from golden file {} (set {UPDATE_GOLDEN} to accept it):\n  {}",
+            path.display(),
+            mismatches.join("\n  ")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agents::{Agent, WorldContext},
+        objects::{Action, Event, Msg},
+    };
+
+    // Grows its wealth by a tenth every step
+    struct Saver {
+        wealth: f64,
+    }
+
+    impl Agent<8, Msg<u8>> for Saver {
+        fn step(&mut self, context: &mut WorldContext<8, Msg<u8>>, id: usize) -> Event {
+            self.wealth *= 1.1;
+            Event::new(context.time, context.time, id, Action::Timeout(1))
+        }
+    }
+
+    fn run(wealth: f64) -> Golden {
+        let mut world = World::<8, 128, 1, u8>::init(10.0, 1.0, 0).unwrap();
+        world.spawn_agent(Box::new(Saver { wealth }));
+        world.spawn_agent(Box::new(Saver { wealth: 1.0 }));
+        world.init_support_layers(None).unwrap();
+        world.schedule_all_agents(1).unwrap();
+        world.run().unwrap();
+        let mut golden = Golden::of_world(&world);
+        for (id, saver) in world.agents_of::<Saver>() {
+            golden.agent(id, "wealth", saver.wealth);
+        }
+        golden
+    }
+
+    #[test]
+    fn test_golden_round_trip() {
+        let golden = run(2.0);
+        assert_eq!(golden.get("world.0.events"), Some(Entry::Count(18)));
+        assert_eq!(Golden::parse(&golden.to_text()).unwrap(), golden);
+        assert!(Golden::parse("world.0.time count ten").is_err());
+    }
+
+    #[test]
+    fn test_values_compare_within_tolerance() {
+        let expected = run(2.0);
+        let actual = run(2.0 + 1e-9);
+        assert!(actual
+            .compare(&expected, Tolerance::relative(1e-6))
+            .is_empty());
+
+        let mismatches = actual.compare(&expected, Tolerance::exact());
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].key, "agent.0.wealth");
+
+        let mut fewer = expected.clone();
+        fewer.entries.remove("agent.1.wealth");
+        let mismatches = expected.compare(&fewer, Tolerance::exact());
+        assert_eq!(mismatches[0].expected, None);
+    }
+
+    #[test]
+    fn test_golden_file_is_written_then_checked() {
+        let path = std::env::temp_dir().join(format!("aika-golden-{}.txt", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let golden = run(2.0);
+        assert!(golden.check(&path, Tolerance::exact()).unwrap().is_empty());
+        assert!(golden.check(&path, Tolerance::exact()).unwrap().is_empty());
+
+        let report = run(3.0).check(&path, Tolerance::absolute(0.1)).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(report.len(), 1);
+        assert!(report[0].starts_with("agent.0.wealth: expected value"));
+    }
+}
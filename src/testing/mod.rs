@@ -0,0 +1,299 @@
+//! Test utilities for simulations built on `aika`.
+//! `MessageLedger` counts sends, deliveries and dead letters per `(from, to)` address pair, so a run can assert
+//! that every `Msg` was either delivered or explicitly dead-lettered instead of hand-logging traffic.
+//! `RunOutcome` condenses a finished run into a few assertable facts, for examples and integration tests.
+//! `golden::Golden` compares a digest of a finished run against a stored golden file, for regression tests.
+use std::collections::BTreeMap;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{mt::hybrid::HybridEngine, st::World, AikaError};
+
+pub mod golden;
+
+/// An agent address. `agent: None` addresses every agent on a world (a planet-level broadcast).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Address {
+    pub world: usize,
+    pub agent: Option<usize>,
+}
+
+impl Address {
+    pub fn new(world: usize, agent: Option<usize>) -> Self {
+        Self { world, agent }
+    }
+}
+
+/// Message counts for a single `(from, to)` pair.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PairCount {
+    pub sent: usize,
+    pub delivered: usize,
+    pub dead_lettered: usize,
+}
+
+impl PairCount {
+    /// Whether every sent message was either delivered or dead-lettered.
+    pub fn is_conserved(&self) -> bool {
+        self.sent == self.delivered + self.dead_lettered
+    }
+}
+
+/// Per-pair message accounting recorded by a `World` or `Planet` during a run.
+#[derive(Debug, Default, Clone)]
+pub struct MessageLedger {
+    pairs: BTreeMap<(Address, Address), PairCount>,
+}
+
+impl MessageLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_sent(&mut self, from: Address, to: Address) {
+        self.pairs.entry((from, to)).or_default().sent += 1;
+    }
+
+    pub fn record_delivered(&mut self, from: Address, to: Address) {
+        self.pairs.entry((from, to)).or_default().delivered += 1;
+    }
+
+    pub fn record_dead_letter(&mut self, from: Address, to: Address) {
+        self.pairs.entry((from, to)).or_default().dead_lettered += 1;
+    }
+
+    /// Turn an earlier delivery into a dead letter, e.g. when an `AntiMsg` annihilates a pending `Msg`.
+    pub fn record_cancelled(&mut self, from: Address, to: Address) {
+        let count = self.pairs.entry((from, to)).or_default();
+        count.delivered = count.delivered.saturating_sub(1);
+        count.dead_lettered += 1;
+    }
+
+    /// Record a `Msg` handed on to a new address: delivered at the old one, sent again to the new one.
+    pub fn record_forwarded(&mut self, from: Address, old_to: Address, new_to: Address) {
+        self.record_delivered(from, old_to);
+        self.record_sent(from, new_to);
+    }
+
+    /// Append all counts from another ledger.
+    pub fn merge(&mut self, other: &MessageLedger) {
+        for (pair, count) in &other.pairs {
+            let entry = self.pairs.entry(*pair).or_default();
+            entry.sent += count.sent;
+            entry.delivered += count.delivered;
+            entry.dead_lettered += count.dead_lettered;
+        }
+    }
+
+    pub fn pair(&self, from: Address, to: Address) -> PairCount {
+        self.pairs.get(&(from, to)).copied().unwrap_or_default()
+    }
+
+    pub fn pairs(&self) -> impl Iterator<Item = (&(Address, Address), &PairCount)> {
+        self.pairs.iter()
+    }
+
+    /// All pairs whose counts don't balance.
+    pub fn violations(&self) -> Vec<((Address, Address), PairCount)> {
+        self.pairs
+            .iter()
+            .filter(|(_, count)| !count.is_conserved())
+            .map(|(pair, count)| (*pair, *count))
+            .collect()
+    }
+
+    /// Panic with every unbalanced pair if any message went missing.
+    pub fn assert_conserved(&self) {
+        let violations = self.violations();
+        if violations.is_empty() {
+            return;
+        }
+        let report = violations
+            .iter()
+            .map(|((from, to), count)| {
+                format!(
+                    "  {from:?} -> {to:?}: sent {}, delivered {}, dead-lettered {}",
+                    count.sent, count.delivered, count.dead_lettered
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        panic!("message conservation violated:\n{report}");
+    }
+}
+
+/// What a finished run looks like from the outside.
+#[derive(Debug, Clone, Default)]
+pub struct RunOutcome {
+    /// local time each world stopped at, in world id order
+    pub final_times: Vec<u64>,
+    pub rollbacks: u64,
+    pub warnings: usize,
+    pub ledger: Option<MessageLedger>,
+}
+
+impl RunOutcome {
+    pub fn of_world<
+        const MESSAGE_SLOTS: usize,
+        const CLOCK_SLOTS: usize,
+        const CLOCK_HEIGHT: usize,
+        MessageType: Clone,
+    >(
+        world: &World<MESSAGE_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
+    ) -> Self {
+        Self {
+            final_times: vec![world.now()],
+            rollbacks: 0,
+            warnings: 0,
+            ledger: world.message_ledger().cloned(),
+        }
+    }
+
+    pub fn of_hybrid<
+        const INTER_SLOTS: usize,
+        const CLOCK_SLOTS: usize,
+        const CLOCK_HEIGHT: usize,
+        MessageType: Pod + Zeroable + Clone,
+    >(
+        engine: &HybridEngine<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
+    ) -> Self {
+        let stats = engine.stats();
+        Self {
+            final_times: engine.planets.iter().map(|planet| planet.now()).collect(),
+            rollbacks: stats.rollbacks(),
+            warnings: stats.warnings().count(),
+            ledger: engine.message_ledger(),
+        }
+    }
+
+    /// Panic unless every world ran to at least `step`.
+    pub fn assert_reached(&self, step: u64) {
+        for (world, time) in self.final_times.iter().enumerate() {
+            assert!(
+                *time >= step,
+                "world {world} stopped at {time}, expected to reach {step}"
+            );
+        }
+    }
+
+    /// Panic if the ledger was recorded and any message went missing.
+    pub fn assert_conserved(&self) {
+        if let Some(ledger) = &self.ledger {
+            ledger.assert_conserved();
+        }
+    }
+}
+
+/// Run a `World` to completion with a `MessageLedger` attached, asserting message conservation.
+pub fn run_world_checked<
+    const MESSAGE_SLOTS: usize,
+    const CLOCK_SLOTS: usize,
+    const CLOCK_HEIGHT: usize,
+    MessageType: Clone,
+>(
+    world: &mut World<MESSAGE_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
+) -> Result<MessageLedger, AikaError> {
+    world.enable_message_ledger();
+    world.run()?;
+    let ledger = world.message_ledger().cloned().unwrap_or_default();
+    ledger.assert_conserved();
+    Ok(ledger)
+}
+
+/// Run a `HybridEngine` to completion with a `MessageLedger` on every `Planet`, asserting message conservation.
+pub fn run_hybrid_checked<
+    const INTER_SLOTS: usize,
+    const CLOCK_SLOTS: usize,
+    const CLOCK_HEIGHT: usize,
+    MessageType: Pod + Zeroable + Clone,
+>(
+    mut engine: HybridEngine<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
+) -> Result<
+    (
+        HybridEngine<INTER_SLOTS, CLOCK_SLOTS, CLOCK_HEIGHT, MessageType>,
+        MessageLedger,
+    ),
+    AikaError,
+> {
+    engine.enable_message_ledger();
+    let engine = engine.run()?;
+    let ledger = engine.message_ledger().unwrap_or_default();
+    ledger.assert_conserved();
+    Ok((engine, ledger))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::config::HybridConfig,
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Token {
+        value: u32,
+    }
+
+    unsafe impl Pod for Token {}
+    unsafe impl Zeroable for Token {}
+
+    // Sends a token to agent 0 of the other planet every 5 ticks, arriving 4 ticks later
+    struct Pinger {
+        other: usize,
+    }
+
+    impl ThreadedAgent<128, Token> for Pinger {
+        fn step(&mut self, context: &mut PlanetContext<128, Token>, agent_id: usize) -> Event {
+            let time = context.time;
+            let msg = Msg::new(Token { value: 1 }, time, time + 4, agent_id, Some(0));
+            context.send_mail(msg, self.other).unwrap();
+            Event::new(time, time, agent_id, Action::Timeout(5))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Token>,
+            _msg: Msg<Token>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_hybrid_message_conservation() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(40.0, 1.0)
+            .with_optimistic_sync(1, 10)
+            .with_uniform_worlds(16, 1, 16);
+        let mut engine = HybridEngine::<128, 128, 1, Token>::create(config).unwrap();
+        engine
+            .spawn_agent(0, Box::new(Pinger { other: 1 }))
+            .unwrap();
+        engine
+            .spawn_agent(1, Box::new(Pinger { other: 0 }))
+            .unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        let (_, ledger) = run_hybrid_checked(engine).unwrap();
+
+        // sends at 1, 6, ..., 36; the one sent at 36 would arrive at the terminal time and is dead-lettered
+        let count = ledger.pair(Address::new(0, Some(0)), Address::new(1, Some(0)));
+        assert_eq!(count.delivered, 7);
+        assert!(count.dead_lettered >= 1);
+        assert!(ledger.violations().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "message conservation violated")]
+    fn test_lost_message_is_reported() {
+        let mut ledger = MessageLedger::new();
+        let from = Address::new(0, Some(0));
+        let to = Address::new(1, Some(2));
+        ledger.record_sent(from, to);
+        ledger.record_sent(from, to);
+        ledger.record_delivered(from, to);
+        ledger.assert_conserved();
+    }
+}
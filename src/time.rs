@@ -0,0 +1,228 @@
+//! Typed simulation time.
+//! Engines count time in whole ticks of a fixed `timestep`, stored as raw `u64`s in `Event`s, `Msg`s and contexts.
+//! `SimTime` is a tick and `SimDuration` a number of ticks, so a point in time can't be passed where a delay is
+//! expected, and a `TimeScale` converts both to and from seconds using the timestep of the `World` or `Planet`. Both
+//! are `Pod` wrappers of the raw ticks. `Event::new()`, `Msg::new()` and the `schedule()` methods take any
+//! `Into<SimTime>`, so raw ticks still work where a typed time isn't at hand.
+use std::{
+    fmt,
+    ops::{Add, AddAssign, Mul, Sub},
+};
+
+use bytemuck::{Pod, Zeroable};
+
+/// A tick of simulation time.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct SimTime(pub u64);
+
+/// A number of ticks between two `SimTime`s.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct SimDuration(pub u64);
+
+unsafe impl Zeroable for SimTime {}
+unsafe impl Pod for SimTime {}
+unsafe impl Zeroable for SimDuration {}
+unsafe impl Pod for SimDuration {}
+
+/// Whole ticks covering `secs` seconds, rounded to the nearest tick. Negative and NaN lengths are zero ticks.
+fn ticks(secs: f64, timestep: f64) -> u64 {
+    (secs / timestep).round() as u64
+}
+
+impl SimTime {
+    pub const ZERO: SimTime = SimTime(0);
+    pub const MAX: SimTime = SimTime(u64::MAX);
+
+    pub const fn from_ticks(ticks: u64) -> Self {
+        Self(ticks)
+    }
+
+    pub const fn ticks(self) -> u64 {
+        self.0
+    }
+
+    /// The tick nearest to `secs` seconds into a run with ticks of `timestep` seconds.
+    pub fn from_secs_f64(secs: f64, timestep: f64) -> Self {
+        Self(ticks(secs, timestep))
+    }
+
+    /// Seconds into a run with ticks of `timestep` seconds.
+    pub fn as_secs_f64(self, timestep: f64) -> f64 {
+        self.0 as f64 * timestep
+    }
+
+    /// `None` on overflow.
+    pub fn checked_add(self, duration: SimDuration) -> Option<SimTime> {
+        self.0.checked_add(duration.0).map(SimTime)
+    }
+
+    pub fn saturating_add(self, duration: SimDuration) -> SimTime {
+        SimTime(self.0.saturating_add(duration.0))
+    }
+
+    /// Ticks since `earlier`, zero if it is later.
+    pub fn saturating_duration_since(self, earlier: SimTime) -> SimDuration {
+        SimDuration(self.0.saturating_sub(earlier.0))
+    }
+}
+
+impl SimDuration {
+    pub const ZERO: SimDuration = SimDuration(0);
+
+    pub const fn from_ticks(ticks: u64) -> Self {
+        Self(ticks)
+    }
+
+    pub const fn ticks(self) -> u64 {
+        self.0
+    }
+
+    /// The number of ticks of `timestep` seconds nearest to `secs` seconds.
+    pub fn from_secs_f64(secs: f64, timestep: f64) -> Self {
+        Self(ticks(secs, timestep))
+    }
+
+    pub fn as_secs_f64(self, timestep: f64) -> f64 {
+        self.0 as f64 * timestep
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl Add<SimDuration> for SimTime {
+    type Output = SimTime;
+
+    fn add(self, duration: SimDuration) -> SimTime {
+        SimTime(self.0 + duration.0)
+    }
+}
+
+impl AddAssign<SimDuration> for SimTime {
+    fn add_assign(&mut self, duration: SimDuration) {
+        self.0 += duration.0;
+    }
+}
+
+impl Sub<SimDuration> for SimTime {
+    type Output = SimTime;
+
+    fn sub(self, duration: SimDuration) -> SimTime {
+        SimTime(self.0 - duration.0)
+    }
+}
+
+impl Sub for SimTime {
+    type Output = SimDuration;
+
+    fn sub(self, earlier: SimTime) -> SimDuration {
+        SimDuration(self.0 - earlier.0)
+    }
+}
+
+impl Add for SimDuration {
+    type Output = SimDuration;
+
+    fn add(self, other: SimDuration) -> SimDuration {
+        SimDuration(self.0 + other.0)
+    }
+}
+
+impl Mul<u64> for SimDuration {
+    type Output = SimDuration;
+
+    fn mul(self, times: u64) -> SimDuration {
+        SimDuration(self.0 * times)
+    }
+}
+
+impl From<u64> for SimTime {
+    fn from(ticks: u64) -> Self {
+        Self(ticks)
+    }
+}
+
+impl From<SimTime> for u64 {
+    fn from(time: SimTime) -> Self {
+        time.0
+    }
+}
+
+impl From<u64> for SimDuration {
+    fn from(ticks: u64) -> Self {
+        Self(ticks)
+    }
+}
+
+impl From<SimDuration> for u64 {
+    fn from(duration: SimDuration) -> Self {
+        duration.0
+    }
+}
+
+impl fmt::Display for SimTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "t{}", self.0)
+    }
+}
+
+impl fmt::Display for SimDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ticks", self.0)
+    }
+}
+
+/// Length of a tick in seconds, for converting between ticks and seconds in one run.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimeScale {
+    timestep: f64,
+}
+
+impl TimeScale {
+    pub fn new(timestep: f64) -> Self {
+        Self { timestep }
+    }
+
+    pub fn timestep(&self) -> f64 {
+        self.timestep
+    }
+
+    /// The tick nearest to `secs` seconds into the run.
+    pub fn time(&self, secs: f64) -> SimTime {
+        SimTime::from_secs_f64(secs, self.timestep)
+    }
+
+    /// The number of ticks nearest to `secs` seconds.
+    pub fn duration(&self, secs: f64) -> SimDuration {
+        SimDuration::from_secs_f64(secs, self.timestep)
+    }
+
+    /// Seconds into the run at `time`.
+    pub fn secs(&self, time: SimTime) -> f64 {
+        time.as_secs_f64(self.timestep)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        let scale = TimeScale::new(0.1);
+        // 0.3 / 0.1 is just under 3 in floating point
+        assert_eq!(scale.duration(0.3), SimDuration(3));
+        assert_eq!(scale.duration(-1.0), SimDuration::ZERO);
+        let start = scale.time(1.0);
+        let end = start + scale.duration(0.25) * 2;
+        assert_eq!(end, SimTime(16));
+        assert_eq!(end - start, SimDuration(6));
+        assert_eq!(start.saturating_duration_since(end), SimDuration::ZERO);
+        assert!((scale.secs(end) - 1.6).abs() < 1e-9);
+        assert_eq!(u64::from(end), 16);
+        assert_eq!(bytemuck::cast::<SimTime, u64>(end), 16);
+    }
+}
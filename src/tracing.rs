@@ -0,0 +1,322 @@
+//! Timeline traces of hybrid runs in the Chrome `trace_event` JSON format.
+//! With tracing enabled, every `Planet` records complete spans for its steps, rollbacks and interplanetary polls,
+//! plus spans for the time it spends stalled at checkpoints, on the throttle horizon or paused, and the `Galaxy`
+//! records GVT as a counter alongside checkpoint releases. `Trace::to_chrome_json()` renders everything on a shared
+//! clock, one thread per planet, for `chrome://tracing` or Perfetto.
+use std::{
+    fmt::Write,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::AikaError;
+
+/// Thread id the `Galaxy` records under.
+pub const GALAXY_TID: usize = usize::MAX;
+
+/// Chrome trace event phases used by aika.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TracePhase {
+    /// a span with a start and a duration
+    Complete,
+    /// a point in time
+    Instant,
+    /// a sampled value
+    Counter,
+}
+
+impl TracePhase {
+    fn code(&self) -> &'static str {
+        match self {
+            TracePhase::Complete => "X",
+            TracePhase::Instant => "i",
+            TracePhase::Counter => "C",
+        }
+    }
+}
+
+/// A single recorded trace event. Times are in microseconds since the engine was created.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEvent {
+    pub name: &'static str,
+    pub phase: TracePhase,
+    /// world id of the recording `Planet`, or `GALAXY_TID`
+    pub tid: usize,
+    pub ts: u64,
+    pub dur: u64,
+    pub args: Vec<(&'static str, u64)>,
+}
+
+/// Collects the `TraceEvent`s of one thread against a clock shared by the whole engine.
+#[derive(Clone, Debug)]
+pub struct TraceRecorder {
+    origin: Instant,
+    tid: usize,
+    events: Vec<TraceEvent>,
+    stall: Option<(&'static str, Instant)>,
+}
+
+impl TraceRecorder {
+    pub(crate) fn new(origin: Instant, tid: usize) -> Self {
+        Self {
+            origin,
+            tid,
+            events: Vec::new(),
+            stall: None,
+        }
+    }
+
+    /// A recorder for another thread on the same clock.
+    pub(crate) fn fork(&self, tid: usize) -> Self {
+        Self::new(self.origin, tid)
+    }
+
+    fn micros(&self, at: Instant) -> u64 {
+        at.saturating_duration_since(self.origin).as_micros() as u64
+    }
+
+    fn push(
+        &mut self,
+        name: &'static str,
+        phase: TracePhase,
+        start: Instant,
+        dur: Duration,
+        args: &[(&'static str, u64)],
+    ) {
+        self.events.push(TraceEvent {
+            name,
+            phase,
+            tid: self.tid,
+            ts: self.micros(start),
+            dur: dur.as_micros() as u64,
+            args: args.to_vec(),
+        });
+    }
+
+    /// Record a span that started at `start` and ends now.
+    pub(crate) fn span(
+        &mut self,
+        name: &'static str,
+        start: Instant,
+        args: &[(&'static str, u64)],
+    ) {
+        self.push(name, TracePhase::Complete, start, start.elapsed(), args);
+    }
+
+    pub(crate) fn instant(&mut self, name: &'static str, args: &[(&'static str, u64)]) {
+        self.push(
+            name,
+            TracePhase::Instant,
+            Instant::now(),
+            Duration::ZERO,
+            args,
+        );
+    }
+
+    pub(crate) fn counter(&mut self, name: &'static str, value: u64) {
+        self.push(
+            name,
+            TracePhase::Counter,
+            Instant::now(),
+            Duration::ZERO,
+            &[(name, value)],
+        );
+    }
+
+    /// Note that the thread is stalled for `reason`, opening a span unless one for the same reason is open.
+    pub(crate) fn stall(&mut self, reason: &'static str) {
+        if self.stall.is_some_and(|(open, _)| open == reason) {
+            return;
+        }
+        self.resume();
+        self.stall = Some((reason, Instant::now()));
+    }
+
+    /// Close the open stall span, if any.
+    pub(crate) fn resume(&mut self) {
+        if let Some((reason, start)) = self.stall.take() {
+            self.span(reason, start, &[]);
+        }
+    }
+
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events
+    }
+}
+
+/// The merged trace of a run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trace {
+    pub events: Vec<TraceEvent>,
+}
+
+impl Trace {
+    pub(crate) fn merge(recorders: impl IntoIterator<Item = TraceRecorder>) -> Self {
+        let mut events = recorders
+            .into_iter()
+            .flat_map(|mut recorder| {
+                recorder.resume();
+                recorder.events
+            })
+            .collect::<Vec<_>>();
+        events.sort_by_key(|event| (event.ts, event.tid));
+        Self { events }
+    }
+
+    /// Events recorded under `name`.
+    pub fn named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a TraceEvent> {
+        self.events.iter().filter(move |event| event.name == name)
+    }
+
+    /// Render as a Chrome `trace_event` JSON object.
+    pub fn to_chrome_json(&self) -> String {
+        let mut threads = self
+            .events
+            .iter()
+            .map(|event| event.tid)
+            .collect::<Vec<_>>();
+        threads.sort();
+        threads.dedup();
+
+        let mut entries = threads
+            .into_iter()
+            .map(|tid| {
+                let name = match tid {
+                    GALAXY_TID => "galaxy".to_string(),
+                    world => format!("planet {world}"),
+                };
+                format!(
+                    r#"{{"name":"thread_name","ph":"M","pid":0,"tid":{},"args":{{"name":"{name}"}}}}"#,
+                    tid_of(tid)
+                )
+            })
+            .collect::<Vec<_>>();
+        for event in &self.events {
+            let mut entry = format!(
+                r#"{{"name":"{}","ph":"{}","pid":0,"tid":{},"ts":{}"#,
+                event.name,
+                event.phase.code(),
+                tid_of(event.tid),
+                event.ts
+            );
+            match event.phase {
+                TracePhase::Complete => {
+                    let _ = write!(entry, r#","dur":{}"#, event.dur);
+                }
+                TracePhase::Instant => entry.push_str(r#","s":"t""#),
+                TracePhase::Counter => {}
+            }
+            if !event.args.is_empty() {
+                let args = event
+                    .args
+                    .iter()
+                    .map(|(key, value)| format!(r#""{key}":{value}"#))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let _ = write!(entry, r#","args":{{{args}}}"#);
+            }
+            entry.push('}');
+            entries.push(entry);
+        }
+        format!(r#"{{"traceEvents":[{}]}}"#, entries.join(","))
+    }
+
+    /// Write the Chrome JSON rendering to `path`.
+    pub fn write_chrome_json(&self, path: impl AsRef<Path>) -> Result<(), AikaError> {
+        std::fs::write(path, self.to_chrome_json())?;
+        Ok(())
+    }
+}
+
+// Chrome thread ids are signed, so the galaxy takes -1
+fn tid_of(tid: usize) -> i64 {
+    if tid == GALAXY_TID {
+        -1
+    } else {
+        tid as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+    use crate::{
+        agents::{PlanetContext, ThreadedAgent},
+        mt::hybrid::{config::HybridConfig, HybridEngine},
+        objects::{Action, Event, Msg},
+    };
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    #[repr(C)]
+    struct Ping {
+        value: u32,
+    }
+
+    unsafe impl Pod for Ping {}
+    unsafe impl Zeroable for Ping {}
+
+    struct Pinger {
+        other: usize,
+    }
+
+    impl ThreadedAgent<128, Ping> for Pinger {
+        fn step(&mut self, context: &mut PlanetContext<128, Ping>, agent_id: usize) -> Event {
+            let time = context.time;
+            let msg = Msg::new(Ping { value: 1 }, time, time + 4, agent_id, Some(0));
+            context.send_mail(msg, self.other).unwrap();
+            Event::new(time, time, agent_id, Action::Timeout(5))
+        }
+
+        fn read_message(
+            &mut self,
+            _context: &mut PlanetContext<128, Ping>,
+            _msg: Msg<Ping>,
+            _agent_id: usize,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_hybrid_run_trace() {
+        let config = HybridConfig::new(2, 64)
+            .with_time_bounds(40.0, 1.0)
+            .with_optimistic_sync(1, 10)
+            .with_uniform_worlds(16, 1, 16)
+            .with_tracing(true);
+        let mut engine = HybridEngine::<128, 128, 1, Ping>::create(config).unwrap();
+        engine
+            .spawn_agent(0, Box::new(Pinger { other: 1 }))
+            .unwrap();
+        engine
+            .spawn_agent(1, Box::new(Pinger { other: 0 }))
+            .unwrap();
+        engine.schedule_all_agents(1).unwrap();
+        let engine = engine.run().unwrap();
+        let trace = engine.trace().unwrap();
+
+        for world in 0..2 {
+            let steps = trace
+                .named("step")
+                .filter(|event| event.tid == world)
+                .count();
+            assert!(steps >= 39, "planet {world} recorded {steps} steps");
+        }
+        assert!(trace.named("poll").count() > 0);
+        let gvt = trace
+            .named("gvt")
+            .map(|event| event.args[0].1)
+            .collect::<Vec<_>>();
+        assert!(gvt.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(trace
+            .named("checkpoint")
+            .all(|event| event.tid == GALAXY_TID));
+        assert!(trace.events.windows(2).all(|pair| pair[0].ts <= pair[1].ts));
+
+        let json = trace.to_chrome_json();
+        assert!(json.starts_with(r#"{"traceEvents":[{"name":"thread_name""#));
+        assert!(json.contains(r#""args":{"name":"galaxy"}"#));
+        assert!(json.contains(r#""name":"step","ph":"X","pid":0,"tid":1"#));
+    }
+}